@@ -0,0 +1,190 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+bold() { echo -e "\033[1m$*\033[0m"; }
+info() { echo -e "\033[36m[INFO]\033[0m $*"; }
+ok() { echo -e "\033[32m[OK]\033[0m $*"; }
+fail() { echo -e "\033[31m[FAIL]\033[0m $*" >&2; exit 1; }
+print_step() { echo -e "\033[33m▶ $*\033[0m"; }
+
+PROGRAM="$1"
+TEST_DIR="test_bundle_$(date +%s)"
+
+mkdir -p "$TEST_DIR" && cd "$TEST_DIR"
+
+export GIT_AUTHOR_NAME="Levio-Z" GIT_AUTHOR_EMAIL="67247011+Levio-z@users.noreply.github.com"
+export GIT_COMMITTER_NAME="$GIT_AUTHOR_NAME" GIT_COMMITTER_EMAIL="$GIT_AUTHOR_EMAIL"
+# 固定时间，两边的提交哈希才相同
+export GIT_AUTHOR_DATE="2024-01-01T00:00:00Z" GIT_COMMITTER_DATE="2024-01-01T00:00:00Z"
+
+# 仓库 src：one(v1，附注标签) -> two(dev) -> three(main，light 轻量标签)，side 从 one 分出 s1 -> s2。
+# 两边共用的 bundle 都由 git 生成：
+# full 是 --all，head 只有 HEAD，range 是 main~1..main side~1..side（两个前提提交），
+# v3 是 --version=3 的 dev、main 和 HEAD，filter 是手工拼出的 blob:none 的 v3 bundle
+print_step "准备测试用的仓库和 bundle"
+git init -q -b main fixture
+(
+    cd fixture
+    git init -q -b main src
+    cd src
+    echo a > a; mkdir d; echo c > d/c; git add .; git commit -q -m one; git tag -a v1 -m v1
+    git checkout -q -b side
+    echo s > s; git add s; git commit -q -m s1
+    echo s >> s; git commit -q -am s2
+    git checkout -q main
+    echo b >> a; git commit -q -am two; git branch dev
+    echo c >> a; git commit -q -am three; git tag light
+    git bundle create -q ../full.bundle --all
+    git bundle create -q ../head.bundle HEAD
+    git bundle create -q ../range.bundle main~1..main side~1..side
+    git bundle create -q --version=3 ../v3.bundle dev main HEAD
+    {
+        printf '# v3 git bundle\n@object-format=sha1\n@filter=blob:none\n'
+        printf '%s refs/heads/main\n\n' "$(git rev-parse main)"
+        echo main | git pack-objects --revs --filter=blob:none --stdout -q
+    } > ../filter.bundle
+    echo junk > ../junk.bundle
+)
+rm -rf fixture/.git
+
+# 每次比较前重新放好仓库和 bundle
+setup() {
+    rm -rf "$1" && cp -r fixture "$1"
+}
+
+# 两边的绝对路径不同，统一记为 ROOT
+normalize() {
+    sed "s#$PWD/git-repo#ROOT#g; s#$PWD/our-repo#ROOT#g" "$1"
+}
+
+# 仓库的配置、引用、HEAD、reflog（时间相同，顺序不定，排序后比较）、对象、状态和文件。
+# git 解开 bundle 时写 pack，我们写松散对象，所以只比较有哪些对象
+snapshot() {
+    (
+        cd "$1/$2" 2>/dev/null || { echo "没有 $2"; exit 0; }
+        git config --local --list
+        git for-each-ref
+        git rev-parse --symbolic-full-name HEAD || true
+        git rev-parse HEAD || true
+        cat FETCH_HEAD .git/FETCH_HEAD 2>/dev/null || true
+        git reflog --all | sort
+        git cat-file --batch-all-objects --batch-check | sort
+        git fsck 2>&1
+        if [ "$(git rev-parse --is-bare-repository)" = false ]; then
+            git status --porcelain
+            git ls-files -s
+            for file in $(git ls-files); do echo "== $file" && cat "$file"; done
+        fi
+    ) 2>&1 | normalize /dev/stdin
+}
+
+# bundle 中的 pack 有哪些对象。pack 的压缩字节与 git 的不同，只比较对象的哈希和类型
+objects() {
+    sed '1,/^$/d' "$1" > "$1.pack"
+    git index-pack "$1.pack" > /dev/null
+    git verify-pack -v "$1.idx" | awk '$2 ~ /^(commit|tree|blob|tag)$/ { print $1, $2 }' | sort
+    rm -f "$1.pack" "$1.idx" "$1.rev"
+}
+
+# bundle 的头：签名、能力、前提提交和引用
+header() {
+    sed '/^$/q' "$1"
+}
+
+# 用法：compare 名称 目录 脚本，脚本在 src 所在的目录中运行，用 G 代替 git 命令，
+# 最后一条命令的退出码参与比较，之后比较“目录”中的仓库。
+# 只比较标准输出，需要比较错误输出时在脚本中用 2>&1（出错时的 fatal 与 Error 前缀不同，不比较）
+compare() {
+    local name="$1" dir="$2" script="$3"
+    setup git-repo; setup our-repo
+    local git_status=0 our_status=0
+    (cd git-repo && G() { git "$@"; } && eval "$script") > git.out 2>/dev/null || git_status=$?
+    (cd our-repo && G() { "$PROGRAM" "$@"; } && eval "$script") > our.out 2>/dev/null || our_status=$?
+    if ! diff -u <(normalize git.out) <(normalize our.out) \
+        || (( (git_status == 0) != (our_status == 0) )); then
+        fail "✗ $name: $script 输出不一致（git 退出码 $git_status，我们 $our_status），请检查实现"
+    fi
+    if diff -u <(snapshot git-repo "$dir") <(snapshot our-repo "$dir"); then
+        ok "✓ $name: $script 与官方git完全一致"
+    else
+        fail "✗ $name: $script 结果不一致，请检查实现"
+    fi
+}
+
+# ========= create =========
+# 生成的 bundle 用 git 检查：头、引用和 pack 中的对象
+print_step "比较 bundle create"
+for args in "--all" "main" "HEAD" "v1" "main~1..main" "main~1..main side~1..side" "side~1..side main~1..main" \
+    "main...side" "main~2..v1" "main ^side" "--branches --tags" "light dev" "main main dev"; do
+    compare "create $args" src "cd src && G bundle create ../out.bundle $args && header ../out.bundle && objects ../out.bundle && git bundle verify ../out.bundle"
+done
+compare "create --version=3" src "cd src && G bundle create --version=3 ../out.bundle main dev && header ../out.bundle && objects ../out.bundle && git bundle verify ../out.bundle"
+compare "create 到标准输出" src "cd src && G bundle create - main~1..main > ../out.bundle && header ../out.bundle && objects ../out.bundle"
+compare "create 不是引用" src "cd src && G bundle create ../out.bundle main~1"
+compare "create 没有提交" src "cd src && G bundle create ../out.bundle main ^main"
+compare "create 不存在的版本" src "cd src && G bundle create --version=4 ../out.bundle main"
+compare "create 不存在的提交" src "cd src && G bundle create ../out.bundle nothere"
+
+# ========= verify / list-heads =========
+print_step "比较 bundle verify 和 list-heads"
+for bundle in full head range v3 filter; do
+    compare "verify $bundle" src "cd src && G bundle verify ../$bundle.bundle"
+    compare "list-heads $bundle" src "G bundle list-heads $bundle.bundle"
+done
+compare "verify -q" src "cd src && G bundle verify -q ../full.bundle"
+compare "verify 缺少前提提交" dst "git init -q dst && cd dst && G bundle verify ../range.bundle"
+compare "verify 不是 bundle" src "cd src && G bundle verify ../junk.bundle"
+compare "verify 不存在" src "cd src && G bundle verify ../nothere.bundle"
+compare "list-heads 指定引用" src "G bundle list-heads full.bundle refs/tags/v1 HEAD"
+compare "list-heads 没有匹配" src "G bundle list-heads full.bundle main"
+compare "list-heads 不是 bundle" src "G bundle list-heads junk.bundle"
+
+# ========= unbundle =========
+print_step "比较 bundle unbundle"
+compare "unbundle" dst "git init -q dst && cd dst && G bundle unbundle ../full.bundle"
+compare "unbundle 指定引用" dst "git init -q dst && cd dst && G bundle unbundle ../full.bundle refs/heads/dev"
+compare "unbundle 有前提提交" dst "git clone -q src dst && cd dst && git reset -q --hard HEAD~1 && G bundle unbundle ../range.bundle"
+compare "unbundle 缺少前提提交" dst "git init -q dst && cd dst && G bundle unbundle ../range.bundle"
+compare "unbundle v3" dst "git init -q dst && cd dst && G bundle unbundle ../v3.bundle"
+
+# ========= clone =========
+print_step "比较从 bundle 克隆"
+compare "clone" c "G clone full.bundle c 2>&1"
+compare "clone 默认目录" full "G clone full.bundle 2>&1"
+compare "clone 只有 HEAD" c "G clone head.bundle c 2>&1"
+compare "clone --bare" c.git "G clone --bare full.bundle c.git 2>&1"
+compare "clone --bare 只有 HEAD" c.git "G clone --bare head.bundle c.git 2>&1"
+compare "clone -b" c "G clone -b dev full.bundle c 2>&1"
+compare "clone -b 标签" c "G clone -b v1 full.bundle c 2>&1"
+compare "clone v3" c "G clone v3.bundle c 2>&1"
+compare "clone 缺少前提提交" c "G clone range.bundle c"
+
+# ========= fetch =========
+print_step "比较从 bundle 取回"
+compare "fetch refspec" dst "git clone -q src dst && cd dst && G fetch ../full.bundle 'refs/heads/*:refs/remotes/b/*' 2>&1"
+compare "fetch 到 FETCH_HEAD" dst "git clone -q src dst && cd dst && G fetch ../range.bundle side 2>&1"
+compare "fetch 远程" dst "git init -q dst && cd dst && git remote add b ../full.bundle && G fetch b 2>&1"
+compare "fetch 前提提交" dst "git clone -q src dst && cd dst && git remote add b ../range.bundle && G fetch b 2>&1"
+compare "fetch 缺少前提提交" dst "git init -q dst && cd dst && G fetch ../range.bundle main"
+
+# ========= --filter =========
+# git 2.39 的 bundle create 还没有 --filter，只检查 git 能读懂我们写出的 v3 bundle
+print_step "检查 bundle create --filter"
+setup our-repo
+(
+    cd our-repo/src
+    "$PROGRAM" bundle create ../out.bundle --filter=blob:none main
+    [ "$(sed -n '1,3p' ../out.bundle)" = "$(printf '# v3 git bundle\n@object-format=sha1\n@filter=blob:none')" ] \
+        || fail "✗ --filter: 头不对"
+    git bundle verify ../out.bundle 2>/dev/null | grep -q "The bundle uses this filter: blob:none" \
+        || fail "✗ --filter: git 认为 bundle 没有过滤条件"
+    diff -u <(objects ../filter.bundle) <(objects ../out.bundle) || fail "✗ --filter: pack 中的对象不一致"
+    ! "$PROGRAM" bundle create --version=2 ../out2.bundle --filter=blob:none main 2>/dev/null \
+        || fail "✗ --filter: v2 不能有过滤条件"
+)
+ok "✓ --filter: 写出的 v3 bundle 带有过滤条件，省略了所有 blob"
+
+# ========= 清理 =========
+cd ..
+rm -rf "$TEST_DIR"
+bold "\n✅ bundle 测试完成！"
@@ -0,0 +1,123 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+bold() { echo -e "\033[1m$*\033[0m"; }
+info() { echo -e "\033[36m[INFO]\033[0m $*"; }
+ok() { echo -e "\033[32m[OK]\033[0m $*"; }
+fail() { echo -e "\033[31m[FAIL]\033[0m $*" >&2; exit 1; }
+print_step() { echo -e "\033[33m▶ $*\033[0m"; }
+
+PROGRAM="$1"
+TEST_DIR="test_cherry_pick_$(date +%s)"
+
+mkdir -p "$TEST_DIR" && cd "$TEST_DIR"
+
+# 与我们写入的提交者一致，这样 git 的摘要里也不会多出 Author 行
+export GIT_AUTHOR_NAME="Levio-Z" GIT_AUTHOR_EMAIL="67247011+Levio-z@users.noreply.github.com"
+export GIT_COMMITTER_NAME="$GIT_AUTHOR_NAME" GIT_COMMITTER_EMAIL="$GIT_AUTHOR_EMAIL"
+
+# topic 上有与 main 冲突的修改、新文件和另一处修改，m2 上有一个合并提交
+setup() {
+    rm -rf "$1" && mkdir "$1" && cd "$1"
+    # 固定时间，两个仓库的提交哈希才相同
+    export GIT_AUTHOR_DATE="2024-01-01T00:00:00Z" GIT_COMMITTER_DATE="2024-01-01T00:00:00Z"
+    git init -q -b main
+    seq 1 10 > f; seq 1 5 > h; git add -A; git commit -q -m base
+    git checkout -q -b topic
+    sed -i 's/^2$/two/' f; git commit -q -am "change two"
+    echo new > g; git add g; git commit -q -m "add g"
+    sed -i 's/^9$/nine/' f; git commit -q -am "change nine" -m "Signed-off-by: A <a@example.com>"
+    git checkout -q -b m2 topic~3
+    echo z > z; git add z; git commit -q -m z
+    git merge -q --no-ff -m "merge it" topic~1
+    git checkout -q main
+    sed -i 's/^2$/TWO/' f; git commit -q -am "main two"
+    cd ..
+}
+
+# 提交哈希（含我们的提交时间）不同，只比较摘要中的其他部分；revert 的作者日期是当前时间
+normalize() {
+    sed -E 's/^\[([^]]*) [0-9a-f]{7}\]/[\1 HASH]/' "$1" | awk '
+        /^\[.*\] Revert / { revert = 1; print; next }
+        revert && /^ Date: / { print " Date: DATE"; revert = 0; next }
+        { revert = 0; print }'
+}
+
+snapshot() {
+    (
+        cd "$1"
+        git ls-files -s
+        git status --porcelain -uall
+        git log -1 --format=%T
+        git log -4 --format='%s|%b|%an <%ae>'
+        find . -path ./.git -prune -o -type f -print | sort | while read -r f; do
+            echo "== $f"; cat "$f"
+        done
+        for state in MERGE_MSG CHERRY_PICK_HEAD REVERT_HEAD sequencer/todo sequencer/opts; do
+            [ -e ".git/$state" ] && { echo "== $state"; cat ".git/$state"; }
+        done
+        true
+    )
+}
+
+# 用法：compare 名称 起始分支 脚本，脚本中用 G 代替 git 命令，最后一条命令的退出码参与比较
+compare() {
+    local name="$1" branch="$2" script="$3"
+    setup git-repo; setup our-repo
+    git -C git-repo checkout -q "$branch"; git -C our-repo checkout -q "$branch"
+    local git_status=0 our_status=0
+    (cd git-repo && G() { git "$@"; } && eval "$script") > git.out 2>/dev/null || git_status=$?
+    (cd our-repo && G() { "$PROGRAM" "$@"; } && eval "$script") > our.out 2>/dev/null || our_status=$?
+    if ! diff -u <(normalize git.out) <(normalize our.out) \
+        || (( (git_status == 0) != (our_status == 0) )); then
+        fail "✗ $name: $script 输出不一致（git 退出码 $git_status，我们 $our_status），请检查实现"
+    fi
+    if diff -u <(snapshot git-repo) <(snapshot our-repo); then
+        ok "✓ $name: $script 与官方git完全一致"
+    else
+        fail "✗ $name: $script 结果不一致，请检查实现"
+    fi
+}
+
+# ========= cherry-pick =========
+print_step "比较 cherry-pick"
+compare "无冲突" main "G cherry-pick topic~1"
+compare "冲突" main "G cherry-pick topic~2"
+compare "范围" main "G cherry-pick topic~3..topic"
+compare "多个提交和 -x" main "G cherry-pick -x topic~1 topic"
+compare "冲突时的 -x" main "G cherry-pick -x topic~3..topic"
+compare "不提交" main "G cherry-pick -n topic~1 topic"
+compare "结果为空" topic "G cherry-pick topic~1"
+compare "暂存区有修改" main "echo dirty > h && git add h && G cherry-pick topic~1"
+compare "冲突时的状态" main "G cherry-pick topic~3..topic; G status"
+
+print_step "比较合并提交和 -m"
+compare "缺少 -m" main "G cherry-pick m2"
+compare "指定 -m" main "G cherry-pick -m 1 m2"
+compare "非合并提交的 -m 1" main "G cherry-pick -m 1 topic~1"
+compare "不存在的父提交" main "G cherry-pick -m 3 m2"
+
+# ========= revert =========
+print_step "比较 revert"
+compare "撤销" topic "G revert HEAD~1"
+compare "撤销冲突" main "G revert topic~2"
+compare "撤销多个" topic "G revert topic~1 topic"
+compare "撤销合并提交" m2 "G revert -m 1 HEAD"
+compare "撤销冲突时的状态" main "G revert topic~2; G status"
+
+# ========= 继续、跳过和放弃 =========
+print_step "比较 --continue、--skip 和 --abort"
+compare "单个继续" main "G cherry-pick topic~2; echo resolved > f && git add f && G cherry-pick --continue"
+compare "范围继续" main "G cherry-pick -x topic~3..topic; echo resolved > f && git add f && G cherry-pick --continue"
+compare "未解决时继续" main "G cherry-pick topic~3..topic; G cherry-pick --continue"
+compare "范围跳过" main "G cherry-pick topic~3..topic; G cherry-pick --skip"
+compare "范围放弃" main "G cherry-pick topic~3..topic; G cherry-pick --abort"
+compare "单个放弃" main "G cherry-pick topic~2; G cherry-pick --abort"
+compare "进行中再次开始" main "G cherry-pick topic~3..topic; G cherry-pick topic~2..topic"
+compare "没有进行中的操作" main "G cherry-pick --abort"
+compare "撤销后继续" main "G revert topic~2; echo resolved > f && git add f && G revert --continue"
+
+# ========= 清理 =========
+cd ..
+rm -rf "$TEST_DIR"
+bold "\n✅ cherry-pick/revert 测试完成！"
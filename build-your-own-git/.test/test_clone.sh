@@ -0,0 +1,121 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+bold() { echo -e "\033[1m$*\033[0m"; }
+info() { echo -e "\033[36m[INFO]\033[0m $*"; }
+ok() { echo -e "\033[32m[OK]\033[0m $*"; }
+fail() { echo -e "\033[31m[FAIL]\033[0m $*" >&2; exit 1; }
+print_step() { echo -e "\033[33m▶ $*\033[0m"; }
+
+PROGRAM="$1"
+TEST_DIR="test_clone_$(date +%s)"
+
+mkdir -p "$TEST_DIR" && cd "$TEST_DIR"
+
+export GIT_AUTHOR_NAME="Levio-Z" GIT_AUTHOR_EMAIL="67247011+Levio-z@users.noreply.github.com"
+export GIT_COMMITTER_NAME="$GIT_AUTHOR_NAME" GIT_COMMITTER_EMAIL="$GIT_AUTHOR_EMAIL"
+# 固定时间，两边的提交哈希才相同
+export GIT_AUTHOR_DATE="2024-01-01T00:00:00Z" GIT_COMMITTER_DATE="2024-01-01T00:00:00Z"
+
+# 仓库 src：one(v1，附注标签) -> two(dev) -> three(main，light 轻量标签)
+setup() {
+    rm -rf "$1" && mkdir "$1" && cd "$1"
+    git init -q -b main src
+    (
+        cd src
+        echo a > a; mkdir d; echo c > d/c; git add .; git commit -q -m one; git tag -a v1 -m v1
+        echo b >> a; git commit -q -am two; git branch dev
+        echo c >> a; git commit -q -am three; git tag light
+    )
+    cd ..
+}
+
+# 两边的绝对路径不同，统一记为 ROOT
+normalize() {
+    sed "s#$PWD/git-repo#ROOT#g; s#$PWD/our-repo#ROOT#g" "$1"
+}
+
+# 克隆得到的配置、引用、HEAD、浅克隆边界、reflog、状态和文件
+snapshot() {
+    (
+        cd "$1/$2" 2>/dev/null || { echo "没有 $2"; exit 0; }
+        git config --local --list
+        git for-each-ref
+        git rev-parse --symbolic-full-name HEAD || true
+        git rev-parse HEAD || true
+        cat shallow .git/shallow 2>/dev/null || true
+        git log --oneline | cat
+        git reflog --all | cat
+        git fsck 2>&1
+        if [ "$(git rev-parse --is-bare-repository)" = false ]; then
+            git status --porcelain
+            git ls-files -s
+            for file in $(git ls-files); do echo "== $file" && cat "$file"; done
+        fi
+    ) 2>&1 | normalize /dev/stdin
+}
+
+# 用法：compare 名称 目录 脚本，脚本在 src 所在的目录中运行，用 G 代替 git 命令，
+# 最后一条命令的退出码参与比较，之后比较克隆到“目录”中的仓库。
+# 只比较标准输出，需要比较错误输出时在脚本中用 2>&1（出错时的 fatal 与 Error 前缀不同，不比较）
+compare() {
+    local name="$1" dir="$2" script="$3"
+    setup git-repo; setup our-repo
+    local git_status=0 our_status=0
+    (cd git-repo && G() { git "$@"; } && eval "$script") > git.out 2>/dev/null || git_status=$?
+    (cd our-repo && G() { "$PROGRAM" "$@"; } && eval "$script") > our.out 2>/dev/null || our_status=$?
+    if ! diff -u <(normalize git.out) <(normalize our.out) \
+        || (( (git_status == 0) != (our_status == 0) )); then
+        fail "✗ $name: $script 输出不一致（git 退出码 $git_status，我们 $our_status），请检查实现"
+    fi
+    if diff -u <(snapshot git-repo "$dir") <(snapshot our-repo "$dir"); then
+        ok "✓ $name: $script 与官方git完全一致"
+    else
+        fail "✗ $name: $script 结果不一致，请检查实现"
+    fi
+}
+
+# ========= 本地路径 =========
+print_step "比较本地路径的克隆"
+compare "clone" c "G clone src c 2>&1"
+compare "默认目录名" w/src "mkdir w && cd w && G clone ../src 2>&1"
+compare "去掉 .git" w/src "mkdir w && cd w && G clone ../src/.git 2>&1"
+compare "末尾的斜杠" c "G clone src/ c 2>&1"
+compare "克隆到空目录" c "mkdir c && G clone src c 2>&1"
+compare "目标目录不为空" c "mkdir c && touch c/x && G clone src c"
+compare "源不存在" c "G clone nope c"
+compare "源 HEAD 指向其他分支" c "git -C src symbolic-ref HEAD refs/heads/dev && G clone src c 2>&1"
+compare "-n" c "G clone -n src c 2>&1"
+compare "-q" c "G clone -q src c 2>&1"
+compare "--no-hardlinks" c "G clone --no-hardlinks src c 2>&1 && find c/.git/objects -type f -links +1 | wc -l"
+compare "硬链接对象" c "G clone -l src c 2>&1 && find c/.git/objects -type f -links 1 | wc -l"
+compare "--no-local" c "G clone --no-local src c 2>&1"
+
+# ========= --branch =========
+print_step "比较 --branch"
+compare "-b 分支" c "G clone -b dev src c 2>&1"
+compare "-b 附注标签" c "G clone -b v1 src c 2>&1"
+compare "-b 轻量标签" c "G clone --branch light src c 2>&1"
+compare "-b 不存在" c "G clone -b nope src c"
+
+# ========= --bare 和 --mirror =========
+print_step "比较 --bare 和 --mirror"
+compare "--bare" w/src.git "mkdir w && cd w && G clone --bare ../src 2>&1"
+compare "--bare 指定目录" b.git "G clone --bare src b.git 2>&1"
+compare "--bare -b" b.git "G clone --bare -b dev src b.git 2>&1"
+compare "--mirror" w/src.git "git -C src update-ref refs/notes/x HEAD && mkdir w && cd w && G clone --mirror ../src 2>&1"
+
+# ========= --depth =========
+print_step "比较 --depth"
+compare "--depth 1" c "G clone --depth 1 file://\$PWD/src c 2>&1"
+compare "--depth 2" c "G clone --depth 2 file://\$PWD/src c 2>&1"
+compare "--depth 超过历史" c "G clone --depth 9 file://\$PWD/src c 2>&1"
+compare "--depth -b" c "G clone --depth 2 -b dev file://\$PWD/src c 2>&1"
+compare "--depth --bare" c "G clone --depth 1 --bare file://\$PWD/src c 2>&1"
+compare "--depth 后 log" c "G clone -q --depth 2 file://\$PWD/src c && cd c && G log --oneline"
+compare "本地路径忽略 --depth" c "G clone --depth 1 src c 2>&1"
+
+# ========= 清理 =========
+cd ..
+rm -rf "$TEST_DIR"
+bold "\n✅ clone 测试完成！"
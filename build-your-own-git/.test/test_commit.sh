@@ -0,0 +1,205 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+bold() { echo -e "\033[1m$*\033[0m"; }
+info() { echo -e "\033[36m[INFO]\033[0m $*"; }
+ok() { echo -e "\033[32m[OK]\033[0m $*"; }
+fail() { echo -e "\033[31m[FAIL]\033[0m $*" >&2; exit 1; }
+print_step() { echo -e "\033[33m▶ $*\033[0m"; }
+
+PROGRAM="$1"
+TEST_DIR="test_commit_$(date +%s)"
+
+mkdir -p "$TEST_DIR" && cd "$TEST_DIR"
+
+# 与我们写入的提交者一致，这样 git 的摘要里也不会多出 Author 行
+export GIT_AUTHOR_NAME="Levio-Z" GIT_AUTHOR_EMAIL="67247011+Levio-z@users.noreply.github.com"
+export GIT_COMMITTER_NAME="$GIT_AUTHOR_NAME" GIT_COMMITTER_EMAIL="$GIT_AUTHOR_EMAIL"
+# 默认不修改说明，需要编辑时在脚本中另外设置
+export GIT_EDITOR=true
+
+# 编辑器：在第一行前加上带空白的标题，在末尾加上注释和空行
+EDIT_SUBJECT="$PWD/edit_subject.sh"
+cat > "$EDIT_SUBJECT" <<'EOF'
+#!/bin/sh
+sed -i '1s/^/  subject  /' "$1"
+printf '# trailing comment\n\n\n' >> "$1"
+EOF
+chmod +x "$EDIT_SUBJECT"
+
+# main 上有两个提交，side 上的修改可以与 main 干净地合并，conflict 上的修改与 main 冲突，
+# other 上的提交作者不是我们
+setup() {
+    rm -rf "$1" && mkdir "$1" && cd "$1"
+    # 固定时间，两个仓库的提交哈希才相同
+    export GIT_AUTHOR_DATE="2024-01-01T00:00:00Z" GIT_COMMITTER_DATE="2024-01-01T00:00:00Z"
+    git init -q -b main
+    seq 1 5 > a; echo b > b; git add -A; git commit -q -m base
+    git checkout -q -b side
+    echo side > s; git add s; git commit -q -m "add s"
+    git checkout -q -b conflict main
+    sed -i 's/^3$/THREE/' a; git commit -q -am "conflict three"
+    git checkout -q main
+    sed -i 's/^3$/three/' a; git commit -q -am "change three" -m "with a body"
+    git checkout -q -b other
+    echo other > o; git add o; git commit -q --author="Other <other@example.com>" -m "by other"
+    git checkout -q main
+    cd ..
+}
+
+# 新提交的哈希（含我们的提交时间）不同，只比较摘要中的其他部分
+normalize() {
+    sed -E 's/\[([^]]*) [0-9a-f]{7}\]/[\1 HASH]/' "$1"
+}
+
+snapshot() {
+    (
+        cd "$1"
+        git ls-files -s
+        git status --porcelain -uall
+        git symbolic-ref -q HEAD || echo "detached"
+        git log -1 --format='%T|%P' 2>/dev/null
+        # 新提交的作者日期是当前时间，只比较沿用下来的日期
+        git log -3 --format='%s|%b|%an <%ae>|%ad' 2>/dev/null | sed -E '/2024 \+0000$/!s/\|[^|]*$/|NOW/'
+        # 原样比较提交说明，包括末尾的换行
+        git cat-file commit HEAD 2>/dev/null | sed '1,/^$/d' | od -c
+        for state in COMMIT_EDITMSG MERGE_HEAD MERGE_MSG; do
+            [ -e ".git/$state" ] && { echo "== $state"; cat ".git/$state"; }
+        done
+        true
+    )
+}
+
+# 用法：compare 名称 起始分支 脚本，脚本中用 G 代替 git 命令，最后一条命令的退出码参与比较。
+# 只比较标准输出，需要比较错误输出时在脚本中用 2>&1
+compare() {
+    local name="$1" branch="$2" script="$3"
+    setup git-repo; setup our-repo
+    git -C git-repo checkout -q "$branch"; git -C our-repo checkout -q "$branch"
+    local git_status=0 our_status=0
+    # 新提交使用当前时间，与我们一致
+    unset GIT_AUTHOR_DATE GIT_COMMITTER_DATE
+    (cd git-repo && G() { git "$@"; } && eval "$script") > git.out 2>/dev/null || git_status=$?
+    (cd our-repo && G() { "$PROGRAM" "$@"; } && eval "$script") > our.out 2>/dev/null || our_status=$?
+    if ! diff -u <(normalize git.out) <(normalize our.out) \
+        || (( (git_status == 0) != (our_status == 0) )); then
+        fail "✗ $name: $script 输出不一致（git 退出码 $git_status，我们 $our_status），请检查实现"
+    fi
+    if diff -u <(snapshot git-repo) <(snapshot our-repo); then
+        ok "✓ $name: $script 与官方git完全一致"
+    else
+        fail "✗ $name: $script 结果不一致，请检查实现"
+    fi
+}
+
+STAGE='echo more >> a && git add a'
+
+# ========= -m 和 -F =========
+print_step "比较 commit -m 和 -F"
+compare "提交暂存区" main "$STAGE && echo dirty > b && G commit -m 'only index'"
+compare "多个 -m" main "$STAGE && G commit -m '  first  ' -m '' -m 'second'"
+compare "-F" main "$STAGE && printf 'from file\n\n\nbody  \n' > ../msg && G commit -F ../msg"
+compare "-F -" main "$STAGE && printf 'from stdin\n' | G commit -F -"
+compare "根提交" main "git checkout -q --orphan fresh && G commit -m root"
+compare "分离头指针" main "git checkout -q --detach && $STAGE && G commit -m detached"
+compare "以 - 开头的说明" main "$STAGE && G commit -m '-M1 rename' -m '-uno'"
+compare "空的说明" main "$STAGE && G commit -m ''"
+compare "读不到的文件" main "$STAGE && G commit -F ../missing"
+
+# ========= 没有修改 =========
+print_step "比较没有要提交的修改"
+compare "工作区干净" main "G commit -m nothing"
+compare "只有未暂存的修改" main "echo more >> a && echo new > n && G commit -m nothing"
+compare "只有未跟踪的文件" main "echo new > n && G commit -m nothing"
+compare "还没有提交" main "git checkout -q --orphan fresh && git rm -rqf . && G commit -m nothing"
+compare "--allow-empty" main "G commit --allow-empty -m empty"
+
+# ========= -a =========
+print_step "比较 commit -a"
+compare "-a" main "echo more >> a && rm b && echo new > n && G commit -a -m all"
+compare "-a 没有修改" main "echo new > n && G commit -a -m all"
+
+# ========= --amend =========
+print_step "比较 commit --amend"
+compare "--amend -m" main "$STAGE && G commit --amend -m amended"
+compare "--amend --no-edit" main "$STAGE && G commit --amend --no-edit"
+compare "--amend 编辑说明" main "GIT_EDITOR=\"sed -i 1s/.*/edited/\" G commit --amend"
+compare "--amend 保留作者" other "G commit --amend -m reworded"
+compare "--amend 结果为空" main "git show HEAD~1:a > a && git add a && G commit --amend -m empty; G commit --amend -m empty 2>&1 >/dev/null"
+compare "--amend 根提交" main "git checkout -q --orphan fresh && git commit -q -m root && G commit --amend -m again"
+
+# ========= 编辑器和 --cleanup =========
+print_step "比较编辑器和 --cleanup"
+SUBJECT="GIT_EDITOR=$EDIT_SUBJECT"
+compare "编辑器模板" main "$STAGE && echo dirty > b && echo new > n && GIT_EDITOR=cat G commit"
+compare "没有提交时的模板" main "git checkout -q --orphan fresh && GIT_EDITOR=cat G commit"
+compare "--amend 的模板" other "GIT_EDITOR=cat G commit --amend"
+compare "默认整理" main "$STAGE && $SUBJECT G commit"
+compare "strip" main "$STAGE && $SUBJECT G commit --cleanup=strip"
+compare "whitespace" main "$STAGE && $SUBJECT G commit --cleanup=whitespace"
+compare "verbatim" main "$STAGE && $SUBJECT G commit --cleanup=verbatim"
+compare "scissors" main "$STAGE && GIT_EDITOR=\"sed -i '1i subject\\\\n# kept'\" G commit --cleanup=scissors"
+compare "-m 的 verbatim" main "$STAGE && G commit --cleanup=verbatim -m '  spaced  ' -m '# not a comment'"
+compare "-F 的 verbatim" main "$STAGE && printf 'no newline' > ../msg && G commit --cleanup=verbatim -F ../msg"
+compare "没有编辑器时的 scissors" main \
+    "$STAGE && printf 'cut\n# ------------------------ >8 ------------------------\nbelow\n' > ../msg && G commit --cleanup=scissors -F ../msg"
+compare "编辑后为空" main "$STAGE && GIT_EDITOR=\"sed -i '/^[^#]/d'\" G commit"
+compare "core.editor" main "$STAGE && unset GIT_EDITOR && git config core.editor \"sed -i 1s/^/configured/\" && G commit"
+compare "无效的模式" main "$STAGE && G commit --cleanup=nope -m x"
+
+# ========= 合并 =========
+print_step "比较合并后的提交"
+compare "结束合并" main "git merge -q --no-commit side && G commit"
+compare "合并的模板" main "git merge -q --no-commit side && GIT_EDITOR=cat G commit"
+compare "有冲突时提交" main "git merge -q conflict; G commit -m resolved"
+compare "解决冲突后提交" main "git merge -q conflict; echo resolved > a && git add a && G commit --no-edit"
+compare "合并中修改提交" main "git merge -q conflict; G commit --amend -m amended"
+
+# ========= 没有暂存区 =========
+# 我们没有 add，还没有 .git/index 时提交整个工作区，git 没有对应的行为，只检查结果
+print_step "检查没有暂存区时提交工作区"
+rm -rf no-index && mkdir no-index && cd no-index
+"$PROGRAM" init > /dev/null
+echo x > f; mkdir d; echo y > d/g
+"$PROGRAM" commit -m first > /dev/null || fail "✗ 没有暂存区时提交失败"
+[ "$(git ls-tree -r --name-only HEAD | tr '\n' ' ')" = "d/g f " ] || fail "✗ 第一个提交没有包含工作区的文件"
+echo z >> f; echo new > n
+"$PROGRAM" commit -a -m second > /dev/null || fail "✗ 没有暂存区时 commit -a 失败"
+[ "$(git diff-tree --no-commit-id --name-only -r HEAD | tr '\n' ' ')" = "f n " ] || fail "✗ 第二个提交的修改不对"
+[ ! -e .git/index ] || fail "✗ commit -a 不应写出 .git/index"
+ok "✓ 没有暂存区时提交了整个工作区"
+cd ..
+
+# ========= 损坏的暂存区 =========
+# 最后一条记录在路径之前就截断了，要报错而不是越界
+print_step "检查损坏的暂存区"
+rm -rf corrupt && mkdir corrupt && cd corrupt
+"$PROGRAM" init > /dev/null
+python3 - <<'PY'
+import hashlib, struct
+body = b"DIRC" + struct.pack(">II", 2, 1) + bytes(60) + struct.pack(">H", 0x4000)
+open(".git/index", "wb").write(body + hashlib.sha1(body).digest())
+PY
+if "$PROGRAM" commit -m x > out 2>&1 || ! grep -q "index file corrupt" out || grep -q panicked out; then
+    cat out; fail "✗ 损坏的暂存区没有报错"
+fi
+ok "✓ 损坏的暂存区报告 index file corrupt"
+cd ..
+
+# 已有 index.lock 说明别的进程在写暂存区，不能覆盖也不能删掉它
+print_step "检查已存在的 index.lock"
+rm -rf locked && mkdir locked && cd locked
+git init -q && echo x > f && git add f && git commit -q -m base
+echo y > f && echo busy > .git/index.lock
+if "$PROGRAM" commit -a -m x > out 2>&1 \
+    || ! grep -q "Unable to create '.git/index.lock': File exists" out \
+    || [ "$(cat .git/index.lock)" != busy ]; then
+    cat out; fail "✗ index.lock 已存在时没有报错或动了锁文件"
+fi
+ok "✓ index.lock 已存在时与 git 一样报错"
+cd ..
+
+# ========= 清理 =========
+cd ..
+rm -rf "$TEST_DIR"
+bold "\n✅ commit 测试完成！"
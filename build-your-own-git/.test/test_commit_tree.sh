@@ -0,0 +1,102 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+bold() { echo -e "\033[1m$*\033[0m"; }
+info() { echo -e "\033[36m[INFO]\033[0m $*"; }
+ok() { echo -e "\033[32m[OK]\033[0m $*"; }
+fail() { echo -e "\033[31m[FAIL]\033[0m $*" >&2; exit 1; }
+print_step() { echo -e "\033[33m▶ $*\033[0m"; }
+
+PROGRAM="$1"
+TEST_DIR="test_commit_tree_$(date +%s)"
+
+mkdir -p "$TEST_DIR" && cd "$TEST_DIR"
+
+# 与我们写入的提交者一致，签名时两边使用同一个密钥
+export GIT_AUTHOR_NAME="Levio-Z" GIT_AUTHOR_EMAIL="67247011+Levio-z@users.noreply.github.com"
+export GIT_COMMITTER_NAME="$GIT_AUTHOR_NAME" GIT_COMMITTER_EMAIL="$GIT_AUTHOR_EMAIL"
+
+# main 和 side 从 base 分叉，两边都有一个提交
+setup() {
+    rm -rf "$1" && mkdir "$1" && cd "$1"
+    # 固定时间，两个仓库的提交哈希才相同
+    export GIT_AUTHOR_DATE="2024-01-01T00:00:00Z" GIT_COMMITTER_DATE="2024-01-01T00:00:00Z"
+    git init -q -b main
+    echo a > a; git add a; git commit -q -m base
+    git checkout -q -b side
+    echo s > s; git add s; git commit -q -m side
+    git checkout -q main
+    echo b > b; git add b; git commit -q -m main
+    cd ..
+}
+
+# 新提交的哈希含提交时间，比较对象内容，其中的时间和时区统一替换掉
+show() {
+    local id
+    read -r id
+    git cat-file -p "$id" | sed -E 's/ [0-9]+ [+-][0-9]{4}$/ DATE/'
+}
+
+# 用法：compare 名称 脚本，脚本中用 G 代替 git 命令，最后一条命令的退出码参与比较，只比较标准输出
+compare() {
+    local name="$1" script="$2"
+    setup git-repo; setup our-repo
+    local git_status=0 our_status=0
+    (cd git-repo && G() { git "$@"; } && eval "$script") > git.out 2>/dev/null || git_status=$?
+    (cd our-repo && G() { "$PROGRAM" "$@"; } && eval "$script") > our.out 2>/dev/null || our_status=$?
+    if diff -u git.out our.out && (( (git_status == 0) == (our_status == 0) )); then
+        ok "✓ $name: $script 与官方git完全一致"
+    else
+        fail "✗ $name: $script 输出不一致（git 退出码 $git_status，我们 $our_status），请检查实现"
+    fi
+}
+
+# ========= 父提交 =========
+print_step "比较 commit-tree 的父提交"
+compare "根提交" "G commit-tree main^{tree} -m root | show"
+compare "一个父提交" "G commit-tree main^{tree} -p main -m one | show"
+compare "合并提交" "G commit-tree main^{tree} -p main -p side -m merge | show"
+compare "重复的父提交" "G commit-tree main^{tree} -p main -p side -p main -m dup | show"
+compare "重复的父提交提示" "G commit-tree main^{tree} -p main -p main -m dup 2>&1 >/dev/null"
+
+# ========= 提交说明 =========
+print_step "比较提交说明"
+compare "多个 -m" "G commit-tree main^{tree} -m '  first  ' -m '' -m second | show"
+compare "以 - 开头的说明" "G commit-tree main^{tree} -m '-M1 rename' -m '-Skey' | show"
+compare "-F" "printf 'no newline' > ../msg && G commit-tree main^{tree} -F ../msg | show"
+compare "多个 -F 和 -m" "printf 'one' > ../m1 && printf 'two\n' > ../m2 && G commit-tree main^{tree} -m para -F ../m1 -F ../m2 | show"
+compare "-F -" "printf 'from stdin\n\n' | G commit-tree main^{tree} -F - | show"
+compare "从标准输入读取" "printf '  raw  \n# kept\n' | G commit-tree main^{tree} | show"
+compare "读不到的文件" "G commit-tree main^{tree} -F ../missing"
+
+# ========= 校验 =========
+print_step "比较对象校验"
+compare "不是树" "G commit-tree main -m x"
+compare "blob 不是树" "G commit-tree main:a -m x"
+compare "无效的树" "G commit-tree nope -m x"
+compare "不存在的树" "G commit-tree 0123456789012345678901234567890123456789 -m x"
+compare "父提交不是提交" "G commit-tree main^{tree} -p main^{tree} -m x"
+compare "无效的父提交" "G commit-tree main^{tree} -p nope -m x"
+compare "不存在的父提交" "G commit-tree main^{tree} -p 0123456789012345678901234567890123456789 -m x"
+
+# ========= 签名 =========
+if command -v gpg > /dev/null; then
+    print_step "比较 -S 签名"
+    # 临时的密钥环中生成没有密码的签名密钥
+    export GNUPGHOME="$PWD/gnupg"
+    mkdir -m 700 "$GNUPGHOME"
+    gpg --batch --quiet --passphrase '' --quick-gen-key \
+        "$GIT_COMMITTER_NAME <$GIT_COMMITTER_EMAIL>" ed25519 sign never 2>/dev/null
+    VERIFY='{ read -r id; git verify-commit "$id" 2>/dev/null && echo verified; git cat-file -p "$id" | grep -c "^gpgsig -----BEGIN PGP SIGNATURE-----$"; git cat-file -p "$id" | sed "/^gpgsig/,/^ -----END/d" | sed -E "s/ [0-9]+ [+-][0-9]{4}\$/ DATE/"; }'
+    compare "-S" "G commit-tree main^{tree} -p main -S -m signed | $VERIFY"
+    compare "--gpg-sign=<key>" "G commit-tree main^{tree} --gpg-sign=\"\$GIT_COMMITTER_EMAIL\" -m signed | $VERIFY"
+    compare "--no-gpg-sign" "G commit-tree main^{tree} -S --no-gpg-sign -m unsigned | show"
+    compare "没有密钥" "G commit-tree main^{tree} -Snobody@example.com -m x"
+else
+    info "没有 gpg，跳过签名测试"
+fi
+
+# ========= 清理 =========
+cd ..
+rm -rf "$TEST_DIR"
+bold "\n✅ commit-tree 测试完成！"
@@ -0,0 +1,99 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+bold() { echo -e "\033[1m$*\033[0m"; }
+info() { echo -e "\033[36m[INFO]\033[0m $*"; }
+ok() { echo -e "\033[32m[OK]\033[0m $*"; }
+fail() { echo -e "\033[31m[FAIL]\033[0m $*" >&2; exit 1; }
+print_step() { echo -e "\033[33m▶ $*\033[0m"; }
+
+PROGRAM="$1"
+TEST_DIR="test_diff_$(date +%s)"
+OUR_OUTPUT=$(mktemp)
+GIT_OUTPUT=$(mktemp)
+
+mkdir -p "$TEST_DIR" && cd "$TEST_DIR"
+
+# 提交者不依赖本机 git 的用户配置
+export GIT_AUTHOR_NAME="Levio-Z" GIT_AUTHOR_EMAIL="67247011+Levio-z@users.noreply.github.com"
+export GIT_COMMITTER_NAME="$GIT_AUTHOR_NAME" GIT_COMMITTER_EMAIL="$GIT_AUTHOR_EMAIL"
+
+compare() {
+    git diff "$@" > "$GIT_OUTPUT"
+    "$PROGRAM" diff "$@" > "$OUR_OUTPUT"
+    if diff -u "$GIT_OUTPUT" "$OUR_OUTPUT"; then
+        ok "✓ diff $* 与官方git完全一致"
+    else
+        fail "✗ diff $* 输出不一致，请检查实现"
+    fi
+}
+
+# ========= 用官方git构造提交、暂存区和工作区 =========
+print_step "使用官方git构造包含多种修改的提交、暂存区和工作区"
+git init -q -b main
+mkdir sub
+cat > code.c <<'SRC'
+#include <stdio.h>
+
+int add(int a, int b)
+{
+    return a + b;
+}
+
+int sub(int a, int b)
+{
+    return a - b;
+}
+
+int main(void)
+{
+    printf("%d\n", add(1, 2));
+    printf("%d\n", sub(3, 4));
+    return 0;
+}
+SRC
+printf 'a\nb\nc\n' > f
+printf 'x\n' > sub/g
+printf 'bin\0ary' > bin
+echo keep > "sp ace"
+echo t > t
+echo m > m
+git add -A && git commit -q -m one
+sed -i 's/a - b/b - a/; s/return 0;/return 1;/' code.c
+printf 'a\nB\nc' > f
+git add -A && git commit -q -m two
+
+echo more >> f                        # 工作区修改，并且原来没有结尾换行
+sed -i 's/int a, int b/int x,  int y/' code.c
+rm sub/g                              # 删除
+printf 'bin\0ary2' > bin              # 二进制文件
+echo changed > "sp ace"               # 路径含空格
+rm t && ln -s f t                     # 普通文件变成符号链接
+chmod +x m                            # 只有模式变化
+echo new > new && git add new         # 新增到暂存区
+
+# ========= 比较结果 =========
+print_step "比较工作区、暂存区和提交之间的差异"
+compare
+compare --cached
+compare HEAD
+compare HEAD~1 HEAD
+compare HEAD~1..HEAD
+compare HEAD -- sub f
+
+print_step "比较上下文、函数上下文和空白选项"
+compare -U1 HEAD~1 HEAD
+compare -U0 HEAD~1 HEAD
+compare -W HEAD~1 HEAD
+compare -b code.c
+compare -w code.c
+
+print_step "比较不同的差异算法"
+for ALGORITHM in --minimal --patience --histogram; do
+    compare "$ALGORITHM" HEAD
+done
+
+# ========= 清理 =========
+cd ..
+rm -rf "$TEST_DIR" "$OUR_OUTPUT" "$GIT_OUTPUT"
+bold "\n✅ diff 测试完成！"
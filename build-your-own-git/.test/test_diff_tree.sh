@@ -0,0 +1,53 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+bold() { echo -e "\033[1m$*\033[0m"; }
+info() { echo -e "\033[36m[INFO]\033[0m $*"; }
+ok() { echo -e "\033[32m[OK]\033[0m $*"; }
+fail() { echo -e "\033[31m[FAIL]\033[0m $*" >&2; exit 1; }
+print_step() { echo -e "\033[33m▶ $*\033[0m"; }
+
+PROGRAM="$1"
+TEST_DIR="test_diff_tree_$(date +%s)"
+OUR_OUTPUT=$(mktemp)
+GIT_OUTPUT=$(mktemp)
+
+mkdir -p "$TEST_DIR" && cd "$TEST_DIR"
+
+# 提交者不依赖本机 git 的用户配置
+export GIT_AUTHOR_NAME="Levio-Z" GIT_AUTHOR_EMAIL="67247011+Levio-z@users.noreply.github.com"
+export GIT_COMMITTER_NAME="$GIT_AUTHOR_NAME" GIT_COMMITTER_EMAIL="$GIT_AUTHOR_EMAIL"
+
+# ========= 用官方git构造两次提交 =========
+print_step "使用官方git构造包含增删改、类型变化和目录替换的两次提交"
+git init -q -b main
+mkdir -p a/b d x
+echo 1 > a/b/w; echo c > a/c; echo d > d/e; echo f > f; echo g > g; echo t > t; echo y > x/y
+git add -A && git commit -q -m one
+echo 2 > a/b/w                      # 子目录中的修改
+rm -rf d                            # 删除目录
+chmod +x g                          # 模式变化
+rm t && ln -s f t                   # 文件变成符号链接
+rm -rf x && echo x > x              # 目录变成文件
+rm f && mkdir f && echo q > f/q     # 文件变成目录
+echo new > new                      # 新增文件
+git add -A && git commit -q -m two
+
+# ========= 比较结果 =========
+for OPTS in "HEAD" "-r HEAD" "--name-only -r HEAD" "--name-status HEAD" \
+    "--raw -r HEAD~1 HEAD" "-r HEAD^{tree} HEAD~1^{tree}" "HEAD~1" "--root -r HEAD~1"; do
+    print_step "比较 diff-tree $OPTS 的输出"
+    git diff-tree $OPTS > "$GIT_OUTPUT"
+    "$PROGRAM" diff-tree $OPTS > "$OUR_OUTPUT"
+    cat "$OUR_OUTPUT"
+    if diff -u "$GIT_OUTPUT" "$OUR_OUTPUT"; then
+        ok "✓ diff-tree $OPTS 与官方git完全一致"
+    else
+        fail "✗ diff-tree $OPTS 输出不一致，请检查实现"
+    fi
+done
+
+# ========= 清理 =========
+cd ..
+rm -rf "$TEST_DIR" "$OUR_OUTPUT" "$GIT_OUTPUT"
+bold "\n✅ diff-tree 测试完成！"
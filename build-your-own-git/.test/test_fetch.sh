@@ -0,0 +1,137 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+bold() { echo -e "\033[1m$*\033[0m"; }
+info() { echo -e "\033[36m[INFO]\033[0m $*"; }
+ok() { echo -e "\033[32m[OK]\033[0m $*"; }
+fail() { echo -e "\033[31m[FAIL]\033[0m $*" >&2; exit 1; }
+print_step() { echo -e "\033[33m▶ $*\033[0m"; }
+
+PROGRAM="$1"
+TEST_DIR="test_fetch_$(date +%s)"
+
+mkdir -p "$TEST_DIR" && cd "$TEST_DIR"
+
+export GIT_AUTHOR_NAME="Levio-Z" GIT_AUTHOR_EMAIL="67247011+Levio-z@users.noreply.github.com"
+export GIT_COMMITTER_NAME="$GIT_AUTHOR_NAME" GIT_COMMITTER_EMAIL="$GIT_AUTHOR_EMAIL"
+# 固定时间，两边的提交哈希才相同
+export GIT_AUTHOR_DATE="2024-01-01T00:00:00Z" GIT_COMMITTER_DATE="2024-01-01T00:00:00Z"
+
+# 仓库 src：one(v1，附注标签) -> two(dev) -> three(main，light 轻量标签)，
+# 另有从 one 分出的 feature-long-name。
+# 仓库 dst 有自己的提交，已把 src 添加为 origin 并取回过一次，之后 src 又有了变化：
+# main 快进到 four，dev 被改写，feature-long-name 被删除，新增了 topic 和附注标签 v2
+setup() {
+    rm -rf "$1" && mkdir "$1" && cd "$1"
+    git init -q -b main src
+    (
+        cd src
+        echo a > a; git add a; git commit -q -m one; git tag -a v1 -m v1
+        git branch feature-long-name
+        echo b >> a; git commit -q -am two; git branch dev
+        echo c >> a; git commit -q -am three; git tag light
+    )
+    git init -q -b main dst
+    (
+        cd dst
+        echo x > x; git add x; git commit -q -m local
+        git remote add origin ../src
+        git fetch -q origin
+    )
+    (
+        cd src
+        echo d >> a; git commit -q -am four
+        git checkout -q dev; echo e >> a; git commit -q --amend -am rewritten; git checkout -q main
+        git branch -q -D feature-long-name
+        git branch topic HEAD~1; git tag -a v2 -m v2 HEAD~1
+    )
+    cd ..
+}
+
+# 两边的绝对路径不同，统一记为 ROOT
+normalize() {
+    sed "s#$PWD/git-repo#ROOT#g; s#$PWD/our-repo#ROOT#g" "$1"
+}
+
+# dst 的配置、引用、FETCH_HEAD、远程跟踪分支的 reflog，以及取回的对象是否完整
+snapshot() {
+    (
+        cd "$1/dst"
+        git config --local --list
+        git for-each-ref
+        cat .git/FETCH_HEAD 2>/dev/null || true
+        for ref in $(git for-each-ref --format='%(refname)' refs/remotes); do
+            [ -f ".git/logs/$ref" ] && echo "== $ref" && cut -f2 ".git/logs/$ref"
+        done
+        find .git/refs .git/logs -type d -empty | sort
+        git fsck --connectivity-only 2>&1
+    ) | normalize /dev/stdin
+}
+
+# 用法：compare 名称 脚本，脚本在 dst 中运行，用 G 代替 git 命令，最后一条命令的退出码参与比较。
+# 只比较标准输出，需要比较错误输出时在脚本中用 2>&1（出错时的 fatal 与 Error 前缀不同，不比较）
+compare() {
+    local name="$1" script="$2"
+    setup git-repo; setup our-repo
+    local git_status=0 our_status=0
+    (cd git-repo/dst && G() { git "$@"; } && eval "$script") > git.out 2>/dev/null || git_status=$?
+    (cd our-repo/dst && G() { "$PROGRAM" "$@"; } && eval "$script") > our.out 2>/dev/null || our_status=$?
+    if ! diff -u <(normalize git.out) <(normalize our.out) \
+        || (( (git_status == 0) != (our_status == 0) )); then
+        fail "✗ $name: $script 输出不一致（git 退出码 $git_status，我们 $our_status），请检查实现"
+    fi
+    if diff -u <(snapshot git-repo) <(snapshot our-repo); then
+        ok "✓ $name: $script 与官方git完全一致"
+    else
+        fail "✗ $name: $script 结果不一致，请检查实现"
+    fi
+}
+
+# ========= remote =========
+print_step "比较 remote"
+compare "列出" "G remote"
+compare "-v" "git remote add up ../src && G remote -v"
+compare "add" "G remote add up ../src && G remote -v"
+compare "add 已存在" "G remote add origin ../src"
+compare "add -f" "G remote add -f up ../src 2>&1"
+compare "remove" "git config branch.main.remote origin && git config branch.main.merge refs/heads/main && G remote remove origin"
+compare "rm 不存在" "G remote rm nope"
+compare "rename" "git config branch.main.remote origin && G remote rename origin up"
+compare "rename 保留其他 refspec" "git config --add remote.origin.fetch +refs/tags/*:refs/tags/* && G remote rename origin up 2>&1"
+compare "rename 目标已存在" "git remote add up ../src && G remote rename origin up"
+compare "rename 符号引用" "git symbolic-ref refs/remotes/origin/HEAD refs/remotes/origin/main && G remote rename origin up && git symbolic-ref refs/remotes/up/HEAD"
+compare "set-url" "G remote set-url origin ../other && G remote -v"
+compare "set-url 不存在" "G remote set-url nope ../src"
+
+# ========= fetch =========
+print_step "比较 fetch"
+compare "fetch" "G fetch 2>&1"
+compare "fetch 远程名" "G fetch origin 2>&1"
+compare "已是最新" "git fetch -q && G fetch 2>&1"
+compare "--prune" "G fetch --prune 2>&1"
+compare "-p 只删除" "git fetch -q && git -C ../src branch -q -D topic && G fetch -p 2>&1"
+compare "--tags" "G fetch --tags 2>&1"
+compare "--tags 拒绝覆盖标签" "git -C ../src tag -f light HEAD~2 > /dev/null && G fetch --tags 2>&1"
+compare "--tags --force" "git -C ../src tag -f light HEAD~2 > /dev/null && G fetch --tags --force 2>&1"
+compare "-q" "G fetch -q 2>&1"
+compare "合并配置" "git config branch.main.remote origin && git config branch.main.merge refs/heads/main && G fetch 2>&1"
+compare "当前分支的远程" "git remote add up ../src && git config branch.main.remote up && G fetch 2>&1"
+compare "命令行分支" "G fetch origin topic 2>&1"
+compare "命令行 dst" "G fetch origin topic:refs/heads/t dev:d 2>&1"
+compare "命令行顺带更新" "G fetch origin main 2>&1"
+compare "命令行标签" "G fetch origin v2 2>&1"
+compare "命令行通配" "G fetch origin 'refs/heads/*:refs/remotes/mirror/*' 2>&1"
+compare "负向 refspec" "G fetch origin 'refs/heads/*:refs/remotes/mirror/*' '^refs/heads/dev' 2>&1"
+compare "非快进被拒绝" "G fetch origin refs/heads/dev:refs/remotes/origin/dev 2>&1"
+compare "强制更新" "G fetch origin +refs/heads/dev:refs/remotes/origin/dev 2>&1"
+compare "不能更新当前分支" "G fetch origin main:main"
+compare "远程 ref 不存在" "G fetch origin nope"
+compare "路径" "G fetch ../src 2>&1"
+compare "路径和分支" "G fetch ../src dev 2>&1"
+compare "不是仓库" "G fetch nope"
+compare "reflog 动作" "G fetch --prune origin > /dev/null 2>&1 && git reflog show refs/remotes/origin/main | cut -d' ' -f2-"
+
+# ========= 清理 =========
+cd ..
+rm -rf "$TEST_DIR"
+bold "\n✅ fetch 测试完成！"
@@ -0,0 +1,186 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+bold() { echo -e "\033[1m$*\033[0m"; }
+info() { echo -e "\033[36m[INFO]\033[0m $*"; }
+ok() { echo -e "\033[32m[OK]\033[0m $*"; }
+fail() { echo -e "\033[31m[FAIL]\033[0m $*" >&2; exit 1; }
+print_step() { echo -e "\033[33m▶ $*\033[0m"; }
+
+PROGRAM="$1"
+TEST_DIR="test_hooks_$(date +%s)"
+
+mkdir -p "$TEST_DIR" && cd "$TEST_DIR"
+
+export GIT_AUTHOR_NAME="Levio-Z" GIT_AUTHOR_EMAIL="67247011+Levio-z@users.noreply.github.com"
+export GIT_COMMITTER_NAME="$GIT_AUTHOR_NAME" GIT_COMMITTER_EMAIL="$GIT_AUTHOR_EMAIL"
+export GIT_EDITOR=true
+# FAIL 为钩子名时这个钩子以 1 退出
+export FAIL=
+
+HOOKS="pre-commit prepare-commit-msg commit-msg post-commit pre-merge-commit post-merge
+       pre-rebase post-checkout reference-transaction"
+
+# 每个钩子把参数、关心的环境变量和标准输入（只有 reference-transaction 有）记到 .git/hooks.log，
+# 并在标准输出上说一句话，用来检查钩子的输出没有混进命令的标准输出
+install_hooks() {
+    mkdir -p "$1"
+    for hook in $HOOKS; do
+        cat > "$1/$hook" <<EOF
+#!/bin/sh
+{
+    echo "$hook \$*"
+    echo "  GIT_INDEX_FILE=\${GIT_INDEX_FILE-unset} GIT_EDITOR=\${GIT_EDITOR-unset}"
+    [ "$hook" = reference-transaction ] && sed 's/^/  /'
+} >> "\$(git rev-parse --git-dir)/hooks.log"
+echo "$hook says hi"
+[ "\$FAIL" != "$hook" ]
+EOF
+        chmod +x "$1/$hook"
+    done
+}
+
+# main 和 side 从 base 分叉，conflict 与 main 修改了 a 的同一行
+setup() {
+    rm -rf "$1" && mkdir "$1" && cd "$1"
+    # 固定时间，两个仓库的提交哈希才相同
+    export GIT_AUTHOR_DATE="2024-01-01T00:00:00Z" GIT_COMMITTER_DATE="2024-01-01T00:00:00Z"
+    git init -q -b main
+    seq 1 5 > a; git add a; git commit -q -m base
+    git checkout -q -b side
+    echo side > s; git add s; git commit -q -m side
+    git checkout -q -b conflict main
+    sed -i 's/^3$/THREE/' a; git commit -q -am conflict
+    git checkout -q main
+    sed -i 's/^3$/three/' a; git commit -q -am main
+    install_hooks .git/hooks
+    git rev-list --all > .git/known
+    cd ..
+}
+
+# 新提交的哈希含提交时间，setup 之外的哈希统一记为 NEW
+normalize() {
+    awk -v known="$2" '
+        BEGIN {
+            while ((getline line < known) > 0) seen[line] = 1
+            # mawk 不支持 {40}，拼出 40 个字符类
+            for (i = 0; i < 40; i++) hex = hex "[0-9a-f]"
+        }
+        {
+            out = ""
+            while (match($0, hex)) {
+                hash = substr($0, RSTART, RLENGTH)
+                out = out substr($0, 1, RSTART - 1) (hash in seen ? hash : "NEW")
+                $0 = substr($0, RSTART + RLENGTH)
+            }
+            print out $0
+        }' "$1"
+}
+
+snapshot() {
+    (
+        cd "$1"
+        git status --porcelain
+        git symbolic-ref -q HEAD || echo "detached"
+        git log -3 --format='%s|%b' 2>/dev/null
+        git ls-files -s
+        for state in MERGE_HEAD MERGE_MSG index.lock; do
+            [ -e ".git/$state" ] && { echo "== $state"; cat ".git/$state"; }
+        done
+        echo "== hooks.log"
+        # -a 时钩子拿到的是 index.lock 的绝对路径
+        [ -e .git/hooks.log ] && normalize .git/hooks.log .git/known | sed "s|$PWD/||"
+        true
+    )
+}
+
+# 用法：compare 名称 起始分支 脚本，脚本中用 G 代替 git 命令，最后一条命令的退出码参与比较。
+# 只比较标准输出，需要比较错误输出时在脚本中用 2>&1
+compare() {
+    local name="$1" branch="$2" script="$3"
+    setup git-repo; setup our-repo
+    git -C git-repo checkout -q "$branch" >/dev/null 2>&1; git -C our-repo checkout -q "$branch" >/dev/null 2>&1
+    rm -f git-repo/.git/hooks.log our-repo/.git/hooks.log
+    local git_status=0 our_status=0
+    unset GIT_AUTHOR_DATE GIT_COMMITTER_DATE
+    (cd git-repo && G() { git "$@"; } && eval "$script") > git.out 2>/dev/null || git_status=$?
+    (cd our-repo && G() { "$PROGRAM" "$@"; } && eval "$script") > our.out 2>/dev/null || our_status=$?
+    if ! diff -u <(sed -E 's/ [0-9a-f]{7}\]/ HASH]/' git.out) <(sed -E 's/ [0-9a-f]{7}\]/ HASH]/' our.out) \
+        || (( (git_status == 0) != (our_status == 0) )); then
+        fail "✗ $name: $script 输出不一致（git 退出码 $git_status，我们 $our_status），请检查实现"
+    fi
+    if diff -u <(snapshot git-repo) <(snapshot our-repo); then
+        ok "✓ $name: $script 与官方git完全一致"
+    else
+        fail "✗ $name: $script 结果不一致，请检查实现"
+    fi
+}
+
+STAGE='echo more >> a && git add a'
+
+# ========= commit =========
+print_step "比较 commit 的钩子"
+compare "提交" main "$STAGE && G commit -m msg"
+compare "-a" main "echo more >> a && G commit -a -m all"
+compare "--amend" main "G commit --amend -m amended"
+compare "--amend --no-edit" main "G commit --amend --no-edit"
+compare "使用编辑器" main "$STAGE && GIT_EDITOR=\"sed -i 1s/^/edited/\" G commit"
+compare "结束合并" main "git merge -q conflict; echo resolved > a && git add a && G commit --no-edit"
+compare "pre-commit 拒绝" main "$STAGE && FAIL=pre-commit G commit -m msg"
+compare "pre-commit 拒绝 -a" main "echo more >> a && FAIL=pre-commit G commit -a -m msg"
+compare "--no-verify" main "$STAGE && FAIL=pre-commit G commit -n -m msg"
+compare "prepare-commit-msg 拒绝" main "$STAGE && FAIL=prepare-commit-msg G commit --no-verify -m msg"
+compare "commit-msg 拒绝" main "$STAGE && FAIL=commit-msg G commit -m msg"
+compare "commit-msg 的 --no-verify" main "$STAGE && FAIL=commit-msg G commit --no-verify -m msg"
+compare "post-commit 失败" main "$STAGE && FAIL=post-commit G commit -m msg"
+compare "没有要提交的修改" main "G commit -m msg"
+compare "钩子修改说明" main \
+    "printf '#!/bin/sh\necho Trailer: yes >> \"\$1\"\n' > .git/hooks/commit-msg && $STAGE && G commit -m msg"
+compare "钩子修改暂存区" main \
+    "printf '#!/bin/sh\necho new > n && git add n\n' > .git/hooks/pre-commit && $STAGE && G commit -m msg"
+compare "不可执行的钩子" main "chmod -x .git/hooks/pre-commit && $STAGE && G commit -m msg 2>&1"
+compare "关闭提示" main \
+    "chmod -x .git/hooks/pre-commit && git config advice.ignoredHook false && $STAGE && G commit -m msg 2>&1"
+compare "core.hooksPath" main \
+    "mkdir custom && echo custom > custom/pre-commit && git config core.hooksPath custom && $STAGE && G commit -m msg 2>&1"
+compare "core.hooksPath 中的钩子" main \
+    "install_hooks hooks2 && rm -f .git/hooks/* && git config core.hooksPath hooks2 && $STAGE && G commit -m msg"
+
+# ========= reference-transaction =========
+print_step "比较 reference-transaction"
+compare "分离头指针" main "git checkout -q --detach && $STAGE && G commit -m detached"
+compare "拒绝引用更新" main "$STAGE && FAIL=reference-transaction G commit -m msg"
+
+# ========= merge =========
+print_step "比较 merge 的钩子"
+compare "合并提交" main "G merge side"
+compare "快进" side "git reset -q --hard main~1 && rm .git/ORIG_HEAD && G merge main"
+compare "已经是最新" main "G merge main~1"
+compare "有冲突" main "G merge conflict"
+compare "--no-commit" main "G merge --no-commit side"
+compare "pre-merge-commit 拒绝" main "FAIL=pre-merge-commit G merge side 2>&1"
+compare "merge --no-verify" main "FAIL=pre-merge-commit G merge --no-verify side"
+compare "commit-msg 拒绝合并" main "FAIL=commit-msg G merge side"
+compare "prepare-commit-msg 修改说明" main \
+    "printf '#!/bin/sh\necho \"\$2\" >> \"\$1\"\n' > .git/hooks/prepare-commit-msg && G merge -m custom side"
+
+# ========= cherry-pick 和 rebase =========
+# 重放提交时 git 还会更新 CHERRY_PICK_HEAD 等引用，这里只比较其他钩子
+print_step "比较 cherry-pick 和 rebase 的钩子"
+NO_RT="rm .git/hooks/reference-transaction"
+compare "cherry-pick" main "$NO_RT && G cherry-pick side"
+compare "prepare-commit-msg 拒绝 cherry-pick" main "$NO_RT && FAIL=prepare-commit-msg G cherry-pick side"
+compare "变基" side "$NO_RT && G rebase main"
+compare "变基指定分支" main "$NO_RT && G rebase main side"
+compare "--onto" side "$NO_RT && G rebase --onto main main~1"
+compare "已经是最新的分支" main "$NO_RT && G rebase main~1 main"
+compare "切换到最新的分支" side "$NO_RT && git reset -q --hard main && git checkout -q main~1 && G rebase main side"
+compare "pre-rebase 拒绝" side "$NO_RT && FAIL=pre-rebase G rebase main"
+compare "pre-rebase 拒绝时不切换" main "$NO_RT && FAIL=pre-rebase G rebase side conflict"
+compare "rebase --no-verify" side "$NO_RT && FAIL=pre-rebase G rebase --no-verify main"
+compare "放弃变基" conflict "$NO_RT && G rebase main; G rebase --abort"
+
+# ========= 清理 =========
+cd ..
+rm -rf "$TEST_DIR"
+bold "\n✅ 钩子测试完成！"
@@ -0,0 +1,233 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+bold() { echo -e "\033[1m$*\033[0m"; }
+info() { echo -e "\033[36m[INFO]\033[0m $*"; }
+ok() { echo -e "\033[32m[OK]\033[0m $*"; }
+fail() { echo -e "\033[31m[FAIL]\033[0m $*" >&2; exit 1; }
+print_step() { echo -e "\033[33m▶ $*\033[0m"; }
+
+PROGRAM="$1"
+TEST_DIR="test_http_$(date +%s)"
+
+mkdir -p "$TEST_DIR" && cd "$TEST_DIR"
+
+export GIT_AUTHOR_NAME="Levio-Z" GIT_AUTHOR_EMAIL="67247011+Levio-z@users.noreply.github.com"
+export GIT_COMMITTER_NAME="$GIT_AUTHOR_NAME" GIT_COMMITTER_EMAIL="$GIT_AUTHOR_EMAIL"
+# 固定时间，两边的提交哈希才相同
+export GIT_AUTHOR_DATE="2024-01-01T00:00:00Z" GIT_COMMITTER_DATE="2024-01-01T00:00:00Z"
+# 需要认证时不在终端上询问
+export GIT_TERMINAL_PROMPT=0
+
+# 代替 HTTP 服务器：把请求按 CGI 交给 git http-backend，仓库根目录是测试目录。
+# 回复总是 chunked 的，客户端接受时用 gzip 压缩；设置了 HTTP_AUTH=user:password 时要求 Basic 认证
+cat > server.py <<'EOF'
+import base64, gzip, http.server, os, socketserver, subprocess, sys
+
+ROOT, PORT_FILE = sys.argv[1], sys.argv[2]
+AUTH = os.environ.get("HTTP_AUTH")
+
+class Handler(http.server.BaseHTTPRequestHandler):
+    protocol_version = "HTTP/1.1"
+
+    def log_message(self, format, *args):
+        pass
+
+    def body(self):
+        if self.headers.get("Transfer-Encoding", "").lower() == "chunked":
+            data = b""
+            while True:
+                size = int(self.rfile.readline().strip(), 16)
+                if size == 0:
+                    self.rfile.readline()
+                    return data
+                data += self.rfile.read(size)
+                self.rfile.readline()
+        return self.rfile.read(int(self.headers.get("Content-Length", 0)))
+
+    def handle_request(self):
+        body = self.body()
+        if AUTH and self.headers.get("Authorization") != "Basic " + base64.b64encode(AUTH.encode()).decode():
+            self.send_response(401)
+            self.send_header("WWW-Authenticate", 'Basic realm="git"')
+            self.send_header("Content-Length", "0")
+            self.end_headers()
+            return
+        path, _, query = self.path.partition("?")
+        env = dict(os.environ, GIT_PROJECT_ROOT=ROOT, GIT_HTTP_EXPORT_ALL="1",
+                   REQUEST_METHOD=self.command, PATH_INFO=path, QUERY_STRING=query,
+                   CONTENT_TYPE=self.headers.get("Content-Type", ""),
+                   CONTENT_LENGTH=str(len(body)), REMOTE_ADDR="127.0.0.1")
+        for name in ("Git-Protocol", "Content-Encoding"):
+            if name in self.headers:
+                env["HTTP_" + name.upper().replace("-", "_")] = self.headers[name]
+        output = subprocess.run(["git", "http-backend"], input=body, env=env,
+                                capture_output=True).stdout
+        head, _, data = output.partition(b"\r\n\r\n")
+        status, headers = 200, []
+        for line in head.decode().split("\r\n"):
+            name, _, value = line.partition(": ")
+            if name.lower() == "status":
+                status = int(value.split()[0])
+            elif name:
+                headers.append((name, value))
+        if "gzip" in self.headers.get("Accept-Encoding", ""):
+            data = gzip.compress(data)
+            headers.append(("Content-Encoding", "gzip"))
+        self.send_response(status)
+        for name, value in headers:
+            self.send_header(name, value)
+        self.send_header("Transfer-Encoding", "chunked")
+        self.end_headers()
+        for i in range(0, len(data), 8192):
+            chunk = data[i:i + 8192]
+            self.wfile.write(b"%x\r\n%s\r\n" % (len(chunk), chunk))
+        self.wfile.write(b"0\r\n\r\n")
+
+    do_GET = do_POST = handle_request
+
+server = socketserver.ThreadingTCPServer(("127.0.0.1", 0), Handler)
+with open(PORT_FILE, "w") as f:
+    f.write(str(server.server_address[1]))
+server.serve_forever()
+EOF
+
+# 启动服务器，等它写出端口号
+start_server() {
+    python3 server.py "$PWD" "$1" &
+    SERVERS+=("$!")
+    for _ in $(seq 50); do [ -s "$1" ] && return; sleep 0.1; done
+    fail "HTTP 服务器没有启动"
+}
+SERVERS=()
+trap 'kill "${SERVERS[@]}" 2>/dev/null || true' EXIT
+start_server port
+HTTP_AUTH=me:secret start_server auth-port
+PORT=$(cat port) AUTH_PORT=$(cat auth-port)
+
+# 假的 askpass：把提示记到 $ASKPASS_LOG，用户名回答 me，密码回答 secret
+cat > askpass <<'EOF'
+#!/bin/sh
+echo "$1" >> "$ASKPASS_LOG"
+case "$1" in Username*) echo me ;; *) echo secret ;; esac
+EOF
+chmod +x askpass
+export ASKPASS_LOG
+
+# 服务端 server.git（裸仓库，允许推送和 filter）：one(v1，附注标签) -> two(dev) -> three(main)。
+# dst 是之前从 server.git 克隆的，之后服务端多了 four(main，附注标签 v2) 和新分支 topic
+setup() {
+    rm -rf "$1" && mkdir "$1" && cd "$1"
+    git init -q -b main src
+    (
+        cd src
+        echo a > a; mkdir d; echo c > d/c; git add .; git commit -q -m one; git tag -a v1 -m v1
+        echo b >> a; git commit -q -am two; git branch dev
+        echo c >> a; git commit -q -am three
+    )
+    git clone -q --bare src server.git
+    git -C server.git config http.receivepack true
+    git -C server.git config uploadpack.allowfilter true
+    git clone -q "file://$PWD/server.git" dst
+    (
+        cd src
+        echo d >> a; git commit -q -am four; git tag -a v2 -m v2; git branch topic HEAD~1
+        git push -q ../server.git main topic v2
+    )
+    cd ..
+}
+
+# 两边的绝对路径、URL 中的仓库目录和端口不同，统一记为 ROOT 和 HOST
+normalize() {
+    sed "s#$PWD/git-repo#ROOT#g; s#$PWD/our-repo#ROOT#g; s#/git-repo/#/ROOT/#g; s#/our-repo/#/ROOT/#g; s#127\.0\.0\.1:[0-9]*#HOST#g" "$1"
+}
+
+# 仓库 $2 的配置、引用、HEAD、浅克隆边界、所有对象（缺少的以 ? 开头）和检出的文件
+snapshot() {
+    (
+        cd "$1/$2" 2>/dev/null || { echo "没有 $2"; exit 0; }
+        git config --local --list
+        git for-each-ref
+        git rev-parse --symbolic-full-name HEAD || true
+        cat shallow .git/shallow 2>/dev/null || true
+        cat .git/FETCH_HEAD 2>/dev/null || true
+        git rev-list --objects --all --missing=print | sort
+        if [ "$(git rev-parse --is-bare-repository)" = false ]; then
+            git status --porcelain
+            git ls-files -s
+        fi
+    ) 2>&1 | normalize /dev/stdin
+}
+
+# 用法：compare 名称 目录 脚本，脚本在 server.git 所在的目录中运行，用 G 代替 git 命令，
+# $URL 是经由 HTTP 访问这个目录的 URL，$AUTH_URL 经由要求认证的服务器。
+# 最后一条命令的退出码参与比较，之后比较“目录”中的仓库。
+# 只比较标准输出，需要比较错误输出时在脚本中用 2>&1（出错时的 fatal 与 Error 前缀不同，不比较）
+compare() {
+    local name="$1" dir="$2" script="$3"
+    setup git-repo; setup our-repo
+    local git_status=0 our_status=0
+    (cd git-repo && ASKPASS_LOG="$PWD/askpass.log" && URL="http://127.0.0.1:$PORT/git-repo" \
+        && AUTH_URL="http://127.0.0.1:$AUTH_PORT/git-repo" && G() { git "$@"; } \
+        && eval "$script") > git.out 2>/dev/null || git_status=$?
+    (cd our-repo && ASKPASS_LOG="$PWD/askpass.log" && URL="http://127.0.0.1:$PORT/our-repo" \
+        && AUTH_URL="http://127.0.0.1:$AUTH_PORT/our-repo" && G() { "$PROGRAM" "$@"; } \
+        && eval "$script") > our.out 2>/dev/null || our_status=$?
+    if ! diff -u <(normalize git.out) <(normalize our.out) \
+        || (( (git_status == 0) != (our_status == 0) )); then
+        fail "✗ $name: $script 输出不一致（git 退出码 $git_status，我们 $our_status），请检查实现"
+    fi
+    if diff -u <(snapshot git-repo "$dir") <(snapshot our-repo "$dir"); then
+        ok "✓ $name: $script 与官方git完全一致"
+    else
+        fail "✗ $name: $script 结果不一致，请检查实现"
+    fi
+}
+
+# ========= ls-remote =========
+print_step "比较经由 HTTP 的 ls-remote"
+compare "ls-remote" dst "G ls-remote \$URL/server.git"
+compare "--symref" dst "G ls-remote --symref --heads \$URL/server.git"
+compare "不存在" dst "G ls-remote \$URL/nope.git"
+
+# ========= clone =========
+print_step "比较经由 HTTP 的克隆"
+compare "clone" c "G clone \$URL/server.git c 2>&1"
+compare "--bare" c.git "G clone --bare \$URL/server.git c.git 2>&1"
+compare "-b 分支" c "G clone -b dev \$URL/server.git c 2>&1"
+compare "--depth 1" c "G clone --depth 1 \$URL/server.git c 2>&1"
+compare "--filter=blob:none" c "G clone --filter=blob:none \$URL/server.git c 2>&1"
+
+# ========= fetch =========
+print_step "比较经由 HTTP 的 fetch"
+fetch_setup="cd dst && git remote set-url origin \$URL/server.git"
+compare "fetch" dst "$fetch_setup && G fetch 2>&1"
+compare "--tags" dst "$fetch_setup && G fetch --tags 2>&1"
+compare "命令行分支" dst "$fetch_setup && G fetch origin topic 2>&1"
+compare "多轮协商" dst "$fetch_setup && for i in \$(seq 40); do git commit -q --allow-empty -m local\$i; done && G fetch 2>&1"
+
+# ========= push =========
+print_step "比较经由 HTTP 的推送"
+push_setup="git -C server.git update-ref refs/heads/main HEAD~1 && git -C server.git update-ref -d refs/heads/topic && cd dst && git remote set-url origin \$URL/server.git && echo e >> a && git commit -q -am five"
+compare "push" server.git "$push_setup && G push 2>&1"
+compare "新分支和标签" server.git "$push_setup && git tag -a v5 -m v5 && G push origin main:feature v5 2>&1"
+compare "删除" server.git "$push_setup && G push origin :dev 2>&1"
+compare "非快进" server.git "cd dst && git remote set-url origin \$URL/server.git && echo e >> a && git commit -q -am five && G push 2>&1"
+compare "--atomic" server.git "$push_setup && G push --atomic origin main v1:refs/tags/v2 2>&1"
+compare "钩子" server.git "printf '#!/bin/sh\necho checking\nexit 1\n' > server.git/hooks/pre-receive && chmod +x server.git/hooks/pre-receive && $push_setup && G push 2>&1"
+
+# ========= 认证 =========
+print_step "比较 HTTP 认证"
+compare "askpass" c "GIT_ASKPASS=\$PWD/../askpass G clone \$AUTH_URL/server.git c 2>&1 && cat \"\$ASKPASS_LOG\""
+compare "URL 中的用户名" c "GIT_ASKPASS=\$PWD/../askpass G clone http://me@\${AUTH_URL#http://}/server.git c 2>&1 && cat \"\$ASKPASS_LOG\""
+compare "URL 中的密码" dst "G ls-remote http://me:secret@\${AUTH_URL#http://}/server.git"
+compare "凭据助手" dst "echo http://me:secret@\${AUTH_URL#http://} > creds && cd dst && git config credential.helper \"store --file=\$PWD/../creds\" && git remote set-url origin \$AUTH_URL/server.git && G fetch 2>&1"
+compare "保存凭据" dst "cd dst && git config credential.helper \"store --file=\$PWD/../creds\" && git remote set-url origin \$AUTH_URL/server.git && GIT_ASKPASS=\$PWD/../../askpass G fetch 2>&1 && normalize ../creds"
+compare "推送认证" server.git "$push_setup && git remote set-url origin \$AUTH_URL/server.git && GIT_ASKPASS=\$PWD/../../askpass G push 2>&1"
+compare "密码错误" dst "G ls-remote http://me:bad@\${AUTH_URL#http://}/server.git"
+compare "不能询问" dst "G ls-remote \$AUTH_URL/server.git"
+
+# ========= 清理 =========
+cd ..
+rm -rf "$TEST_DIR"
+bold "\n✅ http 测试完成！"
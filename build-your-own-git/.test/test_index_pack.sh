@@ -0,0 +1,153 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+bold() { echo -e "\033[1m$*\033[0m"; }
+info() { echo -e "\033[36m[INFO]\033[0m $*"; }
+ok() { echo -e "\033[32m[OK]\033[0m $*"; }
+fail() { echo -e "\033[31m[FAIL]\033[0m $*" >&2; exit 1; }
+print_step() { echo -e "\033[33m▶ $*\033[0m"; }
+
+PROGRAM="$1"
+TEST_DIR="test_index_pack_$(date +%s)"
+
+mkdir -p "$TEST_DIR" && cd "$TEST_DIR"
+
+export GIT_AUTHOR_NAME="Levio-Z" GIT_AUTHOR_EMAIL="67247011+Levio-z@users.noreply.github.com"
+export GIT_COMMITTER_NAME="$GIT_AUTHOR_NAME" GIT_COMMITTER_EMAIL="$GIT_AUTHOR_EMAIL"
+# 固定时间，两边的提交哈希才相同
+export GIT_AUTHOR_DATE="2024-01-01T00:00:00Z" GIT_COMMITTER_DATE="2024-01-01T00:00:00Z"
+
+# 准备各种 pack，两边共用：
+# full.pack 是 src 仓库 repack 出的（ofs-delta，delta 链较深），ref.pack 的 delta 都是 ref-delta，
+# thin.pack 是最近两个提交的 thin pack，基对象在 repo 中。repo 有 src 的所有对象（松散的）
+print_step "准备测试用的 pack"
+git init -q -b main src
+(
+    cd src
+    for i in $(seq 12); do
+        seq 1 $((i * 150)) > numbers
+        seq $i 40 > "small$((i % 3))"
+        mkdir -p "dir$((i % 4))" && echo "$i" > "dir$((i % 4))/file"
+        git add . && git commit -q -m "commit $i"
+    done
+    git tag -a v1 -m "tag v1"
+    git repack -adfq --window=50 --depth=50
+)
+cp src/.git/objects/pack/*.pack full.pack
+git -C src pack-objects --all --revs --stdout -q < /dev/null > ref.pack
+printf 'main\n^main~2\n' | git -C src pack-objects --revs --thin --stdout -q > thin.pack
+# bad.pack 只有一个对象，对象大小的变长编码超出 64 位，校验和是对的
+printf 'PACK\0\0\0\2\0\0\0\1\xb0\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\x01' > bad.pack
+printf "$(sha1sum bad.pack | cut -c1-40 | sed 's/../\\x&/g')" >> bad.pack
+git init -q -b main fixture-repo
+git -C fixture-repo unpack-objects -q < full.pack
+
+# 每次比较前重新放好 pack 和仓库
+setup() {
+    rm -rf "$1" && mkdir "$1"
+    cp full.pack ref.pack thin.pack bad.pack "$1"
+    cp -r fixture-repo "$1/repo"
+}
+
+# 目录中的所有文件和它们的内容，git 失败时留下的临时 pack 除外
+snapshot() {
+    (cd "$1" && find . -type f -not -path './repo/.git/objects/??/*' -not -path './repo/.git/logs/*' -not -name 'tmp_pack_*' \
+        | sort | xargs sha1sum)
+}
+
+# 用法：compare 名称 脚本。脚本在测试目录中执行，G 是 git 或我们的程序。
+# 比较输出、退出码是否为 0 以及生成的文件
+compare() {
+    local name="$1" script="$2"
+    setup git-repo; setup our-repo
+    local git_status=0 our_status=0
+    (cd git-repo && G() { git "$@"; } && eval "$script") > git.out 2>/dev/null || git_status=$?
+    (cd our-repo && G() { "$PROGRAM" "$@"; } && eval "$script") > our.out 2>/dev/null || our_status=$?
+    if ! diff -u git.out our.out || (( (git_status == 0) != (our_status == 0) )); then
+        fail "✗ $name: $script 输出不一致（git 退出码 $git_status，我们 $our_status），请检查实现"
+    fi
+    if diff -u <(snapshot git-repo) <(snapshot our-repo); then
+        ok "✓ $name: $script 与官方git完全一致"
+    else
+        fail "✗ $name: $script 结果不一致，请检查实现"
+    fi
+}
+
+# ========= index-pack =========
+print_step "比较 index-pack"
+compare "ofs-delta" "G index-pack --rev-index full.pack"
+compare "ref-delta" "G index-pack --rev-index ref.pack"
+compare "不写 .rev" "G index-pack --no-rev-index full.pack"
+compare "后面的选项优先" "G index-pack --no-rev-index --rev-index ref.pack"
+compare "-o" "G index-pack --rev-index -o out.idx full.pack"
+compare "重建索引" "G index-pack --rev-index full.pack && rm full.rev && echo junk > full.idx && G index-pack --rev-index full.pack"
+compare "不是 .pack" "cp full.pack full.data && G index-pack full.data"
+compare "-o 不是 .idx" "G index-pack --rev-index -o out.data full.pack"
+compare "不存在" "G index-pack nothere.pack"
+compare "thin pack" "G index-pack thin.pack"
+compare "--fix-thin 需要 --stdin" "G index-pack --fix-thin thin.pack"
+compare "校验和不对" "printf x | dd of=full.pack bs=1 seek=100 conv=notrunc 2>/dev/null && G index-pack full.pack"
+compare "截断" "head -c 1000 full.pack > cut.pack && G index-pack cut.pack"
+compare "对象大小溢出" "G index-pack bad.pack"
+compare "配置不写 .rev" "cd repo && git config pack.writeReverseIndex false && G index-pack ../full.pack"
+compare "配置写 .rev" "cd repo && git config pack.writeReverseIndex true && G index-pack ../ref.pack"
+
+print_step "比较 index-pack --stdin"
+compare "--stdin" "cd repo && G index-pack --stdin --rev-index < ../full.pack"
+compare "--stdin 指定文件" "cd repo && G index-pack --stdin --no-rev-index ../copy.pack < ../ref.pack"
+compare "--keep" "cd repo && G index-pack --stdin --rev-index --keep < ../full.pack"
+compare "--keep=<msg>" "cd repo && G index-pack --stdin --no-rev-index --keep='fetch from origin' < ../ref.pack"
+compare "已有 .keep" "cd repo && G index-pack --stdin --rev-index --keep=one < ../full.pack && G index-pack --stdin --rev-index --keep=two < ../full.pack"
+compare "thin pack 不修复" "cd repo && G index-pack --stdin < ../thin.pack"
+compare "空输入" "cd repo && G index-pack --stdin < /dev/null"
+
+print_step "比较 index-pack --verify"
+compare "一致" "git index-pack --rev-index full.pack && G index-pack --verify full.pack"
+compare "不一致" "git index-pack full.pack && cp full.idx ref.idx && G index-pack --verify ref.pack"
+compare "没有 .idx" "G index-pack --verify full.pack"
+
+# ========= verify-pack =========
+print_step "比较 verify-pack"
+compare "-v" "git index-pack full.pack && G verify-pack -v full.idx"
+compare "ref-delta" "git index-pack ref.pack && G verify-pack -v ref.pack"
+compare "-s" "git index-pack full.pack && G verify-pack -s full"
+compare "不加选项" "git index-pack full.pack && G verify-pack full.idx"
+compare "多个" "git index-pack full.pack && git index-pack ref.pack && G verify-pack -v ref.idx full.idx"
+compare "修复后的 thin pack" "cd repo && git index-pack --stdin --fix-thin ../fixed.pack < ../thin.pack && G verify-pack -v ../fixed.pack"
+compare "不一致" "git index-pack full.pack && git index-pack ref.pack && cp ref.idx full.idx && G verify-pack -v full.idx"
+compare "不一致不加选项" "git index-pack ref.pack && cp ref.idx full.idx && G verify-pack full.idx ref.idx"
+compare "没有 .idx" "G verify-pack -v full.idx"
+compare "没有参数" "G verify-pack"
+
+# ========= --fix-thin =========
+# 补上的基对象由我们重新压缩，zlib 的实现不同，pack 的字节和校验和会与 git 的不同；
+# 所以只比较对象：哈希、类型、大小、delta 链的长度和基对象
+print_step "比较 index-pack --fix-thin"
+objects() {
+    git verify-pack -v "$1" | awk 'NF >= 5 { print $1, $2, $3, $6, $7 }'
+}
+for args in "--fix-thin" "--fix-thin --keep"; do
+    setup git-repo; setup our-repo
+    git_out=$(cd git-repo/repo && git index-pack --stdin $args < ../thin.pack)
+    our_out=$(cd our-repo/repo && "$PROGRAM" index-pack --stdin $args < ../thin.pack)
+    [ "${our_out%%	*}" = "${git_out%%	*}" ] || fail "✗ $args: 输出 $our_out，git 为 $git_out"
+    name="${our_out#*	}"
+    pack="our-repo/repo/.git/objects/pack/pack-$name.pack"
+    [ "$(tail -c 20 "$pack" | od -An -tx1 | tr -d ' \n')" = "$name" ] \
+        || fail "✗ $args: 输出的 $name 不是 pack 的校验和"
+    git index-pack --verify "$pack" || fail "✗ $args: git 认为我们的 .idx 与 pack 不一致"
+    diff -u <(objects git-repo/repo/.git/objects/pack/*.pack) <(objects "$pack") \
+        || fail "✗ $args: 补全后的对象不一致"
+    ok "✓ $args: 补上了缺少的基对象，索引能通过 git 的检查"
+done
+
+setup our-repo
+(cd our-repo/repo && "$PROGRAM" index-pack --stdin --fix-thin ../fixed.pack < ../thin.pack > /dev/null)
+diff -u <(objects git-repo/repo/.git/objects/pack/*.pack) <(objects our-repo/fixed.pack) \
+    || fail "✗ --fix-thin 指定文件: 补全后的对象不一致"
+ok "✓ --fix-thin 指定文件: 补全后的 pack 写到给出的文件"
+
+# ========= 清理 =========
+cd ..
+rm -rf "$TEST_DIR"
+bold "\n✅ index-pack 和 verify-pack 测试完成！"
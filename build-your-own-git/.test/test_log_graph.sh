@@ -0,0 +1,64 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+bold() { echo -e "\033[1m$*\033[0m"; }
+info() { echo -e "\033[36m[INFO]\033[0m $*"; }
+ok() { echo -e "\033[32m[OK]\033[0m $*"; }
+fail() { echo -e "\033[31m[FAIL]\033[0m $*" >&2; exit 1; }
+print_step() { echo -e "\033[33m▶ $*\033[0m"; }
+
+PROGRAM="$1"
+TEST_DIR="test_log_graph_$(date +%s)"
+OUR_OUTPUT=$(mktemp)
+GIT_OUTPUT=$(mktemp)
+
+mkdir -p "$TEST_DIR" && cd "$TEST_DIR"
+
+# 提交者不依赖本机 git 的用户配置
+export GIT_AUTHOR_NAME="Levio-Z" GIT_AUTHOR_EMAIL="67247011+Levio-z@users.noreply.github.com"
+export GIT_COMMITTER_NAME="$GIT_AUTHOR_NAME" GIT_COMMITTER_EMAIL="$GIT_AUTHOR_EMAIL"
+
+# ========= 用官方git构造带合并的历史 =========
+print_step "使用官方git构造包含合并和章鱼合并的历史"
+git init -q -b main
+TIME=1700000000
+commit() {
+    TIME=$((TIME + 60))
+    GIT_AUTHOR_DATE="$TIME +0800" GIT_COMMITTER_DATE="$TIME +0800" \
+        git commit -q --allow-empty -m "$1"
+}
+merge() {
+    TIME=$((TIME + 60))
+    GIT_AUTHOR_DATE="$TIME +0800" GIT_COMMITTER_DATE="$TIME +0800" \
+        git merge -q --no-ff -m "$1" "${@:2}" > /dev/null
+}
+commit A; commit B
+git branch f1; git branch f2; git branch f3
+commit C
+git checkout -q f1; commit F1a; commit F1b
+git checkout -q f2; commit F2a
+git checkout -q f3; commit F3a; commit F3b
+git checkout -q main; merge "octopus" f1 f2 f3; commit D
+git checkout -q -b side HEAD~1; commit S1
+git checkout -q main; commit E; merge "merge side" side
+git tag v1 HEAD~1
+git tag -a -m "annotated" v2 HEAD~3
+REFS=$(git for-each-ref --format='%(refname)')
+
+# ========= 比较结果 =========
+for OPTS in "--graph --oneline --decorate" "--graph"; do
+    print_step "比较 log $OPTS 的输出"
+    git log $OPTS $REFS > "$GIT_OUTPUT"
+    "$PROGRAM" log $OPTS $REFS > "$OUR_OUTPUT"
+    cat "$OUR_OUTPUT"
+    if diff -u "$GIT_OUTPUT" "$OUR_OUTPUT"; then
+        ok "✓ log $OPTS 与官方git完全一致"
+    else
+        fail "✗ log $OPTS 输出不一致，请检查实现"
+    fi
+done
+
+# ========= 清理 =========
+cd ..
+rm -rf "$TEST_DIR" "$OUR_OUTPUT" "$GIT_OUTPUT"
+bold "\n✅ log --graph 测试完成！"
@@ -0,0 +1,126 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+bold() { echo -e "\033[1m$*\033[0m"; }
+info() { echo -e "\033[36m[INFO]\033[0m $*"; }
+ok() { echo -e "\033[32m[OK]\033[0m $*"; }
+fail() { echo -e "\033[31m[FAIL]\033[0m $*" >&2; exit 1; }
+print_step() { echo -e "\033[33m▶ $*\033[0m"; }
+
+PROGRAM="$1"
+TEST_DIR="test_merge_$(date +%s)"
+
+mkdir -p "$TEST_DIR" && cd "$TEST_DIR"
+
+# 提交者不依赖本机 git 的用户配置
+export GIT_AUTHOR_NAME="Levio-Z" GIT_AUTHOR_EMAIL="67247011+Levio-z@users.noreply.github.com"
+export GIT_COMMITTER_NAME="$GIT_AUTHOR_NAME" GIT_COMMITTER_EMAIL="$GIT_AUTHOR_EMAIL"
+
+# 构造双方都有修改的历史：内容冲突、add/add、modify/delete、重命名和目录/文件冲突
+setup() {
+    rm -rf "$1" && mkdir "$1" && cd "$1"
+    # 固定时间，两个仓库的提交哈希才相同
+    export GIT_AUTHOR_DATE="2024-01-01T00:00:00Z" GIT_COMMITTER_DATE="2024-01-01T00:00:00Z"
+    git init -q -b main
+    seq 1 20 > both; seq 1 20 > mod; seq 1 20 > model; seq 1 20 > modrm
+    seq 1 30 > rendel; seq 101 130 > renren; seq 201 230 > renmod
+    echo f > dirfile; seq 1 10 > clean; seq 301 330 > moved
+    git add -A && git commit -q -m base
+    git checkout -q -b feature
+    sed -i 's/^5$/five-f/' both; sed -i 's/^3$/three/' mod; rm model
+    sed -i 's/^4$/four-f/' modrm; rm rendel; git mv renren renren-f
+    echo theirs > addadd; rm dirfile; mkdir dirfile; echo x > dirfile/x
+    sed -i 's/^1$/one-f/' clean; git mv moved moved-f; echo new > new
+    mkdir sub; echo s > sub/s
+    git add -A && git commit -q -m feature
+    git checkout -q -b ahead
+    echo more >> new && git commit -q -am ahead
+    git checkout -q main
+    git tag start
+    sed -i 's/^5$/five-m/' both; sed -i 's/^20$/twenty/' mod; sed -i 's/^4$/four-m/' model
+    git rm -q modrm; git mv rendel rendel2; git mv renren renren-m
+    git mv renmod renmod2; sed -i 's/^229$/x/' renmod2; echo ours > addadd
+    rm dirfile; echo f2 > dirfile; sed -i 's/^305$/x/' moved
+    git add -A && git commit -q -m main
+    git checkout -q -b side start
+    sed -i 's/^9$/nine/' clean && git commit -q -am side
+    git checkout -q main
+    cd ..
+}
+
+# 输出、退出码、暂存区、工作区文件和合并状态都要与 git 一致
+snapshot() {
+    (
+        cd "$1"
+        git ls-files -s
+        git status --porcelain -uall
+        git log -1 --format=%T
+        find . -path ./.git -prune -o -type f -print | sort | while read -r f; do
+            echo "== $f"; cat "$f"
+        done
+        cat .git/MERGE_MSG 2>/dev/null || true
+    )
+}
+
+# 用法：compare 名称 起始分支 合并参数...，PREPARE 给出时先在两个仓库中执行它
+compare() {
+    local name="$1" branch="$2"; shift 2
+    setup git-repo; setup our-repo
+    git -C git-repo checkout -q "$branch"; git -C our-repo checkout -q "$branch"
+    (cd git-repo && eval "${PREPARE:-}"); (cd our-repo && eval "${PREPARE:-}")
+    local git_status=0 our_status=0
+    (cd git-repo && git "$@") > git.out 2>/dev/null || git_status=$?
+    (cd our-repo && "$PROGRAM" "$@") > our.out 2>/dev/null || our_status=$?
+    if ! diff -u git.out our.out || (( (git_status == 0) != (our_status == 0) )); then
+        fail "✗ $name: $* 输出不一致（git 退出码 $git_status，我们 $our_status），请检查实现"
+    fi
+    if diff -u <(snapshot git-repo) <(snapshot our-repo); then
+        ok "✓ $name: $* 与官方git完全一致"
+    else
+        fail "✗ $name: $* 合并结果不一致，请检查实现"
+    fi
+}
+
+# ========= 比较合并 =========
+print_step "比较各种冲突的合并"
+compare "冲突" main merge feature
+
+print_step "比较快进和无冲突的合并"
+compare "已是最新" main merge start
+compare "快进" start merge feature
+compare "只允许快进" start merge --ff-only ahead
+compare "不允许快进" main merge --ff-only side
+compare "不提交" main merge --no-commit side
+compare "合并提交" main merge side
+compare "其他分支" side merge feature
+compare "分离头指针" "side^0" merge -m "custom message" ahead
+compare "以 - 开头的说明" main merge -m "-M1 rename" side
+
+print_step "比较挡路的未跟踪文件"
+PREPARE="echo PRECIOUS > sub" compare "上级路径上的文件" start merge feature
+PREPARE="mkdir new && echo PRECIOUS > new/u" compare "有未跟踪文件的目录" start merge feature
+PREPARE="mkdir -p new/empty" compare "空目录" start merge feature
+PREPARE="echo PRECIOUS > new" compare "同名文件" start merge feature
+
+print_step "比较放弃和继续合并"
+setup our-repo
+(cd our-repo && { "$PROGRAM" merge feature >/dev/null || true; } && "$PROGRAM" merge --abort)
+if [ -z "$(cd our-repo && git status --porcelain)" ] && [ ! -e our-repo/.git/MERGE_HEAD ]; then
+    ok "✓ merge --abort 恢复到合并前的状态"
+else
+    fail "✗ merge --abort 没有恢复工作区和暂存区"
+fi
+(cd our-repo && { "$PROGRAM" merge feature >/dev/null || true; } \
+    && git checkout -q --theirs -- both mod addadd 2>/dev/null; git add -A \
+    && "$PROGRAM" merge --continue >/dev/null)
+if [ "$(cd our-repo && git rev-list --parents -1 HEAD | wc -w)" -eq 3 ] \
+    && [ -z "$(cd our-repo && git status --porcelain)" ]; then
+    ok "✓ merge --continue 创建了两个父提交的合并提交"
+else
+    fail "✗ merge --continue 没有正确创建合并提交"
+fi
+
+# ========= 清理 =========
+cd ..
+rm -rf "$TEST_DIR"
+bold "\n✅ merge 测试完成！"
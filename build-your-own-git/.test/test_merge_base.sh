@@ -0,0 +1,99 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+bold() { echo -e "\033[1m$*\033[0m"; }
+info() { echo -e "\033[36m[INFO]\033[0m $*"; }
+ok() { echo -e "\033[32m[OK]\033[0m $*"; }
+fail() { echo -e "\033[31m[FAIL]\033[0m $*" >&2; exit 1; }
+print_step() { echo -e "\033[33m▶ $*\033[0m"; }
+
+PROGRAM="$1"
+TEST_DIR="test_merge_base_$(date +%s)"
+
+mkdir -p "$TEST_DIR" && cd "$TEST_DIR"
+
+# 提交者不依赖本机 git 的用户配置
+export GIT_AUTHOR_NAME="Levio-Z" GIT_AUTHOR_EMAIL="67247011+Levio-z@users.noreply.github.com"
+export GIT_COMMITTER_NAME="$GIT_AUTHOR_NAME" GIT_COMMITTER_EMAIL="$GIT_AUTHOR_EMAIL"
+
+# 每个提交使用递增的时间，保证按时间排序的结果稳定
+tick=1700000000
+commit() {
+    tick=$((tick + 60))
+    echo "$1" > "$1"
+    git add "$1"
+    GIT_AUTHOR_DATE="@$tick +0000" GIT_COMMITTER_DATE="@$tick +0000" git commit -q -m "$1"
+}
+merge() {
+    tick=$((tick + 60))
+    GIT_AUTHOR_DATE="@$tick +0000" GIT_COMMITTER_DATE="@$tick +0000" \
+        git merge -q --no-edit --no-ff "$@" >/dev/null
+}
+
+info "构造包含交叉合并、多分支和上游变基的历史"
+git init -q -b main repo && cd repo
+commit a; commit b
+git branch one; git branch two; git branch three
+commit c
+git checkout -q one; commit d
+git checkout -q two; commit e
+git checkout -q three; commit f; git tag start
+# 交叉合并：x 和 y 互相合并对方，产生两个最佳公共祖先
+git checkout -q -b x one; merge two; commit x
+git checkout -q -b y two; merge one; commit y
+git checkout -q main
+# 上游被改写：topic 从 upstream 的旧位置分叉，之后 upstream 被 reset 并重新提交
+git checkout -q -b upstream main; commit u1; commit u2
+git checkout -q -b topic; commit t1
+git checkout -q upstream; git reset -q --hard HEAD~1; commit u3
+git checkout -q main
+# 没有公共祖先的孤立分支
+git checkout -q --orphan lonely; git rm -q -rf . ; commit l
+git checkout -q main
+
+# 用法：compare 名称 merge-base 参数...
+compare() {
+    local name="$1"; shift
+    local git_status=0 our_status=0
+    git merge-base "$@" > ../git.out 2>/dev/null || git_status=$?
+    "$PROGRAM" merge-base "$@" > ../our.out 2>/dev/null || our_status=$?
+    if diff -u ../git.out ../our.out && (( (git_status == 0) == (our_status == 0) )); then
+        ok "✓ $name: merge-base $* 与官方git完全一致"
+    else
+        fail "✗ $name: merge-base $* 不一致（git 退出码 $git_status，我们 $our_status），请检查实现"
+    fi
+}
+
+run_all() {
+    compare "分叉" main one
+    compare "祖先" main main~2
+    compare "相同提交" main main
+    compare "交叉合并" x y
+    compare "交叉合并全部" --all x y
+    compare "与多个提交" one two three
+    compare "与多个提交全部" -a main x y
+    compare "没有公共祖先" main lonely
+    compare "octopus" --octopus one two three
+    compare "octopus 全部" --octopus --all x y main
+    compare "octopus 单个" --octopus x
+    compare "是祖先" --is-ancestor main~2 x
+    compare "不是祖先" --is-ancestor x main~2
+    compare "自身是祖先" --is-ancestor x x
+    compare "分叉点" --fork-point upstream topic
+    compare "分叉点默认 HEAD" --fork-point upstream
+    compare "无分叉点" --fork-point main lonely
+    compare "无 reflog 的分叉点" --fork-point start three
+}
+
+print_step "比较没有 commit-graph 时的结果"
+run_all
+
+print_step "比较使用 commit-graph 代数时的结果"
+git commit-graph write --reachable
+[ -f .git/objects/info/commit-graph ] || fail "✗ 没有生成 commit-graph"
+run_all
+
+# ========= 清理 =========
+cd ../..
+rm -rf "$TEST_DIR"
+bold "\n✅ merge-base 测试完成！"
@@ -0,0 +1,92 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+bold() { echo -e "\033[1m$*\033[0m"; }
+info() { echo -e "\033[36m[INFO]\033[0m $*"; }
+ok() { echo -e "\033[32m[OK]\033[0m $*"; }
+fail() { echo -e "\033[31m[FAIL]\033[0m $*" >&2; exit 1; }
+print_step() { echo -e "\033[33m▶ $*\033[0m"; }
+
+PROGRAM="$1"
+TEST_DIR="test_merge_file_$(date +%s)"
+OUR_OUTPUT=$(mktemp)
+GIT_OUTPUT=$(mktemp)
+
+mkdir -p "$TEST_DIR" && cd "$TEST_DIR"
+
+# 提交者不依赖本机 git 的用户配置
+export GIT_AUTHOR_NAME="Levio-Z" GIT_AUTHOR_EMAIL="67247011+Levio-z@users.noreply.github.com"
+export GIT_COMMITTER_NAME="$GIT_AUTHOR_NAME" GIT_COMMITTER_EMAIL="$GIT_AUTHOR_EMAIL"
+
+# 比较输出和退出码（冲突数）
+compare() {
+    local git_status=0 our_status=0
+    git merge-file -p "$@" > "$GIT_OUTPUT" || git_status=$?
+    "$PROGRAM" merge-file -p "$@" > "$OUR_OUTPUT" || our_status=$?
+    if diff -u "$GIT_OUTPUT" "$OUR_OUTPUT" && [ "$git_status" -eq "$our_status" ]; then
+        ok "✓ merge-file $* 与官方git完全一致（退出码 $git_status）"
+    else
+        fail "✗ merge-file $* 输出不一致（git 退出码 $git_status，我们 $our_status），请检查实现"
+    fi
+}
+
+# ========= 构造三个版本 =========
+print_step "构造共同祖先和双方的修改"
+cat > base.c <<'SRC'
+#include <stdio.h>
+
+int add(int a, int b)
+{
+    return a + b;
+}
+
+int sub(int a, int b)
+{
+    return a - b;
+}
+
+int main(void)
+{
+    printf("%d\n", add(1, 2));
+    return 0;
+}
+SRC
+sed 's/return a + b;/return b + a;/; s/add(1, 2)/add(2, 1)/' base.c > ours.c
+sed 's/return a - b;/return b - a;/; s/add(1, 2)/add(3, 4)/' base.c > theirs.c
+printf 'one\ntwo\nthree\n' > clean-base
+printf 'zero\none\ntwo\nthree\n' > clean-ours
+printf 'one\ntwo\nthree\nfour' > clean-theirs
+printf 'a\r\nb\r\nc\r\n' > crlf-base
+printf 'a\r\nB\r\nc\r\n' > crlf-ours
+printf 'a\r\nb2\r\nc\r\n' > crlf-theirs
+
+# ========= 比较结果 =========
+print_step "比较没有冲突的合并"
+compare clean-ours clean-base clean-theirs
+
+print_step "比较冲突样式和标签"
+compare ours.c base.c theirs.c
+compare --diff3 ours.c base.c theirs.c
+compare --zdiff3 ours.c base.c theirs.c
+compare --marker-size=10 -L mine -L orig -L yours ours.c base.c theirs.c
+compare crlf-ours crlf-base crlf-theirs
+
+print_step "比较自动解决冲突"
+compare --ours ours.c base.c theirs.c
+compare --theirs ours.c base.c theirs.c
+compare --union ours.c base.c theirs.c
+
+print_step "合并结果写回 current 文件"
+cp ours.c git-ours.c
+git merge-file -L ours.c git-ours.c base.c theirs.c || true
+"$PROGRAM" merge-file ours.c base.c theirs.c || true
+if diff -u git-ours.c ours.c; then
+    ok "✓ 写回的文件与官方git完全一致"
+else
+    fail "✗ 写回的文件不一致，请检查实现"
+fi
+
+# ========= 清理 =========
+cd ..
+rm -rf "$TEST_DIR" "$OUR_OUTPUT" "$GIT_OUTPUT"
+bold "\n✅ merge-file 测试完成！"
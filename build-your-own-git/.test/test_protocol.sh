@@ -0,0 +1,152 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+bold() { echo -e "\033[1m$*\033[0m"; }
+info() { echo -e "\033[36m[INFO]\033[0m $*"; }
+ok() { echo -e "\033[32m[OK]\033[0m $*"; }
+fail() { echo -e "\033[31m[FAIL]\033[0m $*" >&2; exit 1; }
+print_step() { echo -e "\033[33m▶ $*\033[0m"; }
+
+PROGRAM="$1"
+TEST_DIR="test_protocol_$(date +%s)"
+
+mkdir -p "$TEST_DIR" && cd "$TEST_DIR"
+
+export GIT_AUTHOR_NAME="Levio-Z" GIT_AUTHOR_EMAIL="67247011+Levio-z@users.noreply.github.com"
+export GIT_COMMITTER_NAME="$GIT_AUTHOR_NAME" GIT_COMMITTER_EMAIL="$GIT_AUTHOR_EMAIL"
+# 固定时间，两边的提交哈希才相同
+export GIT_AUTHOR_DATE="2024-01-01T00:00:00Z" GIT_COMMITTER_DATE="2024-01-01T00:00:00Z"
+
+# 假的 ssh：把参数记到 $SSH_LOG，然后在本机运行最后一个参数（远端命令）
+cat > fake-ssh <<'EOF'
+#!/bin/sh
+echo "ssh $*" >> "$SSH_LOG"
+for arg; do command="$arg"; done
+exec sh -c "$command"
+EOF
+chmod +x fake-ssh
+export GIT_SSH_COMMAND="$PWD/fake-ssh" GIT_SSH_VARIANT=ssh SSH_LOG
+
+# 服务端 server.git（裸仓库，允许 filter）：one(v1，附注标签) -> two(dev) -> three(main，light 轻量标签)。
+# dst 是之前从 server.git 克隆的，之后服务端多了 four(main，附注标签 v2)、
+# 指向旧提交 one 的附注标签 old，以及新分支 topic。另有一个 HEAD 指向 trunk 的空仓库 empty.git
+setup() {
+    rm -rf "$1" && mkdir "$1" && cd "$1"
+    git init -q -b main src
+    (
+        cd src
+        echo a > a; mkdir d; echo c > d/c; git add .; git commit -q -m one; git tag -a v1 -m v1
+        echo b >> a; git commit -q -am two; git branch dev
+        echo c >> a; git commit -q -am three; git tag light
+    )
+    git clone -q --bare src server.git
+    git -C server.git config uploadpack.allowfilter true
+    git clone -q "file://$PWD/server.git" dst
+    (
+        cd src
+        echo d >> a; git commit -q -am four; git tag -a v2 -m v2
+        git tag -a old -m old HEAD~3; git branch topic HEAD~1
+        git push -q ../server.git main topic v2 old
+    )
+    git init -q --bare empty.git
+    git -C empty.git symbolic-ref HEAD refs/heads/trunk
+    cd ..
+}
+
+# 两边的绝对路径不同，统一记为 ROOT
+normalize() {
+    sed "s#$PWD/git-repo#ROOT#g; s#$PWD/our-repo#ROOT#g" "$1"
+}
+
+# 仓库 $2 的配置、引用、HEAD、浅克隆边界、所有对象（缺少的以 ? 开头）和检出的文件
+snapshot() {
+    (
+        cd "$1/$2" 2>/dev/null || { echo "没有 $2"; exit 0; }
+        git config --local --list
+        git for-each-ref
+        git rev-parse --symbolic-full-name HEAD || true
+        cat shallow .git/shallow 2>/dev/null || true
+        cat .git/FETCH_HEAD 2>/dev/null || true
+        git rev-list --objects --all --missing=print | sort
+        if [ "$(git rev-parse --is-bare-repository)" = false ]; then
+            git status --porcelain
+            git ls-files -s
+        fi
+    ) 2>&1 | normalize /dev/stdin
+}
+
+# 用法：compare 名称 目录 脚本，脚本在 server.git 所在的目录中运行，用 G 代替 git 命令，
+# 最后一条命令的退出码参与比较，之后比较“目录”中的仓库。
+# 只比较标准输出，需要比较错误输出时在脚本中用 2>&1（出错时的 fatal 与 Error 前缀不同，不比较）
+compare() {
+    local name="$1" dir="$2" script="$3"
+    setup git-repo; setup our-repo
+    local git_status=0 our_status=0
+    (cd git-repo && SSH_LOG="$PWD/ssh.log" && G() { git "$@"; } && eval "$script") > git.out 2>/dev/null || git_status=$?
+    (cd our-repo && SSH_LOG="$PWD/ssh.log" && G() { "$PROGRAM" "$@"; } && eval "$script") > our.out 2>/dev/null || our_status=$?
+    if ! diff -u <(normalize git.out) <(normalize our.out) \
+        || (( (git_status == 0) != (our_status == 0) )); then
+        fail "✗ $name: $script 输出不一致（git 退出码 $git_status，我们 $our_status），请检查实现"
+    fi
+    if diff -u <(snapshot git-repo "$dir") <(snapshot our-repo "$dir"); then
+        ok "✓ $name: $script 与官方git完全一致"
+    else
+        fail "✗ $name: $script 结果不一致，请检查实现"
+    fi
+}
+
+# ========= ls-remote =========
+print_step "比较 ls-remote"
+compare "ls-remote" dst "G ls-remote file://\$PWD/server.git"
+compare "--heads" dst "G ls-remote --heads file://\$PWD/server.git"
+compare "--tags" dst "G ls-remote --tags file://\$PWD/server.git"
+compare "--refs" dst "G ls-remote --refs file://\$PWD/server.git"
+compare "--symref" dst "G ls-remote --symref file://\$PWD/server.git"
+compare "模式" dst "G ls-remote file://\$PWD/server.git main 'v*' HEAD"
+compare "--exit-code 没有匹配" dst "G ls-remote --exit-code file://\$PWD/server.git nope"
+compare "本地路径" dst "G ls-remote \$PWD/server.git"
+compare "默认远程" dst "cd dst && G ls-remote --heads 2>&1"
+compare "-q" dst "cd dst && G ls-remote -q origin refs/tags/v1"
+compare "没有远程" dst "cd src && G ls-remote"
+compare "空仓库" dst "G ls-remote --symref file://\$PWD/empty.git"
+compare "不存在" dst "G ls-remote file://\$PWD/nope.git"
+compare "ssh" dst "G ls-remote host:\$PWD/server.git && cat \"\$SSH_LOG\""
+
+# ========= clone =========
+print_step "比较经由 git-upload-pack 的克隆"
+compare "file://" c "G clone file://\$PWD/server.git c 2>&1"
+compare "--bare" c.git "G clone --bare file://\$PWD/server.git c.git 2>&1"
+compare "--mirror" c.git "G clone --mirror file://\$PWD/server.git c.git 2>&1"
+compare "-b 分支" c "G clone -b dev file://\$PWD/server.git c 2>&1"
+compare "-b 标签" c "G clone -b v1 file://\$PWD/server.git c 2>&1"
+compare "--depth 1" c "G clone --depth 1 file://\$PWD/server.git c 2>&1"
+compare "--depth 2 -b dev" c "G clone --depth 2 -b dev file://\$PWD/server.git c 2>&1"
+compare "--depth 标签" c "G clone --depth 1 -b v1 file://\$PWD/server.git c 2>&1"
+compare "--no-local" c "G clone --no-local \$PWD/server.git c 2>&1"
+compare "--filter=blob:none" c "G clone --filter=blob:none file://\$PWD/server.git c 2>&1"
+compare "--filter 裸仓库" c.git "G clone --bare --filter=blob:none file://\$PWD/server.git c.git 2>&1"
+compare "--filter 不支持" c "git -C server.git config uploadpack.allowfilter false && G clone --filter=blob:none file://\$PWD/server.git c 2>&1"
+compare "--filter 本地路径" c "G clone --filter=blob:none \$PWD/server.git c 2>&1"
+compare "空仓库" c "G clone file://\$PWD/empty.git c 2>&1"
+compare "不存在" c "G clone file://\$PWD/nope.git c"
+compare "scp 式 ssh" c "G clone host:\$PWD/server.git c 2>&1 && cat \"\$SSH_LOG\""
+compare "ssh:// 和端口" c "G clone ssh://me@host:2222\$PWD/server.git c 2>&1 && cat \"\$SSH_LOG\""
+compare "plink 端口" c "GIT_SSH_VARIANT=plink G clone ssh://host:2222\$PWD/server.git c 2>&1 && cat \"\$SSH_LOG\""
+compare "simple 不支持端口" c "GIT_SSH_VARIANT=simple G clone ssh://host:2222\$PWD/server.git c"
+
+# ========= fetch =========
+print_step "比较经由 git-upload-pack 的 fetch"
+compare "fetch" dst "cd dst && G fetch 2>&1"
+compare "已是最新" dst "cd dst && git fetch -q && G fetch 2>&1"
+compare "--tags" dst "cd dst && G fetch --tags 2>&1"
+compare "命令行分支" dst "cd dst && G fetch origin topic 2>&1"
+compare "URL" dst "cd dst && G fetch file://\$(dirname \$PWD)/server.git main 2>&1"
+compare "ssh" dst "cd dst && git remote set-url origin host:\$(dirname \$PWD)/server.git && G fetch 2>&1 && cat \"\$SSH_LOG\""
+compare "浅克隆" shallow "git clone -q --depth 1 -b dev file://\$PWD/server.git shallow && cd shallow && G fetch 2>&1"
+compare "浅克隆取新分支" shallow "git clone -q --depth 1 file://\$PWD/server.git shallow && cd shallow && G fetch origin topic:refs/remotes/origin/topic 2>&1"
+compare "仓库不存在" dst "cd dst && git remote set-url origin file:///nope.git && G fetch"
+
+# ========= 清理 =========
+cd ..
+rm -rf "$TEST_DIR"
+bold "\n✅ protocol 测试完成！"
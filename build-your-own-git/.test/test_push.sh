@@ -0,0 +1,161 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+bold() { echo -e "\033[1m$*\033[0m"; }
+info() { echo -e "\033[36m[INFO]\033[0m $*"; }
+ok() { echo -e "\033[32m[OK]\033[0m $*"; }
+fail() { echo -e "\033[31m[FAIL]\033[0m $*" >&2; exit 1; }
+print_step() { echo -e "\033[33m▶ $*\033[0m"; }
+
+PROGRAM="$1"
+TEST_DIR="test_push_$(date +%s)"
+
+mkdir -p "$TEST_DIR" && cd "$TEST_DIR"
+
+export GIT_AUTHOR_NAME="Levio-Z" GIT_AUTHOR_EMAIL="67247011+Levio-z@users.noreply.github.com"
+export GIT_COMMITTER_NAME="$GIT_AUTHOR_NAME" GIT_COMMITTER_EMAIL="$GIT_AUTHOR_EMAIL"
+# 固定时间，两边的提交哈希才相同
+export GIT_AUTHOR_DATE="2024-01-01T00:00:00Z" GIT_COMMITTER_DATE="2024-01-01T00:00:00Z"
+
+# 仓库 work：one(v1，附注标签) -> two(dev) -> three -> four(main)，light 是 three 上的轻量标签，
+# topic 在 three，附注标签 v2 在 four。裸仓库 remote.git 是 origin，
+# 推送过 main(three)、dev(two) 和 v1，之后 work 中 main 前进到 four，dev 被改写。
+# other 是在此之前从 remote.git 克隆的非裸仓库
+setup() {
+    rm -rf "$1" && mkdir "$1" && cd "$1"
+    git init -q --bare -b main remote.git
+    git init -q -b main work
+    (
+        cd work
+        echo a > a; git add a; git commit -q -m one; git tag -a v1 -m v1
+        echo b >> a; git commit -q -am two; git branch dev
+        echo c >> a; git commit -q -am three; git tag light
+        git remote add origin ../remote.git
+        git push -q origin main dev v1 2>/dev/null
+    )
+    git clone -q remote.git other
+    (
+        cd work
+        git branch topic
+        echo d >> a; git commit -q -am four; git tag -a v2 -m v2
+        git checkout -q dev; echo e >> a; git commit -q --amend -am rewritten; git checkout -q main
+    )
+    cd ..
+}
+
+# 两边的绝对路径不同，统一记为 ROOT
+normalize() {
+    sed "s#$PWD/git-repo#ROOT#g; s#$PWD/our-repo#ROOT#g" "$1"
+}
+
+# 远程的引用和 reflog、对象是否完整，以及 work 的配置、引用和远程跟踪分支的 reflog
+snapshot() {
+    (
+        cd "$1"
+        git -C remote.git for-each-ref
+        find remote.git/logs other/.git/logs -type f 2>/dev/null | sort
+        git -C remote.git fsck --connectivity-only --no-dangling 2>&1
+        git -C other for-each-ref
+        cd work
+        git config --local --list
+        git for-each-ref
+        for ref in $(git for-each-ref --format='%(refname)' refs/remotes); do
+            [ -f ".git/logs/$ref" ] && echo "== $ref" && cut -f2 ".git/logs/$ref"
+        done
+    ) | normalize /dev/stdin
+}
+
+# 用法：compare 名称 脚本，脚本在 work 中运行，用 G 代替 git 命令，最后一条命令的退出码参与比较。
+# 只比较标准输出，需要比较错误输出时在脚本中用 2>&1（出错时的 fatal 与 Error 前缀不同，不比较）
+compare() {
+    local name="$1" script="$2"
+    setup git-repo; setup our-repo
+    local git_status=0 our_status=0
+    (cd git-repo/work && G() { git "$@"; } && eval "$script") > git.out 2>/dev/null || git_status=$?
+    (cd our-repo/work && G() { "$PROGRAM" "$@"; } && eval "$script") > our.out 2>/dev/null || our_status=$?
+    if ! diff -u <(normalize git.out) <(normalize our.out) \
+        || (( (git_status == 0) != (our_status == 0) )); then
+        fail "✗ $name: $script 输出不一致（git 退出码 $git_status，我们 $our_status），请检查实现"
+    fi
+    if diff -u <(snapshot git-repo) <(snapshot our-repo); then
+        ok "✓ $name: $script 与官方git完全一致"
+    else
+        fail "✗ $name: $script 结果不一致，请检查实现"
+    fi
+}
+
+# 在 remote.git（或 work）中写入钩子：hook 仓库 名称 脚本
+hook() {
+    printf '#!/bin/sh\n%s\n' "$3" > "$1/hooks/$2"
+    chmod +x "$1/hooks/$2"
+}
+
+# ========= 推送 =========
+print_step "比较推送"
+compare "快进" "G push origin main 2>&1"
+compare "新分支" "G push origin topic 2>&1"
+compare "新标签" "G push origin v2 2>&1"
+compare "指定 dst" "G push origin topic:feature 2>&1"
+compare "HEAD" "G push origin HEAD 2>&1"
+compare "HEAD 到新分支" "G push origin HEAD:feature 2>&1"
+compare "表达式 src" "G push origin HEAD~1:refs/heads/old 2>&1"
+compare "dst 无法推断" "G push origin HEAD~1:old 2>&1"
+compare "src 不存在" "G push origin nope 2>&1"
+compare "通配" "G push origin 'refs/heads/*:refs/heads/mirror/*' 2>&1"
+compare "多个引用" "G push origin topic main v2 HEAD:refs/x/y 2>&1"
+compare "已是最新" "git push -q origin main 2>/dev/null && G push origin main 2>&1"
+compare "-q" "G push -q origin main topic 2>&1"
+compare "路径" "G push ../remote.git topic 2>&1"
+compare "-u" "G push -u origin topic 2>&1"
+compare "默认推送上游" "git config branch.main.remote origin && git config branch.main.merge refs/heads/main && G push 2>&1"
+compare "没有上游" "G push"
+compare "上游不同名" "git config branch.main.remote origin && git config branch.main.merge refs/heads/trunk && G push"
+
+# ========= 拒绝和强制 =========
+print_step "比较拒绝和强制"
+compare "非快进" "G push origin dev 2>&1"
+compare "当前分支非快进" "G push origin HEAD~2:main 2>&1"
+compare "部分被拒绝" "G push origin main dev 2>&1"
+compare "+ 强制" "G push origin +dev 2>&1"
+compare "--force" "G push --force origin dev main 2>&1"
+compare "远程有本地没有的提交" "(cd ../other && echo z > z && git add z && git commit -q -m z && git push -q origin HEAD:dev 2>/dev/null) && G push origin dev 2>&1"
+compare "标签已存在" "git tag -f v1 HEAD > /dev/null && G push origin v1 2>&1"
+compare "强制更新标签" "git tag -f v1 HEAD > /dev/null && G push -f origin v1 2>&1"
+compare "--force-with-lease" "G push --force-with-lease origin dev 2>&1"
+compare "--force-with-lease 过期" "git update-ref refs/remotes/origin/dev HEAD && G push --force-with-lease origin dev 2>&1"
+compare "--force-with-lease 指定值" "G push --force-with-lease=dev:HEAD~2 origin dev 2>&1"
+compare "--force-with-lease 指定值过期" "G push --force-with-lease=dev:HEAD origin dev 2>&1"
+compare "--force-with-lease 只保护指定引用" "G push --force-with-lease=main origin dev main 2>&1"
+compare "--atomic" "G push --atomic origin main dev"
+compare "--atomic 全部成功" "G push --atomic origin main topic 2>&1"
+
+# ========= 删除 =========
+print_step "比较删除"
+compare "删除" "G push origin :dev 2>&1"
+compare "--delete" "G push --delete origin dev v1 2>&1"
+compare "删除不存在" "G push origin :nope 2>&1"
+compare "--tags" "G push --tags origin 2>&1"
+compare "--tags 和分支" "G push --tags origin topic 2>&1"
+
+# ========= 钩子 =========
+print_step "比较钩子"
+compare "pre-push" "hook .git pre-push 'echo \"pre-push \$*\"; cat' && G push origin main :dev HEAD~1:refs/heads/old 2>&1"
+compare "pre-push 拒绝" "hook .git pre-push 'exit 1' && G push origin main 2>&1"
+compare "--no-verify" "hook .git pre-push 'exit 1' && G push --no-verify origin main 2>&1"
+compare "pre-receive" "hook ../remote.git pre-receive 'echo \"in \$(basename \$PWD)\"; cat; echo err >&2' && G push origin main topic 2>&1"
+compare "pre-receive 拒绝" "hook ../remote.git pre-receive 'echo no; exit 1' && G push origin main topic 2>&1"
+compare "update 拒绝一个" "hook ../remote.git update 'echo \"update \$*\"; [ \$1 != refs/heads/topic ]' && G push origin main topic v2 2>&1"
+compare "post-receive" "hook ../remote.git post-receive 'echo post; cat; echo' && hook ../remote.git post-update 'echo \"post-update \$*\"' && G push origin main :dev 2>&1"
+compare "--atomic 钩子拒绝" "hook ../remote.git update '[ \$1 != refs/heads/topic ]' && G push --atomic origin main topic 2>&1"
+
+# ========= 非裸仓库 =========
+print_step "比较推送到非裸仓库"
+compare "拒绝更新检出的分支" "G push ../other main 2>&1"
+compare "拒绝删除检出的分支" "G push ../other :main"
+compare "denyCurrentBranch=ignore" "git -C ../other config receive.denyCurrentBranch ignore && G push ../other main 2>&1"
+compare "其他分支" "G push ../other main:refs/heads/side 2>&1"
+
+# ========= 清理 =========
+cd ..
+rm -rf "$TEST_DIR"
+bold "\n✅ push 测试完成！"
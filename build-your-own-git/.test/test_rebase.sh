@@ -0,0 +1,160 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+bold() { echo -e "\033[1m$*\033[0m"; }
+info() { echo -e "\033[36m[INFO]\033[0m $*"; }
+ok() { echo -e "\033[32m[OK]\033[0m $*"; }
+fail() { echo -e "\033[31m[FAIL]\033[0m $*" >&2; exit 1; }
+print_step() { echo -e "\033[33m▶ $*\033[0m"; }
+
+PROGRAM="$1"
+TEST_DIR="test_rebase_$(date +%s)"
+
+mkdir -p "$TEST_DIR" && cd "$TEST_DIR"
+
+# 与我们写入的提交者一致，这样 git 的摘要里也不会多出 Author 行
+export GIT_AUTHOR_NAME="Levio-Z" GIT_AUTHOR_EMAIL="67247011+Levio-z@users.noreply.github.com"
+export GIT_COMMITTER_NAME="$GIT_AUTHOR_NAME" GIT_COMMITTER_EMAIL="$GIT_AUTHOR_EMAIL"
+# 默认不修改说明，需要编辑时在脚本中另外设置
+export GIT_EDITOR=true GIT_SEQUENCE_EDITOR=true
+
+# topic 上有三个提交，main 上有不冲突的修改，conflict 上的修改与 topic 的第一个提交冲突，
+# dup 上已经有与 "add g" 相同的修改，fixes 在 topic 后面加了 fixup!/squash!/amend! 提交
+setup() {
+    rm -rf "$1" && mkdir "$1" && cd "$1"
+    # 固定时间，两个仓库的提交哈希才相同
+    export GIT_AUTHOR_DATE="2024-01-01T00:00:00Z" GIT_COMMITTER_DATE="2024-01-01T00:00:00Z"
+    git init -q -b main
+    seq 1 10 > f; seq 1 5 > h; git add -A; git commit -q -m base
+    git checkout -q -b topic
+    sed -i 's/^2$/two/' f; git commit -q -am "change two"
+    echo new > g; git add g; git commit -q -m "add g"
+    sed -i 's/^9$/nine/' f; git commit -q -am "change nine" -m "with a body"
+    git checkout -q -b fixes
+    echo fix >> g; git commit -q -am "fixup! add g"
+    sed -i 's/^5$/five/' f; git commit -q -am "squash! change two" -m "squash body"
+    sed -i 's/^6$/six/' f; git commit -q -am "amend! change nine" -m "change nine, amended"
+    git checkout -q -b conflict main
+    sed -i 's/^2$/TWO/' f; git commit -q -am "main two"
+    git checkout -q main
+    sed -i 's/^4$/FOUR/' h; git commit -q -am "main four"
+    git checkout -q -b dup
+    echo new > g; git add g; git commit -q -m "add g upstream"
+    git checkout -q main
+    cd ..
+}
+
+# 重放生成的提交哈希（含我们的提交时间）不同，只比较摘要中的其他部分
+normalize() {
+    sed -E 's/\[([^]]*) [0-9a-f]{7}\]/[\1 HASH]/' "$1"
+}
+
+snapshot() {
+    (
+        cd "$1"
+        git ls-files -s
+        git status --porcelain -uall
+        git symbolic-ref -q HEAD || echo "detached"
+        git log -1 --format=%T
+        git log -5 --format='%s|%b|%an <%ae>|%ad'
+        git for-each-ref --format='%(refname) %(tree) %(subject)' refs/heads
+        find . -path ./.git -prune -o -type f -print | sort | while read -r f; do
+            echo "== $f"; cat "$f"
+        done
+        for state in MERGE_MSG REBASE_HEAD rebase-merge/git-rebase-todo rebase-merge/done \
+            rebase-merge/msgnum rebase-merge/end rebase-merge/head-name rebase-merge/onto \
+            rebase-merge/orig-head rebase-merge/message rebase-merge/author-script \
+            rebase-merge/stopped-sha rebase-merge/patch; do
+            [ -e ".git/$state" ] && { echo "== $state"; cat ".git/$state"; }
+        done
+        true
+    )
+}
+
+# 用法：compare 名称 起始分支 脚本，脚本中用 G 代替 git 命令，最后一条命令的退出码参与比较。
+# 只比较标准输出，需要比较进度和提示时在脚本中用 2>&1
+compare() {
+    local name="$1" branch="$2" script="$3"
+    setup git-repo; setup our-repo
+    git -C git-repo checkout -q "$branch"; git -C our-repo checkout -q "$branch"
+    local git_status=0 our_status=0
+    (cd git-repo && G() { git "$@"; } && eval "$script") > git.out 2>/dev/null || git_status=$?
+    (cd our-repo && G() { "$PROGRAM" "$@"; } && eval "$script") > our.out 2>/dev/null || our_status=$?
+    if ! diff -u <(normalize git.out) <(normalize our.out) \
+        || (( (git_status == 0) != (our_status == 0) )); then
+        fail "✗ $name: $script 输出不一致（git 退出码 $git_status，我们 $our_status），请检查实现"
+    fi
+    if diff -u <(snapshot git-repo) <(snapshot our-repo); then
+        ok "✓ $name: $script 与官方git完全一致"
+    else
+        fail "✗ $name: $script 结果不一致，请检查实现"
+    fi
+}
+
+RESOLVE='printf "1\nboth\n3\n4\n5\n6\n7\n8\n9\n10\n" > f && git add f'
+
+# ========= 重放 =========
+print_step "比较 rebase"
+compare "重放" topic "G rebase main 2>&1"
+compare "已是最新" topic "G rebase main >/dev/null 2>&1; G rebase main"
+compare "分离头指针已是最新" topic "git checkout -q topic~1 && G rebase topic~2"
+compare "快进" topic "git checkout -q main~1 && G rebase topic 2>&1"
+compare "--onto" topic "G rebase --onto main topic~2 2>&1"
+compare "指定分支" main "G rebase main topic 2>&1"
+compare "指定提交" main "G rebase main topic~1 2>&1"
+compare "跳过已应用的提交" topic "G rebase dup 2>&1"
+
+# ========= 冲突 =========
+print_step "比较冲突和 --continue、--skip、--abort"
+compare "冲突" topic "G rebase conflict 2>&1; G status"
+compare "继续" topic "G rebase conflict; $RESOLVE && G rebase --continue 2>&1; G status"
+compare "未解决时继续" topic "G rebase conflict; G rebase --continue"
+compare "跳过" topic "G rebase conflict; G rebase --skip 2>&1"
+compare "放弃" topic "G rebase conflict; G rebase --abort; G status"
+compare "分离头指针时放弃" topic "git checkout -q topic~0 && G rebase conflict; G rebase --abort; G status"
+compare "进行中再次开始" topic "G rebase conflict; G rebase main"
+compare "没有进行中的变基" topic "G rebase --continue"
+
+# ========= 交互式变基 =========
+print_step "比较 rebase -i"
+compare "reword 和 drop" topic \
+    "GIT_SEQUENCE_EDITOR=\"sed -i '2s/^pick/reword/;3s/^pick/drop/'\" GIT_EDITOR=\"sed -i 1s/.*/renamed/\" G rebase -i main"
+compare "squash 和 fixup" topic \
+    "GIT_SEQUENCE_EDITOR=\"sed -i '2s/^pick/squash/;3s/^pick/fixup/'\" G rebase -i main"
+compare "edit" topic \
+    "GIT_SEQUENCE_EDITOR=\"sed -i '2s/^pick/edit/'\" G rebase -i main 2>&1; G status"
+compare "edit 后继续" topic \
+    "GIT_SEQUENCE_EDITOR=\"sed -i '2s/^pick/edit/'\" G rebase -i main; echo more >> g && git add g && G rebase --continue"
+compare "exec 失败" topic \
+    "GIT_SEQUENCE_EDITOR=\"sed -i '1a exec echo hi; false'\" G rebase -i main 2>&1; G status"
+compare "break" topic \
+    "GIT_SEQUENCE_EDITOR=\"sed -i '2a break'\" G rebase -i main; G status; G rebase --continue 2>&1"
+compare "编辑待办列表" topic \
+    "GIT_SEQUENCE_EDITOR=\"sed -i '1i break'\" G rebase -i main; GIT_SEQUENCE_EDITOR=\"sed -i '1d'\" G rebase --edit-todo; G rebase --continue"
+compare "删掉所有指令" topic "GIT_SEQUENCE_EDITOR=\"sed -i '/^pick/d'\" G rebase -i main"
+compare "squash 冲突" conflict \
+    "git checkout -q topic && GIT_SEQUENCE_EDITOR=\"sed -i '1{h;d};2{G;s/\npick/\nsquash/}'\" G rebase -i conflict; G status; $RESOLVE && G rebase --continue"
+compare "reword 冲突" topic \
+    "GIT_SEQUENCE_EDITOR=\"sed -i '1s/^pick/reword/'\" G rebase -i conflict; $RESOLVE && GIT_EDITOR=\"sed -i 1s/.*/renamed/\" G rebase --continue"
+compare "跳过 fixup 冲突" topic \
+    "GIT_SEQUENCE_EDITOR=\"sed -i '1{h;d};2{G;s/\npick/\nfixup/}'\" G rebase -i conflict; G rebase --skip"
+compare "--autosquash" fixes "G rebase -i --autosquash main 2>&1"
+compare "非交互时忽略 --autosquash" fixes "G rebase --autosquash main 2>&1"
+
+# ========= --update-refs =========
+print_step "比较 --update-refs"
+compare "更新其他分支" fixes "G rebase --update-refs main 2>&1"
+compare "交互式更新其他分支" fixes "git branch mid topic~1 && G rebase -i --update-refs main 2>&1"
+
+# ========= 错误 =========
+print_step "比较错误情况"
+compare "未暂存的修改" topic "echo dirty >> f && G rebase main"
+compare "暂存区有修改" topic "echo dirty >> f && git add f && G rebase main"
+compare "无效的 upstream" topic "G rebase nope"
+compare "无效的 --onto" topic "G rebase --onto nope main"
+compare "不存在的分支" topic "G rebase main nope"
+
+# ========= 清理 =========
+cd ..
+rm -rf "$TEST_DIR"
+bold "\n✅ rebase 测试完成！"
@@ -0,0 +1,95 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+bold() { echo -e "\033[1m$*\033[0m"; }
+info() { echo -e "\033[36m[INFO]\033[0m $*"; }
+ok() { echo -e "\033[32m[OK]\033[0m $*"; }
+fail() { echo -e "\033[31m[FAIL]\033[0m $*" >&2; exit 1; }
+print_step() { echo -e "\033[33m▶ $*\033[0m"; }
+
+PROGRAM="$1"
+TEST_DIR="test_rename_$(date +%s)"
+OUR_OUTPUT=$(mktemp)
+GIT_OUTPUT=$(mktemp)
+
+mkdir -p "$TEST_DIR" && cd "$TEST_DIR"
+
+# 提交者不依赖本机 git 的用户配置
+export GIT_AUTHOR_NAME="Levio-Z" GIT_AUTHOR_EMAIL="67247011+Levio-z@users.noreply.github.com"
+export GIT_COMMITTER_NAME="$GIT_AUTHOR_NAME" GIT_COMMITTER_EMAIL="$GIT_AUTHOR_EMAIL"
+
+compare() {
+    git "$@" > "$GIT_OUTPUT"
+    "$PROGRAM" "$@" > "$OUR_OUTPUT"
+    if diff -u "$GIT_OUTPUT" "$OUR_OUTPUT"; then
+        ok "✓ $* 与官方git完全一致"
+    else
+        fail "✗ $* 输出不一致，请检查实现"
+    fi
+}
+
+# ========= 用官方git构造包含移动和复制的提交 =========
+print_step "使用官方git构造包含重命名、复制和修改的提交"
+git init -q -b main
+mkdir -p src/util docs
+seq 1 40 > src/util/numbers.txt
+seq 100 160 > src/main.txt
+printf 'same\ncontent\n' > docs/readme
+printf 'same\ncontent\n' > docs/copy
+printf 'small\n' > tiny
+git add -A && git commit -q -m one
+
+git mv src/util/numbers.txt src/numbers.txt           # 完全相同的重命名
+sed -i 's/^120$/changed/' src/main.txt
+git mv src/main.txt lib.txt                           # 相似的重命名
+cp src/numbers.txt src/numbers-copy.txt               # 复制自未修改的文件
+sed -i 's/^5$/five/' src/numbers-copy.txt
+git mv docs/copy docs/moved                           # 多个相同内容中的一个
+printf 'other\n' > tiny
+git add -A && git commit -q -m two
+
+git mv lib.txt lib2.txt && echo tail >> lib2.txt
+git add -A && git commit -q -m three
+
+# ========= 比较结果 =========
+print_step "比较 diff-tree 的重命名和复制检测"
+compare diff-tree -r HEAD~1 HEAD
+compare diff-tree -r -M HEAD~1 HEAD
+compare diff-tree -r -M90% HEAD~1 HEAD
+compare diff-tree -r -M100% HEAD~1 HEAD
+compare diff-tree -r --name-status -C HEAD~1 HEAD
+compare diff-tree -r --name-status -C -C HEAD~1 HEAD
+compare diff-tree -r --name-status --find-copies-harder HEAD~1 HEAD
+
+print_step "比较 diff 和 --stat"
+compare diff HEAD~2 HEAD~1
+compare diff --no-renames HEAD~2 HEAD~1
+compare diff --find-copies-harder HEAD~2 HEAD~1
+compare diff --stat HEAD~2 HEAD~1
+compare diff --stat -C -C HEAD~2 HEAD~1
+COLUMNS=40 compare diff --stat HEAD~2 HEAD
+
+print_step "比较 log --stat"
+compare log --stat
+compare log --stat --oneline --graph
+compare log --stat --no-renames
+
+print_step "比较 status"
+git mv docs/readme docs/README
+echo more >> docs/README
+mkdir -p build new/dir
+echo ignored > build/out.o
+echo untracked > new/dir/file
+echo 'build/' > .gitignore
+compare status
+compare status -s
+compare status -s -b --untracked-files=all
+compare status --no-renames --porcelain
+git add -A
+compare status
+compare status -s -uno
+
+# ========= 清理 =========
+cd ..
+rm -rf "$TEST_DIR" "$OUR_OUTPUT" "$GIT_OUTPUT"
+bold "\n✅ 重命名与复制检测测试完成！"
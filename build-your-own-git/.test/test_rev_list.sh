@@ -0,0 +1,76 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+bold() { echo -e "\033[1m$*\033[0m"; }
+info() { echo -e "\033[36m[INFO]\033[0m $*"; }
+ok() { echo -e "\033[32m[OK]\033[0m $*"; }
+fail() { echo -e "\033[31m[FAIL]\033[0m $*" >&2; exit 1; }
+print_step() { echo -e "\033[33m▶ $*\033[0m"; }
+
+PROGRAM="$1"
+TEST_DIR="test_rev_list_$(date +%s)"
+OUR_OUTPUT=$(mktemp)
+GIT_OUTPUT=$(mktemp)
+
+mkdir -p "$TEST_DIR" && cd "$TEST_DIR"
+
+# 提交者不依赖本机 git 的用户配置
+export GIT_AUTHOR_NAME="Levio-Z" GIT_AUTHOR_EMAIL="67247011+Levio-z@users.noreply.github.com"
+export GIT_COMMITTER_NAME="$GIT_AUTHOR_NAME" GIT_COMMITTER_EMAIL="$GIT_AUTHOR_EMAIL"
+
+# ========= 用官方git构造带目录和分支的历史 =========
+print_step "使用官方git构造包含子目录、分支和标签的历史"
+git init -q -b main
+TIME=1700000000
+commit() {
+    TIME=$((TIME + 60))
+    git add -A
+    GIT_AUTHOR_DATE="$TIME +0800" GIT_COMMITTER_DATE="$TIME +0800" \
+        git commit -q -m "$1"
+}
+mkdir -p a/b d
+echo root > root; echo w > a/b/w; echo "a longer file" > a/c; echo d > d/e.txt
+commit A
+echo m > m.txt; commit B
+git checkout -q -b side
+echo side > side.txt; echo w2 > a/b/w; commit S1
+git checkout -q main
+echo m2 > m.txt; commit C
+git tag -a -m "annotated" v1 HEAD~1
+git tag light side
+
+# ========= 比较结果 =========
+for OPTS in "--all" "main..side" "--objects main..side" "--objects --all" \
+    "--left-right side...main" "--count --left-right side...main" \
+    "--objects --filter=blob:none --all" "--objects --filter=blob:limit=5 --all" \
+    "--objects --filter=tree:1 --all" "--branches" "--tags" "-n 2 --all"; do
+    print_step "比较 rev-list $OPTS 的输出"
+    git rev-list $OPTS > "$GIT_OUTPUT"
+    "$PROGRAM" rev-list $OPTS > "$OUR_OUTPUT"
+    if diff -u "$GIT_OUTPUT" "$OUR_OUTPUT"; then
+        ok "✓ rev-list $OPTS 与官方git完全一致"
+    else
+        fail "✗ rev-list $OPTS 输出不一致，请检查实现"
+    fi
+done
+
+# 被省略对象的输出顺序不固定，排序后比较
+print_step "比较 rev-list --filter-print-omitted 的输出"
+git rev-list --objects --filter=tree:1 --filter-print-omitted --all | sort > "$GIT_OUTPUT"
+"$PROGRAM" rev-list --objects --filter=tree:1 --filter-print-omitted --all | sort > "$OUR_OUTPUT"
+diff -u "$GIT_OUTPUT" "$OUR_OUTPUT" || fail "✗ --filter-print-omitted 输出不一致"
+ok "✓ --filter-print-omitted 与官方git一致"
+
+# 删除一个 blob 后 --missing=print 应该报告它
+print_step "比较 rev-list --missing=print 的输出"
+BLOB=$(git rev-parse main:root)
+rm -f ".git/objects/${BLOB:0:2}/${BLOB:2}"
+git rev-list --objects --missing=print --all > "$GIT_OUTPUT"
+"$PROGRAM" rev-list --objects --missing=print --all > "$OUR_OUTPUT"
+diff -u "$GIT_OUTPUT" "$OUR_OUTPUT" || fail "✗ --missing=print 输出不一致"
+ok "✓ --missing=print 与官方git一致"
+
+# ========= 清理 =========
+cd ..
+rm -rf "$TEST_DIR" "$OUR_OUTPUT" "$GIT_OUTPUT"
+bold "\n✅ rev-list 测试完成！"
@@ -0,0 +1,281 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+bold() { echo -e "\033[1m$*\033[0m"; }
+info() { echo -e "\033[36m[INFO]\033[0m $*"; }
+ok() { echo -e "\033[32m[OK]\033[0m $*"; }
+fail() { echo -e "\033[31m[FAIL]\033[0m $*" >&2; exit 1; }
+print_step() { echo -e "\033[33m▶ $*\033[0m"; }
+
+PROGRAM="$1"
+TEST_DIR="test_serve_$(date +%s)"
+
+mkdir -p "$TEST_DIR" && cd "$TEST_DIR"
+
+export GIT_AUTHOR_NAME="Levio-Z" GIT_AUTHOR_EMAIL="67247011+Levio-z@users.noreply.github.com"
+export GIT_COMMITTER_NAME="$GIT_AUTHOR_NAME" GIT_COMMITTER_EMAIL="$GIT_AUTHOR_EMAIL"
+# 固定时间，两边的提交哈希才相同
+export GIT_AUTHOR_DATE="2024-01-01T00:00:00Z" GIT_COMMITTER_DATE="2024-01-01T00:00:00Z"
+
+# 参照用的 HTTP 服务器：把请求按 CGI 交给 git http-backend，仓库根目录是 $1。
+# EXPORT_ALL=1 时提供所有仓库并允许推送，否则只提供有 git-daemon-export-ok 的仓库、不允许推送
+cat > server.py <<'EOF'
+import http.server, os, socketserver, subprocess, sys
+
+ROOT, PORT_FILE = sys.argv[1], sys.argv[2]
+EXPORT_ALL = os.environ.get("EXPORT_ALL") == "1"
+
+class Handler(http.server.BaseHTTPRequestHandler):
+    protocol_version = "HTTP/1.1"
+
+    def log_message(self, format, *args):
+        pass
+
+    def body(self):
+        if self.headers.get("Transfer-Encoding", "").lower() == "chunked":
+            data = b""
+            while True:
+                size = int(self.rfile.readline().strip(), 16)
+                if size == 0:
+                    self.rfile.readline()
+                    return data
+                data += self.rfile.read(size)
+                self.rfile.readline()
+        return self.rfile.read(int(self.headers.get("Content-Length", 0)))
+
+    def handle_request(self):
+        body = self.body()
+        path, _, query = self.path.partition("?")
+        env = dict(os.environ, GIT_PROJECT_ROOT=ROOT, REQUEST_METHOD=self.command,
+                   PATH_INFO=path, QUERY_STRING=query,
+                   CONTENT_TYPE=self.headers.get("Content-Type", ""),
+                   CONTENT_LENGTH=str(len(body)), REMOTE_ADDR="127.0.0.1")
+        if EXPORT_ALL:
+            env.update(GIT_HTTP_EXPORT_ALL="1", GIT_CONFIG_COUNT="1",
+                       GIT_CONFIG_KEY_0="http.receivepack", GIT_CONFIG_VALUE_0="true")
+        for name in ("Git-Protocol", "Content-Encoding"):
+            if name in self.headers:
+                env["HTTP_" + name.upper().replace("-", "_")] = self.headers[name]
+        output = subprocess.run(["git", "http-backend"], input=body, env=env,
+                                capture_output=True).stdout
+        head, _, data = output.partition(b"\r\n\r\n")
+        status, headers = 200, []
+        for line in head.decode().split("\r\n"):
+            name, _, value = line.partition(": ")
+            if name.lower() == "status":
+                status = int(value.split()[0])
+            elif name:
+                headers.append((name, value))
+        self.send_response(status)
+        for name, value in headers:
+            self.send_header(name, value)
+        self.send_header("Content-Length", str(len(data)))
+        self.end_headers()
+        self.wfile.write(data)
+
+    do_GET = do_POST = handle_request
+
+server = socketserver.ThreadingTCPServer(("127.0.0.1", 0), Handler)
+with open(PORT_FILE, "w") as f:
+    f.write(str(server.server_address[1]))
+server.serve_forever()
+EOF
+
+SERVERS=()
+trap 'kill "${SERVERS[@]}" 2>/dev/null || true' EXIT
+
+# 等文件 $1 中出现 $2 行
+wait_for() {
+    for _ in $(seq 50); do [ "$(wc -l < "$1" 2>/dev/null || echo 0)" -ge "$2" ] && return; sleep 0.1; done
+    fail "服务器没有启动：$(cat "$1" 2>/dev/null)"
+}
+
+free_port() {
+    python3 -c 'import socket; s = socket.socket(); s.bind(("127.0.0.1", 0)); print(s.getsockname()[1])'
+}
+
+# 启动参照用的 HTTP 服务器和 git daemon，$1 为 all（提供所有仓库、允许推送）或 strict。
+# 端口写入 $1.http 和 $1.git
+start_git() {
+    local port
+    mkdir -p git-repo
+    if [ "$1" = all ]; then
+        EXPORT_ALL=1 python3 server.py "$PWD/git-repo" port &
+    else
+        python3 server.py "$PWD/git-repo" port &
+    fi
+    SERVERS+=("$!")
+    for _ in $(seq 50); do [ -s port ] && break; sleep 0.1; done
+    mv port "git-$1.http"
+    port=$(free_port)
+    local args=(--base-path="$PWD/git-repo" --listen=127.0.0.1 --port="$port" --reuseaddr)
+    [ "$1" = all ] && args+=(--export-all --enable=receive-pack)
+    git daemon "${args[@]}" &
+    SERVERS+=("$!")
+    echo "$port" > "git-$1.git"
+}
+
+# 启动我们的 serve，$1 同上，之后是 serve 的其他参数
+start_ours() {
+    local mode="$1"; shift
+    mkdir -p our-repo
+    local args=(--http-port 0 --git-port 0)
+    if [ "$mode" = strict ]; then args+=(--read-only); else args+=(--export-all); fi
+    "$PROGRAM" serve "${args[@]}" "$@" "$PWD/our-repo" > "our-$mode.out" 2> "our-$mode.err" &
+    SERVERS+=("$!")
+    wait_for "our-$mode.out" 2
+    sed -n 's#Serving http://127.0.0.1:\([0-9]*\)/#\1#p' "our-$mode.out" > "our-$mode.http"
+    sed -n 's#Serving git://127.0.0.1:\([0-9]*\)/#\1#p' "our-$mode.out" > "our-$mode.git"
+}
+
+start_git all; start_git strict
+start_ours all; start_ours strict
+# git daemon 启动需要一点时间
+for _ in $(seq 50); do git ls-remote "git://127.0.0.1:$(cat git-all.git)/nope" 2>&1 | grep -q "not exported" && break; sleep 0.1; done
+
+# 服务端 server.git（裸仓库，允许 filter）：one(v1，附注标签) -> two(dev) -> three(main)。
+# dst 是之前从 server.git 克隆的，之后服务端多了 four(main，附注标签 v2) 和新分支 topic。
+# work 是有工作区的仓库，检出了 main
+setup() {
+    rm -rf "$1" && mkdir "$1" && cd "$1"
+    git init -q -b main src
+    (
+        cd src
+        echo a > a; mkdir d; echo c > d/c; git add .; git commit -q -m one; git tag -a v1 -m v1
+        echo b >> a; git commit -q -am two; git branch dev
+        echo c >> a; git commit -q -am three
+    )
+    git clone -q --bare src server.git
+    git -C server.git config uploadpack.allowfilter true
+    git clone -q "file://$PWD/server.git" dst
+    (
+        cd src
+        echo d >> a; git commit -q -am four; git tag -a v2 -m v2; git branch topic HEAD~1
+        git push -q ../server.git main topic v2
+    )
+    git clone -q src work
+    cd ..
+}
+
+# 两边的绝对路径、URL 中的仓库目录、端口和服务端程序不同，统一记为 ROOT、HOST 和 git。
+# 客户端提前断开时服务端报错的前缀不同（fatal 与 Error），去掉这一行
+normalize() {
+    sed "/the remote end hung up unexpectedly/d; s#$PWD/git-repo#ROOT#g; s#$PWD/our-repo#ROOT#g; s#/git-repo/#/ROOT/#g; s#/our-repo/#/ROOT/#g; s#127\.0\.0\.1:[0-9]*#HOST#g; s#$PROGRAM #git #g" "$1"
+}
+
+# 仓库 $2 的配置、引用、HEAD、浅克隆边界、所有对象（缺少的以 ? 开头）和检出的文件
+snapshot() {
+    (
+        cd "$1/$2" 2>/dev/null || { echo "没有 $2"; exit 0; }
+        git config --local --list
+        git for-each-ref
+        git rev-parse --symbolic-full-name HEAD || true
+        cat shallow .git/shallow 2>/dev/null || true
+        git rev-list --objects --all --missing=print | sort
+        if [ "$(git rev-parse --is-bare-repository)" = false ]; then
+            git status --porcelain
+            git ls-files -s
+        fi
+        git fsck --connectivity-only 2>&1 | grep -v "^notice\|^dangling" || true
+    ) 2>&1 | normalize /dev/stdin
+}
+
+# 用法：compare 名称 目录 脚本。客户端总是 git，脚本中 $HTTP 和 $GIT 是提供所有仓库的
+# 服务器的 http:// 和 git:// URL，$STRICT_HTTP 和 $STRICT_GIT 是只读、要求导出标记的服务器的。
+# 最后一条命令的退出码参与比较，之后比较“目录”中的仓库
+compare() {
+    local name="$1" dir="$2" script="$3"
+    setup git-repo; setup our-repo
+    local git_status=0 our_status=0
+    (cd git-repo && HTTP="http://127.0.0.1:$(cat ../git-all.http)" \
+        && GIT="git://127.0.0.1:$(cat ../git-all.git)" \
+        && STRICT_HTTP="http://127.0.0.1:$(cat ../git-strict.http)" \
+        && STRICT_GIT="git://127.0.0.1:$(cat ../git-strict.git)" \
+        && eval "$script") > git.out 2>/dev/null || git_status=$?
+    (cd our-repo && HTTP="http://127.0.0.1:$(cat ../our-all.http)" \
+        && GIT="git://127.0.0.1:$(cat ../our-all.git)" \
+        && STRICT_HTTP="http://127.0.0.1:$(cat ../our-strict.http)" \
+        && STRICT_GIT="git://127.0.0.1:$(cat ../our-strict.git)" \
+        && eval "$script") > our.out 2>/dev/null || our_status=$?
+    if ! diff -u <(normalize git.out) <(normalize our.out) \
+        || (( (git_status == 0) != (our_status == 0) )); then
+        fail "✗ $name: $script 输出不一致（git 退出码 $git_status，我们 $our_status），请检查实现"
+    fi
+    if diff -u <(snapshot git-repo "$dir") <(snapshot our-repo "$dir"); then
+        ok "✓ $name: $script 与官方git完全一致"
+    else
+        fail "✗ $name: $script 结果不一致，请检查实现"
+    fi
+}
+
+# ========= 智能 HTTP =========
+print_step "比较 serve 的智能 HTTP"
+compare "ls-remote" dst "git ls-remote \$HTTP/server.git"
+compare "ls-remote v0" dst "git -c protocol.version=0 ls-remote \$HTTP/server.git"
+compare "省略 .git" dst "git ls-remote --symref \$HTTP/server"
+compare "名字带点时补上 .git" dst "mv server.git my.server.git && git ls-remote \$HTTP/my.server && git ls-remote \$GIT/my.server"
+compare "不存在" dst "git ls-remote \$HTTP/nope.git"
+compare "clone" c "git clone \$HTTP/server.git c 2>&1"
+compare "clone v0" c "git -c protocol.version=0 clone \$HTTP/server.git c 2>&1"
+compare "--depth" c "git clone --depth 1 \$HTTP/server.git c 2>&1"
+compare "--filter" c "git clone --filter=blob:none \$HTTP/server.git c 2>&1"
+compare "非裸仓库" c "git clone \$HTTP/work c 2>&1"
+compare "fetch" dst "cd dst && git remote set-url origin \$HTTP/server.git && for i in \$(seq 40); do git commit -q --allow-empty -m local\$i; done && git fetch 2>&1"
+push_setup="git -C server.git update-ref refs/heads/main HEAD~1 && git -C server.git update-ref -d refs/heads/topic && cd dst && echo e >> a && git commit -q -am five"
+compare "push" server.git "$push_setup && git push \$HTTP/server.git main :dev 2>&1"
+compare "大的推送" server.git "$push_setup && for i in \$(seq 300); do echo \$i > f\$i; done && git add . && git commit -q -m big && git push \$HTTP/server.git main 2>&1"
+
+# ========= git:// =========
+print_step "比较 serve 的 git:// 协议"
+compare "ls-remote" dst "git ls-remote \$GIT/server.git"
+compare "ls-remote v0" dst "git -c protocol.version=0 ls-remote \$GIT/server"
+compare "不存在" dst "git ls-remote \$GIT/nope.git"
+compare "跳出根目录" dst "git ls-remote \$GIT/../git-repo/server.git"
+compare "clone" c "git clone \$GIT/server.git c 2>&1"
+compare "clone v0" c "git -c protocol.version=0 clone --depth 2 \$GIT/server.git c 2>&1"
+compare "fetch" dst "cd dst && git remote set-url origin \$GIT/server.git && git fetch 2>&1"
+compare "push" server.git "$push_setup && git push \$GIT/server.git main main:feature 2>&1"
+
+# ========= 导出标记和只读 =========
+print_step "比较导出标记和只读"
+compare "没有导出" dst "git ls-remote \$STRICT_HTTP/server.git; git ls-remote \$STRICT_GIT/server.git"
+compare "导出" c "touch server.git/git-daemon-export-ok && git ls-remote \$STRICT_GIT/server.git && git clone \$STRICT_HTTP/server.git c 2>&1"
+compare "只读 HTTP" server.git "touch server.git/git-daemon-export-ok && $push_setup && git push \$STRICT_HTTP/server.git main 2>&1"
+compare "只读 git://" server.git "touch server.git/git-daemon-export-ok && $push_setup && git push \$STRICT_GIT/server.git main 2>&1"
+
+# ========= 连接数上限 =========
+print_step "检查连接数上限"
+setup our-repo
+start_ours limited --max-connections 1
+LIMITED_HTTP="http://127.0.0.1:$(cat our-limited.http)/server.git"
+LIMITED_GIT="git://127.0.0.1:$(cat our-limited.git)/server.git"
+git ls-remote "$LIMITED_HTTP" > /dev/null || fail "✗ 连接数上限: 没有占满时应该可以访问"
+# 占住唯一的连接
+python3 -c 'import socket, sys, time; s = socket.create_connection(("127.0.0.1", int(sys.argv[1]))); time.sleep(3)' "$(cat our-limited.git)" &
+HOLDER=$!
+sleep 0.5
+output=$(git ls-remote "$LIMITED_HTTP" 2>&1 || true)
+echo "$output" | grep -q "returned error: 503" || fail "✗ 连接数上限: HTTP 应该返回 503，实际：$output"
+output=$(git ls-remote "$LIMITED_GIT" 2>&1 || true)
+echo "$output" | grep -q "remote error: too many connections" || fail "✗ 连接数上限: git:// 应该拒绝，实际：$output"
+wait "$HOLDER"
+git ls-remote "$LIMITED_HTTP" > /dev/null || fail "✗ 连接数上限: 连接释放后应该可以访问"
+ok "✓ 连接数上限: 超过上限的 HTTP 和 git:// 连接被拒绝，释放后恢复"
+
+# ========= 请求体大小上限 =========
+print_step "检查请求体大小上限"
+setup our-repo
+start_ours small --max-request-size 1000
+SMALL_HTTP="http://127.0.0.1:$(cat our-small.http)/server.git"
+git ls-remote "$SMALL_HTTP" > /dev/null || fail "✗ 请求体上限: 没有请求体的请求应该可以访问"
+code=$(curl -s -o /dev/null -w '%{http_code}' -X POST -H 'Content-Length: 999999999999' "$SMALL_HTTP/git-upload-pack" || true)
+[ "$code" = 413 ] || fail "✗ 请求体上限: 过大的 Content-Length 应该返回 413，实际：$code"
+code=$(head -c 5000 /dev/zero | curl -s -o /dev/null -w '%{http_code}' -H 'Transfer-Encoding: chunked' --data-binary @- "$SMALL_HTTP/git-upload-pack" || true)
+[ "$code" = 413 ] || fail "✗ 请求体上限: 过大的 chunked 请求体应该返回 413，实际：$code"
+git ls-remote "$SMALL_HTTP" > /dev/null || fail "✗ 请求体上限: 拒绝之后服务应该仍然可用"
+ok "✓ 请求体上限: 过大的请求体返回 413，服务不受影响"
+
+# ========= 清理 =========
+cd ..
+rm -rf "$TEST_DIR"
+bold "\n✅ serve 测试完成！"
@@ -0,0 +1,255 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+bold() { echo -e "\033[1m$*\033[0m"; }
+info() { echo -e "\033[36m[INFO]\033[0m $*"; }
+ok() { echo -e "\033[32m[OK]\033[0m $*"; }
+fail() { echo -e "\033[31m[FAIL]\033[0m $*" >&2; exit 1; }
+print_step() { echo -e "\033[33m▶ $*\033[0m"; }
+
+PROGRAM="$1"
+TEST_DIR="test_serve_pack_$(date +%s)"
+
+mkdir -p "$TEST_DIR" && cd "$TEST_DIR"
+
+export GIT_AUTHOR_NAME="Levio-Z" GIT_AUTHOR_EMAIL="67247011+Levio-z@users.noreply.github.com"
+export GIT_COMMITTER_NAME="$GIT_AUTHOR_NAME" GIT_COMMITTER_EMAIL="$GIT_AUTHOR_EMAIL"
+# 固定时间，两边的提交哈希才相同
+export GIT_AUTHOR_DATE="2024-01-01T00:00:00Z" GIT_COMMITTER_DATE="2024-01-01T00:00:00Z"
+
+export OUR_PROGRAM="$PROGRAM"
+
+# 代替 http-backend 的 HTTP 服务器：直接用 `--stateless-rpc` 运行服务端，
+# /git-repo/ 下的仓库用 git 的，/our-repo/ 下的用我们的
+cat > server.py <<'EOF'
+import http.server, os, socketserver, subprocess, sys
+
+ROOT, PORT_FILE = sys.argv[1], sys.argv[2]
+
+class Handler(http.server.BaseHTTPRequestHandler):
+    protocol_version = "HTTP/1.1"
+
+    def log_message(self, format, *args):
+        pass
+
+    def handle_request(self):
+        body = self.rfile.read(int(self.headers.get("Content-Length", 0)))
+        path, _, query = self.path.partition("?")
+        program = "git" if path.startswith("/git-repo/") else os.environ["OUR_PROGRAM"]
+        env = dict(os.environ)
+        if "Git-Protocol" in self.headers:
+            env["GIT_PROTOCOL"] = self.headers["Git-Protocol"]
+        if path.endswith("/info/refs"):
+            service = query.partition("service=")[2]
+            repo = path[:-len("/info/refs")]
+            args = ["--stateless-rpc", "--advertise-refs"]
+            content_type = "application/x-%s-advertisement" % service
+        else:
+            repo, _, service = path.rpartition("/")
+            args = ["--stateless-rpc"]
+            content_type = "application/x-%s-result" % service
+        command = [program, service[len("git-"):]] + args + [ROOT + repo]
+        data = subprocess.run(command, input=body, env=env, capture_output=True).stdout
+        # 与 http-backend 一样，协议 v0 的通告前面加上服务名
+        if args[-1] == "--advertise-refs" and "version=2" not in env.get("GIT_PROTOCOL", ""):
+            line = "# service=%s\n" % service
+            data = b"%04x%s0000" % (len(line) + 4, line.encode()) + data
+        self.send_response(200)
+        self.send_header("Content-Type", content_type)
+        self.send_header("Cache-Control", "no-cache")
+        self.send_header("Content-Length", str(len(data)))
+        self.end_headers()
+        self.wfile.write(data)
+
+    do_GET = do_POST = handle_request
+
+server = socketserver.ThreadingTCPServer(("127.0.0.1", 0), Handler)
+with open(PORT_FILE, "w") as f:
+    f.write(str(server.server_address[1]))
+server.serve_forever()
+EOF
+
+python3 server.py "$PWD" port &
+SERVER_PID=$!
+trap 'kill "$SERVER_PID" 2>/dev/null || true' EXIT
+for _ in $(seq 50); do [ -s port ] && break; sleep 0.1; done
+[ -s port ] || fail "HTTP 服务器没有启动"
+PORT=$(cat port)
+
+# 假的 ssh：在本机运行最后一个参数给出的命令，按 ssh 的方式传递 GIT_PROTOCOL
+cat > fake-ssh <<'EOF'
+#!/bin/sh
+for last; do :; done
+exec sh -c "$last"
+EOF
+chmod +x fake-ssh
+export GIT_SSH_COMMAND="$PWD/fake-ssh" GIT_SSH_VARIANT=ssh
+
+# 服务端 server.git（裸仓库，允许 filter）：one(v1，附注标签) -> two(dev) -> three(main)。
+# dst 是之前从 server.git 克隆的，之后服务端多了 four(main，附注标签 v2) 和新分支 topic。
+# work 是有工作区的仓库，检出了 main
+setup() {
+    rm -rf "$1" && mkdir "$1" && cd "$1"
+    git init -q -b main src
+    (
+        cd src
+        echo a > a; mkdir d; echo c > d/c; git add .; git commit -q -m one; git tag -a v1 -m v1
+        echo b >> a; git commit -q -am two; git branch dev
+        echo c >> a; git commit -q -am three
+    )
+    git clone -q --bare src server.git
+    git -C server.git config uploadpack.allowfilter true
+    git clone -q "file://$PWD/server.git" dst
+    (
+        cd src
+        echo d >> a; git commit -q -am four; git tag -a v2 -m v2; git branch topic HEAD~1
+        git push -q ../server.git main topic v2
+    )
+    git clone -q src work
+    cd ..
+}
+
+# 两边的绝对路径、URL 中的仓库目录、端口和服务端程序不同，统一记为 ROOT、HOST 和 git。
+# 客户端提前断开时服务端报错的前缀不同（fatal 与 Error），去掉这一行
+normalize() {
+    sed "/the remote end hung up unexpectedly/d; s#$PWD/git-repo#ROOT#g; s#$PWD/our-repo#ROOT#g; s#/git-repo/#/ROOT/#g; s#/our-repo/#/ROOT/#g; s#127\.0\.0\.1:[0-9]*#HOST#g; s#$PROGRAM #git #g" "$1"
+}
+
+# 仓库 $2 的配置、引用、HEAD、浅克隆边界、所有对象（缺少的以 ? 开头）和检出的文件
+snapshot() {
+    (
+        cd "$1/$2" 2>/dev/null || { echo "没有 $2"; exit 0; }
+        git config --local --list
+        git for-each-ref
+        git rev-parse --symbolic-full-name HEAD || true
+        cat shallow .git/shallow 2>/dev/null || true
+        git rev-list --objects --all --missing=print | sort
+        if [ "$(git rev-parse --is-bare-repository)" = false ]; then
+            git status --porcelain
+            git ls-files -s
+        fi
+        git fsck --connectivity-only 2>&1 | grep -v "^notice\|^dangling" || true
+    ) 2>&1 | normalize /dev/stdin
+}
+
+# 用法：compare 名称 目录 脚本。客户端总是 git，脚本中 $SERVER 是服务端程序（git 或我们的），
+# $U 和 $R 是让客户端运行它的 upload-pack 和 receive-pack 的选项，$URL 是经由 HTTP 访问
+# 当前目录的 URL，$SSH 是经由 ssh 访问的。最后一条命令的退出码参与比较，之后比较“目录”中的仓库
+compare() {
+    local name="$1" dir="$2" script="$3"
+    setup git-repo; setup our-repo
+    local git_status=0 our_status=0
+    (cd git-repo && SERVER=git && U="--upload-pack=git upload-pack" \
+        && R="--receive-pack=git receive-pack" && URL="http://127.0.0.1:$PORT/git-repo" \
+        && SSH="localhost:$PWD" && eval "$script") > git.out 2>/dev/null || git_status=$?
+    (cd our-repo && SERVER="$PROGRAM" && U="--upload-pack=$PROGRAM upload-pack" \
+        && R="--receive-pack=$PROGRAM receive-pack" && URL="http://127.0.0.1:$PORT/our-repo" \
+        && SSH="localhost:$PWD" && eval "$script") > our.out 2>/dev/null || our_status=$?
+    if ! diff -u <(normalize git.out) <(normalize our.out) \
+        || (( (git_status == 0) != (our_status == 0) )); then
+        fail "✗ $name: $script 输出不一致（git 退出码 $git_status，我们 $our_status），请检查实现"
+    fi
+    if diff -u <(snapshot git-repo "$dir") <(snapshot our-repo "$dir"); then
+        ok "✓ $name: $script 与官方git完全一致"
+    else
+        fail "✗ $name: $script 结果不一致，请检查实现"
+    fi
+}
+
+FILE='file://$PWD/server.git'
+
+# ========= upload-pack =========
+print_step "比较 upload-pack：ls-remote"
+compare "ls-remote" dst "git ls-remote \"\$U\" $FILE"
+compare "ls-remote v0" dst "git -c protocol.version=0 ls-remote \"\$U\" $FILE"
+compare "ls-remote v1" dst "git -c protocol.version=1 ls-remote --symref \"\$U\" $FILE"
+compare "--symref" dst "git ls-remote --symref --heads \"\$U\" $FILE"
+compare "空仓库" dst "git init -q --bare empty.git && git ls-remote --symref \"\$U\" file://\$PWD/empty.git"
+compare "不是仓库" dst "git ls-remote \"\$U\" file://\$PWD/nope.git"
+
+print_step "比较 upload-pack：克隆"
+compare "clone" c "git clone \"\$U\" $FILE c 2>&1"
+compare "clone v0" c "git -c protocol.version=0 clone \"\$U\" $FILE c 2>&1"
+compare "--bare" c.git "git clone --bare \"\$U\" $FILE c.git 2>&1"
+compare "--mirror" c.git "git clone --mirror \"\$U\" $FILE c.git 2>&1"
+compare "-b 分支" c "git clone -b dev \"\$U\" $FILE c 2>&1"
+compare "非裸仓库" c "git clone \"\$U\" file://\$PWD/work c 2>&1"
+compare "空仓库" c "git init -q --bare empty.git && git clone \"\$U\" file://\$PWD/empty.git c 2>&1"
+compare "--depth 1" c "git clone --depth 1 \"\$U\" $FILE c 2>&1"
+compare "--depth 2 v0" c "git -c protocol.version=0 clone --depth 2 \"\$U\" $FILE c 2>&1"
+compare "--depth 多分支" c "git clone --depth 1 --no-single-branch \"\$U\" $FILE c 2>&1"
+compare "--filter=blob:none" c "git clone --filter=blob:none \"\$U\" $FILE c 2>&1"
+compare "--filter=tree:0" c "git clone --no-checkout --filter=tree:0 \"\$U\" $FILE c 2>&1"
+compare "--filter=blob:limit" c "git clone --filter=blob:limit=3 \"\$U\" $FILE c 2>&1"
+compare "补取缺少的对象" c "git clone -q --no-checkout --filter=blob:none \"\$U\" $FILE c && git -C c checkout -q dev 2>&1 && git -C c log --oneline"
+compare "没有 filter 权限" c "git -C server.git config uploadpack.allowfilter false && git clone --filter=blob:none \"\$U\" $FILE c 2>&1"
+
+print_step "比较 upload-pack：fetch"
+compare "fetch" dst "cd dst && git fetch \"\$U\" 2>&1"
+compare "fetch v0" dst "cd dst && git -c protocol.version=0 fetch \"\$U\" 2>&1"
+compare "--tags" dst "cd dst && git fetch --tags \"\$U\" 2>&1"
+compare "命令行分支" dst "cd dst && git fetch \"\$U\" origin topic 2>&1"
+compare "按对象名" dst "cd dst && git fetch \"\$U\" origin \$(git -C ../server.git rev-parse topic) 2>&1 && cat .git/FETCH_HEAD"
+compare "多轮协商" dst "cd dst && for i in \$(seq 40); do git commit -q --allow-empty -m local\$i; done && git fetch \"\$U\" 2>&1"
+compare "多轮协商 v0" dst "cd dst && for i in \$(seq 40); do git commit -q --allow-empty -m local\$i; done && git -c protocol.version=0 fetch \"\$U\" 2>&1"
+compare "--deepen" c "git clone -q --depth 1 \"\$U\" $FILE c && git -C c fetch --deepen 2 \"\$U\" 2>&1"
+compare "--unshallow v0" c "git clone -q --depth 1 \"\$U\" $FILE c && git -C c -c protocol.version=0 fetch --unshallow \"\$U\" 2>&1"
+compare "--deepen v0" c "git clone -q --depth 1 \"\$U\" $FILE c && git -C c -c protocol.version=0 fetch --deepen 1 \"\$U\" 2>&1"
+compare "--shallow-exclude" c "git clone --shallow-exclude=v1 \"\$U\" $FILE c 2>&1"
+compare "--shallow-exclude v0" c "git -c protocol.version=0 clone --shallow-exclude=dev \"\$U\" $FILE c 2>&1"
+compare "--shallow-since" c "cd src && GIT_COMMITTER_DATE=2025-01-01T00:00:00Z git commit -q --allow-empty -m new && git push -q ../server.git main && cd .. && git clone --shallow-since=2024-06-01 \"\$U\" $FILE c 2>&1"
+compare "--shallow-since 太晚" c "git clone --shallow-since=2025-06-01 \"\$U\" $FILE c"
+compare "浅克隆 fetch" c "git clone -q --depth 1 -b dev \"\$U\" $FILE c && git -C c fetch \"\$U\" origin main:main 2>&1"
+
+# ========= receive-pack =========
+print_step "比较 receive-pack"
+push_setup="git -C server.git update-ref refs/heads/main HEAD~1 && git -C server.git update-ref -d refs/heads/topic && cd dst && echo e >> a && git commit -q -am five"
+compare "push" server.git "$push_setup && git push \"\$R\" 2>&1"
+compare "push v0" server.git "$push_setup && git -c protocol.version=0 push \"\$R\" 2>&1"
+compare "新分支和标签" server.git "$push_setup && git tag -a v5 -m v5 && git push \"\$R\" origin main:feature v5 2>&1"
+compare "删除" server.git "$push_setup && git push \"\$R\" origin :dev :refs/tags/v1 2>&1"
+compare "非快进" server.git "cd dst && echo e >> a && git commit -q -am five && git push \"\$R\" 2>&1"
+compare "--force" server.git "cd dst && echo e >> a && git commit -q -am five && git push -f \"\$R\" 2>&1"
+compare "--atomic" server.git "$push_setup && git push --atomic \"\$R\" origin main v1:refs/tags/v2 2>&1"
+compare "已是最新" server.git "cd dst && git push \"\$R\" origin dev 2>&1"
+compare "空仓库" empty.git "git init -q --bare empty.git && cd dst && git push \"\$R\" ../empty.git main dev v1 2>&1"
+compare "检出的分支" work "cd dst && git push \"\$R\" ../work main:main 2>&1"
+compare "钩子" server.git "printf '#!/bin/sh\necho pre-receive; cat\n' > server.git/hooks/pre-receive && printf '#!/bin/sh\necho update \$1; [ \$1 != refs/heads/dev ]\n' > server.git/hooks/update && printf '#!/bin/sh\necho post-receive; cat\n' > server.git/hooks/post-receive && chmod +x server.git/hooks/* && $push_setup && git push \"\$R\" origin main main:dev 2>&1"
+compare "pre-receive 拒绝" server.git "printf '#!/bin/sh\necho checking\nexit 1\n' > server.git/hooks/pre-receive && chmod +x server.git/hooks/pre-receive && $push_setup && git push \"\$R\" 2>&1"
+
+# git 只在对象多于 receive.unpackLimit 时保留 pack，我们总是保留，所以单独检查
+setup our-repo
+packs() { find our-repo/server.git/objects/pack -name "pack-*.idx" | wc -l; }
+before=$(packs)
+(cd our-repo && eval "$push_setup" && git push -q "--receive-pack=$PROGRAM receive-pack" 2>/dev/null) \
+    || fail "✗ 保存 pack: 推送失败"
+[ "$(packs)" -eq $((before + 1)) ] || fail "✗ 保存 pack: 收到的 pack 没有带着 .idx 保存在 objects/pack 中"
+for idx in our-repo/server.git/objects/pack/pack-*.idx; do
+    git verify-pack "$idx" || fail "✗ 保存 pack: git verify-pack $idx 失败"
+done
+ok "✓ 保存 pack: 收到的 pack 带着 .idx 保存在 objects/pack 中"
+
+# ========= ssh =========
+print_step "比较经由 ssh 的服务端"
+compare "ssh clone" c "git clone \"\$U\" \$SSH/server.git c 2>&1"
+compare "ssh clone v0" c "git -c protocol.version=0 clone --depth 1 \"\$U\" \$SSH/server.git c 2>&1"
+compare "ssh push" server.git "$push_setup && git push \"\$R\" \$SSH/server.git main:feature 2>&1"
+
+# ========= 无状态（智能 HTTP）=========
+print_step "比较无状态的服务端（智能 HTTP）"
+compare "http ls-remote" dst "git ls-remote \$URL/server.git"
+compare "http ls-remote v0" dst "git -c protocol.version=0 ls-remote \$URL/server.git"
+compare "http clone" c "git clone \$URL/server.git c 2>&1"
+compare "http clone v0" c "git -c protocol.version=0 clone \$URL/server.git c 2>&1"
+compare "http --depth" c "git clone --depth 1 \$URL/server.git c 2>&1"
+compare "http --depth v0" c "git -c protocol.version=0 clone --depth 2 \$URL/server.git c 2>&1"
+compare "http --filter" c "git clone --filter=blob:none \$URL/server.git c 2>&1"
+compare "http 多轮协商" dst "cd dst && git remote set-url origin \$URL/server.git && for i in \$(seq 40); do git commit -q --allow-empty -m local\$i; done && git fetch 2>&1"
+compare "http 多轮协商 v0" dst "cd dst && git remote set-url origin \$URL/server.git && for i in \$(seq 40); do git commit -q --allow-empty -m local\$i; done && git -c protocol.version=0 fetch 2>&1"
+compare "http push" server.git "$push_setup && git remote set-url origin \$URL/server.git && git push 2>&1"
+compare "http 删除" server.git "cd dst && git push \$URL/server.git :dev 2>&1"
+
+# ========= 清理 =========
+cd ..
+rm -rf "$TEST_DIR"
+bold "\n✅ 服务端测试完成！"
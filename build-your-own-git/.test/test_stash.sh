@@ -0,0 +1,186 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+bold() { echo -e "\033[1m$*\033[0m"; }
+info() { echo -e "\033[36m[INFO]\033[0m $*"; }
+ok() { echo -e "\033[32m[OK]\033[0m $*"; }
+fail() { echo -e "\033[31m[FAIL]\033[0m $*" >&2; exit 1; }
+print_step() { echo -e "\033[33m▶ $*\033[0m"; }
+
+PROGRAM="$1"
+TEST_DIR="test_stash_$(date +%s)"
+
+mkdir -p "$TEST_DIR" && cd "$TEST_DIR"
+
+export GIT_AUTHOR_NAME="Levio-Z" GIT_AUTHOR_EMAIL="67247011+Levio-z@users.noreply.github.com"
+export GIT_COMMITTER_NAME="$GIT_AUTHOR_NAME" GIT_COMMITTER_EMAIL="$GIT_AUTHOR_EMAIL"
+
+# main 和 other 从 base 分叉，other 修改了 a 的第 3 行
+setup() {
+    rm -rf "$1" && mkdir "$1" && cd "$1"
+    # 固定时间，两个仓库的提交哈希才相同
+    export GIT_AUTHOR_DATE="2024-01-01T00:00:00Z" GIT_COMMITTER_DATE="2024-01-01T00:00:00Z"
+    git init -q -b main
+    seq 1 5 > a; echo b > b; mkdir d; echo c > d/c; git add .; git commit -q -m base
+    git checkout -q -b other
+    sed -i 's/^3$/OTHER/' a; git commit -q -am other
+    git checkout -q main
+    echo ignored > .gitignore; git add .gitignore; git commit -q -m main
+    git rev-list --all > .git/known
+    cd ..
+}
+
+# stash 提交的哈希含提交时间，setup 之外的哈希统一记为 NEW
+normalize() {
+    awk -v known="$2" '
+        BEGIN {
+            while ((getline line < known) > 0) seen[line] = 1
+            # mawk 不支持 {40}，拼出 40 个字符类
+            for (i = 0; i < 40; i++) hex = hex "[0-9a-f]"
+        }
+        {
+            out = ""
+            while (match($0, hex)) {
+                hash = substr($0, RSTART, RLENGTH)
+                out = out substr($0, 1, RSTART - 1) (hash in seen ? hash : "NEW")
+                $0 = substr($0, RSTART + RLENGTH)
+            }
+            print out $0
+        }' "$1"
+}
+
+# 工作区、暂存区和每个 stash 的结构：说明、树和各个父提交（HEAD、暂存区、未跟踪文件）。
+# reflog 只比较说明，并检查每条记录的旧值是上一条的新值
+snapshot() {
+    (
+        cd "$1"
+        git status --porcelain
+        git symbolic-ref -q HEAD || echo "detached"
+        git ls-files -s
+        for file in $(git ls-files -o --exclude-standard; git ls-files); do
+            [ -f "$file" ] && echo "== $file" && cat "$file"
+        done
+        git stash list
+        local n=0
+        while git rev-parse -q --verify "stash@{$n}" > /dev/null; do
+            echo "== stash@{$n}"
+            git show -s --format='%s %T' "stash@{$n}"
+            git show -s --format='%s %T' "stash@{$n}^1"
+            git show -s --format='%s %T %P' "stash@{$n}^2" | normalize /dev/stdin .git/known
+            git rev-parse -q --verify "stash@{$n}^3" > /dev/null \
+                && git show -s --format='%s %T %P' "stash@{$n}^3"
+            n=$((n + 1))
+        done
+        if [ -e .git/logs/refs/stash ]; then
+            echo "== reflog"
+            awk -F '\t' '{ split($1, f, " "); print (f[1] == prev ? "chained" : f[1] ~ /^0+$/ ? "first" : "broken"), $2; prev = f[2] }' \
+                .git/logs/refs/stash
+            [ "$(git rev-parse refs/stash)" = "$(tail -1 .git/logs/refs/stash | cut -d' ' -f2)" ] || echo "refs/stash 与 reflog 不一致"
+        fi
+        true
+    )
+}
+
+# 用法：compare 名称 脚本，脚本中用 G 代替 git 命令，最后一条命令的退出码参与比较。
+# 只比较标准输出，需要比较错误输出时在脚本中用 2>&1
+compare() {
+    local name="$1" script="$2"
+    setup git-repo; setup our-repo
+    local git_status=0 our_status=0
+    unset GIT_AUTHOR_DATE GIT_COMMITTER_DATE
+    (cd git-repo && G() { git "$@"; } && eval "$script") > git.out 2>/dev/null || git_status=$?
+    (cd our-repo && G() { "$PROGRAM" "$@"; } && eval "$script") > our.out 2>/dev/null || our_status=$?
+    if ! diff -u <(normalize git.out git-repo/.git/known) <(normalize our.out our-repo/.git/known) \
+        || (( (git_status == 0) != (our_status == 0) )); then
+        fail "✗ $name: $script 输出不一致（git 退出码 $git_status，我们 $our_status），请检查实现"
+    fi
+    if diff -u <(snapshot git-repo) <(snapshot our-repo); then
+        ok "✓ $name: $script 与官方git完全一致"
+    else
+        fail "✗ $name: $script 结果不一致，请检查实现"
+    fi
+}
+
+# 暂存了 a 和新文件 n，工作区又改了 a、删了 b，还有未跟踪的 u 和被忽略的 ignored
+DIRTY='echo staged >> a && echo n > n && git add a n && echo work >> a && rm b && echo u > u && echo i > ignored'
+
+# ========= push =========
+print_step "比较 stash push"
+compare "没有修改" "G stash"
+compare "保存修改" "$DIRTY && G stash"
+compare "push" "$DIRTY && G stash push"
+compare "-m" "$DIRTY && G stash push -m 'my message'"
+compare "以 - 开头的说明" "$DIRTY && G stash push -m '-uno'"
+compare "-q" "$DIRTY && G stash -q"
+compare "--keep-index" "$DIRTY && G stash --keep-index"
+compare "--include-untracked" "$DIRTY && mkdir e && echo f > e/f && G stash -u"
+compare "只有未跟踪的文件" "echo u > u && G stash -u"
+compare "没有 -u 时忽略未跟踪的文件" "echo u > u && G stash"
+compare "路径" "$DIRTY && G stash push a n"
+compare "-- 路径" "$DIRTY && echo changed > d/c && G stash -- d"
+compare "路径和 -k" "$DIRTY && echo changed > d/c && G stash push -k a d"
+compare "路径和 -u" "$DIRTY && echo v > v && G stash push -u u a"
+compare "路径没有修改" "$DIRTY && G stash push d"
+compare "路径没有匹配" "$DIRTY && G stash push nope"
+compare "分离头指针" "git checkout -q --detach && $DIRTY && G stash"
+compare "有冲突" "git merge -q other; sed -i 's/^3\$/MAIN/' a && git commit -qam m; git merge -q other; G stash"
+compare "多个 stash" "echo 1 >> a && G stash && echo 2 >> b && G stash -m two && echo 3 >> d/c && G stash"
+
+# ========= list 和 show =========
+print_step "比较 stash list 和 show"
+STASHES="echo 1 >> a && git stash -q && echo 2 >> b && echo n > n && git add n && git stash -q -m two"
+compare "list" "$STASHES && G stash list"
+compare "没有 stash 时 list" "G stash list"
+compare "show" "$STASHES && G stash show"
+compare "show -p" "$STASHES && G stash show -p"
+compare "show --stat -p" "$STASHES && G stash show --stat -p"
+compare "show stash@{1}" "$STASHES && G stash show -p stash@{1}"
+compare "show 数字" "$STASHES && G stash show 1"
+compare "没有 stash 时 show" "G stash show"
+compare "show 不存在的 stash" "$STASHES && G stash show stash@{5}"
+compare "show 不是 stash 的提交" "$STASHES && G stash show main"
+
+# ========= apply 和 pop =========
+print_step "比较 stash apply 和 pop"
+compare "apply" "$DIRTY && git stash -q && G stash apply"
+compare "apply --index" "$DIRTY && git stash -q && G stash apply --index"
+compare "apply -q" "$DIRTY && git stash -q && G stash apply -q"
+compare "pop" "$DIRTY && git stash -q && G stash pop"
+compare "pop -u" "$DIRTY && git stash -q -u && G stash pop"
+compare "pop -q" "$DIRTY && git stash -q && G stash pop -q"
+compare "pop stash@{1}" "$STASHES && G stash pop stash@{1}"
+compare "apply 到别的提交上" "echo x >> b && git stash -q && git checkout -q other && G stash apply"
+compare "apply 冲突" "sed -i 's/^3\$/MINE/' a && echo x >> b && git stash -q && git checkout -q other && G stash apply"
+compare "pop 冲突时保留" "sed -i 's/^3\$/MINE/' a && git stash -q && git checkout -q other && G stash pop"
+compare "--index 冲突" "sed -i 's/^3\$/MINE/' a && git add a && git stash -q && git checkout -q other && G stash apply --index"
+compare "本地修改会被覆盖" "echo x >> a && git stash -q && echo y >> a && G stash apply"
+compare "无关的本地修改" "echo x >> a && git stash -q && echo y >> b && G stash apply"
+compare "未跟踪的文件已存在" "echo x >> a && echo u > u && git stash -q -u && echo other > u && G stash pop"
+compare "暂存区有冲突" "echo x >> a && git stash -q && sed -i 's/^3\$/MAIN/' a && git commit -qam m && git merge -q other; G stash apply"
+compare "没有 stash 时 pop" "G stash pop"
+compare "pop 不存在的 stash" "$STASHES && G stash pop stash@{5}"
+compare "pop 不是 stash 引用" "$STASHES && G stash pop \$(git rev-parse stash@{0})"
+compare "apply 提交哈希" "$STASHES && G stash apply \$(git rev-parse stash@{1})"
+
+# ========= drop、clear 和 branch =========
+print_step "比较 stash drop、clear 和 branch"
+THREE="echo 1 >> a && git stash -q && echo 2 >> a && git stash -q -m two && echo 3 >> a && git stash -q -m three"
+compare "drop" "$THREE && G stash drop"
+compare "drop 中间的" "$THREE && G stash drop stash@{1}"
+compare "drop 最早的" "$THREE && G stash drop 2"
+compare "drop -q" "$THREE && G stash drop -q stash@{1}"
+compare "drop 最后一个" "echo 1 >> a && git stash -q && G stash drop"
+compare "drop 全部" "$THREE && G stash drop && G stash drop && G stash drop && G stash drop"
+compare "drop 不存在的" "$THREE && G stash drop stash@{3}"
+compare "clear" "$THREE && G stash clear"
+compare "没有 stash 时 clear" "G stash clear"
+compare "clear 后再保存" "$THREE && G stash clear && echo 4 >> a && G stash"
+compare "branch" "$DIRTY && git stash -q && git commit -q --allow-empty -m later && G stash branch topic"
+compare "branch stash@{1}" "$THREE && G stash branch topic stash@{1}"
+compare "branch 已存在" "$THREE && G stash branch other"
+compare "branch 冲突" "sed -i 's/^3\$/MINE/' a && git stash -q && git checkout -q other && sed -i 's/^1\$/ONE/' a && G stash branch topic"
+
+# ========= 清理 =========
+cd ..
+rm -rf "$TEST_DIR"
+bold "\n✅ stash 测试完成！"
@@ -0,0 +1,135 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+bold() { echo -e "\033[1m$*\033[0m"; }
+info() { echo -e "\033[36m[INFO]\033[0m $*"; }
+ok() { echo -e "\033[32m[OK]\033[0m $*"; }
+fail() { echo -e "\033[31m[FAIL]\033[0m $*" >&2; exit 1; }
+print_step() { echo -e "\033[33m▶ $*\033[0m"; }
+
+PROGRAM="$1"
+TEST_DIR="test_submodule_$(date +%s)"
+
+mkdir -p "$TEST_DIR" && cd "$TEST_DIR"
+
+export GIT_AUTHOR_NAME="Levio-Z" GIT_AUTHOR_EMAIL="67247011+Levio-z@users.noreply.github.com"
+export GIT_COMMITTER_NAME="$GIT_AUTHOR_NAME" GIT_COMMITTER_EMAIL="$GIT_AUTHOR_EMAIL"
+# 固定时间，两边的提交哈希才相同
+export GIT_AUTHOR_DATE="2024-01-01T00:00:00Z" GIT_COMMITTER_DATE="2024-01-01T00:00:00Z"
+
+# 仓库 lib：zero -> one(v1，附注标签) -> two -> three(v3，轻量标签)。
+# 超项目 super 在 sub 记录了 lib 的 two，子模块还没有初始化
+setup() {
+    rm -rf "$1" && mkdir "$1" && cd "$1"
+    git init -q -b main lib
+    (
+        cd lib
+        echo 0 > f; git add f; git commit -q -m zero
+        echo 1 >> f; git commit -q -am one; git tag -a v1 -m v1
+        echo 2 >> f; git commit -q -am two
+        echo 3 >> f; git commit -q -am three; git tag v3
+    )
+    git init -q -b main super
+    (
+        cd super
+        echo x > x; git add x
+        git -c protocol.file.allow=always submodule -q add ../lib sub 2> /dev/null
+        git -C sub checkout -q HEAD~1
+        git add sub; git commit -q -m super
+        # 去掉 submodule add 留下的克隆
+        git submodule -q deinit -f sub; rm -rf .git/modules
+    )
+    cd ..
+}
+
+# 两个仓库的绝对路径不同，统一记为 ROOT
+normalize() {
+    sed "s#$PWD/git-repo#ROOT#g; s#$PWD/our-repo#ROOT#g" "$1"
+}
+
+# 超项目的状态、暂存区和子模块配置，以及子模块检出的提交、引用和文件
+snapshot() {
+    (
+        cd "$1/super"
+        git status --porcelain
+        git ls-files -s
+        git config --get-regexp '^submodule\.' || true
+        if [ -e sub/.git ]; then
+            git -C sub status | head -1
+            git -C sub rev-parse HEAD
+            git -C sub status --porcelain
+            git -C sub for-each-ref
+            git -C sub config remote.origin.url
+            for file in $(git -C sub ls-files); do echo "== $file" && cat "sub/$file"; done
+        fi
+    ) | normalize /dev/stdin
+}
+
+# 用法：compare 名称 脚本，脚本在超项目中运行，用 G 代替 git 命令，最后一条命令的退出码参与比较。
+# 只比较标准输出，需要比较错误输出时在脚本中用 2>&1
+compare() {
+    local name="$1" script="$2"
+    setup git-repo; setup our-repo
+    local git_status=0 our_status=0
+    (cd git-repo/super && G() { git -c protocol.file.allow=always "$@"; } && eval "$script") > git.out 2>/dev/null || git_status=$?
+    (cd our-repo/super && G() { "$PROGRAM" "$@"; } && eval "$script") > our.out 2>/dev/null || our_status=$?
+    if ! diff -u <(normalize git.out) <(normalize our.out) \
+        || (( (git_status == 0) != (our_status == 0) )); then
+        fail "✗ $name: $script 输出不一致（git 退出码 $git_status，我们 $our_status），请检查实现"
+    fi
+    if diff -u <(snapshot git-repo) <(snapshot our-repo); then
+        ok "✓ $name: $script 与官方git完全一致"
+    else
+        fail "✗ $name: $script 结果不一致，请检查实现"
+    fi
+}
+
+# 用 git 克隆并检出子模块，再把 .git/modules/lib 移回子模块目录，成为我们能读取的 .git 目录
+CLONED='git -c protocol.file.allow=always submodule -q update --init 2>/dev/null && rm sub/.git && mv .git/modules/lib sub/.git && git -C sub config --unset core.worktree'
+
+# ========= 树中的子模块 =========
+print_step "比较树中的子模块"
+# 我们的 ls-tree 用两个空格分隔名称，统一空白后比较
+compare "ls-tree" "G ls-tree \$(git rev-parse HEAD^{tree}) | tr -s ' \t' ' '"
+compare "ls-tree --name-only" "G ls-tree --name-only \$(git rev-parse HEAD^{tree})"
+compare "write-tree 记为 gitlink" "$CLONED && G write-tree"
+compare "write-tree 嵌套的仓库" "$CLONED && git init -q -b main nested && git -C nested commit -q --allow-empty -m n && git add nested 2>/dev/null && G write-tree"
+compare "status 新提交" "$CLONED && git -C sub checkout -q main && G status"
+compare "status --porcelain 新提交" "$CLONED && git -C sub checkout -q main && G status --porcelain"
+compare "status 未检出" "G status --porcelain"
+compare "status 删除子模块目录" "rm -rf sub && G status --porcelain"
+compare "diff" "$CLONED && git -C sub checkout -q main && G diff"
+compare "diff --stat" "$CLONED && git -C sub checkout -q main && G diff --stat"
+compare "diff --cached" "$CLONED && git -C sub checkout -q main && git add sub && G diff --cached"
+
+# ========= submodule status =========
+print_step "比较 submodule status"
+compare "未初始化" "G submodule status"
+compare "没有子命令" "G submodule"
+compare "已检出" "$CLONED && G submodule status"
+compare "检出了轻量标签" "$CLONED && git -C sub checkout -q main && G submodule status"
+compare "检出了附注标签" "$CLONED && git -C sub checkout -q v1 && G submodule status"
+compare "包含它的标签" "$CLONED && git -C sub checkout -q v1~1 && G submodule status"
+compare "路径" "$CLONED && G submodule status sub"
+compare "路径没有匹配" "$CLONED && G submodule status nope"
+compare "已初始化未克隆" "git submodule -q init && G submodule status"
+
+# ========= submodule init 和 update =========
+print_step "比较 submodule init 和 update"
+compare "init" "G submodule init 2>&1"
+compare "init 已初始化" "git submodule -q init && G submodule init 2>&1"
+compare "init 路径" "G submodule init sub 2>&1"
+compare "init 相对 remote.origin.url" "git config remote.origin.url \"\$PWD/../upstream/super\" && G submodule init 2>&1"
+compare "update 跳过未初始化的" "G submodule update 2>&1"
+compare "update --init" "G submodule update --init 2>&1"
+compare "init 后 update" "git submodule -q init && G submodule update 2>&1"
+compare "update 路径" "G submodule update --init sub 2>&1"
+compare "update 已是最新" "$CLONED && G submodule update 2>&1"
+compare "update 检出记录的提交" "$CLONED && git -C sub checkout -q main && G submodule update 2>&1"
+compare "update 取回新提交" "$CLONED && git -C ../lib commit -q --allow-empty -m four && git update-index --cacheinfo 160000,\$(git -C ../lib rev-parse HEAD),sub && G submodule update 2>&1"
+compare "update 本地修改会被覆盖" "$CLONED && git -C sub checkout -q main && echo local >> sub/f && G submodule update"
+
+# ========= 清理 =========
+cd ..
+rm -rf "$TEST_DIR"
+bold "\n✅ submodule 测试完成！"
@@ -0,0 +1,98 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+bold() { echo -e "\033[1m$*\033[0m"; }
+info() { echo -e "\033[36m[INFO]\033[0m $*"; }
+ok() { echo -e "\033[32m[OK]\033[0m $*"; }
+fail() { echo -e "\033[31m[FAIL]\033[0m $*" >&2; exit 1; }
+print_step() { echo -e "\033[33m▶ $*\033[0m"; }
+
+PROGRAM="$1"
+TEST_DIR="test_symlink_$(date +%s)"
+
+mkdir -p "$TEST_DIR" && cd "$TEST_DIR"
+
+export GIT_AUTHOR_NAME="Levio-Z" GIT_AUTHOR_EMAIL="67247011+Levio-z@users.noreply.github.com"
+export GIT_COMMITTER_NAME="$GIT_AUTHOR_NAME" GIT_COMMITTER_EMAIL="$GIT_AUTHOR_EMAIL"
+# 固定时间，两个仓库的提交哈希才相同
+export GIT_AUTHOR_DATE="2024-01-01T00:00:00Z" GIT_COMMITTER_DATE="2024-01-01T00:00:00Z"
+
+# base 中有指向文件、目录和不存在路径的符号链接；other 修改了 link，新增了 new，
+# 并把普通文件 b 换成了符号链接
+setup() {
+    rm -rf "$1" && mkdir "$1" && cd "$1"
+    git init -q -b main
+    echo a > a; echo b > b; mkdir d; echo c > d/c
+    ln -s a link; ln -s d dirlink; ln -s nowhere broken
+    git add .; git commit -q -m base
+    git checkout -q -b other
+    ln -sfn d/c link; ln -s ../a d/new; rm b; ln -s a b
+    git add .; git commit -q -m other
+    git checkout -q main
+    echo more >> a; git commit -q -am main
+    cd ..
+}
+
+# 工作区和暂存区：符号链接显示指向的路径，普通文件显示内容
+snapshot() {
+    (
+        cd "$1"
+        git status --porcelain
+        git ls-files -s
+        for file in $(git ls-files); do
+            if [ -L "$file" ]; then
+                echo "== $file -> $(readlink "$file")"
+            elif [ -f "$file" ]; then
+                echo "== $file" && cat "$file"
+            fi
+        done
+    )
+}
+
+# 用法：compare 名称 脚本，脚本中用 G 代替 git 命令，最后一条命令的退出码参与比较。
+# 只比较标准输出，需要比较错误输出时在脚本中用 2>&1
+compare() {
+    local name="$1" script="$2"
+    setup git-repo; setup our-repo
+    local git_status=0 our_status=0
+    (cd git-repo && G() { git "$@"; } && eval "$script") > git.out 2>/dev/null || git_status=$?
+    (cd our-repo && G() { "$PROGRAM" "$@"; } && eval "$script") > our.out 2>/dev/null || our_status=$?
+    if ! diff -u git.out our.out || (( (git_status == 0) != (our_status == 0) )); then
+        fail "✗ $name: $script 输出不一致（git 退出码 $git_status，我们 $our_status），请检查实现"
+    fi
+    if diff -u <(snapshot git-repo) <(snapshot our-repo); then
+        ok "✓ $name: $script 与官方git完全一致"
+    else
+        fail "✗ $name: $script 结果不一致，请检查实现"
+    fi
+}
+
+# ========= 树中的符号链接 =========
+print_step "比较树中的符号链接"
+# 我们的 ls-tree 用两个空格分隔名称，统一空白后比较
+compare "ls-tree" "G ls-tree \$(git rev-parse HEAD^{tree}) | tr -s ' \t' ' '"
+compare "ls-tree 子目录中的链接" "G ls-tree \$(git rev-parse other:d) | tr -s ' \t' ' '"
+compare "write-tree" "G write-tree"
+compare "write-tree 新的符号链接" "ln -s d/c new && git add new && G write-tree"
+compare "write-tree 修改指向" "ln -sfn b link && git add link && G write-tree"
+
+# ========= 工作区中的符号链接 =========
+print_step "比较工作区中的符号链接"
+compare "status 修改指向" "ln -sfn b link && G status --porcelain"
+compare "status 链接换成文件" "rm link && echo a > link && G status --porcelain"
+compare "diff 修改指向" "ln -sfn b link && G diff"
+compare "commit -a" "ln -sfn b link && G commit -a -m change > /dev/null && git ls-tree -r HEAD"
+compare "merge 检出符号链接" "G merge other > /dev/null; G status --porcelain"
+compare "stash 恢复符号链接" "ln -sfn b link && G stash -q && G status --porcelain && G stash pop -q"
+
+# ========= core.symlinks=false =========
+print_step "比较 core.symlinks=false"
+compare "检出成普通文件" "git config core.symlinks false && G merge other > /dev/null; G status --porcelain"
+compare "内容相同的普通文件" "git config core.symlinks false && rm link && printf a > link && G status --porcelain"
+compare "修改后提交" "git config core.symlinks false && rm link && printf b > link && G commit -a -m change > /dev/null && git ls-tree -r HEAD"
+compare "stash" "git config core.symlinks false && rm link && printf b > link && G stash -q && G status --porcelain"
+
+# ========= 清理 =========
+cd ..
+rm -rf "$TEST_DIR"
+bold "\n✅ 符号链接测试完成！"
@@ -153,6 +153,31 @@ main() {
     TESTS=("Git 初始化|../.test/test_init.sh"
            "文件内容读取|../.test/test_cat_file.sh"
             "读取树对象|../.test/test_ls_tree.sh"
+            "提交历史图形|../.test/test_log_graph.sh"
+            "提交遍历与对象枚举|../.test/test_rev_list.sh"
+            "树对象比较|../.test/test_diff_tree.sh"
+            "文件差异|../.test/test_diff.sh"
+            "重命名与复制检测|../.test/test_rename.sh"
+            "三方文件合并|../.test/test_merge_file.sh"
+            "分支合并|../.test/test_merge.sh"
+            "公共祖先|../.test/test_merge_base.sh"
+            "拣选与撤销提交|../.test/test_cherry_pick.sh"
+            "变基|../.test/test_rebase.sh"
+            "提交|../.test/test_commit.sh"
+            "底层提交|../.test/test_commit_tree.sh"
+            "钩子|../.test/test_hooks.sh"
+            "储藏|../.test/test_stash.sh"
+            "子模块|../.test/test_submodule.sh"
+            "符号链接|../.test/test_symlink.sh"
+            "克隆|../.test/test_clone.sh"
+            "远程和 fetch|../.test/test_fetch.sh"
+            "推送|../.test/test_push.sh"
+            "传输协议|../.test/test_protocol.sh"
+            "智能 HTTP|../.test/test_http.sh"
+            "服务端|../.test/test_serve_pack.sh"
+            "内置服务器|../.test/test_serve.sh"
+            "pack 索引|../.test/test_index_pack.sh"
+            "bundle 文件|../.test/test_bundle.sh"
            )
     TOTAL_TESTS=${#TESTS[@]}
     
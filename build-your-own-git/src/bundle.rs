@@ -0,0 +1,172 @@
+//! bundle 文件：把引用和 pack 放在一个文件中，用于不能直接连接的仓库之间传输。
+//!
+//! v2 的头是 `# v2 git bundle`，之后每行一个前提提交 `-<哈希> <标题>` 或引用 `<哈希> <引用名>`，
+//! 空行之后是 pack。v3 的头是 `# v3 git bundle`，紧接着是 `@<名称>[=<值>]` 形式的能力：
+//! `object-format`（只支持 sha1）和 `filter`（pack 按这个过滤条件省略了对象）。
+//! 前提提交是 pack 中的提交引用到、但不在 pack 中的父提交，接收方必须已经有它们
+
+use std::{
+    io::{BufRead, BufReader, Read},
+    path::Path,
+};
+
+use anyhow::Context;
+
+use crate::{
+    objects::{commit::parse_hex, object_exists_in},
+    pack,
+    revision::list_objects::Filter,
+};
+
+const V2_SIGNATURE: &str = "# v2 git bundle";
+const V3_SIGNATURE: &str = "# v3 git bundle";
+
+#[derive(Debug, Default)]
+pub(crate) struct Bundle {
+    /// 2 或 3
+    pub(crate) version: u32,
+    /// v3 的 `filter` 能力，如 `blob:none`
+    pub(crate) filter: Option<String>,
+    /// 前提提交和它的标题
+    pub(crate) prerequisites: Vec<([u8; 20], String)>,
+    /// 按在文件中的顺序
+    pub(crate) refs: Vec<(String, [u8; 20])>,
+    /// 头之后的 pack
+    pub(crate) pack: Vec<u8>,
+}
+
+impl Bundle {
+    /// 读取 path 处的 bundle
+    pub(crate) fn read(path: &Path) -> anyhow::Result<Bundle> {
+        let data =
+            std::fs::read(path).with_context(|| format!("could not open '{}'", path.display()))?;
+        Bundle::parse(data).with_context(|| {
+            format!(
+                "'{}' does not look like a v2 or v3 bundle file",
+                path.display()
+            )
+        })
+    }
+
+    /// path 是否是 bundle 文件：只看开头的签名
+    pub(crate) fn is_bundle(path: &Path) -> bool {
+        let Ok(file) = std::fs::File::open(path) else {
+            return false;
+        };
+        let mut line = Vec::new();
+        if BufReader::new(file.take(64))
+            .read_until(b'\n', &mut line)
+            .is_err()
+        {
+            return false;
+        }
+        [V2_SIGNATURE, V3_SIGNATURE]
+            .iter()
+            .any(|signature| line == format!("{signature}\n").as_bytes())
+    }
+
+    fn parse(mut data: Vec<u8>) -> anyhow::Result<Bundle> {
+        let mut bundle = Bundle::default();
+        let mut offset = 0;
+        let mut next_line = |data: &[u8]| -> anyhow::Result<String> {
+            let end = data[offset..]
+                .iter()
+                .position(|&b| b == b'\n')
+                .context("unexpected end of bundle header")?;
+            let line = String::from_utf8_lossy(&data[offset..offset + end]).into_owned();
+            offset += end + 1;
+            Ok(line)
+        };
+        bundle.version = match next_line(&data)?.as_str() {
+            V2_SIGNATURE => 2,
+            V3_SIGNATURE => 3,
+            signature => anyhow::bail!("unknown bundle signature '{signature}'"),
+        };
+        loop {
+            let line = next_line(&data)?;
+            if line.is_empty() {
+                break;
+            }
+            if let Some(capability) = line.strip_prefix('@') {
+                anyhow::ensure!(bundle.version == 3, "capability in a v2 bundle: {line}");
+                match capability.split_once('=') {
+                    Some(("object-format", "sha1")) => {}
+                    Some(("object-format", format)) => {
+                        anyhow::bail!("unrecognized bundle hash algorithm: {format}")
+                    }
+                    Some(("filter", spec)) => {
+                        Filter::parse(spec)?;
+                        bundle.filter = Some(spec.to_string());
+                    }
+                    _ => anyhow::bail!("unknown capability '{capability}'"),
+                }
+                continue;
+            }
+            // 前提提交的标题可以没有；引用必须有名字
+            let (prerequisite, line) = match line.strip_prefix('-') {
+                Some(rest) => (true, rest),
+                None => (false, line.as_str()),
+            };
+            let (hex, name) = line.split_once(' ').unwrap_or((line, ""));
+            let id = parse_hex(hex).with_context(|| format!("unrecognized header: {line}"))?;
+            match prerequisite {
+                true => bundle.prerequisites.push((id, name.to_string())),
+                false if name.is_empty() => anyhow::bail!("unrecognized header: {line}"),
+                false => bundle.refs.push((name.to_string(), id)),
+            }
+        }
+        bundle.pack = data.split_off(offset);
+        Ok(bundle)
+    }
+
+    /// 写出 bundle：头、空行和 pack。有过滤条件时必须是 v3
+    pub(crate) fn write(&self) -> Vec<u8> {
+        let mut out = String::new();
+        if self.version == 3 {
+            out.push_str(V3_SIGNATURE);
+            out.push_str("\n@object-format=sha1\n");
+            if let Some(filter) = &self.filter {
+                out.push_str(&format!("@filter={filter}\n"));
+            }
+        } else {
+            out.push_str(V2_SIGNATURE);
+            out.push('\n');
+        }
+        for (id, subject) in &self.prerequisites {
+            out.push_str(&format!("-{} {subject}\n", hex::encode(id)));
+        }
+        for (name, id) in &self.refs {
+            out.push_str(&format!("{} {name}\n", hex::encode(id)));
+        }
+        out.push('\n');
+        let mut data = out.into_bytes();
+        data.extend_from_slice(&self.pack);
+        data
+    }
+
+    /// objects 这个对象目录中缺少的前提提交
+    pub(crate) fn missing_prerequisites(&self, objects: &Path) -> Vec<[u8; 20]> {
+        self.prerequisites
+            .iter()
+            .map(|(id, _)| *id)
+            .filter(|id| !object_exists_in(objects, id))
+            .collect()
+    }
+
+    /// 检查前提提交后把 pack 中的对象写入 objects
+    pub(crate) async fn unbundle(&self, objects: &Path) -> anyhow::Result<()> {
+        let missing = self.missing_prerequisites(objects);
+        if !missing.is_empty() {
+            let list: Vec<String> = missing
+                .iter()
+                .map(|id| format!("{} ", hex::encode(id)))
+                .collect();
+            anyhow::bail!(
+                "Repository lacks these prerequisite commits:\n{}",
+                list.join("\n")
+            );
+        }
+        pack::unpack(&self.pack, objects).await?;
+        Ok(())
+    }
+}
@@ -1,5 +1,28 @@
+pub(crate) mod bundle;
 pub(crate) mod cat_file;
+pub(crate) mod cherry_pick;
+pub(crate) mod clone;
 pub(crate) mod commit;
+pub(crate) mod diff;
+pub(crate) mod diff_tree;
+pub(crate) mod fetch;
 pub(crate) mod hash_object;
+pub(crate) mod index_pack;
+pub(crate) mod log;
+pub(crate) mod ls_remote;
 pub(crate) mod ls_tree;
+pub(crate) mod merge;
+pub(crate) mod merge_base;
+pub(crate) mod merge_file;
+pub(crate) mod push;
+pub(crate) mod rebase;
+pub(crate) mod receive_pack;
+pub(crate) mod remote;
+pub(crate) mod rev_list;
+pub(crate) mod serve;
+pub(crate) mod stash;
+pub(crate) mod status;
+pub(crate) mod submodule;
+pub(crate) mod upload_pack;
+pub(crate) mod verify_pack;
 pub(crate) mod write_tree;
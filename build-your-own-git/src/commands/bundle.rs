@@ -0,0 +1,217 @@
+//! bundle：创建、检查和解开 bundle 文件，格式见 [`crate::bundle`]。
+//!
+//! `create` 像 rev-list 一样接受起点和范围，参数中能解析成引用的正向起点写成 bundle 的引用，
+//! 范围排除的边界提交成为前提提交；`unbundle` 只把对象放进仓库并列出引用，不更新引用，
+//! 更新引用要经由 fetch 或 clone
+
+use std::{collections::HashSet, io::Write, path::Path};
+
+use anyhow::Context;
+
+use crate::{
+    bundle::Bundle,
+    objects::{Kind, commit::Commit},
+    pack, refs,
+    revision::{
+        RevWalk,
+        list_objects::{Filter, MissingAction, list_objects},
+        peel,
+    },
+};
+
+/// 把 args 选中的提交以及它们引用的对象写成 bundle，file 为 `-` 时写到标准输出。
+/// version 默认是 2，有过滤条件时是 3
+pub(crate) async fn create(
+    file: &str,
+    args: &[String],
+    version: Option<u32>,
+) -> anyhow::Result<()> {
+    let mut walk = RevWalk::new();
+    // 参数中的正向起点，包括范围的一端
+    let mut tips: Vec<String> = Vec::new();
+    let mut filter = None;
+    for arg in args {
+        let prefix = match arg.as_str() {
+            "--all" => Some("refs/"),
+            "--branches" => Some("refs/heads/"),
+            "--tags" => Some("refs/tags/"),
+            "--remotes" => Some("refs/remotes/"),
+            _ => None,
+        };
+        if let Some(prefix) = prefix {
+            walk.push_refs(prefix).await?;
+            for (name, _) in refs::list_refs(prefix)? {
+                tips.push(name);
+            }
+            if prefix == "refs/" && refs::resolve_ref("HEAD")?.is_some() {
+                tips.push("HEAD".to_string());
+            }
+        } else if let Some(spec) = arg.strip_prefix("--filter=") {
+            Filter::parse(spec)?;
+            filter = Some(spec.to_string());
+        } else if arg.starts_with('-') {
+            anyhow::bail!("unrecognized argument: {arg}");
+        } else {
+            walk.push_arg(arg).await?;
+            if let Some((left, right)) = arg.split_once("...") {
+                for side in [left, right] {
+                    tips.push(if side.is_empty() { "HEAD" } else { side }.to_string());
+                }
+            } else if let Some((_, to)) = arg.split_once("..") {
+                tips.push(if to.is_empty() { "HEAD" } else { to }.to_string());
+            } else if !arg.starts_with('^') {
+                tips.push(arg.clone());
+            }
+        }
+    }
+    let version = match (version, &filter) {
+        (None, None) => 2,
+        (None, Some(_)) => 3,
+        (Some(version), _) if !(2..=3).contains(&version) => {
+            anyhow::bail!("unsupported bundle version {version}")
+        }
+        (Some(2), Some(_)) => anyhow::bail!("cannot write bundle version 2 with a filter"),
+        (Some(version), _) => version,
+    };
+
+    let commits = walk.walk().await?;
+    let shown: HashSet<[u8; 20]> = commits.iter().copied().collect();
+
+    // 与 git 一样同名的参数只算一次，解析不成引用的（如 `main~1`）和被排除的提交不写入；
+    // 附注标签即使指向的提交被排除也写入，pack 中只有标签对象
+    let mut bundle_refs: Vec<(String, [u8; 20])> = Vec::new();
+    let mut names = HashSet::new();
+    for name in tips {
+        if !names.insert(name.clone()) {
+            continue;
+        }
+        let Some(full) = refs::dwim_ref(&name)? else {
+            continue;
+        };
+        let Some(id) = refs::resolve_ref(&full)? else {
+            continue;
+        };
+        let Ok(commit) = peel(id, Some(Kind::Commit)).await else {
+            continue;
+        };
+        if commit == id && !shown.contains(&commit) {
+            continue;
+        }
+        bundle_refs.push((full, id));
+    }
+    anyhow::ensure!(!bundle_refs.is_empty(), "Refusing to create empty bundle.");
+
+    // 前提提交：输出的提交中被排除的父提交，与 git 的 `rev-list --boundary` 顺序相同
+    let mut boundary = Vec::new();
+    for id in &commits {
+        for parent in &walk.commit(id).parents {
+            if !shown.contains(parent) && !boundary.contains(parent) {
+                boundary.push(*parent);
+            }
+        }
+    }
+    boundary.reverse();
+    let mut prerequisites = Vec::new();
+    for id in boundary {
+        prerequisites.push((id, Commit::read(&id).await?.subject()));
+    }
+
+    let parsed_filter = filter.as_deref().map(Filter::parse).transpose()?;
+    let list = list_objects(&walk, &commits, parsed_filter, false, MissingAction::Error).await?;
+    let mut ids = commits.clone();
+    ids.extend(list.objects.iter().map(|(id, _)| *id));
+    let bundle = Bundle {
+        version,
+        filter,
+        prerequisites,
+        refs: bundle_refs,
+        pack: pack::write(Path::new(".git/objects"), &ids).await?,
+    };
+    let data = bundle.write();
+    match file {
+        "-" => {
+            let mut stdout = std::io::stdout().lock();
+            stdout.write_all(&data)?;
+            stdout.flush()?;
+        }
+        file => std::fs::write(file, data).with_context(|| format!("cannot create '{file}'"))?,
+    }
+    Ok(())
+}
+
+/// 检查 bundle 能否在当前仓库中使用，列出其中的引用和前提提交。缺少前提提交时返回 false
+pub(crate) fn verify(file: &str, quiet: bool) -> anyhow::Result<bool> {
+    anyhow::ensure!(
+        Path::new(".git").is_dir(),
+        "need a repository to verify a bundle"
+    );
+    let bundle = Bundle::read(Path::new(file))?;
+    if !check_prerequisites(&bundle) {
+        return Ok(false);
+    }
+    if !quiet {
+        let count = |n: usize| match n {
+            1 => "this ref".to_string(),
+            n => format!("these {n} refs"),
+        };
+        println!("The bundle contains {}:", count(bundle.refs.len()));
+        for (name, id) in &bundle.refs {
+            println!("{} {name}", hex::encode(id));
+        }
+        if bundle.prerequisites.is_empty() {
+            println!("The bundle records a complete history.");
+        } else {
+            println!("The bundle requires {}:", count(bundle.prerequisites.len()));
+            // 与 git 一样只列出哈希，名字为空
+            for (id, _) in &bundle.prerequisites {
+                println!("{} ", hex::encode(id));
+            }
+        }
+        println!("The bundle uses this hash algorithm: sha1");
+        if let Some(filter) = &bundle.filter {
+            println!("The bundle uses this filter: {filter}");
+        }
+    }
+    eprintln!("{file} is okay");
+    Ok(true)
+}
+
+/// 列出 bundle 中的引用，给出 refnames 时只列出名字完全相同的
+pub(crate) fn list_heads(file: &str, refnames: &[String]) -> anyhow::Result<()> {
+    let bundle = Bundle::read(Path::new(file))?;
+    print_refs(&bundle, refnames);
+    Ok(())
+}
+
+/// 把 bundle 中的对象放进当前仓库，列出其中的引用。缺少前提提交时返回 false
+pub(crate) async fn unbundle(file: &str, refnames: &[String]) -> anyhow::Result<bool> {
+    anyhow::ensure!(Path::new(".git").is_dir(), "need a repository to unbundle");
+    let bundle = Bundle::read(Path::new(file))?;
+    if !check_prerequisites(&bundle) {
+        return Ok(false);
+    }
+    bundle.unbundle(Path::new(".git/objects")).await?;
+    print_refs(&bundle, refnames);
+    Ok(true)
+}
+
+/// 当前仓库是否有所有前提提交，没有时列出缺少的
+fn check_prerequisites(bundle: &Bundle) -> bool {
+    let missing = bundle.missing_prerequisites(Path::new(".git/objects"));
+    if missing.is_empty() {
+        return true;
+    }
+    eprintln!("error: Repository lacks these prerequisite commits:");
+    for id in missing {
+        eprintln!("error: {} ", hex::encode(id));
+    }
+    false
+}
+
+fn print_refs(bundle: &Bundle, refnames: &[String]) {
+    for (name, id) in &bundle.refs {
+        if refnames.is_empty() || refnames.contains(name) {
+            println!("{} {name}", hex::encode(id));
+        }
+    }
+}
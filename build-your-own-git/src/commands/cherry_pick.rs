@@ -0,0 +1,39 @@
+use anyhow::Context;
+
+pub(crate) use crate::sequencer::{abort, continue_sequence, skip};
+use crate::{
+    objects::Kind,
+    revision::{RevWalk, peel, resolve},
+    sequencer::{Action, ReplayOptions, pick_revisions},
+};
+
+/// cherry-pick/revert 命令行给出的提交：有范围（`A..B`、`^A`）时按时间从旧到新遍历，
+/// 否则按给出的顺序逐个处理。全部成功时返回 true
+pub(crate) async fn invoke(
+    action: Action,
+    revs: &[String],
+    options: ReplayOptions,
+) -> anyhow::Result<bool> {
+    let ranged = revs
+        .iter()
+        .any(|rev| rev.starts_with('^') || rev.contains(".."));
+    let commits = if ranged {
+        let mut walk = RevWalk::new();
+        for rev in revs {
+            walk.push_arg(rev).await?;
+        }
+        let mut commits = walk.walk().await?;
+        commits.reverse();
+        commits
+    } else {
+        let mut commits = Vec::with_capacity(revs.len());
+        for rev in revs {
+            let id = resolve(rev)
+                .await
+                .with_context(|| format!("bad revision '{rev}'"))?;
+            commits.push(peel(id, Some(Kind::Commit)).await?);
+        }
+        commits
+    };
+    pick_revisions(action, commits, !ranged && revs.len() == 1, options).await
+}
@@ -0,0 +1,584 @@
+//! clone：克隆另一个仓库，源可以是普通路径、`file://` URL、ssh 地址或 bundle 文件。
+//!
+//! 与 git 一样，普通路径走本地优化，直接硬链接（`--no-hardlinks` 时复制）整个对象目录；
+//! URL 和 `--no-local` 时经由 git-upload-pack 只取需要的对象，`--depth` 和 `--filter` 也只在这时生效。
+//! bundle 文件解开其中的整个 pack
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use crate::{
+    bundle::Bundle,
+    commands::commit::committer_ident,
+    config,
+    diff::flatten_tree,
+    index::Index,
+    objects::{
+        Kind, Mode,
+        commit::{Commit, parse_hex},
+        object_exists, object_exists_in, read_object_in,
+    },
+    refs, submodule,
+    transport::{self, FetchArgs, RemoteRef, Transport},
+    worktree,
+};
+
+#[derive(Debug, Default)]
+pub(crate) struct CloneOptions {
+    /// `--bare`：不检出，仓库直接放在目标目录中，远程分支原样记在 `refs/heads` 下
+    pub(crate) bare: bool,
+    /// `--mirror`：同 `--bare`，并原样镜像远程的所有引用
+    pub(crate) mirror: bool,
+    /// `-b`：检出的分支或标签，默认是远程 HEAD 指向的分支
+    pub(crate) branch: Option<String>,
+    /// `--depth`：只取最近的 n 个提交，并且只取一个分支
+    pub(crate) depth: Option<usize>,
+    /// `--filter`：部分克隆，如 `blob:none` 时不取文件内容，检出时再补取需要的
+    pub(crate) filter: Option<String>,
+    /// 普通路径是否走本地优化，`--no-local` 时为 false
+    pub(crate) local: bool,
+    /// `--no-hardlinks`：本地优化时复制对象文件而不是硬链接
+    pub(crate) no_hardlinks: bool,
+    /// `-n`：不检出，HEAD 指向的分支也不写入暂存区
+    pub(crate) no_checkout: bool,
+    pub(crate) quiet: bool,
+}
+
+/// 要检出的远程引用
+enum Target {
+    Branch(String),
+    /// 标签检出为分离头指针
+    Tag(String),
+    /// 远程 HEAD 不指向任何分支时（如只有 HEAD 的 bundle），检出为分离头指针
+    Detached([u8; 20]),
+}
+
+/// 把 url 处的仓库克隆到 directory，没有指定目录时取 url 的最后一级（去掉 `.git`）
+pub(crate) async fn invoke(
+    url: &str,
+    directory: Option<&str>,
+    options: &CloneOptions,
+) -> anyhow::Result<()> {
+    let is_path = transport::is_local(url);
+    // bundle 文件不走本地优化，但同样记录绝对路径
+    let is_bundle = is_path && Bundle::is_bundle(Path::new(url));
+    if is_path && !is_bundle {
+        source_git_dir(url)?;
+    }
+    let bare = options.bare || options.mirror;
+    let dest = match directory {
+        Some(directory) => PathBuf::from(directory),
+        None => PathBuf::from(default_directory(url, bare)),
+    };
+    let existed = dest.exists();
+    if existed && std::fs::read_dir(&dest).map_or(true, |mut entries| entries.next().is_some()) {
+        anyhow::bail!(
+            "destination path '{}' already exists and is not an empty directory.",
+            dest.display()
+        );
+    }
+    if !options.quiet {
+        match bare {
+            true => eprintln!("Cloning into bare repository '{}'...", dest.display()),
+            false => eprintln!("Cloning into '{}'...", dest.display()),
+        }
+    }
+    let local = options.local && is_path && !is_bundle;
+    let depth = match options.depth {
+        Some(_) if local => {
+            eprintln!("warning: --depth is ignored in local clones; use file:// instead.");
+            None
+        }
+        depth => depth,
+    };
+    let filter = match &options.filter {
+        Some(_) if local => {
+            eprintln!("warning: --filter is ignored in local clones; use file:// instead.");
+            None
+        }
+        filter => filter.clone(),
+    };
+    // 本地路径记成绝对路径，URL 原样记录
+    let remote_url = match is_path {
+        false => url.to_string(),
+        true => std::env::current_dir()?
+            .join(url)
+            .to_string_lossy()
+            .into_owned(),
+    };
+
+    std::fs::create_dir_all(&dest).with_context(|| format!("create {}", dest.display()))?;
+    let fetch_args = FetchArgs {
+        depth,
+        filter,
+        quiet: options.quiet,
+        ..FetchArgs::default()
+    };
+    let cloned = clone_into(url, &remote_url, &dest, local, fetch_args, options).await;
+    if cloned.is_err() {
+        // 与 git 一样失败时不留下半个仓库
+        if existed {
+            if let Ok(entries) = std::fs::read_dir(&dest) {
+                for entry in entries.flatten() {
+                    let _ = match entry.file_type() {
+                        Ok(file_type) if file_type.is_dir() => {
+                            std::fs::remove_dir_all(entry.path())
+                        }
+                        _ => std::fs::remove_file(entry.path()),
+                    };
+                }
+            }
+        } else {
+            let _ = std::fs::remove_dir_all(&dest);
+        }
+    }
+    cloned
+}
+
+/// 在已经建好的空目录 dest 中建立仓库：对象、引用、配置，最后检出
+async fn clone_into(
+    url: &str,
+    remote_url: &str,
+    dest: &Path,
+    local: bool,
+    mut fetch_args: FetchArgs,
+    options: &CloneOptions,
+) -> anyhow::Result<()> {
+    let bare = options.bare || options.mirror;
+    // 与 git 一样，即使本地路径忽略了 --depth 也只取一个分支
+    let single_branch = options.depth.is_some() && !options.mirror;
+    let mut transport = Transport::connect(url, local)?;
+    let prefixes: &[&str] = match options.mirror {
+        true => &[],
+        false => &["HEAD", "refs/heads/", "refs/tags/"],
+    };
+    let listed = transport.list_refs(prefixes).await?;
+    let remote_refs: BTreeMap<String, [u8; 20]> = listed
+        .iter()
+        .filter(|entry| entry.name.starts_with("refs/"))
+        .map(|entry| (entry.name.clone(), entry.id))
+        .collect();
+    let head = listed.iter().find(|entry| entry.name == "HEAD");
+    // 远程 HEAD 分离（或是 bundle 中的 HEAD）时的哈希
+    let detached_head = head
+        .filter(|head| head.symref.is_none() && head.id != [0; 20])
+        .map(|head| head.id);
+    let remote_head = match detached_head {
+        Some(id) => guess_remote_head(id, &listed),
+        None => head.and_then(|head| head.symref.clone()),
+    };
+    let default_branch = remote_head
+        .as_deref()
+        .and_then(|head| head.strip_prefix("refs/heads/"))
+        .map(str::to_string);
+    let target = match &options.branch {
+        Some(name) if remote_refs.contains_key(&format!("refs/heads/{name}")) => {
+            Some(Target::Branch(name.clone()))
+        }
+        Some(name) if remote_refs.contains_key(&format!("refs/tags/{name}")) => {
+            Some(Target::Tag(name.clone()))
+        }
+        Some(name) => anyhow::bail!("Remote branch {name} not found in upstream origin"),
+        None => match default_branch
+            .clone()
+            .filter(|branch| remote_refs.contains_key(&format!("refs/heads/{branch}")))
+        {
+            Some(branch) => Some(Target::Branch(branch)),
+            None => detached_head.map(Target::Detached),
+        },
+    };
+    let target_ref = match &target {
+        Some(Target::Branch(branch)) => Some(format!("refs/heads/{branch}")),
+        Some(Target::Tag(tag)) => Some(format!("refs/tags/{tag}")),
+        Some(Target::Detached(_)) | None => None,
+    };
+
+    let git_dir = if bare {
+        dest.to_path_buf()
+    } else {
+        dest.join(".git")
+    };
+    for dir in ["objects", "refs/heads", "refs/tags"] {
+        std::fs::create_dir_all(git_dir.join(dir))
+            .with_context(|| format!("create {}", git_dir.join(dir).display()))?;
+    }
+
+    // 对象：本地优化时整个复制或硬链接对象目录，否则经由 git-upload-pack 取回需要的
+    let objects = git_dir.join("objects");
+    let mut fetched: BTreeMap<String, [u8; 20]> = remote_refs.clone();
+    let tips: Vec<[u8; 20]> = match &target {
+        Some(Target::Detached(id)) => vec![*id],
+        _ => target_ref
+            .iter()
+            .filter_map(|name| remote_refs.get(name).copied())
+            .collect(),
+    };
+    match &transport {
+        Transport::Local(source) => {
+            let source_objects = source.join("objects");
+            copy_objects(&source_objects, &objects, !options.no_hardlinks)?;
+            if source.join("shallow").is_file() {
+                std::fs::copy(source.join("shallow"), git_dir.join("shallow"))
+                    .context("copy shallow")?;
+            }
+            // 只取一个分支时只保留目标分支和指向它的历史中的提交的标签
+            if single_branch {
+                let commits = reachable_commits(&source_objects, &tips).await?;
+                let mut kept = BTreeMap::new();
+                for (name, id) in fetched {
+                    let wanted = Some(&name) == target_ref.as_ref()
+                        || (name.starts_with("refs/tags/")
+                            && commits.contains(&peel_in(&source_objects, id).await?));
+                    if wanted {
+                        kept.insert(name, id);
+                    }
+                }
+                fetched = kept;
+            }
+        }
+        Transport::Remote(_) | Transport::Bundle(_) => {
+            let wants: Vec<[u8; 20]> = match single_branch {
+                true => tips.clone(),
+                false => fetched.values().chain(&tips).copied().collect(),
+            };
+            fetch_args.include_tag = single_branch;
+            transport.fetch(&git_dir, &wants, &fetch_args).await?;
+            // 只取一个分支时只保留目标分支和服务端顺带发送了的标签
+            if single_branch {
+                let peeled: BTreeMap<&str, [u8; 20]> = listed
+                    .iter()
+                    .map(|entry| (entry.name.as_str(), entry.peeled.unwrap_or(entry.id)))
+                    .collect();
+                fetched.retain(|name, id| {
+                    Some(name) == target_ref.as_ref()
+                        || (name.starts_with("refs/tags/")
+                            && object_exists_in(&objects, id)
+                            && object_exists_in(&objects, &peeled[name.as_str()]))
+                });
+            }
+        }
+    }
+
+    // 配置
+    let config_path = git_dir.join("config");
+    // 部分克隆缺少对象，要求读取它的 git 认识 promisor 远程。与 git 一样本地路径忽略了过滤条件也这样配置
+    let partial = options.filter.as_ref();
+    let mut settings = vec![
+        (
+            "core.repositoryformatversion",
+            (partial.is_some() as u8).to_string(),
+        ),
+        ("core.filemode", "true".to_string()),
+        ("core.bare", bare.to_string()),
+    ];
+    if !bare {
+        settings.push(("core.logallrefupdates", "true".to_string()));
+    }
+    settings.push(("remote.origin.url", remote_url.to_string()));
+    if options.mirror {
+        settings.push(("remote.origin.fetch", "+refs/*:refs/*".to_string()));
+        settings.push(("remote.origin.mirror", "true".to_string()));
+    } else if !bare {
+        let refspec = match (&target, single_branch) {
+            (Some(Target::Branch(branch)), true) => {
+                format!("+refs/heads/{branch}:refs/remotes/origin/{branch}")
+            }
+            (Some(Target::Tag(tag)), true) => format!("+refs/tags/{tag}:refs/tags/{tag}"),
+            _ => "+refs/heads/*:refs/remotes/origin/*".to_string(),
+        };
+        settings.push(("remote.origin.fetch", refspec));
+    }
+    if let Some(filter) = partial {
+        settings.push(("remote.origin.promisor", "true".to_string()));
+        settings.push(("remote.origin.partialclonefilter", filter.clone()));
+    }
+    for (name, value) in &settings {
+        config::set(&config_path, name, value)?;
+    }
+    // 空仓库时按远程 HEAD 指向的未诞生分支配置上游
+    let upstream = match &target {
+        Some(Target::Branch(branch)) => Some(branch),
+        Some(Target::Tag(_) | Target::Detached(_)) => None,
+        None => default_branch.as_ref(),
+    };
+    if let (false, Some(branch)) = (bare, upstream) {
+        config::set(&config_path, &format!("branch.{branch}.remote"), "origin")?;
+        let merge = format!("refs/heads/{branch}");
+        config::set(&config_path, &format!("branch.{branch}.merge"), &merge)?;
+    }
+
+    // 引用
+    for (name, id) in &fetched {
+        let local_name = if options.mirror {
+            name.clone()
+        } else if let Some(branch) = name.strip_prefix("refs/heads/") {
+            match bare {
+                true => name.clone(),
+                false => format!("refs/remotes/origin/{branch}"),
+            }
+        } else if name.starts_with("refs/tags/") {
+            name.clone()
+        } else {
+            continue;
+        };
+        write_ref_file(&git_dir, &local_name, &hex::encode(id))?;
+    }
+    let checkout = match &target {
+        Some(Target::Branch(branch)) => remote_refs[&format!("refs/heads/{branch}")],
+        Some(Target::Tag(tag)) => {
+            peel_in(&objects, remote_refs[&format!("refs/tags/{tag}")]).await?
+        }
+        Some(Target::Detached(id)) => *id,
+        None => [0; 20],
+    };
+    let head = match (&target, bare) {
+        (Some(Target::Branch(branch)), _) => format!("ref: refs/heads/{branch}"),
+        (Some(Target::Tag(_)), false) | (Some(Target::Detached(_)), _) => hex::encode(checkout),
+        _ => match &remote_head {
+            Some(head) => format!("ref: {head}"),
+            None => "ref: refs/heads/main".to_string(),
+        },
+    };
+    write_ref_file(&git_dir, "HEAD", &head)?;
+    match (&target, remote_refs.is_empty()) {
+        (Some(_), _) => {}
+        (None, true) => eprintln!("warning: You appear to have cloned an empty repository."),
+        (None, false) => {
+            eprintln!("warning: remote HEAD refers to nonexistent ref, unable to checkout")
+        }
+    }
+    if local && !options.quiet {
+        eprintln!("done.");
+    }
+    if bare {
+        return Ok(());
+    }
+    if let Some(Target::Branch(branch)) = &target {
+        write_ref_file(
+            &git_dir,
+            &format!("refs/heads/{branch}"),
+            &hex::encode(checkout),
+        )?;
+    }
+    let default_fetched = default_branch
+        .as_ref()
+        .filter(|branch| fetched.contains_key(&format!("refs/heads/{branch}")));
+    if let Some(branch) = default_fetched {
+        let target = format!("ref: refs/remotes/origin/{branch}");
+        write_ref_file(&git_dir, "refs/remotes/origin/HEAD", &target)?;
+    }
+
+    let _cwd = EnterDir::new(&dest.to_string_lossy())?;
+    if target.is_some() {
+        let message = format!("clone: from {remote_url}");
+        let ident = committer_ident()?;
+        let mut logs = vec!["HEAD".to_string()];
+        if let Some(Target::Branch(branch)) = &target {
+            logs.push(format!("refs/heads/{branch}"));
+        }
+        if let Some(branch) = default_fetched {
+            let id = remote_refs[&format!("refs/heads/{branch}")];
+            refs::append_reflog("refs/remotes/origin/HEAD", &[0; 20], &id, &ident, &message)?;
+        }
+        for name in logs {
+            refs::append_reflog(&name, &[0; 20], &checkout, &ident, &message)?;
+        }
+    }
+    if options.no_checkout || target.is_none() {
+        return Ok(());
+    }
+    let files = flatten_tree(&Commit::read(&checkout).await?.tree).await?;
+    if partial.is_some() {
+        // 补取检出需要、但按过滤条件没有取回的文件
+        let missing: Vec<[u8; 20]> = files
+            .values()
+            .filter(|entry| entry.mode != Mode::Gitlink && !object_exists(&hex::encode(entry.hash)))
+            .map(|entry| entry.hash)
+            .collect();
+        let args = FetchArgs {
+            no_haves: true,
+            quiet: options.quiet,
+            ..FetchArgs::default()
+        };
+        transport.fetch(Path::new(".git"), &missing, &args).await?;
+    }
+    let mut index = worktree::switch(
+        &Index::default(),
+        &BTreeMap::new(),
+        &files,
+        "checkout",
+        false,
+    )
+    .await?;
+    index.write().await?;
+    if let (Some(Target::Tag(_) | Target::Detached(_)), false) = (&target, options.quiet) {
+        eprintln!(
+            "Note: switching to '{}'.\n\n\
+             You are in 'detached HEAD' state. You can look around, make experimental\n\
+             changes and commit them, and you can discard any commits you make in this\n\
+             state without impacting any branches by switching back to a branch.\n\n\
+             If you want to create a new branch to retain commits you create, you may\n\
+             do so (now or later) by using -c with the switch command. Example:\n\n  \
+             git switch -c <new-branch-name>\n\n\
+             Or undo this operation with:\n\n  \
+             git switch -\n\n\
+             Turn off this advice by setting config variable advice.detachedHead to false\n",
+            hex::encode(checkout)
+        );
+    }
+    Ok(())
+}
+
+/// 远程没有告知 HEAD 指向哪个分支时（如 bundle），与 git 一样猜测：依次是默认分支 main、
+/// master，然后是第一个与 HEAD 指向同一提交的分支
+fn guess_remote_head(head: [u8; 20], listed: &[RemoteRef]) -> Option<String> {
+    let points_to_head = |entry: &&RemoteRef| entry.id == head;
+    ["refs/heads/main", "refs/heads/master"]
+        .iter()
+        .find_map(|name| {
+            listed
+                .iter()
+                .filter(points_to_head)
+                .find(|entry| entry.name == *name)
+        })
+        .or_else(|| {
+            listed
+                .iter()
+                .filter(points_to_head)
+                .find(|entry| entry.name.starts_with("refs/heads/"))
+        })
+        .map(|entry| entry.name.clone())
+}
+
+/// url 对应的默认目录名：最后一级路径去掉 `.git` 或 `.bundle`，裸仓库再加上 `.git`
+fn default_directory(url: &str, bare: bool) -> String {
+    let url = url.trim_end_matches('/');
+    let url = url.strip_suffix("/.git").unwrap_or(url);
+    let name = url.rsplit('/').next().unwrap_or(url);
+    let name = name
+        .strip_suffix(".git")
+        .or_else(|| name.strip_suffix(".bundle"))
+        .unwrap_or(name);
+    match bare {
+        true => format!("{name}.git"),
+        false => name.to_string(),
+    }
+}
+
+/// 本地仓库的 git 目录：工作区中的 `.git`，或者 url 本身是裸仓库
+pub(crate) fn source_git_dir(url: &str) -> anyhow::Result<PathBuf> {
+    let path = url.strip_prefix("file://").unwrap_or(url);
+    if let Some(git_dir) = submodule::git_dir(path) {
+        return Ok(git_dir);
+    }
+    let path = PathBuf::from(path);
+    anyhow::ensure!(
+        path.join("objects").is_dir() && path.join("HEAD").is_file(),
+        "repository '{url}' does not exist"
+    );
+    Ok(path)
+}
+
+/// 把 from 下的对象文件放到 to 中，已有的跳过。hardlink 时硬链接，失败（如跨文件系统）时复制
+pub(crate) fn copy_objects(from: &Path, to: &Path, hardlink: bool) -> anyhow::Result<()> {
+    let entries = std::fs::read_dir(from).with_context(|| format!("read {}", from.display()))?;
+    for entry in entries {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            std::fs::create_dir_all(&target)
+                .with_context(|| format!("create {}", target.display()))?;
+            copy_objects(&entry.path(), &target, hardlink)?;
+        } else if !target.exists() {
+            if hardlink && std::fs::hard_link(entry.path(), &target).is_ok() {
+                continue;
+            }
+            std::fs::copy(entry.path(), &target)
+                .with_context(|| format!("copy to {}", target.display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// 从 tips 出发可以到达的所有提交
+async fn reachable_commits(objects: &Path, tips: &[[u8; 20]]) -> anyhow::Result<HashSet<[u8; 20]>> {
+    let mut commits = HashSet::new();
+    let mut stack = Vec::new();
+    for tip in tips {
+        stack.push(peel_in(objects, *tip).await?);
+    }
+    while let Some(id) = stack.pop() {
+        if !commits.insert(id) {
+            continue;
+        }
+        let (_, data) = read_object_in(objects, &hex::encode(id)).await?;
+        stack.extend(Commit::parse(&data)?.parents);
+    }
+    Ok(commits)
+}
+
+/// 在 objects 目录中剥离标签，得到最终指向的对象
+pub(crate) async fn peel_in(objects: &Path, mut id: [u8; 20]) -> anyhow::Result<[u8; 20]> {
+    loop {
+        let (kind, data) = read_object_in(objects, &hex::encode(id)).await?;
+        if kind != Kind::Tag {
+            return Ok(id);
+        }
+        id = tag_target(&data)?;
+    }
+}
+
+pub(crate) fn tag_target(data: &[u8]) -> anyhow::Result<[u8; 20]> {
+    let text = String::from_utf8_lossy(data);
+    let target = text
+        .lines()
+        .find_map(|line| line.strip_prefix("object "))
+        .context("tag has no object header")?;
+    parse_hex(target)
+}
+
+/// 原样复制一个松散对象文件
+pub(crate) fn copy_object(from: &Path, to: &Path, id: &[u8; 20]) -> anyhow::Result<()> {
+    let hex = hex::encode(id);
+    let (dir, file) = hex.split_at(2);
+    let target = to.join(dir).join(file);
+    if target.exists() {
+        return Ok(());
+    }
+    std::fs::create_dir_all(to.join(dir)).with_context(|| format!("create {}", to.display()))?;
+    std::fs::copy(from.join(dir).join(file), &target)
+        .with_context(|| format!("copy object {hex}"))?;
+    Ok(())
+}
+
+/// 直接在 git_dir 中写入引用文件，content 是哈希或 `ref: <引用>`
+fn write_ref_file(git_dir: &Path, name: &str, content: &str) -> anyhow::Result<()> {
+    let path = git_dir.join(name);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).with_context(|| format!("create {}", parent.display()))?;
+    }
+    std::fs::write(&path, format!("{content}\n")).with_context(|| format!("write {name}"))
+}
+
+/// 切换当前目录，离开作用域时切换回来
+pub(crate) struct EnterDir {
+    previous: PathBuf,
+}
+
+impl EnterDir {
+    pub(crate) fn new(path: &str) -> anyhow::Result<EnterDir> {
+        let previous = std::env::current_dir()?;
+        std::env::set_current_dir(path).with_context(|| format!("cd {path}"))?;
+        Ok(EnterDir { previous })
+    }
+}
+
+impl Drop for EnterDir {
+    fn drop(&mut self) {
+        let _ = std::env::set_current_dir(&self.previous);
+    }
+}
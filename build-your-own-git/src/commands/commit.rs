@@ -1,4 +1,5 @@
 use std::{
+    collections::BTreeSet,
     env,
     ffi::CStr,
     io::{BufRead, Cursor, Read, Write},
@@ -14,65 +15,665 @@ use tempfile::NamedTempFile;
 use tokio::fs;
 
 use crate::{
-    commands,
-    objects::{Kind, Object, hash_to_reader},
+    commands::{
+        self,
+        merge::{MERGE_HEAD, MERGE_MSG, remove_merge_state},
+        status::{CommitStatus, commit_status},
+    },
+    diff::{
+        diff_trees_with_renames, flatten_tree, index_files,
+        lines::LineDiffOptions,
+        read_worktree_file,
+        rename::RenameOptions,
+        stat::{file_stats, write_shortstat, write_summary},
+    },
+    editor::{COMMIT_EDITMSG, edit_file},
+    hook,
+    index::{INDEX_FILE, INDEX_LOCK, Index, IndexEntry},
+    objects::{
+        Kind, Object,
+        commit::{Commit, Signature, gpg, parse_hex},
+        find_unique_abbrev, hash_to_reader, read_object,
+        tree::write_tree_from_files,
+    },
+    refs,
+    revision::resolve,
+    worktree::hash_worktree_file,
 };
+pub(crate) struct CommitTreeOptions {
+    /// `-p`，按顺序写成 parent 行，重复的忽略
+    pub(crate) parents: Vec<String>,
+    /// `-m`，可以给出多次，每个是单独的段落
+    pub(crate) messages: Vec<String>,
+    /// `-F`，依次读取的说明文件，`-` 表示标准输入，接在 `-m` 的段落后面
+    pub(crate) files: Vec<String>,
+    /// `-S`，用这个密钥签名，空字符串表示默认的密钥
+    pub(crate) sign: Option<String>,
+}
+
+/// 用已有的树创建提交对象。说明原样写入，没有 `-m` 和 `-F` 时从标准输入读取。
+/// 只返回新提交的哈希，由调用方输出
 pub(crate) async fn invoke_commit_tree(
-    tree_sha: String,
-    message: String,
-    parent: Option<String>,
-) -> Result<[u8; 20], anyhow::Error> {
-    let mut buf = Vec::new();
-    writeln!(buf, "tree {tree_sha}")?;
-    if let Some(parent) = parent {
-        writeln!(buf, "parent {parent}")?;
+    tree: &str,
+    options: CommitTreeOptions,
+) -> anyhow::Result<[u8; 20]> {
+    let tree = resolve_object(tree, Kind::Tree).await?;
+    let mut parents: Vec<String> = Vec::new();
+    for parent in &options.parents {
+        let parent = hex::encode(resolve_object(parent, Kind::Commit).await?);
+        if parents.contains(&parent) {
+            eprintln!("error: duplicate parent {parent} ignored");
+        } else {
+            parents.push(parent);
+        }
+    }
+
+    let mut message = join_paragraphs(&options.messages);
+    for file in &options.files {
+        if !message.is_empty() {
+            message.push('\n');
+        }
+        message.push_str(&read_message_file(file)?);
+    }
+    if options.messages.is_empty() && options.files.is_empty() {
+        message = read_message_file("-")?;
+    }
+    write_commit_object(
+        &hex::encode(tree),
+        &message,
+        &parents,
+        None,
+        options.sign.as_deref(),
+    )
+    .await
+}
+
+/// 解析 rev 并确认对象存在且类型是 kind
+async fn resolve_object(rev: &str, kind: Kind) -> anyhow::Result<[u8; 20]> {
+    let id = resolve(rev)
+        .await
+        .map_err(|_| anyhow::anyhow!("not a valid object name {rev}"))?;
+    let hex = hex::encode(id);
+    let (actual, _) = read_object(&hex)
+        .await
+        .map_err(|_| anyhow::anyhow!("{hex} is not a valid object"))?;
+    anyhow::ensure!(actual == kind, "{hex} is not a valid '{kind}' object");
+    Ok(id)
+}
+
+/// 把 `-m` 给出的各段连接起来，段落之间空一行，每段以换行结尾
+fn join_paragraphs(paragraphs: &[String]) -> String {
+    let mut message = String::new();
+    for paragraph in paragraphs {
+        if !message.is_empty() {
+            message.push('\n');
+        }
+        message.push_str(paragraph);
+        if !message.ends_with('\n') {
+            message.push('\n');
+        }
     }
-    let name = "Levio-Z";
-    let email = "67247011+Levio-z@users.noreply.github.com";
+    message
+}
+
+/// 读取 `-F` 给出的说明文件，`-` 表示标准输入
+fn read_message_file(file: &str) -> anyhow::Result<String> {
+    if file == "-" {
+        let mut input = String::new();
+        std::io::stdin()
+            .read_to_string(&mut input)
+            .context("could not read log from standard input")?;
+        Ok(input)
+    } else {
+        std::fs::read_to_string(file).with_context(|| format!("could not read log file '{file}'"))
+    }
+}
+
+/// 提交者，也是默认的作者
+const COMMITTER_NAME: &str = "Levio-Z";
+const COMMITTER_EMAIL: &str = "67247011+Levio-z@users.noreply.github.com";
+
+/// 提交者和当前时间：`名字 <邮箱> 时间 时区`，也用在 reflog 中
+pub(crate) fn committer_ident() -> anyhow::Result<String> {
     let time = std::time::SystemTime::now()
         .duration_since(std::time::SystemTime::UNIX_EPOCH)
         .context("current system time is before UNIX epoch")?
         .as_secs();
-    writeln!(buf, "author {name} <{email}> {time} +0800")?;
-    writeln!(buf, "committer {name} <{email}> {time} +0800")?;
+    Ok(format!("{COMMITTER_NAME} <{COMMITTER_EMAIL}> {time} +0800"))
+}
+
+/// 写入提交对象，parents 按顺序写成 parent 行，合并提交有多个。
+/// author 不为空时沿用原来的作者（如 cherry-pick），否则作者就是提交者
+pub(crate) async fn write_commit(
+    tree_sha: &str,
+    message: &str,
+    parents: &[String],
+    author: Option<&Signature>,
+) -> Result<[u8; 20], anyhow::Error> {
+    write_commit_object(tree_sha, &format!("{message}\n"), parents, author, None).await
+}
+
+/// 同 write_commit，但 message 原样写入，不在末尾补换行。
+/// sign 不为空时用这个密钥（空字符串为默认密钥）签名，签名写成 gpgsig 头部
+async fn write_commit_object(
+    tree_sha: &str,
+    message: &str,
+    parents: &[String],
+    author: Option<&Signature>,
+    sign: Option<&str>,
+) -> Result<[u8; 20], anyhow::Error> {
+    let mut buf = Vec::new();
+    writeln!(buf, "tree {tree_sha}")?;
+    for parent in parents {
+        writeln!(buf, "parent {parent}")?;
+    }
+    let committer = committer_ident()?;
+    match author {
+        Some(author) => writeln!(buf, "author {author}")?,
+        None => writeln!(buf, "author {committer}")?,
+    }
+    writeln!(buf, "committer {committer}")?;
     writeln!(buf)?;
-    writeln!(buf, "{message}")?;
+    buf.extend_from_slice(message.as_bytes());
+    if let Some(key) = sign {
+        let signature = gpg::sign(&buf, key, &format!("{COMMITTER_NAME} <{COMMITTER_EMAIL}>"))
+            .context("failed to sign commit object")?;
+        gpg::insert_header(&mut buf, "gpgsig", &signature);
+    }
 
     let mut commit = Object {
         kind: Kind::Commit,
         expected_size: buf.len() as u64,
         reader: Cursor::new(buf),
     };
-    let hash = commit.write_object().await?;
-    println!("{}", hex::encode(hash));
-    Ok(hash)
+    commit.write_object().await
 }
 
-pub(crate) async fn invoke_commit(message: String) -> Result<(), anyhow::Error> {
-    let head_ref = std::fs::read_to_string(".git/HEAD").context("read HEAD")?;
-    let Some(head_ref) = head_ref.strip_prefix("ref: ") else {
-        anyhow::bail!("refusing to commit onto detached HEAD");
+/// 提交后的摘要：`[main 1a2b3c4] subject`，initial（提交前还没有 HEAD）时标出根提交，
+/// 作者与提交者不同时显示作者，show_author_date 时显示作者日期，
+/// 最后是相对第一个父提交的 --shortstat 和 --summary
+pub(crate) async fn print_commit_summary(
+    id: &[u8; 20],
+    initial: bool,
+    show_author_date: bool,
+) -> anyhow::Result<()> {
+    let commit = Commit::read(id).await?;
+    let branch = match refs::head_branch()? {
+        Some(branch) => branch
+            .strip_prefix("refs/heads/")
+            .unwrap_or(&branch)
+            .to_string(),
+        None => "detached HEAD".to_string(),
+    };
+    let root = if initial { " (root-commit)" } else { "" };
+    let stdout = std::io::stdout();
+    let mut out = std::io::BufWriter::new(stdout.lock());
+    writeln!(
+        out,
+        "[{branch}{root} {}] {}",
+        find_unique_abbrev(&hex::encode(id), 7),
+        commit.subject()
+    )?;
+    let (author, committer) = (&commit.author, &commit.committer);
+    if (&author.name, &author.email) != (&committer.name, &committer.email) {
+        writeln!(out, " Author: {} <{}>", author.name, author.email)?;
+    }
+    if show_author_date {
+        writeln!(out, " Date: {}", author.format_date())?;
+    }
+    // 与 git 一样，合并提交不显示统计
+    if commit.parents.len() > 1 {
+        out.flush()?;
+        return Ok(());
+    }
+    let parent_tree = match commit.parents.first() {
+        Some(parent) => Some(Commit::read(parent).await?.tree),
+        None => None,
+    };
+    let changes = diff_trees_with_renames(
+        parent_tree.as_ref(),
+        Some(&commit.tree),
+        true,
+        RenameOptions::renames(),
+    )
+    .await?;
+    write_shortstat(
+        &mut out,
+        &file_stats(&changes, LineDiffOptions::default()).await?,
+    )?;
+    write_summary(&mut out, &changes)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// `--cleanup`：如何整理提交说明
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Cleanup {
+    /// 使用编辑器时同 Strip，否则同 Whitespace
+    Default,
+    /// 去掉注释行、行尾空白、开头和结尾的空行，合并连续的空行
+    Strip,
+    /// 同 Strip 但保留注释行
+    Whitespace,
+    /// 原样保留
+    Verbatim,
+    /// 同 Whitespace，使用编辑器时还去掉剪刀线和它后面的所有内容
+    Scissors,
+}
+
+impl Cleanup {
+    pub(crate) fn from_str(s: &str) -> anyhow::Result<Cleanup> {
+        match s {
+            "default" => Ok(Cleanup::Default),
+            "strip" => Ok(Cleanup::Strip),
+            "whitespace" => Ok(Cleanup::Whitespace),
+            "verbatim" => Ok(Cleanup::Verbatim),
+            "scissors" => Ok(Cleanup::Scissors),
+            _ => anyhow::bail!("Invalid cleanup mode {s}"),
+        }
+    }
+}
+
+pub(crate) struct CommitOptions {
+    /// `-m`，可以给出多次，每个是单独的段落
+    pub(crate) messages: Vec<String>,
+    /// `-F`，从文件读取提交说明，`-` 表示标准输入
+    pub(crate) file: Option<String>,
+    /// `-a`，先暂存所有已跟踪文件的修改和删除
+    pub(crate) all: bool,
+    /// `--amend`，替换 HEAD：沿用它的父提交和作者，默认沿用它的说明
+    pub(crate) amend: bool,
+    /// `--allow-empty`，允许与父提交的树相同
+    pub(crate) allow_empty: bool,
+    /// `--no-edit`，直接使用已有的说明，不打开编辑器
+    pub(crate) no_edit: bool,
+    pub(crate) cleanup: Cleanup,
+    /// `-n`/`--no-verify`，不运行 pre-commit 和 commit-msg 钩子
+    pub(crate) no_verify: bool,
+}
+
+/// 剪刀线，`--cleanup=scissors` 时去掉它和后面的内容
+const CUT_LINE: &str = "# ------------------------ >8 ------------------------\n";
+
+/// 用暂存区的内容创建提交并移动 HEAD。没有要提交的修改时输出状态并返回 false。
+/// 还没有 `.git/index` 时与原来一样提交整个工作区
+pub(crate) async fn invoke_commit(mut options: CommitOptions) -> anyhow::Result<bool> {
+    let merge_heads = std::fs::read_to_string(MERGE_HEAD).ok();
+    anyhow::ensure!(
+        !(options.amend && merge_heads.is_some()),
+        "You are in the middle of a merge -- cannot amend."
+    );
+    let snapshot = !Path::new(INDEX_FILE).exists();
+    let mut index = if !snapshot {
+        Index::read().await?
+    } else {
+        // 快照已经包含所有修改，-a 不需要再暂存，也不会因此写出 `.git/index`
+        options.all = false;
+        snapshot_worktree().await?
+    };
+    if options.all {
+        stage_tracked(&mut index).await?;
+    }
+    if index.has_conflicts() {
+        // 与 git 一样先列出冲突的路径
+        let unmerged: BTreeSet<&str> = index
+            .entries
+            .iter()
+            .filter(|entry| entry.stage > 0)
+            .map(|entry| entry.path.as_str())
+            .collect();
+        for path in unmerged {
+            println!("U\t{path}");
+        }
+        anyhow::bail!("Committing is not possible because you have unmerged files.");
+    }
+    let head = refs::resolve_ref("HEAD")?;
+    let amended = match head {
+        Some(head) if options.amend => Some(Commit::read(&head).await?),
+        None if options.amend => anyhow::bail!("You have nothing to amend."),
+        _ => None,
+    };
+    let parents = match &amended {
+        Some(amended) => amended.parents.clone(),
+        None => {
+            let mut parents: Vec<[u8; 20]> = head.into_iter().collect();
+            for line in merge_heads.iter().flat_map(|heads| heads.lines()) {
+                parents.push(parse_hex(line.trim())?);
+            }
+            parents
+        }
+    };
+
+    // 提交说明的来源：-m、-F、被修改的提交、合并的说明
+    let from_message = !options.messages.is_empty() || options.file.is_some();
+    let use_editor = !from_message && !options.no_edit;
+
+    // -a 暂存的结果先写到 index.lock，钩子通过 GIT_INDEX_FILE 看到的就是要提交的内容
+    let lock = if options.all {
+        index.write_lock().await?;
+        Some(IndexLock)
+    } else {
+        None
     };
-    // 去除末尾的换行符
-    let head_ref = head_ref.trim_end();
-    let parent = if let Ok(hash) = std::fs::read_to_string(format!(".git/{head_ref}")) {
-        Some(hash.trim().to_string())
+    // 与 git 一样，index.lock 以绝对路径交给钩子
+    let index_file = if options.all {
+        env::current_dir()?.join(INDEX_LOCK).display().to_string()
     } else {
+        INDEX_FILE.to_string()
+    };
+    let editor_env = if use_editor {
         None
+    } else {
+        Some(("GIT_EDITOR", ":"))
     };
+    let hook_env: Vec<(&str, &str)> = std::iter::once(("GIT_INDEX_FILE", index_file.as_str()))
+        .chain(editor_env)
+        .collect();
+    if !options.no_verify {
+        if !hook::run_with("pre-commit", &[], &hook_env, None)? {
+            return Ok(false);
+        }
+        // 钩子可能修改了暂存区，提交工作区的快照时没有暂存区可读
+        if !snapshot {
+            index = Index::read_from(&index_file).await?;
+        }
+    }
 
-    // 计算hash
-    let tree_hash = crate::commands::write_tree::invoke(PathBuf::from("."))
-        .await
-        .context("write tree")?;
+    let files = index_files(&index);
+    let tree = write_tree_from_files(
+        files
+            .iter()
+            .map(|(path, side)| (path.as_str(), side.mode, side.hash)),
+    )
+    .await?;
+    let mut message = if let Some(file) = &options.file {
+        read_message_file(file)?
+    } else if !options.messages.is_empty() {
+        join_paragraphs(&options.messages)
+    } else if let Some(amended) = &amended {
+        amended.message.clone()
+    } else if merge_heads.is_some() {
+        std::fs::read_to_string(MERGE_MSG).unwrap_or_default()
+    } else {
+        String::new()
+    };
+    let cleanup = match options.cleanup {
+        Cleanup::Default if use_editor => Cleanup::Strip,
+        Cleanup::Default => Cleanup::Whitespace,
+        Cleanup::Scissors if !use_editor => Cleanup::Whitespace,
+        cleanup => cleanup,
+    };
+    if cleanup != Cleanup::Verbatim {
+        message = stripspace(&message, false);
+    }
 
-    // 提交hash
-    let commit_hash = invoke_commit_tree(hex::encode(tree_hash), message, parent)
+    if use_editor {
+        let author = amended.as_ref().map(|commit| &commit.author);
+        message.push_str(&template(&index, cleanup, merge_heads.is_some(), author).await?);
+    }
+    std::fs::write(COMMIT_EDITMSG, &message).context("write COMMIT_EDITMSG")?;
+
+    // 与 git 一样先写好 COMMIT_EDITMSG 再检查是否有要提交的修改
+    let committable = match parents.first() {
+        Some(parent) => Commit::read(parent).await?.tree != tree,
+        None => !files.is_empty(),
+    };
+    // 合并提交和修改合并提交时允许与第一个父提交相同
+    let merge = merge_heads.is_some() || parents.len() > 1;
+    if !committable && !merge && !options.allow_empty {
+        let status = commit_status(
+            &index,
+            CommitStatus {
+                template: false,
+                amend: options.amend,
+            },
+        )
+        .await?;
+        print!("{status}");
+        if options.amend {
+            eprintln!(
+                "You asked to amend the most recent commit, but doing so would make\n\
+                 it empty. You can repeat your command with --allow-empty, or you can\n\
+                 remove the commit entirely with \"git reset HEAD^\"."
+            );
+        }
+        return Ok(false);
+    }
+    let source: &[&str] = if from_message {
+        &["message"]
+    } else if amended.is_some() {
+        &["commit", "HEAD"]
+    } else if merge_heads.is_some() {
+        &["merge"]
+    } else {
+        &[]
+    };
+    let args: Vec<&str> = [COMMIT_EDITMSG].iter().chain(source).copied().collect();
+    if !hook::run_with("prepare-commit-msg", &args, &hook_env, None)? {
+        return Ok(false);
+    }
+    if use_editor {
+        edit_file(Path::new(COMMIT_EDITMSG), false)?;
+    }
+    if !options.no_verify && !hook::run_with("commit-msg", &[COMMIT_EDITMSG], &hook_env, None)? {
+        return Ok(false);
+    }
+    // 钩子和编辑器都可能修改了说明
+    message = std::fs::read_to_string(COMMIT_EDITMSG).context("read COMMIT_EDITMSG")?;
+    let message = match cleanup {
+        Cleanup::Verbatim => message,
+        Cleanup::Strip => stripspace(&message, true),
+        Cleanup::Scissors => {
+            let cut = if message.starts_with(CUT_LINE) {
+                Some(0)
+            } else {
+                message.find(&format!("\n{CUT_LINE}")).map(|at| at + 1)
+            };
+            stripspace(&message[..cut.unwrap_or(message.len())], false)
+        }
+        Cleanup::Default | Cleanup::Whitespace => stripspace(&message, false),
+    };
+    anyhow::ensure!(
+        cleanup == Cleanup::Verbatim || !message.is_empty(),
+        "Aborting commit due to empty commit message."
+    );
+
+    let parents: Vec<String> = parents.iter().map(hex::encode).collect();
+    let author = amended.as_ref().map(|commit| &commit.author);
+    let id = write_commit_object(&hex::encode(tree), &message, &parents, author, None).await?;
+    refs::update_head(&id)?;
+    if let Some(lock) = lock {
+        lock.commit()?;
+    }
+    if merge_heads.is_some() {
+        remove_merge_state()?;
+    }
+    // post-commit 运行时暂存区已经提交，退出码不影响结果
+    let hook_env: Vec<(&str, &str)> = std::iter::once(("GIT_INDEX_FILE", INDEX_FILE))
+        .chain(editor_env)
+        .collect();
+    hook::run_with("post-commit", &[], &hook_env, None)?;
+    print_commit_summary(&id, head.is_none(), options.amend).await?;
+    Ok(true)
+}
+
+/// `-a` 时写在 index.lock 中的暂存结果，提交成功后替换 `.git/index`，中途退出时丢弃
+struct IndexLock;
+
+impl IndexLock {
+    fn commit(self) -> anyhow::Result<()> {
+        std::fs::rename(INDEX_LOCK, INDEX_FILE).context("rename .git/index.lock")?;
+        std::mem::forget(self);
+        Ok(())
+    }
+}
+
+impl Drop for IndexLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(INDEX_LOCK);
+    }
+}
+
+/// 没有暂存区时把整个工作区当作暂存区：写入工作区的树，再按它展开成暂存区记录
+async fn snapshot_worktree() -> anyhow::Result<Index> {
+    let mut index = Index::default();
+    let Some(mut tree) = commands::write_tree::write_tree(PathBuf::from("."))
         .await
-        .context("commit tree")?;
-    let commit_hash = hex::encode(commit_hash);
-    std::fs::write(format!(".git/{head_ref}"), &commit_hash)
-        .with_context(|| format!("update HEAD reference target {head_ref}"))?;
-    println!("HEAD is now at {commit_hash}");
+        .context("write tree")?
+    else {
+        return Ok(index);
+    };
+    let tree = tree.write_object().await?;
+    for (path, side) in flatten_tree(&tree).await? {
+        let metadata = std::fs::symlink_metadata(&path).ok();
+        index.entries.push(IndexEntry::new(
+            &path,
+            side.mode,
+            side.hash,
+            0,
+            metadata.as_ref(),
+        ));
+    }
+    Ok(index)
+}
+
+/// `-a`：已跟踪文件的修改写入暂存区，工作区中删除的从暂存区删除，冲突的路径按工作区的内容解决
+async fn stage_tracked(index: &mut Index) -> anyhow::Result<()> {
+    let paths: BTreeSet<String> = index
+        .entries
+        .iter()
+        .map(|entry| entry.path.clone())
+        .collect();
+    let staged = index_files(index);
+    let mut entries = Vec::new();
+    for path in &paths {
+        let Some(side) = hash_worktree_file(path, staged.get(path).map(|side| side.mode)).await?
+        else {
+            continue;
+        };
+        if staged.get(path) == Some(&side) {
+            entries.extend(
+                index
+                    .entries
+                    .iter()
+                    .filter(|entry| entry.path == *path)
+                    .cloned(),
+            );
+            continue;
+        }
+        let data = read_worktree_file(path)?;
+        let mut blob = Object {
+            kind: Kind::Blob,
+            expected_size: data.len() as u64,
+            reader: data.as_slice(),
+        };
+        let hash = blob.write_object().await?;
+        let metadata = std::fs::symlink_metadata(path).with_context(|| format!("stat {path}"))?;
+        entries.push(IndexEntry::new(path, side.mode, hash, 0, Some(&metadata)));
+    }
+    index.entries = entries;
     Ok(())
 }
+
+/// 编辑器中提交说明后面的注释：说明如何填写、被修改提交的作者信息和当前状态。
+/// author 是 `--amend` 时被修改提交的作者
+async fn template(
+    index: &Index,
+    cleanup: Cleanup,
+    merging: bool,
+    author: Option<&Signature>,
+) -> anyhow::Result<String> {
+    let mut out = String::new();
+    if merging {
+        if cleanup == Cleanup::Scissors {
+            out.push_str(&scissors());
+        }
+        out.push_str(
+            "#\n\
+             # It looks like you may be committing a merge.\n\
+             # If this is not correct, please run\n\
+             #\tgit update-ref -d MERGE_HEAD\n\
+             # and try again.\n\n",
+        );
+    }
+    out.push('\n');
+    match cleanup {
+        Cleanup::Scissors if !merging => out.push_str(&scissors()),
+        Cleanup::Scissors => {}
+        cleanup => out.push_str(instructions(cleanup)),
+    }
+    out.push_str("#\n");
+    if let Some(author) = author {
+        if (author.name.as_str(), author.email.as_str()) != (COMMITTER_NAME, COMMITTER_EMAIL) {
+            out.push_str(&format!(
+                "# Author:    {} <{}>\n",
+                author.name, author.email
+            ));
+        }
+        out.push_str(&format!("# Date:      {}\n#\n", author.format_date()));
+    }
+    let status = commit_status(
+        index,
+        CommitStatus {
+            template: true,
+            amend: author.is_some(),
+        },
+    )
+    .await?;
+    for line in status.lines() {
+        match line {
+            "" => out.push_str("#\n"),
+            line if line.starts_with('\t') => out.push_str(&format!("#{line}\n")),
+            line => out.push_str(&format!("# {line}\n")),
+        }
+    }
+    Ok(out)
+}
+
+/// 编辑器中说明如何填写提交说明的注释，rebase 编辑说明时也用它。
+/// 注释行会被去掉（Strip）和会被保留时说法不同
+pub(crate) fn instructions(cleanup: Cleanup) -> &'static str {
+    match cleanup {
+        Cleanup::Strip => {
+            "# Please enter the commit message for your changes. Lines starting\n\
+             # with '#' will be ignored, and an empty message aborts the commit.\n"
+        }
+        Cleanup::Default | Cleanup::Whitespace | Cleanup::Verbatim | Cleanup::Scissors => {
+            "# Please enter the commit message for your changes. Lines starting\n\
+             # with '#' will be kept; you may remove them yourself if you want to.\n\
+             # An empty message aborts the commit.\n"
+        }
+    }
+}
+
+fn scissors() -> String {
+    format!(
+        "{CUT_LINE}\
+         # Do not modify or remove the line above.\n\
+         # Everything below it will be ignored.\n"
+    )
+}
+
+/// 整理提交说明（git 的 stripspace）：去掉每行末尾的空白、开头和结尾的空行，
+/// 连续的空行合并成一行；strip_comments 时去掉 `#` 开头的注释行
+pub(crate) fn stripspace(message: &str, strip_comments: bool) -> String {
+    let mut out = String::new();
+    let mut blank = false;
+    for line in message.lines() {
+        if strip_comments && line.starts_with('#') {
+            continue;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            blank = true;
+            continue;
+        }
+        if blank && !out.is_empty() {
+            out.push('\n');
+        }
+        blank = false;
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
@@ -0,0 +1,207 @@
+use std::{
+    collections::BTreeMap,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::Context;
+
+use crate::{
+    diff::{
+        Side, diff_maps, diff_trees, flatten_tree, index_files,
+        patch::{PatchOptions, write_patch},
+        read_worktree_file,
+        rename::{Detect, RenameOptions, detect_renames},
+        side_content,
+        stat::{FileStat, stat_width, write_stat},
+        unchanged_files, worktree_files,
+    },
+    index::Index,
+    objects::{Kind, Mode, Object, read_object},
+    revision::{merge_bases, peel, resolve},
+};
+
+pub(crate) struct DiffOptions {
+    /// 比较暂存区而不是工作区
+    pub(crate) cached: bool,
+    pub(crate) patch: PatchOptions,
+    pub(crate) renames: RenameOptions,
+    /// 输出 --stat 统计而不是补丁
+    pub(crate) stat: bool,
+}
+
+/// 比较的一侧
+#[derive(Debug, Clone, Copy)]
+enum Source {
+    /// 树对象，None 表示空树
+    Tree(Option<[u8; 20]>),
+    Index,
+    Worktree,
+}
+
+pub(crate) async fn invoke(
+    args: &[String],
+    paths: &[String],
+    options: DiffOptions,
+) -> anyhow::Result<()> {
+    // 没有 `--` 时，能解析成提交的参数是版本，从第一个不能解析的参数开始都是路径
+    let mut revisions = Vec::new();
+    let mut paths = paths.to_vec();
+    for (n, arg) in args.iter().enumerate() {
+        let base = arg.split("..").next().unwrap_or(arg);
+        if resolve(if base.is_empty() { "HEAD" } else { base })
+            .await
+            .is_ok()
+        {
+            revisions.push(arg.as_str());
+            continue;
+        }
+        for path in &args[n..] {
+            anyhow::ensure!(
+                Path::new(path).exists(),
+                "ambiguous argument '{path}': unknown revision or path not in the working tree."
+            );
+        }
+        paths.splice(0..0, args[n..].iter().cloned());
+        break;
+    }
+
+    let (old, new) = match (revisions.as_slice(), options.cached) {
+        ([], false) => (Source::Index, Source::Worktree),
+        ([], true) => (Source::Tree(head_tree().await?), Source::Index),
+        ([range], _) if range.contains("...") => {
+            let (a, b) = range.split_once("...").expect("checked");
+            let a = commit_or_head(a).await?;
+            let b = commit_or_head(b).await?;
+            let base = *merge_bases(a, &[b])
+                .await?
+                .first()
+                .context("no merge base found")?;
+            (
+                Source::Tree(Some(peel(base, Some(Kind::Tree)).await?)),
+                Source::Tree(Some(peel(b, Some(Kind::Tree)).await?)),
+            )
+        }
+        ([range], _) if range.contains("..") => {
+            let (a, b) = range.split_once("..").expect("checked");
+            (
+                Source::Tree(Some(tree_or_head(a).await?)),
+                Source::Tree(Some(tree_or_head(b).await?)),
+            )
+        }
+        ([rev], cached) => (
+            Source::Tree(Some(tree_or_head(rev).await?)),
+            if cached {
+                Source::Index
+            } else {
+                Source::Worktree
+            },
+        ),
+        ([a, b], _) => (
+            Source::Tree(Some(tree_or_head(a).await?)),
+            Source::Tree(Some(tree_or_head(b).await?)),
+        ),
+        _ => anyhow::bail!("usage: diff [<options>] [<commit> [<commit>]] [--] [<path>...]"),
+    };
+
+    let index = Index::read().await?;
+    let (mut changes, old_files) = match (old, new) {
+        (Source::Tree(a), Source::Tree(b)) => {
+            (diff_trees(a.as_ref(), b.as_ref(), true).await?, None)
+        }
+        _ => {
+            let old_files = load_files(old, &index).await?;
+            let changes = diff_maps(&old_files, &load_files(new, &index).await?);
+            (changes, Some(old_files))
+        }
+    };
+    // 先按路径过滤再检测重命名，与 git 一致
+    changes.retain(|change| matches_paths(&change.path, &paths));
+    let unchanged = if options.renames.detect == Detect::CopiesHarder {
+        let old_files = match old_files {
+            Some(files) => files,
+            None => load_files(old, &index).await?,
+        };
+        let mut files = unchanged_files(old_files, &changes);
+        files.retain(|path, _| matches_paths(path, &paths));
+        files
+    } else {
+        BTreeMap::new()
+    };
+    let changes = detect_renames(
+        changes,
+        &unchanged,
+        options.renames,
+        matches!(new, Source::Worktree),
+    )
+    .await?;
+
+    let stdout = std::io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let mut stats = Vec::new();
+    for change in &changes {
+        let old_data = load_content(change.old, old, change.old_path()).await?;
+        let new_data = load_content(change.new, new, &change.path).await?;
+        if options.stat {
+            stats.push(FileStat::new(
+                change,
+                &old_data,
+                &new_data,
+                options.patch.line,
+            ));
+        } else {
+            write_patch(&mut out, change, &old_data, &new_data, options.patch)?;
+        }
+    }
+    write_stat(&mut out, &stats, stat_width(), "")?;
+    out.flush()?;
+    Ok(())
+}
+
+async fn head_tree() -> anyhow::Result<Option<[u8; 20]>> {
+    match resolve("HEAD").await {
+        Ok(head) => Ok(Some(peel(head, Some(Kind::Tree)).await?)),
+        // 还没有提交时和空树比较
+        Err(_) => Ok(None),
+    }
+}
+
+/// `A..B` 中省略的一侧表示 HEAD
+async fn commit_or_head(rev: &str) -> anyhow::Result<[u8; 20]> {
+    let rev = if rev.is_empty() { "HEAD" } else { rev };
+    peel(resolve(rev).await?, Some(Kind::Commit)).await
+}
+
+async fn tree_or_head(rev: &str) -> anyhow::Result<[u8; 20]> {
+    let rev = if rev.is_empty() { "HEAD" } else { rev };
+    peel(resolve(rev).await?, Some(Kind::Tree)).await
+}
+
+/// 按路径展开一侧的所有文件；工作区只包含暂存区中跟踪的文件
+async fn load_files(source: Source, index: &Index) -> anyhow::Result<BTreeMap<String, Side>> {
+    let mut files = BTreeMap::new();
+    match source {
+        Source::Tree(None) => {}
+        Source::Tree(Some(tree)) => files = flatten_tree(&tree).await?,
+        Source::Index => files = index_files(index),
+        Source::Worktree => files = worktree_files(index).await?,
+    }
+    Ok(files)
+}
+
+async fn load_content(side: Option<Side>, source: Source, path: &str) -> anyhow::Result<Vec<u8>> {
+    match (side, source) {
+        (None, _) => Ok(Vec::new()),
+        (Some(side), Source::Worktree) if side.mode != Mode::Gitlink => read_worktree_file(path),
+        (Some(side), _) => side_content(side).await,
+    }
+}
+
+/// 路径等于某个路径参数或位于其目录下
+pub(crate) fn matches_paths(path: &str, specs: &[String]) -> bool {
+    specs.is_empty()
+        || specs.iter().any(|spec| {
+            let spec = spec.trim_end_matches('/');
+            spec == "." || path == spec || path.starts_with(&format!("{spec}/"))
+        })
+}
@@ -0,0 +1,77 @@
+use std::io::{BufWriter, Write};
+
+use crate::{
+    diff::{Change, diff_trees_with_renames, quote_path, rename::RenameOptions},
+    objects::{Kind, commit::Commit},
+    revision::{peel, resolve},
+};
+
+/// 变更列表的输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Format {
+    Raw,
+    NameOnly,
+    NameStatus,
+}
+
+pub(crate) struct DiffTreeOptions {
+    pub(crate) recursive: bool,
+    pub(crate) root: bool,
+    pub(crate) format: Format,
+    pub(crate) renames: RenameOptions,
+}
+
+pub(crate) async fn invoke(
+    old: &str,
+    new: Option<&str>,
+    options: DiffTreeOptions,
+) -> anyhow::Result<()> {
+    // 两个参数时直接比较两棵树；一个参数时比较提交和它的父提交，并输出提交 id
+    let (header, old_tree, new_tree) = match new {
+        Some(new) => {
+            let old_tree = peel(resolve(old).await?, Some(Kind::Tree)).await?;
+            let new_tree = peel(resolve(new).await?, Some(Kind::Tree)).await?;
+            (None, Some(old_tree), new_tree)
+        }
+        None => {
+            let id = peel(resolve(old).await?, Some(Kind::Commit)).await?;
+            let commit = Commit::read(&id).await?;
+            let parent_tree = match commit.parents.as_slice() {
+                [] if options.root => None,
+                [parent] => Some(Commit::read(parent).await?.tree),
+                // 没有 --root 的根提交和合并提交都不输出
+                _ => return Ok(()),
+            };
+            (Some(id), parent_tree, commit.tree)
+        }
+    };
+
+    let changes = diff_trees_with_renames(
+        old_tree.as_ref(),
+        Some(&new_tree),
+        options.recursive,
+        options.renames,
+    )
+    .await?;
+    if changes.is_empty() {
+        return Ok(());
+    }
+    let stdout = std::io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    if let Some(id) = header {
+        writeln!(out, "{}", hex::encode(id))?;
+    }
+    for change in &changes {
+        writeln!(out, "{}", format_change(change, options.format))?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+pub(crate) fn format_change(change: &Change, format: Format) -> String {
+    match format {
+        Format::Raw => change.format_raw(),
+        Format::NameOnly => quote_path(&change.path),
+        Format::NameStatus => change.format_name_status(),
+    }
+}
@@ -0,0 +1,502 @@
+//! fetch：从另一个仓库取回当前仓库没有的对象，按 refspec 更新远程跟踪分支和标签，
+//! 并把取回的引用写入 `.git/FETCH_HEAD`。远程是本地路径时直接读它的目录，URL 时经由 git-upload-pack
+
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::Context;
+
+use crate::{
+    commands::commit::committer_ident,
+    config::Config,
+    objects::{find_unique_abbrev, object_exists},
+    refs,
+    refspec::{self, Refspec},
+    remote::{self, Remote},
+    revision::is_ancestor,
+    transport::{self, FetchArgs, Transport},
+};
+
+#[derive(Debug, Default)]
+pub(crate) struct FetchOptions {
+    /// `--prune`：删除远程已经没有的远程跟踪分支
+    pub(crate) prune: bool,
+    /// `--tags`：取回远程的所有标签
+    pub(crate) tags: bool,
+    /// `--force`：所有引用都强制更新
+    pub(crate) force: bool,
+    pub(crate) quiet: bool,
+    /// 写入 reflog 的动作，与 git 一样是 `fetch` 加上命令行参数
+    pub(crate) reflog_action: String,
+}
+
+/// 取回的一个远程引用
+#[derive(Debug, Clone)]
+struct FetchRef {
+    /// 远程引用的完整名称，没有 refspec 时是 HEAD
+    remote: String,
+    id: [u8; 20],
+    /// 要更新的本地引用，只写入 FETCH_HEAD 时为 None
+    local: Option<String>,
+    force: bool,
+    /// 在 FETCH_HEAD 中是否可以合并；None 表示不写入 FETCH_HEAD，如顺带更新的远程跟踪分支
+    merge: Option<bool>,
+}
+
+/// 从 remote（远程名称或仓库路径，默认是当前分支的远程或 origin）取回 refspecs 指定的引用，
+/// 没有 refspecs 时使用远程配置的 fetch refspec。有引用被拒绝更新时返回 false
+pub(crate) async fn invoke(
+    remote: Option<&str>,
+    refspecs: &[String],
+    options: &FetchOptions,
+) -> anyhow::Result<bool> {
+    // 与 git 一样先清空 FETCH_HEAD，下面出错时不留下上次的结果
+    std::fs::write(".git/FETCH_HEAD", "").context("write FETCH_HEAD")?;
+    let config = Config::read()?;
+    let name = match remote {
+        Some(name) => name.to_string(),
+        None => remote::default_name(&config)?,
+    };
+    let remote = Remote::get(&config, &name)?;
+    let url = remote
+        .as_ref()
+        .map_or(name.clone(), |remote| remote.url.clone());
+    let mut transport = match Transport::connect(&url, true) {
+        Ok(transport) => transport,
+        Err(_) if transport::is_local(&url) => {
+            anyhow::bail!("'{name}' does not appear to be a git repository")
+        }
+        Err(e) => return Err(e),
+    };
+    let listed = transport.list_refs(&[]).await?;
+    let remote_head = listed
+        .iter()
+        .find(|entry| entry.name == "HEAD" && entry.id != [0; 20])
+        .map(|entry| entry.id);
+    // 按远程列出的顺序，通配的 refspec 按这个顺序取回；bundle 与其他传输的顺序不同
+    let ordered: Vec<(String, [u8; 20])> = listed
+        .iter()
+        .filter(|entry| entry.name.starts_with("refs/"))
+        .map(|entry| (entry.name.clone(), entry.id))
+        .collect();
+    let remote_refs: BTreeMap<String, [u8; 20]> = ordered.iter().cloned().collect();
+    // 标签最终指向的对象，用于自动跟随标签
+    let peeled: BTreeMap<&str, [u8; 20]> = listed
+        .iter()
+        .map(|entry| (entry.name.as_str(), entry.peeled.unwrap_or(entry.id)))
+        .collect();
+
+    let configured = remote.map(|remote| remote.fetch).unwrap_or_default();
+    let cmdline: Vec<Refspec> = refspecs
+        .iter()
+        .map(|spec| Refspec::parse(spec))
+        .collect::<anyhow::Result<_>>()?;
+    let mut specs = if cmdline.is_empty() {
+        configured.clone()
+    } else {
+        cmdline.clone()
+    };
+    if options.tags {
+        specs.push(Refspec::parse("refs/tags/*:refs/tags/*")?);
+    }
+
+    let mut entries: Vec<FetchRef> = Vec::new();
+    if !cmdline.is_empty() {
+        // 命令行上的 refspec 都可以合并
+        for spec in cmdline.iter().filter(|spec| !spec.negative) {
+            if spec.is_glob() {
+                for (name, id) in ordered.iter().filter(|(name, _)| spec.matches(name)) {
+                    entries.push(FetchRef {
+                        remote: name.clone(),
+                        id: *id,
+                        local: spec.map(name),
+                        force: spec.force,
+                        merge: Some(true),
+                    });
+                }
+                continue;
+            }
+            let (name, id) = dwim_remote_ref(remote_head, &remote_refs, &spec.src)
+                .with_context(|| format!("couldn't find remote ref {}", spec.src))?;
+            let local = spec.dst.as_ref().map(|dst| expand_dst(dst, &name));
+            entries.push(FetchRef {
+                remote: name,
+                id,
+                local,
+                force: spec.force,
+                merge: Some(true),
+            });
+        }
+        // 与 git 一样，顺带按配置的 refspec 更新取回的引用对应的远程跟踪分支
+        let fetched: Vec<FetchRef> = entries.clone();
+        for entry in &fetched {
+            for spec in configured.iter().filter(|spec| !spec.negative) {
+                if let Some(local) = spec.map(&entry.remote) {
+                    entries.push(FetchRef {
+                        local: Some(local),
+                        force: spec.force,
+                        merge: None,
+                        ..entry.clone()
+                    });
+                }
+            }
+        }
+    } else if !configured.is_empty() {
+        // 当前分支配置了从这个远程合并的分支时，只有它可以合并
+        let merge = match refs::head_branch()?
+            .as_deref()
+            .and_then(|branch| branch.strip_prefix("refs/heads/"))
+        {
+            Some(branch)
+                if config.get(&format!("branch.{branch}.remote")) == Some(name.clone()) =>
+            {
+                config.get(&format!("branch.{branch}.merge"))
+            }
+            _ => None,
+        };
+        for spec in configured.iter().filter(|spec| !spec.negative) {
+            for (remote_name, id) in ordered.iter().filter(|(name, _)| spec.matches(name)) {
+                entries.push(FetchRef {
+                    remote: remote_name.clone(),
+                    id: *id,
+                    local: spec.map(remote_name),
+                    force: spec.force,
+                    merge: Some(merge.as_ref() == Some(remote_name)),
+                });
+            }
+        }
+    } else {
+        entries.push(FetchRef {
+            remote: "HEAD".to_string(),
+            id: remote_head.context("couldn't find remote ref HEAD")?,
+            local: None,
+            force: false,
+            merge: Some(true),
+        });
+    }
+    if options.tags {
+        for (name, id) in remote_refs.range("refs/tags/".to_string()..) {
+            if !name.starts_with("refs/tags/") {
+                break;
+            }
+            entries.push(FetchRef {
+                remote: name.clone(),
+                id: *id,
+                local: Some(name.clone()),
+                force: false,
+                merge: Some(false),
+            });
+        }
+    }
+    entries.retain(|entry| !refspec::is_excluded(&specs, &entry.remote));
+    let mut unique: Vec<FetchRef> = Vec::new();
+    for entry in entries {
+        let duplicate = unique
+            .iter()
+            .any(|other| other.remote == entry.remote && other.local == entry.local);
+        if !duplicate {
+            unique.push(entry);
+        }
+    }
+    let mut entries = unique;
+
+    if let Some(branch) = refs::head_branch()? {
+        if entries
+            .iter()
+            .any(|entry| entry.local.as_ref() == Some(&branch))
+        {
+            anyhow::bail!(
+                "refusing to fetch into branch '{branch}' checked out at '{}'",
+                std::env::current_dir()?.display()
+            );
+        }
+    }
+
+    // 没有 --tags 时自动跟随指向已有对象的标签，本地已有同名标签的跳过。
+    // 与 git 一样，只有使用的 refspec 会更新本地引用时才跟随
+    let stores = entries
+        .iter()
+        .any(|entry| entry.local.is_some() && entry.merge.is_some());
+    let follow_tags = !options.tags && stores;
+    let wants: Vec<[u8; 20]> = entries.iter().map(|entry| entry.id).collect();
+    let args = FetchArgs {
+        include_tag: follow_tags,
+        quiet: options.quiet,
+        ..FetchArgs::default()
+    };
+    let git_dir = Path::new(".git");
+    transport.fetch(git_dir, &wants, &args).await?;
+    if follow_tags {
+        let mut followed = Vec::new();
+        for (name, id) in &remote_refs {
+            if !name.starts_with("refs/tags/")
+                || refs::resolve_ref(name)?.is_some()
+                || entries.iter().any(|entry| &entry.remote == name)
+                || refspec::is_excluded(&specs, name)
+            {
+                continue;
+            }
+            if object_exists(&hex::encode(peeled[name.as_str()])) {
+                followed.push((name, *id));
+            }
+        }
+        // 服务端没有顺带发送的标签对象再取一次
+        let missing: Vec<[u8; 20]> = followed
+            .iter()
+            .map(|(_, id)| *id)
+            .filter(|id| !object_exists(&hex::encode(id)))
+            .collect();
+        if !missing.is_empty() {
+            transport.fetch(git_dir, &missing, &args).await?;
+        }
+        for (name, id) in followed {
+            entries.push(FetchRef {
+                remote: name.clone(),
+                id,
+                local: Some(name.clone()),
+                force: false,
+                merge: Some(false),
+            });
+        }
+    }
+
+    let mut lines: Vec<Line> = Vec::new();
+    if options.prune {
+        for local in stale_refs(&specs, &remote_refs)? {
+            refs::delete_ref(&local)?;
+            lines.push(Line::new('-', "[deleted]", "(none)", shorten(&local)));
+        }
+    }
+
+    // 按 FETCH_HEAD 的顺序：可以合并的、不可合并的，最后是顺带更新的
+    entries.sort_by_key(|entry| match entry.merge {
+        Some(true) => 0,
+        Some(false) => 1,
+        None => 2,
+    });
+    let ident = committer_ident()?;
+    let mut ok = true;
+    for entry in &entries {
+        let remote_short = shorten(&entry.remote);
+        let Some(local) = &entry.local else {
+            let kind = match kind(&entry.remote) {
+                "tag" => "tag",
+                _ => "branch",
+            };
+            lines.push(Line::new('*', kind, remote_short, "FETCH_HEAD"));
+            continue;
+        };
+        let line = |flag, summary: &str| Line::new(flag, summary, remote_short, shorten(local));
+        let force = entry.force || options.force;
+        let old = refs::resolve_ref(local)?;
+        if old == Some(entry.id) {
+            continue;
+        }
+        let abbrev = |id: &[u8; 20]| find_unique_abbrev(&hex::encode(id), 7);
+        let (line, message) = match old {
+            None => {
+                let (what, message) = if entry.remote.starts_with("refs/tags/") {
+                    ("[new tag]", "storing tag")
+                } else if entry.remote.starts_with("refs/heads/") {
+                    ("[new branch]", "storing head")
+                } else {
+                    ("[new ref]", "storing ref")
+                };
+                (line('*', what), message)
+            }
+            // 已有的标签只在强制时更新
+            Some(_) if local.starts_with("refs/tags/") && !force => {
+                ok = false;
+                lines.push(line('!', "[rejected]").suffix("would clobber existing tag"));
+                continue;
+            }
+            Some(_) if local.starts_with("refs/tags/") => {
+                (line('t', "[tag update]"), "updating tag")
+            }
+            Some(old) if is_ancestor(old, entry.id).await? => {
+                let range = format!("{}..{}", abbrev(&old), abbrev(&entry.id));
+                (line(' ', &range), "fast-forward")
+            }
+            Some(old) if force => {
+                let range = format!("{}...{}", abbrev(&old), abbrev(&entry.id));
+                (line('+', &range).suffix("forced update"), "forced-update")
+            }
+            Some(_) => {
+                ok = false;
+                lines.push(line('!', "[rejected]").suffix("non-fast-forward"));
+                continue;
+            }
+        };
+        refs::write_ref(local, &entry.id)?;
+        if ["refs/heads/", "refs/remotes/", "refs/notes/"]
+            .iter()
+            .any(|prefix| local.starts_with(prefix))
+        {
+            let message = format!("{}: {message}", options.reflog_action);
+            let old = old.unwrap_or_default();
+            refs::append_reflog(local, &old, &entry.id, &ident, &message)?;
+        }
+        lines.push(line);
+    }
+
+    let display_url = trim_url(&url);
+    let fetch_head: String = entries
+        .iter()
+        .filter_map(|entry| {
+            let merge = entry.merge?;
+            let short = shorten(&entry.remote);
+            let description = match (entry.remote.as_str(), kind(&entry.remote)) {
+                ("HEAD", _) => display_url.to_string(),
+                (_, "") => format!("'{short}' of {display_url}"),
+                (_, kind) => format!("{kind} '{short}' of {display_url}"),
+            };
+            let marker = if merge { "" } else { "not-for-merge" };
+            Some(format!(
+                "{}\t{marker}\t{description}\n",
+                hex::encode(entry.id)
+            ))
+        })
+        .collect();
+    std::fs::write(".git/FETCH_HEAD", fetch_head).context("write FETCH_HEAD")?;
+
+    if !options.quiet && !lines.is_empty() {
+        // 只写入 FETCH_HEAD 的引用不参与名称列的对齐
+        let width = lines
+            .iter()
+            .filter(|line| line.local != "FETCH_HEAD")
+            .map(|line| line.remote.len())
+            .max()
+            .unwrap_or(0)
+            .max(10);
+        eprintln!("From {display_url}");
+        for line in lines {
+            let Line {
+                flag,
+                summary,
+                remote,
+                local,
+                suffix,
+            } = line;
+            eprintln!(" {flag} {summary:<17} {remote:<width$} -> {local}{suffix}");
+        }
+    }
+    Ok(ok)
+}
+
+/// 输出中的一行：` <标记> <摘要> <远程引用> -> <本地引用><说明>`
+struct Line {
+    flag: char,
+    /// `[new branch]`、`old..new` 等，占 17 列
+    summary: String,
+    remote: String,
+    local: String,
+    suffix: String,
+}
+
+impl Line {
+    fn new(flag: char, summary: &str, remote: &str, local: &str) -> Line {
+        Line {
+            flag,
+            summary: summary.to_string(),
+            remote: remote.to_string(),
+            local: local.to_string(),
+            suffix: String::new(),
+        }
+    }
+
+    fn suffix(self, reason: &str) -> Line {
+        Line {
+            suffix: format!("  ({reason})"),
+            ..self
+        }
+    }
+}
+
+/// 本地引用中按 specs 对应到远程、但远程已经没有的。符号引用不算
+fn stale_refs(
+    specs: &[Refspec],
+    remote_refs: &BTreeMap<String, [u8; 20]>,
+) -> anyhow::Result<Vec<String>> {
+    let mut stale = Vec::new();
+    for (local, _) in refs::list_refs("refs/")? {
+        if refs::read_ref(&local)?.is_some_and(|content| content.starts_with("ref: ")) {
+            continue;
+        }
+        let remote_name = specs
+            .iter()
+            .filter(|spec| !spec.negative)
+            .find_map(|spec| spec.reverse(&local));
+        if let Some(remote_name) = remote_name {
+            if !remote_refs.contains_key(&remote_name) && !refspec::is_excluded(specs, &remote_name)
+            {
+                stale.push(local);
+            }
+        }
+    }
+    Ok(stale)
+}
+
+/// 按 git 的规则在远程引用中查找简写 name：依次尝试 name、`refs/<name>`、`refs/tags/<name>`、
+/// `refs/heads/<name>`、`refs/remotes/<name>`，HEAD 是远程 HEAD 指向的提交
+fn dwim_remote_ref(
+    head: Option<[u8; 20]>,
+    remote_refs: &BTreeMap<String, [u8; 20]>,
+    name: &str,
+) -> Option<(String, [u8; 20])> {
+    if name == "HEAD" {
+        return head.map(|id| ("HEAD".to_string(), id));
+    }
+    for pattern in [
+        "{}",
+        "refs/{}",
+        "refs/tags/{}",
+        "refs/heads/{}",
+        "refs/remotes/{}",
+        "refs/remotes/{}/HEAD",
+    ] {
+        let full = pattern.replace("{}", name);
+        if let Some(id) = remote_refs.get(&full) {
+            return Some((full, *id));
+        }
+    }
+    None
+}
+
+/// 命令行上 refspec 的 dst 不以 `refs/` 开头时，按远程引用的类型补全，如 `dev` -> `refs/heads/dev`
+fn expand_dst(dst: &str, remote: &str) -> String {
+    if dst.starts_with("refs/") {
+        return dst.to_string();
+    }
+    match remote.strip_prefix("refs/tags/") {
+        Some(_) => format!("refs/tags/{dst}"),
+        None => format!("refs/heads/{dst}"),
+    }
+}
+
+/// FETCH_HEAD 中描述引用的类型
+fn kind(name: &str) -> &'static str {
+    if name.starts_with("refs/heads/") {
+        "branch"
+    } else if name.starts_with("refs/tags/") {
+        "tag"
+    } else if name.starts_with("refs/remotes/") {
+        "remote-tracking branch"
+    } else {
+        ""
+    }
+}
+
+/// 显示用的短引用名：去掉 `refs/heads/`、`refs/tags/` 或 `refs/remotes/`
+pub(crate) fn shorten(name: &str) -> &str {
+    ["refs/heads/", "refs/tags/", "refs/remotes/"]
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix))
+        .unwrap_or(name)
+}
+
+/// 显示和写入 FETCH_HEAD 的 URL：去掉末尾的 `/` 和 `.git`
+fn trim_url(url: &str) -> &str {
+    let url = url.trim_end_matches('/');
+    url.strip_suffix(".git")
+        .filter(|rest| !rest.is_empty())
+        .unwrap_or(url)
+}
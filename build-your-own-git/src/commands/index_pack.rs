@@ -0,0 +1,153 @@
+//! index-pack：给 pack 文件建索引。
+//!
+//! 读取 `<name>.pack`，还原所有 delta 算出每个对象的哈希，写出 `<name>.idx` 和 `<name>.rev`，
+//! 输出 pack 的校验和。`--stdin` 时从标准输入读取 pack，写到给出的文件或仓库的
+//! `objects/pack/pack-<校验和>.pack`；`--fix-thin` 时从本仓库补上 thin pack 缺少的基对象
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use crate::{
+    config::Config,
+    pack::{self, index},
+};
+
+pub(crate) struct IndexPackOptions {
+    /// `-o`：索引文件的路径，默认由 pack 文件名得出
+    pub(crate) output: Option<PathBuf>,
+    /// 从标准输入读取 pack
+    pub(crate) stdin: bool,
+    /// 补上 thin pack 中不在 pack 里的基对象
+    pub(crate) fix_thin: bool,
+    /// `--keep[=<msg>]`：同时写出内容为这条消息的 `.keep` 文件
+    pub(crate) keep: Option<String>,
+    /// 是否写出 `.rev`，None 时按 pack.writeReverseIndex，与较新的 git 一样默认写出
+    pub(crate) rev_index: Option<bool>,
+    /// 不写文件，只检查已有的索引与 pack 是否一致
+    pub(crate) verify: bool,
+}
+
+pub(crate) async fn invoke(
+    pack_file: Option<&Path>,
+    options: &IndexPackOptions,
+) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        !options.fix_thin || options.stdin,
+        "the option '--fix-thin' requires '--stdin'"
+    );
+    anyhow::ensure!(
+        !(options.verify && options.stdin),
+        "the options '--verify' and '--stdin' cannot be used together"
+    );
+    // 与 git 一样先检查文件名再读取 pack
+    let idx_path = match (&options.output, pack_file) {
+        (Some(output), _) => Some(output.clone()),
+        (None, Some(path)) => Some(derive(path, "pack", "idx")?),
+        (None, None) => None,
+    };
+    let rev_index = match options.rev_index {
+        Some(rev_index) => rev_index,
+        None => Config::read()
+            .unwrap_or_default()
+            .get_bool("pack.writeReverseIndex")?
+            .unwrap_or(true),
+    };
+    if let (true, Some(idx_path)) = (rev_index, &idx_path) {
+        derive(idx_path, "idx", "rev")?;
+    }
+    let mut pack = match (pack_file, options.stdin) {
+        (_, true) => {
+            anyhow::ensure!(
+                Path::new(".git").is_dir(),
+                "--stdin requires a git repository"
+            );
+            pack::read(&mut std::io::stdin().lock())?
+        }
+        (Some(path), false) => std::fs::read(path)
+            .with_context(|| format!("cannot open packfile '{}'", path.display()))?,
+        (None, false) => anyhow::bail!("a pack file is required without --stdin"),
+    };
+
+    let thin = options.fix_thin.then(|| Path::new(".git/objects"));
+    let index = index::index(&mut pack, thin).await?;
+    let idx = index::write_idx(&index);
+    let name = hex::encode(index.checksum);
+
+    if options.verify {
+        let idx_path = idx_path.expect("verify requires a pack file");
+        let existing = std::fs::read(&idx_path).with_context(|| {
+            format!(
+                "Cannot open existing pack idx file for '{}'",
+                idx_path.display()
+            )
+        })?;
+        anyhow::ensure!(
+            existing == idx,
+            "sha1 file '{}' validation error",
+            idx_path.display()
+        );
+        return Ok(());
+    }
+
+    // --stdin 而没有给出文件名时放进仓库，以校验和命名
+    let pack_path = match pack_file {
+        Some(path) => path.to_path_buf(),
+        None => {
+            let dir = Path::new(".git/objects/pack");
+            std::fs::create_dir_all(dir)?;
+            dir.join(format!("pack-{name}.pack"))
+        }
+    };
+    let idx_path = match idx_path {
+        Some(path) => path,
+        None => derive(&pack_path, "pack", "idx")?,
+    };
+    if options.stdin {
+        std::fs::write(&pack_path, &pack)
+            .with_context(|| format!("write {}", pack_path.display()))?;
+    }
+    std::fs::write(&idx_path, &idx).with_context(|| format!("write {}", idx_path.display()))?;
+    if rev_index {
+        let rev_path = derive(&idx_path, "idx", "rev")?;
+        std::fs::write(&rev_path, index::write_rev(&index))
+            .with_context(|| format!("write {}", rev_path.display()))?;
+    }
+
+    // .keep 已经存在时不覆盖，报告中也仍然是 pack
+    let mut report = "pack";
+    if let Some(message) = &options.keep {
+        let keep_path = derive(&pack_path, "pack", "keep")?;
+        match std::fs::File::create_new(&keep_path) {
+            Ok(mut file) => {
+                if !message.is_empty() {
+                    writeln!(file, "{message}")?;
+                }
+                report = "keep";
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+            Err(e) => {
+                return Err(e).with_context(|| format!("cannot write {}", keep_path.display()));
+            }
+        }
+    }
+    match options.stdin {
+        true => println!("{report}\t{name}"),
+        false => println!("{name}"),
+    }
+    Ok(())
+}
+
+/// 把 path 的 `.strip` 后缀换成 `.ext`，如 `a.pack` 换成 `a.idx`
+pub(crate) fn derive(path: &Path, strip: &str, ext: &str) -> anyhow::Result<PathBuf> {
+    let name = path.to_string_lossy();
+    match name.strip_suffix(&format!(".{strip}")) {
+        Some(base) if !base.is_empty() && !base.ends_with('/') => {
+            Ok(PathBuf::from(format!("{base}.{ext}")))
+        }
+        _ => anyhow::bail!("packfile name '{name}' does not end with '.{strip}'"),
+    }
+}
@@ -0,0 +1,295 @@
+use std::{
+    collections::HashMap,
+    io::{BufWriter, Write},
+};
+
+use anyhow::Context;
+
+use crate::{
+    diff::{
+        diff_trees_with_renames,
+        lines::LineDiffOptions,
+        rename::RenameOptions,
+        side_content,
+        stat::{FileStat, stat_width, write_stat},
+    },
+    graph::Graph,
+    objects::{Kind, commit::Commit, find_unique_abbrev, read_object},
+    refs,
+    revision::{RevWalk, Sort, peel},
+};
+
+/// `--decorate` 的引用名称格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Decorate {
+    No,
+    Short,
+    Full,
+}
+
+impl Decorate {
+    pub(crate) fn from_str(s: &str) -> anyhow::Result<Decorate> {
+        match s {
+            "no" => Ok(Decorate::No),
+            "short" | "auto" => Ok(Decorate::Short),
+            "full" => Ok(Decorate::Full),
+            _ => anyhow::bail!("invalid --decorate option: {s}"),
+        }
+    }
+}
+
+pub(crate) struct LogOptions {
+    pub(crate) graph: bool,
+    pub(crate) oneline: bool,
+    pub(crate) decorate: Decorate,
+    pub(crate) max_count: Option<usize>,
+    pub(crate) sort: Option<Sort>,
+    pub(crate) first_parent: bool,
+    /// 在每个提交后面输出 --stat 统计
+    pub(crate) stat: bool,
+    pub(crate) renames: RenameOptions,
+}
+
+pub(crate) async fn invoke(revisions: &[String], options: LogOptions) -> anyhow::Result<()> {
+    let mut walk = RevWalk::new();
+    walk.first_parent = options.first_parent;
+    // --graph 默认使用拓扑顺序
+    walk.sort = options.sort.unwrap_or(if options.graph {
+        Sort::Topo
+    } else {
+        Sort::Date
+    });
+    if revisions.is_empty() {
+        walk.push_arg("HEAD")
+            .await
+            .context("HEAD has no commits yet")?;
+    }
+    for rev in revisions {
+        walk.push_arg(rev).await?;
+    }
+    let mut commits = walk.walk().await?;
+    if let Some(max_count) = options.max_count {
+        commits.truncate(max_count);
+    }
+
+    let decorations = match options.decorate {
+        Decorate::No => HashMap::new(),
+        style => load_decorations(style).await?,
+    };
+
+    let stdout = std::io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let mut graph = options.graph.then(Graph::new);
+    for (n, id) in commits.iter().enumerate() {
+        if let Some(graph) = graph.as_mut() {
+            graph.update(*id, walk.interesting_parents(id));
+        }
+        let commit = walk.commit(id);
+        let hex = hex::encode(id);
+        let decoration = decorations
+            .get(id)
+            .map(|names| format!(" ({})", names.join(", ")))
+            .unwrap_or_default();
+
+        // 非 oneline 格式的提交之间用空行分隔
+        if n > 0 && !options.oneline {
+            if let Some(graph) = graph.as_mut() {
+                write!(out, "{}", graph.padding_line())?;
+            }
+            writeln!(out)?;
+        }
+
+        // 输出直到提交行为止的图形
+        if let Some(graph) = graph.as_mut() {
+            let lines = graph.commit_lines();
+            let (last, before) = lines.split_last().context("graph produced no lines")?;
+            for line in before {
+                writeln!(out, "{line}")?;
+            }
+            write!(out, "{last}")?;
+        }
+
+        let message = if options.oneline {
+            write!(out, "{}{decoration} ", find_unique_abbrev(&hex, 7))?;
+            commit.subject()
+        } else {
+            writeln!(out, "commit {hex}{decoration}")?;
+            if let Some(graph) = graph.as_mut() {
+                write!(out, "{}", graph.next_line().0)?;
+            }
+            medium_message(commit)
+        };
+
+        // 逐行输出说明，后续每一行前面都接上图形
+        let mut lines = message.split_inclusive('\n').peekable();
+        while let Some(line) = lines.next() {
+            write!(out, "{line}")?;
+            if let (Some(graph), Some(_)) = (graph.as_mut(), lines.peek()) {
+                write!(out, "{}", graph.next_line().0)?;
+            }
+        }
+        let newline_terminated = message.ends_with('\n');
+        if let Some(graph) = graph.as_mut() {
+            if !graph.is_commit_finished() {
+                if !newline_terminated {
+                    writeln!(out)?;
+                }
+                write!(out, "{}", graph.remainder().join("\n"))?;
+                if newline_terminated {
+                    writeln!(out)?;
+                }
+            }
+        }
+        if options.oneline {
+            writeln!(out)?;
+        }
+
+        if options.stat {
+            let stats = commit_stats(commit, options.first_parent, options.renames).await?;
+            if !stats.is_empty() {
+                let prefix = match graph.as_mut() {
+                    Some(graph) => graph.padding_line(),
+                    None => String::new(),
+                };
+                // 完整格式的说明和统计之间空一行
+                if !options.oneline {
+                    writeln!(out, "{prefix}")?;
+                }
+                write_stat(&mut out, &stats, stat_width(), &prefix)?;
+            }
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// 提交相对于父提交的统计；根提交和空树比较，合并提交只在 --first-parent 时和第一个父提交比较
+async fn commit_stats(
+    commit: &Commit,
+    first_parent: bool,
+    renames: RenameOptions,
+) -> anyhow::Result<Vec<FileStat>> {
+    let parent_tree = match commit.parents.as_slice() {
+        [] => None,
+        [parent] => Some(Commit::read(parent).await?.tree),
+        [parent, ..] if first_parent => Some(Commit::read(parent).await?.tree),
+        _ => return Ok(Vec::new()),
+    };
+    let changes =
+        diff_trees_with_renames(parent_tree.as_ref(), Some(&commit.tree), true, renames).await?;
+    let mut stats = Vec::with_capacity(changes.len());
+    for change in &changes {
+        let old = match change.old {
+            Some(side) => side_content(side).await?,
+            None => Vec::new(),
+        };
+        let new = match change.new {
+            Some(side) => side_content(side).await?,
+            None => Vec::new(),
+        };
+        stats.push(FileStat::new(
+            change,
+            &old,
+            &new,
+            LineDiffOptions::default(),
+        ));
+    }
+    Ok(stats)
+}
+
+/// git 默认的 medium 格式，不含 `commit` 行
+fn medium_message(commit: &Commit) -> String {
+    let mut buf = String::new();
+    if commit.parents.len() > 1 {
+        let parents: Vec<String> = commit
+            .parents
+            .iter()
+            .map(|p| find_unique_abbrev(&hex::encode(p), 7))
+            .collect();
+        buf.push_str(&format!("Merge: {}\n", parents.join(" ")));
+    }
+    buf.push_str(&format!(
+        "Author: {} <{}>\n",
+        commit.author.name, commit.author.email
+    ));
+    buf.push_str(&format!("Date:   {}\n\n", commit.author.format_date()));
+    // 跳过开头的空行，每行去掉行尾空白、展开制表符并缩进四格
+    for line in commit
+        .message
+        .lines()
+        .skip_while(|line| line.trim().is_empty())
+    {
+        buf.push_str("    ");
+        for c in line.trim_end().chars() {
+            if c == '\t' {
+                let width = buf.len() - buf.rfind('\n').map_or(0, |n| n + 1) - 4;
+                buf.push_str(&" ".repeat(8 - width % 8));
+            } else {
+                buf.push(c);
+            }
+        }
+        buf.push('\n');
+    }
+    // 去掉末尾空白后补一个换行
+    let len = buf.trim_end().len();
+    buf.truncate(len);
+    buf.push('\n');
+    buf
+}
+
+/// 收集每个提交上的引用名称，顺序与 git 一致：HEAD 在最前，其余按名称倒序
+async fn load_decorations(style: Decorate) -> anyhow::Result<HashMap<[u8; 20], Vec<String>>> {
+    let mut named: HashMap<[u8; 20], Vec<(String, bool)>> = HashMap::new();
+    for (name, id) in refs::list_refs("refs/")? {
+        let Ok(id) = peel(id, Some(Kind::Commit)).await else {
+            continue;
+        };
+        let is_tag = name.starts_with("refs/tags/");
+        named.entry(id).or_default().insert(0, (name, is_tag));
+    }
+
+    let head_branch = refs::head_branch()?;
+    if let Some(head) = refs::resolve_ref("HEAD")? {
+        named
+            .entry(head)
+            .or_default()
+            .insert(0, ("HEAD".to_string(), false));
+    }
+
+    let short = |name: &str| -> String {
+        if style == Decorate::Full {
+            return name.to_string();
+        }
+        ["refs/heads/", "refs/tags/", "refs/remotes/"]
+            .iter()
+            .find_map(|prefix| name.strip_prefix(prefix))
+            .unwrap_or(name)
+            .to_string()
+    };
+
+    let mut decorations = HashMap::new();
+    for (id, names) in named {
+        // HEAD 指向的分支在同一个提交上时合并显示为 `HEAD -> main`
+        let current = head_branch
+            .as_deref()
+            .filter(|branch| names.iter().any(|(name, _)| name == branch));
+        let labels = names
+            .iter()
+            .filter(|(name, _)| Some(name.as_str()) != current)
+            .map(|(name, is_tag)| {
+                let mut label = String::new();
+                if *is_tag {
+                    label.push_str("tag: ");
+                }
+                label.push_str(&short(name));
+                if let (true, Some(current)) = (name == "HEAD", current) {
+                    label.push_str(" -> ");
+                    label.push_str(&short(current));
+                }
+                label
+            })
+            .collect();
+        decorations.insert(id, labels);
+    }
+    Ok(decorations)
+}
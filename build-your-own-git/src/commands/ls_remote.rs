@@ -0,0 +1,100 @@
+//! ls-remote：列出远程仓库的引用，不取回任何对象
+
+use crate::{
+    config::Config,
+    remote::{self, Remote},
+    transport::Transport,
+};
+
+#[derive(Debug, Default)]
+pub(crate) struct LsRemoteOptions {
+    /// `--heads`：只列出 `refs/heads/` 下的引用
+    pub(crate) heads: bool,
+    /// `--tags`：只列出 `refs/tags/` 下的引用
+    pub(crate) tags: bool,
+    /// `--refs`：不列出剥离后的标签和 HEAD
+    pub(crate) refs: bool,
+    /// `--symref`：同时列出符号引用指向的引用
+    pub(crate) symref: bool,
+    /// `-q`：不输出 `From <url>`
+    pub(crate) quiet: bool,
+}
+
+/// 列出 repository（远程名称或 URL，默认是当前分支的远程或 origin）中与 patterns 匹配的引用。
+/// 没有匹配的引用时返回 false
+pub(crate) async fn invoke(
+    repository: Option<&str>,
+    patterns: &[String],
+    options: &LsRemoteOptions,
+) -> anyhow::Result<bool> {
+    let config = Config::read()?;
+    let name = match repository {
+        Some(name) => name.to_string(),
+        None => remote::default_name(&config)?,
+    };
+    let url = match Remote::get(&config, &name)? {
+        Some(remote) => remote.url,
+        None if repository.is_none() => anyhow::bail!("No remote configured to list refs from."),
+        None => name.clone(),
+    };
+    let mut prefixes = Vec::new();
+    if options.heads {
+        prefixes.push("refs/heads/");
+    }
+    if options.tags {
+        prefixes.push("refs/tags/");
+    }
+    // 与 git 一样，本地路径也经由 git-upload-pack
+    let mut transport = Transport::connect(&url, false)?;
+    let listed = transport.list_refs(&prefixes).await?;
+    if repository.is_none() && !options.quiet {
+        eprintln!("From {url}");
+    }
+
+    let mut found = false;
+    for entry in listed {
+        // 空仓库未诞生的 HEAD 没有值
+        if entry.id == [0; 20] {
+            continue;
+        }
+        if !prefixes.is_empty() && !prefixes.iter().any(|p| entry.name.starts_with(p)) {
+            continue;
+        }
+        if options.refs && !entry.name.starts_with("refs/") {
+            continue;
+        }
+        // 剥离后的标签是名为 `<标签>^{}` 的另一行，单独匹配
+        let matches =
+            |name: &str| patterns.is_empty() || patterns.iter().any(|p| tail_match(p, name));
+        if matches(&entry.name) {
+            found = true;
+            if let (true, Some(target)) = (options.symref, &entry.symref) {
+                println!("ref: {target}\t{}", entry.name);
+            }
+            println!("{}\t{}", hex::encode(entry.id), entry.name);
+        }
+        let peeled_name = format!("{}^{{}}", entry.name);
+        if let (false, Some(peeled), true) = (options.refs, entry.peeled, matches(&peeled_name)) {
+            found = true;
+            println!("{}\t{peeled_name}", hex::encode(peeled));
+        }
+    }
+    Ok(found)
+}
+
+/// 与 git 一样，pattern 匹配引用名的末尾若干级，如 `main` 匹配 `refs/heads/main`
+fn tail_match(pattern: &str, name: &str) -> bool {
+    let pattern = format!("*/{pattern}");
+    let name = format!("/{name}");
+    glob(pattern.as_bytes(), name.as_bytes())
+}
+
+/// 通配符匹配，`*` 可以匹配 `/`
+fn glob(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) => (0..=text.len()).any(|i| glob(rest, &text[i..])),
+        Some((b'?', rest)) => !text.is_empty() && glob(rest, &text[1..]),
+        Some((c, rest)) => text.first() == Some(c) && glob(rest, &text[1..]),
+    }
+}
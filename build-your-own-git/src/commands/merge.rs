@@ -0,0 +1,344 @@
+use std::{
+    collections::BTreeMap,
+    io::{BufWriter, Write},
+};
+
+use anyhow::Context;
+
+use crate::{
+    commands::commit::{CommitTreeOptions, invoke_commit_tree, stripspace},
+    diff::{
+        diff_trees_with_renames, flatten_tree, index_files,
+        lines::LineDiffOptions,
+        rename::RenameOptions,
+        stat::{file_stats, stat_width, write_stat, write_summary},
+    },
+    hook,
+    index::{INDEX_FILE, Index, IndexEntry},
+    merge::tree::{TreeMergeOptions, merge_commits},
+    objects::{
+        Kind,
+        commit::{Commit, parse_hex},
+        find_unique_abbrev,
+        tree::write_tree_from_files,
+    },
+    refs,
+    revision::{merge_bases, peel, resolve},
+    worktree,
+};
+
+/// 是否允许快进
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FastForward {
+    /// 能快进时快进，否则创建合并提交
+    Allow,
+    /// `--no-ff`，总是创建合并提交
+    Never,
+    /// `--ff-only`，不能快进时失败
+    Only,
+}
+
+pub(crate) struct MergeOptions {
+    /// `-m`，替换默认的合并说明
+    pub(crate) message: Option<String>,
+    pub(crate) ff: FastForward,
+    /// 为假时（`--no-commit`）合并成功后也不提交
+    pub(crate) commit: bool,
+    /// `--no-verify`，不运行 pre-merge-commit 和 commit-msg 钩子
+    pub(crate) no_verify: bool,
+}
+
+pub(crate) const MERGE_HEAD: &str = ".git/MERGE_HEAD";
+pub(crate) const MERGE_MSG: &str = ".git/MERGE_MSG";
+const MERGE_MODE: &str = ".git/MERGE_MODE";
+
+/// 把 rev 合并到当前分支，有冲突或钩子拒绝提交时返回 false
+pub(crate) async fn invoke(rev: &str, options: MergeOptions) -> anyhow::Result<bool> {
+    anyhow::ensure!(
+        !std::path::Path::new(MERGE_HEAD).exists(),
+        "You have not concluded your merge (MERGE_HEAD exists).\n\
+         Please, commit your changes before you merge."
+    );
+    let index = Index::read().await?;
+    anyhow::ensure!(
+        !index.has_conflicts(),
+        "Merging is not possible because you have unmerged files."
+    );
+    let theirs = peel(resolve(rev).await?, Some(Kind::Commit))
+        .await
+        .with_context(|| format!("{rev} - not something we can merge"))?;
+    let theirs_files = flatten_tree(&Commit::read(&theirs).await?.tree).await?;
+
+    // 还没有提交的分支直接指向对方
+    let Some(head) = refs::resolve_ref("HEAD")? else {
+        let index =
+            worktree::switch(&index, &BTreeMap::new(), &theirs_files, "merge", false).await?;
+        write_index(index).await?;
+        refs::update_head(&theirs)?;
+        return Ok(true);
+    };
+    // 与 git 一样，即使已经是最新的也先记下 ORIG_HEAD
+    refs::write_ref("ORIG_HEAD", &head)?;
+    let head_tree = Commit::read(&head).await?.tree;
+    let head_files = flatten_tree(&head_tree).await?;
+
+    let bases = merge_bases(head, &[theirs]).await?;
+    if bases.contains(&theirs) {
+        println!("Already up to date.");
+        return Ok(true);
+    }
+    if bases == [head] && options.ff != FastForward::Never {
+        println!(
+            "Updating {}..{}",
+            find_unique_abbrev(&hex::encode(head), 7),
+            find_unique_abbrev(&hex::encode(theirs), 7)
+        );
+        let index = worktree::switch(&index, &head_files, &theirs_files, "merge", false).await?;
+        println!("Fast-forward");
+        write_index(index).await?;
+        refs::update_head(&theirs)?;
+        print_stat(&head_tree, &Commit::read(&theirs).await?.tree).await?;
+        hook::run("post-merge", &["0"])?;
+        return Ok(true);
+    }
+    anyhow::ensure!(
+        options.ff != FastForward::Only,
+        "Not possible to fast-forward, aborting."
+    );
+
+    // 三方合并要求暂存区和 HEAD 一致
+    let staged = index_files(&index);
+    let changed: Vec<&str> = head_files
+        .keys()
+        .chain(staged.keys())
+        .filter(|path| head_files.get(*path) != staged.get(*path))
+        .map(String::as_str)
+        .collect::<std::collections::BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    anyhow::ensure!(
+        changed.is_empty(),
+        "Your local changes to the following files would be overwritten by merge:\n  {}",
+        changed.join(" ")
+    );
+
+    let result = merge_commits(head, theirs, TreeMergeOptions::new("HEAD", rev, "")).await?;
+    let mut index = worktree::switch(&index, &head_files, &result.files, "merge", false).await?;
+    index
+        .entries
+        .retain(|entry| !result.conflicts.contains_key(&entry.path));
+    for (path, stages) in &result.conflicts {
+        for (stage, side) in (1..).zip(stages) {
+            if let Some(side) = side {
+                index
+                    .entries
+                    .push(IndexEntry::new(path, side.mode, side.hash, stage, None));
+            }
+        }
+    }
+    write_index(index).await?;
+
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    for message in result.messages.values().flatten() {
+        writeln!(out, "{message}")?;
+    }
+    let custom = options.message.is_some();
+    let message = match options.message {
+        Some(message) => message,
+        None => merge_message(rev)?,
+    };
+    let mode = if options.ff == FastForward::Never {
+        "no-ff"
+    } else {
+        ""
+    };
+
+    if !result.is_clean() {
+        let mut msg = format!("{message}\n\n# Conflicts:\n");
+        for path in result.conflicts.keys() {
+            msg.push_str(&format!("#\t{path}\n"));
+        }
+        write_merge_state(&theirs, &msg, mode)?;
+        writeln!(
+            out,
+            "Automatic merge failed; fix conflicts and then commit the result."
+        )?;
+        return Ok(false);
+    }
+    // 与 git 一样，`-m` 给出的说明原样写入 MERGE_MSG，默认的说明以换行结尾
+    let merge_msg = if custom {
+        message.clone()
+    } else {
+        format!("{message}\n")
+    };
+    write_merge_state(&theirs, &merge_msg, mode)?;
+    if !options.commit {
+        eprintln!("Automatic merge went well; stopped before committing as requested");
+        return Ok(true);
+    }
+
+    // 与 git commit 一样运行钩子，说明写在 MERGE_MSG 中交给钩子修改，拒绝时保留合并状态
+    let hook_env = [("GIT_INDEX_FILE", INDEX_FILE), ("GIT_EDITOR", ":")];
+    let verified = (options.no_verify || hook::run_with("pre-merge-commit", &[], &hook_env, None)?)
+        && hook::run_with("prepare-commit-msg", &[MERGE_MSG, "merge"], &hook_env, None)?
+        && (options.no_verify || hook::run_with("commit-msg", &[MERGE_MSG], &hook_env, None)?);
+    let mut message = String::new();
+    if verified {
+        let raw = std::fs::read_to_string(MERGE_MSG).context("read MERGE_MSG")?;
+        message = stripspace(&raw, false).trim_end().to_string();
+        if message.is_empty() {
+            eprintln!("error: Empty commit message.");
+        }
+    }
+    if message.is_empty() {
+        drop(out);
+        eprintln!("Not committing merge; use 'git commit' to complete the merge.");
+        return Ok(false);
+    }
+
+    let files = result
+        .files
+        .iter()
+        .map(|(path, side)| (path.as_str(), side.mode, side.hash));
+    let tree = write_tree_from_files(files).await?;
+    let parents = vec![hex::encode(head), hex::encode(theirs)];
+    let commit = commit_merge(&tree, message, parents).await?;
+    refs::update_head(&commit)?;
+    remove_merge_state()?;
+    writeln!(out, "Merge made by the 'ort' strategy.")?;
+    drop(out);
+    print_stat(&head_tree, &tree).await?;
+    hook::run("post-merge", &["0"])?;
+    Ok(true)
+}
+
+/// `--continue`：冲突都解决后用暂存区的内容创建合并提交
+pub(crate) async fn continue_merge() -> anyhow::Result<()> {
+    let merge_heads = std::fs::read_to_string(MERGE_HEAD)
+        .map_err(|_| anyhow::anyhow!("There is no merge in progress (MERGE_HEAD missing)."))?;
+    let index = Index::read().await?;
+    anyhow::ensure!(
+        !index.has_conflicts(),
+        "Committing is not possible because you have unmerged files."
+    );
+    let head = refs::resolve_ref("HEAD")?.context("HEAD has no commits yet")?;
+    let mut parents = vec![hex::encode(head)];
+    for line in merge_heads.lines() {
+        parents.push(hex::encode(parse_hex(line.trim())?));
+    }
+
+    // 去掉注释行和多余的空行
+    let raw = std::fs::read_to_string(MERGE_MSG).unwrap_or_default();
+    let message = stripspace(&raw, true).trim_end().to_string();
+    anyhow::ensure!(
+        !message.is_empty(),
+        "Aborting commit due to empty commit message."
+    );
+
+    let files = index_files(&index);
+    let tree = write_tree_from_files(
+        files
+            .iter()
+            .map(|(path, side)| (path.as_str(), side.mode, side.hash)),
+    )
+    .await?;
+    let subject = message.lines().next().unwrap_or_default().to_string();
+    let commit = commit_merge(&tree, message, parents).await?;
+    refs::update_head(&commit)?;
+    remove_merge_state()?;
+
+    let branch = match refs::head_branch()? {
+        Some(branch) => branch
+            .strip_prefix("refs/heads/")
+            .unwrap_or(&branch)
+            .to_string(),
+        None => "detached HEAD".to_string(),
+    };
+    println!(
+        "[{branch} {}] {subject}",
+        find_unique_abbrev(&hex::encode(commit), 7)
+    );
+    Ok(())
+}
+
+/// `--abort`：丢弃合并的结果，恢复到 HEAD
+pub(crate) async fn abort() -> anyhow::Result<()> {
+    anyhow::ensure!(
+        std::path::Path::new(MERGE_HEAD).exists(),
+        "There is no merge to abort (MERGE_HEAD missing)."
+    );
+    let index = Index::read().await?;
+    let head = refs::resolve_ref("HEAD")?.context("HEAD has no commits yet")?;
+    let head_files = flatten_tree(&Commit::read(&head).await?.tree).await?;
+    let index = worktree::switch(&index, &index_files(&index), &head_files, "merge", true).await?;
+    write_index(index).await?;
+    remove_merge_state()
+}
+
+/// 经由 commit-tree 创建合并提交，父提交和树在那里检查
+async fn commit_merge(
+    tree: &[u8; 20],
+    message: String,
+    parents: Vec<String>,
+) -> anyhow::Result<[u8; 20]> {
+    let options = CommitTreeOptions {
+        parents,
+        messages: vec![message],
+        files: Vec::new(),
+        sign: None,
+    };
+    invoke_commit_tree(&hex::encode(tree), options).await
+}
+
+async fn write_index(mut index: Index) -> anyhow::Result<()> {
+    index.write().await
+}
+
+fn write_merge_state(theirs: &[u8; 20], message: &str, mode: &str) -> anyhow::Result<()> {
+    std::fs::write(MERGE_HEAD, format!("{}\n", hex::encode(theirs))).context("write MERGE_HEAD")?;
+    std::fs::write(MERGE_MSG, message).context("write MERGE_MSG")?;
+    std::fs::write(MERGE_MODE, mode).context("write MERGE_MODE")?;
+    Ok(())
+}
+
+pub(crate) fn remove_merge_state() -> anyhow::Result<()> {
+    refs::remove_files(&[MERGE_HEAD, MERGE_MSG, MERGE_MODE])
+}
+
+/// 默认的合并说明，如 `Merge branch 'topic'`，当前分支不是 main/master 时加上 ` into <分支>`
+fn merge_message(rev: &str) -> anyhow::Result<String> {
+    let kind = if refs::read_ref(&format!("refs/heads/{rev}"))?.is_some() {
+        "branch"
+    } else if refs::read_ref(&format!("refs/tags/{rev}"))?.is_some() {
+        "tag"
+    } else if refs::read_ref(&format!("refs/remotes/{rev}"))?.is_some() {
+        "remote-tracking branch"
+    } else {
+        "commit"
+    };
+    let mut message = format!("Merge {kind} '{rev}'");
+    match refs::head_branch()? {
+        Some(branch) => {
+            let branch = branch.strip_prefix("refs/heads/").unwrap_or(&branch);
+            if branch != "main" && branch != "master" {
+                message.push_str(&format!(" into {branch}"));
+            }
+        }
+        None => message.push_str(" into HEAD"),
+    }
+    Ok(message)
+}
+
+/// 合并后输出两棵树之间的 --stat 和 --summary
+async fn print_stat(old: &[u8; 20], new: &[u8; 20]) -> anyhow::Result<()> {
+    let changes =
+        diff_trees_with_renames(Some(old), Some(new), true, RenameOptions::renames()).await?;
+    let stats = file_stats(&changes, LineDiffOptions::default()).await?;
+    let stdout = std::io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    write_stat(&mut out, &stats, stat_width(), "")?;
+    write_summary(&mut out, &changes)?;
+    out.flush()?;
+    Ok(())
+}
@@ -0,0 +1,74 @@
+use anyhow::Context;
+
+use crate::{
+    objects::Kind,
+    refs,
+    revision::{fork_point, is_ancestor, merge_bases, octopus_merge_bases, peel, resolve},
+};
+
+/// merge-base 的工作模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MergeBaseMode {
+    /// 第一个提交与其余提交（视作一次合并）的最佳公共祖先
+    Default,
+    /// `--octopus`，所有提交共同的最佳公共祖先
+    Octopus,
+    /// `--is-ancestor`，只用退出码表示第一个提交是否是第二个的祖先
+    IsAncestor,
+    /// `--fork-point`，借助 reflog 找出提交从引用分叉的位置
+    ForkPoint,
+}
+
+/// 输出公共祖先，`all` 为假时只输出第一个。没有结果时返回 false
+pub(crate) async fn invoke(
+    args: &[String],
+    mode: MergeBaseMode,
+    all: bool,
+) -> anyhow::Result<bool> {
+    let bases = match mode {
+        MergeBaseMode::Default => {
+            anyhow::ensure!(args.len() >= 2, "merge-base needs at least two commits");
+            let commits = commits(args).await?;
+            merge_bases(commits[0], &commits[1..]).await?
+        }
+        MergeBaseMode::Octopus => octopus_merge_bases(&commits(args).await?).await?,
+        MergeBaseMode::IsAncestor => {
+            anyhow::ensure!(args.len() == 2, "--is-ancestor takes exactly two commits");
+            let commits = commits(args).await?;
+            return is_ancestor(commits[0], commits[1]).await;
+        }
+        MergeBaseMode::ForkPoint => {
+            anyhow::ensure!(
+                matches!(args.len(), 1 | 2),
+                "--fork-point takes a ref and an optional commit"
+            );
+            let refname =
+                refs::dwim_ref(&args[0])?.with_context(|| format!("No such ref: '{}'", args[0]))?;
+            let commit = commit(args.get(1).map_or("HEAD", String::as_str)).await?;
+            fork_point(&refname, commit).await?.into_iter().collect()
+        }
+    };
+
+    let count = if all { bases.len() } else { 1 };
+    for base in bases.iter().take(count) {
+        println!("{}", hex::encode(base));
+    }
+    Ok(!bases.is_empty())
+}
+
+async fn commits(args: &[String]) -> anyhow::Result<Vec<[u8; 20]>> {
+    let mut commits = Vec::with_capacity(args.len());
+    for arg in args {
+        commits.push(commit(arg).await?);
+    }
+    Ok(commits)
+}
+
+async fn commit(rev: &str) -> anyhow::Result<[u8; 20]> {
+    let id = resolve(rev)
+        .await
+        .with_context(|| format!("Not a valid object name {rev}"))?;
+    peel(id, Some(Kind::Commit))
+        .await
+        .with_context(|| format!("Not a valid commit name {rev}"))
+}
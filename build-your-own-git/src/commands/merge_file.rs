@@ -0,0 +1,46 @@
+use std::io::Write;
+
+use anyhow::Context;
+
+use crate::merge::{Labels, MergeOptions, merge_content};
+
+/// 三方合并 current、base、other 三个文件，返回冲突数。
+/// labels 依次对应三个文件，缺省时使用文件名
+pub(crate) fn invoke(
+    current: &str,
+    base: &str,
+    other: &str,
+    labels: &[String],
+    stdout: bool,
+    options: MergeOptions,
+) -> anyhow::Result<usize> {
+    anyhow::ensure!(labels.len() <= 3, "too many labels on the command line");
+    let names = [current, base, other];
+    let label = |n: usize| labels.get(n).map_or(names[n], String::as_str);
+
+    let mut contents = Vec::with_capacity(names.len());
+    for name in names {
+        contents.push(std::fs::read(name).with_context(|| format!("Could not open {name}"))?);
+    }
+    let result = merge_content(
+        &contents[1],
+        &contents[0],
+        &contents[2],
+        Labels {
+            ours: Some(label(0)),
+            base: Some(label(1)),
+            theirs: Some(label(2)),
+        },
+        options,
+    )
+    .with_context(|| format!("Cannot merge {current}"))?;
+
+    if stdout {
+        let mut out = std::io::stdout().lock();
+        out.write_all(&result.content)?;
+        out.flush()?;
+    } else {
+        std::fs::write(current, &result.content).with_context(|| format!("write {current}"))?;
+    }
+    Ok(result.conflicts)
+}
@@ -0,0 +1,652 @@
+//! push：把本地引用和它们需要的对象送到本地磁盘上的另一个仓库，或经由 HTTP 送到服务端的
+//! git-receive-pack。不是快进的更新默认拒绝，本地目标仓库中的钩子见 receive
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    commands::{clone::source_git_dir, commit::committer_ident, fetch::shorten},
+    config::{self, Config},
+    objects::{Kind, find_unique_abbrev, object_exists, read_object},
+    receive::{self, Command},
+    refs,
+    refspec::Refspec,
+    remote::{self, Remote},
+    revision::{self, is_ancestor},
+    transport::{self, send_pack},
+};
+
+#[derive(Debug, Default)]
+pub(crate) struct PushOptions {
+    /// `--force`：所有引用都强制更新
+    pub(crate) force: bool,
+    /// `--delete`：删除远程的这些引用
+    pub(crate) delete: bool,
+    /// `--tags`：推送所有标签
+    pub(crate) tags: bool,
+    /// `--atomic`：有一个引用不能更新时都不更新
+    pub(crate) atomic: bool,
+    /// `--no-verify`：不运行 pre-push 钩子
+    pub(crate) no_verify: bool,
+    /// `--set-upstream`：把推送的分支设为本地分支的上游
+    pub(crate) set_upstream: bool,
+    pub(crate) quiet: bool,
+    /// `--force-with-lease` 的参数：`<ref>:<expect>`、`<ref>`，不带参数时为空字符串
+    pub(crate) force_with_lease: Vec<String>,
+}
+
+/// 推送的目标
+enum Target {
+    /// 本地仓库的 git 目录：直接复制对象，在其中应用更新
+    Local(PathBuf),
+    /// 服务端的 git-receive-pack
+    Remote(send_pack::Connection),
+}
+
+/// 推送的一个引用
+#[derive(Debug)]
+struct PushRef {
+    /// 输出中显示的来源：本地引用的短名，或命令行上的写法如 `HEAD~2`
+    display: String,
+    /// 本地引用的完整名称，来源不是引用（如 `HEAD~2`）时是命令行上的写法，删除时为 None
+    local: Option<String>,
+    /// 远程引用的完整名称
+    dst: String,
+    /// 推送的对象，删除时为全零
+    new: [u8; 20],
+    /// 远程引用当前的值
+    old: Option<[u8; 20]>,
+    force: bool,
+    /// 不是快进，强制更新
+    forced: bool,
+    status: Status,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Status {
+    UpToDate,
+    Ok,
+    /// 在本地就被拒绝，没有送到远程
+    Rejected(&'static str),
+    /// 远程拒绝更新
+    RemoteRejected(String),
+}
+
+/// 把 refspecs 推送到 remote（远程名称或仓库路径，默认是当前分支的远程或 origin）。
+/// 没有 refspecs 时与 git 默认的 `push.default=simple` 一样推送当前分支到它的上游。
+/// 有引用没有推送成功时返回 false
+pub(crate) async fn invoke(
+    remote: Option<&str>,
+    refspecs: &[String],
+    options: &PushOptions,
+) -> anyhow::Result<bool> {
+    let config = Config::read()?;
+    let name = match remote {
+        Some(name) => name.to_string(),
+        None => remote::default_name(&config)?,
+    };
+    let remote = Remote::get(&config, &name)?;
+    let url = config
+        .get(&format!("remote.{name}.pushurl"))
+        .or_else(|| remote.as_ref().map(|remote| remote.url.clone()))
+        .unwrap_or(name.clone());
+    let mut target = match transport::is_http(&url) {
+        true => Target::Remote(send_pack::Connection::open(&url)?),
+        false => match source_git_dir(&url) {
+            Ok(git_dir) => Target::Local(git_dir),
+            Err(_) => anyhow::bail!("'{name}' does not appear to be a git repository"),
+        },
+    };
+    let remote_refs: BTreeMap<String, [u8; 20]> = match &target {
+        Target::Local(git_dir) => refs::list_refs_in(git_dir, "refs/")?.into_iter().collect(),
+        Target::Remote(connection) => connection.refs.iter().cloned().collect(),
+    };
+
+    let mut specs: Vec<String> = Vec::new();
+    if options.delete {
+        anyhow::ensure!(
+            !refspecs.is_empty(),
+            "--delete doesn't make sense without any refs"
+        );
+        for spec in refspecs {
+            anyhow::ensure!(
+                !spec.contains(':'),
+                "--delete only accepts plain target ref names"
+            );
+            specs.push(format!(":{spec}"));
+        }
+    } else if refspecs.is_empty() && !options.tags {
+        specs.push(default_refspec(&config, &name)?);
+    } else {
+        specs.extend(refspecs.iter().cloned());
+    }
+    if options.tags {
+        specs.push("refs/tags/*:refs/tags/*".to_string());
+    }
+
+    // 先把所有 refspec 对应到远程引用，有一个不能对应就什么也不推送
+    let mut pushes: Vec<PushRef> = Vec::new();
+    let mut errors = Vec::new();
+    for spec in &specs {
+        match resolve_spec(spec, &remote_refs, options.force).await? {
+            Ok(refs) => pushes.extend(refs),
+            Err(error) => errors.push(error),
+        }
+    }
+    if !errors.is_empty() {
+        for error in errors {
+            eprintln!("{error}");
+        }
+        eprintln!("error: failed to push some refs to '{url}'");
+        return Ok(false);
+    }
+
+    let leases = parse_leases(&options.force_with_lease).await?;
+    for push in &mut pushes {
+        // 指定了引用的优先于不带参数的
+        let lease = leases
+            .iter()
+            .find(|(name, _)| {
+                name.as_deref()
+                    .is_some_and(|name| lease_matches(name, &push.dst))
+            })
+            .or_else(|| leases.iter().find(|(name, _)| name.is_none()));
+        let lease = match lease {
+            Some((_, Some(expect))) => Some(*expect),
+            // 没有指定期望值时期望与远程跟踪分支一致，没有远程跟踪分支时期望远程还没有这个引用
+            Some((_, None)) => Some(
+                match remote
+                    .as_ref()
+                    .and_then(|remote| tracking_ref(remote, &push.dst))
+                {
+                    Some(tracking) => refs::resolve_ref(&tracking)?.unwrap_or_default(),
+                    None => [0; 20],
+                },
+            ),
+            None => None,
+        };
+        check(push, lease).await?;
+    }
+    if options.atomic && pushes.iter().any(|push| push.is_failed()) {
+        for push in &mut pushes {
+            if push.status == Status::Ok {
+                push.status = Status::Rejected("atomic push failed");
+            }
+        }
+    }
+
+    // 远程的引用在前、新建的在后，成功的在前、失败的在后
+    pushes.sort_by_key(|push| match push.old {
+        Some(_) => (false, push.dst.clone()),
+        None => (true, String::new()),
+    });
+    pushes.sort_by_key(|push| push.is_failed());
+
+    let updates: Vec<usize> = (0..pushes.len())
+        .filter(|&i| pushes[i].status == Status::Ok)
+        .collect();
+    if updates.is_empty() && !pushes.iter().any(|push| push.is_failed()) {
+        if !options.quiet {
+            eprintln!("Everything up-to-date");
+        }
+        finish(&name, remote.as_ref(), &pushes, options)?;
+        return Ok(true);
+    }
+
+    if !updates.is_empty() && !options.no_verify {
+        let input: String = updates
+            .iter()
+            .map(|&i| {
+                let push = &pushes[i];
+                format!(
+                    "{} {} {} {}\n",
+                    push.local.as_deref().unwrap_or("(delete)"),
+                    hex::encode(push.new),
+                    push.dst,
+                    hex::encode(push.old.unwrap_or_default())
+                )
+            })
+            .collect();
+        let args = [name.as_str(), url.as_str()];
+        if !crate::hook::run_with("pre-push", &args, &[], Some(input.as_bytes()))? {
+            eprintln!("error: failed to push some refs to '{url}'");
+            return Ok(false);
+        }
+    }
+
+    if !updates.is_empty() {
+        let commands: Vec<Command> = updates
+            .iter()
+            .map(|&i| Command {
+                name: pushes[i].dst.clone(),
+                old: pushes[i].old.unwrap_or_default(),
+                new: pushes[i].new,
+            })
+            .collect();
+        let results = match &mut target {
+            Target::Local(git_dir) => {
+                let wants: Vec<[u8; 20]> = commands
+                    .iter()
+                    .map(|command| command.new)
+                    .filter(|id| id != &[0; 20])
+                    .collect();
+                let objects = git_dir.join("objects");
+                remote::transfer_objects(Path::new(".git/objects"), &objects, &wants).await?;
+                receive::receive(git_dir, &commands, options.atomic)?
+            }
+            Target::Remote(connection) => connection.push(&commands, options.atomic).await?,
+        };
+        for (&i, result) in updates.iter().zip(results) {
+            if let Some(reason) = result {
+                pushes[i].status = Status::RemoteRejected(reason);
+            }
+        }
+        pushes.sort_by_key(|push| push.is_failed());
+    }
+
+    let failed = pushes.iter().any(|push| push.is_failed());
+    if !options.quiet || failed {
+        eprintln!("To {url}");
+        for push in &pushes {
+            if let Some(line) = push.line() {
+                eprintln!("{line}");
+            }
+        }
+    }
+    finish(&name, remote.as_ref(), &pushes, options)?;
+    if failed {
+        eprintln!("error: failed to push some refs to '{url}'");
+        let head = refs::head_branch()?;
+        advise(&pushes, head.as_deref());
+    }
+    Ok(!failed)
+}
+
+impl PushRef {
+    fn is_failed(&self) -> bool {
+        matches!(self.status, Status::Rejected(_) | Status::RemoteRejected(_))
+    }
+
+    /// 输出中的一行，已经是最新的不输出
+    fn line(&self) -> Option<String> {
+        let abbrev = |id: &[u8; 20]| find_unique_abbrev(&hex::encode(id), 7);
+        let (flag, summary, suffix) = match &self.status {
+            Status::UpToDate => return None,
+            Status::Ok if self.new == [0; 20] => ('-', "[deleted]".to_string(), String::new()),
+            Status::Ok => match self.old {
+                None => {
+                    let what = if self.dst.starts_with("refs/tags/") {
+                        "[new tag]"
+                    } else if self.dst.starts_with("refs/heads/") {
+                        "[new branch]"
+                    } else {
+                        "[new reference]"
+                    };
+                    ('*', what.to_string(), String::new())
+                }
+                Some(old) if self.forced => (
+                    '+',
+                    format!("{}...{}", abbrev(&old), abbrev(&self.new)),
+                    " (forced update)".to_string(),
+                ),
+                Some(old) => (
+                    ' ',
+                    format!("{}..{}", abbrev(&old), abbrev(&self.new)),
+                    String::new(),
+                ),
+            },
+            Status::Rejected(reason) => ('!', "[rejected]".to_string(), format!(" ({reason})")),
+            Status::RemoteRejected(reason) => {
+                ('!', "[remote rejected]".to_string(), format!(" ({reason})"))
+            }
+        };
+        let dst = shorten(&self.dst);
+        Some(match self.new == [0; 20] {
+            true => format!(" {flag} {summary:<17} {dst}{suffix}"),
+            false => format!(" {flag} {summary:<17} {} -> {dst}{suffix}", self.display),
+        })
+    }
+}
+
+/// 没有 refspec 时推送的当前分支。与 git 的 `push.default=simple` 一样，
+/// 推送到分支的上游所在的远程时要求上游与分支同名
+fn default_refspec(config: &Config, name: &str) -> anyhow::Result<String> {
+    let Some(branch) = refs::head_branch()? else {
+        anyhow::bail!(
+            "You are not currently on a branch.\n\
+             To push the history leading to the current (detached HEAD)\n\
+             state now, use\n\
+             \n    git push {name} HEAD:<name-of-remote-branch>\n"
+        );
+    };
+    let short = branch.strip_prefix("refs/heads/").unwrap_or(&branch);
+    let upstream_remote = config
+        .get(&format!("branch.{short}.remote"))
+        .unwrap_or_else(|| "origin".to_string());
+    if upstream_remote != name {
+        return Ok(format!("{branch}:{branch}"));
+    }
+    let Some(merge) = config.get(&format!("branch.{short}.merge")) else {
+        anyhow::bail!(
+            "The current branch {short} has no upstream branch.\n\
+             To push the current branch and set the remote as upstream, use\n\
+             \n    git push --set-upstream {name} {short}\n\n\
+             To have this happen automatically for branches without a tracking\n\
+             upstream, see 'push.autoSetupRemote' in 'git help config'.\n"
+        );
+    };
+    anyhow::ensure!(
+        merge == branch,
+        "The upstream branch of your current branch does not match\n\
+         the name of your current branch.  To push to the upstream branch\n\
+         on the remote, use\n\
+         \n    git push {name} HEAD:{}\n\n\
+         To push to the branch of the same name on the remote, use\n\
+         \n    git push {name} HEAD\n\n\
+         To choose either option permanently, see push.default in 'git help config'.\n\n\
+         To avoid automatically configuring an upstream branch when its name\n\
+         won't match the local branch, see option 'track' in 'git help branch'.\n",
+        shorten(&merge)
+    );
+    Ok(format!("{branch}:{branch}"))
+}
+
+/// 把一个 refspec 对应到要推送的引用。`:<dst>` 删除远程引用，src 带 `*` 时匹配所有本地引用。
+/// 不能对应时返回 git 的错误信息
+async fn resolve_spec(
+    spec: &str,
+    remote_refs: &BTreeMap<String, [u8; 20]>,
+    force: bool,
+) -> anyhow::Result<Result<Vec<PushRef>, String>> {
+    let (force, rest) = match spec.strip_prefix('+') {
+        Some(rest) => (true, rest),
+        None => (force, spec),
+    };
+    let push = |display: String, local: Option<String>, dst: String, new: [u8; 20]| PushRef {
+        display,
+        local,
+        old: remote_refs.get(&dst).copied(),
+        dst,
+        new,
+        force,
+        forced: false,
+        status: Status::Ok,
+    };
+    if let Some(dst) = rest.strip_prefix(':') {
+        return Ok(match dwim_remote_ref(remote_refs, dst) {
+            Some(dst) => Ok(vec![push(String::new(), None, dst, [0; 20])]),
+            None => Err(format!(
+                "error: unable to delete '{dst}': remote ref does not exist"
+            )),
+        });
+    }
+
+    let (src, dst) = match rest.split_once(':') {
+        Some((src, dst)) => (src, Some(dst)),
+        None => (rest, None),
+    };
+    if src.contains('*') {
+        let spec = Refspec::parse(rest)?;
+        let mut pushes = Vec::new();
+        for (local, id) in refs::list_refs("refs/")? {
+            let dst = match spec.dst {
+                Some(_) => spec.map(&local),
+                None => spec.matches(&local).then(|| local.clone()),
+            };
+            if let Some(dst) = dst {
+                pushes.push(push(shorten(&local).to_string(), Some(local), dst, id));
+            }
+        }
+        return Ok(Ok(pushes));
+    }
+
+    let local_ref = match src {
+        "HEAD" => refs::head_branch()?,
+        _ => refs::dwim_ref(src)?,
+    };
+    let Ok(new) = revision::resolve(src).await else {
+        return Ok(Err(format!("error: src refspec {src} does not match any")));
+    };
+    let dst = match (dst, &local_ref) {
+        (Some(dst), _) if dst.starts_with("refs/") => dst.to_string(),
+        (Some(dst), local_ref) => match dwim_remote_ref(remote_refs, dst) {
+            Some(full) => full,
+            None => match local_ref.as_deref() {
+                Some(local) if local.starts_with("refs/heads/") => format!("refs/heads/{dst}"),
+                Some(local) if local.starts_with("refs/tags/") => format!("refs/tags/{dst}"),
+                _ => return Ok(Err(dst_error(src, dst, &new).await?)),
+            },
+        },
+        (None, Some(local)) => local.clone(),
+        (None, None) => return Ok(Err(dst_error(src, src, &new).await?)),
+    };
+    let (display, local) = match (src, local_ref) {
+        ("HEAD", _) | (_, None) => (src.to_string(), src.to_string()),
+        (_, Some(local)) => (shorten(&local).to_string(), local),
+    };
+    Ok(Ok(vec![push(display, Some(local), dst, new)]))
+}
+
+/// 在远程引用中查找简写 name：依次尝试 name、`refs/<name>`、`refs/tags/<name>`、
+/// `refs/heads/<name>`、`refs/remotes/<name>`
+fn dwim_remote_ref(remote_refs: &BTreeMap<String, [u8; 20]>, name: &str) -> Option<String> {
+    [
+        "{}",
+        "refs/{}",
+        "refs/tags/{}",
+        "refs/heads/{}",
+        "refs/remotes/{}",
+    ]
+    .iter()
+    .map(|pattern| pattern.replace("{}", name))
+    .find(|full| remote_refs.contains_key(full))
+}
+
+/// dst 不是完整引用名、又不能从远程引用或 src 推断时 git 的错误信息，按 src 对象的类型给出建议
+async fn dst_error(src: &str, dst: &str, id: &[u8; 20]) -> anyhow::Result<String> {
+    let mut message = format!(
+        "error: The destination you provided is not a full refname (i.e.,\n\
+         starting with \"refs/\"). We tried to guess what you meant by:\n\
+         \n\
+         - Looking for a ref that matches '{dst}' on the remote side.\n\
+         - Checking if the <src> being pushed ('{src}')\n  \
+         is a ref in \"refs/{{heads,tags}}/\". If so we add a corresponding\n  \
+         refs/{{heads,tags}}/ prefix on the remote side.\n\
+         \n\
+         Neither worked, so we gave up. You must fully qualify the ref."
+    );
+    let (kind, _) = read_object(&hex::encode(id)).await?;
+    let (what, action, prefix) = match kind {
+        Kind::Commit => ("commit", "create a new branch", "refs/heads/"),
+        Kind::Tag => ("tag", "create a new tag", "refs/tags/"),
+        Kind::Tree => ("tree", "tag a new tree", "refs/tags/"),
+        Kind::Blob => ("blob", "tag a new blob", "refs/tags/"),
+    };
+    message.push_str(&format!(
+        "\nhint: The <src> part of the refspec is a {what} object.\n\
+         hint: Did you mean to {action} by pushing to\n\
+         hint: '{src}:{prefix}{dst}'?"
+    ));
+    Ok(message)
+}
+
+/// 解析 `--force-with-lease` 的参数，得到 (引用名, 期望的值)。
+/// 不带参数时引用名为 None，表示所有推送的引用；期望值为 None 时取远程跟踪分支的值，
+/// `<ref>:` 期望远程还没有这个引用
+async fn parse_leases(args: &[String]) -> anyhow::Result<Vec<(Option<String>, Option<[u8; 20]>)>> {
+    let mut leases = Vec::new();
+    for arg in args {
+        let lease = match arg.split_once(':') {
+            _ if arg.is_empty() => (None, None),
+            None => (Some(arg.clone()), None),
+            Some((name, "")) => (Some(name.to_string()), Some([0; 20])),
+            Some((name, expect)) => {
+                let Ok(id) = revision::resolve(expect).await else {
+                    anyhow::bail!("cannot parse expected object name '{expect}'");
+                };
+                (Some(name.to_string()), Some(id))
+            }
+        };
+        leases.push(lease);
+    }
+    Ok(leases)
+}
+
+/// `--force-with-lease=<name>` 中的 name 是否指远程引用 dst，可以是简写
+fn lease_matches(name: &str, dst: &str) -> bool {
+    [
+        "{}",
+        "refs/{}",
+        "refs/tags/{}",
+        "refs/heads/{}",
+        "refs/remotes/{}",
+    ]
+    .iter()
+    .any(|pattern| pattern.replace("{}", name) == dst)
+}
+
+/// 远程引用 dst 按 fetch refspec 对应的远程跟踪分支
+fn tracking_ref(remote: &Remote, dst: &str) -> Option<String> {
+    remote
+        .fetch
+        .iter()
+        .filter(|spec| !spec.negative)
+        .find_map(|spec| spec.map(dst))
+}
+
+/// 在本地检查能否更新：lease 是 `--force-with-lease` 期望的远程值，与远程一致时允许强制更新
+async fn check(push: &mut PushRef, lease: Option<[u8; 20]>) -> anyhow::Result<()> {
+    let old = push.old.unwrap_or_default();
+    if lease.is_some_and(|expect| expect != old) {
+        push.status = Status::Rejected("stale info");
+        return Ok(());
+    }
+    let force = push.force || lease.is_some();
+    push.status = match push.old {
+        None => Status::Ok,
+        Some(_) if push.new == [0; 20] => Status::Ok,
+        Some(old) if old == push.new => Status::UpToDate,
+        Some(old) => {
+            // 已有的标签与 git 一样不论是否快进都要强制更新
+            let reason = if push.dst.starts_with("refs/tags/") {
+                Some("already exists")
+            } else if !object_exists(&hex::encode(old)) {
+                Some("fetch first")
+            } else {
+                match fast_forward(old, push.new).await? {
+                    Some(true) => None,
+                    Some(false) => Some("non-fast-forward"),
+                    None => Some("needs force"),
+                }
+            };
+            match reason {
+                Some(reason) if !force => Status::Rejected(reason),
+                reason => {
+                    push.forced = reason.is_some();
+                    Status::Ok
+                }
+            }
+        }
+    };
+    Ok(())
+}
+
+/// new 是否是 old 的快进，两边都要能解引用到提交，否则返回 None
+async fn fast_forward(old: [u8; 20], new: [u8; 20]) -> anyhow::Result<Option<bool>> {
+    let (Ok(old), Ok(new)) = (
+        revision::peel(old, Some(Kind::Commit)).await,
+        revision::peel(new, Some(Kind::Commit)).await,
+    ) else {
+        return Ok(None);
+    };
+    Ok(Some(is_ancestor(old, new).await?))
+}
+
+/// 推送成功（或已经是最新）后，更新对应的远程跟踪分支，`--set-upstream` 时设置分支的上游
+fn finish(
+    name: &str,
+    remote: Option<&Remote>,
+    pushes: &[PushRef],
+    options: &PushOptions,
+) -> anyhow::Result<()> {
+    let Some(remote) = remote else {
+        return Ok(());
+    };
+    let ident = committer_ident()?;
+    for push in pushes {
+        if !matches!(push.status, Status::Ok | Status::UpToDate) {
+            continue;
+        }
+        if let Some(tracking) = tracking_ref(remote, &push.dst) {
+            let old = refs::resolve_ref(&tracking)?;
+            if push.new == [0; 20] {
+                refs::delete_ref(&tracking)?;
+            } else if old != Some(push.new) {
+                refs::write_ref(&tracking, &push.new)?;
+                let old = old.unwrap_or_default();
+                refs::append_reflog(&tracking, &old, &push.new, &ident, "update by push")?;
+            }
+        }
+        let branch = push
+            .local
+            .as_deref()
+            .and_then(|local| local.strip_prefix("refs/heads/"));
+        if let (true, Some(branch), Some(merge)) = (
+            options.set_upstream,
+            branch,
+            push.dst.strip_prefix("refs/heads/"),
+        ) {
+            if push.new == [0; 20] {
+                continue;
+            }
+            let file = ".git/config";
+            config::set(file, &format!("branch.{branch}.remote"), name)?;
+            config::set(file, &format!("branch.{branch}.merge"), &push.dst)?;
+            println!("branch '{branch}' set up to track '{name}/{merge}'.");
+        }
+    }
+    Ok(())
+}
+
+/// 按被拒绝的原因给出提示，与 git 一样只给出最重要的一条
+fn advise(pushes: &[PushRef], head: Option<&str>) {
+    // head_only 为 Some 时只看推送到（或不是推送到）当前分支同名引用的
+    let rejected = |reason: &'static str, head_only: Option<bool>| {
+        pushes.iter().any(|push| {
+            push.status == Status::Rejected(reason)
+                && head_only.map_or(true, |head_only| {
+                    (Some(push.dst.as_str()) == head) == head_only
+                })
+        })
+    };
+    let hint = if rejected("non-fast-forward", Some(true)) {
+        "Updates were rejected because the tip of your current branch is behind\n\
+         its remote counterpart. Integrate the remote changes (e.g.\n\
+         'git pull ...') before pushing again.\n\
+         See the 'Note about fast-forwards' in 'git push --help' for details."
+    } else if rejected("non-fast-forward", Some(false)) {
+        "Updates were rejected because a pushed branch tip is behind its remote\n\
+         counterpart. Check out this branch and integrate the remote changes\n\
+         (e.g. 'git pull ...') before pushing again.\n\
+         See the 'Note about fast-forwards' in 'git push --help' for details."
+    } else if rejected("already exists", None) {
+        "Updates were rejected because the tag already exists in the remote."
+    } else if rejected("fetch first", None) {
+        "Updates were rejected because the remote contains work that you do\n\
+         not have locally. This is usually caused by another repository pushing\n\
+         to the same ref. You may want to first integrate the remote changes\n\
+         (e.g., 'git pull ...') before pushing again.\n\
+         See the 'Note about fast-forwards' in 'git push --help' for details."
+    } else if rejected("needs force", None) {
+        "You cannot update a remote ref that points at a non-commit object,\n\
+         or update a remote ref to make it point at a non-commit object,\n\
+         without using the '--force' option."
+    } else {
+        return;
+    };
+    for line in hint.lines() {
+        eprintln!("hint: {line}");
+    }
+}
@@ -0,0 +1,264 @@
+use std::collections::{BTreeMap, HashSet};
+
+use anyhow::Context;
+
+pub(crate) use crate::sequencer::rebase::{abort, continue_rebase, edit_todo, skip};
+use crate::{
+    diff::{flatten_tree, index_files, patch_id::patch_id, worktree_files},
+    hook,
+    index::Index,
+    objects::{Kind, commit::Commit, find_unique_abbrev},
+    refs,
+    revision::{RevWalk, Sort, merge_bases, peel, resolve},
+    sequencer::{
+        rebase::{self, Setup},
+        todo::{Command, TodoItem, rearrange_squash},
+    },
+    worktree,
+};
+
+pub(crate) struct RebaseOptions {
+    /// `-i`，先编辑待办列表
+    pub(crate) interactive: bool,
+    /// `--onto`，重放到这个提交上而不是 upstream
+    pub(crate) onto: Option<String>,
+    /// `--autosquash`，只在交互式变基中生效
+    pub(crate) autosquash: bool,
+    /// `--update-refs`，同时更新指向被重放提交的其他分支
+    pub(crate) update_refs: bool,
+    /// `--no-verify`，不运行 pre-rebase 钩子
+    pub(crate) no_verify: bool,
+}
+
+/// 把 upstream..HEAD 中的提交重放到 onto（默认 upstream）上，给出 branch 时改为重放这个分支。
+/// 全部完成时返回 true，停下来等待处理时返回 false
+pub(crate) async fn invoke(
+    upstream: &str,
+    branch: Option<&str>,
+    options: RebaseOptions,
+) -> anyhow::Result<bool> {
+    anyhow::ensure!(
+        !rebase::in_progress(),
+        "It seems that there is already a rebase-merge directory, and\n\
+         I wonder if you are in the middle of another rebase.  If that is the\n\
+         case, please try\n\tgit rebase (--continue | --abort | --skip)\n\
+         If that is not the case, please\n\trm -fr \".git/rebase-merge\"\n\
+         and run me again.  I am stopping in case you still have something\n\
+         valuable there.\n"
+    );
+    let upstream_id = resolve_commit(upstream)
+        .await
+        .with_context(|| format!("invalid upstream '{upstream}'"))?;
+    let onto = match &options.onto {
+        Some(onto) => resolve_commit(onto)
+            .await
+            .with_context(|| format!("Does not point to a valid commit '{onto}'"))?,
+        None => upstream_id,
+    };
+    let current = refs::resolve_ref("HEAD")?.context("HEAD has no commits yet")?;
+    ensure_clean(&current).await?;
+    // 与 git 一样不先切换到 branch，确定不需要变基时才切换过去，否则直接从当前的 HEAD 检出 onto
+    let (head_name, head) = match branch {
+        Some(branch) => resolve_branch(branch).await?,
+        None => (refs::head_branch()?, current),
+    };
+
+    // 已经基于 onto 时不需要重放
+    if !options.interactive
+        && merge_bases(onto, &[head]).await? == [onto]
+        && (upstream_id == onto || merge_bases(upstream_id, &[head]).await? == [onto])
+    {
+        // 与 git 一样显示命令行给出的名称，分离头指针时只说 HEAD
+        let name = branch.or_else(|| {
+            head_name
+                .as_deref()
+                .map(|name| name.strip_prefix("refs/heads/").unwrap_or(name))
+        });
+        if branch.is_some() {
+            switch_to(&current, head_name.as_deref(), &head).await?;
+            hook::run(
+                "post-checkout",
+                &[&hex::encode(current), &hex::encode(head), "1"],
+            )?;
+        }
+        match name {
+            Some(name) => println!("Current branch {name} is up to date."),
+            None => println!("HEAD is up to date."),
+        }
+        return Ok(true);
+    }
+    if !options.no_verify {
+        let args: Vec<&str> = std::iter::once(upstream).chain(branch).collect();
+        anyhow::ensure!(
+            hook::run("pre-rebase", &args)?,
+            "The pre-rebase hook refused to rebase."
+        );
+    }
+
+    let mut todo = todo_list(upstream_id, head, head_name.as_deref(), &options).await?;
+    if options.interactive && options.autosquash {
+        rearrange_squash(&mut todo).await?;
+    }
+    let setup = Setup {
+        head_name,
+        orig_head: head,
+        checked_out: current,
+        onto,
+        upstream: upstream_id,
+        interactive: options.interactive,
+    };
+    rebase::start(setup, todo).await
+}
+
+async fn resolve_commit(rev: &str) -> anyhow::Result<[u8; 20]> {
+    peel(resolve(rev).await?, Some(Kind::Commit)).await
+}
+
+/// 要变基的分支名和它指向的提交，不是分支名时按提交解析，分支名为 None
+async fn resolve_branch(branch: &str) -> anyhow::Result<(Option<String>, [u8; 20])> {
+    let full = format!("refs/heads/{branch}");
+    match refs::resolve_ref(&full)? {
+        Some(id) => Ok((Some(full), id)),
+        None => {
+            let id = resolve_commit(branch)
+                .await
+                .with_context(|| format!("no such branch/commit '{branch}'"))?;
+            Ok((None, id))
+        }
+    }
+}
+
+/// 从 head 切换到要变基的分支，没有分支名时分离到 target
+async fn switch_to(head: &[u8; 20], branch: Option<&str>, target: &[u8; 20]) -> anyhow::Result<()> {
+    if head != target {
+        let index = Index::read().await?;
+        let old = flatten_tree(&Commit::read(head).await?.tree).await?;
+        let new = flatten_tree(&Commit::read(target).await?.tree).await?;
+        let mut index = worktree::switch(&index, &old, &new, "checkout", false).await?;
+        index.write().await?;
+    }
+    match branch {
+        Some(branch) => refs::write_symbolic_ref("HEAD", branch),
+        None => refs::write_ref("HEAD", target),
+    }
+}
+
+/// 工作区和暂存区都必须与 HEAD 一致
+async fn ensure_clean(head: &[u8; 20]) -> anyhow::Result<()> {
+    let index = Index::read().await?;
+    let staged = index_files(&index);
+    let unstaged = index.has_conflicts() || worktree_files(&index).await? != staged;
+    let uncommitted = staged != flatten_tree(&Commit::read(head).await?.tree).await?;
+    match (unstaged, uncommitted) {
+        (false, false) => Ok(()),
+        (true, false) => anyhow::bail!(
+            "cannot rebase: You have unstaged changes.\n\
+             error: Please commit or stash them."
+        ),
+        (false, true) => anyhow::bail!(
+            "cannot rebase: Your index contains uncommitted changes.\n\
+             error: Please commit or stash them."
+        ),
+        (true, true) => anyhow::bail!(
+            "cannot rebase: You have unstaged changes.\n\
+             error: additionally, your index contains uncommitted changes.\n\
+             error: Please commit or stash them."
+        ),
+    }
+}
+
+/// 生成待办列表：upstream..head 中的非合并提交从旧到新逐个 pick。upstream 一侧已有
+/// 相同修改（patch id 相同）的提交跳过，`--update-refs` 时在其他分支指向的提交后面更新该分支
+async fn todo_list(
+    upstream: [u8; 20],
+    head: [u8; 20],
+    head_name: Option<&str>,
+    options: &RebaseOptions,
+) -> anyhow::Result<Vec<TodoItem>> {
+    let mut walk = RevWalk::new();
+    walk.sort = Sort::Topo;
+    walk.push(head);
+    walk.hide(upstream);
+    let mut commits = walk.walk().await?;
+    commits.reverse();
+
+    let mut upstream_walk = RevWalk::new();
+    upstream_walk.push(upstream);
+    upstream_walk.hide(head);
+    let upstream_only = upstream_walk.walk().await?;
+    let mut applied = HashSet::new();
+    if !commits.is_empty() {
+        for id in upstream_only {
+            if let Some(patch_id) = patch_id(&Commit::read(&id).await?).await? {
+                applied.insert(patch_id);
+            }
+        }
+    }
+
+    let mut branches: BTreeMap<[u8; 20], Vec<String>> = BTreeMap::new();
+    if options.update_refs {
+        for (name, id) in refs::list_refs("refs/heads/")? {
+            if Some(name.as_str()) != head_name {
+                branches.entry(id).or_default().push(name);
+            }
+        }
+    }
+
+    let mut todo = Vec::new();
+    let mut skipped = Vec::new();
+    for id in commits {
+        let commit = Commit::read(&id).await?;
+        if commit.parents.len() > 1 {
+            continue;
+        }
+        if !applied.is_empty() {
+            if let Some(patch_id) = patch_id(&commit).await? {
+                if applied.contains(&patch_id) {
+                    skipped.push(id);
+                    continue;
+                }
+            }
+        }
+        let mut subject = commit
+            .message
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        let parent_tree = match commit.parents.first() {
+            Some(parent) => Some(Commit::read(parent).await?.tree),
+            None => None,
+        };
+        let empty = match parent_tree {
+            Some(tree) => tree == commit.tree,
+            None => flatten_tree(&commit.tree).await?.is_empty(),
+        };
+        if empty {
+            subject.push_str(" # empty");
+        }
+        todo.push(TodoItem::pick(id, subject));
+        if let Some(names) = branches.get(&id) {
+            for name in names {
+                todo.push(TodoItem::new(Command::UpdateRef, name.clone()));
+            }
+            todo.push(TodoItem::new(Command::Comment, String::new()));
+        }
+    }
+    if todo.is_empty() {
+        todo.push(TodoItem::new(Command::Noop, String::new()));
+    }
+
+    for id in &skipped {
+        eprintln!(
+            "warning: skipped previously applied commit {}",
+            find_unique_abbrev(&hex::encode(id), 7)
+        );
+    }
+    if !skipped.is_empty() {
+        eprintln!(
+            "hint: use --reapply-cherry-picks to include skipped commits\n\
+             hint: Disable this message with \"git config advice.skippedCherryPicks false\""
+        );
+    }
+    Ok(todo)
+}
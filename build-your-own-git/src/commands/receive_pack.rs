@@ -0,0 +1,161 @@
+//! receive-pack：服务端，通过标准输入输出接收 push 的客户端发来的引用更新和 pack。
+//!
+//! 推送只有协议 v0：先列出所有引用，第一行在 NUL 之后带着能力列表；客户端发送
+//! `旧值 新值 引用名` 命令、flush 和 pack，这里像 `index-pack --fix-thin` 一样给 pack
+//! 建索引，存为仓库的 `objects/pack/pack-<校验和>.pack`，运行钩子并更新引用，
+//! 最后用 report-status 报告结果。客户端请求了 side-band-64k 时，
+//! 钩子的输出经由 2 号通道送回，报告经由 1 号通道
+
+use std::io::Write;
+
+use anyhow::Context;
+
+use crate::{
+    commands::upload_pack::{SIDEBAND_MAX, requested_version, server_git_dir, write_sideband},
+    hook,
+    objects::commit::parse_hex,
+    pack,
+    pkt_line::{self, Packet},
+    receive::{self, Command},
+    refs,
+};
+
+pub(crate) struct ReceivePackOptions {
+    /// 只处理一个请求，不先发送通告
+    pub(crate) stateless_rpc: bool,
+    /// 只发送引用通告就退出
+    pub(crate) advertise_refs: bool,
+}
+
+const CAPABILITIES: &str = concat!(
+    "report-status delete-refs side-band-64k quiet atomic ofs-delta object-format=sha1 \
+     agent=own-git/",
+    env!("CARGO_PKG_VERSION")
+);
+
+pub(crate) async fn invoke(directory: &str, options: &ReceivePackOptions) -> anyhow::Result<()> {
+    let git_dir = server_git_dir(directory)?;
+    let mut output = Vec::new();
+    if options.advertise_refs || !options.stateless_rpc {
+        // 推送没有协议 v2，请求 v2 时与 git 一样按 v0 回复
+        if requested_version() == 1 {
+            pkt_line::write_line(&mut output, "version 1")?;
+        }
+        let refs = refs::list_refs_in(&git_dir, "refs/")?;
+        if refs.is_empty() {
+            let line = format!("{} capabilities^{{}}\0{CAPABILITIES}", hex::encode([0; 20]));
+            pkt_line::write_line(&mut output, &line)?;
+        }
+        for (i, (name, id)) in refs.iter().enumerate() {
+            let line = match i {
+                0 => format!("{} {name}\0{CAPABILITIES}", hex::encode(id)),
+                _ => format!("{} {name}", hex::encode(id)),
+            };
+            pkt_line::write_line(&mut output, &line)?;
+        }
+        pkt_line::flush(&mut output)?;
+        send(&mut output)?;
+    }
+    if options.advertise_refs {
+        return Ok(());
+    }
+
+    // 命令直到 flush，客户端没有要推送的时直接 flush
+    let mut input = std::io::stdin().lock();
+    let mut commands = Vec::new();
+    let mut capabilities = Vec::new();
+    loop {
+        let line = match pkt_line::expect(&mut input)? {
+            Packet::Flush => break,
+            packet => packet
+                .text()
+                .context("protocol error: bad command")?
+                .to_string(),
+        };
+        // 浅克隆的客户端先列出它的边界，我们不检查推送来的历史是否完整
+        if line.starts_with("shallow ") {
+            continue;
+        }
+        let line = match line.split_once('\0') {
+            Some((line, list)) => {
+                capabilities = list.split(' ').map(str::to_string).collect();
+                line.to_string()
+            }
+            None => line,
+        };
+        let mut fields = line.splitn(3, ' ');
+        let (Some(old), Some(new), Some(name)) = (fields.next(), fields.next(), fields.next())
+        else {
+            anyhow::bail!("protocol error: expected old/new/ref, got '{line}'");
+        };
+        commands.push(Command {
+            name: name.to_string(),
+            old: parse_hex(old)?,
+            new: parse_hex(new)?,
+        });
+    }
+    if commands.is_empty() {
+        return Ok(());
+    }
+    let supports = |name: &str| capabilities.iter().any(|c| c == name);
+    let sideband = supports("side-band-64k");
+
+    // 只删除引用时没有 pack
+    let mut unpack = Ok(());
+    if commands.iter().any(|command| command.new != [0; 20]) {
+        unpack = match pack::read(&mut input) {
+            Ok(data) => pack::store::store(&git_dir.join("objects"), data)
+                .await
+                .map(|_| ()),
+            Err(e) => Err(e),
+        };
+    }
+    let results = match &unpack {
+        Ok(()) => {
+            if sideband {
+                hook::capture();
+            }
+            let results = receive::receive(&git_dir, &commands, supports("atomic"));
+            if let Some(messages) = hook::take_captured().filter(|m| !m.is_empty()) {
+                write_sideband(&mut output, 2, messages.as_bytes(), SIDEBAND_MAX)?;
+            }
+            results?
+        }
+        Err(_) => vec![Some("unpacker error".to_string()); commands.len()],
+    };
+
+    if !supports("report-status") {
+        return send(&mut output);
+    }
+    let mut report = Vec::new();
+    match &unpack {
+        Ok(()) => pkt_line::write_line(&mut report, "unpack ok")?,
+        Err(e) => pkt_line::write_line(&mut report, &format!("unpack {e}"))?,
+    }
+    for (command, result) in commands.iter().zip(&results) {
+        match result {
+            None => pkt_line::write_line(&mut report, &format!("ok {}", command.name))?,
+            Some(reason) => {
+                pkt_line::write_line(&mut report, &format!("ng {} {reason}", command.name))?
+            }
+        }
+    }
+    pkt_line::flush(&mut report)?;
+    match sideband {
+        true => {
+            write_sideband(&mut output, 1, &report, SIDEBAND_MAX)?;
+            pkt_line::flush(&mut output)?;
+        }
+        false => output.extend(report),
+    }
+    send(&mut output)
+}
+
+/// 发出积累的回复
+fn send(output: &mut Vec<u8>) -> anyhow::Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(output)?;
+    stdout.flush()?;
+    output.clear();
+    Ok(())
+}
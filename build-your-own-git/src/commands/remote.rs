@@ -0,0 +1,201 @@
+//! remote：管理 `.git/config` 中记录的远程仓库
+
+use std::path::Path;
+
+use crate::{
+    commands::{
+        commit::committer_ident,
+        fetch::{self, FetchOptions},
+    },
+    config::{self, Config},
+    refs,
+    remote::{self, Remote},
+};
+
+const CONFIG_FILE: &str = ".git/config";
+
+/// 列出所有远程，verbose 时同时列出用于 fetch 和 push 的 URL
+pub(crate) fn list(verbose: bool) -> anyhow::Result<()> {
+    let config = Config::read()?;
+    for name in remote::names(&config) {
+        if !verbose {
+            println!("{name}");
+            continue;
+        }
+        let url = config
+            .get(&format!("remote.{name}.url"))
+            .unwrap_or_default();
+        let push_url = config
+            .get(&format!("remote.{name}.pushurl"))
+            .unwrap_or(url.clone());
+        println!("{name}\t{url} (fetch)");
+        println!("{name}\t{push_url} (push)");
+    }
+    Ok(())
+}
+
+/// 添加远程 name，默认把它的所有分支取回到 `refs/remotes/<name>/` 下。
+/// fetch 为 true 时随即从它取回，有引用被拒绝更新时返回 false
+pub(crate) async fn add(name: &str, url: &str, fetch: bool) -> anyhow::Result<bool> {
+    let config = Config::read()?;
+    anyhow::ensure!(
+        !name.is_empty() && !name.contains(char::is_whitespace) && !name.contains(['*', ':']),
+        "'{name}' is not a valid remote name"
+    );
+    if remote::names(&config).iter().any(|remote| remote == name) {
+        anyhow::bail!("remote {name} already exists.");
+    }
+    config::set(CONFIG_FILE, &format!("remote.{name}.url"), url)?;
+    let refspec = format!("+refs/heads/*:refs/remotes/{name}/*");
+    config::add(CONFIG_FILE, &format!("remote.{name}.fetch"), &refspec)?;
+    if !fetch {
+        return Ok(true);
+    }
+    println!("Updating {name}");
+    let options = FetchOptions {
+        reflog_action: format!("fetch {name}"),
+        ..Default::default()
+    };
+    fetch::invoke(Some(name), &[], &options).await
+}
+
+/// 删除远程 name：它的配置、远程跟踪分支，以及分支上指向它的上游配置
+pub(crate) fn remove(name: &str) -> anyhow::Result<()> {
+    let config = Config::read()?;
+    let Some(remote) = Remote::get(&config, name)? else {
+        anyhow::bail!("No such remote: '{name}'");
+    };
+    for branch in config.subsections("branch") {
+        if config.get(&format!("branch.{branch}.remote")).as_deref() == Some(name) {
+            config::unset(CONFIG_FILE, &format!("branch.{branch}.remote"))?;
+            config::unset(CONFIG_FILE, &format!("branch.{branch}.merge"))?;
+        }
+    }
+    config::remove_section(CONFIG_FILE, &format!("remote.{name}"))?;
+
+    for (local, _) in refs::list_refs("refs/")? {
+        let tracked = remote
+            .fetch
+            .iter()
+            .any(|spec| !spec.negative && spec.reverse(&local).is_some());
+        if !tracked {
+            continue;
+        }
+        if is_symbolic(&local)? {
+            std::fs::remove_file(Path::new(".git").join(&local))?;
+        } else {
+            refs::delete_ref(&local)?;
+        }
+        remove_empty_parents(&local);
+    }
+    Ok(())
+}
+
+/// 把远程 old 改名为 new：配置的节名、默认的 fetch refspec、分支的上游配置，
+/// 以及 `refs/remotes/<old>/` 下的远程跟踪分支和它们的 reflog
+pub(crate) fn rename(old: &str, new: &str) -> anyhow::Result<()> {
+    let config = Config::read()?;
+    if Remote::get(&config, old)?.is_none() {
+        anyhow::bail!("No such remote: '{old}'");
+    }
+    if remote::names(&config).iter().any(|remote| remote == new) {
+        anyhow::bail!("remote {new} already exists.");
+    }
+    config::rename_section(
+        CONFIG_FILE,
+        &format!("remote.{old}"),
+        &format!("remote.{new}"),
+    )?;
+
+    // 只改写指向 refs/remotes/<old>/ 的 refspec，其余的提示用户自己处理
+    let (old_prefix, new_prefix) = (
+        format!("refs/remotes/{old}/"),
+        format!("refs/remotes/{new}/"),
+    );
+    let fetch_key = format!("remote.{new}.fetch");
+    config::unset(CONFIG_FILE, &fetch_key)?;
+    for spec in config.get_all(&format!("remote.{old}.fetch")) {
+        if spec.contains(&old_prefix) {
+            config::add(
+                CONFIG_FILE,
+                &fetch_key,
+                &spec.replace(&old_prefix, &new_prefix),
+            )?;
+        } else {
+            eprintln!(
+                "warning: Not updating non-default fetch refspec\n\t{spec}\n\t\
+                 Please update the configuration manually if necessary."
+            );
+            config::add(CONFIG_FILE, &fetch_key, &spec)?;
+        }
+    }
+    for branch in config.subsections("branch") {
+        let key = format!("branch.{branch}.remote");
+        if config.get(&key).as_deref() == Some(old) {
+            config::set(CONFIG_FILE, &key, new)?;
+        }
+    }
+
+    // 先移动普通引用，再改写指向它们的符号引用（如 origin/HEAD）
+    let ident = committer_ident()?;
+    let mut symbolic = Vec::new();
+    for (name, id) in refs::list_refs(&old_prefix)? {
+        let renamed = format!("{new_prefix}{}", &name[old_prefix.len()..]);
+        if is_symbolic(&name)? {
+            symbolic.push((name, renamed));
+            continue;
+        }
+        let reflog = refs::read_reflog_entries(&name)?;
+        refs::delete_ref(&name)?;
+        refs::write_ref(&renamed, &id)?;
+        if !reflog.is_empty() {
+            let path = Path::new(".git/logs").join(&renamed);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            refs::write_reflog(&renamed, &reflog)?;
+        }
+        let message = format!("remote: renamed {name} to {renamed}");
+        refs::append_reflog(&renamed, &id, &id, &ident, &message)?;
+        remove_empty_parents(&name);
+    }
+    for (name, renamed) in symbolic {
+        let target = refs::read_ref(&name)?.unwrap_or_default();
+        let target = target
+            .trim_start_matches("ref: ")
+            .replacen(&old_prefix, &new_prefix, 1);
+        std::fs::remove_file(Path::new(".git").join(&name))?;
+        refs::write_symbolic_ref(&renamed, &target)?;
+        let id = refs::resolve_ref(&renamed)?.unwrap_or_default();
+        let message = format!("remote: renamed {name} to {renamed}");
+        refs::append_reflog(&renamed, &[0; 20], &id, &ident, &message)?;
+        remove_empty_parents(&name);
+    }
+    Ok(())
+}
+
+/// 修改远程 name 的 URL
+pub(crate) fn set_url(name: &str, url: &str) -> anyhow::Result<()> {
+    let config = Config::read()?;
+    if Remote::get(&config, name)?.is_none() {
+        anyhow::bail!("No such remote '{name}'");
+    }
+    config::set(CONFIG_FILE, &format!("remote.{name}.url"), url)
+}
+
+fn is_symbolic(name: &str) -> anyhow::Result<bool> {
+    Ok(refs::read_ref(name)?.is_some_and(|content| content.starts_with("ref: ")))
+}
+
+/// 删除引用后，依次删除它在 `.git` 和 `.git/logs` 下变空的上级目录，
+/// 与 git 一样保留 `refs/remotes` 这一级
+fn remove_empty_parents(name: &str) {
+    for root in [".git", ".git/logs"] {
+        let mut name = Path::new(name).to_path_buf();
+        while name.pop() && name.components().count() > 2 {
+            if std::fs::remove_dir(Path::new(root).join(&name)).is_err() {
+                break;
+            }
+        }
+    }
+}
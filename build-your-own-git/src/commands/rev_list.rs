@@ -0,0 +1,95 @@
+use std::io::{BufWriter, Write};
+
+use crate::revision::{
+    RevWalk,
+    list_objects::{Filter, MissingAction, list_objects},
+};
+
+pub(crate) struct RevListOptions {
+    pub(crate) objects: bool,
+    pub(crate) count: bool,
+    pub(crate) left_right: bool,
+    pub(crate) missing: MissingAction,
+    pub(crate) filter: Option<Filter>,
+    pub(crate) filter_print_omitted: bool,
+    pub(crate) all: bool,
+    pub(crate) branches: bool,
+    pub(crate) tags: bool,
+    pub(crate) max_count: Option<usize>,
+}
+
+pub(crate) async fn invoke(revisions: &[String], options: RevListOptions) -> anyhow::Result<()> {
+    let mut walk = RevWalk::new();
+    if options.all {
+        walk.push_refs("refs/").await?;
+    }
+    if options.branches {
+        walk.push_refs("refs/heads/").await?;
+    }
+    if options.tags {
+        walk.push_refs("refs/tags/").await?;
+    }
+    for rev in revisions {
+        walk.push_arg(rev).await?;
+    }
+    anyhow::ensure!(
+        !revisions.is_empty() || options.all || options.branches || options.tags,
+        "rev-list requires at least one revision"
+    );
+
+    let mut commits = walk.walk().await?;
+    if let Some(max_count) = options.max_count {
+        commits.truncate(max_count);
+    }
+
+    let stdout = std::io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    if options.count {
+        if options.left_right {
+            let left = commits
+                .iter()
+                .filter(|id| walk.is_left(id) == Some(true))
+                .count();
+            writeln!(out, "{left}\t{}", commits.len() - left)?;
+        } else {
+            writeln!(out, "{}", commits.len())?;
+        }
+        out.flush()?;
+        return Ok(());
+    }
+
+    for id in &commits {
+        if options.left_right {
+            match walk.is_left(id) {
+                Some(true) => write!(out, "<")?,
+                Some(false) => write!(out, ">")?,
+                None => {}
+            }
+        }
+        writeln!(out, "{}", hex::encode(id))?;
+    }
+
+    if options.objects {
+        let list = list_objects(
+            &walk,
+            &commits,
+            options.filter,
+            options.filter_print_omitted,
+            options.missing,
+        )
+        .await?;
+        for (hash, path) in &list.objects {
+            writeln!(out, "{} {path}", hex::encode(hash))?;
+        }
+        if options.filter_print_omitted {
+            for hash in &list.omitted {
+                writeln!(out, "~{}", hex::encode(hash))?;
+            }
+        }
+        for hash in &list.missing {
+            writeln!(out, "?{}", hex::encode(hash))?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
@@ -0,0 +1,465 @@
+//! serve：把一个目录下的仓库经由智能 HTTP 和 `git://` 协议提供出去，相当于 git http-backend
+//! 加上 Web 服务器，以及 git daemon。
+//!
+//! 每个请求都启动我们自己的 upload-pack 或 receive-pack 处理：HTTP 用 `--stateless-rpc`，
+//! 请求体作为它的标准输入；`git://` 连接直接接到它的标准输入输出上。
+//! 与 git daemon 一样，只提供有 `git-daemon-export-ok` 文件的仓库，除非指定了 `--export-all`
+
+use std::{
+    io::Read,
+    path::{Component, Path, PathBuf},
+    process::Stdio,
+    sync::Arc,
+};
+
+use anyhow::Context;
+use flate2::read::GzDecoder;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    process::Command,
+    sync::Semaphore,
+};
+
+use crate::{commands::clone::source_git_dir, pkt_line, transport::http::percent_decode};
+
+pub(crate) struct ServeOptions {
+    /// 监听的地址
+    pub(crate) listen: String,
+    /// 智能 HTTP 的端口，None 时不提供 HTTP
+    pub(crate) http_port: Option<u16>,
+    /// `git://` 协议的端口，None 时不提供
+    pub(crate) git_port: Option<u16>,
+    /// 提供所有仓库，不要求 `git-daemon-export-ok`
+    pub(crate) export_all: bool,
+    /// 不接受推送
+    pub(crate) read_only: bool,
+    /// 同时处理的连接数上限，超过的连接直接拒绝
+    pub(crate) max_connections: usize,
+    /// HTTP 请求体（解压后）的大小上限，超过的请求回复 413
+    pub(crate) max_request_size: usize,
+}
+
+/// 各个连接共享的设置
+struct Server {
+    base: PathBuf,
+    export_all: bool,
+    read_only: bool,
+    max_request_size: usize,
+    /// 处理请求的程序，就是我们自己
+    program: PathBuf,
+    connections: Arc<Semaphore>,
+}
+
+pub(crate) async fn invoke(directory: &str, options: &ServeOptions) -> anyhow::Result<()> {
+    let base = Path::new(directory)
+        .canonicalize()
+        .with_context(|| format!("cannot access '{directory}'"))?;
+    let server = Arc::new(Server {
+        base,
+        export_all: options.export_all,
+        read_only: options.read_only,
+        max_request_size: options.max_request_size,
+        program: std::env::current_exe().context("cannot find own executable")?,
+        connections: Arc::new(Semaphore::new(options.max_connections)),
+    });
+
+    let mut tasks = Vec::new();
+    if let Some(port) = options.http_port {
+        let listener = bind(&options.listen, port).await?;
+        println!("Serving http://{}/", listener.local_addr()?);
+        tasks.push(tokio::spawn(accept(
+            listener,
+            server.clone(),
+            Protocol::Http,
+        )));
+    }
+    if let Some(port) = options.git_port {
+        let listener = bind(&options.listen, port).await?;
+        println!("Serving git://{}/", listener.local_addr()?);
+        tasks.push(tokio::spawn(accept(
+            listener,
+            server.clone(),
+            Protocol::Git,
+        )));
+    }
+    // 让启动脚本可以从标准输出读到实际的端口（端口为 0 时由系统分配）
+    std::io::Write::flush(&mut std::io::stdout())?;
+    for task in tasks {
+        task.await??;
+    }
+    Ok(())
+}
+
+async fn bind(listen: &str, port: u16) -> anyhow::Result<TcpListener> {
+    TcpListener::bind((listen, port))
+        .await
+        .with_context(|| format!("unable to listen on {listen}:{port}"))
+}
+
+#[derive(Clone, Copy)]
+enum Protocol {
+    Http,
+    Git,
+}
+
+async fn accept(
+    listener: TcpListener,
+    server: Arc<Server>,
+    protocol: Protocol,
+) -> anyhow::Result<()> {
+    loop {
+        let (mut stream, peer) = listener.accept().await?;
+        let server = server.clone();
+        tokio::spawn(async move {
+            // 连接数到了上限时不排队，直接拒绝
+            let permit = server.connections.clone().try_acquire_owned();
+            let result = match (protocol, permit) {
+                (Protocol::Http, Ok(_permit)) => server.http(stream).await,
+                (Protocol::Git, Ok(_permit)) => server.daemon(stream).await,
+                (Protocol::Http, Err(_)) => {
+                    respond(
+                        stream,
+                        503,
+                        "text/plain",
+                        b"too many connections\n".to_vec(),
+                    )
+                    .await
+                }
+                // 先读掉请求再回复，否则客户端写请求时连接已关闭
+                (Protocol::Git, Err(_)) => match read_request(&mut stream).await {
+                    Ok(_) => daemon_error(stream, "too many connections".to_string()).await,
+                    Err(e) => Err(e),
+                },
+            };
+            if let Err(e) = result {
+                eprintln!("serve: {peer}: {e:#}");
+            }
+        });
+    }
+}
+
+/// HTTP 请求的路径对应的服务
+enum Route {
+    /// `GET <仓库>/info/refs?service=<服务>`
+    Advertise(String),
+    /// `POST <仓库>/<服务>`
+    Rpc(String),
+}
+
+impl Server {
+    /// 请求路径 path 对应的已提供的仓库。与 git daemon 一样依次尝试 `path`、`path.git`，
+    /// 不允许用 `..` 跳出根目录
+    fn repository(&self, path: &str) -> Option<PathBuf> {
+        let relative = Path::new(path.trim_start_matches('/'));
+        if relative
+            .components()
+            .any(|component| !matches!(component, Component::Normal(_)))
+        {
+            return None;
+        }
+        let path = self.base.join(relative);
+        let mut with_git = path.clone().into_os_string();
+        with_git.push(".git");
+        let candidates = [path, PathBuf::from(with_git)];
+        let git_dir = candidates
+            .iter()
+            .find_map(|candidate| source_git_dir(&candidate.to_string_lossy()).ok())?;
+        match self.export_all || git_dir.join("git-daemon-export-ok").is_file() {
+            true => Some(git_dir),
+            false => None,
+        }
+    }
+
+    /// 处理一个 HTTP 请求，回复后关闭连接
+    async fn http(&self, stream: TcpStream) -> anyhow::Result<()> {
+        let mut reader = BufReader::new(stream);
+        let request_line = read_line(&mut reader).await?;
+        let mut parts = request_line.split(' ');
+        let (method, target) = (
+            parts.next().unwrap_or_default(),
+            parts.next().unwrap_or("/"),
+        );
+        let mut headers = Vec::new();
+        loop {
+            let line = read_line(&mut reader).await?;
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+            }
+        }
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        };
+        if header("expect").is_some_and(|value| value.eq_ignore_ascii_case("100-continue")) {
+            reader
+                .get_mut()
+                .write_all(b"HTTP/1.1 100 Continue\r\n\r\n")
+                .await?;
+        }
+        let chunked =
+            header("transfer-encoding").is_some_and(|value| value.eq_ignore_ascii_case("chunked"));
+        let gzip =
+            header("content-encoding").is_some_and(|value| value.eq_ignore_ascii_case("gzip"));
+        let length = header("content-length").map(str::to_string);
+        let Some(body) =
+            read_body(&mut reader, chunked, length, gzip, self.max_request_size).await?
+        else {
+            return respond(reader.into_inner(), 413, "text/plain", Vec::new()).await;
+        };
+        let protocol = header("git-protocol").map(str::to_string);
+        let stream = reader.into_inner();
+
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let path = percent_decode(path);
+        let (repository, route) = if let Some(repository) = path.strip_suffix("/info/refs") {
+            let service = query
+                .split('&')
+                .find_map(|pair| pair.strip_prefix("service="))
+                .unwrap_or_default();
+            (repository, Route::Advertise(service.to_string()))
+        } else if let Some((repository, service)) = path.rsplit_once('/') {
+            (repository, Route::Rpc(service.to_string()))
+        } else {
+            return respond(stream, 404, "text/plain", Vec::new()).await;
+        };
+        let (service, expected_method) = match &route {
+            Route::Advertise(service) => (service.as_str(), "GET"),
+            Route::Rpc(service) => (service.as_str(), "POST"),
+        };
+        // 只支持智能 HTTP，没有 service 参数的哑协议请求也当作不存在
+        let Some(git_dir) = self
+            .repository(repository)
+            .filter(|_| matches!(service, "git-upload-pack" | "git-receive-pack"))
+        else {
+            return respond(stream, 404, "text/plain", Vec::new()).await;
+        };
+        if method != expected_method {
+            return respond(stream, 405, "text/plain", Vec::new()).await;
+        }
+        if service == "git-receive-pack" && self.read_only {
+            return respond(stream, 403, "text/plain", Vec::new()).await;
+        }
+
+        let mut args = vec!["--stateless-rpc"];
+        if matches!(route, Route::Advertise(_)) {
+            args.push("--advertise-refs");
+        }
+        let mut command = self.command(service, &args, &git_dir);
+        // 推送没有协议 v2，只给 upload-pack 传递协议版本
+        if let (Some(protocol), "git-upload-pack") = (&protocol, service) {
+            command.env("GIT_PROTOCOL", protocol);
+        }
+        let output = run(command, body).await?;
+        match route {
+            Route::Advertise(service) => {
+                // 与 http-backend 一样，协议 v0 的通告前面加上 `# service=<服务>` 和 flush
+                let v2 = service == "git-upload-pack"
+                    && protocol.is_some_and(|protocol| protocol.contains("version=2"));
+                let mut data = Vec::new();
+                if !v2 {
+                    pkt_line::write_line(&mut data, &format!("# service={service}"))?;
+                    pkt_line::flush(&mut data)?;
+                }
+                data.extend(output);
+                let content_type = format!("application/x-{service}-advertisement");
+                respond(stream, 200, &content_type, data).await
+            }
+            Route::Rpc(service) => {
+                let content_type = format!("application/x-{service}-result");
+                respond(stream, 200, &content_type, output).await
+            }
+        }
+    }
+
+    /// 处理一个 `git://` 连接：第一个 pkt-line 是 `<服务> <路径>\0host=<主机>\0`，
+    /// 之后可能还有 `\0` 分隔的额外参数，如 `version=2`
+    async fn daemon(&self, mut stream: TcpStream) -> anyhow::Result<()> {
+        let request = read_request(&mut stream).await?;
+        let mut fields = request.trim_end_matches('\n').split('\0');
+        let line = fields.next().unwrap_or_default();
+        // host= 之后以空字段分隔的是额外参数
+        let extra: Vec<&str> = fields
+            .skip_while(|field| !field.is_empty())
+            .filter(|field| !field.is_empty())
+            .collect();
+        let Some((service, path)) = line.split_once(' ') else {
+            anyhow::bail!("bad request '{line}'");
+        };
+        let enabled = match service {
+            "git-upload-pack" => true,
+            "git-receive-pack" => !self.read_only,
+            _ => false,
+        };
+        // 与 git daemon 一样不区分仓库不存在、没有导出和服务没有启用，免得泄露仓库是否存在
+        let Some(git_dir) = self.repository(path).filter(|_| enabled) else {
+            let message = format!("access denied or repository not exported: {path}");
+            return daemon_error(stream, message).await;
+        };
+
+        let mut command = self.command(service, &[], &git_dir);
+        if !extra.is_empty() && service == "git-upload-pack" {
+            command.env("GIT_PROTOCOL", extra.join(":"));
+        }
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("cannot run {service}"))?;
+        let (mut from_client, mut to_client) = stream.into_split();
+        let mut stdin = child.stdin.take().context("no stdin")?;
+        let mut stdout = child.stdout.take().context("no stdout")?;
+        // 客户端说完了或服务端退出了，转发客户端输入的任务都随之结束
+        let input = tokio::spawn(async move {
+            let _ = tokio::io::copy(&mut from_client, &mut stdin).await;
+        });
+        tokio::io::copy(&mut stdout, &mut to_client).await?;
+        input.abort();
+        child.wait().await?;
+        to_client.shutdown().await?;
+        Ok(())
+    }
+
+    /// 运行服务 service（`git-upload-pack` 或 `git-receive-pack`）的命令
+    fn command(&self, service: &str, args: &[&str], git_dir: &Path) -> Command {
+        let mut command = Command::new(&self.program);
+        command
+            .arg(service.trim_start_matches("git-"))
+            .args(args)
+            .arg(git_dir)
+            .env_remove("GIT_PROTOCOL")
+            .stderr(Stdio::inherit());
+        command
+    }
+}
+
+/// 运行 command，把 input 写入它的标准输入，返回它的标准输出
+async fn run(mut command: Command, input: Vec<u8>) -> anyhow::Result<Vec<u8>> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("cannot run service")?;
+    let mut stdin = child.stdin.take().context("no stdin")?;
+    // 边写边读，避免双方都等着对方读取时卡住
+    let writer = tokio::spawn(async move {
+        let _ = stdin.write_all(&input).await;
+    });
+    let output = child.wait_with_output().await?;
+    writer.await?;
+    Ok(output.stdout)
+}
+
+/// 读取请求体，按 chunked 或 Content-Length 分帧，gzip 时解压。
+/// 超过 limit 字节（解压后也算）时不再读取，返回 None
+async fn read_body<R: AsyncRead + Unpin>(
+    reader: &mut BufReader<R>,
+    chunked: bool,
+    length: Option<String>,
+    gzip: bool,
+    limit: usize,
+) -> anyhow::Result<Option<Vec<u8>>> {
+    let mut body = Vec::new();
+    if chunked {
+        loop {
+            let line = read_line(reader).await?;
+            let size = line.split(';').next().unwrap_or_default().trim();
+            let size = usize::from_str_radix(size, 16)
+                .with_context(|| format!("bad chunk size '{line}'"))?;
+            if size == 0 {
+                while !read_line(reader).await?.is_empty() {}
+                break;
+            }
+            if size > limit - body.len() {
+                return Ok(None);
+            }
+            let start = body.len();
+            body.resize(start + size, 0);
+            reader.read_exact(&mut body[start..]).await?;
+            read_line(reader).await?;
+        }
+    } else if let Some(length) = length {
+        let length: u64 = length.parse().context("bad Content-Length")?;
+        if length > limit as u64 {
+            return Ok(None);
+        }
+        body.resize(length as usize, 0);
+        reader.read_exact(&mut body).await?;
+    }
+    if gzip {
+        let mut decoded = Vec::new();
+        GzDecoder::new(body.as_slice())
+            .take(limit as u64 + 1)
+            .read_to_end(&mut decoded)
+            .context("bad gzip request body")?;
+        if decoded.len() > limit {
+            return Ok(None);
+        }
+        body = decoded;
+    }
+    Ok(Some(body))
+}
+
+/// 读取以 CRLF 结尾的一行，不含行尾
+async fn read_line<R: AsyncRead + Unpin>(reader: &mut BufReader<R>) -> anyhow::Result<String> {
+    let mut line = String::new();
+    let n = reader.read_line(&mut line).await?;
+    anyhow::ensure!(n > 0, "connection closed");
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+async fn respond(
+    mut stream: TcpStream,
+    status: u16,
+    content_type: &str,
+    body: Vec<u8>,
+) -> anyhow::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        _ => "Service Unavailable",
+    };
+    let head = format!(
+        "HTTP/1.1 {status} {reason}\r\n\
+         Content-Type: {content_type}\r\n\
+         Content-Length: {}\r\n\
+         Cache-Control: no-cache\r\n\
+         Connection: close\r\n\r\n",
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(&body).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// 读取 `git://` 连接的第一个 pkt-line
+async fn read_request(stream: &mut TcpStream) -> anyhow::Result<String> {
+    let mut header = [0u8; 4];
+    stream.read_exact(&mut header).await?;
+    let len = std::str::from_utf8(&header)
+        .ok()
+        .and_then(|len| usize::from_str_radix(len, 16).ok())
+        .filter(|len| *len > 4)
+        .context("bad request")?;
+    let mut request = vec![0; len - 4];
+    stream.read_exact(&mut request).await?;
+    Ok(String::from_utf8_lossy(&request).into_owned())
+}
+
+/// git daemon 拒绝请求时回复一个 `ERR <原因>` 并关闭连接
+async fn daemon_error(mut stream: TcpStream, message: String) -> anyhow::Result<()> {
+    let mut packet = Vec::new();
+    pkt_line::write_line(&mut packet, &format!("ERR {message}"))?;
+    stream.write_all(&packet).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,586 @@
+//! stash：把工作区和暂存区的修改保存成提交，恢复到干净的状态，之后再取回来。
+//!
+//! 与 git 一样，每个 stash 是一个工作区提交 W，父提交依次是当时的 HEAD、保存暂存区的提交 I
+//! 和（`-u` 时）保存未跟踪文件的根提交 U。最新的 stash 记在 `refs/stash` 中，
+//! 更早的在它的 reflog 里，`stash@{n}` 就是 reflog 中倒数第 n 条
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::Context;
+
+use crate::{
+    commands::{
+        commit::{committer_ident, write_commit},
+        diff::{DiffOptions, matches_paths},
+        status::{Untracked, untracked_files},
+    },
+    diff::{
+        Side, flatten_tree, index_files, read_worktree_file, rename::RenameOptions, worktree_files,
+    },
+    hook,
+    index::{Index, IndexEntry},
+    merge::tree::{TreeMergeOptions, merge_trees},
+    objects::{
+        Kind, Mode, Object, commit::Commit, find_unique_abbrev, tree::write_tree_from_files,
+    },
+    refs,
+    revision::resolve,
+    sequencer::{apply_merge, print_merge_messages, print_status},
+    worktree,
+};
+
+const STASH_REF: &str = "refs/stash";
+
+pub(crate) struct PushOptions {
+    /// `-k`，暂存区的修改保存后仍然留在暂存区和工作区
+    pub(crate) keep_index: bool,
+    /// `-u`，同时保存并删除未跟踪的文件
+    pub(crate) include_untracked: bool,
+    /// `-m`，代替默认的 `WIP on <分支>: <提交>` 说明
+    pub(crate) message: Option<String>,
+    pub(crate) quiet: bool,
+    /// 只保存并还原这些路径
+    pub(crate) paths: Vec<String>,
+}
+
+/// 保存修改并把它们从工作区和暂存区中去掉，路径参数没有匹配任何文件时返回 false
+pub(crate) async fn push(options: PushOptions) -> anyhow::Result<bool> {
+    let head = refs::resolve_ref("HEAD")?.context("You do not have the initial commit yet")?;
+    let head_commit = Commit::read(&head).await?;
+    let index = Index::read().await?;
+    if !check_merged(&index) {
+        return Ok(false);
+    }
+    let specs = &options.paths;
+
+    let untracked: Vec<String> = if options.include_untracked {
+        untracked_files(&index, Untracked::All)?
+            .into_iter()
+            // 嵌套的仓库不保存
+            .filter(|path| !path.ends_with('/') && matches_paths(path, specs))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let unmatched: Vec<&String> = specs
+        .iter()
+        .filter(|spec| {
+            let spec = std::slice::from_ref(*spec);
+            !index
+                .entries
+                .iter()
+                .any(|entry| matches_paths(&entry.path, spec))
+                && !untracked.iter().any(|path| matches_paths(path, spec))
+        })
+        .collect();
+    if !unmatched.is_empty() {
+        for spec in unmatched {
+            eprintln!("error: pathspec '{spec}' did not match any file(s) known to git");
+        }
+        eprintln!("Did you forget to 'git add'?");
+        return Ok(false);
+    }
+
+    let head_files = flatten_tree(&head_commit.tree).await?;
+    let staged = index_files(&index);
+    let worktree = worktree_files(&index).await?;
+    let selected = |files: &BTreeMap<String, Side>| -> BTreeMap<String, Side> {
+        files
+            .iter()
+            .filter(|(path, _)| matches_paths(path, specs))
+            .map(|(path, side)| (path.clone(), *side))
+            .collect()
+    };
+    if selected(&head_files) == selected(&staged)
+        && selected(&staged) == selected(&worktree)
+        && untracked.is_empty()
+    {
+        println!("No local changes to save");
+        return Ok(true);
+    }
+
+    // 说明中的 `<分支>: <提交> <标题>`
+    let branch = match refs::head_branch()? {
+        Some(branch) => branch
+            .strip_prefix("refs/heads/")
+            .unwrap_or(&branch)
+            .to_string(),
+        None => "(no branch)".to_string(),
+    };
+    let head_hex = hex::encode(head);
+    let on = format!(
+        "{branch}: {} {}",
+        find_unique_abbrev(&head_hex, 7),
+        head_commit.subject()
+    );
+
+    let index_tree = write_tree_from_files(
+        staged
+            .iter()
+            .map(|(path, side)| (path.as_str(), side.mode, side.hash)),
+    )
+    .await?;
+    let index_commit = write_commit(
+        &hex::encode(index_tree),
+        &format!("index on {on}"),
+        std::slice::from_ref(&head_hex),
+        None,
+    )
+    .await?;
+    let mut parents = vec![head_hex, hex::encode(index_commit)];
+    if !untracked.is_empty() {
+        let mut files = Vec::new();
+        for path in &untracked {
+            if let Some(side) = write_worktree_blob(path, None).await? {
+                files.push((path.as_str(), side.mode, side.hash));
+            }
+        }
+        let tree = write_tree_from_files(files).await?;
+        let commit = write_commit(
+            &hex::encode(tree),
+            &format!("untracked files on {on}"),
+            &[],
+            None,
+        )
+        .await?;
+        parents.push(hex::encode(commit));
+    }
+
+    // 工作区提交：暂存区加上选中路径在工作区中的版本
+    let mut work_files = staged.clone();
+    for path in staged.keys().filter(|path| matches_paths(path, specs)) {
+        match worktree.get(path) {
+            Some(side) if staged.get(path) == Some(side) => {}
+            Some(_) => {
+                let staged = staged.get(path).map(|side| side.mode);
+                let side = write_worktree_blob(path, staged)
+                    .await?
+                    .context("file vanished")?;
+                work_files.insert(path.clone(), side);
+            }
+            None => {
+                work_files.remove(path);
+            }
+        }
+    }
+    let work_tree = write_tree_from_files(
+        work_files
+            .iter()
+            .map(|(path, side)| (path.as_str(), side.mode, side.hash)),
+    )
+    .await?;
+    let message = match &options.message {
+        Some(message) => format!("On {branch}: {message}"),
+        None => format!("WIP on {on}"),
+    };
+    let stash = write_commit(&hex::encode(work_tree), &message, &parents, None).await?;
+    let old = refs::resolve_ref(STASH_REF)?.unwrap_or_default();
+    refs::write_ref(STASH_REF, &stash)?;
+    refs::append_reflog(STASH_REF, &old, &stash, &committer_ident()?, &message)?;
+    if !options.quiet {
+        println!("Saved working directory and index state {message}");
+    }
+
+    // 选中的路径恢复成 HEAD（`-k` 时是暂存区）的版本，其余路径不动
+    let target = if options.keep_index {
+        &staged
+    } else {
+        &head_files
+    };
+    let mut index = reset_paths(&index, target, specs).await?;
+    index.write().await?;
+    for path in &untracked {
+        worktree::remove_file(path)?;
+    }
+    Ok(true)
+}
+
+/// 列出所有 stash，最新的在最前面
+pub(crate) fn list() -> anyhow::Result<()> {
+    for (n, entry) in refs::read_reflog_entries(STASH_REF)?
+        .iter()
+        .rev()
+        .enumerate()
+    {
+        println!("stash@{{{n}}}: {}", entry.message);
+    }
+    Ok(())
+}
+
+/// 显示 stash 相对它的 HEAD 的修改，默认是 --stat
+pub(crate) async fn show(stash: Option<&str>, stat: bool, patch: bool) -> anyhow::Result<()> {
+    let commit = resolve_stash(stash).await?;
+    let args = [hex::encode(commit.parents[0]), hex::encode(commit.tree)];
+    let options = |stat| DiffOptions {
+        cached: false,
+        patch: Default::default(),
+        renames: RenameOptions::renames(),
+        stat,
+    };
+    if stat || !patch {
+        crate::commands::diff::invoke(&args, &[], options(true)).await?;
+    }
+    if patch {
+        if stat {
+            println!();
+        }
+        crate::commands::diff::invoke(&args, &[], options(false)).await?;
+    }
+    Ok(())
+}
+
+/// 把 stash 中的修改应用到当前的工作区，有冲突或未跟踪的文件无法恢复时返回 false。
+/// index 为真时（`--index`）同时恢复暂存区，否则只有新增的文件留在暂存区
+pub(crate) async fn apply(stash: Option<&str>, index: bool, quiet: bool) -> anyhow::Result<bool> {
+    let commit = resolve_stash(stash).await?;
+    apply_commit(&commit, index, quiet).await
+}
+
+/// 应用 stash 后把它删除，应用失败时保留
+pub(crate) async fn pop(stash: Option<&str>, index: bool, quiet: bool) -> anyhow::Result<bool> {
+    let (name, position) = stash_entry(stash)?;
+    let commit = resolve_stash(Some(&name)).await?;
+    if !apply_commit(&commit, index, quiet).await? {
+        println!("The stash entry is kept in case you need it again.");
+        return Ok(false);
+    }
+    drop_entry(&name, position, quiet)
+}
+
+/// 删除一个 stash
+pub(crate) fn drop(stash: Option<&str>, quiet: bool) -> anyhow::Result<()> {
+    let (name, position) = stash_entry(stash)?;
+    drop_entry(&name, position, quiet)?;
+    Ok(())
+}
+
+/// 删除所有 stash
+pub(crate) fn clear() -> anyhow::Result<()> {
+    refs::delete_ref(STASH_REF)
+}
+
+/// 在 stash 所基于的提交上创建并切换到新分支，恢复修改和暂存区，成功后删除 stash
+pub(crate) async fn branch(name: &str, stash: Option<&str>) -> anyhow::Result<bool> {
+    let (entry, position) = stash_entry(stash)?;
+    let commit = resolve_stash(Some(&entry)).await?;
+    let branch = format!("refs/heads/{name}");
+    anyhow::ensure!(
+        refs::resolve_ref(&branch)?.is_none(),
+        "a branch named '{name}' already exists"
+    );
+    let head = refs::resolve_ref("HEAD")?.context("You do not have the initial commit yet")?;
+    let base = commit.parents[0];
+    let index = Index::read().await?;
+    let old = flatten_tree(&Commit::read(&head).await?.tree).await?;
+    let new = flatten_tree(&Commit::read(&base).await?.tree).await?;
+    let mut index = worktree::switch(&index, &old, &new, "checkout", false).await?;
+    index.write().await?;
+    refs::write_ref(&branch, &base)?;
+    refs::write_symbolic_ref("HEAD", &branch)?;
+    eprintln!("Switched to a new branch '{name}'");
+    hook::run(
+        "post-checkout",
+        &[&hex::encode(head), &hex::encode(base), "1"],
+    )?;
+    if !apply_commit(&commit, true, false).await? {
+        return Ok(false);
+    }
+    drop_entry(&entry, position, false)
+}
+
+async fn apply_commit(commit: &Commit, restore_index: bool, quiet: bool) -> anyhow::Result<bool> {
+    let index = Index::read().await?;
+    if !check_merged(&index) {
+        return Ok(false);
+    }
+    let base = Commit::read(&commit.parents[0]).await?;
+    let stashed_index = Commit::read(&commit.parents[1]).await?;
+    let base_files = flatten_tree(&base.tree).await?;
+    let current = index_files(&index);
+
+    // 先把暂存区的修改合并到当前暂存区上，合并不干净时放弃。暂存区没有修改或者
+    // 已经与当前暂存区相同时不需要恢复
+    let current_tree = write_tree_from_files(
+        current
+            .iter()
+            .map(|(path, side)| (path.as_str(), side.mode, side.hash)),
+    )
+    .await?;
+    let index_files_after =
+        if restore_index && stashed_index.tree != base.tree && stashed_index.tree != current_tree {
+            let result = merge_trees(
+                &base_files,
+                &current,
+                &flatten_tree(&stashed_index.tree).await?,
+                TreeMergeOptions::new("HEAD", "stash", "base"),
+            )
+            .await?;
+            anyhow::ensure!(
+                result.conflicts.is_empty(),
+                "Conflicts in index. Try without --index."
+            );
+            Some(result.files)
+        } else {
+            None
+        };
+
+    let options = TreeMergeOptions::new(
+        "Updated upstream",
+        "Stashed changes",
+        "Version stash was based on",
+    );
+    let stashed = flatten_tree(&commit.tree).await?;
+    let mut ok = match apply_merge(&index, &base_files, &current, &stashed, options, "merge").await
+    {
+        Ok(result) => {
+            print_merge_messages(&result)?;
+            if result.conflicts.is_empty() {
+                // 暂存区恢复成 stash 中的暂存区，或者去掉合并带来的修改，只留下新增的文件
+                let merged = Index::read().await?;
+                let files = match index_files_after {
+                    Some(files) => files,
+                    None => {
+                        let mut files = current.clone();
+                        for (path, side) in index_files(&merged) {
+                            if !current.contains_key(&path) {
+                                files.insert(path, side);
+                            }
+                        }
+                        files
+                    }
+                };
+                reset_index(&merged, &files).write().await?;
+                true
+            } else {
+                if index_files_after.is_some() {
+                    eprintln!("Index was not unstashed.");
+                }
+                false
+            }
+        }
+        // 与 git 一样，本地修改会被覆盖时照常输出状态
+        Err(e) => {
+            eprintln!("error: {e}");
+            false
+        }
+    };
+
+    if let Some(untracked) = commit.parents.get(2) {
+        if !restore_untracked(untracked).await? {
+            eprintln!("error: could not restore untracked files from stash");
+            ok = false;
+        }
+    }
+    if !quiet {
+        print_status().await?;
+    }
+    Ok(ok)
+}
+
+/// 把未跟踪文件提交中的文件写回工作区，已经存在的文件不覆盖，有这样的文件时返回 false
+async fn restore_untracked(commit: &[u8; 20]) -> anyhow::Result<bool> {
+    let files = flatten_tree(&Commit::read(commit).await?.tree).await?;
+    let mut ok = true;
+    for (path, side) in files {
+        if std::fs::symlink_metadata(&path).is_ok() {
+            eprintln!("{path} already exists, no checkout");
+            ok = false;
+            continue;
+        }
+        worktree::checkout_file(&path, side).await?;
+    }
+    Ok(ok)
+}
+
+/// 删除 reflog 中的第 position 条（0 是最新的），后一条的旧值接上被删除记录的旧值。
+/// 删除的是最新的一条时 `refs/stash` 指向新的最新记录，删光时删除这个引用
+fn drop_entry(name: &str, position: usize, quiet: bool) -> anyhow::Result<bool> {
+    let mut entries = refs::read_reflog_entries(STASH_REF)?;
+    let at = entries.len() - 1 - position;
+    let removed = entries.remove(at);
+    if let Some(next) = entries.get_mut(at) {
+        next.old = removed.old;
+    }
+    match entries.last() {
+        Some(last) => {
+            if position == 0 {
+                refs::write_ref(STASH_REF, &last.new)?;
+            }
+            refs::write_reflog(STASH_REF, &entries)?;
+        }
+        None => refs::delete_ref(STASH_REF)?,
+    }
+    if !quiet {
+        println!("Dropped {name} ({})", hex::encode(removed.new));
+    }
+    Ok(true)
+}
+
+/// 解析命令行上的 stash，默认是最新的一个，纯数字 n 表示 `stash@{n}`。
+/// 不是 stash 形状（至少两个父提交）的提交报错
+async fn resolve_stash(stash: Option<&str>) -> anyhow::Result<Commit> {
+    let name = stash_name(stash)?;
+    let id = resolve(&name)
+        .await
+        .map_err(|_| anyhow::anyhow!("{name} is not a valid reference"))?;
+    let commit = Commit::read(&id).await?;
+    anyhow::ensure!(
+        commit.parents.len() >= 2,
+        "'{name}' is not a stash-like commit"
+    );
+    Ok(commit)
+}
+
+/// drop 和 pop 只接受 `stash@{n}` 形式的 stash，返回名称和它在 reflog 中的位置（0 是最新的）
+fn stash_entry(stash: Option<&str>) -> anyhow::Result<(String, usize)> {
+    let name = stash_name(stash)?;
+    let (reference, position) = name
+        .strip_suffix('}')
+        .and_then(|rest| rest.rsplit_once("@{"))
+        .and_then(|(reference, n)| Some((reference, n.parse::<usize>().ok()?)))
+        .filter(|(reference, _)| {
+            refs::dwim_ref(reference).ok().flatten().as_deref() == Some(STASH_REF)
+        })
+        .with_context(|| format!("'{name}' is not a stash reference"))?;
+    let count = refs::read_reflog_entries(STASH_REF)?.len();
+    anyhow::ensure!(
+        position < count,
+        "log for '{reference}' only has {count} entries"
+    );
+    Ok((name, position))
+}
+
+/// 命令行上的 stash 对应的名称，没有 stash 时报错
+fn stash_name(stash: Option<&str>) -> anyhow::Result<String> {
+    let exists = refs::resolve_ref(STASH_REF)?.is_some();
+    match stash {
+        None => {
+            anyhow::ensure!(exists, "No stash entries found.");
+            Ok(format!("{STASH_REF}@{{0}}"))
+        }
+        Some(stash) => {
+            anyhow::ensure!(exists, "{stash} is not a valid reference");
+            Ok(if stash.bytes().all(|b| b.is_ascii_digit()) {
+                format!("{STASH_REF}@{{{stash}}}")
+            } else {
+                stash.to_string()
+            })
+        }
+    }
+}
+
+/// 暂存区中有冲突时不能保存或应用 stash，与 git 一样在标准输出上列出冲突的路径并返回 false
+fn check_merged(index: &Index) -> bool {
+    let conflicts: BTreeSet<&str> = index
+        .entries
+        .iter()
+        .filter(|entry| entry.stage > 0)
+        .map(|entry| entry.path.as_str())
+        .collect();
+    for path in &conflicts {
+        println!("{path}: needs merge");
+    }
+    conflicts.is_empty()
+}
+
+/// 把工作区文件写成 blob 对象，文件不存在时返回 None。staged 是路径在暂存区中的模式
+async fn write_worktree_blob(path: &str, staged: Option<Mode>) -> anyhow::Result<Option<Side>> {
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return Ok(None);
+    };
+    let data = read_worktree_file(path)?;
+    let hash = Object {
+        kind: Kind::Blob,
+        expected_size: data.len() as u64,
+        reader: data.as_slice(),
+    }
+    .write_object()
+    .await?;
+    Ok(Some(Side {
+        mode: worktree::worktree_mode(&metadata, staged)?,
+        hash,
+    }))
+}
+
+/// 与 files 一致的暂存区，内容没变的路径保留原来的记录（包括文件状态）
+fn reset_index(index: &Index, files: &BTreeMap<String, Side>) -> Index {
+    let staged = index_files(index);
+    let entries = files
+        .iter()
+        .map(|(path, side)| match staged.get(path) {
+            Some(old) if old == side => index
+                .entries
+                .iter()
+                .find(|entry| entry.path == *path && entry.stage == 0)
+                .cloned()
+                .expect("staged entry"),
+            _ => IndexEntry::new(path, side.mode, side.hash, 0, None),
+        })
+        .collect();
+    Index { entries }
+}
+
+/// 把匹配 specs 的路径在暂存区和工作区中都恢复成 target 中的版本，target 中没有的删除
+async fn reset_paths(
+    index: &Index,
+    target: &BTreeMap<String, Side>,
+    specs: &[String],
+) -> anyhow::Result<Index> {
+    let paths: BTreeSet<&String> = index
+        .entries
+        .iter()
+        .map(|entry| &entry.path)
+        .chain(target.keys())
+        .filter(|path| matches_paths(path, specs))
+        .collect();
+    let mut entries: Vec<IndexEntry> = index
+        .entries
+        .iter()
+        .filter(|entry| !paths.contains(&entry.path))
+        .cloned()
+        .collect();
+    let staged = index_files(index);
+    // 先删除再写入，这样文件和目录可以互相替换
+    for &path in &paths {
+        if !target.contains_key(path) {
+            worktree::remove_file(path)?;
+        }
+    }
+    for &path in &paths {
+        let Some(&side) = target.get(path) else {
+            continue;
+        };
+        let in_index = staged.get(path).map(|side| side.mode);
+        if worktree::hash_worktree_file(path, in_index).await? == Some(side) {
+            if staged.get(path) == Some(&side) {
+                entries.extend(
+                    index
+                        .entries
+                        .iter()
+                        .find(|entry| entry.path == *path && entry.stage == 0)
+                        .cloned(),
+                );
+                continue;
+            }
+            let metadata =
+                std::fs::symlink_metadata(path).with_context(|| format!("stat {path}"))?;
+            entries.push(IndexEntry::new(
+                path,
+                side.mode,
+                side.hash,
+                0,
+                Some(&metadata),
+            ));
+            continue;
+        }
+        let metadata = worktree::checkout_file(path, side).await?;
+        entries.push(IndexEntry::new(
+            path,
+            side.mode,
+            side.hash,
+            0,
+            Some(&metadata),
+        ));
+    }
+    Ok(Index { entries })
+}
@@ -0,0 +1,692 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use crate::{
+    diff::{
+        Change, Status, diff_maps, flatten_tree, index_files, quote_path,
+        rename::{RenameOptions, detect_renames},
+        worktree_files,
+    },
+    ignore::Ignore,
+    index::Index,
+    objects::{
+        Kind, Mode,
+        commit::{Commit, parse_hex},
+        find_unique_abbrev,
+    },
+    refs,
+    revision::{peel, resolve},
+    sequencer::{
+        self, Action,
+        rebase::{Progress, abbreviate_line},
+    },
+};
+
+/// 输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Format {
+    Long,
+    /// `-s`，每个文件一行 `XY path`
+    Short,
+    /// `--porcelain`，和短格式相同但不受配置影响
+    Porcelain,
+}
+
+/// `--untracked-files` 的模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Untracked {
+    No,
+    /// 完全未跟踪的目录只显示目录名
+    Normal,
+    /// 列出未跟踪目录中的每个文件
+    All,
+}
+
+impl Untracked {
+    pub(crate) fn from_str(s: &str) -> anyhow::Result<Untracked> {
+        match s {
+            "no" => Ok(Untracked::No),
+            "normal" => Ok(Untracked::Normal),
+            "all" => Ok(Untracked::All),
+            _ => anyhow::bail!("Invalid untracked files mode '{s}'"),
+        }
+    }
+}
+
+pub(crate) struct StatusOptions {
+    pub(crate) format: Format,
+    /// 短格式中也输出分支行
+    pub(crate) branch: bool,
+    pub(crate) untracked: Untracked,
+    pub(crate) renames: RenameOptions,
+}
+
+/// 一个路径在暂存区和工作区的变更，以新路径为键
+#[derive(Debug, Default)]
+struct Entry {
+    staged: Option<Change>,
+    unstaged: Option<Change>,
+    /// 冲突路径在暂存区中有哪些阶段，第 n 位对应阶段 n + 1
+    unmerged: Option<u8>,
+}
+
+/// 冲突路径的短格式状态和长格式标签
+fn unmerged_status(stages: u8) -> (&'static str, &'static str) {
+    match stages {
+        0b001 => ("DD", "both deleted:"),
+        0b010 => ("AU", "added by us:"),
+        0b011 => ("UD", "deleted by them:"),
+        0b100 => ("UA", "added by them:"),
+        0b101 => ("DU", "deleted by us:"),
+        0b110 => ("AA", "both added:"),
+        _ => ("UU", "both modified:"),
+    }
+}
+
+/// `git commit` 显示的长格式状态与 `git status` 的几处区别
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct CommitStatus {
+    /// 写进提交说明模板：不显示提示，也不输出最后一行总结
+    pub(crate) template: bool,
+    /// `--amend`：与 HEAD 的第一个父提交比较
+    pub(crate) amend: bool,
+}
+
+/// 比较 base 提交、暂存区和工作区得到的各路径状态
+struct Collected {
+    /// base 不存在（还没有提交）
+    initial: bool,
+    entries: BTreeMap<String, Entry>,
+    untracked: Vec<String>,
+}
+
+pub(crate) async fn invoke(options: StatusOptions) -> anyhow::Result<()> {
+    let index = Index::read().await?;
+    let head = resolve("HEAD").await.ok();
+    let collected = collect(&index, head, &options).await?;
+
+    let stdout = std::io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    match options.format {
+        Format::Long => {
+            let rebase = rebase_progress().await?;
+            write_long(&mut out, &collected, rebase.as_ref(), options, None)?
+        }
+        Format::Short | Format::Porcelain => {
+            if options.branch {
+                write_short_branch(&mut out, collected.initial)?;
+            }
+            write_short(&mut out, &collected.entries, &collected.untracked)?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// 提交时的长格式状态，index 是要提交的暂存区，`--amend` 时与 HEAD 的父提交比较
+pub(crate) async fn commit_status(index: &Index, commit: CommitStatus) -> anyhow::Result<String> {
+    let options = StatusOptions {
+        format: Format::Long,
+        branch: false,
+        untracked: Untracked::Normal,
+        renames: RenameOptions::renames(),
+    };
+    let head = resolve("HEAD").await.ok();
+    let base = match head {
+        Some(head) if commit.amend => Commit::read(&head).await?.parents.first().copied(),
+        head => head,
+    };
+    let collected = collect(index, base, &options).await?;
+    let rebase = rebase_progress().await?;
+    let mut out = Vec::new();
+    write_long(&mut out, &collected, rebase.as_ref(), options, Some(commit))?;
+    Ok(String::from_utf8_lossy(&out).into_owned())
+}
+
+async fn collect(
+    index: &Index,
+    base: Option<[u8; 20]>,
+    options: &StatusOptions,
+) -> anyhow::Result<Collected> {
+    let head_files = match base {
+        Some(base) => flatten_tree(&peel(base, Some(Kind::Tree)).await?).await?,
+        None => BTreeMap::new(),
+    };
+    let staged_files = index_files(index);
+
+    // 有冲突的路径不参与比较
+    let unmerged: HashSet<&str> = index
+        .entries
+        .iter()
+        .filter(|entry| entry.stage > 0)
+        .map(|entry| entry.path.as_str())
+        .collect();
+    let mut staged = diff_maps(&head_files, &staged_files);
+    staged.retain(|change| !unmerged.contains(change.path.as_str()));
+    let staged = detect_renames(staged, &BTreeMap::new(), options.renames, false).await?;
+    let mut unstaged = diff_maps(&staged_files, &worktree_files(index).await?);
+    unstaged.retain(|change| !unmerged.contains(change.path.as_str()));
+
+    let mut entries: BTreeMap<String, Entry> = BTreeMap::new();
+    for change in staged {
+        let path = change.path.clone();
+        entries.entry(path).or_default().staged = Some(change);
+    }
+    for change in unstaged {
+        let path = change.path.clone();
+        entries.entry(path).or_default().unstaged = Some(change);
+    }
+    for entry in index.entries.iter().filter(|entry| entry.stage > 0) {
+        let stages = entries.entry(entry.path.clone()).or_default();
+        *stages.unmerged.get_or_insert(0) |= 1 << (entry.stage - 1);
+    }
+    let untracked = match options.untracked {
+        Untracked::No => Vec::new(),
+        mode => untracked_files(index, mode)?,
+    };
+    Ok(Collected {
+        initial: base.is_none(),
+        entries,
+        untracked,
+    })
+}
+
+/// 长格式状态，commit 不为空时按 `git commit` 的方式显示
+fn write_long(
+    out: &mut impl Write,
+    collected: &Collected,
+    rebase: Option<&Progress>,
+    options: StatusOptions,
+    commit: Option<CommitStatus>,
+) -> anyhow::Result<()> {
+    let Collected {
+        initial,
+        entries,
+        untracked,
+    } = collected;
+    let hints = !commit.is_some_and(|commit| commit.template);
+    match rebase {
+        Some(rebase) => {
+            let onto = find_unique_abbrev(&hex::encode(rebase.onto), 7);
+            writeln!(out, "interactive rebase in progress; onto {onto}")?;
+            write_rebase_commands(out, rebase)?;
+        }
+        None => writeln!(out, "{}", branch_line()?)?,
+    }
+    let unmerged: Vec<(&String, u8)> = entries
+        .iter()
+        .filter_map(|(path, e)| Some((path, e.unmerged?)))
+        .collect();
+    // 合并进行中时提示如何结束合并，并且不再提示如何撤销暂存
+    let merging = Path::new(".git/MERGE_HEAD").exists();
+    // cherry-pick 冲突时同样不提示撤销暂存
+    let picking = merging || Path::new(".git/CHERRY_PICK_HEAD").exists();
+    if merging && !unmerged.is_empty() {
+        writeln!(out, "You have unmerged paths.")?;
+        if hints {
+            writeln!(out, "  (fix conflicts and run \"git commit\")")?;
+            writeln!(out, "  (use \"git merge --abort\" to abort the merge)")?;
+        }
+        writeln!(out)?;
+    } else if merging {
+        writeln!(out, "All conflicts fixed but you are still merging.")?;
+        if hints {
+            writeln!(out, "  (use \"git commit\" to conclude merge)")?;
+        }
+        writeln!(out)?;
+    } else if let Some(rebase) = rebase {
+        let onto = find_unique_abbrev(&hex::encode(rebase.onto), 7);
+        let editing = unmerged.is_empty() && !Path::new(sequencer::MERGE_MSG).exists();
+        match (&rebase.branch, editing) {
+            (Some(branch), false) => writeln!(
+                out,
+                "You are currently rebasing branch '{branch}' on '{onto}'."
+            )?,
+            (None, false) => writeln!(out, "You are currently rebasing.")?,
+            (Some(branch), true) => writeln!(
+                out,
+                "You are currently editing a commit while rebasing branch '{branch}' on '{onto}'."
+            )?,
+            (None, true) => writeln!(out, "You are currently editing a commit during a rebase.")?,
+        }
+        if !hints {
+            // 提交说明模板中不显示提示
+        } else if !unmerged.is_empty() {
+            writeln!(
+                out,
+                "  (fix conflicts and then run \"git rebase --continue\")"
+            )?;
+            writeln!(out, "  (use \"git rebase --skip\" to skip this patch)")?;
+            writeln!(
+                out,
+                "  (use \"git rebase --abort\" to check out the original branch)"
+            )?;
+        } else if !editing {
+            writeln!(
+                out,
+                "  (all conflicts fixed: run \"git rebase --continue\")"
+            )?;
+        } else {
+            writeln!(
+                out,
+                "  (use \"git commit --amend\" to amend the current commit)"
+            )?;
+            writeln!(
+                out,
+                "  (use \"git rebase --continue\" once you are satisfied with your changes)"
+            )?;
+        }
+        writeln!(out)?;
+    } else if let Some((action, head)) = sequencer::in_progress()? {
+        let (name, verb, operation) = match action {
+            Action::Pick => ("cherry-pick", "cherry-picking", "Cherry-pick"),
+            Action::Revert => ("revert", "reverting", "Revert"),
+        };
+        match head {
+            Some(head) => writeln!(
+                out,
+                "You are currently {verb} commit {}.",
+                find_unique_abbrev(&hex::encode(head), 7)
+            )?,
+            None => writeln!(out, "{operation} currently in progress.")?,
+        }
+        if !hints {
+            // 提交说明模板中不显示提示
+        } else if !unmerged.is_empty() {
+            writeln!(out, "  (fix conflicts and run \"git {name} --continue\")")?;
+        } else if head.is_none() {
+            writeln!(out, "  (run \"git {name} --continue\" to continue)")?;
+        } else {
+            writeln!(
+                out,
+                "  (all conflicts fixed: run \"git {name} --continue\")"
+            )?;
+        }
+        if hints {
+            writeln!(out, "  (use \"git {name} --skip\" to skip this patch)")?;
+            writeln!(
+                out,
+                "  (use \"git {name} --abort\" to cancel the {name} operation)"
+            )?;
+        }
+        writeln!(out)?;
+    }
+    if *initial && commit.is_some() {
+        writeln!(out, "\nInitial commit\n")?;
+    } else if *initial {
+        writeln!(out, "\nNo commits yet\n")?;
+    }
+
+    let staged: Vec<&Change> = entries.values().filter_map(|e| e.staged.as_ref()).collect();
+    if !staged.is_empty() {
+        writeln!(out, "Changes to be committed:")?;
+        if picking || !hints {
+            // 合并中的暂存内容不能撤销
+        } else if *initial {
+            writeln!(out, "  (use \"git rm --cached <file>...\" to unstage)")?;
+        } else {
+            writeln!(out, "  (use \"git restore --staged <file>...\" to unstage)")?;
+        }
+        for change in &staged {
+            write_change(out, change, false)?;
+        }
+        writeln!(out)?;
+    }
+
+    if !unmerged.is_empty() {
+        writeln!(out, "Unmerged paths:")?;
+        if picking || !hints {
+            // 合并中的暂存内容不能撤销
+        } else if *initial {
+            writeln!(out, "  (use \"git rm --cached <file>...\" to unstage)")?;
+        } else {
+            writeln!(out, "  (use \"git restore --staged <file>...\" to unstage)")?;
+        }
+        let both_deleted = unmerged.iter().any(|(_, stages)| *stages == 0b001);
+        let delete_modify = unmerged
+            .iter()
+            .any(|(_, stages)| matches!(stages, 0b011 | 0b101));
+        let not_deleted = unmerged
+            .iter()
+            .any(|(_, stages)| !matches!(stages, 0b001 | 0b011 | 0b101));
+        let hint = if !both_deleted && !delete_modify {
+            "  (use \"git add <file>...\" to mark resolution)"
+        } else if both_deleted && !delete_modify && !not_deleted {
+            "  (use \"git rm <file>...\" to mark resolution)"
+        } else {
+            "  (use \"git add/rm <file>...\" as appropriate to mark resolution)"
+        };
+        if hints {
+            writeln!(out, "{hint}")?;
+        }
+        for (path, stages) in &unmerged {
+            let label = unmerged_status(*stages).1;
+            writeln!(out, "\t{label:<17}{}", quote_path(path))?;
+        }
+        writeln!(out)?;
+    }
+
+    let unstaged: Vec<&Change> = entries
+        .values()
+        .filter_map(|e| e.unstaged.as_ref())
+        .collect();
+    if !unstaged.is_empty() {
+        writeln!(out, "Changes not staged for commit:")?;
+        // 有删除时提示 add/rm
+        let verb = if unstaged.iter().any(|c| c.status == Status::Deleted) {
+            "add/rm"
+        } else {
+            "add"
+        };
+        if hints {
+            writeln!(
+                out,
+                "  (use \"git {verb} <file>...\" to update what will be committed)"
+            )?;
+            writeln!(
+                out,
+                "  (use \"git restore <file>...\" to discard changes in working directory)"
+            )?;
+        }
+        for change in &unstaged {
+            write_change(out, change, true)?;
+        }
+        writeln!(out)?;
+    }
+
+    if !untracked.is_empty() {
+        writeln!(out, "Untracked files:")?;
+        if hints {
+            writeln!(
+                out,
+                "  (use \"git add <file>...\" to include in what will be committed)"
+            )?;
+        }
+        for path in untracked {
+            writeln!(out, "\t{}", quote_path(path))?;
+        }
+        writeln!(out)?;
+    }
+
+    if !staged.is_empty() {
+        return Ok(());
+    }
+    // `--amend` 的结果为空时只说没有修改
+    if commit.is_some_and(|commit| commit.amend) {
+        writeln!(out, "No changes")?;
+    } else if !hints {
+        // 提交说明模板中没有总结
+    } else if !unstaged.is_empty() || !unmerged.is_empty() {
+        writeln!(
+            out,
+            "no changes added to commit (use \"git add\" and/or \"git commit -a\")"
+        )?;
+    } else if !untracked.is_empty() {
+        writeln!(
+            out,
+            "nothing added to commit but untracked files present (use \"git add\" to track)"
+        )?;
+    } else if options.untracked == Untracked::No {
+        writeln!(out, "nothing to commit (use -u to show untracked files)")?;
+    } else if *initial {
+        writeln!(
+            out,
+            "nothing to commit (create/copy files and use \"git add\" to track)"
+        )?;
+    } else {
+        writeln!(out, "nothing to commit, working tree clean")?;
+    }
+    Ok(())
+}
+
+/// 进行中的变基，指令中的提交缩写显示
+async fn rebase_progress() -> anyhow::Result<Option<Progress>> {
+    let Some(mut rebase) = sequencer::rebase::progress()? else {
+        return Ok(None);
+    };
+    for line in rebase.done.iter_mut().chain(rebase.todo.iter_mut()) {
+        *line = abbreviate_line(line).await;
+    }
+    Ok(Some(rebase))
+}
+
+/// 变基时显示最近执行的两条指令和接下来的两条指令
+fn write_rebase_commands(out: &mut impl Write, rebase: &Progress) -> std::io::Result<()> {
+    const SHOWN: usize = 2;
+    match rebase.done.len() {
+        0 => writeln!(out, "No commands done.")?,
+        1 => writeln!(out, "Last command done (1 command done):")?,
+        n => writeln!(out, "Last commands done ({n} commands done):")?,
+    }
+    for line in &rebase.done[rebase.done.len().saturating_sub(SHOWN)..] {
+        writeln!(out, "   {line}")?;
+    }
+    if rebase.done.len() > SHOWN {
+        writeln!(out, "  (see more in file .git/rebase-merge/done)")?;
+    }
+    match rebase.todo.len() {
+        0 => writeln!(out, "No commands remaining.")?,
+        1 => writeln!(out, "Next command to do (1 remaining command):")?,
+        n => writeln!(out, "Next commands to do ({n} remaining commands):")?,
+    }
+    for line in rebase.todo.iter().take(SHOWN) {
+        writeln!(out, "   {line}")?;
+    }
+    if !rebase.todo.is_empty() {
+        writeln!(out, "  (use \"git rebase --edit-todo\" to view and edit)")?;
+    }
+    Ok(())
+}
+
+/// 长格式的一条变更：`\tmodified:   path`，标签补齐到 12 列。
+/// 工作区中检出了别的提交的子模块后面注明 `(new commits)`
+fn write_change(out: &mut impl Write, change: &Change, worktree: bool) -> std::io::Result<()> {
+    let label = match change.status {
+        Status::Added => "new file:",
+        Status::Deleted => "deleted:",
+        Status::Modified => "modified:",
+        Status::TypeChanged => "typechange:",
+        Status::Renamed(_) => "renamed:",
+        Status::Copied(_) => "copied:",
+    };
+    match &change.from {
+        Some(from) => writeln!(
+            out,
+            "\t{label:<12}{} -> {}",
+            quote_path(from),
+            quote_path(&change.path)
+        ),
+        None => {
+            let new_commits = worktree
+                && matches!((change.old, change.new), (Some(old), Some(new))
+                    if old.mode == Mode::Gitlink && new.mode == Mode::Gitlink && old.hash != new.hash);
+            let suffix = if new_commits { " (new commits)" } else { "" };
+            writeln!(out, "\t{label:<12}{}{suffix}", quote_path(&change.path))
+        }
+    }
+}
+
+/// `On branch main`，分离头指针时根据 reflog 显示从哪里分离
+fn branch_line() -> anyhow::Result<String> {
+    if let Some(branch) = refs::head_branch()? {
+        let name = branch.strip_prefix("refs/heads/").unwrap_or(&branch);
+        return Ok(format!("On branch {name}"));
+    }
+    let Some((from, id)) = detached_from()? else {
+        return Ok("Not currently on any branch.".to_string());
+    };
+    let position = if refs::resolve_ref("HEAD")? == Some(id) {
+        "at"
+    } else {
+        "from"
+    };
+    Ok(format!("HEAD detached {position} {from}"))
+}
+
+/// 从 HEAD 的 reflog 找到最后一次 checkout，返回显示名称和当时切换到的提交。
+/// 目标是指向该提交的标签或远程分支时显示其名称，否则显示缩写哈希
+fn detached_from() -> anyhow::Result<Option<(String, [u8; 20])>> {
+    let Ok(log) = std::fs::read_to_string(".git/logs/HEAD") else {
+        return Ok(None);
+    };
+    let Some((target, id)) = log.lines().rev().find_map(|line| {
+        let (header, message) = line.split_once('\t')?;
+        let (_, to) = message
+            .strip_prefix("checkout: moving from ")?
+            .rsplit_once(" to ")?;
+        let id = parse_hex(header.split(' ').nth(1)?).ok()?;
+        Some((to.to_string(), id))
+    }) else {
+        return Ok(None);
+    };
+    for prefix in ["refs/tags/", "refs/remotes/"] {
+        if refs::resolve_ref(&format!("{prefix}{target}"))? == Some(id) {
+            return Ok(Some((target, id)));
+        }
+    }
+    Ok(Some((find_unique_abbrev(&hex::encode(id), 7), id)))
+}
+
+/// `## main`、`## HEAD (no branch)` 或 `## No commits yet on main`
+fn write_short_branch(out: &mut impl Write, initial: bool) -> anyhow::Result<()> {
+    match refs::head_branch()? {
+        Some(branch) => {
+            let name = branch.strip_prefix("refs/heads/").unwrap_or(&branch);
+            if initial {
+                writeln!(out, "## No commits yet on {name}")?;
+            } else {
+                writeln!(out, "## {name}")?;
+            }
+        }
+        None => writeln!(out, "## HEAD (no branch)")?,
+    }
+    Ok(())
+}
+
+/// `XY path`：X 是暂存区的状态，Y 是工作区的状态
+fn write_short(
+    out: &mut impl Write,
+    entries: &BTreeMap<String, Entry>,
+    untracked: &[String],
+) -> std::io::Result<()> {
+    let letter = |change: &Option<Change>| change.as_ref().map_or(' ', |c| c.status.letter());
+    for (path, entry) in entries {
+        if let Some(stages) = entry.unmerged {
+            writeln!(out, "{} {}", unmerged_status(stages).0, quote_short(path))?;
+            continue;
+        }
+        let change = entry
+            .staged
+            .as_ref()
+            .or(entry.unstaged.as_ref())
+            .expect("entry has a change");
+        write!(out, "{}{} ", letter(&entry.staged), letter(&entry.unstaged))?;
+        if let Some(from) = entry.staged.as_ref().and_then(|c| c.from.as_ref()) {
+            write!(out, "{} -> ", quote_short(from))?;
+        }
+        writeln!(out, "{}", quote_short(&change.path))?;
+    }
+    for path in untracked {
+        writeln!(out, "?? {}", quote_short(path))?;
+    }
+    Ok(())
+}
+
+/// 短格式中含空格的路径也加引号
+fn quote_short(path: &str) -> String {
+    let quoted = quote_path(path);
+    if quoted == path && path.contains(' ') {
+        format!("\"{path}\"")
+    } else {
+        quoted
+    }
+}
+
+/// 列出未跟踪的文件，跳过被忽略的路径，目录以 `/` 结尾
+pub(crate) fn untracked_files(index: &Index, mode: Untracked) -> anyhow::Result<Vec<String>> {
+    let tracked: HashSet<&str> = index.entries.iter().map(|e| e.path.as_str()).collect();
+    // 含有跟踪文件的目录
+    let mut tracked_dirs: BTreeSet<&str> = BTreeSet::new();
+    for entry in &index.entries {
+        let mut path = entry.path.as_str();
+        while let Some((dir, _)) = path.rsplit_once('/') {
+            if !tracked_dirs.insert(dir) {
+                break;
+            }
+            path = dir;
+        }
+    }
+    let mut walker = Walker {
+        tracked,
+        tracked_dirs,
+        ignore: Ignore::load(),
+        mode,
+        found: Vec::new(),
+    };
+    walker.walk_dir("")?;
+    let mut found = walker.found;
+    found.sort();
+    Ok(found)
+}
+
+struct Walker<'a> {
+    tracked: HashSet<&'a str>,
+    tracked_dirs: BTreeSet<&'a str>,
+    ignore: Ignore,
+    mode: Untracked,
+    found: Vec<String>,
+}
+
+impl Walker<'_> {
+    /// 遍历目录，prefix 是 `dir/` 形式，根目录为空
+    fn walk_dir(&mut self, prefix: &str) -> anyhow::Result<()> {
+        let dir = if prefix.is_empty() { "." } else { prefix };
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if prefix.is_empty() && name == ".git" {
+                continue;
+            }
+            let path = format!("{prefix}{name}");
+            let is_dir = entry.file_type()?.is_dir();
+            if self.ignore.is_ignored(&path, is_dir) {
+                continue;
+            }
+            if !is_dir {
+                if !self.tracked.contains(path.as_str()) {
+                    self.found.push(path);
+                }
+                continue;
+            }
+            // 嵌套的仓库作为一个整体显示
+            if std::fs::symlink_metadata(format!("{path}/.git")).is_ok() {
+                if !self.tracked.contains(path.as_str()) {
+                    self.found.push(format!("{path}/"));
+                }
+                continue;
+            }
+            if self.mode == Untracked::Normal && !self.tracked_dirs.contains(path.as_str()) {
+                // 完全未跟踪的目录，里面有未被忽略的文件时只显示目录名
+                let len = self.found.len();
+                self.enter(&path)?;
+                if self.found.len() > len {
+                    self.found.truncate(len);
+                    self.found.push(format!("{path}/"));
+                }
+                continue;
+            }
+            self.enter(&path)?;
+        }
+        Ok(())
+    }
+
+    fn enter(&mut self, path: &str) -> anyhow::Result<()> {
+        let len = self.ignore.push_dir(path);
+        self.walk_dir(&format!("{path}/"))?;
+        self.ignore.pop_dir(len);
+        Ok(())
+    }
+}
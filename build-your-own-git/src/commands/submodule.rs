@@ -0,0 +1,353 @@
+//! submodule：按 `.gitmodules` 初始化、检出子模块，查看子模块的状态。
+//!
+//! 只支持本地路径的 URL。与 git 把子模块的仓库放在 `.git/modules/<名称>` 不同，
+//! 这里克隆出的仓库直接放在子模块目录的 `.git` 中，子模块中的操作在切换到该目录后进行
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use crate::{
+    commands::{
+        clone::{self, CloneOptions, EnterDir, copy_objects, source_git_dir},
+        commit::committer_ident,
+        diff::matches_paths,
+    },
+    config::{self, Config},
+    diff::flatten_tree,
+    index::{INDEX_FILE, Index, IndexEntry},
+    objects::{Kind, Mode, commit::Commit, find_unique_abbrev, object_exists, read_object},
+    refs,
+    revision::{RevWalk, peel},
+    submodule::{self, Submodule},
+    worktree,
+};
+
+/// `git describe --contains` 中经过合并提交的非第一个父提交时增加的距离，与 git 的 name-rev 相同
+const MERGE_TRAVERSAL_WEIGHT: usize = 65535;
+
+/// 把 `.gitmodules` 中子模块的 URL 写入 `.git/config`，已经初始化的跳过
+pub(crate) async fn init(paths: &[String]) -> anyhow::Result<()> {
+    let index = Index::read().await?;
+    let config = Config::read()?;
+    for (entry, submodule) in submodules(&index, paths)? {
+        let name = &submodule.name;
+        if config.get(&format!("submodule.{name}.url")).is_some() {
+            continue;
+        }
+        let url = submodule.url.as_deref().with_context(|| {
+            format!(
+                "No url found for submodule path '{}' in .gitmodules",
+                entry.path
+            )
+        })?;
+        let url = if url.starts_with("./") || url.starts_with("../") {
+            let base = match config.get("remote.origin.url") {
+                Some(base) => base,
+                None => {
+                    eprintln!(
+                        "warning: could not look up configuration 'remote.origin.url'. \
+                         Assuming this repository is its own authoritative upstream."
+                    );
+                    std::env::current_dir()?.to_string_lossy().into_owned()
+                }
+            };
+            relative_url(&base, url)
+        } else {
+            url.to_string()
+        };
+        config::set(".git/config", &format!("submodule.{name}.active"), "true")?;
+        config::set(".git/config", &format!("submodule.{name}.url"), &url)?;
+        eprintln!(
+            "Submodule '{name}' ({url}) registered for path '{}'",
+            entry.path
+        );
+    }
+    Ok(())
+}
+
+/// 克隆还没有克隆的子模块，并把子模块检出到超项目记录的提交（分离头指针）。
+/// 没有初始化的子模块跳过，init_first 为 true 时先初始化
+pub(crate) async fn update(init_first: bool, paths: &[String]) -> anyhow::Result<()> {
+    if init_first {
+        init(paths).await?;
+    }
+    let index = Index::read().await?;
+    let config = Config::read()?;
+    for (entry, submodule) in submodules(&index, paths)? {
+        let name = &submodule.name;
+        let Some(url) = config.get(&format!("submodule.{name}.url")) else {
+            continue;
+        };
+        let path = entry.path.as_str();
+        let hex = hex::encode(entry.hash);
+        if submodule::git_dir(path).is_none() {
+            let dest = std::env::current_dir()?.join(path);
+            let options = CloneOptions {
+                local: true,
+                no_checkout: true,
+                ..Default::default()
+            };
+            clone::invoke(&url, Some(&dest.to_string_lossy()), &options)
+                .await
+                .with_context(|| format!("clone of '{url}' into submodule path '{path}' failed"))?;
+        } else if submodule::head(path)? == Some(entry.hash) {
+            continue;
+        }
+
+        let _cwd = EnterDir::new(path)?;
+        if !object_exists(&hex) {
+            let url = Config::read()?.get("remote.origin.url").unwrap_or(url);
+            fetch(&url).await?;
+            anyhow::ensure!(
+                object_exists(&hex),
+                "Fetched in submodule path '{path}', but it did not contain {hex}. \
+                 Direct fetching of that commit failed."
+            );
+        }
+        checkout(entry.hash)
+            .await
+            .with_context(|| format!("Unable to checkout '{hex}' in submodule path '{path}'"))?;
+        println!("Submodule path '{path}': checked out '{hex}'");
+    }
+    Ok(())
+}
+
+/// 每个子模块一行：未初始化的以 `-` 开头，检出了别的提交的以 `+` 开头，
+/// 有冲突的以 `U` 开头，后面是提交、路径和 `git describe` 得到的名称
+pub(crate) async fn status(paths: &[String]) -> anyhow::Result<()> {
+    let index = Index::read().await?;
+    let config = Config::read()?;
+    for (entry, submodule) in submodules(&index, paths)? {
+        let path = entry.path.as_str();
+        if entry.stage != 0 {
+            println!("U{} {path}", hex::encode([0; 20]));
+            continue;
+        }
+        let name = &submodule.name;
+        let active = match config.get_bool(&format!("submodule.{name}.active"))? {
+            Some(active) => active,
+            None => config.get(&format!("submodule.{name}.url")).is_some(),
+        };
+        let head = submodule::head(path)?;
+        let Some(head) = head.filter(|_| active) else {
+            println!("-{} {path}", hex::encode(entry.hash));
+            continue;
+        };
+        let (prefix, id) = if head == entry.hash {
+            (' ', entry.hash)
+        } else {
+            ('+', head)
+        };
+        let name = {
+            let _cwd = EnterDir::new(path)?;
+            describe(id).await?
+        };
+        println!("{prefix}{} {path} ({name})", hex::encode(id));
+    }
+    Ok(())
+}
+
+/// 暂存区中匹配 paths 的子模块及其在 `.gitmodules` 中的记录，冲突的子模块只取一次
+fn submodules<'a>(
+    index: &'a Index,
+    paths: &[String],
+) -> anyhow::Result<Vec<(&'a IndexEntry, Submodule)>> {
+    let modules = submodule::read_gitmodules()?;
+    let mut found: Vec<(&IndexEntry, Submodule)> = Vec::new();
+    for entry in &index.entries {
+        if entry.mode != Mode::Gitlink || !matches_paths(&entry.path, paths) {
+            continue;
+        }
+        if found
+            .last()
+            .is_some_and(|(last, _)| last.path == entry.path)
+        {
+            continue;
+        }
+        let submodule = modules
+            .iter()
+            .find(|module| module.path == entry.path)
+            .with_context(|| {
+                format!(
+                    "no submodule mapping found in .gitmodules for path '{}'",
+                    entry.path
+                )
+            })?;
+        found.push((entry, submodule.clone()));
+    }
+    Ok(found)
+}
+
+/// 相对 base 解析 `./` 或 `../` 开头的 URL，每个 `../` 去掉 base 的最后一级
+fn relative_url(base: &str, url: &str) -> String {
+    let mut base = base.trim_end_matches('/').to_string();
+    let mut url = url;
+    loop {
+        if let Some(rest) = url.strip_prefix("./") {
+            url = rest;
+        } else if let Some(rest) = url.strip_prefix("../") {
+            url = rest;
+            match base.rfind('/') {
+                Some(n) => base.truncate(n),
+                None => base.clear(),
+            }
+        } else {
+            break;
+        }
+    }
+    format!("{base}/{url}")
+}
+
+/// 在当前仓库中从 url 处的本地仓库取回对象，并更新远程分支和标签
+async fn fetch(url: &str) -> anyhow::Result<()> {
+    let source = source_git_dir(url)?;
+    copy_objects(&source.join("objects"), Path::new(".git/objects"), false)?;
+    for (name, id) in refs::list_refs_in(&source, "refs/")? {
+        let target = if let Some(branch) = name.strip_prefix("refs/heads/") {
+            format!("refs/remotes/origin/{branch}")
+        } else if name.starts_with("refs/tags/") {
+            name
+        } else {
+            continue;
+        };
+        if refs::resolve_ref(&target)? != Some(id) {
+            refs::write_ref(&target, &id)?;
+        }
+    }
+    Ok(())
+}
+
+/// 在当前仓库中检出提交 id 并分离头指针，有会被覆盖的本地修改时拒绝。
+/// 刚克隆的仓库还没有暂存区，从空树开始检出
+async fn checkout(id: [u8; 20]) -> anyhow::Result<()> {
+    let head = if Path::new(INDEX_FILE).exists() {
+        refs::resolve_ref("HEAD")?
+    } else {
+        None
+    };
+    let old = match head {
+        Some(head) => flatten_tree(&Commit::read(&head).await?.tree).await?,
+        None => BTreeMap::new(),
+    };
+    let new = flatten_tree(&Commit::read(&id).await?.tree).await?;
+    let index = Index::read().await?;
+    let mut index = worktree::switch(&index, &old, &new, "checkout", false).await?;
+    index.write().await?;
+
+    // 与 git 一样记下 reflog，子模块中的 status 据此显示 `HEAD detached at <提交>`
+    let from = match refs::head_branch()? {
+        Some(branch) => branch.trim_start_matches("refs/heads/").to_string(),
+        None => hex::encode(refs::resolve_ref("HEAD")?.unwrap_or_default()),
+    };
+    let previous = refs::resolve_ref("HEAD")?.unwrap_or_default();
+    refs::write_ref("HEAD", &id)?;
+    let message = format!("checkout: moving from {from} to {}", hex::encode(id));
+    refs::append_reflog("HEAD", &previous, &id, &committer_ident()?, &message)
+}
+
+/// 与 `git submodule status` 一样依次尝试 `git describe`、`describe --tags`、
+/// `describe --contains` 和 `describe --all --always`，在当前仓库中为提交 id 取名
+async fn describe(id: [u8; 20]) -> anyhow::Result<String> {
+    let mut tags = Vec::new();
+    let mut all = Vec::new();
+    for (name, target) in refs::list_refs_in(Path::new(".git"), "refs/")? {
+        let Ok(commit) = peel(target, Some(Kind::Commit)).await else {
+            continue;
+        };
+        if let Some(tag) = name.strip_prefix("refs/tags/") {
+            let annotated = read_object(&hex::encode(target)).await?.0 == Kind::Tag;
+            tags.push((tag.to_string(), commit, annotated));
+        }
+        let short = name.strip_prefix("refs/").unwrap_or(&name).to_string();
+        all.push((short, commit, false));
+    }
+    let annotated: Vec<_> = tags.iter().filter(|tag| tag.2).cloned().collect();
+    if let Some(name) = describe_with(id, &annotated).await? {
+        return Ok(name);
+    }
+    if let Some(name) = describe_with(id, &tags).await? {
+        return Ok(name);
+    }
+    if let Some(name) = name_rev(id, &tags).await? {
+        return Ok(name);
+    }
+    if let Some(name) = describe_with(id, &all).await? {
+        return Ok(name);
+    }
+    Ok(find_unique_abbrev(&hex::encode(id), 7))
+}
+
+/// 用 candidates 中的 (名称, 提交, 是否附注标签) 描述 id：正好是某个候选时取它的名称
+/// （附注标签优先），否则取遍历中遇到的第一个祖先，记为 `名称-距离-g缩写`
+async fn describe_with(
+    id: [u8; 20],
+    candidates: &[(String, [u8; 20], bool)],
+) -> anyhow::Result<Option<String>> {
+    if let Some((name, _, _)) = candidates
+        .iter()
+        .filter(|(_, commit, _)| *commit == id)
+        .max_by_key(|(_, _, annotated)| *annotated)
+    {
+        return Ok(Some(name.clone()));
+    }
+    let mut walk = RevWalk::new();
+    walk.push(id);
+    let Some((name, base)) = walk.walk().await?.into_iter().find_map(|commit| {
+        candidates
+            .iter()
+            .find(|(_, candidate, _)| *candidate == commit)
+            .map(|(name, base, _)| (name.clone(), *base))
+    }) else {
+        return Ok(None);
+    };
+    let mut between = RevWalk::new();
+    between.push(id);
+    between.hide(base);
+    let depth = between.walk().await?.len();
+    let abbrev = find_unique_abbrev(&hex::encode(id), 7);
+    Ok(Some(format!("{name}-{depth}-g{abbrev}")))
+}
+
+/// `git describe --contains`：从包含 id 的标签出发的路径，如 `v2~1` 或 `v2~2^2~1`。
+/// 与 git 的 name-rev 一样取距离最短的，经过合并提交的其他父提交时距离加上很大的权重
+async fn name_rev(
+    id: [u8; 20],
+    tags: &[(String, [u8; 20], bool)],
+) -> anyhow::Result<Option<String>> {
+    // 提交 -> (距离, 名称前缀, 距前缀的代数)
+    let mut best: HashMap<[u8; 20], (usize, String, usize)> = HashMap::new();
+    for (tag, commit, _) in tags {
+        let mut stack = vec![(*commit, 0, tag.clone(), 0)];
+        while let Some((commit, distance, prefix, generation)) = stack.pop() {
+            if best
+                .get(&commit)
+                .is_some_and(|(known, _, _)| *known <= distance)
+            {
+                continue;
+            }
+            best.insert(commit, (distance, prefix.clone(), generation));
+            let parents = Commit::read(&commit).await?.parents;
+            for (n, parent) in parents.iter().enumerate().rev() {
+                if n == 0 {
+                    stack.push((*parent, distance + 1, prefix.clone(), generation + 1));
+                } else {
+                    let name = match generation {
+                        0 => format!("{prefix}^{}", n + 1),
+                        _ => format!("{prefix}~{generation}^{}", n + 1),
+                    };
+                    stack.push((*parent, distance + MERGE_TRAVERSAL_WEIGHT, name, 0));
+                }
+            }
+        }
+    }
+    Ok(best
+        .remove(&id)
+        .map(|(_, prefix, generation)| match generation {
+            0 => prefix,
+            _ => format!("{prefix}~{generation}"),
+        }))
+}
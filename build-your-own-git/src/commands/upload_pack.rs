@@ -0,0 +1,1009 @@
+//! upload-pack：服务端，通过标准输入输出把仓库中的对象发送给 fetch/clone 的客户端，
+//! 通常由 ssh 或 `file://` 传输在服务端启动。
+//!
+//! 客户端用环境变量 GIT_PROTOCOL 请求协议版本：协议 v2 先通告能力，之后客户端每次发送一个
+//! ls-refs 或 fetch 命令；协议 v0 先列出所有引用，之后客户端发送 want、have 和 done 协商，
+//! 最后服务端发送 pack。`--stateless-rpc` 时只处理一个请求就退出，状态都在请求中，用于智能 HTTP。
+//!
+//! pack 在请求时现场生成：从 want 出发、到客户端已有的提交为止枚举对象，按浅克隆的深度
+//! 和部分克隆的过滤条件省略对象，不做 delta 压缩
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    io::{BufRead, Write},
+    path::PathBuf,
+};
+
+use anyhow::Context;
+
+use crate::{
+    commands::clone::{peel_in, source_git_dir, tag_target},
+    config::Config,
+    objects::{
+        Kind, Mode, commit::Commit, commit::parse_hex, object_exists_in, read_object_in,
+        tree::parse_tree,
+    },
+    pack,
+    pkt_line::{self, Packet},
+    refs,
+    revision::list_objects::Filter,
+};
+
+pub(crate) struct UploadPackOptions {
+    /// 只处理一个请求，不先发送通告
+    pub(crate) stateless_rpc: bool,
+    /// 只发送通告（协议 v0 的引用列表或协议 v2 的能力）就退出
+    pub(crate) advertise_refs: bool,
+}
+
+/// side-band-64k 中一帧最多携带的数据：去掉 4 字节长度和 1 字节通道号
+pub(crate) const SIDEBAND_MAX: usize = pkt_line::MAX_LEN - 5;
+/// 旧的 side-band 每帧最多 1000 字节
+const SIDEBAND_SMALL_MAX: usize = 1000 - 5;
+
+pub(crate) async fn invoke(directory: &str, options: &UploadPackOptions) -> anyhow::Result<()> {
+    let git_dir = server_git_dir(directory)?;
+    let config = Config::read_file(git_dir.join("config"))?;
+    let mut server = Server {
+        objects: git_dir.join("objects"),
+        allow_filter: config.get_bool("uploadpack.allowFilter")?.unwrap_or(false),
+        git_dir,
+        input: std::io::stdin().lock(),
+        output: Vec::new(),
+    };
+    match requested_version() {
+        2 => server.serve_v2(options).await,
+        version => {
+            // 协议 v1 只是在 v0 的通告之前加上版本号
+            if version == 1 && (options.advertise_refs || !options.stateless_rpc) {
+                pkt_line::write_line(&mut server.output, "version 1")?;
+            }
+            server.serve_v0(options).await
+        }
+    }
+}
+
+/// 服务端命令的参数 directory 对应的 git 目录：裸仓库本身或工作区中的 `.git`
+pub(crate) fn server_git_dir(directory: &str) -> anyhow::Result<PathBuf> {
+    source_git_dir(directory)
+        .with_context(|| format!("'{directory}' does not appear to be a git repository"))
+}
+
+/// 客户端通过 GIT_PROTOCOL（如 `version=2`，多项用 `:` 分隔）请求的协议版本，没有时为 0
+pub(crate) fn requested_version() -> u8 {
+    std::env::var("GIT_PROTOCOL")
+        .unwrap_or_default()
+        .split(':')
+        .filter_map(|item| item.strip_prefix("version="))
+        .filter_map(|version| version.parse().ok())
+        .max()
+        .unwrap_or(0)
+}
+
+/// 把 data 经由 side-band 的 band 号通道写入 out，按 max 分成多帧
+pub(crate) fn write_sideband(
+    out: &mut Vec<u8>,
+    band: u8,
+    data: &[u8],
+    max: usize,
+) -> anyhow::Result<()> {
+    for chunk in data.chunks(max) {
+        let mut packet = Vec::with_capacity(chunk.len() + 1);
+        packet.push(band);
+        packet.extend_from_slice(chunk);
+        pkt_line::write(out, &packet)?;
+    }
+    Ok(())
+}
+
+/// fetch 请求中的参数，协议 v0 和 v2 共用
+#[derive(Default)]
+struct Request {
+    wants: Vec<[u8; 20]>,
+    haves: Vec<[u8; 20]>,
+    /// 客户端浅克隆的边界
+    shallows: Vec<[u8; 20]>,
+    depth: Option<usize>,
+    /// deepen-relative：深度从客户端现在的浅克隆边界算起
+    deepen_relative: bool,
+    /// deepen-since：只要这个时间之后的提交
+    deepen_since: Option<i64>,
+    /// deepen-not：不要这些引用可以到达的提交
+    deepen_not: Vec<String>,
+    filter: Option<Filter>,
+    include_tag: bool,
+    done: bool,
+}
+
+impl Request {
+    /// 解析 want、shallow、deepen 等参数，返回是否认得
+    fn parse_arg(&mut self, line: &str, allow_filter: bool) -> anyhow::Result<bool> {
+        if let Some(id) = line.strip_prefix("want ") {
+            // 协议 v0 的第一个 want 后面跟着能力列表
+            let id = id.split(' ').next().unwrap_or_default();
+            self.wants.push(parse_hex(id)?);
+        } else if let Some(id) = line.strip_prefix("have ") {
+            self.haves.push(parse_hex(id)?);
+        } else if let Some(id) = line.strip_prefix("shallow ") {
+            self.shallows.push(parse_hex(id)?);
+        } else if let Some(depth) = line.strip_prefix("deepen ") {
+            let depth: usize = depth
+                .parse()
+                .with_context(|| format!("invalid deepen: {depth}"))?;
+            anyhow::ensure!(depth > 0, "invalid deepen: {depth}");
+            self.depth = Some(depth);
+        } else if let Some(time) = line.strip_prefix("deepen-since ") {
+            let time = time
+                .parse()
+                .with_context(|| format!("invalid deepen-since: {line}"))?;
+            self.deepen_since = Some(time);
+        } else if let Some(name) = line.strip_prefix("deepen-not ") {
+            self.deepen_not.push(name.to_string());
+        } else if line == "deepen-relative" {
+            self.deepen_relative = true;
+        } else if let Some(spec) = line.strip_prefix("filter ") {
+            anyhow::ensure!(allow_filter, "unexpected line: '{line}'");
+            self.filter = Some(Filter::parse(spec)?);
+        } else if line == "include-tag" {
+            self.include_tag = true;
+        } else if line == "done" {
+            self.done = true;
+        } else {
+            // 不做 delta 压缩，thin-pack 和 ofs-delta 都不影响发送的 pack
+            return Ok(matches!(
+                line,
+                "thin-pack" | "ofs-delta" | "no-progress" | "wait-for-done"
+            ));
+        }
+        Ok(true)
+    }
+
+    /// 是否要求加深或按时间、引用截断历史
+    fn deepens(&self) -> bool {
+        self.depth.is_some() || self.deepen_since.is_some() || !self.deepen_not.is_empty()
+    }
+}
+
+struct Server<R> {
+    git_dir: PathBuf,
+    objects: PathBuf,
+    /// uploadpack.allowFilter：是否接受部分克隆的 filter
+    allow_filter: bool,
+    input: R,
+    /// 还没有发出的回复
+    output: Vec<u8>,
+}
+
+impl<R: BufRead> Server<R> {
+    /// 发出积累的回复。有状态的连接中要在等待客户端之前发出
+    fn send(&mut self) -> anyhow::Result<()> {
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(&self.output)?;
+        stdout.flush()?;
+        self.output.clear();
+        Ok(())
+    }
+
+    /// 向客户端报告错误并退出
+    fn fail(&mut self, message: String) -> anyhow::Error {
+        let _ = pkt_line::write_line(&mut self.output, &format!("ERR {message}"));
+        let _ = self.send();
+        anyhow::anyhow!("{message}")
+    }
+
+    async fn serve_v2(&mut self, options: &UploadPackOptions) -> anyhow::Result<()> {
+        if options.advertise_refs || !options.stateless_rpc {
+            pkt_line::write_line(&mut self.output, "version 2")?;
+            pkt_line::write_line(
+                &mut self.output,
+                concat!("agent=own-git/", env!("CARGO_PKG_VERSION")),
+            )?;
+            pkt_line::write_line(&mut self.output, "ls-refs=unborn")?;
+            match self.allow_filter {
+                true => pkt_line::write_line(&mut self.output, "fetch=shallow filter")?,
+                false => pkt_line::write_line(&mut self.output, "fetch=shallow")?,
+            }
+            pkt_line::write_line(&mut self.output, "server-option")?;
+            pkt_line::write_line(&mut self.output, "object-format=sha1")?;
+            pkt_line::flush(&mut self.output)?;
+            self.send()?;
+        }
+        if options.advertise_refs {
+            return Ok(());
+        }
+        loop {
+            // 请求：`command=<命令>`、能力、delim、参数、flush
+            let command = match pkt_line::read(&mut self.input)? {
+                None => return Ok(()),
+                Some(Packet::Flush) => continue,
+                Some(packet) => packet
+                    .text()
+                    .and_then(|line| line.strip_prefix("command="))
+                    .map(str::to_string)
+                    .context("protocol error: expected command")?,
+            };
+            let mut args = Vec::new();
+            let mut in_args = false;
+            loop {
+                match pkt_line::expect(&mut self.input)? {
+                    Packet::Flush => break,
+                    Packet::Delim => in_args = true,
+                    packet => {
+                        let line = packet.text().context("protocol error: bad request")?;
+                        // 能力中只有 agent、object-format 和 server-option，都不影响回复
+                        if in_args {
+                            args.push(line.to_string());
+                        }
+                    }
+                }
+            }
+            match command.as_str() {
+                "ls-refs" => self.ls_refs(&args).await?,
+                "fetch" => self.fetch_v2(&args).await?,
+                command => return Err(self.fail(format!("unknown command '{command}'"))),
+            }
+            self.send()?;
+            if options.stateless_rpc {
+                return Ok(());
+            }
+        }
+    }
+
+    /// 协议 v2 的 ls-refs：每个引用一行 `<对象> <引用名>`，按参数附带 symref-target 和 peeled
+    async fn ls_refs(&mut self, args: &[String]) -> anyhow::Result<()> {
+        let mut prefixes = Vec::new();
+        let (mut symrefs, mut peel, mut unborn) = (false, false, false);
+        for arg in args {
+            match arg.as_str() {
+                "symrefs" => symrefs = true,
+                "peel" => peel = true,
+                "unborn" => unborn = true,
+                arg => match arg.strip_prefix("ref-prefix ") {
+                    Some(prefix) => prefixes.push(prefix.to_string()),
+                    None => return Err(self.fail(format!("unexpected line: '{arg}'"))),
+                },
+            }
+        }
+        let wanted =
+            |name: &str| prefixes.is_empty() || prefixes.iter().any(|p| name.starts_with(p));
+
+        let mut lines = Vec::new();
+        if wanted("HEAD") {
+            let head = refs::read_ref_in(&self.git_dir, "HEAD")?.unwrap_or_default();
+            let target = head.strip_prefix("ref: ");
+            match refs::resolve_ref_in(&self.git_dir, "HEAD")? {
+                Some(id) => {
+                    let mut line = format!("{} HEAD", hex::encode(id));
+                    if let (true, Some(target)) = (symrefs, target) {
+                        line.push_str(&format!(" symref-target:{target}"));
+                    }
+                    lines.push(line);
+                }
+                // 空仓库：告诉客户端 HEAD 指向的分支
+                None => {
+                    if let (true, true, Some(target)) = (unborn, symrefs, target) {
+                        lines.push(format!("unborn HEAD symref-target:{target}"));
+                    }
+                }
+            }
+        }
+        for (name, id) in refs::list_refs_in(&self.git_dir, "refs/")? {
+            if !wanted(&name) {
+                continue;
+            }
+            let mut line = format!("{} {name}", hex::encode(id));
+            if symrefs {
+                let content = refs::read_ref_in(&self.git_dir, &name)?.unwrap_or_default();
+                if let Some(target) = content.strip_prefix("ref: ") {
+                    line.push_str(&format!(" symref-target:{target}"));
+                }
+            }
+            if peel {
+                let peeled = peel_in(&self.objects, id).await?;
+                if peeled != id {
+                    line.push_str(&format!(" peeled:{}", hex::encode(peeled)));
+                }
+            }
+            lines.push(line);
+        }
+        for line in lines {
+            pkt_line::write_line(&mut self.output, &line)?;
+        }
+        pkt_line::flush(&mut self.output)
+    }
+
+    /// 协议 v2 的 fetch：没有 done 时回复这一轮的确认，服务端还没准备好就到此为止；
+    /// 否则依次发送 shallow-info 和 packfile 两节
+    async fn fetch_v2(&mut self, args: &[String]) -> anyhow::Result<()> {
+        let mut request = Request::default();
+        for arg in args {
+            if !request.parse_arg(arg, self.allow_filter)? {
+                return Err(self.fail(format!("unexpected line: '{arg}'")));
+            }
+        }
+        self.check_wants(&request.wants, None)?;
+        let common = self.common(&request.haves);
+
+        if !request.done {
+            pkt_line::write_line(&mut self.output, "acknowledgments")?;
+            for id in &common {
+                pkt_line::write_line(&mut self.output, &format!("ACK {}", hex::encode(id)))?;
+            }
+            if common.is_empty() {
+                pkt_line::write_line(&mut self.output, "NAK")?;
+            }
+            let shallows: HashSet<[u8; 20]> = request.shallows.iter().copied().collect();
+            if !self
+                .ok_to_give_up(&request.wants, &common, &shallows)
+                .await?
+            {
+                return pkt_line::flush(&mut self.output);
+            }
+            pkt_line::write_line(&mut self.output, "ready")?;
+            pkt_line::delim(&mut self.output)?;
+        }
+
+        let shallow = self.deepen(&request).await?;
+        if request.deepens() || !request.shallows.is_empty() {
+            pkt_line::write_line(&mut self.output, "shallow-info")?;
+            shallow.write(&mut self.output)?;
+            pkt_line::delim(&mut self.output)?;
+        }
+        let pack = self.pack_objects(&request, &common, &shallow).await?;
+        pkt_line::write_line(&mut self.output, "packfile")?;
+        write_sideband(&mut self.output, 1, &pack, SIDEBAND_MAX)?;
+        pkt_line::flush(&mut self.output)
+    }
+
+    /// 协议 v0：通告引用，读取 want 等参数，用 multi_ack_detailed 协商，最后发送 pack
+    async fn serve_v0(&mut self, options: &UploadPackOptions) -> anyhow::Result<()> {
+        let tips = self.advertised_refs().await?;
+        if options.advertise_refs || !options.stateless_rpc {
+            self.advertise_v0(&tips).await?;
+            self.send()?;
+        }
+        if options.advertise_refs {
+            return Ok(());
+        }
+
+        // want 以及浅克隆的参数，直到 flush；客户端什么都不要时直接 flush 或断开
+        let mut request = Request::default();
+        let mut capabilities = Vec::new();
+        loop {
+            let line = match pkt_line::read(&mut self.input)? {
+                None | Some(Packet::Flush) => break,
+                Some(packet) => packet
+                    .text()
+                    .context("protocol error: bad request")?
+                    .to_string(),
+            };
+            if request.wants.is_empty() && line.starts_with("want ") {
+                capabilities = line.split(' ').skip(2).map(str::to_string).collect();
+            }
+            let allow_filter = self.allow_filter;
+            if !request.parse_arg(&line, allow_filter)? || line == "done" {
+                return Err(self.fail(format!("protocol error: unexpected '{line}'")));
+            }
+        }
+        if request.wants.is_empty() {
+            return Ok(());
+        }
+        let tips: HashSet<[u8; 20]> = tips.iter().map(|(_, id)| *id).collect();
+        self.check_wants(&request.wants, Some(&tips))?;
+        let supports = |name: &str| capabilities.iter().any(|c| c == name);
+        // 协议 v0 中 include-tag 是能力而不是参数
+        request.include_tag = supports("include-tag");
+        request.deepen_relative = supports("deepen-relative");
+        let detailed = supports("multi_ack_detailed");
+        let multi_ack = detailed || supports("multi_ack");
+        let no_done = supports("no-done");
+        let sideband = match (supports("side-band-64k"), supports("side-band")) {
+            (true, _) => Some(SIDEBAND_MAX),
+            (false, true) => Some(SIDEBAND_SMALL_MAX),
+            _ => None,
+        };
+
+        let shallow = self.deepen(&request).await?;
+        if request.deepens() {
+            shallow.write(&mut self.output)?;
+            pkt_line::flush(&mut self.output)?;
+            self.send()?;
+        }
+
+        // 与 git 的 get_common_commits 相同：每个 have 回复一次，每批 have 之后的 flush 处
+        // 没有共同提交时回复 NAK，无状态时每个请求只处理一批
+        let client_shallows: HashSet<[u8; 20]> = request.shallows.iter().copied().collect();
+        let mut common: Vec<[u8; 20]> = Vec::new();
+        let mut last = String::new();
+        let (mut got_common, mut got_other, mut sent_ready) = (false, false, false);
+        loop {
+            let line = match pkt_line::read(&mut self.input)? {
+                // 无状态的请求没有 done 就结束了，或者客户端断开
+                None => {
+                    self.send()?;
+                    return Ok(());
+                }
+                Some(Packet::Flush) => {
+                    if detailed
+                        && got_common
+                        && !got_other
+                        && self
+                            .ok_to_give_up(&request.wants, &common, &client_shallows)
+                            .await?
+                    {
+                        sent_ready = true;
+                        pkt_line::write_line(&mut self.output, &format!("ACK {last} ready"))?;
+                    }
+                    if common.is_empty() || multi_ack {
+                        pkt_line::write_line(&mut self.output, "NAK")?;
+                    }
+                    if no_done && sent_ready {
+                        pkt_line::write_line(&mut self.output, &format!("ACK {last}"))?;
+                        break;
+                    }
+                    self.send()?;
+                    if options.stateless_rpc {
+                        return Ok(());
+                    }
+                    got_common = false;
+                    got_other = false;
+                    continue;
+                }
+                Some(packet) => packet
+                    .text()
+                    .context("protocol error: bad request")?
+                    .to_string(),
+            };
+            if let Some(id) = line.strip_prefix("have ") {
+                let id = parse_hex(id)?;
+                if !self.has_object(&id) {
+                    got_other = true;
+                    if multi_ack
+                        && self
+                            .ok_to_give_up(&request.wants, &common, &client_shallows)
+                            .await?
+                    {
+                        let hex = hex::encode(id);
+                        match detailed {
+                            true => {
+                                sent_ready = true;
+                                pkt_line::write_line(&mut self.output, &format!("ACK {hex} ready"))?
+                            }
+                            false => pkt_line::write_line(
+                                &mut self.output,
+                                &format!("ACK {hex} continue"),
+                            )?,
+                        }
+                    }
+                    continue;
+                }
+                got_common = true;
+                if !common.contains(&id) {
+                    common.push(id);
+                }
+                last = hex::encode(id);
+                if detailed {
+                    pkt_line::write_line(&mut self.output, &format!("ACK {last} common"))?;
+                } else if multi_ack {
+                    pkt_line::write_line(&mut self.output, &format!("ACK {last} continue"))?;
+                } else if common.len() == 1 {
+                    pkt_line::write_line(&mut self.output, &format!("ACK {last}"))?;
+                }
+            } else if line == "done" {
+                match common.is_empty() {
+                    true => pkt_line::write_line(&mut self.output, "NAK")?,
+                    false if multi_ack => {
+                        pkt_line::write_line(&mut self.output, &format!("ACK {last}"))?
+                    }
+                    false => {}
+                }
+                break;
+            } else {
+                return Err(self.fail(format!(
+                    "protocol error: expected have or done, got '{line}'"
+                )));
+            }
+        }
+
+        let pack = self.pack_objects(&request, &common, &shallow).await?;
+        match sideband {
+            Some(max) => {
+                write_sideband(&mut self.output, 1, &pack, max)?;
+                pkt_line::flush(&mut self.output)?;
+            }
+            None => self.output.extend_from_slice(&pack),
+        }
+        self.send()
+    }
+
+    /// 协议 v0 通告的引用：HEAD 在最前，之后按名称排序
+    async fn advertised_refs(&self) -> anyhow::Result<Vec<(String, [u8; 20])>> {
+        let mut list = Vec::new();
+        if let Some(id) = refs::resolve_ref_in(&self.git_dir, "HEAD")? {
+            list.push(("HEAD".to_string(), id));
+        }
+        list.extend(refs::list_refs_in(&self.git_dir, "refs/")?);
+        Ok(list)
+    }
+
+    /// 每个引用一行 `<对象> <引用名>`，附注标签之后跟着 `<引用名>^{}` 一行给出它指向的对象。
+    /// 第一行在 NUL 之后带着能力列表，空仓库用 `capabilities^{}` 这个假引用带出
+    async fn advertise_v0(&mut self, tips: &[(String, [u8; 20])]) -> anyhow::Result<()> {
+        let mut capabilities = String::from(
+            "multi_ack_detailed no-done side-band side-band-64k thin-pack ofs-delta shallow \
+             deepen-since deepen-not deepen-relative no-progress include-tag",
+        );
+        let head = refs::read_ref_in(&self.git_dir, "HEAD")?.unwrap_or_default();
+        if let (Some(target), true) = (head.strip_prefix("ref: "), !tips.is_empty()) {
+            capabilities.push_str(&format!(" symref=HEAD:{target}"));
+        }
+        if self.allow_filter {
+            capabilities.push_str(" filter");
+        }
+        capabilities.push_str(concat!(
+            " object-format=sha1 agent=own-git/",
+            env!("CARGO_PKG_VERSION")
+        ));
+        if tips.is_empty() {
+            let line = format!("{} capabilities^{{}}\0{capabilities}", hex::encode([0; 20]));
+            pkt_line::write_line(&mut self.output, &line)?;
+        }
+        for (i, (name, id)) in tips.iter().enumerate() {
+            let line = match i {
+                0 => format!("{} {name}\0{capabilities}", hex::encode(id)),
+                _ => format!("{} {name}", hex::encode(id)),
+            };
+            pkt_line::write_line(&mut self.output, &line)?;
+            let peeled = peel_in(&self.objects, *id).await?;
+            if name != "HEAD" && peeled != *id {
+                let line = format!("{} {name}^{{}}", hex::encode(peeled));
+                pkt_line::write_line(&mut self.output, &line)?;
+            }
+        }
+        pkt_line::flush(&mut self.output)
+    }
+
+    /// want 的对象都要存在；协议 v0 中还必须是通告过的引用 tips
+    fn check_wants(
+        &mut self,
+        wants: &[[u8; 20]],
+        tips: Option<&HashSet<[u8; 20]>>,
+    ) -> anyhow::Result<()> {
+        for id in wants {
+            let ours = match tips {
+                Some(tips) => tips.contains(id),
+                None => self.has_object(id),
+            };
+            if !ours {
+                return Err(self.fail(format!("upload-pack: not our ref {}", hex::encode(id))));
+            }
+        }
+        Ok(())
+    }
+
+    fn has_object(&self, id: &[u8; 20]) -> bool {
+        object_exists_in(&self.objects, id)
+    }
+
+    /// haves 中服务端也有的，去掉重复
+    fn common(&self, haves: &[[u8; 20]]) -> Vec<[u8; 20]> {
+        let mut common = Vec::new();
+        for id in haves {
+            if self.has_object(id) && !common.contains(id) {
+                common.push(*id);
+            }
+        }
+        common
+    }
+
+    /// 提交的父提交，浅克隆边界 grafts 上的视为没有父提交
+    async fn parents(
+        &self,
+        id: &[u8; 20],
+        grafts: &HashSet<[u8; 20]>,
+    ) -> anyhow::Result<Vec<[u8; 20]>> {
+        if grafts.contains(id) {
+            return Ok(Vec::new());
+        }
+        let (_, data) = read_object_in(&self.objects, &hex::encode(id)).await?;
+        Ok(Commit::parse(&data)?.parents)
+    }
+
+    /// 对象剥去标签后是提交时返回这个提交
+    async fn peel_commit(&self, id: [u8; 20]) -> anyhow::Result<Option<[u8; 20]>> {
+        let peeled = peel_in(&self.objects, id).await?;
+        let (kind, _) = read_object_in(&self.objects, &hex::encode(peeled)).await?;
+        Ok((kind == Kind::Commit).then_some(peeled))
+    }
+
+    /// 是否每个 want 都能到达某个共同提交，这时再协商下去也不会让 pack 更小
+    async fn ok_to_give_up(
+        &self,
+        wants: &[[u8; 20]],
+        common: &[[u8; 20]],
+        grafts: &HashSet<[u8; 20]>,
+    ) -> anyhow::Result<bool> {
+        if common.is_empty() {
+            return Ok(false);
+        }
+        let common: HashSet<[u8; 20]> = common.iter().copied().collect();
+        for want in wants {
+            // 不是提交的对象无法从历史判断，不影响结果
+            let Some(want) = self.peel_commit(*want).await? else {
+                continue;
+            };
+            let mut stack = vec![want];
+            let mut seen = HashSet::new();
+            let mut found = false;
+            while let Some(id) = stack.pop() {
+                if common.contains(&id) {
+                    found = true;
+                    break;
+                }
+                if seen.insert(id) {
+                    stack.extend(self.parents(&id, grafts).await?);
+                }
+            }
+            if !found {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// 按 deepen 计算新的浅克隆边界：从 want 出发第 depth 个提交是新的边界，
+    /// 客户端原来的边界在这之内的不再是边界
+    async fn deepen(&self, request: &Request) -> anyhow::Result<Shallow> {
+        let mut shallow = Shallow::default();
+        let Some(depth) = request.depth else {
+            if request.deepens() {
+                return self.deepen_by_history(request).await;
+            }
+            return Ok(shallow);
+        };
+        let server_shallow = self.server_shallow();
+        // deepen-relative 时从客户端现在的边界再往下 depth 个提交
+        let (starts, depth) = match request.deepen_relative {
+            true => (request.shallows.clone(), depth + 1),
+            false => (request.wants.clone(), depth),
+        };
+        // 广度优先，得到每个提交离起点最近的距离
+        let mut depths: HashMap<[u8; 20], usize> = HashMap::new();
+        let mut queue = VecDeque::new();
+        for start in starts {
+            if let Some(commit) = self.peel_commit(start).await? {
+                if depths.insert(commit, 1).is_none() {
+                    queue.push_back(commit);
+                }
+            }
+        }
+        let mut boundary = Vec::new();
+        while let Some(id) = queue.pop_front() {
+            let d = depths[&id];
+            if d >= depth {
+                boundary.push(id);
+                continue;
+            }
+            for parent in self.parents(&id, &server_shallow).await? {
+                if let std::collections::hash_map::Entry::Vacant(entry) = depths.entry(parent) {
+                    entry.insert(d + 1);
+                    queue.push_back(parent);
+                }
+            }
+        }
+        shallow.shallow = boundary
+            .into_iter()
+            .filter(|id| !request.shallows.contains(id))
+            .collect();
+        shallow.unshallow = request
+            .shallows
+            .iter()
+            .filter(|id| depths.get(*id).is_some_and(|d| *d < depth))
+            .copied()
+            .collect();
+        Ok(shallow)
+    }
+
+    /// deepen-since 和 deepen-not：只要 want 可以到达的、不早于给定时间且不能从给定引用到达的提交，
+    /// 其中有父提交不要的是新的边界
+    async fn deepen_by_history(&self, request: &Request) -> anyhow::Result<Shallow> {
+        let server_shallow = self.server_shallow();
+        let mut excluded = HashSet::new();
+        let mut stack = Vec::new();
+        for name in &request.deepen_not {
+            let id = self
+                .expand_ref(name)?
+                .with_context(|| format!("git upload-pack: ambiguous deepen-not: {name}"))?;
+            stack.extend(self.peel_commit(id).await?);
+        }
+        while let Some(id) = stack.pop() {
+            if excluded.insert(id) {
+                stack.extend(self.parents(&id, &server_shallow).await?);
+            }
+        }
+
+        let mut included = HashSet::new();
+        let mut order = Vec::new();
+        for want in &request.wants {
+            stack.extend(self.peel_commit(*want).await?);
+        }
+        while let Some(id) = stack.pop() {
+            if excluded.contains(&id) || included.contains(&id) {
+                continue;
+            }
+            let (_, data) = read_object_in(&self.objects, &hex::encode(id)).await?;
+            let commit = Commit::parse(&data)?;
+            if request
+                .deepen_since
+                .is_some_and(|since| commit.committer.time < since)
+            {
+                continue;
+            }
+            included.insert(id);
+            order.push((id, commit.parents.clone()));
+            if !server_shallow.contains(&id) {
+                stack.extend(commit.parents);
+            }
+        }
+        anyhow::ensure!(
+            !included.is_empty(),
+            "no commits selected for shallow requests"
+        );
+
+        let mut shallow = Shallow::default();
+        let mut boundary = HashSet::new();
+        for (id, parents) in order {
+            if parents.iter().any(|parent| !included.contains(parent)) {
+                boundary.insert(id);
+                if !request.shallows.contains(&id) {
+                    shallow.shallow.push(id);
+                }
+            }
+        }
+        shallow.unshallow = request
+            .shallows
+            .iter()
+            .filter(|id| included.contains(*id) && !boundary.contains(*id))
+            .copied()
+            .collect();
+        Ok(shallow)
+    }
+
+    /// 按 git 的规则把简写的引用名展开成对象
+    fn expand_ref(&self, name: &str) -> anyhow::Result<Option<[u8; 20]>> {
+        for candidate in [
+            name.to_string(),
+            format!("refs/{name}"),
+            format!("refs/tags/{name}"),
+            format!("refs/heads/{name}"),
+            format!("refs/remotes/{name}"),
+            format!("refs/remotes/{name}/HEAD"),
+        ] {
+            if let Some(id) = refs::resolve_ref_in(&self.git_dir, &candidate)? {
+                return Ok(Some(id));
+            }
+        }
+        Ok(None)
+    }
+
+    /// 服务端自己是浅克隆时的边界
+    fn server_shallow(&self) -> HashSet<[u8; 20]> {
+        std::fs::read_to_string(self.git_dir.join("shallow"))
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| parse_hex(line).ok())
+            .collect()
+    }
+
+    /// 客户端需要的对象写成的 pack：want 可以到达、共同提交不能到达的提交，以及它们的树和 blob
+    /// 中客户端还没有的，按浅克隆边界截断历史，按 filter 省略树和 blob
+    async fn pack_objects(
+        &self,
+        request: &Request,
+        common: &[[u8; 20]],
+        shallow: &Shallow,
+    ) -> anyhow::Result<Vec<u8>> {
+        // 客户端原来的和新的浅克隆边界都视为没有父提交
+        let mut grafts = self.server_shallow();
+        grafts.extend(request.shallows.iter().copied());
+        grafts.extend(shallow.shallow.iter().copied());
+
+        // 客户端已有的提交：共同提交的所有祖先
+        let mut have = HashSet::new();
+        let mut stack = Vec::new();
+        for id in common {
+            if let Some(commit) = self.peel_commit(*id).await? {
+                stack.push(commit);
+            }
+        }
+        while let Some(id) = stack.pop() {
+            if have.insert(id) {
+                stack.extend(self.parents(&id, &grafts).await?);
+            }
+        }
+
+        let mut ids = Vec::new();
+        let mut included = HashSet::new();
+        let mut commits = Vec::new();
+        // 不再是边界的提交，它们的父提交也要发送
+        let mut starts: Vec<[u8; 20]> = Vec::new();
+        for id in &shallow.unshallow {
+            starts.extend(self.parents(id, &HashSet::new()).await?);
+        }
+        let mut trees = Vec::new();
+        for want in &request.wants {
+            let mut id = *want;
+            loop {
+                let (kind, data) = read_object_in(&self.objects, &hex::encode(id)).await?;
+                match kind {
+                    Kind::Tag => {
+                        if included.insert(id) {
+                            ids.push(id);
+                        }
+                        id = tag_target(&data)?;
+                    }
+                    Kind::Commit => {
+                        starts.push(id);
+                        break;
+                    }
+                    // 明确请求的树和 blob 不受 filter 影响，如部分克隆补取缺少的 blob
+                    Kind::Tree => {
+                        trees.push((id, 0, true));
+                        break;
+                    }
+                    Kind::Blob => {
+                        if included.insert(id) {
+                            ids.push(id);
+                        }
+                        break;
+                    }
+                }
+            }
+        }
+
+        let mut edges: Vec<[u8; 20]> = have.iter().copied().collect();
+        edges.extend(shallow.unshallow.iter().copied());
+        let mut stack = starts;
+        let mut seen = HashSet::new();
+        while let Some(id) = stack.pop() {
+            if have.contains(&id) || !seen.insert(id) {
+                continue;
+            }
+            let (_, data) = read_object_in(&self.objects, &hex::encode(id)).await?;
+            let commit = Commit::parse(&data)?;
+            commits.push((id, commit.tree));
+            if !grafts.contains(&id) {
+                stack.extend(commit.parents);
+            }
+        }
+        for (id, tree) in &commits {
+            ids.push(*id);
+            included.insert(*id);
+            trees.push((*tree, 0, false));
+        }
+
+        // 客户端已有的提交的树中的对象都不用发送
+        let mut known: HashMap<[u8; 20], usize> = HashMap::new();
+        let mut stack = Vec::new();
+        for id in edges {
+            if let Ok((Kind::Commit, data)) = read_object_in(&self.objects, &hex::encode(id)).await
+            {
+                stack.push(Commit::parse(&data)?.tree);
+            }
+        }
+        while let Some(id) = stack.pop() {
+            if known.insert(id, 0).is_some() {
+                continue;
+            }
+            let Ok((Kind::Tree, data)) = read_object_in(&self.objects, &hex::encode(id)).await
+            else {
+                continue;
+            };
+            for entry in parse_tree(&data)? {
+                if entry.mode != Mode::Gitlink {
+                    stack.push(entry.hash);
+                }
+            }
+        }
+        for id in included.iter() {
+            known.insert(*id, 0);
+        }
+
+        // 树和 blob，tree:<深度> 过滤时同一个对象以更浅的深度再次遇到时要重新看
+        let mut stack = trees;
+        stack.reverse();
+        while let Some((id, depth, explicit)) = stack.pop() {
+            if known.get(&id).is_some_and(|seen| *seen <= depth) && !explicit {
+                continue;
+            }
+            if !explicit
+                && matches!(request.filter, Some(Filter::TreeDepth(limit)) if depth >= limit)
+            {
+                continue;
+            }
+            known.insert(id, depth);
+            if included.insert(id) {
+                ids.push(id);
+            }
+            let (_, data) = read_object_in(&self.objects, &hex::encode(id)).await?;
+            for entry in parse_tree(&data)?.into_iter().rev() {
+                match entry.mode {
+                    Mode::Gitlink => {}
+                    Mode::Directory => stack.push((entry.hash, depth + 1, false)),
+                    _ => {
+                        if known.contains_key(&entry.hash) || included.contains(&entry.hash) {
+                            continue;
+                        }
+                        if !self
+                            .blob_wanted(&entry.hash, depth + 1, request.filter)
+                            .await?
+                        {
+                            continue;
+                        }
+                        known.insert(entry.hash, depth + 1);
+                        included.insert(entry.hash);
+                        ids.push(entry.hash);
+                    }
+                }
+            }
+        }
+
+        if request.include_tag {
+            for (_, id) in refs::list_refs_in(&self.git_dir, "refs/tags/")? {
+                let mut chain = Vec::new();
+                let mut target = id;
+                while let Ok((Kind::Tag, data)) =
+                    read_object_in(&self.objects, &hex::encode(target)).await
+                {
+                    chain.push(target);
+                    target = tag_target(&data)?;
+                }
+                if !chain.is_empty() && included.contains(&target) {
+                    for tag in chain {
+                        if included.insert(tag) {
+                            ids.push(tag);
+                        }
+                    }
+                }
+            }
+        }
+        pack::write(&self.objects, &ids).await
+    }
+
+    /// 按 filter 决定是否发送深度为 depth 的 blob
+    async fn blob_wanted(
+        &self,
+        id: &[u8; 20],
+        depth: usize,
+        filter: Option<Filter>,
+    ) -> anyhow::Result<bool> {
+        Ok(match filter {
+            None => true,
+            Some(Filter::BlobNone) => false,
+            Some(Filter::TreeDepth(limit)) => depth < limit,
+            Some(Filter::BlobLimit(limit)) => {
+                let (_, data) = read_object_in(&self.objects, &hex::encode(id)).await?;
+                (data.len() as u64) < limit
+            }
+        })
+    }
+}
+
+/// deepen 的结果：客户端新的浅克隆边界，和不再是边界的提交
+#[derive(Default)]
+struct Shallow {
+    shallow: Vec<[u8; 20]>,
+    unshallow: Vec<[u8; 20]>,
+}
+
+impl Shallow {
+    fn write(&self, out: &mut Vec<u8>) -> anyhow::Result<()> {
+        for id in &self.shallow {
+            pkt_line::write_line(out, &format!("shallow {}", hex::encode(id)))?;
+        }
+        for id in &self.unshallow {
+            pkt_line::write_line(out, &format!("unshallow {}", hex::encode(id)))?;
+        }
+        Ok(())
+    }
+}
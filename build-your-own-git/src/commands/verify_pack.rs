@@ -0,0 +1,110 @@
+//! verify-pack：检查 pack 和它的 `.idx` 是否一致。
+//!
+//! 像 index-pack 一样重新给 pack 建索引，与已有的 `.idx` 逐字节比较。`-v` 按在 pack 中的顺序
+//! 列出每个对象的哈希、类型、大小、在 pack 中占的字节数和偏移，delta 还有链的长度和基对象，
+//! 之后是 delta 链长度的分布
+
+use std::path::Path;
+
+use anyhow::Context;
+
+use crate::{commands::index_pack::derive, pack::index};
+
+pub(crate) struct VerifyPackOptions {
+    /// 列出所有对象和统计
+    pub(crate) verbose: bool,
+    /// 只输出统计
+    pub(crate) stat_only: bool,
+}
+
+/// 检查每个 pack，有不一致的返回 false
+pub(crate) async fn invoke(packs: &[String], options: &VerifyPackOptions) -> anyhow::Result<bool> {
+    let mut all_ok = true;
+    for name in packs {
+        // 与 git 一样接受 `x.idx`、`x.pack` 或 `x`
+        let name = match name.strip_suffix(".idx") {
+            Some(base) => format!("{base}.pack"),
+            None if name.ends_with(".pack") => name.clone(),
+            None => format!("{name}.pack"),
+        };
+        let result = verify(Path::new(&name), options).await;
+        if let Err(e) = &result {
+            eprintln!("fatal: {e:#}");
+        }
+        if options.verbose || options.stat_only {
+            match &result {
+                Ok(()) if options.stat_only => {}
+                Ok(()) => println!("{name}: ok"),
+                Err(_) => println!("{name}: bad"),
+            }
+        }
+        all_ok &= result.is_ok();
+    }
+    Ok(all_ok)
+}
+
+async fn verify(path: &Path, options: &VerifyPackOptions) -> anyhow::Result<()> {
+    let idx_path = derive(path, "pack", "idx")?;
+    let existing = std::fs::read(&idx_path).with_context(|| {
+        format!(
+            "Cannot open existing pack idx file for '{}'",
+            idx_path.display()
+        )
+    })?;
+    let mut pack = std::fs::read(path)
+        .with_context(|| format!("Cannot open existing pack file '{}'", path.display()))?;
+    let index = index::index(&mut pack, None).await?;
+    if options.verbose || options.stat_only {
+        show(&index, options.stat_only);
+    }
+    anyhow::ensure!(
+        index::write_idx(&index) == existing,
+        "sha1 file '{}' validation error",
+        idx_path.display()
+    );
+    Ok(())
+}
+
+/// 列出对象以及 delta 链长度的分布
+fn show(index: &index::Index, stat_only: bool) {
+    let entries = &index.entries;
+    let mut histogram = Vec::new();
+    for entry in entries {
+        if let Some((depth, base)) = entry.delta {
+            if histogram.len() < depth {
+                histogram.resize(depth, 0);
+            }
+            histogram[depth - 1] += 1;
+            if !stat_only {
+                println!(
+                    "{} {:<6} {} {} {} {depth} {}",
+                    hex::encode(entry.id),
+                    entry.kind.to_string(),
+                    entry.size,
+                    entry.packed_size,
+                    entry.offset,
+                    hex::encode(entries[base].id)
+                );
+            }
+        } else if !stat_only {
+            println!(
+                "{} {:<6} {} {} {}",
+                hex::encode(entry.id),
+                entry.kind.to_string(),
+                entry.size,
+                entry.packed_size,
+                entry.offset
+            );
+        }
+    }
+    let plural = |n: usize| if n == 1 { "object" } else { "objects" };
+    let base_objects = entries.iter().filter(|entry| entry.delta.is_none()).count();
+    if base_objects > 0 {
+        println!("non delta: {base_objects} {}", plural(base_objects));
+    }
+    for (i, &n) in histogram.iter().enumerate() {
+        if n > 0 {
+            println!("chain length = {}: {n} {}", i + 1, plural(n));
+        }
+    }
+}
@@ -20,7 +20,10 @@ use sha1::{Digest, Sha1};
 use tempfile::NamedTempFile;
 use tokio::fs;
 
-use crate::objects::{self, Kind, Mode, Object};
+use crate::{
+    objects::{self, Kind, Mode, Object},
+    submodule,
+};
 type TreeFuture =
     Pin<Box<dyn Future<Output = Result<Option<Object<Cursor<Vec<u8>>>>, anyhow::Error>> + Send>>;
 
@@ -32,7 +35,11 @@ pub(crate) fn write_tree(path: PathBuf) -> TreeFuture {
         while let Some(entry) = dir.next_entry().await.context("read directory failed")? {
             let name = entry.file_name();
             let path = entry.path();
-            let mode = Mode::from_path(&path).await?;
+            let mut mode = Mode::from_path(&path).await?;
+            // 嵌套的仓库记为 gitlink，不再递归进去
+            if mode.is_dir() && name != ".git" && submodule::git_dir(&path).is_some() {
+                mode = Mode::Gitlink;
+            }
             vec.push((name, path, mode));
         }
 
@@ -68,7 +75,32 @@ pub(crate) fn write_tree(path: PathBuf) -> TreeFuture {
                 if item.0 == ".git" {
                     continue;
                 }
-                invoke(item.1).await?
+                // 与 git 一样不记录空目录，如还没有检出的子模块
+                match write_tree(item.1).await? {
+                    Some(mut tree) => tree.write_object().await.context("write object failed")?,
+                    None => continue,
+                }
+            } else if Mode::Gitlink == item.2 {
+                submodule::head(&item.1)?.with_context(|| {
+                    format!(
+                        "'{}/' does not have a commit checked out",
+                        item.0.to_string_lossy()
+                    )
+                })?
+            } else if Mode::SymbolicLink == item.2 {
+                // 符号链接存为内容是目标路径的 blob，不跟随链接
+                let target = std::fs::read_link(&item.1)
+                    .with_context(|| format!("readlink {}", item.1.display()))?
+                    .into_os_string()
+                    .into_encoded_bytes();
+                Object {
+                    kind: Kind::Blob,
+                    expected_size: target.len() as u64,
+                    reader: target.as_slice(),
+                }
+                .write_object()
+                .await
+                .context("write object failed")?
             } else {
                 crate::objects::file_to_object(&item.1)?
                     .write_object()
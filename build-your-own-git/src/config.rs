@@ -0,0 +1,439 @@
+//! 读取 git 的配置文件：依次是全局的 `~/.gitconfig`（或 `$XDG_CONFIG_HOME/git/config`）
+//! 和仓库的 `.git/config`，后读到的值覆盖前面的
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+/// 配置中的一项，name 是 `section.key` 或 `section.subsection.key` 形式的完整名称，
+/// 节名和键名统一成小写，子节名区分大小写。只有键名没有 `=` 时值为 None（表示 true）
+#[derive(Debug, Clone)]
+struct Entry {
+    name: String,
+    value: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Config {
+    entries: Vec<Entry>,
+}
+
+impl Config {
+    /// 读取全局和仓库的配置，不存在的文件跳过
+    pub(crate) fn read() -> anyhow::Result<Config> {
+        let mut config = Config::default();
+        let home = std::env::var_os("HOME").map(PathBuf::from);
+        let xdg = match std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+            Some(dir) => Some(PathBuf::from(dir).join("git/config")),
+            None => home.as_ref().map(|home| home.join(".config/git/config")),
+        };
+        let global = home.map(|home| home.join(".gitconfig"));
+        for path in [xdg, global, Some(PathBuf::from(".git/config"))]
+            .into_iter()
+            .flatten()
+        {
+            if let Ok(text) = std::fs::read_to_string(&path) {
+                config
+                    .parse(&text)
+                    .with_context(|| format!("bad config file {}", path.display()))?;
+            }
+        }
+        Ok(config)
+    }
+
+    /// 只读取一个配置格式的文件，如 `.gitmodules`，文件不存在时为空
+    pub(crate) fn read_file(path: impl AsRef<Path>) -> anyhow::Result<Config> {
+        let path = path.as_ref();
+        let mut config = Config::default();
+        if let Ok(text) = std::fs::read_to_string(path) {
+            config
+                .parse(&text)
+                .with_context(|| format!("bad config file {}", path.display()))?;
+        }
+        Ok(config)
+    }
+
+    /// section 下所有子节的名称，按第一次出现的顺序，如 `submodule` 下的各个子模块
+    pub(crate) fn subsections(&self, section: &str) -> Vec<String> {
+        let prefix = format!("{}.", section.to_ascii_lowercase());
+        let mut names: Vec<String> = Vec::new();
+        for entry in &self.entries {
+            let Some(rest) = entry.name.strip_prefix(&prefix) else {
+                continue;
+            };
+            if let Some((subsection, _)) = rest.rsplit_once('.') {
+                if !names.iter().any(|name| name == subsection) {
+                    names.push(subsection.to_string());
+                }
+            }
+        }
+        names
+    }
+
+    /// name 的所有值，按出现的顺序，如 `remote.origin.fetch` 的各个 refspec
+    pub(crate) fn get_all(&self, name: &str) -> Vec<String> {
+        let name = normalize(name);
+        self.entries
+            .iter()
+            .filter(|entry| entry.name == name)
+            .map(|entry| entry.value.clone().unwrap_or_default())
+            .collect()
+    }
+
+    /// name 的最后一个值，没有值的键返回空字符串
+    pub(crate) fn get(&self, name: &str) -> Option<String> {
+        let name = normalize(name);
+        self.entries
+            .iter()
+            .rev()
+            .find(|entry| entry.name == name)
+            .map(|entry| entry.value.clone().unwrap_or_default())
+    }
+
+    /// 按 git 的规则把 name 的最后一个值解析成布尔值：没有 `=` 的键为真，空字符串为假
+    pub(crate) fn get_bool(&self, name: &str) -> anyhow::Result<Option<bool>> {
+        let normalized = normalize(name);
+        let Some(entry) = self
+            .entries
+            .iter()
+            .rev()
+            .find(|entry| entry.name == normalized)
+        else {
+            return Ok(None);
+        };
+        let Some(value) = &entry.value else {
+            return Ok(Some(true));
+        };
+        match value.to_ascii_lowercase().as_str() {
+            "true" | "yes" | "on" => Ok(Some(true)),
+            "false" | "no" | "off" | "" => Ok(Some(false)),
+            number => match number.parse::<i64>() {
+                Ok(number) => Ok(Some(number != 0)),
+                Err(_) => anyhow::bail!("bad boolean config value '{value}' for '{name}'"),
+            },
+        }
+    }
+
+    fn parse(&mut self, text: &str) -> anyhow::Result<()> {
+        let mut section: Option<String> = None;
+        let mut lines = text.lines().enumerate();
+        while let Some((number, line)) = lines.next() {
+            let line = line.trim_start();
+            if line.is_empty() || line.starts_with(['#', ';']) {
+                continue;
+            }
+            let line = if let Some(rest) = line.strip_prefix('[') {
+                let (header, rest) = rest
+                    .split_once(']')
+                    .with_context(|| format!("line {}", number + 1))?;
+                section =
+                    Some(parse_section(header).with_context(|| format!("line {}", number + 1))?);
+                rest.trim_start()
+            } else {
+                line
+            };
+            if line.is_empty() || line.starts_with(['#', ';']) {
+                continue;
+            }
+            let section = section
+                .as_deref()
+                .with_context(|| format!("line {}: key outside of a section", number + 1))?;
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), Some(value)),
+                None => (line.trim(), None),
+            };
+            anyhow::ensure!(
+                !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'),
+                "line {}: invalid key '{key}'",
+                number + 1
+            );
+            let value = match value {
+                Some(value) => {
+                    // 行尾的反斜杠把下一行接上来
+                    let mut raw = value.to_string();
+                    while raw.ends_with('\\') && !raw.ends_with("\\\\") {
+                        raw.pop();
+                        match lines.next() {
+                            Some((_, next)) => raw.push_str(next),
+                            None => break,
+                        }
+                    }
+                    Some(parse_value(&raw).with_context(|| format!("line {}", number + 1))?)
+                }
+                None => None,
+            };
+            self.entries.push(Entry {
+                name: format!("{section}.{}", key.to_ascii_lowercase()),
+                value,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// 在配置文件 path 中把 name 设为 value：已有这个键时替换最后一个，
+/// 否则加到所在节的末尾，没有这个节时在文件末尾新建
+pub(crate) fn set(path: impl AsRef<Path>, name: &str, value: &str) -> anyhow::Result<()> {
+    write_value(path.as_ref(), name, value, true)
+}
+
+/// 在配置文件 path 中给 name 再加一个值，用于 `remote.<name>.fetch` 这样的多值键
+pub(crate) fn add(path: impl AsRef<Path>, name: &str, value: &str) -> anyhow::Result<()> {
+    write_value(path.as_ref(), name, value, false)
+}
+
+fn write_value(path: &Path, name: &str, value: &str, replace: bool) -> anyhow::Result<()> {
+    let (section, key) = name
+        .rsplit_once('.')
+        .with_context(|| format!("key does not contain a section: {name}"))?;
+    let (section, key) = (normalize_section(section), key.to_ascii_lowercase());
+    let text = std::fs::read_to_string(path).unwrap_or_default();
+    let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+    let line = format!("\t{key} = {}", quote_value(value));
+
+    // 找出节的最后一行和节中最后一个同名键所在的行
+    let (mut section_end, mut existing) = (None, None);
+    for (n, current, key_name) in scan(&lines) {
+        if current.as_deref() != Some(section.as_str()) {
+            continue;
+        }
+        section_end = Some(n);
+        let header = lines[n].trim_start().starts_with('[');
+        if !header && key_name.is_some_and(|name| name.eq_ignore_ascii_case(&key)) {
+            existing = Some(n);
+        }
+    }
+    match (existing.filter(|_| replace), section_end) {
+        (Some(n), _) => lines[n] = line,
+        (None, Some(n)) => lines.insert(n + 1, line),
+        (None, None) => {
+            lines.push(section_header(&section));
+            lines.push(line);
+        }
+    }
+    write_lines(path, &lines)
+}
+
+/// 删除配置文件 path 中 name 的所有值，节中不再有键时连节头一起删除
+pub(crate) fn unset(path: impl AsRef<Path>, name: &str) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let (section, key) = name
+        .rsplit_once('.')
+        .with_context(|| format!("key does not contain a section: {name}"))?;
+    let (section, key) = (normalize_section(section), key.to_ascii_lowercase());
+    let text = std::fs::read_to_string(path).unwrap_or_default();
+    let lines: Vec<String> = text.lines().map(str::to_string).collect();
+    let mut removed = vec![false; lines.len()];
+    for (n, current, key_name) in scan(&lines) {
+        if current.as_deref() == Some(section.as_str())
+            && key_name.is_some_and(|name| name.eq_ignore_ascii_case(&key))
+        {
+            removed[n] = true;
+        }
+    }
+    // 节中剩下的行（不算节头）都是空行或注释时，删除整节
+    let mut header: Option<usize> = None;
+    let mut empty = true;
+    for (n, current, key_name) in scan(&lines) {
+        if lines[n].trim_start().starts_with('[') {
+            if let (Some(header), true) = (header, empty) {
+                removed[header] = true;
+            }
+            header = (current.as_deref() == Some(section.as_str())).then_some(n);
+            empty = key_name.is_none();
+        } else if !removed[n] && key_name.is_some() {
+            empty = false;
+        }
+    }
+    if let (Some(header), true) = (header, empty) {
+        removed[header] = true;
+    }
+    if !removed.contains(&true) {
+        return Ok(());
+    }
+    let lines: Vec<String> = lines
+        .into_iter()
+        .zip(removed)
+        .filter(|(_, removed)| !removed)
+        .map(|(line, _)| line)
+        .collect();
+    write_lines(path, &lines)
+}
+
+/// 删除配置文件 path 中名为 section 的所有节，如 `remote.origin`
+pub(crate) fn remove_section(path: impl AsRef<Path>, section: &str) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let section = normalize_section(section);
+    let text = std::fs::read_to_string(path).unwrap_or_default();
+    let lines: Vec<String> = text.lines().map(str::to_string).collect();
+    let kept: Vec<String> = scan(&lines)
+        .into_iter()
+        .filter(|(_, current, _)| current.as_deref() != Some(section.as_str()))
+        .map(|(n, _, _)| lines[n].clone())
+        .collect();
+    write_lines(path, &kept)
+}
+
+/// 把配置文件 path 中名为 old 的节改名为 new，节的位置和内容不变
+pub(crate) fn rename_section(path: impl AsRef<Path>, old: &str, new: &str) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let (old, new) = (normalize_section(old), normalize_section(new));
+    let text = std::fs::read_to_string(path).unwrap_or_default();
+    let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+    for (n, current, key_name) in scan(&lines) {
+        if current.as_deref() != Some(old.as_str()) || !lines[n].trim_start().starts_with('[') {
+            continue;
+        }
+        // 节头后面同一行还有键时保留下来
+        let rest = lines[n]
+            .split_once(']')
+            .map(|(_, rest)| rest.trim_start().to_string())
+            .unwrap_or_default();
+        lines[n] = match key_name {
+            Some(_) => format!("{}\t{rest}", section_header(&new)),
+            None => section_header(&new),
+        };
+    }
+    write_lines(path, &lines)
+}
+
+/// 逐行给出 (行号, 所在的节, 这一行的键名)，节头所在的行算作这一节
+fn scan(lines: &[String]) -> Vec<(usize, Option<String>, Option<String>)> {
+    let mut current: Option<String> = None;
+    let mut result = Vec::new();
+    for (n, raw) in lines.iter().enumerate() {
+        let trimmed = raw.trim_start();
+        let rest = if let Some(header) = trimmed.strip_prefix('[') {
+            let (header, rest) = header.split_once(']').unwrap_or((header, ""));
+            current = parse_section(header).ok();
+            rest.trim_start()
+        } else {
+            trimmed
+        };
+        let name = rest.split(['=', ' ', '\t']).next().unwrap_or("");
+        let key = (!name.is_empty() && !name.starts_with(['#', ';'])).then(|| name.to_string());
+        result.push((n, current.clone(), key));
+    }
+    result
+}
+
+/// 节名 `remote.origin` 写成节头 `[remote "origin"]`
+fn section_header(section: &str) -> String {
+    match section.split_once('.') {
+        Some((name, subsection)) => format!(
+            "[{name} \"{}\"]",
+            subsection.replace('\\', "\\\\").replace('"', "\\\"")
+        ),
+        None => format!("[{section}]"),
+    }
+}
+
+/// 节名中第一段不区分大小写，子节名原样保留
+fn normalize_section(section: &str) -> String {
+    match section.split_once('.') {
+        Some((name, subsection)) => format!("{}.{subsection}", name.to_ascii_lowercase()),
+        None => section.to_ascii_lowercase(),
+    }
+}
+
+fn write_lines(path: &Path, lines: &[String]) -> anyhow::Result<()> {
+    let mut content = lines.join("\n");
+    if !content.is_empty() {
+        content.push('\n');
+    }
+    std::fs::write(path, content).with_context(|| format!("write {}", path.display()))
+}
+
+/// 写入配置文件时的值：转义反斜杠、引号和换行，两端有空白或含注释符号时加引号
+fn quote_value(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t");
+    if value.starts_with(char::is_whitespace)
+        || value.ends_with(char::is_whitespace)
+        || value.contains(['#', ';'])
+    {
+        format!("\"{escaped}\"")
+    } else {
+        escaped
+    }
+}
+
+/// `[core]`、`[remote "origin"]` 或旧式的 `[branch.main]`
+fn parse_section(header: &str) -> anyhow::Result<String> {
+    let header = header.trim();
+    match header.split_once(char::is_whitespace) {
+        Some((name, subsection)) => {
+            let subsection = subsection
+                .trim()
+                .strip_prefix('"')
+                .and_then(|rest| rest.strip_suffix('"'))
+                .context("invalid section header")?;
+            let mut unescaped = String::new();
+            let mut chars = subsection.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => unescaped.extend(chars.next()),
+                    c => unescaped.push(c),
+                }
+            }
+            Ok(format!("{}.{unescaped}", name.to_ascii_lowercase()))
+        }
+        None => Ok(match header.split_once('.') {
+            Some((name, subsection)) => format!("{}.{subsection}", name.to_ascii_lowercase()),
+            None => header.to_ascii_lowercase(),
+        }),
+    }
+}
+
+/// 去掉两端的空白和注释，处理引号和转义，引号内的空白原样保留
+fn parse_value(raw: &str) -> anyhow::Result<String> {
+    let mut value = String::new();
+    // 引号外的空白先记下，后面还有内容时才加进去，这样末尾的空白会被去掉
+    let mut pending = String::new();
+    let mut quoted = false;
+    let mut chars = raw.trim_start().chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                value.push_str(&std::mem::take(&mut pending));
+                quoted = !quoted;
+            }
+            '#' | ';' if !quoted => break,
+            c if c.is_whitespace() && !quoted => pending.push(c),
+            '\\' => {
+                value.push_str(&std::mem::take(&mut pending));
+                match chars.next() {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some('b') => {
+                        value.pop();
+                    }
+                    Some(c @ ('"' | '\\')) => value.push(c),
+                    other => anyhow::bail!("invalid escape sequence '\\{}'", other.unwrap_or(' ')),
+                }
+            }
+            c => {
+                value.push_str(&std::mem::take(&mut pending));
+                value.push(c);
+            }
+        }
+    }
+    anyhow::ensure!(!quoted, "missing closing quote");
+    Ok(value)
+}
+
+/// 完整名称中第一段和最后一段不区分大小写，中间的子节名原样保留
+fn normalize(name: &str) -> String {
+    match (name.split_once('.'), name.rsplit_once('.')) {
+        (Some((section, _)), Some((rest, key))) if rest.len() > section.len() => format!(
+            "{}{}.{}",
+            section.to_ascii_lowercase(),
+            &rest[section.len()..],
+            key.to_ascii_lowercase()
+        ),
+        _ => name.to_ascii_lowercase(),
+    }
+}
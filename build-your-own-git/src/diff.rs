@@ -0,0 +1,443 @@
+pub(crate) mod histogram;
+pub(crate) mod lines;
+pub(crate) mod myers;
+pub(crate) mod patch;
+pub(crate) mod patch_id;
+pub(crate) mod patience;
+pub(crate) mod rename;
+pub(crate) mod stat;
+pub(crate) mod unified;
+
+use std::{collections::BTreeMap, future::Future, pin::Pin};
+
+use anyhow::Context;
+
+use crate::{
+    diff::rename::{Detect, RenameOptions, detect_renames},
+    index::Index,
+    objects::{
+        Kind, Mode, Object, read_object,
+        tree::{TreeEntry, read_tree},
+    },
+    submodule, worktree,
+};
+
+/// 一条变更的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Status {
+    Added,
+    Deleted,
+    Modified,
+    /// 文件类型变化，如普通文件变成符号链接
+    TypeChanged,
+    /// 重命名，附带相似度百分比
+    Renamed(u32),
+    /// 复制，附带相似度百分比
+    Copied(u32),
+}
+
+impl Status {
+    /// raw 和 --name-status 输出中的状态字母
+    pub(crate) fn letter(self) -> char {
+        match self {
+            Status::Added => 'A',
+            Status::Deleted => 'D',
+            Status::Modified => 'M',
+            Status::TypeChanged => 'T',
+            Status::Renamed(_) => 'R',
+            Status::Copied(_) => 'C',
+        }
+    }
+
+    /// 状态字母，重命名和复制后面跟三位相似度，如 `R086`
+    pub(crate) fn code(self) -> String {
+        match self {
+            Status::Renamed(score) | Status::Copied(score) => {
+                format!("{}{score:03}", self.letter())
+            }
+            _ => self.letter().to_string(),
+        }
+    }
+}
+
+/// 变更一侧的文件：模式和对象哈希
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Side {
+    pub(crate) mode: Mode,
+    pub(crate) hash: [u8; 20],
+}
+
+/// 两棵树之间的一条变更
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Change {
+    pub(crate) status: Status,
+    pub(crate) path: String,
+    /// 重命名或复制的来源路径
+    pub(crate) from: Option<String>,
+    /// 新增时为 None
+    pub(crate) old: Option<Side>,
+    /// 删除时为 None
+    pub(crate) new: Option<Side>,
+}
+
+impl Change {
+    /// 旧文件的路径，只有重命名和复制时与新路径不同
+    pub(crate) fn old_path(&self) -> &str {
+        self.from.as_deref().unwrap_or(&self.path)
+    }
+
+    /// 状态和路径，重命名和复制时是 `R086\told\tnew`
+    pub(crate) fn format_name_status(&self) -> String {
+        match &self.from {
+            Some(from) => format!(
+                "{}\t{}\t{}",
+                self.status.code(),
+                quote_path(from),
+                quote_path(&self.path)
+            ),
+            None => format!("{}\t{}", self.status.code(), quote_path(&self.path)),
+        }
+    }
+
+    /// `:100644 100644 <旧哈希> <新哈希> M\tpath`
+    pub(crate) fn format_raw(&self) -> String {
+        let mode =
+            |side: Option<Side>| side.map_or("000000".to_string(), |side| side.mode.to_padded());
+        let hash = |side: Option<Side>| hex::encode(side.map_or([0; 20], |side| side.hash));
+        format!(
+            ":{} {} {} {} {}",
+            mode(self.old),
+            mode(self.new),
+            hash(self.old),
+            hash(self.new),
+            self.format_name_status()
+        )
+    }
+}
+
+/// 路径含有控制字符、引号、反斜杠或非 ASCII 字符时按 C 风格加引号转义
+pub(crate) fn quote_path(path: &str) -> String {
+    let needs_quote = |b: u8| b < 0x20 || b == b'"' || b == b'\\' || b >= 0x7f;
+    if !path.bytes().any(needs_quote) {
+        return path.to_string();
+    }
+    let mut out = String::from("\"");
+    for b in path.bytes() {
+        match b {
+            0x07 => out.push_str("\\a"),
+            0x08 => out.push_str("\\b"),
+            b'\t' => out.push_str("\\t"),
+            b'\n' => out.push_str("\\n"),
+            0x0b => out.push_str("\\v"),
+            0x0c => out.push_str("\\f"),
+            b'\r' => out.push_str("\\r"),
+            b'"' => out.push_str("\\\""),
+            b'\\' => out.push_str("\\\\"),
+            b if needs_quote(b) => out.push_str(&format!("\\{b:03o}")),
+            b => out.push(b as char),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// 比较两组按路径展开的文件，路径按字节序排列，与递归比较树的结果顺序一致
+pub(crate) fn diff_maps(old: &BTreeMap<String, Side>, new: &BTreeMap<String, Side>) -> Vec<Change> {
+    let mut paths: Vec<&String> = old.keys().chain(new.keys()).collect();
+    paths.sort();
+    paths.dedup();
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let (old, new) = (old.get(path).copied(), new.get(path).copied());
+            let status = match (old, new) {
+                (None, Some(_)) => Status::Added,
+                (Some(_), None) => Status::Deleted,
+                (Some(a), Some(b)) if a == b => return None,
+                (Some(a), Some(b)) if !same_type(a.mode, b.mode) => Status::TypeChanged,
+                (Some(_), Some(_)) => Status::Modified,
+                (None, None) => return None,
+            };
+            Some(Change {
+                status,
+                path: path.clone(),
+                from: None,
+                old,
+                new,
+            })
+        })
+        .collect()
+}
+
+/// 递归展开树中的所有文件
+pub(crate) async fn flatten_tree(tree: &[u8; 20]) -> anyhow::Result<BTreeMap<String, Side>> {
+    let mut files = BTreeMap::new();
+    for change in diff_trees(None, Some(tree), true).await? {
+        if let Some(side) = change.new {
+            files.insert(change.path, side);
+        }
+    }
+    Ok(files)
+}
+
+/// 暂存区中没有冲突的文件
+pub(crate) fn index_files(index: &Index) -> BTreeMap<String, Side> {
+    index
+        .entries
+        .iter()
+        .filter(|entry| entry.stage == 0)
+        .map(|entry| {
+            let side = Side {
+                mode: entry.mode,
+                hash: entry.hash,
+            };
+            (entry.path.clone(), side)
+        })
+        .collect()
+}
+
+/// 展开工作区中被暂存区跟踪的文件，已从工作区删除的路径不包含在内
+pub(crate) async fn worktree_files(index: &Index) -> anyhow::Result<BTreeMap<String, Side>> {
+    let mut files = BTreeMap::new();
+    for entry in &index.entries {
+        if files.contains_key(&entry.path) {
+            continue;
+        }
+        let Ok(metadata) = std::fs::symlink_metadata(&entry.path) else {
+            continue;
+        };
+        if metadata.is_dir() {
+            // 子模块取它检出的提交，还没有检出（空目录）时看作没有修改
+            if entry.mode == Mode::Gitlink {
+                let hash = submodule::head(&entry.path)?.unwrap_or(entry.hash);
+                let side = Side {
+                    mode: Mode::Gitlink,
+                    hash,
+                };
+                files.insert(entry.path.clone(), side);
+            }
+            continue;
+        }
+        let data = read_worktree_file(&entry.path)?;
+        let mut object = Object {
+            kind: Kind::Blob,
+            expected_size: data.len() as u64,
+            reader: data.as_slice(),
+        };
+        let hash = object.compute_hash(std::io::sink()).await?;
+        files.insert(
+            entry.path.clone(),
+            Side {
+                mode: worktree::worktree_mode(&metadata, Some(entry.mode))?,
+                hash,
+            },
+        );
+    }
+    Ok(files)
+}
+
+/// 变更一侧的内容。子模块没有内容，与 git 一样显示成 `Subproject commit <哈希>`
+pub(crate) async fn side_content(side: Side) -> anyhow::Result<Vec<u8>> {
+    if side.mode == Mode::Gitlink {
+        return Ok(format!("Subproject commit {}\n", hex::encode(side.hash)).into_bytes());
+    }
+    Ok(read_object(&hex::encode(side.hash)).await?.1)
+}
+
+/// 工作区文件的内容，符号链接取其指向的路径
+pub(crate) fn read_worktree_file(path: &str) -> anyhow::Result<Vec<u8>> {
+    let metadata = std::fs::symlink_metadata(path).with_context(|| format!("stat {path}"))?;
+    if metadata.file_type().is_symlink() {
+        let target = std::fs::read_link(path).with_context(|| format!("readlink {path}"))?;
+        Ok(target.into_os_string().into_encoded_bytes())
+    } else {
+        std::fs::read(path).with_context(|| format!("read {path}"))
+    }
+}
+
+/// 比较两棵树，并按选项检测重命名和复制
+pub(crate) async fn diff_trees_with_renames(
+    old: Option<&[u8; 20]>,
+    new: Option<&[u8; 20]>,
+    recursive: bool,
+    renames: RenameOptions,
+) -> anyhow::Result<Vec<Change>> {
+    let changes = diff_trees(old, new, recursive).await?;
+    let unchanged = match old {
+        Some(tree) if renames.detect == Detect::CopiesHarder => {
+            unchanged_files(flatten_tree(tree).await?, &changes)
+        }
+        _ => BTreeMap::new(),
+    };
+    detect_renames(changes, &unchanged, renames, false).await
+}
+
+/// 从旧的一侧去掉有变化的路径，剩下的作为 --find-copies-harder 的复制来源
+pub(crate) fn unchanged_files(
+    mut files: BTreeMap<String, Side>,
+    changes: &[Change],
+) -> BTreeMap<String, Side> {
+    for change in changes {
+        files.remove(&change.path);
+    }
+    files
+}
+
+type DiffFuture<'a> = Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>>;
+
+/// 比较两棵树，None 表示空树。recursive 为假时子树的变化作为一条目录记录输出，
+/// 否则深入子树只输出文件。哈希相同的子树直接跳过
+pub(crate) async fn diff_trees(
+    old: Option<&[u8; 20]>,
+    new: Option<&[u8; 20]>,
+    recursive: bool,
+) -> anyhow::Result<Vec<Change>> {
+    let mut changes = Vec::new();
+    diff_into(
+        old.copied(),
+        new.copied(),
+        String::new(),
+        recursive,
+        &mut changes,
+    )
+    .await?;
+    Ok(changes)
+}
+
+fn diff_into(
+    old: Option<[u8; 20]>,
+    new: Option<[u8; 20]>,
+    prefix: String,
+    recursive: bool,
+    changes: &mut Vec<Change>,
+) -> DiffFuture<'_> {
+    Box::pin(async move {
+        if old == new {
+            return Ok(());
+        }
+        let old_entries = match old {
+            Some(hash) => read_tree(&hash).await?,
+            None => Vec::new(),
+        };
+        let new_entries = match new {
+            Some(hash) => read_tree(&hash).await?,
+            None => Vec::new(),
+        };
+
+        // 树中条目按“目录名后面加 /”的顺序排列，同名的文件和目录视为不同条目
+        let mut old_iter = old_entries.into_iter().peekable();
+        let mut new_iter = new_entries.into_iter().peekable();
+        loop {
+            let order = match (old_iter.peek(), new_iter.peek()) {
+                (None, None) => break,
+                (Some(_), None) => std::cmp::Ordering::Less,
+                (None, Some(_)) => std::cmp::Ordering::Greater,
+                (Some(a), Some(b)) => sort_key(a).cmp(&sort_key(b)),
+            };
+            match order {
+                std::cmp::Ordering::Less => {
+                    let entry = old_iter.next().expect("peeked");
+                    one_side(entry, &prefix, true, recursive, changes).await?;
+                }
+                std::cmp::Ordering::Greater => {
+                    let entry = new_iter.next().expect("peeked");
+                    one_side(entry, &prefix, false, recursive, changes).await?;
+                }
+                std::cmp::Ordering::Equal => {
+                    let a = old_iter.next().expect("peeked");
+                    let b = new_iter.next().expect("peeked");
+                    if a.hash == b.hash && a.mode == b.mode {
+                        continue;
+                    }
+                    let path = format!("{prefix}{}", a.name);
+                    if a.mode.is_dir() && recursive {
+                        diff_into(
+                            Some(a.hash),
+                            Some(b.hash),
+                            format!("{path}/"),
+                            recursive,
+                            changes,
+                        )
+                        .await?;
+                        continue;
+                    }
+                    let status = if !same_type(a.mode, b.mode) {
+                        Status::TypeChanged
+                    } else {
+                        Status::Modified
+                    };
+                    changes.push(Change {
+                        status,
+                        path,
+                        from: None,
+                        old: Some(Side {
+                            mode: a.mode,
+                            hash: a.hash,
+                        }),
+                        new: Some(Side {
+                            mode: b.mode,
+                            hash: b.hash,
+                        }),
+                    });
+                }
+            }
+        }
+        Ok(())
+    })
+}
+
+/// 只在一侧存在的条目：整个删除或新增，递归时展开子树
+async fn one_side(
+    entry: TreeEntry,
+    prefix: &str,
+    deleted: bool,
+    recursive: bool,
+    changes: &mut Vec<Change>,
+) -> anyhow::Result<()> {
+    let path = format!("{prefix}{}", entry.name);
+    if entry.mode.is_dir() && recursive {
+        let (old, new) = if deleted {
+            (Some(entry.hash), None)
+        } else {
+            (None, Some(entry.hash))
+        };
+        return diff_into(old, new, format!("{path}/"), recursive, changes).await;
+    }
+    let side = Some(Side {
+        mode: entry.mode,
+        hash: entry.hash,
+    });
+    changes.push(if deleted {
+        Change {
+            status: Status::Deleted,
+            path,
+            from: None,
+            old: side,
+            new: None,
+        }
+    } else {
+        Change {
+            status: Status::Added,
+            path,
+            from: None,
+            old: None,
+            new: side,
+        }
+    });
+    Ok(())
+}
+
+/// 树条目的排序键：目录名后面补 `/`
+fn sort_key(entry: &TreeEntry) -> Vec<u8> {
+    let mut key = entry.name.as_bytes().to_vec();
+    if entry.mode.is_dir() {
+        key.push(b'/');
+    }
+    key
+}
+
+/// 普通文件、符号链接和子模块之间的变化是类型变化，可执行位的变化不是
+fn same_type(a: Mode, b: Mode) -> bool {
+    let regular = |mode| matches!(mode, Mode::File | Mode::Executable);
+    a == b || regular(a) && regular(b)
+}
@@ -0,0 +1,172 @@
+//! histogram 差异算法：以出现次数最少的公共行为中心扩展出公共区间，递归处理两侧，
+//! 公共行都太常见时退回 Myers，移植自 git 的 xdiff（xhistogram.c）
+
+use std::collections::HashMap;
+
+use crate::diff::myers;
+
+/// 出现次数超过这个值的行不作为候选
+const MAX_CHAIN_LENGTH: usize = 64;
+
+pub(crate) fn diff(a: &[usize], b: &[usize], rchg1: &mut [bool], rchg2: &mut [bool]) {
+    let mut ctx = Histogram { a, b, rchg1, rchg2 };
+    ctx.diff(1, a.len(), 1, b.len());
+}
+
+/// 公共区间，闭区间，行号从 1 开始，全为 0 表示没有找到
+#[derive(Debug, Default)]
+struct Region {
+    begin1: usize,
+    end1: usize,
+    begin2: usize,
+    end2: usize,
+}
+
+/// 旧文件中某一类行：第一次出现的行号和出现次数
+struct Record {
+    ptr: usize,
+    cnt: usize,
+}
+
+struct Histogram<'a> {
+    a: &'a [usize],
+    b: &'a [usize],
+    rchg1: &'a mut [bool],
+    rchg2: &'a mut [bool],
+}
+
+impl Histogram<'_> {
+    fn diff(&mut self, mut line1: usize, mut count1: usize, mut line2: usize, mut count2: usize) {
+        loop {
+            if count1 == 0 {
+                self.rchg2[line2 - 1..line2 - 1 + count2].fill(true);
+                return;
+            }
+            if count2 == 0 {
+                self.rchg1[line1 - 1..line1 - 1 + count1].fill(true);
+                return;
+            }
+
+            let Some(lcs) = self.find_lcs(line1, count1, line2, count2) else {
+                myers::diff(
+                    &self.a[line1 - 1..line1 - 1 + count1],
+                    &self.b[line2 - 1..line2 - 1 + count2],
+                    false,
+                    &mut self.rchg1[line1 - 1..line1 - 1 + count1],
+                    &mut self.rchg2[line2 - 1..line2 - 1 + count2],
+                );
+                return;
+            };
+            if lcs.begin1 == 0 && lcs.begin2 == 0 {
+                self.rchg1[line1 - 1..line1 - 1 + count1].fill(true);
+                self.rchg2[line2 - 1..line2 - 1 + count2].fill(true);
+                return;
+            }
+
+            self.diff(line1, lcs.begin1 - line1, line2, lcs.begin2 - line2);
+            // 尾部循环处理
+            count1 = line1 + count1 - 1 - lcs.end1;
+            line1 = lcs.end1 + 1;
+            count2 = line2 + count2 - 1 - lcs.end2;
+            line2 = lcs.end2 + 1;
+        }
+    }
+
+    /// 找出最长公共区间；公共行都太常见时返回 None，表示应当退回 Myers
+    fn find_lcs(&self, line1: usize, count1: usize, line2: usize, count2: usize) -> Option<Region> {
+        let end1 = line1 + count1 - 1;
+        let end2 = line2 + count2 - 1;
+
+        // 从后往前扫描旧文件，每类行的记录指向它第一次出现的位置，
+        // next_ptrs 把同一类行按位置串起来（0 表示没有下一个）
+        let mut records: Vec<Record> = Vec::new();
+        let mut by_class: HashMap<usize, usize> = HashMap::new();
+        let mut line_map = vec![0; count1];
+        let mut next_ptrs = vec![0; count1];
+        for ptr in (line1..=end1).rev() {
+            let class = self.a[ptr - 1];
+            match by_class.get(&class) {
+                Some(&r) => {
+                    next_ptrs[ptr - line1] = records[r].ptr;
+                    records[r].ptr = ptr;
+                    records[r].cnt += 1;
+                    line_map[ptr - line1] = r;
+                }
+                None => {
+                    by_class.insert(class, records.len());
+                    line_map[ptr - line1] = records.len();
+                    records.push(Record { ptr, cnt: 1 });
+                }
+            }
+        }
+        let cnt_at = |ptr: usize| records[line_map[ptr - line1]].cnt;
+
+        let mut lcs = Region::default();
+        let mut cnt = MAX_CHAIN_LENGTH + 1;
+        let mut has_common = false;
+        let mut b_ptr = line2;
+        while b_ptr <= end2 {
+            let mut b_next = b_ptr + 1;
+            if let Some(rec) = by_class.get(&self.b[b_ptr - 1]).map(|&r| &records[r]) {
+                has_common = true;
+                if rec.cnt <= cnt {
+                    let mut as_ = rec.ptr;
+                    'occurrences: loop {
+                        let mut np = next_ptrs[as_ - line1];
+                        let mut bs = b_ptr;
+                        let mut ae = as_;
+                        let mut be = bs;
+                        let mut rc = rec.cnt;
+
+                        while line1 < as_ && line2 < bs && self.a[as_ - 2] == self.b[bs - 2] {
+                            as_ -= 1;
+                            bs -= 1;
+                            if 1 < rc {
+                                rc = rc.min(cnt_at(as_));
+                            }
+                        }
+                        while ae < end1 && be < end2 && self.a[ae] == self.b[be] {
+                            ae += 1;
+                            be += 1;
+                            if 1 < rc {
+                                rc = rc.min(cnt_at(ae));
+                            }
+                        }
+
+                        if b_next <= be {
+                            b_next = be + 1;
+                        }
+                        if lcs.end1 - lcs.begin1 < ae - as_ || rc < cnt {
+                            lcs = Region {
+                                begin1: as_,
+                                end1: ae,
+                                begin2: bs,
+                                end2: be,
+                            };
+                            cnt = rc;
+                        }
+
+                        // 跳到这类行在当前区间之后的下一次出现
+                        if np == 0 {
+                            break;
+                        }
+                        while np <= ae {
+                            np = next_ptrs[np - line1];
+                            if np == 0 {
+                                break 'occurrences;
+                            }
+                        }
+                        as_ = np;
+                    }
+                }
+            }
+            b_ptr = b_next;
+        }
+
+        if has_common && MAX_CHAIN_LENGTH < cnt {
+            None
+        } else {
+            Some(lcs)
+        }
+    }
+}
@@ -0,0 +1,478 @@
+use std::collections::HashMap;
+
+use crate::diff::{histogram, myers, patience};
+
+/// 行差异算法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum Algorithm {
+    #[default]
+    Myers,
+    /// 不使用启发式剪枝的 Myers，保证编辑最少
+    Minimal,
+    Patience,
+    Histogram,
+}
+
+impl Algorithm {
+    pub(crate) fn from_str(s: &str) -> anyhow::Result<Algorithm> {
+        match s {
+            "myers" | "default" => Ok(Algorithm::Myers),
+            "minimal" => Ok(Algorithm::Minimal),
+            "patience" => Ok(Algorithm::Patience),
+            "histogram" => Ok(Algorithm::Histogram),
+            _ => anyhow::bail!("unknown diff algorithm '{s}'"),
+        }
+    }
+}
+
+/// 比较行时如何处理空白
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum Whitespace {
+    #[default]
+    Exact,
+    /// `-b`：连续空白视为一个空格，忽略行尾空白
+    IgnoreChange,
+    /// `-w`：忽略所有空白
+    IgnoreAll,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct LineDiffOptions {
+    pub(crate) algorithm: Algorithm,
+    pub(crate) whitespace: Whitespace,
+    /// 用缩进启发式选择修改组的位置，关闭时尽量下移（三方合并不使用）
+    pub(crate) indent_heuristic: bool,
+}
+
+impl Default for LineDiffOptions {
+    fn default() -> Self {
+        LineDiffOptions {
+            algorithm: Algorithm::default(),
+            whitespace: Whitespace::default(),
+            indent_heuristic: true,
+        }
+    }
+}
+
+/// 一处连续的修改：旧文件从 old 开始的 old_len 行被替换为新文件从 new 开始的 new_len 行，
+/// 行号从 0 开始
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Edit {
+    pub(crate) old: usize,
+    pub(crate) old_len: usize,
+    pub(crate) new: usize,
+    pub(crate) new_len: usize,
+}
+
+/// 按 `\n` 切分，每行保留换行符，最后一行可能没有换行符
+pub(crate) fn split_lines(data: &[u8]) -> Vec<&[u8]> {
+    data.split_inclusive(|&b| b == b'\n').collect()
+}
+
+/// 比较两组行，返回按位置排序的修改列表
+pub(crate) fn diff_lines(old: &[&[u8]], new: &[&[u8]], options: LineDiffOptions) -> Vec<Edit> {
+    // 内容相同（按空白规则）的行归为同一类，之后只比较类别编号
+    let mut classes: HashMap<Vec<u8>, usize> = HashMap::new();
+    let mut classify = |lines: &[&[u8]]| -> Vec<usize> {
+        lines
+            .iter()
+            .map(|line| {
+                let next = classes.len();
+                *classes
+                    .entry(normalize(line, options.whitespace))
+                    .or_insert(next)
+            })
+            .collect()
+    };
+    let class1 = classify(old);
+    let class2 = classify(new);
+
+    let mut rchg1 = vec![false; old.len()];
+    let mut rchg2 = vec![false; new.len()];
+    match options.algorithm {
+        Algorithm::Myers => myers::diff(&class1, &class2, false, &mut rchg1, &mut rchg2),
+        Algorithm::Minimal => myers::diff(&class1, &class2, true, &mut rchg1, &mut rchg2),
+        Algorithm::Patience => patience::diff(&class1, &class2, &mut rchg1, &mut rchg2),
+        Algorithm::Histogram => histogram::diff(&class1, &class2, &mut rchg1, &mut rchg2),
+    }
+
+    compact(&mut rchg1, &class1, old, &rchg2, options.indent_heuristic);
+    compact(&mut rchg2, &class2, new, &rchg1, options.indent_heuristic);
+    build_script(&rchg1, &rchg2)
+}
+
+/// 按空白规则判断两行是否相同
+pub(crate) fn lines_match(a: &[u8], b: &[u8], whitespace: Whitespace) -> bool {
+    match whitespace {
+        Whitespace::Exact => a == b,
+        _ => normalize(a, whitespace) == normalize(b, whitespace),
+    }
+}
+
+/// 按空白规则生成用于比较的行内容
+fn normalize(line: &[u8], whitespace: Whitespace) -> Vec<u8> {
+    match whitespace {
+        Whitespace::Exact => line.to_vec(),
+        Whitespace::IgnoreAll => line.iter().copied().filter(|&b| !is_space(b)).collect(),
+        Whitespace::IgnoreChange => {
+            let mut out = Vec::with_capacity(line.len());
+            let mut i = 0;
+            while i < line.len() {
+                if is_space(line[i]) {
+                    while i < line.len() && is_space(line[i]) {
+                        i += 1;
+                    }
+                    // 行尾空白（包括换行符）直接忽略
+                    if i < line.len() {
+                        out.push(b' ');
+                    }
+                } else {
+                    out.push(line[i]);
+                    i += 1;
+                }
+            }
+            out
+        }
+    }
+}
+
+/// git 的 isspace：空格、制表符、换行和回车
+pub(crate) fn is_space(b: u8) -> bool {
+    matches!(b, b' ' | b'\t' | b'\n' | b'\r')
+}
+
+/// 根据修改标记生成修改列表，两个文件中未修改的行一一对应
+fn build_script(rchg1: &[bool], rchg2: &[bool]) -> Vec<Edit> {
+    let (n1, n2) = (rchg1.len(), rchg2.len());
+    let mut edits = Vec::new();
+    let (mut i1, mut i2) = (0, 0);
+    while i1 < n1 || i2 < n2 {
+        if (i1 < n1 && rchg1[i1]) || (i2 < n2 && rchg2[i2]) {
+            let (start1, start2) = (i1, i2);
+            while i1 < n1 && rchg1[i1] {
+                i1 += 1;
+            }
+            while i2 < n2 && rchg2[i2] {
+                i2 += 1;
+            }
+            edits.push(Edit {
+                old: start1,
+                old_len: i1 - start1,
+                new: start2,
+                new_len: i2 - start2,
+            });
+        } else {
+            i1 += 1;
+            i2 += 1;
+        }
+    }
+    edits
+}
+
+/// 一组连续的被修改行 `[start, end)`，为空时 start == end
+#[derive(Debug, Clone, Copy)]
+struct Group {
+    start: usize,
+    end: usize,
+}
+
+impl Group {
+    fn first(rchg: &[bool]) -> Group {
+        let mut end = 0;
+        while end < rchg.len() && rchg[end] {
+            end += 1;
+        }
+        Group { start: 0, end }
+    }
+
+    /// 移到下一组，已经是最后一组时返回 false
+    fn next(&mut self, rchg: &[bool]) -> bool {
+        if self.end == rchg.len() {
+            return false;
+        }
+        self.start = self.end + 1;
+        self.end = self.start;
+        while self.end < rchg.len() && rchg[self.end] {
+            self.end += 1;
+        }
+        true
+    }
+
+    /// 移到上一组，已经是第一组时返回 false
+    fn previous(&mut self, rchg: &[bool]) -> bool {
+        if self.start == 0 {
+            return false;
+        }
+        self.end = self.start - 1;
+        self.start = self.end;
+        while self.start > 0 && rchg[self.start - 1] {
+            self.start -= 1;
+        }
+        true
+    }
+
+    /// 组后面的一行和组的第一行相同时整组下移一行，碰到下一组则合并
+    fn slide_down(&mut self, rchg: &mut [bool], class: &[usize]) -> bool {
+        if self.end < rchg.len() && class[self.start] == class[self.end] {
+            rchg[self.start] = false;
+            self.start += 1;
+            rchg[self.end] = true;
+            self.end += 1;
+            while self.end < rchg.len() && rchg[self.end] {
+                self.end += 1;
+            }
+            true
+        } else {
+            false
+        }
+    }
+
+    /// 组前面的一行和组的最后一行相同时整组上移一行，碰到上一组则合并
+    fn slide_up(&mut self, rchg: &mut [bool], class: &[usize]) -> bool {
+        if self.start > 0 && class[self.start - 1] == class[self.end - 1] {
+            self.start -= 1;
+            rchg[self.start] = true;
+            self.end -= 1;
+            rchg[self.end] = false;
+            while self.start > 0 && rchg[self.start - 1] {
+                self.start -= 1;
+            }
+            true
+        } else {
+            false
+        }
+    }
+}
+
+const INDENT_HEURISTIC_MAX_SLIDING: usize = 100;
+
+/// 把可以上下滑动的修改组移到最自然的位置（与 git 的 xdl_change_compact 相同）：
+/// 尽量与另一个文件的修改组对齐，否则用缩进启发式选择切分位置
+fn compact(
+    rchg: &mut [bool],
+    class: &[usize],
+    lines: &[&[u8]],
+    other: &[bool],
+    indent_heuristic: bool,
+) {
+    let mut g = Group::first(rchg);
+    let mut go = Group::first(other);
+    loop {
+        if g.end != g.start {
+            let mut earliest_end;
+            let mut end_matching_other;
+            let mut group_size;
+            // 先尽量上移再尽量下移，途中可能与相邻的组合并，合并后重新来过
+            loop {
+                group_size = g.end - g.start;
+                end_matching_other = None;
+                while g.slide_up(rchg, class) {
+                    go.previous(other);
+                }
+                earliest_end = g.end;
+                if go.end > go.start {
+                    end_matching_other = Some(g.end);
+                }
+                while g.slide_down(rchg, class) {
+                    go.next(other);
+                    if go.end > go.start {
+                        end_matching_other = Some(g.end);
+                    }
+                }
+                if group_size == g.end - g.start {
+                    break;
+                }
+            }
+
+            if g.end == earliest_end {
+                // 无法移动
+            } else if end_matching_other.is_some() {
+                // 移回与另一个文件的修改组对齐的位置
+                while go.end == go.start {
+                    g.slide_up(rchg, class);
+                    go.previous(other);
+                }
+            } else if indent_heuristic {
+                // 缩进启发式：给组上下两个切分位置打分，取总分最低的位置
+                let mut shift = earliest_end;
+                if g.end > group_size + 1 && g.end - group_size - 1 > shift {
+                    shift = g.end - group_size - 1;
+                }
+                if g.end > INDENT_HEURISTIC_MAX_SLIDING
+                    && g.end - INDENT_HEURISTIC_MAX_SLIDING > shift
+                {
+                    shift = g.end - INDENT_HEURISTIC_MAX_SLIDING;
+                }
+                let mut best: Option<(usize, SplitScore)> = None;
+                while shift <= g.end {
+                    let mut score = SplitScore::default();
+                    score.add(&measure_split(lines, shift));
+                    score.add(&measure_split(lines, shift - group_size));
+                    if best.map_or(true, |(_, best_score)| score.cmp(&best_score) <= 0) {
+                        best = Some((shift, score));
+                    }
+                    shift += 1;
+                }
+                if let Some((best_shift, _)) = best {
+                    while g.end > best_shift {
+                        g.slide_up(rchg, class);
+                        go.previous(other);
+                    }
+                }
+            }
+        }
+        if !g.next(rchg) {
+            break;
+        }
+        go.next(other);
+    }
+}
+
+const MAX_INDENT: i32 = 200;
+const MAX_BLANKS: i32 = 20;
+
+/// 行的缩进宽度（制表符按 8 列），只有空白的行返回 -1
+fn get_indent(line: &[u8]) -> i32 {
+    let mut indent = 0;
+    for &c in line {
+        if !is_space(c) {
+            return indent;
+        } else if c == b' ' {
+            indent += 1;
+        } else if c == b'\t' {
+            indent += 8 - indent % 8;
+        }
+        if indent >= MAX_INDENT {
+            return MAX_INDENT;
+        }
+    }
+    -1
+}
+
+/// 在第 split 行之前切分时周围的缩进和空行情况
+struct SplitMeasurement {
+    end_of_file: bool,
+    indent: i32,
+    pre_blank: i32,
+    pre_indent: i32,
+    post_blank: i32,
+    post_indent: i32,
+}
+
+fn measure_split(lines: &[&[u8]], split: usize) -> SplitMeasurement {
+    let (end_of_file, indent) = match lines.get(split) {
+        Some(line) => (false, get_indent(line)),
+        None => (true, -1),
+    };
+
+    let mut pre_blank = 0;
+    let mut pre_indent = -1;
+    for line in lines[..split.min(lines.len())].iter().rev() {
+        pre_indent = get_indent(line);
+        if pre_indent != -1 {
+            break;
+        }
+        pre_blank += 1;
+        if pre_blank == MAX_BLANKS {
+            pre_indent = 0;
+            break;
+        }
+    }
+
+    let mut post_blank = 0;
+    let mut post_indent = -1;
+    for line in lines.iter().skip(split + 1) {
+        post_indent = get_indent(line);
+        if post_indent != -1 {
+            break;
+        }
+        post_blank += 1;
+        if post_blank == MAX_BLANKS {
+            post_indent = 0;
+            break;
+        }
+    }
+
+    SplitMeasurement {
+        end_of_file,
+        indent,
+        pre_blank,
+        pre_indent,
+        post_blank,
+        post_indent,
+    }
+}
+
+const START_OF_FILE_PENALTY: i32 = 1;
+const END_OF_FILE_PENALTY: i32 = 21;
+const TOTAL_BLANK_WEIGHT: i32 = -30;
+const POST_BLANK_WEIGHT: i32 = 6;
+const RELATIVE_INDENT_PENALTY: i32 = -4;
+const RELATIVE_INDENT_WITH_BLANK_PENALTY: i32 = 10;
+const RELATIVE_OUTDENT_PENALTY: i32 = 24;
+const RELATIVE_OUTDENT_WITH_BLANK_PENALTY: i32 = 17;
+const RELATIVE_DEDENT_PENALTY: i32 = 23;
+const RELATIVE_DEDENT_WITH_BLANK_PENALTY: i32 = 17;
+const INDENT_WEIGHT: i32 = 60;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct SplitScore {
+    effective_indent: i32,
+    penalty: i32,
+}
+
+impl SplitScore {
+    fn add(&mut self, m: &SplitMeasurement) {
+        if m.pre_indent == -1 && m.pre_blank == 0 {
+            self.penalty += START_OF_FILE_PENALTY;
+        }
+        if m.end_of_file {
+            self.penalty += END_OF_FILE_PENALTY;
+        }
+
+        // 切分处之后的空行数，包括紧跟的那一行
+        let post_blank = if m.indent == -1 { 1 + m.post_blank } else { 0 };
+        let total_blank = m.pre_blank + post_blank;
+        self.penalty += TOTAL_BLANK_WEIGHT * total_blank;
+        self.penalty += POST_BLANK_WEIGHT * post_blank;
+
+        let indent = if m.indent != -1 {
+            m.indent
+        } else {
+            m.post_indent
+        };
+        let any_blanks = total_blank != 0;
+        self.effective_indent += indent;
+
+        if indent == -1 || m.pre_indent == -1 {
+        } else if indent > m.pre_indent {
+            self.penalty += if any_blanks {
+                RELATIVE_INDENT_WITH_BLANK_PENALTY
+            } else {
+                RELATIVE_INDENT_PENALTY
+            };
+        } else if indent == m.pre_indent {
+        } else if m.post_indent != -1 && m.post_indent > indent {
+            // 下一行缩进更多，这一行可能是新块的开始
+            self.penalty += if any_blanks {
+                RELATIVE_OUTDENT_WITH_BLANK_PENALTY
+            } else {
+                RELATIVE_OUTDENT_PENALTY
+            };
+        } else {
+            // 多半是上一个块的结束
+            self.penalty += if any_blanks {
+                RELATIVE_DEDENT_WITH_BLANK_PENALTY
+            } else {
+                RELATIVE_DEDENT_PENALTY
+            };
+        }
+    }
+
+    /// 小于 0 表示 self 更好
+    fn cmp(&self, other: &SplitScore) -> i32 {
+        let cmp_indents = (self.effective_indent > other.effective_indent) as i32
+            - (self.effective_indent < other.effective_indent) as i32;
+        INDENT_WEIGHT * cmp_indents + (self.penalty - other.penalty)
+    }
+}
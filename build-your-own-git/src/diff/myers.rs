@@ -0,0 +1,439 @@
+//! Myers 差异算法，线性空间的分治实现，移植自 git 的 xdiff（xdiffi.c）
+
+use std::collections::HashMap;
+
+/// 开始使用启发式剪枝的编辑代价
+const HEUR_MIN_COST: isize = 256;
+const MAX_COST_MIN: isize = 256;
+/// 长度超过这个值的对角线才算一段“好的蛇形”
+const SNAKE_CNT: isize = 20;
+const K_HEUR: isize = 4;
+const MAX_EQLIMIT: usize = 1024;
+const SIMSCAN_WINDOW: usize = 100;
+const KPDIS_RUN: usize = 4;
+
+/// 比较两个行类别序列，把修改的行在 rchg1/rchg2 中标记为 true。
+/// minimal 为真时不做启发式剪枝
+pub(crate) fn diff(
+    a: &[usize],
+    b: &[usize],
+    minimal: bool,
+    rchg1: &mut [bool],
+    rchg2: &mut [bool],
+) {
+    let (n1, n2) = (a.len(), b.len());
+
+    // 去掉相同的开头和结尾
+    let lim = n1.min(n2);
+    let mut start = 0;
+    while start < lim && a[start] == b[start] {
+        start += 1;
+    }
+    let mut tail = 0;
+    while tail < lim - start && a[n1 - 1 - tail] == b[n2 - 1 - tail] {
+        tail += 1;
+    }
+
+    // 丢弃在另一侧不存在的行，以及夹在这类行中间、在另一侧出现很多次的行，
+    // 它们一定是修改，不用参与比较
+    let mut count1: HashMap<usize, usize> = HashMap::new();
+    let mut count2: HashMap<usize, usize> = HashMap::new();
+    for &class in a {
+        *count1.entry(class).or_default() += 1;
+    }
+    for &class in b {
+        *count2.entry(class).or_default() += 1;
+    }
+    let (ha1, rindex1) = cleanup(a, start, n1 - tail, &count2, rchg1);
+    let (ha2, rindex2) = cleanup(b, start, n2 - tail, &count1, rchg2);
+
+    let ndiags = ha1.len() + ha2.len() + 3;
+    let mut env = Env {
+        ha1: &ha1,
+        ha2: &ha2,
+        kvdf: vec![0; ndiags],
+        kvdb: vec![0; ndiags],
+        offset: ha2.len() as isize + 1,
+        mxcost: bogosqrt(ndiags).max(MAX_COST_MIN as usize) as isize,
+    };
+
+    // 分治：每次找到中间的蛇形把问题一分为二
+    let mut stack = vec![(0, ha1.len() as isize, 0, ha2.len() as isize, minimal)];
+    while let Some((mut off1, mut lim1, mut off2, mut lim2, need_min)) = stack.pop() {
+        while off1 < lim1 && off2 < lim2 && ha1[off1 as usize] == ha2[off2 as usize] {
+            off1 += 1;
+            off2 += 1;
+        }
+        while off1 < lim1 && off2 < lim2 && ha1[lim1 as usize - 1] == ha2[lim2 as usize - 1] {
+            lim1 -= 1;
+            lim2 -= 1;
+        }
+        if off1 == lim1 {
+            for i in off2..lim2 {
+                rchg2[rindex2[i as usize]] = true;
+            }
+        } else if off2 == lim2 {
+            for i in off1..lim1 {
+                rchg1[rindex1[i as usize]] = true;
+            }
+        } else {
+            let split = env.split(off1, lim1, off2, lim2, need_min);
+            stack.push((split.i1, lim1, split.i2, lim2, split.min_hi));
+            stack.push((off1, split.i1, off2, split.i2, split.min_lo));
+        }
+    }
+}
+
+/// 整数平方根的近似值
+fn bogosqrt(mut n: usize) -> usize {
+    let mut i = 1;
+    while n > 0 {
+        i <<= 1;
+        n >>= 2;
+    }
+    i
+}
+
+/// 返回保留下来参与比较的行类别及其原始行号，被丢弃的行直接标记为修改
+fn cleanup(
+    lines: &[usize],
+    start: usize,
+    end: usize,
+    other_count: &HashMap<usize, usize>,
+    rchg: &mut [bool],
+) -> (Vec<usize>, Vec<usize>) {
+    let mlim = bogosqrt(lines.len()).min(MAX_EQLIMIT);
+    // 0：另一侧没有；1：另一侧有；2：另一侧出现太多次
+    let mut dis = vec![0u8; lines.len()];
+    for i in start..end {
+        let nm = other_count.get(&lines[i]).copied().unwrap_or(0);
+        dis[i] = if nm == 0 {
+            0
+        } else if nm >= mlim {
+            2
+        } else {
+            1
+        };
+    }
+
+    let mut ha = Vec::new();
+    let mut rindex = Vec::new();
+    for i in start..end {
+        if dis[i] == 1 || (dis[i] == 2 && !clean_mmatch(&dis, i, start, end - 1)) {
+            ha.push(lines[i]);
+            rindex.push(i);
+        } else {
+            rchg[i] = true;
+        }
+    }
+    (ha, rindex)
+}
+
+/// 出现太多次的行两侧以没有匹配的行为主时也丢弃
+fn clean_mmatch(dis: &[u8], i: usize, start: usize, end: usize) -> bool {
+    let s = start.max(i.saturating_sub(SIMSCAN_WINDOW));
+    let e = end.min(i + SIMSCAN_WINDOW);
+
+    let (mut rdis0, mut rpdis0) = (0, 1);
+    let mut r = 1;
+    while i >= s + r {
+        match dis[i - r] {
+            0 => rdis0 += 1,
+            2 => rpdis0 += 1,
+            _ => break,
+        }
+        r += 1;
+    }
+    if rdis0 == 0 {
+        return false;
+    }
+    let (mut rdis1, mut rpdis1) = (0, 1);
+    let mut r = 1;
+    while i + r <= e {
+        match dis[i + r] {
+            0 => rdis1 += 1,
+            2 => rpdis1 += 1,
+            _ => break,
+        }
+        r += 1;
+    }
+    if rdis1 == 0 {
+        return false;
+    }
+    rdis1 += rdis0;
+    rpdis1 += rpdis0;
+    rpdis1 * KPDIS_RUN < rpdis1 + rdis1
+}
+
+struct Split {
+    i1: isize,
+    i2: isize,
+    min_lo: bool,
+    min_hi: bool,
+}
+
+struct Env<'a> {
+    ha1: &'a [usize],
+    ha2: &'a [usize],
+    /// 前向和后向搜索在每条对角线上到达的最远位置，下标加上 offset
+    kvdf: Vec<isize>,
+    kvdb: Vec<isize>,
+    offset: isize,
+    mxcost: isize,
+}
+
+impl Env<'_> {
+    fn f(&mut self, d: isize) -> &mut isize {
+        &mut self.kvdf[(d + self.offset) as usize]
+    }
+
+    fn b(&mut self, d: isize) -> &mut isize {
+        &mut self.kvdb[(d + self.offset) as usize]
+    }
+
+    fn eq(&self, i1: isize, i2: isize) -> bool {
+        self.ha1[i1 as usize] == self.ha2[i2 as usize]
+    }
+
+    /// 从两端同时搜索，找到中间的蛇形；代价过高时用启发式直接给出切分点
+    fn split(
+        &mut self,
+        off1: isize,
+        lim1: isize,
+        off2: isize,
+        lim2: isize,
+        need_min: bool,
+    ) -> Split {
+        let dmin = off1 - lim2;
+        let dmax = lim1 - off2;
+        let fmid = off1 - off2;
+        let bmid = lim1 - lim2;
+        let odd = (fmid - bmid) & 1 != 0;
+        let (mut fmin, mut fmax) = (fmid, fmid);
+        let (mut bmin, mut bmax) = (bmid, bmid);
+
+        *self.f(fmid) = off1;
+        *self.b(bmid) = lim1;
+
+        let mut ec = 1;
+        loop {
+            let mut got_snake = false;
+
+            // 对角线范围向两侧扩展一格，越界时反向收缩
+            if fmin > dmin {
+                fmin -= 1;
+                *self.f(fmin - 1) = -1;
+            } else {
+                fmin += 1;
+            }
+            if fmax < dmax {
+                fmax += 1;
+                *self.f(fmax + 1) = -1;
+            } else {
+                fmax -= 1;
+            }
+
+            let mut d = fmax;
+            while d >= fmin {
+                let mut i1 = if *self.f(d - 1) >= *self.f(d + 1) {
+                    *self.f(d - 1) + 1
+                } else {
+                    *self.f(d + 1)
+                };
+                let prev1 = i1;
+                let mut i2 = i1 - d;
+                while i1 < lim1 && i2 < lim2 && self.eq(i1, i2) {
+                    i1 += 1;
+                    i2 += 1;
+                }
+                if i1 - prev1 > SNAKE_CNT {
+                    got_snake = true;
+                }
+                *self.f(d) = i1;
+                if odd && bmin <= d && d <= bmax && *self.b(d) <= i1 {
+                    return Split {
+                        i1,
+                        i2,
+                        min_lo: true,
+                        min_hi: true,
+                    };
+                }
+                d -= 2;
+            }
+
+            if bmin > dmin {
+                bmin -= 1;
+                *self.b(bmin - 1) = isize::MAX;
+            } else {
+                bmin += 1;
+            }
+            if bmax < dmax {
+                bmax += 1;
+                *self.b(bmax + 1) = isize::MAX;
+            } else {
+                bmax -= 1;
+            }
+
+            let mut d = bmax;
+            while d >= bmin {
+                let mut i1 = if *self.b(d - 1) < *self.b(d + 1) {
+                    *self.b(d - 1)
+                } else {
+                    *self.b(d + 1) - 1
+                };
+                let prev1 = i1;
+                let mut i2 = i1 - d;
+                while i1 > off1 && i2 > off2 && self.eq(i1 - 1, i2 - 1) {
+                    i1 -= 1;
+                    i2 -= 1;
+                }
+                if prev1 - i1 > SNAKE_CNT {
+                    got_snake = true;
+                }
+                *self.b(d) = i1;
+                if !odd && fmin <= d && d <= fmax && i1 <= *self.f(d) {
+                    return Split {
+                        i1,
+                        i2,
+                        min_lo: true,
+                        min_hi: true,
+                    };
+                }
+                d -= 2;
+            }
+
+            if need_min {
+                ec += 1;
+                continue;
+            }
+
+            // 代价超过阈值且出现过长蛇形时，挑选离起点足够远、
+            // 又以足够长的蛇形结尾的对角线作为切分点
+            if got_snake && ec > HEUR_MIN_COST {
+                let mut best = 0;
+                let mut found = None;
+                let mut d = fmax;
+                while d >= fmin {
+                    let dd = (d - fmid).abs();
+                    let i1 = *self.f(d);
+                    let i2 = i1 - d;
+                    let v = (i1 - off1) + (i2 - off2) - dd;
+                    if v > K_HEUR * ec
+                        && v > best
+                        && off1 + SNAKE_CNT <= i1
+                        && i1 < lim1
+                        && off2 + SNAKE_CNT <= i2
+                        && i2 < lim2
+                    {
+                        let mut k = 1;
+                        while self.eq(i1 - k, i2 - k) {
+                            if k == SNAKE_CNT {
+                                best = v;
+                                found = Some((i1, i2));
+                                break;
+                            }
+                            k += 1;
+                        }
+                    }
+                    d -= 2;
+                }
+                if let Some((i1, i2)) = found {
+                    return Split {
+                        i1,
+                        i2,
+                        min_lo: true,
+                        min_hi: false,
+                    };
+                }
+
+                let mut best = 0;
+                let mut found = None;
+                let mut d = bmax;
+                while d >= bmin {
+                    let dd = (d - bmid).abs();
+                    let i1 = *self.b(d);
+                    let i2 = i1 - d;
+                    let v = (lim1 - i1) + (lim2 - i2) - dd;
+                    if v > K_HEUR * ec
+                        && v > best
+                        && off1 < i1
+                        && i1 <= lim1 - SNAKE_CNT
+                        && off2 < i2
+                        && i2 <= lim2 - SNAKE_CNT
+                    {
+                        let mut k = 0;
+                        while self.eq(i1 + k, i2 + k) {
+                            if k == SNAKE_CNT - 1 {
+                                best = v;
+                                found = Some((i1, i2));
+                                break;
+                            }
+                            k += 1;
+                        }
+                    }
+                    d -= 2;
+                }
+                if let Some((i1, i2)) = found {
+                    return Split {
+                        i1,
+                        i2,
+                        min_lo: false,
+                        min_hi: true,
+                    };
+                }
+            }
+
+            // 代价太高，取两个方向中走得最远的路径
+            if ec >= self.mxcost {
+                let (mut fbest, mut fbest1) = (-1, -1);
+                let mut d = fmax;
+                while d >= fmin {
+                    let mut i1 = (*self.f(d)).min(lim1);
+                    let mut i2 = i1 - d;
+                    if lim2 < i2 {
+                        i1 = lim2 + d;
+                        i2 = lim2;
+                    }
+                    if fbest < i1 + i2 {
+                        fbest = i1 + i2;
+                        fbest1 = i1;
+                    }
+                    d -= 2;
+                }
+
+                let (mut bbest, mut bbest1) = (isize::MAX, isize::MAX);
+                let mut d = bmax;
+                while d >= bmin {
+                    let mut i1 = off1.max(*self.b(d));
+                    let mut i2 = i1 - d;
+                    if i2 < off2 {
+                        i1 = off2 + d;
+                        i2 = off2;
+                    }
+                    if i1 + i2 < bbest {
+                        bbest = i1 + i2;
+                        bbest1 = i1;
+                    }
+                    d -= 2;
+                }
+
+                return if (lim1 + lim2) - bbest < fbest - (off1 + off2) {
+                    Split {
+                        i1: fbest1,
+                        i2: fbest - fbest1,
+                        min_lo: true,
+                        min_hi: false,
+                    }
+                } else {
+                    Split {
+                        i1: bbest1,
+                        i2: bbest - bbest1,
+                        min_lo: false,
+                        min_hi: true,
+                    }
+                };
+            }
+            ec += 1;
+        }
+    }
+}
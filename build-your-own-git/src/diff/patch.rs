@@ -0,0 +1,139 @@
+use std::io::Write;
+
+use crate::{
+    diff::{
+        Change, Side, Status,
+        lines::{LineDiffOptions, diff_lines, split_lines},
+        quote_path,
+        unified::{UnifiedOptions, write_hunks},
+    },
+    objects::find_unique_abbrev,
+};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct PatchOptions {
+    pub(crate) unified: UnifiedOptions,
+    pub(crate) line: LineDiffOptions,
+}
+
+/// 判断二进制时检查的字节数
+const FIRST_FEW_BYTES: usize = 8000;
+
+/// 输出一个文件的 `diff --git` 补丁，old/new 是两侧的内容（不存在的一侧为空）
+pub(crate) fn write_patch(
+    out: &mut impl Write,
+    change: &Change,
+    old: &[u8],
+    new: &[u8],
+    options: PatchOptions,
+) -> std::io::Result<()> {
+    // 普通文件和符号链接之间的变化拆成一次删除和一次新增
+    if change.status == Status::TypeChanged {
+        let deleted = Change {
+            status: Status::Deleted,
+            new: None,
+            ..change.clone()
+        };
+        let added = Change {
+            status: Status::Added,
+            old: None,
+            ..change.clone()
+        };
+        write_patch(out, &deleted, old, b"", options)?;
+        return write_patch(out, &added, b"", new, options);
+    }
+
+    let a_path = quote_path(&format!("a/{}", change.old_path()));
+    let b_path = quote_path(&format!("b/{}", change.path));
+    let mut header = format!("diff --git {a_path} {b_path}\n");
+    let mut must_show_header = false;
+    match (change.old, change.new) {
+        (None, Some(new)) => {
+            header.push_str(&format!("new file mode {}\n", mode_str(new)));
+            must_show_header = true;
+        }
+        (Some(old), None) => {
+            header.push_str(&format!("deleted file mode {}\n", mode_str(old)));
+            must_show_header = true;
+        }
+        (Some(old), Some(new)) if old.mode != new.mode => {
+            header.push_str(&format!("old mode {}\n", mode_str(old)));
+            header.push_str(&format!("new mode {}\n", mode_str(new)));
+            must_show_header = true;
+        }
+        _ => {}
+    }
+    if let (Status::Renamed(score) | Status::Copied(score), Some(from)) =
+        (change.status, &change.from)
+    {
+        let verb = if matches!(change.status, Status::Renamed(_)) {
+            "rename"
+        } else {
+            "copy"
+        };
+        header.push_str(&format!("similarity index {score}%\n"));
+        header.push_str(&format!("{verb} from {}\n", quote_path(from)));
+        header.push_str(&format!("{verb} to {}\n", quote_path(&change.path)));
+        must_show_header = true;
+    }
+    let old_hash = change.old.map_or([0; 20], |side| side.hash);
+    let new_hash = change.new.map_or([0; 20], |side| side.hash);
+    if old_hash != new_hash {
+        header.push_str(&format!(
+            "index {}..{}",
+            find_unique_abbrev(&hex::encode(old_hash), 7),
+            find_unique_abbrev(&hex::encode(new_hash), 7)
+        ));
+        if let (Some(old), Some(new)) = (change.old, change.new) {
+            if old.mode == new.mode {
+                header.push_str(&format!(" {}", mode_str(old)));
+            }
+        }
+        header.push('\n');
+    }
+
+    let old_label = if change.old.is_some() {
+        a_path
+    } else {
+        "/dev/null".to_string()
+    };
+    let new_label = if change.new.is_some() {
+        b_path
+    } else {
+        "/dev/null".to_string()
+    };
+
+    if is_binary(old) || is_binary(new) {
+        out.write_all(header.as_bytes())?;
+        if old_hash != new_hash {
+            writeln!(out, "Binary files {old_label} and {new_label} differ")?;
+        }
+        return Ok(());
+    }
+
+    let old_lines = split_lines(old);
+    let new_lines = split_lines(new);
+    let edits = diff_lines(&old_lines, &new_lines, options.line);
+    // 忽略空白后没有差异时只在有模式变化等信息时输出头部
+    if edits.is_empty() {
+        if must_show_header {
+            out.write_all(header.as_bytes())?;
+        }
+        return Ok(());
+    }
+    out.write_all(header.as_bytes())?;
+    // 路径含空格时在末尾加制表符，方便 patch 工具识别
+    let tab = |label: &str| if label.contains(' ') { "\t" } else { "" };
+    writeln!(out, "--- {old_label}{}", tab(&old_label))?;
+    writeln!(out, "+++ {new_label}{}", tab(&new_label))?;
+    write_hunks(out, &old_lines, &new_lines, &edits, options.unified)
+}
+
+fn mode_str(side: Side) -> String {
+    side.mode.to_padded()
+}
+
+/// 前 8000 个字节中含有 NUL 的视为二进制
+pub(crate) fn is_binary(data: &[u8]) -> bool {
+    data[..data.len().min(FIRST_FEW_BYTES)].contains(&0)
+}
@@ -0,0 +1,51 @@
+use sha1::{Digest, Sha1};
+
+use crate::{
+    diff::{
+        diff_trees,
+        lines::is_space,
+        patch::{PatchOptions, write_patch},
+        side_content,
+    },
+    objects::commit::Commit,
+};
+
+/// 提交相对父提交的 patch id：补丁去掉 `index` 行、hunk 头和所有空白后的 SHA-1，
+/// 行号或上下文变化不影响结果，用来判断两个提交是否做了相同的修改。合并提交没有 patch id
+pub(crate) async fn patch_id(commit: &Commit) -> anyhow::Result<Option<[u8; 20]>> {
+    let Some(patch) = commit_patch(commit).await? else {
+        return Ok(None);
+    };
+    let mut hasher = Sha1::new();
+    for line in patch.split(|&b| b == b'\n') {
+        if line.starts_with(b"index ") || line.starts_with(b"@@") {
+            continue;
+        }
+        let stripped: Vec<u8> = line.iter().copied().filter(|&b| !is_space(b)).collect();
+        hasher.update(&stripped);
+    }
+    Ok(Some(hasher.finalize().into()))
+}
+
+/// 提交相对父提交（根提交相对空树）的补丁，合并提交返回 None
+pub(crate) async fn commit_patch(commit: &Commit) -> anyhow::Result<Option<Vec<u8>>> {
+    let parent_tree = match commit.parents.as_slice() {
+        [] => None,
+        [parent] => Some(Commit::read(parent).await?.tree),
+        _ => return Ok(None),
+    };
+    let changes = diff_trees(parent_tree.as_ref(), Some(&commit.tree), true).await?;
+    let mut patch = Vec::new();
+    for change in &changes {
+        let old = match change.old {
+            Some(side) => side_content(side).await?,
+            None => Vec::new(),
+        };
+        let new = match change.new {
+            Some(side) => side_content(side).await?,
+            None => Vec::new(),
+        };
+        write_patch(&mut patch, change, &old, &new, PatchOptions::default())?;
+    }
+    Ok(Some(patch))
+}
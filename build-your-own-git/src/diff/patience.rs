@@ -0,0 +1,163 @@
+//! patience 差异算法：以两侧都只出现一次的行为锚点递归切分，
+//! 找不到锚点时退回 Myers，移植自 git 的 xdiff（xpatience.c）
+
+use std::collections::HashMap;
+
+use crate::diff::myers;
+
+/// 在某一侧出现多次
+const NON_UNIQUE: usize = usize::MAX;
+
+pub(crate) fn diff(a: &[usize], b: &[usize], rchg1: &mut [bool], rchg2: &mut [bool]) {
+    let mut ctx = Patience { a, b, rchg1, rchg2 };
+    ctx.diff(1, a.len(), 1, b.len());
+}
+
+/// 候选锚点：在旧文件中第一次出现的行号，以及在新文件中唯一出现的行号（从 1 开始）
+struct Entry {
+    line1: usize,
+    line2: usize,
+}
+
+struct Patience<'a> {
+    a: &'a [usize],
+    b: &'a [usize],
+    rchg1: &'a mut [bool],
+    rchg2: &'a mut [bool],
+}
+
+impl Patience<'_> {
+    fn matches(&self, line1: usize, line2: usize) -> bool {
+        self.a[line1 - 1] == self.b[line2 - 1]
+    }
+
+    fn diff(&mut self, line1: usize, count1: usize, line2: usize, count2: usize) {
+        if count1 == 0 {
+            self.rchg2[line2 - 1..line2 - 1 + count2].fill(true);
+            return;
+        }
+        if count2 == 0 {
+            self.rchg1[line1 - 1..line1 - 1 + count1].fill(true);
+            return;
+        }
+
+        // 按在旧文件中第一次出现的顺序收集各类行
+        let mut index: HashMap<usize, usize> = HashMap::new();
+        let mut entries: Vec<Entry> = Vec::new();
+        for line in line1..line1 + count1 {
+            match index.get(&self.a[line - 1]) {
+                Some(&i) => entries[i].line2 = NON_UNIQUE,
+                None => {
+                    index.insert(self.a[line - 1], entries.len());
+                    entries.push(Entry {
+                        line1: line,
+                        line2: 0,
+                    });
+                }
+            }
+        }
+        let mut has_matches = false;
+        for line in line2..line2 + count2 {
+            if let Some(&i) = index.get(&self.b[line - 1]) {
+                has_matches = true;
+                let entry = &mut entries[i];
+                entry.line2 = if entry.line2 == 0 { line } else { NON_UNIQUE };
+            }
+        }
+
+        if !has_matches {
+            self.rchg1[line1 - 1..line1 - 1 + count1].fill(true);
+            self.rchg2[line2 - 1..line2 - 1 + count2].fill(true);
+            return;
+        }
+
+        let sequence = longest_common_sequence(&entries);
+        if sequence.is_empty() {
+            // 没有唯一的公共行，退回 Myers
+            myers::diff(
+                &self.a[line1 - 1..line1 - 1 + count1],
+                &self.b[line2 - 1..line2 - 1 + count2],
+                false,
+                &mut self.rchg1[line1 - 1..line1 - 1 + count1],
+                &mut self.rchg2[line2 - 1..line2 - 1 + count2],
+            );
+        } else {
+            self.walk_common_sequence(&sequence, line1, count1, line2, count2);
+        }
+    }
+
+    /// 依次处理锚点之间的区间：先向两侧扩展相同的行，剩下的部分递归比较
+    fn walk_common_sequence(
+        &mut self,
+        sequence: &[(usize, usize)],
+        mut line1: usize,
+        count1: usize,
+        mut line2: usize,
+        count2: usize,
+    ) {
+        let (end1, end2) = (line1 + count1, line2 + count2);
+        let mut k = 0;
+        loop {
+            let (mut next1, mut next2) = match sequence.get(k) {
+                Some(&(next1, next2)) => (next1, next2),
+                None => (end1, end2),
+            };
+            if k < sequence.len() {
+                while next1 > line1 && next2 > line2 && self.matches(next1 - 1, next2 - 1) {
+                    next1 -= 1;
+                    next2 -= 1;
+                }
+            }
+            while line1 < next1 && line2 < next2 && self.matches(line1, line2) {
+                line1 += 1;
+                line2 += 1;
+            }
+
+            if next1 > line1 || next2 > line2 {
+                self.diff(line1, next1 - line1, line2, next2 - line2);
+            }
+            if k >= sequence.len() {
+                return;
+            }
+
+            // 跳过连续的锚点
+            while k + 1 < sequence.len()
+                && sequence[k + 1].0 == sequence[k].0 + 1
+                && sequence[k + 1].1 == sequence[k].1 + 1
+            {
+                k += 1;
+            }
+            line1 = sequence[k].0 + 1;
+            line2 = sequence[k].1 + 1;
+            k += 1;
+        }
+    }
+}
+
+/// 在两侧都唯一的行中，按旧文件顺序求新文件行号的最长递增子序列
+fn longest_common_sequence(entries: &[Entry]) -> Vec<(usize, usize)> {
+    // sequence[i]：长度为 i + 1 的子序列中结尾行号最小的那个的最后一项
+    let mut sequence: Vec<usize> = Vec::new();
+    let mut previous: Vec<Option<usize>> = vec![None; entries.len()];
+    for (n, entry) in entries.iter().enumerate() {
+        if entry.line2 == 0 || entry.line2 == NON_UNIQUE {
+            continue;
+        }
+        let i = sequence.partition_point(|&s| entries[s].line2 <= entry.line2);
+        previous[n] = i.checked_sub(1).map(|i| sequence[i]);
+        if i == sequence.len() {
+            sequence.push(n);
+        } else {
+            sequence[i] = n;
+        }
+    }
+
+    let mut result = Vec::new();
+    let mut cur = sequence.last().copied();
+    while let Some(n) = cur {
+        result.push((entries[n].line1, entries[n].line2));
+        cur = previous[n];
+    }
+    result.reverse();
+    result
+}
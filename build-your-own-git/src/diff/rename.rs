@@ -0,0 +1,604 @@
+//! 重命名和复制检测：先按对象哈希找完全相同的文件，再用内容指纹估计相似度，
+//! 移植自 git 的 diffcore-rename.c 和 diffcore-delta.c
+
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap, HashSet},
+};
+
+use crate::{
+    diff::{Change, Side, Status, patch::is_binary, read_worktree_file},
+    objects::{Mode, read_object},
+};
+
+/// 相似度满分，`-M50%` 对应 MAX_SCORE / 2
+pub(crate) const MAX_SCORE: u32 = 60000;
+const DEFAULT_RENAME_SCORE: u32 = 30000;
+/// 每个目标文件保留的最佳候选来源数
+const NUM_CANDIDATE_PER_DST: usize = 4;
+/// 来源数乘目标数超过它的平方时放弃按内容比较
+const RENAME_LIMIT: usize = 1000;
+/// 内容相同的候选来源最多检查的个数
+const MAX_IDENTICAL: usize = 100;
+/// 内容指纹的取模基数
+const HASHBASE: u32 = 107927;
+
+/// 检测的范围
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Detect {
+    Off,
+    Renames,
+    /// 同时把修改过的文件作为复制的来源
+    Copies,
+    /// 所有未修改的文件也作为复制的来源
+    CopiesHarder,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RenameOptions {
+    pub(crate) detect: Detect,
+    /// 低于这个相似度的文件对不算重命名，取值 0..=MAX_SCORE
+    pub(crate) min_score: u32,
+}
+
+impl Default for RenameOptions {
+    fn default() -> Self {
+        RenameOptions {
+            detect: Detect::Off,
+            min_score: DEFAULT_RENAME_SCORE,
+        }
+    }
+}
+
+impl RenameOptions {
+    /// 默认检测重命名的命令（diff、log、status）使用的选项
+    pub(crate) fn renames() -> Self {
+        RenameOptions {
+            detect: Detect::Renames,
+            ..RenameOptions::default()
+        }
+    }
+
+    /// 由命令行的 `-M[<n>]`、`-C[<n>]`（可重复）和 `--find-copies-harder` 组合出选项，
+    /// base 是命令默认的检测范围
+    pub(crate) fn from_args(
+        base: RenameOptions,
+        find_renames: Option<&str>,
+        find_copies: &[String],
+        find_copies_harder: bool,
+        no_renames: bool,
+    ) -> anyhow::Result<Self> {
+        let mut options = base;
+        if let Some(score) = find_renames {
+            options.detect = Detect::Renames;
+            if !score.is_empty() {
+                options.min_score = parse_score(score, "-M")?;
+            }
+        }
+        for score in find_copies {
+            // 第二个 -C 等同于 --find-copies-harder
+            options.detect = if options.detect == Detect::Copies {
+                Detect::CopiesHarder
+            } else {
+                Detect::Copies
+            };
+            if !score.is_empty() {
+                options.min_score = parse_score(score, "-C")?;
+            }
+        }
+        if find_copies_harder {
+            options.detect = Detect::CopiesHarder;
+        }
+        if no_renames {
+            options.detect = Detect::Off;
+        }
+        Ok(options)
+    }
+}
+
+/// 解析 `50`、`50%`、`.5` 这样的相似度：不带 % 的整数表示小数点后的数字
+fn parse_score(arg: &str, option: &str) -> anyhow::Result<u32> {
+    let (mut num, mut scale) = (0u64, 1u64);
+    let mut dot = false;
+    let mut rest = arg;
+    while let Some(c) = rest.chars().next() {
+        match c {
+            '.' if !dot => {
+                scale = 1;
+                dot = true;
+            }
+            '%' => {
+                scale = if dot { scale * 100 } else { 100 };
+                rest = &rest[1..];
+                break;
+            }
+            '0'..='9' => {
+                if scale < 100000 {
+                    scale *= 10;
+                    num = num * 10 + u64::from(c as u8 - b'0');
+                }
+            }
+            _ => break,
+        }
+        rest = &rest[1..];
+    }
+    anyhow::ensure!(rest.is_empty(), "invalid argument to {option}: {arg}");
+    Ok(if num >= scale {
+        MAX_SCORE
+    } else {
+        (u64::from(MAX_SCORE) * num / scale) as u32
+    })
+}
+
+/// 在变更列表中检测重命名和复制：新增的文件如果与删除（复制时还包括修改）的文件
+/// 足够相似，就合并成一条重命名或复制记录，被重命名的删除记录不再输出。
+/// unchanged 是没有变化的文件，只在 CopiesHarder 时作为复制来源；
+/// worktree 为真时新文件的内容从工作区读取
+pub(crate) async fn detect_renames(
+    changes: Vec<Change>,
+    unchanged: &BTreeMap<String, Side>,
+    options: RenameOptions,
+    worktree: bool,
+) -> anyhow::Result<Vec<Change>> {
+    if options.detect == Detect::Off {
+        return Ok(changes);
+    }
+    let copies = options.detect != Detect::Renames;
+
+    let mut renamer = Renamer {
+        sources: Vec::new(),
+        targets: Vec::new(),
+        worktree,
+    };
+    for (n, change) in changes.iter().enumerate() {
+        match (change.status, change.old, change.new) {
+            (Status::Added, None, Some(side)) => renamer.targets.push(Target {
+                change: n,
+                path: change.path.clone(),
+                side,
+                found: None,
+                content: None,
+            }),
+            (Status::Deleted, Some(side), None) => renamer.sources.push(Source {
+                change: Some(n),
+                path: change.path.clone(),
+                side,
+                used: 0,
+                unmodified: false,
+                content: None,
+            }),
+            // 仍然存在的文件作为复制来源时先记一次使用，这样它永远不会被当作重命名
+            (_, Some(side), Some(_)) if copies => renamer.sources.push(Source {
+                change: Some(n),
+                path: change.path.clone(),
+                side,
+                used: 1,
+                unmodified: false,
+                content: None,
+            }),
+            _ => {}
+        }
+    }
+    if options.detect == Detect::CopiesHarder {
+        for (path, side) in unchanged {
+            renamer.sources.push(Source {
+                change: None,
+                path: path.clone(),
+                side: *side,
+                used: 1,
+                unmodified: true,
+                content: None,
+            });
+        }
+        renamer.sources.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+
+    if !renamer.targets.is_empty() && !renamer.sources.is_empty() {
+        renamer.run(options, copies).await?;
+    }
+    Ok(renamer.apply(changes))
+}
+
+/// 来源或目标文件的内容指纹
+struct Content {
+    size: u64,
+    /// 按哈希值排序的 (片段哈希, 字节数)
+    spans: Vec<(u32, u64)>,
+}
+
+struct Source {
+    /// 对应的删除或修改记录，未修改的文件为 None
+    change: Option<usize>,
+    path: String,
+    side: Side,
+    /// 被多少条重命名或复制记录使用
+    used: u32,
+    unmodified: bool,
+    content: Option<Content>,
+}
+
+struct Target {
+    change: usize,
+    path: String,
+    side: Side,
+    /// 找到的来源和相似度
+    found: Option<(usize, u32)>,
+    content: Option<Content>,
+}
+
+/// 相似度矩阵中的一个候选
+#[derive(Debug, Clone, Copy)]
+struct Candidate {
+    src: usize,
+    dst: usize,
+    score: u32,
+    name_score: bool,
+}
+
+struct Renamer {
+    sources: Vec<Source>,
+    targets: Vec<Target>,
+    worktree: bool,
+}
+
+impl Renamer {
+    async fn run(&mut self, options: RenameOptions, copies: bool) -> anyhow::Result<()> {
+        let mut active: Vec<usize> = (0..self.sources.len()).collect();
+        self.find_exact_renames(&active, copies);
+        if options.min_score == MAX_SCORE {
+            return Ok(());
+        }
+
+        if !copies {
+            // 已经找到去向的删除不再参与匹配，剩下的先按同名文件配对
+            active.retain(|&src| self.sources[src].used == 0);
+            let min_basename_score = options.min_score + (MAX_SCORE - options.min_score) / 2;
+            self.find_basename_matches(&active, min_basename_score)
+                .await?;
+            active.retain(|&src| self.sources[src].used == 0);
+        }
+
+        let remaining: Vec<usize> = (0..self.targets.len())
+            .filter(|&dst| self.targets[dst].found.is_none())
+            .collect();
+        if remaining.is_empty() || active.is_empty() {
+            return Ok(());
+        }
+
+        let mut skip_unmodified = false;
+        let limit = RENAME_LIMIT * RENAME_LIMIT;
+        if remaining.len() * active.len() > limit {
+            let needed = remaining.len().max(active.len());
+            let modified = active
+                .iter()
+                .filter(|&&src| !self.sources[src].unmodified)
+                .count();
+            if options.detect == Detect::CopiesHarder && remaining.len() * modified <= limit {
+                eprintln!("warning: only found copies from modified paths due to too many files.");
+                skip_unmodified = true;
+            } else {
+                eprintln!(
+                    "warning: exhaustive rename detection was skipped due to too many files."
+                );
+                eprintln!(
+                    "warning: you may want to set your diff.renameLimit variable to at least {needed} and retry the command."
+                );
+                return Ok(());
+            }
+        }
+
+        let mut matrix: Vec<Option<Candidate>> = Vec::new();
+        for &dst in &remaining {
+            let mut best = [None; NUM_CANDIDATE_PER_DST];
+            for &src in &active {
+                if skip_unmodified && self.sources[src].unmodified {
+                    continue;
+                }
+                let candidate = Candidate {
+                    src,
+                    dst,
+                    score: self
+                        .estimate_similarity(src, dst, options.min_score)
+                        .await?,
+                    name_score: basename_same(&self.sources[src].path, &self.targets[dst].path),
+                };
+                record_if_better(&mut best, candidate);
+            }
+            matrix.extend(best);
+        }
+        // 从最相似的文件对开始分配
+        matrix.sort_by(score_compare);
+        self.find_renames(&matrix, options.min_score, false);
+        if copies {
+            self.find_renames(&matrix, options.min_score, true);
+        }
+        Ok(())
+    }
+
+    fn record(&mut self, dst: usize, src: usize, score: u32) {
+        self.targets[dst].found = Some((src, score));
+        self.sources[src].used += 1;
+    }
+
+    /// 对象哈希相同的文件对直接记为满分，优先选还没用过且文件名相同的来源
+    fn find_exact_renames(&mut self, active: &[usize], copies: bool) {
+        let mut by_hash: HashMap<[u8; 20], Vec<usize>> = HashMap::new();
+        for &src in active {
+            by_hash
+                .entry(self.sources[src].side.hash)
+                .or_default()
+                .push(src);
+        }
+        for dst in 0..self.targets.len() {
+            let target = &self.targets[dst];
+            let Some(candidates) = by_hash.get(&target.side.hash) else {
+                continue;
+            };
+            let mut best = None;
+            let mut best_score = -1;
+            let mut budget = MAX_IDENTICAL;
+            for &src in candidates {
+                let source = &self.sources[src];
+                // 符号链接等非普通文件只和模式相同的文件配对
+                if (!is_regular(source.side.mode) || !is_regular(target.side.mode))
+                    && source.side.mode != target.side.mode
+                {
+                    continue;
+                }
+                if source.used > 0 && !copies {
+                    continue;
+                }
+                let score = i32::from(source.used == 0)
+                    + i32::from(basename_same(&source.path, &target.path));
+                if score > best_score {
+                    best = Some(src);
+                    best_score = score;
+                    if score == 2 {
+                        break;
+                    }
+                }
+                budget -= 1;
+                if budget == 0 {
+                    break;
+                }
+            }
+            if let Some(src) = best {
+                self.record(dst, src, MAX_SCORE);
+            }
+        }
+    }
+
+    /// 文件名在剩下的来源和目标中都唯一时，只比较同名的那一对
+    async fn find_basename_matches(
+        &mut self,
+        active: &[usize],
+        min_score: u32,
+    ) -> anyhow::Result<()> {
+        let sources = unique_basenames(
+            active
+                .iter()
+                .map(|&src| (basename(&self.sources[src].path), src)),
+        );
+        let targets = unique_basenames(
+            (0..self.targets.len())
+                .filter(|&dst| self.targets[dst].found.is_none())
+                .map(|dst| (basename(&self.targets[dst].path), dst)),
+        );
+
+        let mut pairs = Vec::new();
+        for &src in active {
+            let name = basename(&self.sources[src].path);
+            if let (Some(Some(_)), Some(&Some(dst))) = (sources.get(name), targets.get(name)) {
+                pairs.push((src, dst));
+            }
+        }
+        for (src, dst) in pairs {
+            if self.targets[dst].found.is_some() {
+                continue;
+            }
+            let score = self.estimate_similarity(src, dst, min_score).await?;
+            if score >= min_score {
+                self.record(dst, src, score);
+            }
+        }
+        Ok(())
+    }
+
+    /// 按相似度从高到低分配，每个目标只取一个来源；不找复制时每个来源只用一次
+    fn find_renames(&mut self, matrix: &[Option<Candidate>], min_score: u32, copies: bool) {
+        for candidate in matrix {
+            let Some(candidate) = candidate.filter(|c| c.score >= min_score) else {
+                break;
+            };
+            if self.targets[candidate.dst].found.is_some() {
+                continue;
+            }
+            if !copies && self.sources[candidate.src].used > 0 {
+                continue;
+            }
+            self.record(candidate.dst, candidate.src, candidate.score);
+        }
+    }
+
+    /// 目标文件的内容有多少来自来源文件，按 0..=MAX_SCORE 计分。
+    /// 只比较普通文件，大小相差太多的直接记 0 分
+    async fn estimate_similarity(
+        &mut self,
+        src: usize,
+        dst: usize,
+        min_score: u32,
+    ) -> anyhow::Result<u32> {
+        if !is_regular(self.sources[src].side.mode) || !is_regular(self.targets[dst].side.mode) {
+            return Ok(0);
+        }
+        if self.sources[src].content.is_none() {
+            let source = &self.sources[src];
+            let data = read_object(&hex::encode(source.side.hash)).await?.1;
+            self.sources[src].content = Some(fingerprint(&data));
+        }
+        if self.targets[dst].content.is_none() {
+            let target = &self.targets[dst];
+            let data = if self.worktree {
+                read_worktree_file(&target.path)?
+            } else {
+                read_object(&hex::encode(target.side.hash)).await?.1
+            };
+            self.targets[dst].content = Some(fingerprint(&data));
+        }
+        let one = self.sources[src].content.as_ref().expect("loaded");
+        let two = self.targets[dst].content.as_ref().expect("loaded");
+
+        let max_size = one.size.max(two.size);
+        let delta_size = max_size - one.size.min(two.size);
+        if max_size * u64::from(MAX_SCORE - min_score) < delta_size * u64::from(MAX_SCORE) {
+            return Ok(0);
+        }
+        if two.size == 0 {
+            return Ok(0);
+        }
+        Ok((copied_bytes(one, two) * u64::from(MAX_SCORE) / max_size) as u32)
+    }
+
+    /// 生成最终的变更列表：新增记录换成重命名或复制记录，被重命名的删除记录去掉。
+    /// 同一个来源被多次使用时，只有最后一次算重命名，前面的都是复制
+    fn apply(mut self, changes: Vec<Change>) -> Vec<Change> {
+        let mut renamed_to: HashMap<usize, usize> = HashMap::new();
+        for (dst, target) in self.targets.iter().enumerate() {
+            if target.found.is_some() {
+                renamed_to.insert(target.change, dst);
+            }
+        }
+        // 被用作重命名来源的删除记录不再输出
+        let moved: HashSet<usize> = self
+            .sources
+            .iter()
+            .filter(|source| source.used > 0)
+            .filter_map(|source| source.change)
+            .collect();
+
+        let mut result = Vec::with_capacity(changes.len());
+        for (n, change) in changes.into_iter().enumerate() {
+            if let Some(&dst) = renamed_to.get(&n) {
+                let (src, score) = self.targets[dst].found.expect("found");
+                let source = &mut self.sources[src];
+                source.used -= 1;
+                let similarity = score * 100 / MAX_SCORE;
+                result.push(Change {
+                    status: if source.used > 0 {
+                        Status::Copied(similarity)
+                    } else {
+                        Status::Renamed(similarity)
+                    },
+                    path: change.path,
+                    from: Some(source.path.clone()),
+                    old: Some(source.side),
+                    new: change.new,
+                });
+                continue;
+            }
+            if change.status != Status::Deleted || !moved.contains(&n) {
+                result.push(change);
+            }
+        }
+        result
+    }
+}
+
+fn is_regular(mode: Mode) -> bool {
+    matches!(mode, Mode::File | Mode::Executable)
+}
+
+fn basename(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// 文件名到序号的映射，重复出现的文件名映射到 None
+fn unique_basenames<'a>(
+    names: impl Iterator<Item = (&'a str, usize)>,
+) -> HashMap<&'a str, Option<usize>> {
+    let mut map: HashMap<&str, Option<usize>> = HashMap::new();
+    for (name, n) in names {
+        map.entry(name)
+            .and_modify(|slot| *slot = None)
+            .or_insert(Some(n));
+    }
+    map
+}
+
+fn basename_same(a: &str, b: &str) -> bool {
+    basename(a) == basename(b)
+}
+
+/// 排序规则：空位排在最后，相似度高的在前，相似度相同时文件名相同的在前
+fn score_compare(a: &Option<Candidate>, b: &Option<Candidate>) -> Ordering {
+    match (a, b) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(a), Some(b)) if a.score == b.score => b.name_score.cmp(&a.name_score),
+        (Some(a), Some(b)) => b.score.cmp(&a.score),
+    }
+}
+
+/// 新候选比当前最差的一个好时替换它
+fn record_if_better(best: &mut [Option<Candidate>], candidate: Candidate) {
+    let mut worst = 0;
+    for i in 1..best.len() {
+        if score_compare(&best[i], &best[worst]) == Ordering::Greater {
+            worst = i;
+        }
+    }
+    if score_compare(&best[worst], &Some(candidate)) == Ordering::Greater {
+        best[worst] = Some(candidate);
+    }
+}
+
+/// 把内容切成以换行结尾或最长 64 字节的片段，统计每种片段哈希的总字节数。
+/// 文本文件忽略 CRLF 中的 CR
+fn fingerprint(data: &[u8]) -> Content {
+    let is_text = !is_binary(data);
+    let mut counts: HashMap<u32, u64> = HashMap::new();
+    let (mut accum1, mut accum2) = (0u32, 0u32);
+    let mut n = 0u64;
+    for (i, &c) in data.iter().enumerate() {
+        if is_text && c == b'\r' && data.get(i + 1) == Some(&b'\n') {
+            continue;
+        }
+        let old_1 = accum1;
+        accum1 = (accum1 << 7) ^ (accum2 >> 25);
+        accum2 = (accum2 << 7) ^ (old_1 >> 25);
+        accum1 = accum1.wrapping_add(u32::from(c));
+        n += 1;
+        if n < 64 && c != b'\n' {
+            continue;
+        }
+        let hash = accum1.wrapping_add(accum2.wrapping_mul(0x61)) % HASHBASE;
+        *counts.entry(hash).or_default() += n;
+        n = 0;
+        accum1 = 0;
+        accum2 = 0;
+    }
+    if n > 0 {
+        let hash = accum1.wrapping_add(accum2.wrapping_mul(0x61)) % HASHBASE;
+        *counts.entry(hash).or_default() += n;
+    }
+    let mut spans: Vec<(u32, u64)> = counts.into_iter().collect();
+    spans.sort_unstable();
+    Content {
+        size: data.len() as u64,
+        spans,
+    }
+}
+
+/// 两个指纹共有的字节数
+fn copied_bytes(one: &Content, two: &Content) -> u64 {
+    let mut copied = 0;
+    let mut d = two.spans.iter().peekable();
+    for &(hash, src_count) in &one.spans {
+        while d.next_if(|&&(h, _)| h < hash).is_some() {}
+        if let Some(&(_, dst_count)) = d.next_if(|&&(h, _)| h == hash) {
+            copied += src_count.min(dst_count);
+        }
+    }
+    copied
+}
@@ -0,0 +1,347 @@
+//! `--stat` 统计：每个文件一行的增删行数和 +/- 图形，最后一行汇总，
+//! 宽度分配移植自 git 的 diff.c（show_stats）
+
+use std::io::Write;
+
+use crate::diff::{
+    Change, Side, Status,
+    lines::{LineDiffOptions, diff_lines, split_lines},
+    patch::is_binary,
+    quote_path, side_content,
+};
+
+/// 一个文件的统计结果
+#[derive(Debug, Clone)]
+pub(crate) struct FileStat {
+    /// 显示的名称，重命名时是 `dir/{old => new}` 这样的形式
+    name: String,
+    /// 文本文件是增删的行数，二进制文件是新旧内容的字节数
+    added: usize,
+    deleted: usize,
+    binary: bool,
+    /// 只有空白变化被忽略时不显示
+    interesting: bool,
+}
+
+impl FileStat {
+    pub(crate) fn new(change: &Change, old: &[u8], new: &[u8], options: LineDiffOptions) -> Self {
+        let name = match &change.from {
+            Some(from) => pprint_rename(from, &change.path),
+            None => quote_path(&change.path),
+        };
+        let same = change.old.map(|side| side.hash) == change.new.map(|side| side.hash);
+        let mut stat = FileStat {
+            name,
+            added: 0,
+            deleted: 0,
+            binary: false,
+            interesting: true,
+        };
+        if is_binary(old) || is_binary(new) {
+            stat.binary = true;
+            if !same {
+                stat.added = new.len();
+                stat.deleted = old.len();
+            }
+        } else if !same {
+            for edit in diff_lines(&split_lines(old), &split_lines(new), options) {
+                stat.added += edit.new_len;
+                stat.deleted += edit.old_len;
+            }
+            if change.old.is_some() && change.new.is_some() && stat.added + stat.deleted == 0 {
+                stat.interesting = false;
+            }
+        }
+        stat
+    }
+
+    fn shown(&self) -> bool {
+        self.interesting || self.added + self.deleted > 0
+    }
+}
+
+/// 输出宽度：环境变量 COLUMNS，默认 80
+pub(crate) fn stat_width() -> usize {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok())
+        .filter(|&columns| columns > 0)
+        .unwrap_or(80)
+}
+
+/// 输出统计，每一行前面加上 prefix（如 `--graph` 的图形），width 包含 prefix
+pub(crate) fn write_stat(
+    out: &mut impl Write,
+    stats: &[FileStat],
+    width: usize,
+    prefix: &str,
+) -> std::io::Result<()> {
+    if stats.is_empty() {
+        return Ok(());
+    }
+    let shown: Vec<&FileStat> = stats.iter().filter(|stat| stat.shown()).collect();
+
+    // 最长的名称、最大的变化行数，以及二进制文件 `Bin XXX -> YYY bytes` 需要的宽度
+    let mut max_len = 0;
+    let mut max_change = 0;
+    let mut number_width = 0;
+    let mut bin_width = 0;
+    for stat in &shown {
+        max_len = max_len.max(stat.name.len());
+        if stat.binary {
+            bin_width = bin_width.max(14 + decimal_width(stat.added) + decimal_width(stat.deleted));
+            number_width = 3;
+            continue;
+        }
+        max_change = max_change.max(stat.added + stat.deleted);
+    }
+
+    // 名称最多占 5/8，图形至少 6 列；放不下时先压缩图形再截断名称
+    let width = (width as isize - prefix.len() as isize).max(0) as usize;
+    number_width = number_width.max(decimal_width(max_change));
+    let width = width.max(16 + 6 + number_width);
+    let mut graph_width = if max_change + 4 > bin_width {
+        max_change
+    } else {
+        bin_width - 4
+    };
+    let mut name_width = max_len;
+    if name_width + number_width + 6 + graph_width > width {
+        let limit = (width * 3 / 8) as isize - number_width as isize - 6;
+        if graph_width as isize > limit {
+            graph_width = limit.max(6) as usize;
+        }
+        if name_width > width - number_width - 6 - graph_width {
+            name_width = width - number_width - 6 - graph_width;
+        } else {
+            graph_width = width - number_width - 6 - name_width;
+        }
+    }
+
+    for stat in &shown {
+        // 名称太长时从前面截断，并尽量从某个 `/` 处开始
+        let mut name = stat.name.as_str();
+        let mut ellipsis = "";
+        let mut len = name_width;
+        if name_width < name.len() {
+            ellipsis = "...";
+            len = len.saturating_sub(3);
+            name = &name[name.len() - len..];
+            if let Some(slash) = name.find('/') {
+                name = &name[slash..];
+            }
+        }
+        let padding = " ".repeat(len.saturating_sub(name.len()));
+
+        if stat.binary {
+            write!(
+                out,
+                "{prefix} {ellipsis}{name}{padding} | {:>number_width$}",
+                "Bin"
+            )?;
+            if stat.added + stat.deleted == 0 {
+                writeln!(out)?;
+            } else {
+                writeln!(out, " {} -> {} bytes", stat.deleted, stat.added)?;
+            }
+            continue;
+        }
+
+        let total = stat.added + stat.deleted;
+        let (mut add, mut del) = (stat.added, stat.deleted);
+        if graph_width <= max_change {
+            let mut scaled = scale_linear(total, graph_width, max_change);
+            if scaled < 2 && add > 0 && del > 0 {
+                scaled = 2;
+            }
+            if add < del {
+                add = scale_linear(add, graph_width, max_change);
+                del = scaled - add;
+            } else {
+                del = scale_linear(del, graph_width, max_change);
+                add = scaled - del;
+            }
+        }
+        writeln!(
+            out,
+            "{prefix} {ellipsis}{name}{padding} | {total:>number_width$}{}{}{}",
+            if total > 0 { " " } else { "" },
+            "+".repeat(add),
+            "-".repeat(del)
+        )?;
+    }
+
+    writeln!(out, "{prefix}{}", totals(&shown))
+}
+
+/// `--shortstat`：只输出 `--stat` 的最后一行
+pub(crate) fn write_shortstat(out: &mut impl Write, stats: &[FileStat]) -> std::io::Result<()> {
+    if stats.is_empty() {
+        return Ok(());
+    }
+    let shown: Vec<&FileStat> = stats.iter().filter(|stat| stat.shown()).collect();
+    writeln!(out, "{}", totals(&shown))
+}
+
+/// 汇总行，二进制文件只计入文件数
+fn totals(shown: &[&FileStat]) -> String {
+    let mut insertions = 0;
+    let mut deletions = 0;
+    for stat in shown.iter().filter(|stat| !stat.binary) {
+        insertions += stat.added;
+        deletions += stat.deleted;
+    }
+    summary_line(shown.len(), insertions, deletions)
+}
+
+/// 读出每个变更两侧的内容并统计
+pub(crate) async fn file_stats(
+    changes: &[Change],
+    options: LineDiffOptions,
+) -> anyhow::Result<Vec<FileStat>> {
+    let content = |side: Option<Side>| async move {
+        match side {
+            Some(side) => side_content(side).await,
+            None => Ok(Vec::new()),
+        }
+    };
+    let mut stats = Vec::with_capacity(changes.len());
+    for change in changes {
+        let (old, new) = (content(change.old).await?, content(change.new).await?);
+        stats.push(FileStat::new(change, &old, &new, options));
+    }
+    Ok(stats)
+}
+
+/// `--summary` 输出：新增、删除、重命名和模式变化的文件各一行
+pub(crate) fn write_summary(out: &mut impl Write, changes: &[Change]) -> std::io::Result<()> {
+    let mode = |side: Option<Side>| side.map_or("000000".to_string(), |side| side.mode.to_padded());
+    for change in changes {
+        let path = quote_path(&change.path);
+        match change.status {
+            Status::Added => writeln!(out, " create mode {} {path}", mode(change.new))?,
+            Status::Deleted => writeln!(out, " delete mode {} {path}", mode(change.old))?,
+            Status::Renamed(score) | Status::Copied(score) => {
+                let kind = if matches!(change.status, Status::Renamed(_)) {
+                    "rename"
+                } else {
+                    "copy"
+                };
+                let name = pprint_rename(change.old_path(), &change.path);
+                writeln!(out, " {kind} {name} ({score}%)")?;
+                if change.old.map(|side| side.mode) != change.new.map(|side| side.mode) {
+                    writeln!(
+                        out,
+                        " mode change {} => {}",
+                        mode(change.old),
+                        mode(change.new)
+                    )?;
+                }
+            }
+            Status::Modified | Status::TypeChanged => {
+                if change.old.map(|side| side.mode) != change.new.map(|side| side.mode) {
+                    writeln!(
+                        out,
+                        " mode change {} => {} {path}",
+                        mode(change.old),
+                        mode(change.new)
+                    )?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// ` 2 files changed, 3 insertions(+), 1 deletion(-)`
+pub(crate) fn summary_line(files: usize, insertions: usize, deletions: usize) -> String {
+    if files == 0 {
+        return " 0 files changed".to_string();
+    }
+    let plural = |n: usize, one: &str, many: &str| {
+        if n == 1 {
+            format!("{n} {one}")
+        } else {
+            format!("{n} {many}")
+        }
+    };
+    let mut line = format!(" {}", plural(files, "file changed", "files changed"));
+    if insertions > 0 || deletions == 0 {
+        line.push_str(&format!(
+            ", {}",
+            plural(insertions, "insertion(+)", "insertions(+)")
+        ));
+    }
+    if deletions > 0 || insertions == 0 {
+        line.push_str(&format!(
+            ", {}",
+            plural(deletions, "deletion(-)", "deletions(-)")
+        ));
+    }
+    line
+}
+
+fn decimal_width(n: usize) -> usize {
+    n.to_string().len()
+}
+
+/// 按比例缩放，只要有变化至少画一个字符
+fn scale_linear(it: usize, width: usize, max_change: usize) -> usize {
+    if it == 0 {
+        return 0;
+    }
+    1 + it * (width - 1) / max_change
+}
+
+/// 重命名的显示名称：提取公共的目录前缀和后缀，如 `a/{b => c}/d`
+fn pprint_rename(a: &str, b: &str) -> String {
+    let (qa, qb) = (quote_path(a), quote_path(b));
+    if qa != a || qb != b {
+        return format!("{qa} => {qb}");
+    }
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+
+    // 公共前缀只算到最后一个 `/`
+    let mut pfx_length = 0;
+    for (i, (x, y)) in a.iter().zip(b).enumerate() {
+        if x != y {
+            break;
+        }
+        if *x == b'/' {
+            pfx_length = i + 1;
+        }
+    }
+
+    // 公共后缀从最后一个字符往前找，只算到 `/`；有前缀时允许回退到前缀末尾的 `/`
+    let adjust = isize::from(pfx_length > 0);
+    let mut sfx_length = 0;
+    let (mut i, mut j) = (a.len() as isize, b.len() as isize);
+    let at = |s: &[u8], i: isize| s.get(i as usize).copied().unwrap_or(0);
+    while pfx_length as isize - adjust <= i
+        && pfx_length as isize - adjust <= j
+        && at(a, i) == at(b, j)
+    {
+        if at(a, i) == b'/' {
+            sfx_length = a.len() - i as usize;
+        }
+        i -= 1;
+        j -= 1;
+    }
+
+    let a_mid = a.len().saturating_sub(pfx_length + sfx_length);
+    let b_mid = b.len().saturating_sub(pfx_length + sfx_length);
+    let text = |s: &[u8]| String::from_utf8_lossy(s).into_owned();
+    let mut name = String::new();
+    if pfx_length + sfx_length > 0 {
+        name.push_str(&text(&a[..pfx_length]));
+        name.push('{');
+    }
+    name.push_str(&text(&a[pfx_length..pfx_length + a_mid]));
+    name.push_str(" => ");
+    name.push_str(&text(&b[pfx_length..pfx_length + b_mid]));
+    if pfx_length + sfx_length > 0 {
+        name.push('}');
+        name.push_str(&text(&a[a.len() - sfx_length..]));
+    }
+    name
+}
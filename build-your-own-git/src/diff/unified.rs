@@ -0,0 +1,226 @@
+use std::io::Write;
+
+use crate::diff::lines::{Edit, is_space};
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct UnifiedOptions {
+    /// 每处修改前后的上下文行数（`-U<n>`）
+    pub(crate) context: usize,
+    /// 把修改所在的整个函数作为上下文（`-W`）
+    pub(crate) function_context: bool,
+}
+
+impl Default for UnifiedOptions {
+    fn default() -> Self {
+        UnifiedOptions {
+            context: 3,
+            function_context: false,
+        }
+    }
+}
+
+/// 函数名最多保留的字节数
+const FUNC_LINE_MAX: usize = 80;
+
+/// 按 unified 格式输出所有 hunk，hunk 头带上所在函数的名称（与 git 的 xdl_emit_diff 相同）
+pub(crate) fn write_hunks(
+    out: &mut impl Write,
+    old: &[&[u8]],
+    new: &[&[u8]],
+    edits: &[Edit],
+    options: UnifiedOptions,
+) -> std::io::Result<()> {
+    let ctxlen = options.context as isize;
+    let nrec1 = old.len() as isize;
+    let nrec2 = new.len() as isize;
+    let edit = |k: usize| {
+        let e = edits[k];
+        (
+            e.old as isize,
+            e.old_len as isize,
+            e.new as isize,
+            e.new_len as isize,
+        )
+    };
+
+    let mut func_line: Vec<u8> = Vec::new();
+    let mut funcline_prev = -1;
+    let mut first = 0;
+    while first < edits.len() {
+        // 间隔不超过两倍上下文的修改合并到同一个 hunk
+        let mut last = first;
+        while last + 1 < edits.len() {
+            let (i1, chg1, _, _) = edit(last);
+            if edit(last + 1).0 - (i1 + chg1) > 2 * ctxlen {
+                break;
+            }
+            last += 1;
+        }
+
+        let (i1, _, i2, _) = edit(first);
+        let mut s1 = (i1 - ctxlen).max(0);
+        let mut s2 = (i2 - ctxlen).max(0);
+
+        if options.function_context {
+            let mut start = i1;
+            // 追加在文件末尾的修改：新增了完整的函数就不需要额外上下文
+            let appended = start >= nrec1;
+            let whole_function_added =
+                appended && (i2..nrec2).any(|l| is_func_line(new[l as usize]));
+            if appended {
+                start = nrec1 - 1;
+            }
+            if !whole_function_added {
+                let mut fs1 = get_func_line(old, start, -1).map_or(-1, |(l, _)| l);
+                while fs1 > 0
+                    && !is_empty_line(old[fs1 as usize - 1])
+                    && !is_func_line(old[fs1 as usize - 1])
+                {
+                    fs1 -= 1;
+                }
+                let fs1 = fs1.max(0);
+                if fs1 < s1 {
+                    s2 = (s2 - (s1 - fs1)).max(0);
+                    s1 = fs1;
+                }
+            }
+        }
+
+        let (mut e1, mut e2);
+        loop {
+            let (i1, chg1, i2, chg2) = edit(last);
+            let lctx = ctxlen.min(nrec1 - (i1 + chg1)).min(nrec2 - (i2 + chg2));
+            e1 = i1 + chg1 + lctx;
+            e2 = i2 + chg2 + lctx;
+
+            if options.function_context {
+                let mut fe1 = get_func_line(old, i1 + chg1, nrec1).map_or(-1, |(l, _)| l);
+                while fe1 > 0 && is_empty_line(old[fe1 as usize - 1]) {
+                    fe1 -= 1;
+                }
+                if fe1 < 0 {
+                    fe1 = nrec1;
+                }
+                if fe1 > e1 {
+                    e2 = (e2 + (fe1 - e1)).min(nrec2);
+                    e1 = fe1;
+                }
+                // 与下一处修改重叠时把它也并进来
+                if last + 1 < edits.len() {
+                    let l = edit(last + 1).0.min(nrec1 - 1);
+                    if l - ctxlen <= e1 || get_func_line(old, l, e1).is_none() {
+                        last += 1;
+                        continue;
+                    }
+                }
+            }
+            break;
+        }
+
+        // 没找到时沿用上一个 hunk 的函数名
+        if let Some((_, name)) = get_func_line(old, s1 - 1, funcline_prev) {
+            func_line = name;
+        }
+        funcline_prev = s1 - 1;
+
+        write!(out, "@@ -")?;
+        write_range(out, s1 + 1, e1 - s1)?;
+        write!(out, " +")?;
+        write_range(out, s2 + 1, e2 - s2)?;
+        write!(out, " @@")?;
+        if !func_line.is_empty() {
+            out.write_all(b" ")?;
+            out.write_all(&func_line)?;
+        }
+        writeln!(out)?;
+
+        // 前置上下文
+        let (i1, _, i2, _) = edit(first);
+        while s2 < i2 {
+            write_line(out, b" ", new[s2 as usize])?;
+            s2 += 1;
+        }
+        s1 = i1;
+        s2 = i2;
+        for k in first..=last {
+            let (i1, chg1, i2, chg2) = edit(k);
+            while s1 < i1 && s2 < i2 {
+                write_line(out, b" ", new[s2 as usize])?;
+                s1 += 1;
+                s2 += 1;
+            }
+            for line in &old[i1 as usize..(i1 + chg1) as usize] {
+                write_line(out, b"-", line)?;
+            }
+            for line in &new[i2 as usize..(i2 + chg2) as usize] {
+                write_line(out, b"+", line)?;
+            }
+            s1 = i1 + chg1;
+            s2 = i2 + chg2;
+        }
+        // 后置上下文
+        while s2 < e2 {
+            write_line(out, b" ", new[s2 as usize])?;
+            s2 += 1;
+        }
+
+        first = last + 1;
+    }
+    Ok(())
+}
+
+/// `start,count`，只有一行时省略数量，没有行时起始行号减一
+fn write_range(out: &mut impl Write, start: isize, count: isize) -> std::io::Result<()> {
+    write!(out, "{}", if count == 0 { start - 1 } else { start })?;
+    if count != 1 {
+        write!(out, ",{count}")?;
+    }
+    Ok(())
+}
+
+fn write_line(out: &mut impl Write, prefix: &[u8], line: &[u8]) -> std::io::Result<()> {
+    out.write_all(prefix)?;
+    out.write_all(line)?;
+    if !line.ends_with(b"\n") {
+        out.write_all(b"\n\\ No newline at end of file\n")?;
+    }
+    Ok(())
+}
+
+/// 以字母、`_` 或 `$` 开头的行视为函数头，返回去掉行尾空白、截断后的内容
+fn func_name(line: &[u8]) -> Option<Vec<u8>> {
+    match line.first() {
+        Some(c) if c.is_ascii_alphabetic() || *c == b'_' || *c == b'$' => {
+            let mut name = &line[..line.len().min(FUNC_LINE_MAX)];
+            while let Some((last, rest)) = name.split_last() {
+                if !is_space(*last) {
+                    break;
+                }
+                name = rest;
+            }
+            Some(name.to_vec())
+        }
+        _ => None,
+    }
+}
+
+fn is_func_line(line: &[u8]) -> bool {
+    func_name(line).is_some()
+}
+
+fn is_empty_line(line: &[u8]) -> bool {
+    line.iter().all(|&b| is_space(b))
+}
+
+/// 从 start 开始朝 limit 方向（不含 limit）查找旧文件中的函数头
+fn get_func_line(old: &[&[u8]], start: isize, limit: isize) -> Option<(isize, Vec<u8>)> {
+    let step = if start > limit { -1 } else { 1 };
+    let mut l = start;
+    while l != limit && 0 <= l && l < old.len() as isize {
+        if let Some(name) = func_name(old[l as usize]) {
+            return Some((l, name));
+        }
+        l += step;
+    }
+    None
+}
@@ -0,0 +1,74 @@
+//! 调用用户的编辑器修改文件。提交说明依次使用 `GIT_EDITOR`、`core.editor`、`VISUAL`、`EDITOR`，
+//! rebase 的待办列表优先使用 `GIT_SEQUENCE_EDITOR` 和 `sequence.editor`，都没有设置时用 vi
+
+use std::{path::Path, process::Command};
+
+use anyhow::Context;
+
+use crate::{
+    commands::commit::{Cleanup, instructions, stripspace},
+    config::Config,
+};
+
+pub(crate) const COMMIT_EDITMSG: &str = ".git/COMMIT_EDITMSG";
+
+/// 编辑器命令，与 git 一样在终端不支持时拒绝回退到 vi
+fn editor(sequence: bool) -> anyhow::Result<String> {
+    let from_env = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+    let dumb = std::env::var("TERM").map_or(true, |term| term == "dumb");
+    let config = Config::read()?;
+    let from_config = |name: &str| config.get(name).filter(|value| !value.is_empty());
+    let editor = sequence
+        .then(|| from_env("GIT_SEQUENCE_EDITOR").or_else(|| from_config("sequence.editor")))
+        .flatten()
+        .or_else(|| from_env("GIT_EDITOR"))
+        .or_else(|| from_config("core.editor"))
+        .or_else(|| if dumb { None } else { from_env("VISUAL") })
+        .or_else(|| from_env("EDITOR"));
+    match editor {
+        Some(editor) => Ok(editor),
+        None if dumb => anyhow::bail!("Terminal is dumb, but EDITOR unset"),
+        None => Ok("vi".to_string()),
+    }
+}
+
+/// 用编辑器打开 path，编辑器命令交给 shell 执行，`:` 表示不编辑
+pub(crate) fn edit_file(path: &Path, sequence: bool) -> anyhow::Result<()> {
+    let editor = editor(sequence)?;
+    if editor == ":" {
+        return Ok(());
+    }
+    // 与 git 一样给编辑器传绝对路径
+    let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{editor} \"$@\""))
+        .arg(&editor)
+        .arg(&path)
+        .status()
+        .with_context(|| format!("unable to start editor '{editor}'"))?;
+    anyhow::ensure!(
+        status.success(),
+        "There was a problem with the editor '{editor}'."
+    );
+    Ok(())
+}
+
+/// 在 COMMIT_EDITMSG 中编辑提交说明，返回去掉注释并整理后的结果，为空时报错
+pub(crate) fn edit_message(message: &str) -> anyhow::Result<String> {
+    let mut content = message.to_string();
+    if !content.ends_with('\n') {
+        content.push('\n');
+    }
+    content.push('\n');
+    content.push_str(instructions(Cleanup::Strip));
+    std::fs::write(COMMIT_EDITMSG, content).context("write COMMIT_EDITMSG")?;
+    edit_file(Path::new(COMMIT_EDITMSG), false)?;
+    let edited = std::fs::read_to_string(COMMIT_EDITMSG).context("read COMMIT_EDITMSG")?;
+    let message = stripspace(&edited, true);
+    anyhow::ensure!(
+        !message.is_empty(),
+        "Aborting commit due to empty commit message."
+    );
+    Ok(message)
+}
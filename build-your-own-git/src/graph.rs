@@ -0,0 +1,524 @@
+//! `log --graph` 的 ASCII 图形绘制，逐行移植自 git 的 graph.c，
+//! 以保证合并、章鱼合并和分支收拢时的输出与 git 完全一致。
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Padding,
+    Skip,
+    PreCommit,
+    Commit,
+    PostMerge,
+    Collapsing,
+}
+
+pub(crate) struct Graph {
+    /// 当前正在绘制的提交
+    commit: Option<[u8; 20]>,
+    /// 当前提交参与绘制的父提交
+    parents: Vec<[u8; 20]>,
+    /// 当前提交输出的图形宽度（字符数）
+    width: usize,
+    /// 章鱼合并前的扩展行序号
+    expansion_row: usize,
+    state: State,
+    prev_state: State,
+    /// 当前提交所在的列
+    commit_index: usize,
+    prev_commit_index: usize,
+    /// 合并边的布局：0 表示第一个父提交在左侧，1 表示在正下方
+    merge_layout: isize,
+    /// 合并新增的列数
+    edges_added: isize,
+    prev_edges_added: isize,
+    /// 当前提交之前的各列
+    columns: Vec<[u8; 20]>,
+    /// 当前提交之后的各列
+    new_columns: Vec<[u8; 20]>,
+    /// 输出位置 -> new_columns 下标，-1 表示空白；
+    /// 与 git 一样只增长不收缩，有效长度由 mapping_size 给出
+    mapping: Vec<isize>,
+    old_mapping: Vec<isize>,
+    mapping_size: usize,
+}
+
+impl Graph {
+    pub(crate) fn new() -> Self {
+        Graph {
+            commit: None,
+            parents: Vec::new(),
+            width: 0,
+            expansion_row: 0,
+            state: State::Padding,
+            prev_state: State::Padding,
+            commit_index: 0,
+            prev_commit_index: 0,
+            merge_layout: 0,
+            edges_added: 0,
+            prev_edges_added: 0,
+            columns: Vec::new(),
+            new_columns: Vec::new(),
+            mapping: Vec::new(),
+            old_mapping: Vec::new(),
+            mapping_size: 0,
+        }
+    }
+
+    /// 切换到下一个要输出的提交，parents 只包含会被输出的父提交
+    pub(crate) fn update(&mut self, commit: [u8; 20], parents: Vec<[u8; 20]>) {
+        self.commit = Some(commit);
+        self.parents = parents;
+        self.prev_commit_index = self.commit_index;
+        self.update_columns();
+        self.expansion_row = 0;
+
+        self.state = if self.state != State::Padding {
+            State::Skip
+        } else if self.needs_pre_commit_line() {
+            State::PreCommit
+        } else {
+            State::Commit
+        };
+    }
+
+    /// 当前提交的图形是否已全部输出
+    pub(crate) fn is_commit_finished(&self) -> bool {
+        self.state == State::Padding
+    }
+
+    /// 输出下一行图形，返回该行是否是提交所在行
+    pub(crate) fn next_line(&mut self) -> (String, bool) {
+        let mut line = String::new();
+        let mut shown_commit_line = false;
+        match self.state {
+            State::Padding => self.output_padding_line(&mut line),
+            State::Skip => self.output_skip_line(&mut line),
+            State::PreCommit => self.output_pre_commit_line(&mut line),
+            State::Commit => {
+                self.output_commit_line(&mut line);
+                shown_commit_line = true;
+            }
+            State::PostMerge => self.output_post_merge_line(&mut line),
+            State::Collapsing => self.output_collapsing_line(&mut line),
+        }
+        self.pad_horizontally(&mut line);
+        (line, shown_commit_line)
+    }
+
+    /// 不改变任何分支线的填充行，用于提交说明的后续行
+    pub(crate) fn padding_line(&mut self) -> String {
+        if self.state != State::Commit {
+            return self.next_line().0;
+        }
+        let mut line = String::new();
+        for column in &self.columns {
+            line.push('|');
+            if Some(*column) == self.commit && self.parents.len() > 2 {
+                push_chars(&mut line, ' ', (self.parents.len() - 2) * 2);
+            } else {
+                line.push(' ');
+            }
+        }
+        self.pad_horizontally(&mut line);
+        self.prev_state = State::Padding;
+        line
+    }
+
+    /// 输出直到提交行为止的所有图形行，最后一行是提交行
+    pub(crate) fn commit_lines(&mut self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if self.is_commit_finished() {
+            lines.push(self.padding_line());
+            return lines;
+        }
+        loop {
+            let (line, shown_commit_line) = self.next_line();
+            lines.push(line);
+            if shown_commit_line || self.is_commit_finished() {
+                return lines;
+            }
+        }
+    }
+
+    /// 提交说明输出完后，剩余的图形行
+    pub(crate) fn remainder(&mut self) -> Vec<String> {
+        let mut lines = Vec::new();
+        while !self.is_commit_finished() {
+            lines.push(self.next_line().0);
+        }
+        lines
+    }
+
+    fn update_state(&mut self, state: State) {
+        self.prev_state = self.state;
+        self.state = state;
+    }
+
+    fn num_parents(&self) -> usize {
+        self.parents.len()
+    }
+
+    fn num_dashed_parents(&self) -> isize {
+        self.num_parents() as isize + self.merge_layout - 3
+    }
+
+    fn num_expansion_rows(&self) -> isize {
+        self.num_dashed_parents() * 2
+    }
+
+    fn needs_pre_commit_line(&self) -> bool {
+        self.num_parents() >= 3
+            && self.commit_index + 1 < self.columns.len()
+            && (self.expansion_row as isize) < self.num_expansion_rows()
+    }
+
+    fn find_new_column_by_commit(&self, commit: &[u8; 20]) -> Option<usize> {
+        self.new_columns.iter().position(|c| c == commit)
+    }
+
+    fn insert_into_new_columns(&mut self, commit: [u8; 20], idx: Option<usize>) {
+        let i = match self.find_new_column_by_commit(&commit) {
+            Some(i) => i,
+            None => {
+                self.new_columns.push(commit);
+                self.new_columns.len() - 1
+            }
+        };
+
+        let mapping_idx;
+        if let (true, Some(idx), -1) = (self.num_parents() > 1, idx, self.merge_layout) {
+            // 合并提交的第一个父提交：根据它在左侧还是正下方决定合并边的布局
+            let dist = idx as isize - i as isize;
+            let shift = if dist > 1 { 2 * dist - 3 } else { 1 };
+
+            self.merge_layout = if dist > 0 { 0 } else { 1 };
+            self.edges_added = self.num_parents() as isize + self.merge_layout - 2;
+
+            mapping_idx = (self.width as isize + (self.merge_layout - 1) * shift) as usize;
+            self.width += 2 * self.merge_layout as usize;
+        } else if self.edges_added > 0
+            && self.width >= 2
+            && self.mapping[self.width - 2] == i as isize
+        {
+            // 合并新增了列，但父提交就在最后一个已有列中，让两条边直接汇合
+            mapping_idx = self.width - 2;
+            self.edges_added = -1;
+        } else {
+            mapping_idx = self.width;
+            self.width += 2;
+        }
+
+        self.mapping[mapping_idx] = i as isize;
+    }
+
+    fn update_columns(&mut self) {
+        std::mem::swap(&mut self.columns, &mut self.new_columns);
+        self.new_columns.clear();
+
+        let max_new_columns = self.columns.len() + self.num_parents();
+        // 保留上一行的 mapping，提交行据此判断是否延续收拢时的 '/'
+        std::mem::swap(&mut self.mapping, &mut self.old_mapping);
+        if self.mapping.len() < 2 * max_new_columns {
+            self.mapping.resize(2 * max_new_columns, -1);
+            self.old_mapping.resize(2 * max_new_columns, -1);
+        }
+        self.mapping_size = 2 * max_new_columns;
+        self.mapping[..self.mapping_size].fill(-1);
+
+        self.width = 0;
+        self.prev_edges_added = self.edges_added;
+        self.edges_added = 0;
+
+        let commit = self.commit.expect("update_columns without commit");
+        let mut seen_this = false;
+        let num_columns = self.columns.len();
+        for i in 0..=num_columns {
+            let col_commit = if i == num_columns {
+                if seen_this {
+                    break;
+                }
+                commit
+            } else {
+                self.columns[i]
+            };
+
+            if col_commit == commit {
+                seen_this = true;
+                self.commit_index = i;
+                self.merge_layout = -1;
+                for parent in self.parents.clone() {
+                    self.insert_into_new_columns(parent, Some(i));
+                }
+                // 即使没有父提交，提交本身也至少占两个字符
+                if self.num_parents() == 0 {
+                    self.width += 2;
+                }
+            } else {
+                self.insert_into_new_columns(col_commit, None);
+            }
+        }
+
+        // 收缩 mapping 到最小长度
+        while self.mapping_size > 1 && self.mapping[self.mapping_size - 1] < 0 {
+            self.mapping_size -= 1;
+        }
+    }
+
+    fn is_mapping_correct(&self) -> bool {
+        // 每个分支都已在目标位置（或在目标右边一格，下一行会输出 '/'）
+        self.mapping[..self.mapping_size]
+            .iter()
+            .enumerate()
+            .all(|(i, &target)| target < 0 || target == (i / 2) as isize)
+    }
+
+    fn pad_horizontally(&self, line: &mut String) {
+        let len = line.chars().count();
+        if len < self.width {
+            push_chars(line, ' ', self.width - len);
+        }
+    }
+
+    fn output_padding_line(&mut self, line: &mut String) {
+        for _ in &self.new_columns {
+            line.push_str("| ");
+        }
+    }
+
+    fn output_skip_line(&mut self, line: &mut String) {
+        line.push_str("...");
+        if self.needs_pre_commit_line() {
+            self.update_state(State::PreCommit);
+        } else {
+            self.update_state(State::Commit);
+        }
+    }
+
+    fn output_pre_commit_line(&mut self, line: &mut String) {
+        let mut seen_this = false;
+        for (i, column) in self.columns.iter().enumerate() {
+            if Some(*column) == self.commit {
+                seen_this = true;
+                line.push('|');
+                push_chars(line, ' ', self.expansion_row);
+            } else if seen_this && self.expansion_row == 0 {
+                // 上一个提交是合并且停在 POST_MERGE，右侧分支线继续输出 '\'
+                if self.prev_state == State::PostMerge && self.prev_commit_index < i {
+                    line.push('\\');
+                } else {
+                    line.push('|');
+                }
+            } else if seen_this {
+                line.push('\\');
+            } else {
+                line.push('|');
+            }
+            line.push(' ');
+        }
+
+        self.expansion_row += 1;
+        if !self.needs_pre_commit_line() {
+            self.update_state(State::Commit);
+        }
+    }
+
+    fn draw_octopus_merge(&self, line: &mut String) {
+        let dashed_parents = self.num_dashed_parents();
+        for i in 0..dashed_parents {
+            line.push('-');
+            line.push(if i == dashed_parents - 1 { '.' } else { '-' });
+        }
+    }
+
+    fn output_commit_line(&mut self, line: &mut String) {
+        let num_columns = self.columns.len();
+        let mut seen_this = false;
+        for i in 0..=num_columns {
+            let col_commit = if i == num_columns {
+                if seen_this {
+                    break;
+                }
+                self.commit.expect("commit line without commit")
+            } else {
+                self.columns[i]
+            };
+
+            if Some(col_commit) == self.commit {
+                seen_this = true;
+                line.push('*');
+                if self.num_parents() > 2 {
+                    self.draw_octopus_merge(line);
+                }
+            } else if seen_this && self.edges_added > 1 {
+                line.push('\\');
+            } else if seen_this && self.edges_added == 1 {
+                // 右偏的二路合并或左偏的三路合并没有 PRE_COMMIT 行，
+                // 如果上一行是 POST_MERGE 的 '\'，这里继续输出 '\'
+                if self.prev_state == State::PostMerge
+                    && self.prev_edges_added > 0
+                    && self.prev_commit_index < i
+                {
+                    line.push('\\');
+                } else {
+                    line.push('|');
+                }
+            } else if self.prev_state == State::Collapsing
+                && self.old_mapping[2 * i + 1] == i as isize
+                && self.mapping[2 * i] < i as isize
+            {
+                line.push('/');
+            } else {
+                line.push('|');
+            }
+            line.push(' ');
+        }
+
+        if self.num_parents() > 1 {
+            self.update_state(State::PostMerge);
+        } else if self.is_mapping_correct() {
+            self.update_state(State::Padding);
+        } else {
+            self.update_state(State::Collapsing);
+        }
+    }
+
+    fn output_post_merge_line(&mut self, line: &mut String) {
+        const MERGE_CHARS: [char; 3] = ['/', '|', '\\'];
+
+        let commit = self.commit.expect("post merge line without commit");
+        let first_parent = self.parents[0];
+        let num_columns = self.columns.len();
+        let mut seen_this = false;
+        let mut parent_col_seen = false;
+        for i in 0..=num_columns {
+            let col_commit = if i == num_columns {
+                if seen_this {
+                    break;
+                }
+                commit
+            } else {
+                self.columns[i]
+            };
+
+            if col_commit == commit {
+                // 合并提交：在 new_columns 中找到各父提交的列来绘制边
+                seen_this = true;
+                let mut idx = self.merge_layout as usize;
+                for j in 0..self.num_parents() {
+                    line.push(MERGE_CHARS[idx]);
+                    if idx == 2 {
+                        if self.edges_added > 0 || j < self.num_parents() - 1 {
+                            line.push(' ');
+                        }
+                    } else {
+                        idx += 1;
+                    }
+                }
+                if self.edges_added == 0 {
+                    line.push(' ');
+                }
+            } else if seen_this {
+                line.push(if self.edges_added > 0 { '\\' } else { '|' });
+                line.push(' ');
+            } else {
+                line.push('|');
+                if self.merge_layout != 0 || i + 1 != self.commit_index {
+                    line.push(if parent_col_seen { '_' } else { ' ' });
+                }
+            }
+
+            if col_commit == first_parent {
+                parent_col_seen = true;
+            }
+        }
+
+        if self.is_mapping_correct() {
+            self.update_state(State::Padding);
+        } else {
+            self.update_state(State::Collapsing);
+        }
+    }
+
+    fn output_collapsing_line(&mut self, line: &mut String) {
+        let mut used_horizontal = false;
+        let mut horizontal_edge: isize = -1;
+        let mut horizontal_edge_target: isize = -1;
+
+        std::mem::swap(&mut self.mapping, &mut self.old_mapping);
+        self.mapping[..self.mapping_size].fill(-1);
+
+        for i in 0..self.mapping_size {
+            let target = self.old_mapping[i];
+            if target < 0 {
+                continue;
+            }
+            // 分支只会向左移动，这样交叉时只有一条线在移动
+            let target_pos = (target * 2) as usize;
+            debug_assert!(target_pos <= i);
+
+            if target_pos == i {
+                // 已经在正确位置
+                self.mapping[i] = target;
+            } else if self.mapping[i - 1] < 0 {
+                // 左边为空，向左移动一格
+                self.mapping[i - 1] = target;
+                if horizontal_edge == -1 {
+                    horizontal_edge = i as isize;
+                    horizontal_edge_target = target;
+                    let mut j = target_pos + 3;
+                    while j + 2 < i {
+                        self.mapping[j] = target;
+                        j += 2;
+                    }
+                }
+            } else if self.mapping[i - 1] == target {
+                // 左边的分支线就是目标，直接合并
+            } else {
+                // 左边有别的分支线，需要跨过它
+                self.mapping[i - 2] = target;
+                if horizontal_edge == -1 {
+                    horizontal_edge_target = target;
+                    horizontal_edge = i as isize - 1;
+                    let mut j = target_pos + 3;
+                    while j + 2 < i {
+                        self.mapping[j] = target;
+                        j += 2;
+                    }
+                }
+            }
+        }
+
+        // 新 mapping 可能比旧的短一格
+        if self.mapping[self.mapping_size - 1] < 0 {
+            self.mapping_size -= 1;
+        }
+
+        for i in 0..self.mapping_size {
+            let target = self.mapping[i];
+            if target < 0 {
+                line.push(' ');
+            } else if target * 2 == i as isize {
+                line.push('|');
+            } else if target == horizontal_edge_target && i as isize != horizontal_edge - 1 {
+                // 除了第一段外，其余水平线不延续到下一行
+                if i as isize != target * 2 + 3 {
+                    self.mapping[i] = -1;
+                }
+                used_horizontal = true;
+                line.push('_');
+            } else {
+                if used_horizontal && (i as isize) < horizontal_edge {
+                    self.mapping[i] = -1;
+                }
+                line.push('/');
+            }
+        }
+
+        if self.is_mapping_correct() {
+            self.update_state(State::Padding);
+        }
+    }
+}
+
+fn push_chars(line: &mut String, c: char, n: usize) {
+    line.extend(std::iter::repeat(c).take(n));
+}
@@ -0,0 +1,189 @@
+//! 运行钩子：`core.hooksPath`（默认 `.git/hooks`）下与钩子同名的可执行文件。
+//! 钩子在工作区根目录运行，与 git 一样把它的标准输出转到标准错误。
+//! 接收推送时运行的是目标仓库的钩子，见 run_in
+
+use std::{
+    collections::BTreeSet,
+    io::{IsTerminal, Read, Seek, Write},
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::Mutex,
+};
+
+use anyhow::Context;
+
+use crate::config::Config;
+
+/// 已经提示过不可执行的钩子，与 git 一样每个钩子只提示一次
+static IGNORED: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// 作为服务端（receive-pack）运行时收集的远端消息，由它经由 side-band 送给客户端，见 capture
+static CAPTURED: Mutex<Option<String>> = Mutex::new(None);
+
+/// 钩子的路径，没有这个钩子时返回 None。文件存在但不可执行时与 git 一样提示后忽略
+pub(crate) fn find(name: &str) -> anyhow::Result<Option<PathBuf>> {
+    locate(&Config::read()?, Path::new(""), Path::new(".git"), name)
+}
+
+/// 在 git_dir 这个仓库中查找钩子，相对的 `core.hooksPath` 相对于 base
+fn locate(
+    config: &Config,
+    base: &Path,
+    git_dir: &Path,
+    name: &str,
+) -> anyhow::Result<Option<PathBuf>> {
+    let dir = config
+        .get("core.hooksPath")
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| match dir.strip_prefix("~/") {
+            Some(rest) => PathBuf::from(std::env::var_os("HOME").unwrap_or_default()).join(rest),
+            None => base.join(dir),
+        })
+        .unwrap_or_else(|| git_dir.join("hooks"));
+    let path = dir.join(name);
+    let Ok(metadata) = std::fs::metadata(&path) else {
+        return Ok(None);
+    };
+    if !metadata.is_file() {
+        return Ok(None);
+    }
+    if metadata.permissions().mode() & 0o111 == 0 {
+        let first = IGNORED
+            .lock()
+            .map_or(true, |mut ignored| ignored.insert(name.to_string()));
+        if first && config.get_bool("advice.ignoredHook")? != Some(false) {
+            eprintln!(
+                "hint: The '{}' hook was ignored because it's not set as executable.\n\
+                 hint: You can disable this warning with `git config advice.ignoredHook false`.",
+                path.display()
+            );
+        }
+        return Ok(None);
+    }
+    Ok(Some(path))
+}
+
+/// 运行钩子，没有这个钩子或钩子成功退出时返回 true
+pub(crate) fn run(name: &str, args: &[&str]) -> anyhow::Result<bool> {
+    run_with(name, args, &[], None)
+}
+
+/// 同 run，额外设置环境变量 env，stdin 不为空时写给钩子的标准输入
+pub(crate) fn run_with(
+    name: &str,
+    args: &[&str],
+    env: &[(&str, &str)],
+    stdin: Option<&[u8]>,
+) -> anyhow::Result<bool> {
+    let Some(path) = find(name)? else {
+        return Ok(true);
+    };
+    // 钩子的输出要排在我们已经输出的内容后面
+    std::io::stdout().flush()?;
+    let mut child = Command::new(&path)
+        .args(args)
+        .envs(env.iter().copied())
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::from(std::io::stderr()))
+        .spawn()
+        .with_context(|| format!("cannot run {}", path.display()))?;
+    if let (Some(input), Some(mut pipe)) = (stdin, child.stdin.take()) {
+        // 钩子不读标准输入就退出时会断开管道，不算错误
+        match pipe.write_all(input) {
+            Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => {}
+            result => result.with_context(|| format!("write to hook {name}"))?,
+        }
+    }
+    let status = child
+        .wait()
+        .with_context(|| format!("wait for hook {name}"))?;
+    Ok(status.success())
+}
+
+/// 在另一个仓库 git_dir 中运行它的钩子，如接收推送时的 pre-receive。
+/// 与 git 一样在 git_dir 中运行，标准输出和标准错误合在一起，每行加上 `remote: ` 前缀输出到标准错误
+pub(crate) fn run_in(
+    git_dir: &Path,
+    name: &str,
+    args: &[&str],
+    stdin: Option<&[u8]>,
+) -> anyhow::Result<bool> {
+    let git_dir = git_dir
+        .canonicalize()
+        .with_context(|| format!("cannot access {}", git_dir.display()))?;
+    let config = Config::read_file(git_dir.join("config"))?;
+    let Some(path) = locate(&config, &git_dir, &git_dir, name)? else {
+        return Ok(true);
+    };
+    std::io::stdout().flush()?;
+    // 两个输出写到同一个临时文件，保持钩子输出的先后顺序
+    let mut output = tempfile::tempfile().context("create temporary file")?;
+    let mut child = Command::new(&path)
+        .args(args)
+        .current_dir(&git_dir)
+        .env("GIT_DIR", ".")
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(output.try_clone()?)
+        .stderr(output.try_clone()?)
+        .spawn()
+        .with_context(|| format!("cannot run {}", path.display()))?;
+    if let (Some(input), Some(mut pipe)) = (stdin, child.stdin.take()) {
+        match pipe.write_all(input) {
+            Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => {}
+            result => result.with_context(|| format!("write to hook {name}"))?,
+        }
+    }
+    let status = child
+        .wait()
+        .with_context(|| format!("wait for hook {name}"))?;
+    let mut text = String::new();
+    output.rewind()?;
+    output.read_to_string(&mut text)?;
+    remote_message(&text);
+    Ok(status.success())
+}
+
+/// 输出远端的消息。与 git 一样，非空行末尾加上清除到行尾的控制序列，不是终端时用 8 个空格代替
+pub(crate) fn remote_message(text: &str) {
+    if let Some(captured) = CAPTURED.lock().unwrap().as_mut() {
+        for line in text.lines() {
+            captured.push_str(line);
+            captured.push('\n');
+        }
+        return;
+    }
+    let suffix = clear_to_eol();
+    for line in text.lines() {
+        match line.is_empty() {
+            true => eprintln!("remote: "),
+            false => eprintln!("remote: {line}{suffix}"),
+        }
+    }
+}
+
+/// 开始收集远端消息而不输出。服务端的消息由客户端加上 `remote: ` 前缀显示
+pub(crate) fn capture() {
+    *CAPTURED.lock().unwrap() = Some(String::new());
+}
+
+/// 停止收集远端消息，返回收集到的
+pub(crate) fn take_captured() -> Option<String> {
+    CAPTURED.lock().unwrap().take()
+}
+
+/// 远端消息行末清除到行尾的控制序列，标准错误不是终端时是 8 个空格
+pub(crate) fn clear_to_eol() -> &'static str {
+    match std::io::stderr().is_terminal() {
+        true => "\x1b[K",
+        false => "        ",
+    }
+}
@@ -0,0 +1,230 @@
+//! `.gitignore` 和 `.git/info/exclude` 的忽略规则
+
+use std::path::Path;
+
+/// 一条忽略规则
+#[derive(Debug, Clone)]
+struct Pattern {
+    pattern: Vec<u8>,
+    /// 规则所在 .gitignore 的目录，如 `sub/`，根目录为空
+    base: String,
+    /// `!` 开头，重新包含被忽略的路径
+    negated: bool,
+    /// `/` 结尾，只匹配目录
+    dir_only: bool,
+    /// 不含 `/`，匹配任意层级的文件名
+    basename_only: bool,
+}
+
+/// 当前生效的所有规则，后加入的优先级更高
+#[derive(Debug, Default)]
+pub(crate) struct Ignore {
+    patterns: Vec<Pattern>,
+}
+
+impl Ignore {
+    /// 读取 `.git/info/exclude` 和根目录的 `.gitignore`
+    pub(crate) fn load() -> Ignore {
+        let mut ignore = Ignore::default();
+        ignore.add_file(Path::new(".git/info/exclude"), "");
+        ignore.add_file(Path::new(".gitignore"), "");
+        ignore
+    }
+
+    /// 进入子目录时加入它的 `.gitignore`，返回加入前的规则数，离开时用来恢复
+    pub(crate) fn push_dir(&mut self, dir: &str) -> usize {
+        let len = self.patterns.len();
+        self.add_file(&Path::new(dir).join(".gitignore"), &format!("{dir}/"));
+        len
+    }
+
+    pub(crate) fn pop_dir(&mut self, len: usize) {
+        self.patterns.truncate(len);
+    }
+
+    fn add_file(&mut self, path: &Path, base: &str) {
+        let Ok(content) = std::fs::read(path) else {
+            return;
+        };
+        for line in content.split(|&b| b == b'\n') {
+            if let Some(pattern) = parse_line(line, base) {
+                self.patterns.push(pattern);
+            }
+        }
+    }
+
+    /// 路径是否被忽略，由最后一条匹配的规则决定
+    pub(crate) fn is_ignored(&self, path: &str, is_dir: bool) -> bool {
+        for pattern in self.patterns.iter().rev() {
+            if pattern.dir_only && !is_dir {
+                continue;
+            }
+            let matched = if pattern.basename_only {
+                let name = path.rsplit('/').next().unwrap_or(path);
+                wildmatch(&pattern.pattern, name.as_bytes())
+            } else {
+                match path.strip_prefix(pattern.base.as_str()) {
+                    Some(rest) => wildmatch(&pattern.pattern, rest.as_bytes()),
+                    None => false,
+                }
+            };
+            if matched {
+                return !pattern.negated;
+            }
+        }
+        false
+    }
+}
+
+fn parse_line(line: &[u8], base: &str) -> Option<Pattern> {
+    let mut line = line.strip_suffix(b"\r").unwrap_or(line);
+    if line.is_empty() || line[0] == b'#' {
+        return None;
+    }
+    // 去掉行尾没有转义的空格
+    while let [rest @ .., b' '] = line {
+        if rest.ends_with(b"\\") {
+            break;
+        }
+        line = rest;
+    }
+    let negated = line[0] == b'!';
+    if negated {
+        line = &line[1..];
+    }
+    let dir_only = line.ends_with(b"/");
+    if dir_only {
+        line = &line[..line.len() - 1];
+    }
+    if line.is_empty() {
+        return None;
+    }
+    let basename_only = !line.contains(&b'/');
+    let line = line.strip_prefix(b"/").unwrap_or(line);
+    Some(Pattern {
+        pattern: line.to_vec(),
+        base: base.to_string(),
+        negated,
+        dir_only,
+        basename_only,
+    })
+}
+
+/// 通配符匹配：`*` 和 `?` 不匹配 `/`，`**/`、`/**/` 和结尾的 `/**` 可以跨越目录
+fn wildmatch(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    while p < pattern.len() {
+        match pattern[p] {
+            b'\\' if p + 1 < pattern.len() => {
+                if text.get(t) != Some(&pattern[p + 1]) {
+                    return false;
+                }
+                p += 2;
+                t += 1;
+            }
+            b'?' => {
+                if t >= text.len() || text[t] == b'/' {
+                    return false;
+                }
+                p += 1;
+                t += 1;
+            }
+            b'*' => {
+                let mut end = p;
+                while end < pattern.len() && pattern[end] == b'*' {
+                    end += 1;
+                }
+                let whole_segment = (p == 0 || pattern[p - 1] == b'/')
+                    && (end == pattern.len() || pattern[end] == b'/');
+                if end - p >= 2 && whole_segment {
+                    if end == pattern.len() {
+                        return true;
+                    }
+                    // `**/` 匹配零个或多个目录
+                    let rest = &pattern[end + 1..];
+                    if wildmatch(rest, &text[t..]) {
+                        return true;
+                    }
+                    return (t..text.len())
+                        .filter(|&i| text[i] == b'/')
+                        .any(|i| wildmatch(rest, &text[i + 1..]));
+                }
+                let rest = &pattern[end..];
+                let mut i = t;
+                loop {
+                    if wildmatch(rest, &text[i..]) {
+                        return true;
+                    }
+                    if i >= text.len() || text[i] == b'/' {
+                        return false;
+                    }
+                    i += 1;
+                }
+            }
+            b'[' => {
+                let Some(&c) = text.get(t) else {
+                    return false;
+                };
+                match match_class(&pattern[p + 1..], c) {
+                    Some((true, len)) if c != b'/' => {
+                        p += 1 + len;
+                        t += 1;
+                    }
+                    Some(_) => return false,
+                    // 没有闭合的 `[` 按普通字符处理
+                    None => {
+                        if c != b'[' {
+                            return false;
+                        }
+                        p += 1;
+                        t += 1;
+                    }
+                }
+            }
+            c => {
+                if text.get(t) != Some(&c) {
+                    return false;
+                }
+                p += 1;
+                t += 1;
+            }
+        }
+    }
+    t == text.len()
+}
+
+/// 匹配 `[...]` 字符类（不含开头的 `[`），返回是否匹配和消耗的字节数（含结尾的 `]`）
+fn match_class(class: &[u8], c: u8) -> Option<(bool, usize)> {
+    let mut i = 0;
+    let negated = matches!(class.first(), Some(b'!' | b'^'));
+    if negated {
+        i += 1;
+    }
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        let &start = class.get(i)?;
+        if start == b']' && !first {
+            return Some((matched != negated, i + 1));
+        }
+        first = false;
+        let start = if start == b'\\' {
+            i += 1;
+            *class.get(i)?
+        } else {
+            start
+        };
+        if class.get(i + 1) == Some(&b'-') && class.get(i + 2).is_some_and(|&end| end != b']') {
+            let end = class[i + 2];
+            if start <= c && c <= end {
+                matched = true;
+            }
+            i += 3;
+        } else {
+            if start == c {
+                matched = true;
+            }
+            i += 1;
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use anyhow::Context;
+use sha1::{Digest, Sha1};
+use tokio::{fs, io::AsyncWriteExt};
+
+use crate::objects::Mode;
+
+pub(crate) const INDEX_FILE: &str = ".git/index";
+/// 写暂存区时先写到这里再改名，`-a` 提交时也用它存放还没提交的暂存结果
+pub(crate) const INDEX_LOCK: &str = ".git/index.lock";
+
+/// 暂存区（`.git/index`）中的一条记录
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct IndexEntry {
+    pub(crate) ctime: (u32, u32),
+    pub(crate) mtime: (u32, u32),
+    pub(crate) dev: u32,
+    pub(crate) ino: u32,
+    pub(crate) mode: Mode,
+    pub(crate) uid: u32,
+    pub(crate) gid: u32,
+    pub(crate) size: u32,
+    pub(crate) hash: [u8; 20],
+    /// 合并阶段：0 表示正常，1/2/3 分别是冲突的共同祖先、我们的和他们的版本
+    pub(crate) stage: u8,
+    pub(crate) path: String,
+}
+
+/// 暂存区，记录按路径和阶段排序
+#[derive(Debug, Default)]
+pub(crate) struct Index {
+    pub(crate) entries: Vec<IndexEntry>,
+}
+
+impl IndexEntry {
+    /// 新的记录，metadata 是检出后工作区文件的状态，冲突记录没有
+    pub(crate) fn new(
+        path: &str,
+        mode: Mode,
+        hash: [u8; 20],
+        stage: u8,
+        metadata: Option<&std::fs::Metadata>,
+    ) -> IndexEntry {
+        let mut entry = IndexEntry {
+            ctime: (0, 0),
+            mtime: (0, 0),
+            dev: 0,
+            ino: 0,
+            mode,
+            uid: 0,
+            gid: 0,
+            size: 0,
+            hash,
+            stage,
+            path: path.to_string(),
+        };
+        #[cfg(unix)]
+        if let Some(metadata) = metadata {
+            use std::os::unix::fs::MetadataExt;
+            // 暂存区中的字段都是 32 位，超出的部分直接截断
+            entry.ctime = (metadata.ctime() as u32, metadata.ctime_nsec() as u32);
+            entry.mtime = (metadata.mtime() as u32, metadata.mtime_nsec() as u32);
+            entry.dev = metadata.dev() as u32;
+            entry.ino = metadata.ino() as u32;
+            entry.uid = metadata.uid();
+            entry.gid = metadata.gid();
+            entry.size = metadata.size() as u32;
+        }
+        #[cfg(not(unix))]
+        if let Some(metadata) = metadata {
+            entry.size = metadata.len() as u32;
+        }
+        entry
+    }
+}
+
+impl Index {
+    /// 读取 `.git/index`，文件不存在时返回空暂存区
+    pub(crate) async fn read() -> anyhow::Result<Index> {
+        Index::read_from(INDEX_FILE).await
+    }
+
+    /// 读取 path 处的暂存区文件，如 `-a` 提交时钩子看到的 `.git/index.lock`
+    pub(crate) async fn read_from(path: &str) -> anyhow::Result<Index> {
+        match fs::read(path).await {
+            Ok(data) => Index::parse(&data),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Index::default()),
+            Err(e) => Err(e).with_context(|| format!("read {path}")),
+        }
+    }
+
+    /// 解析版本 2/3 的暂存区文件，忽略扩展
+    pub(crate) fn parse(data: &[u8]) -> anyhow::Result<Index> {
+        anyhow::ensure!(data.len() >= 32, "index file too short");
+        let (body, checksum) = data.split_at(data.len() - 20);
+        anyhow::ensure!(
+            Sha1::digest(body).as_slice() == checksum,
+            "index file corrupt: bad checksum"
+        );
+        anyhow::ensure!(&body[..4] == b"DIRC", "index file has bad signature");
+        let version = be32(&body[4..8]);
+        anyhow::ensure!(
+            version == 2 || version == 3,
+            "index version {version} not supported"
+        );
+        let count = be32(&body[8..12]) as usize;
+
+        // 每条记录至少 64 字节，条目数不可信
+        let mut entries = Vec::with_capacity(count.min(body.len() / 64));
+        let mut pos = 12;
+        for _ in 0..count {
+            let start = pos;
+            let field = |n: usize| -> anyhow::Result<u32> {
+                let at = start + n * 4;
+                Ok(be32(body.get(at..at + 4).context("truncated index entry")?))
+            };
+            let raw_mode = field(6)?;
+            let hash: [u8; 20] = body
+                .get(start + 40..start + 60)
+                .context("truncated index entry")?
+                .try_into()?;
+            let flags = u16::from_be_bytes(
+                body.get(start + 60..start + 62)
+                    .context("truncated index entry")?
+                    .try_into()?,
+            );
+            pos = start + 62;
+            // 版本 3 的扩展标志多占两个字节
+            if flags & 0x4000 != 0 {
+                pos += 2;
+            }
+            // 截断或损坏的文件里路径可能越界或缺少 NUL
+            let Some(name_len) = body
+                .get(pos..)
+                .and_then(|rest| rest.iter().position(|&b| b == 0))
+            else {
+                anyhow::bail!("index file corrupt");
+            };
+            let path = std::str::from_utf8(&body[pos..pos + name_len])
+                .context("index entry path isn't valid utf-8")?
+                .to_string();
+            // 记录长度补齐到 8 的倍数，至少有一个 NUL
+            let entry_len = pos + name_len - start;
+            pos = start + (entry_len + 8) / 8 * 8;
+
+            entries.push(IndexEntry {
+                ctime: (field(0)?, field(1)?),
+                mtime: (field(2)?, field(3)?),
+                dev: field(4)?,
+                ino: field(5)?,
+                mode: Mode::from_str(&format!("{raw_mode:o}"))?,
+                uid: field(7)?,
+                gid: field(8)?,
+                size: field(9)?,
+                hash,
+                stage: ((flags >> 12) & 3) as u8,
+                path,
+            });
+        }
+        Ok(Index { entries })
+    }
+}
+
+impl Index {
+    /// 按路径和阶段排序后写入 `.git/index`（版本 2）：先写 `index.lock` 再改名
+    pub(crate) async fn write(&mut self) -> anyhow::Result<()> {
+        self.write_lock().await?;
+        let result = fs::rename(INDEX_LOCK, INDEX_FILE)
+            .await
+            .context("rename .git/index.lock");
+        if result.is_err() {
+            let _ = fs::remove_file(INDEX_LOCK).await;
+        }
+        result
+    }
+
+    /// 只写到 `index.lock`，不替换 `.git/index`。与 git 一样锁文件已存在时
+    /// 说明有别的进程在写暂存区，报错退出；写入失败时删掉自己建的锁文件
+    pub(crate) async fn write_lock(&mut self) -> anyhow::Result<()> {
+        let data = self.encode()?;
+        let mut file = match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(INDEX_LOCK)
+            .await
+        {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                anyhow::bail!("Unable to create '{INDEX_LOCK}': File exists.")
+            }
+            Err(e) => return Err(e).context("create .git/index.lock"),
+        };
+        let result = async {
+            file.write_all(&data).await?;
+            file.flush().await
+        }
+        .await
+        .context("write .git/index.lock");
+        if result.is_err() {
+            drop(file);
+            let _ = fs::remove_file(INDEX_LOCK).await;
+        }
+        result
+    }
+
+    /// 按路径和阶段排序后编码成暂存区文件的内容
+    fn encode(&mut self) -> anyhow::Result<Vec<u8>> {
+        self.entries
+            .sort_by(|a, b| a.path.cmp(&b.path).then(a.stage.cmp(&b.stage)));
+        let mut data = Vec::new();
+        data.extend_from_slice(b"DIRC");
+        data.extend_from_slice(&2u32.to_be_bytes());
+        data.extend_from_slice(&(self.entries.len() as u32).to_be_bytes());
+        for entry in &self.entries {
+            let start = data.len();
+            let mode = u32::from_str_radix(std::str::from_utf8(entry.mode.to_bytes())?, 8)?;
+            for field in [
+                entry.ctime.0,
+                entry.ctime.1,
+                entry.mtime.0,
+                entry.mtime.1,
+                entry.dev,
+                entry.ino,
+                mode,
+                entry.uid,
+                entry.gid,
+                entry.size,
+            ] {
+                data.extend_from_slice(&field.to_be_bytes());
+            }
+            data.extend_from_slice(&entry.hash);
+            // 路径长度超过 0xFFF 时记为 0xFFF
+            let flags = entry.path.len().min(0xFFF) as u16 | (u16::from(entry.stage) << 12);
+            data.extend_from_slice(&flags.to_be_bytes());
+            data.extend_from_slice(entry.path.as_bytes());
+            // 记录长度补齐到 8 的倍数，至少有一个 NUL
+            let entry_len = data.len() - start;
+            data.resize(start + (entry_len + 8) / 8 * 8, 0);
+        }
+        let checksum = Sha1::digest(&data);
+        data.extend_from_slice(&checksum);
+        Ok(data)
+    }
+
+    /// 是否有冲突中的记录
+    pub(crate) fn has_conflicts(&self) -> bool {
+        self.entries.iter().any(|entry| entry.stage > 0)
+    }
+}
+
+fn be32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes(bytes.try_into().expect("4 bytes"))
+}
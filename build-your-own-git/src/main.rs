@@ -1,9 +1,29 @@
+pub(crate) mod bundle;
 #[allow(unused_imports)]
 pub(crate) mod commands;
+pub(crate) mod config;
+pub(crate) mod diff;
+pub(crate) mod editor;
+pub(crate) mod graph;
+pub(crate) mod hook;
+pub(crate) mod ignore;
+pub(crate) mod index;
+pub(crate) mod merge;
 pub(crate) mod objects;
+pub(crate) mod pack;
+pub(crate) mod pkt_line;
+pub(crate) mod receive;
+pub(crate) mod refs;
+pub(crate) mod refspec;
+pub(crate) mod remote;
+pub(crate) mod revision;
+pub(crate) mod sequencer;
+pub(crate) mod submodule;
+pub(crate) mod transport;
+pub(crate) mod worktree;
 use std::{env, path::PathBuf};
 
-use clap::{ArgGroup, Parser, Subcommand};
+use clap::{ArgAction, ArgGroup, Args, CommandFactory, Parser, Subcommand};
 use tokio::fs;
 #[derive(Parser)]
 #[command(
@@ -78,21 +98,1095 @@ enum Commands {
         tree_sha: String,
     },
     WriteTree,
+    /// 用已有的树创建提交对象
     CommitTree {
         /// tree 对象 SHA-1 或引用
         tree_sha: String,
 
-        #[arg(short = 'm', long = "message")]
-        message: String,
+        /// 提交说明，多次给出时每个是单独的段落
+        #[arg(short = 'm', long = "message", allow_hyphen_values = true)]
+        message: Vec<String>,
 
+        /// 从文件读取提交说明，`-` 表示标准输入
+        #[arg(short = 'F', long = "file")]
+        file: Vec<String>,
+
+        /// 父提交，合并提交给出多个
         #[arg(short = 'p', long = "parent")]
-        parent: Option<String>,
+        parent: Vec<String>,
+
+        /// 用 GPG 签名，可以指定密钥
+        #[arg(
+            short = 'S',
+            long = "gpg-sign",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "",
+            overrides_with = "no_gpg_sign"
+        )]
+        gpg_sign: Option<String>,
+
+        /// 不签名，覆盖前面的 -S
+        #[arg(long = "no-gpg-sign", overrides_with = "gpg_sign")]
+        no_gpg_sign: bool,
     },
+    /// 用暂存区的内容创建提交
     Commit {
-        #[clap(short = 'm')]
-        message: String,
+        /// 提交说明，多次给出时每个是单独的段落
+        #[arg(
+            short = 'm',
+            long = "message",
+            conflicts_with = "file",
+            allow_hyphen_values = true
+        )]
+        message: Vec<String>,
+
+        /// 从文件读取提交说明，`-` 表示标准输入
+        #[arg(short = 'F', long = "file")]
+        file: Option<String>,
+
+        /// 先暂存所有已跟踪文件的修改和删除
+        #[arg(short = 'a', long = "all")]
+        all: bool,
+
+        /// 替换当前分支的最新提交
+        #[arg(long = "amend")]
+        amend: bool,
+
+        /// 允许创建与父提交内容相同的提交
+        #[arg(long = "allow-empty")]
+        allow_empty: bool,
+
+        /// 直接使用已有的说明，不打开编辑器
+        #[arg(long = "no-edit")]
+        no_edit: bool,
+
+        /// 整理提交说明的方式: strip, whitespace, verbatim, scissors, default
+        #[arg(long = "cleanup")]
+        cleanup: Option<String>,
+
+        /// 不运行 pre-commit 和 commit-msg 钩子
+        #[arg(short = 'n', long = "no-verify")]
+        no_verify: bool,
+    },
+    /// 显示提交历史
+    Log {
+        /// 在提交左侧绘制分支结构的 ASCII 图形
+        #[arg(long = "graph")]
+        graph: bool,
+
+        /// 每个提交只显示一行：短哈希和标题
+        #[arg(long = "oneline")]
+        oneline: bool,
+
+        /// 显示指向提交的引用名称: short, full, no
+        #[arg(
+            long = "decorate",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "short"
+        )]
+        decorate: Option<String>,
+
+        /// 最多显示的提交数
+        #[arg(short = 'n', long = "max-count")]
+        max_count: Option<usize>,
+
+        /// 按拓扑顺序输出
+        #[arg(long = "topo-order", conflicts_with = "date_order")]
+        topo_order: bool,
+
+        /// 按提交时间输出
+        #[arg(long = "date-order")]
+        date_order: bool,
+
+        /// 遇到合并时只跟随第一个父提交
+        #[arg(long = "first-parent")]
+        first_parent: bool,
+
+        /// 在每个提交后面输出增删行数的统计
+        #[arg(long = "stat")]
+        stat: bool,
+
+        #[command(flatten)]
+        renames: RenameArgs,
+
+        /// 起始提交，支持 `A..B` 和 `^A`，默认 HEAD
+        revisions: Vec<String>,
+    },
+    /// 按时间倒序列出可达的提交
+    RevList {
+        /// 同时列出提交引用的树和 blob 及其路径
+        #[arg(long = "objects")]
+        objects: bool,
+
+        /// 只输出提交数量
+        #[arg(long = "count")]
+        count: bool,
+
+        /// 标记对称差中提交属于哪一侧（`<` 左侧，`>` 右侧）
+        #[arg(long = "left-right")]
+        left_right: bool,
+
+        /// 缺失对象的处理方式: error, allow-any, print
+        #[arg(long = "missing", default_value = "error")]
+        missing: String,
+
+        /// 省略对象: blob:none, blob:limit=<n>, tree:<depth>
+        #[arg(long = "filter")]
+        filter: Option<String>,
+
+        /// 以 `~` 前缀输出被过滤器省略的对象
+        #[arg(long = "filter-print-omitted")]
+        filter_print_omitted: bool,
+
+        /// 把所有引用和 HEAD 作为起点
+        #[arg(long = "all")]
+        all: bool,
+
+        /// 把所有分支作为起点
+        #[arg(long = "branches")]
+        branches: bool,
+
+        /// 把所有标签作为起点
+        #[arg(long = "tags")]
+        tags: bool,
+
+        /// 最多输出的提交数
+        #[arg(short = 'n', long = "max-count")]
+        max_count: Option<usize>,
+
+        /// 起始提交，支持 `A..B`、`A...B` 和 `^A`
+        revisions: Vec<String>,
+    },
+    /// 比较两棵树，或比较提交和它的父提交
+    #[command(group(
+        ArgGroup::new("format")
+            .required(false)
+            .args(&["raw", "name_only", "name_status"])
+    ))]
+    DiffTree {
+        /// 递归进入子树
+        #[arg(short = 'r')]
+        recursive: bool,
+
+        /// 根提交和空树比较
+        #[arg(long = "root")]
+        root: bool,
+
+        /// 只输出路径
+        #[arg(long = "name-only")]
+        name_only: bool,
+
+        /// 输出状态字母和路径
+        #[arg(long = "name-status")]
+        name_status: bool,
+
+        /// 输出模式、哈希、状态和路径（默认）
+        #[arg(long = "raw")]
+        raw: bool,
+
+        #[command(flatten)]
+        renames: RenameArgs,
+
+        /// 提交，或者旧的树
+        old: String,
+
+        /// 新的树
+        new: Option<String>,
+    },
+    /// 比较工作区、暂存区和提交之间的差异
+    #[command(group(
+        ArgGroup::new("algorithm")
+            .required(false)
+            .args(&["minimal", "patience", "histogram", "diff_algorithm"])
+    ))]
+    Diff {
+        /// 比较暂存区和提交（默认 HEAD）
+        #[arg(long = "cached", visible_alias = "staged")]
+        cached: bool,
+
+        /// 上下文行数
+        #[arg(short = 'U', long = "unified", default_value_t = 3)]
+        unified: usize,
+
+        /// 把修改所在的整个函数作为上下文
+        #[arg(short = 'W', long = "function-context")]
+        function_context: bool,
+
+        /// 忽略空白数量的变化
+        #[arg(short = 'b', long = "ignore-space-change")]
+        ignore_space_change: bool,
+
+        /// 忽略所有空白
+        #[arg(short = 'w', long = "ignore-all-space")]
+        ignore_all_space: bool,
+
+        /// 花更多时间保证差异最小
+        #[arg(long = "minimal")]
+        minimal: bool,
+
+        /// 使用 patience 算法
+        #[arg(long = "patience")]
+        patience: bool,
+
+        /// 使用 histogram 算法
+        #[arg(long = "histogram")]
+        histogram: bool,
+
+        /// 差异算法: myers, minimal, patience, histogram
+        #[arg(long = "diff-algorithm")]
+        diff_algorithm: Option<String>,
+
+        /// 输出每个文件增删行数的统计
+        #[arg(long = "stat")]
+        stat: bool,
+
+        #[command(flatten)]
+        renames: RenameArgs,
+
+        /// 提交或 `A..B`、`A...B`，之后可以跟路径
+        args: Vec<String>,
+
+        /// `--` 之后的路径
+        #[arg(last = true)]
+        paths: Vec<String>,
+    },
+    /// 显示暂存区和工作区的状态
+    Status {
+        /// 短格式输出
+        #[arg(short = 's', long = "short")]
+        short: bool,
+
+        /// 供脚本解析的输出，格式同短格式
+        #[arg(long = "porcelain")]
+        porcelain: bool,
+
+        /// 短格式中也显示分支
+        #[arg(short = 'b', long = "branch")]
+        branch: bool,
+
+        /// 未跟踪文件的显示方式: no, normal, all
+        #[arg(
+            short = 'u',
+            long = "untracked-files",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "all"
+        )]
+        untracked_files: Option<String>,
+
+        /// 检测暂存区中的重命名，可以带相似度阈值
+        #[arg(
+            short = 'M',
+            long = "find-renames",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = ""
+        )]
+        find_renames: Option<String>,
+
+        /// 关闭重命名检测
+        #[arg(long = "no-renames")]
+        no_renames: bool,
+    },
+    /// 三方合并文件：把 base 到 other 的修改合入 current
+    #[command(group(
+        ArgGroup::new("favor")
+            .required(false)
+            .args(&["ours", "theirs", "union"])
+    ))]
+    MergeFile {
+        /// 输出到标准输出而不是覆盖 current
+        #[arg(short = 'p', long = "stdout")]
+        stdout: bool,
+
+        /// 冲突中同时显示 base 的内容
+        #[arg(long = "diff3", conflicts_with = "zdiff3")]
+        diff3: bool,
+
+        /// 同 --diff3，但把双方相同的行移出冲突
+        #[arg(long = "zdiff3")]
+        zdiff3: bool,
+
+        /// 冲突时取 current 的内容
+        #[arg(long = "ours")]
+        ours: bool,
+
+        /// 冲突时取 other 的内容
+        #[arg(long = "theirs")]
+        theirs: bool,
+
+        /// 冲突时保留双方的内容
+        #[arg(long = "union")]
+        union: bool,
+
+        /// 冲突标记的长度
+        #[arg(long = "marker-size", default_value_t = merge::DEFAULT_MARKER_SIZE)]
+        marker_size: usize,
+
+        /// 不输出警告
+        #[arg(short = 'q', long = "quiet")]
+        _quiet: bool,
+
+        /// 冲突标记中使用的名称，依次对应 current、base、other
+        #[arg(short = 'L', action = ArgAction::Append)]
+        labels: Vec<String>,
+
+        current: String,
+        base: String,
+        other: String,
+    },
+    /// 把已有提交引入的修改应用到当前分支
+    CherryPick {
+        /// 在提交说明末尾加上 `(cherry picked from commit ...)`
+        #[arg(short = 'x')]
+        record_origin: bool,
+
+        #[command(flatten)]
+        args: SequencerArgs,
+    },
+    /// 创建撤销已有提交修改的新提交
+    Revert {
+        #[command(flatten)]
+        args: SequencerArgs,
+    },
+    /// 把当前分支的提交重放到新的基础上
+    #[command(group(
+        ArgGroup::new("action")
+            .args(&["continue_rebase", "skip", "abort", "edit_todo"])
+            .conflicts_with_all(&["interactive", "onto", "autosquash", "update_refs", "upstream"])
+    ))]
+    Rebase {
+        /// 先编辑待办列表再开始
+        #[arg(short = 'i', long = "interactive")]
+        interactive: bool,
+
+        /// 重放到这个提交上，而不是 upstream
+        #[arg(long = "onto")]
+        onto: Option<String>,
+
+        /// 把 `fixup!`、`squash!`、`amend!` 提交移到它们修正的提交后面
+        #[arg(long = "autosquash")]
+        autosquash: bool,
+
+        /// 同时更新指向被重放提交的其他分支
+        #[arg(long = "update-refs")]
+        update_refs: bool,
+
+        /// 不运行 pre-rebase 钩子
+        #[arg(long = "no-verify")]
+        no_verify: bool,
+
+        /// 解决冲突或修改完成后继续
+        #[arg(long = "continue")]
+        continue_rebase: bool,
+
+        /// 跳过当前提交，继续处理剩下的
+        #[arg(long = "skip")]
+        skip: bool,
+
+        /// 放弃变基，回到开始前的分支
+        #[arg(long = "abort")]
+        abort: bool,
+
+        /// 编辑剩下的待办列表
+        #[arg(long = "edit-todo")]
+        edit_todo: bool,
+
+        /// 新的上游，重放 upstream..HEAD 中的提交
+        #[arg(required_unless_present_any = ["continue_rebase", "skip", "abort", "edit_todo"])]
+        upstream: Option<String>,
+
+        /// 先切换到这个分支
+        #[arg(requires = "upstream")]
+        branch: Option<String>,
+    },
+    /// 找出提交之间的最佳公共祖先
+    #[command(group(
+        ArgGroup::new("mode").args(&["octopus", "is_ancestor", "fork_point"])
+    ))]
+    MergeBase {
+        /// 输出所有最佳公共祖先，而不只是第一个
+        #[arg(short = 'a', long = "all", conflicts_with_all = ["is_ancestor", "fork_point"])]
+        all: bool,
+
+        /// 所有提交共同的最佳公共祖先
+        #[arg(long = "octopus")]
+        octopus: bool,
+
+        /// 第一个提交是否是第二个的祖先，结果只体现在退出码上
+        #[arg(long = "is-ancestor")]
+        is_ancestor: bool,
+
+        /// 根据 reflog 找出提交从引用分叉的位置
+        #[arg(long = "fork-point")]
+        fork_point: bool,
+
+        #[arg(required = true)]
+        commits: Vec<String>,
+    },
+    /// 把另一个提交合并到当前分支
+    #[command(group(
+        ArgGroup::new("action")
+            .args(&["continue_merge", "abort"])
+            .conflicts_with_all(&["commit", "message", "no_ff", "ff_only", "no_commit"])
+    ))]
+    Merge {
+        /// 合并提交的说明
+        #[arg(short = 'm', allow_hyphen_values = true)]
+        message: Option<String>,
+
+        /// 能快进时也创建合并提交
+        #[arg(long = "no-ff", conflicts_with = "ff_only")]
+        no_ff: bool,
+
+        /// 只允许快进
+        #[arg(long = "ff-only")]
+        ff_only: bool,
+
+        /// 合并成功后不提交
+        #[arg(long = "no-commit")]
+        no_commit: bool,
+
+        /// 不运行 pre-merge-commit 和 commit-msg 钩子
+        #[arg(long = "no-verify")]
+        no_verify: bool,
+
+        /// 解决冲突后创建合并提交
+        #[arg(long = "continue")]
+        continue_merge: bool,
+
+        /// 放弃合并，恢复到合并前的状态
+        #[arg(long = "abort")]
+        abort: bool,
+
+        /// 要合并的提交
+        #[arg(required_unless_present_any = ["continue_merge", "abort"])]
+        commit: Option<String>,
+    },
+    /// 把工作区和暂存区的修改保存起来，恢复到干净的状态
+    #[command(args_conflicts_with_subcommands = true)]
+    Stash {
+        #[command(subcommand)]
+        command: Option<StashCommand>,
+
+        /// 没有子命令时同 `stash push`
+        #[command(flatten)]
+        push: StashPushArgs,
+    },
+    /// 按 .gitmodules 初始化、检出子模块，查看子模块的状态
+    Submodule {
+        #[command(subcommand)]
+        command: Option<SubmoduleCommand>,
+    },
+    /// 克隆仓库，源可以是路径、file:// URL 或 ssh 地址
+    Clone {
+        /// 建立裸仓库
+        #[arg(long = "bare")]
+        bare: bool,
+
+        /// 建立裸仓库并镜像远程的所有引用
+        #[arg(long = "mirror")]
+        mirror: bool,
+
+        /// 检出这个分支或标签，而不是远程 HEAD 指向的分支
+        #[arg(short = 'b', long = "branch")]
+        branch: Option<String>,
+
+        /// 只取最近的 n 个提交，本地路径时忽略
+        #[arg(long = "depth")]
+        depth: Option<usize>,
+
+        /// 部分克隆，如 blob:none 时只在检出时取需要的文件内容，本地路径时忽略
+        #[arg(long = "filter", value_name = "filter-spec")]
+        filter: Option<String>,
+
+        /// 本地路径时硬链接对象文件，这是默认的
+        #[arg(short = 'l', long = "local")]
+        _local: bool,
+
+        /// 本地路径也像 URL 一样只复制需要的对象
+        #[arg(long = "no-local")]
+        no_local: bool,
+
+        /// 本地路径时复制对象文件而不是硬链接
+        #[arg(long = "no-hardlinks")]
+        no_hardlinks: bool,
+
+        /// 克隆后不检出
+        #[arg(short = 'n', long = "no-checkout")]
+        no_checkout: bool,
+
+        /// 不输出进度信息
+        #[arg(short = 'q', long = "quiet")]
+        quiet: bool,
+
+        repository: String,
+
+        directory: Option<String>,
     },
+    /// 管理远程仓库，没有子命令时列出所有远程
+    Remote {
+        /// 同时列出远程的 URL
+        #[arg(short = 'v', long = "verbose")]
+        verbose: bool,
+
+        #[command(subcommand)]
+        command: Option<RemoteCommand>,
+    },
+    /// 从另一个仓库取回对象，更新远程跟踪分支和 FETCH_HEAD
+    Fetch {
+        /// 删除远程已经没有的远程跟踪分支
+        #[arg(short = 'p', long = "prune")]
+        prune: bool,
+
+        /// 取回远程的所有标签
+        #[arg(short = 't', long = "tags")]
+        tags: bool,
+
+        /// 不是快进时也强制更新本地引用
+        #[arg(short = 'f', long = "force")]
+        force: bool,
+
+        /// 不输出更新了哪些引用
+        #[arg(short = 'q', long = "quiet")]
+        quiet: bool,
+
+        /// 远程名称或仓库路径，默认是当前分支的远程或 origin
+        remote: Option<String>,
+
+        refspecs: Vec<String>,
+    },
+    /// 列出远程仓库的引用
+    LsRemote {
+        /// 只列出分支
+        #[arg(long = "heads", visible_alias = "branches")]
+        heads: bool,
+
+        /// 只列出标签
+        #[arg(short = 't', long = "tags")]
+        tags: bool,
+
+        /// 不列出剥离后的标签和 HEAD
+        #[arg(long = "refs")]
+        refs: bool,
+
+        /// 同时列出符号引用指向的引用
+        #[arg(long = "symref")]
+        symref: bool,
+
+        /// 不输出远程的 URL
+        #[arg(short = 'q', long = "quiet")]
+        quiet: bool,
+
+        /// 没有匹配的引用时退出码为 2
+        #[arg(long = "exit-code")]
+        exit_code: bool,
+
+        /// 远程名称或 URL，默认是当前分支的远程或 origin
+        repository: Option<String>,
+
+        /// 只列出末尾若干级与之匹配的引用
+        patterns: Vec<String>,
+    },
+    /// 把本地引用和需要的对象推送到另一个仓库
+    Push {
+        /// 不是快进时也强制更新远程引用
+        #[arg(short = 'f', long = "force")]
+        force: bool,
+
+        /// 远程引用的当前值与期望的一致时才强制更新，期望值默认是远程跟踪分支
+        #[arg(
+            long = "force-with-lease",
+            value_name = "refname[:expect]",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = ""
+        )]
+        force_with_lease: Vec<String>,
+
+        /// 删除远程的这些引用
+        #[arg(short = 'd', long = "delete")]
+        delete: bool,
+
+        /// 推送所有标签
+        #[arg(long = "tags")]
+        tags: bool,
+
+        /// 有一个引用不能更新时都不更新
+        #[arg(long = "atomic")]
+        atomic: bool,
+
+        /// 不运行 pre-push 钩子
+        #[arg(long = "no-verify")]
+        no_verify: bool,
+
+        /// 把推送的分支设为本地分支的上游
+        #[arg(short = 'u', long = "set-upstream")]
+        set_upstream: bool,
+
+        /// 不输出更新了哪些引用
+        #[arg(short = 'q', long = "quiet")]
+        quiet: bool,
+
+        /// 远程名称或仓库路径，默认是当前分支的远程或 origin
+        repository: Option<String>,
+
+        refspecs: Vec<String>,
+    },
+    /// 服务端：把仓库中的对象发送给 fetch 或 clone 的客户端
+    UploadPack {
+        /// 只处理一个请求，用于智能 HTTP
+        #[arg(long = "stateless-rpc")]
+        stateless_rpc: bool,
+
+        /// 只输出引用通告或协议 v2 的能力
+        #[arg(long = "advertise-refs", visible_alias = "http-backend-info-refs")]
+        advertise_refs: bool,
+
+        /// 不尝试 <directory>/.git（接受但忽略）
+        #[arg(long = "strict")]
+        strict: bool,
+
+        /// 等待客户端的超时秒数（接受但忽略）
+        #[arg(long = "timeout", value_name = "n")]
+        timeout: Option<u64>,
+
+        /// 仓库目录
+        directory: String,
+    },
+    /// 服务端：接收 push 的客户端发来的对象并更新引用
+    ReceivePack {
+        /// 只处理一个请求，用于智能 HTTP
+        #[arg(long = "stateless-rpc")]
+        stateless_rpc: bool,
+
+        /// 只输出引用通告
+        #[arg(long = "advertise-refs", visible_alias = "http-backend-info-refs")]
+        advertise_refs: bool,
+
+        /// 仓库目录
+        directory: String,
+    },
+    /// 经由智能 HTTP 和 git:// 协议提供一个目录下的仓库
+    Serve {
+        /// 监听的地址
+        #[arg(long = "listen", default_value = "127.0.0.1")]
+        listen: String,
+
+        /// 智能 HTTP 的端口，0 表示由系统分配。与 --git-port 都没有指定时两种协议都提供
+        #[arg(long = "http-port", value_name = "port")]
+        http_port: Option<u16>,
+
+        /// git:// 协议的端口，0 表示由系统分配
+        #[arg(long = "git-port", value_name = "port")]
+        git_port: Option<u16>,
+
+        /// 提供所有仓库，而不只是有 git-daemon-export-ok 文件的
+        #[arg(long = "export-all")]
+        export_all: bool,
+
+        /// 只读，不接受推送
+        #[arg(long = "read-only")]
+        read_only: bool,
+
+        /// 同时处理的连接数上限，超过的连接被拒绝
+        #[arg(long = "max-connections", value_name = "n", default_value_t = 32)]
+        max_connections: usize,
+
+        /// HTTP 请求体的大小上限（字节），超过的请求回复 413
+        #[arg(long = "max-request-size", value_name = "bytes", default_value_t = 100 << 20)]
+        max_request_size: usize,
+
+        /// 仓库所在的目录，请求的路径相对于它
+        #[arg(default_value = ".")]
+        directory: String,
+    },
+    /// 给 pack 文件建索引，写出 .idx 和 .rev
+    IndexPack {
+        /// 索引文件的路径，默认把 pack 文件名的 .pack 换成 .idx
+        #[arg(short = 'o', value_name = "index-file")]
+        output: Option<PathBuf>,
+
+        /// 从标准输入读取 pack，写到 <pack-file> 或仓库的 objects/pack 中
+        #[arg(long = "stdin")]
+        stdin: bool,
+
+        /// 从本仓库补上 thin pack 缺少的基对象，需要 --stdin
+        #[arg(long = "fix-thin")]
+        fix_thin: bool,
+
+        /// 同时写出 .keep 文件，可以指定其中的消息
+        #[arg(
+            long = "keep",
+            value_name = "msg",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = ""
+        )]
+        keep: Option<String>,
+
+        /// 写出 .rev 文件
+        #[arg(long = "rev-index", overrides_with = "no_rev_index")]
+        rev_index: bool,
+
+        /// 不写出 .rev 文件
+        #[arg(long = "no-rev-index", overrides_with = "rev_index")]
+        no_rev_index: bool,
+
+        /// 只检查已有的 .idx 与 pack 是否一致
+        #[arg(long = "verify")]
+        verify: bool,
+
+        /// pack 文件
+        pack_file: Option<PathBuf>,
+    },
+    /// 检查 pack 文件与它的 .idx 是否一致
+    VerifyPack {
+        /// 列出每个对象以及 delta 链长度的分布
+        #[arg(short = 'v', long = "verbose")]
+        verbose: bool,
+
+        /// 只输出 delta 链长度的分布
+        #[arg(short = 's', long = "stat-only")]
+        stat_only: bool,
+
+        /// pack 的 .idx 文件
+        #[arg(required = true, value_name = "pack")]
+        packs: Vec<String>,
+    },
+    /// 创建、检查和解开 bundle 文件，用于在不能直接连接的仓库之间传输
+    Bundle {
+        #[command(subcommand)]
+        command: BundleCommand,
+    },
+}
+
+/// bundle 的子命令
+#[derive(Subcommand, Debug)]
+enum BundleCommand {
+    /// 把 rev-list 参数选中的提交和引用写成 bundle
+    Create {
+        /// 不输出进度（接受但忽略）
+        #[arg(short = 'q', long = "quiet")]
+        _quiet: bool,
+
+        /// bundle 格式的版本，2 或 3
+        #[arg(long = "version")]
+        version: Option<u32>,
+
+        /// 写出的文件，`-` 表示标准输出
+        file: String,
+
+        /// rev-list 的参数，如 `--all`、`main`、`v1..main`
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// 检查 bundle 能否在当前仓库中使用
+    Verify {
+        /// 只输出检查结果
+        #[arg(short = 'q', long = "quiet")]
+        quiet: bool,
+
+        file: String,
+    },
+    /// 列出 bundle 中的引用
+    ListHeads { file: String, refnames: Vec<String> },
+    /// 把 bundle 中的对象放进当前仓库并列出其中的引用
+    Unbundle { file: String, refnames: Vec<String> },
+}
+
+/// remote 的子命令
+#[derive(Subcommand, Debug)]
+enum RemoteCommand {
+    /// 添加远程
+    Add {
+        /// 添加后随即取回
+        #[arg(short = 'f', long = "fetch")]
+        fetch: bool,
+
+        name: String,
+
+        url: String,
+    },
+    /// 删除远程和它的远程跟踪分支
+    #[command(alias = "rm")]
+    Remove { name: String },
+    /// 给远程改名
+    Rename { old: String, new: String },
+    /// 修改远程的 URL
+    SetUrl { name: String, url: String },
+}
+
+/// submodule 的子命令
+#[derive(Subcommand, Debug)]
+enum SubmoduleCommand {
+    /// 把 .gitmodules 中子模块的 URL 写入 .git/config
+    Init { paths: Vec<String> },
+    /// 克隆子模块并检出超项目记录的提交
+    Update {
+        /// 先初始化还没有初始化的子模块
+        #[arg(long = "init")]
+        init: bool,
+
+        paths: Vec<String>,
+    },
+    /// 显示子模块检出的提交，没有子命令时这是默认的
+    Status { paths: Vec<String> },
+}
+
+/// stash 的子命令
+#[derive(Subcommand, Debug)]
+enum StashCommand {
+    /// 保存修改并恢复到 HEAD
+    Push(StashPushArgs),
+    /// 列出所有 stash
+    List,
+    /// 显示 stash 相对它所基于的提交的修改
+    Show {
+        /// 输出 --stat 统计，不带 -p 时这是默认的
+        #[arg(long = "stat")]
+        stat: bool,
+
+        /// 输出补丁
+        #[arg(short = 'p', long = "patch")]
+        patch: bool,
+
+        stash: Option<String>,
+    },
+    /// 把 stash 中的修改应用到工作区
+    Apply {
+        #[command(flatten)]
+        args: StashApplyArgs,
+    },
+    /// 应用 stash 并删除它
+    Pop {
+        #[command(flatten)]
+        args: StashApplyArgs,
+    },
+    /// 删除一个 stash
+    Drop {
+        #[arg(short = 'q', long = "quiet")]
+        quiet: bool,
+
+        stash: Option<String>,
+    },
+    /// 删除所有 stash
+    Clear,
+    /// 在 stash 所基于的提交上创建新分支并应用 stash
+    Branch {
+        branch: String,
+        stash: Option<String>,
+    },
+}
+
+/// `stash push` 的选项
+#[derive(Args, Debug)]
+struct StashPushArgs {
+    /// 暂存区的修改保存后仍然保留
+    #[arg(short = 'k', long = "keep-index")]
+    keep_index: bool,
+
+    /// 同时保存并删除未跟踪的文件
+    #[arg(short = 'u', long = "include-untracked")]
+    include_untracked: bool,
+
+    /// stash 的说明
+    #[arg(short = 'm', long = "message", allow_hyphen_values = true)]
+    message: Option<String>,
+
+    #[arg(short = 'q', long = "quiet")]
+    quiet: bool,
+
+    /// 只保存这些路径
+    paths: Vec<String>,
+}
+
+impl StashPushArgs {
+    fn options(self) -> commands::stash::PushOptions {
+        commands::stash::PushOptions {
+            keep_index: self.keep_index,
+            include_untracked: self.include_untracked,
+            message: self.message,
+            quiet: self.quiet,
+            paths: self.paths,
+        }
+    }
+}
+
+/// `stash apply` 和 `stash pop` 共用的选项
+#[derive(Args, Debug)]
+struct StashApplyArgs {
+    /// 同时恢复暂存区
+    #[arg(long = "index")]
+    index: bool,
+
+    #[arg(short = 'q', long = "quiet")]
+    quiet: bool,
+
+    stash: Option<String>,
+}
+
+/// cherry-pick 和 revert 共用的选项
+#[derive(Args, Debug)]
+struct SequencerArgs {
+    /// 处理合并提交时以第几个父提交为基准
+    #[arg(short = 'm', long = "mainline")]
+    mainline: Option<usize>,
+
+    /// 只更新暂存区和工作区，不提交
+    #[arg(short = 'n', long = "no-commit")]
+    no_commit: bool,
+
+    /// 解决冲突后继续
+    #[arg(long = "continue", conflicts_with_all = ["abort", "skip", "commits"])]
+    continue_sequence: bool,
+
+    /// 跳过当前提交，继续处理剩下的
+    #[arg(long = "skip", conflicts_with_all = ["abort", "commits"])]
+    skip: bool,
+
+    /// 放弃操作，回到开始前的状态
+    #[arg(long = "abort", conflicts_with = "commits")]
+    abort: bool,
+
+    /// 要处理的提交，可以是 `A..B` 这样的范围
+    #[arg(required_unless_present_any = ["continue_sequence", "skip", "abort"])]
+    commits: Vec<String>,
+}
+
+impl SequencerArgs {
+    /// 执行 cherry-pick 或 revert，全部完成时返回 true
+    async fn run(self, action: sequencer::Action, record_origin: bool) -> anyhow::Result<bool> {
+        if self.continue_sequence {
+            return commands::cherry_pick::continue_sequence(action).await;
+        }
+        if self.skip {
+            return commands::cherry_pick::skip(action).await;
+        }
+        if self.abort {
+            commands::cherry_pick::abort().await?;
+            return Ok(true);
+        }
+        let options = sequencer::ReplayOptions {
+            mainline: self.mainline,
+            record_origin,
+            no_commit: self.no_commit,
+        };
+        commands::cherry_pick::invoke(action, &self.commits, options).await
+    }
+}
+
+/// 重命名和复制检测的选项，diff、diff-tree 和 log 共用
+#[derive(Args, Debug)]
+struct RenameArgs {
+    /// 检测重命名，可以带相似度阈值，如 -M90%（默认 50%）
+    #[arg(
+        short = 'M',
+        long = "find-renames",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = ""
+    )]
+    find_renames: Option<String>,
+
+    /// 同时检测复制，重复两次相当于 --find-copies-harder
+    #[arg(
+        short = 'C',
+        long = "find-copies",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "",
+        action = ArgAction::Append
+    )]
+    find_copies: Vec<String>,
+
+    /// 把没有修改的文件也作为复制的来源
+    #[arg(long = "find-copies-harder")]
+    find_copies_harder: bool,
+
+    /// 关闭重命名检测
+    #[arg(long = "no-renames")]
+    no_renames: bool,
 }
+
+impl RenameArgs {
+    fn options(
+        &self,
+        base: diff::rename::RenameOptions,
+    ) -> anyhow::Result<diff::rename::RenameOptions> {
+        diff::rename::RenameOptions::from_args(
+            base,
+            self.find_renames.as_deref(),
+            &self.find_copies,
+            self.find_copies_harder,
+            self.no_renames,
+        )
+    }
+}
+
+/// git 的 `-M90%`、`-C50`、`-uno`、`-S<keyid>` 把可选的值直接接在选项后面，clap 只认 `-M=90%`。
+/// 按子命令的定义逐个查看参数，只改写当前子命令中要求 `=` 的短选项，
+/// 跳过其他选项的值（如 `-m "-M1 rename"`）和 `--` 之后的参数
+fn attach_values(mut args: impl Iterator<Item = String>) -> Vec<String> {
+    let command = Cli::command();
+    let mut current = &command;
+    let mut out = Vec::new();
+    out.extend(args.next());
+    // 需要单独的值的选项：值是下一个参数，不能当作选项改写。没有设置 num_args 的取一个值
+    let separate_value = |arg: &clap::Arg| {
+        arg.get_action().takes_values()
+            && !arg.is_require_equals_set()
+            && !matches!(arg.get_num_args(), Some(range) if range.min_values() == 0)
+    };
+    while let Some(mut arg) = args.next() {
+        if arg == "--" {
+            out.push(arg);
+            out.extend(args);
+            break;
+        }
+        let mut value_next = false;
+        if let Some(long) = arg.strip_prefix("--") {
+            value_next = !long.contains('=')
+                && current
+                    .get_arguments()
+                    .any(|option| option.get_long() == Some(long) && separate_value(option));
+        } else if let Some(shorts) = arg.strip_prefix('-').filter(|shorts| !shorts.is_empty()) {
+            // 依次是开关，直到第一个带值的选项，剩下的是它的值
+            let mut rewritten = String::from("-");
+            for (at, flag) in shorts.char_indices() {
+                let rest = &shorts[at + flag.len_utf8()..];
+                rewritten.push(flag);
+                let Some(option) = current
+                    .get_arguments()
+                    .find(|option| option.get_short() == Some(flag))
+                else {
+                    rewritten.push_str(rest);
+                    break;
+                };
+                if !option.get_action().takes_values() {
+                    continue;
+                }
+                if option.is_require_equals_set() && !rest.is_empty() && !rest.starts_with('=') {
+                    rewritten.push('=');
+                }
+                value_next = rest.is_empty() && separate_value(option);
+                rewritten.push_str(rest);
+                break;
+            }
+            arg = rewritten;
+        } else if let Some(subcommand) = current.find_subcommand(&arg) {
+            current = subcommand;
+        }
+        out.push(arg);
+        if value_next {
+            out.extend(args.next());
+        }
+    }
+    out
+}
+
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
     // You can use print statements as follows for debugging, they'll be visible
@@ -102,7 +1196,7 @@ async fn main() -> Result<(), anyhow::Error> {
     let _program = args.next(); // 跳过程序名
     let _first_arg = args.next(); // 获取用户输入的第一个参数（可能是子命令）
 
-    let cli = Cli::parse();
+    let cli = Cli::parse_from(attach_values(env::args()));
     match cli.command {
         Some(Commands::Init) => {
             fs::create_dir(".git").await?;
@@ -144,13 +1238,634 @@ async fn main() -> Result<(), anyhow::Error> {
         Some(Commands::CommitTree {
             tree_sha,
             message,
+            file,
             parent,
+            gpg_sign,
+            no_gpg_sign,
         }) => {
-            let hash = commands::commit::invoke_commit_tree(tree_sha, message, parent).await?;
+            let options = commands::commit::CommitTreeOptions {
+                parents: parent,
+                messages: message,
+                files: file,
+                sign: gpg_sign.filter(|_| !no_gpg_sign),
+            };
+            let hash = commands::commit::invoke_commit_tree(&tree_sha, options).await?;
+            // 与 git 一样只输出一次新提交的哈希
             println!("{}", hex::encode(hash));
         }
-        Some(Commands::Commit { message }) => {
-            commands::commit::invoke_commit(message).await?;
+        Some(Commands::Commit {
+            message,
+            file,
+            all,
+            amend,
+            allow_empty,
+            no_edit,
+            cleanup,
+            no_verify,
+        }) => {
+            let options = commands::commit::CommitOptions {
+                messages: message,
+                file,
+                all,
+                amend,
+                allow_empty,
+                no_edit,
+                cleanup: match cleanup {
+                    Some(mode) => commands::commit::Cleanup::from_str(&mode)?,
+                    None => commands::commit::Cleanup::Default,
+                },
+                no_verify,
+            };
+            // 没有要提交的修改时退出码为 1
+            if !commands::commit::invoke_commit(options).await? {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Log {
+            graph,
+            oneline,
+            decorate,
+            max_count,
+            topo_order,
+            date_order,
+            first_parent,
+            stat,
+            renames,
+            revisions,
+        }) => {
+            let sort = match (topo_order, date_order) {
+                (true, _) => Some(revision::Sort::Topo),
+                (_, true) => Some(revision::Sort::Date),
+                _ => None,
+            };
+            let options = commands::log::LogOptions {
+                graph,
+                oneline,
+                decorate: match decorate {
+                    Some(style) => commands::log::Decorate::from_str(&style)?,
+                    None => commands::log::Decorate::No,
+                },
+                max_count,
+                sort,
+                first_parent,
+                stat,
+                renames: renames.options(diff::rename::RenameOptions::renames())?,
+            };
+            commands::log::invoke(&revisions, options).await?;
+        }
+        Some(Commands::RevList {
+            objects,
+            count,
+            left_right,
+            missing,
+            filter,
+            filter_print_omitted,
+            all,
+            branches,
+            tags,
+            max_count,
+            revisions,
+        }) => {
+            let options = commands::rev_list::RevListOptions {
+                objects,
+                count,
+                left_right,
+                missing: revision::list_objects::MissingAction::parse(&missing)?,
+                filter: filter
+                    .as_deref()
+                    .map(revision::list_objects::Filter::parse)
+                    .transpose()?,
+                filter_print_omitted,
+                all,
+                branches,
+                tags,
+                max_count,
+            };
+            commands::rev_list::invoke(&revisions, options).await?;
+        }
+        Some(Commands::DiffTree {
+            recursive,
+            root,
+            name_only,
+            name_status,
+            raw: _,
+            renames,
+            old,
+            new,
+        }) => {
+            let format = if name_only {
+                commands::diff_tree::Format::NameOnly
+            } else if name_status {
+                commands::diff_tree::Format::NameStatus
+            } else {
+                commands::diff_tree::Format::Raw
+            };
+            let options = commands::diff_tree::DiffTreeOptions {
+                recursive,
+                root,
+                format,
+                renames: renames.options(diff::rename::RenameOptions::default())?,
+            };
+            commands::diff_tree::invoke(&old, new.as_deref(), options).await?;
+        }
+        Some(Commands::Diff {
+            cached,
+            unified,
+            function_context,
+            ignore_space_change,
+            ignore_all_space,
+            minimal,
+            patience,
+            histogram,
+            diff_algorithm,
+            stat,
+            renames,
+            args,
+            paths,
+        }) => {
+            use diff::lines::{Algorithm, Whitespace};
+            let algorithm = match diff_algorithm {
+                Some(name) => Algorithm::from_str(&name)?,
+                None if minimal => Algorithm::Minimal,
+                None if patience => Algorithm::Patience,
+                None if histogram => Algorithm::Histogram,
+                None => Algorithm::Myers,
+            };
+            let whitespace = if ignore_all_space {
+                Whitespace::IgnoreAll
+            } else if ignore_space_change {
+                Whitespace::IgnoreChange
+            } else {
+                Whitespace::Exact
+            };
+            let options = commands::diff::DiffOptions {
+                cached,
+                patch: diff::patch::PatchOptions {
+                    unified: diff::unified::UnifiedOptions {
+                        context: unified,
+                        function_context,
+                    },
+                    line: diff::lines::LineDiffOptions {
+                        algorithm,
+                        whitespace,
+                        indent_heuristic: true,
+                    },
+                },
+                renames: renames.options(diff::rename::RenameOptions::renames())?,
+                stat,
+            };
+            commands::diff::invoke(&args, &paths, options).await?;
+        }
+        Some(Commands::Status {
+            short,
+            porcelain,
+            branch,
+            untracked_files,
+            find_renames,
+            no_renames,
+        }) => {
+            let format = if porcelain {
+                commands::status::Format::Porcelain
+            } else if short {
+                commands::status::Format::Short
+            } else {
+                commands::status::Format::Long
+            };
+            let options = commands::status::StatusOptions {
+                format,
+                branch,
+                untracked: match untracked_files {
+                    Some(mode) => commands::status::Untracked::from_str(&mode)?,
+                    None => commands::status::Untracked::Normal,
+                },
+                renames: diff::rename::RenameOptions::from_args(
+                    diff::rename::RenameOptions::renames(),
+                    find_renames.as_deref(),
+                    &[],
+                    false,
+                    no_renames,
+                )?,
+            };
+            commands::status::invoke(options).await?;
+        }
+        Some(Commands::MergeFile {
+            stdout,
+            diff3,
+            zdiff3,
+            ours,
+            theirs,
+            union,
+            marker_size,
+            _quiet,
+            labels,
+            current,
+            base,
+            other,
+        }) => {
+            let options = merge::MergeOptions {
+                level: merge::Level::ZealousAlnum,
+                style: if zdiff3 {
+                    merge::ConflictStyle::ZealousDiff3
+                } else if diff3 {
+                    merge::ConflictStyle::Diff3
+                } else {
+                    merge::ConflictStyle::Merge
+                },
+                favor: if ours {
+                    Some(merge::Favor::Ours)
+                } else if theirs {
+                    Some(merge::Favor::Theirs)
+                } else if union {
+                    Some(merge::Favor::Union)
+                } else {
+                    None
+                },
+                marker_size,
+                ..Default::default()
+            };
+            let conflicts =
+                commands::merge_file::invoke(&current, &base, &other, &labels, stdout, options)?;
+            // 退出码是冲突数，最大 127
+            if conflicts > 0 {
+                std::process::exit(conflicts.min(127) as i32);
+            }
+        }
+        Some(Commands::CherryPick {
+            record_origin,
+            args,
+        }) => {
+            // 冲突或结果为空而停下时退出码为 1
+            if !args.run(sequencer::Action::Pick, record_origin).await? {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Revert { args }) => {
+            if !args.run(sequencer::Action::Revert, false).await? {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Rebase {
+            interactive,
+            onto,
+            autosquash,
+            update_refs,
+            no_verify,
+            continue_rebase,
+            skip,
+            abort,
+            edit_todo,
+            upstream,
+            branch,
+        }) => {
+            let done = if continue_rebase {
+                commands::rebase::continue_rebase().await?
+            } else if skip {
+                commands::rebase::skip().await?
+            } else if abort {
+                commands::rebase::abort().await?;
+                true
+            } else if edit_todo {
+                commands::rebase::edit_todo().await?;
+                true
+            } else if let Some(upstream) = upstream {
+                let options = commands::rebase::RebaseOptions {
+                    interactive,
+                    onto,
+                    autosquash,
+                    update_refs,
+                    no_verify,
+                };
+                commands::rebase::invoke(&upstream, branch.as_deref(), options).await?
+            } else {
+                true
+            };
+            // 冲突或 exec 失败而停下时退出码为 1
+            if !done {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::MergeBase {
+            all,
+            octopus,
+            is_ancestor,
+            fork_point,
+            commits,
+        }) => {
+            let mode = if octopus {
+                commands::merge_base::MergeBaseMode::Octopus
+            } else if is_ancestor {
+                commands::merge_base::MergeBaseMode::IsAncestor
+            } else if fork_point {
+                commands::merge_base::MergeBaseMode::ForkPoint
+            } else {
+                commands::merge_base::MergeBaseMode::Default
+            };
+            // 没有公共祖先或不是祖先时退出码为 1
+            if !commands::merge_base::invoke(&commits, mode, all).await? {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Merge {
+            message,
+            no_ff,
+            ff_only,
+            no_commit,
+            no_verify,
+            continue_merge,
+            abort,
+            commit,
+        }) => {
+            if continue_merge {
+                commands::merge::continue_merge().await?;
+            } else if abort {
+                commands::merge::abort().await?;
+            } else if let Some(commit) = commit {
+                let options = commands::merge::MergeOptions {
+                    message,
+                    ff: if no_ff {
+                        commands::merge::FastForward::Never
+                    } else if ff_only {
+                        commands::merge::FastForward::Only
+                    } else {
+                        commands::merge::FastForward::Allow
+                    },
+                    commit: !no_commit,
+                    no_verify,
+                };
+                // 有冲突或钩子拒绝提交时退出码为 1
+                if !commands::merge::invoke(&commit, options).await? {
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some(Commands::Stash { command, push }) => {
+            use commands::stash;
+            let ok = match command {
+                None => stash::push(push.options()).await?,
+                Some(StashCommand::Push(push)) => stash::push(push.options()).await?,
+                Some(StashCommand::List) => {
+                    stash::list()?;
+                    true
+                }
+                Some(StashCommand::Show { stat, patch, stash }) => {
+                    stash::show(stash.as_deref(), stat, patch).await?;
+                    true
+                }
+                Some(StashCommand::Apply { args }) => {
+                    stash::apply(args.stash.as_deref(), args.index, args.quiet).await?
+                }
+                Some(StashCommand::Pop { args }) => {
+                    stash::pop(args.stash.as_deref(), args.index, args.quiet).await?
+                }
+                Some(StashCommand::Drop { quiet, stash }) => {
+                    stash::drop(stash.as_deref(), quiet)?;
+                    true
+                }
+                Some(StashCommand::Clear) => {
+                    stash::clear()?;
+                    true
+                }
+                Some(StashCommand::Branch { branch, stash }) => {
+                    stash::branch(&branch, stash.as_deref()).await?
+                }
+            };
+            // 冲突、未跟踪的文件无法恢复或路径没有匹配时退出码为 1
+            if !ok {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Submodule { command }) => {
+            use commands::submodule;
+            match command {
+                Some(SubmoduleCommand::Init { paths }) => submodule::init(&paths).await?,
+                Some(SubmoduleCommand::Update { init, paths }) => {
+                    submodule::update(init, &paths).await?
+                }
+                Some(SubmoduleCommand::Status { paths }) => submodule::status(&paths).await?,
+                None => submodule::status(&[]).await?,
+            }
+        }
+        Some(Commands::Clone {
+            bare,
+            mirror,
+            branch,
+            depth,
+            filter,
+            _local,
+            no_local,
+            no_hardlinks,
+            no_checkout,
+            quiet,
+            repository,
+            directory,
+        }) => {
+            let options = commands::clone::CloneOptions {
+                bare,
+                mirror,
+                branch,
+                depth,
+                filter,
+                local: !no_local,
+                no_hardlinks,
+                no_checkout,
+                quiet,
+            };
+            commands::clone::invoke(&repository, directory.as_deref(), &options).await?;
+        }
+        Some(Commands::Remote { verbose, command }) => {
+            use commands::remote;
+            match command {
+                None => remote::list(verbose)?,
+                Some(RemoteCommand::Add { fetch, name, url }) => {
+                    // 取回时有引用被拒绝更新，退出码为 1
+                    if !remote::add(&name, &url, fetch).await? {
+                        std::process::exit(1);
+                    }
+                }
+                Some(RemoteCommand::Remove { name }) => remote::remove(&name)?,
+                Some(RemoteCommand::Rename { old, new }) => remote::rename(&old, &new)?,
+                Some(RemoteCommand::SetUrl { name, url }) => remote::set_url(&name, &url)?,
+            }
+        }
+        Some(Commands::Fetch {
+            prune,
+            tags,
+            force,
+            quiet,
+            remote,
+            refspecs,
+        }) => {
+            let options = commands::fetch::FetchOptions {
+                prune,
+                tags,
+                force,
+                quiet,
+                reflog_action: env::args().skip(1).collect::<Vec<_>>().join(" "),
+            };
+            // 有引用被拒绝更新时退出码为 1
+            if !commands::fetch::invoke(remote.as_deref(), &refspecs, &options).await? {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::LsRemote {
+            heads,
+            tags,
+            refs,
+            symref,
+            quiet,
+            exit_code,
+            repository,
+            patterns,
+        }) => {
+            let options = commands::ls_remote::LsRemoteOptions {
+                heads,
+                tags,
+                refs,
+                symref,
+                quiet,
+            };
+            let found =
+                commands::ls_remote::invoke(repository.as_deref(), &patterns, &options).await?;
+            if exit_code && !found {
+                std::process::exit(2);
+            }
+        }
+        Some(Commands::Push {
+            force,
+            force_with_lease,
+            delete,
+            tags,
+            atomic,
+            no_verify,
+            set_upstream,
+            quiet,
+            repository,
+            refspecs,
+        }) => {
+            let options = commands::push::PushOptions {
+                force,
+                delete,
+                tags,
+                atomic,
+                no_verify,
+                set_upstream,
+                quiet,
+                force_with_lease,
+            };
+            // 有引用没有推送成功时退出码为 1
+            if !commands::push::invoke(repository.as_deref(), &refspecs, &options).await? {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::UploadPack {
+            stateless_rpc,
+            advertise_refs,
+            strict: _,
+            timeout: _,
+            directory,
+        }) => {
+            let options = commands::upload_pack::UploadPackOptions {
+                stateless_rpc,
+                advertise_refs,
+            };
+            commands::upload_pack::invoke(&directory, &options).await?;
+        }
+        Some(Commands::ReceivePack {
+            stateless_rpc,
+            advertise_refs,
+            directory,
+        }) => {
+            let options = commands::receive_pack::ReceivePackOptions {
+                stateless_rpc,
+                advertise_refs,
+            };
+            commands::receive_pack::invoke(&directory, &options).await?;
+        }
+        Some(Commands::Serve {
+            listen,
+            http_port,
+            git_port,
+            export_all,
+            read_only,
+            max_connections,
+            max_request_size,
+            directory,
+        }) => {
+            let (http_port, git_port) = match (http_port, git_port) {
+                (None, None) => (Some(8080), Some(9418)),
+                ports => ports,
+            };
+            let options = commands::serve::ServeOptions {
+                listen,
+                http_port,
+                git_port,
+                export_all,
+                read_only,
+                max_connections,
+                max_request_size,
+            };
+            commands::serve::invoke(&directory, &options).await?;
+        }
+        Some(Commands::IndexPack {
+            output,
+            stdin,
+            fix_thin,
+            keep,
+            rev_index,
+            no_rev_index,
+            verify,
+            pack_file,
+        }) => {
+            let options = commands::index_pack::IndexPackOptions {
+                output,
+                stdin,
+                fix_thin,
+                keep,
+                rev_index: match (rev_index, no_rev_index) {
+                    (true, _) => Some(true),
+                    (_, true) => Some(false),
+                    _ => None,
+                },
+                verify,
+            };
+            commands::index_pack::invoke(pack_file.as_deref(), &options).await?;
+        }
+        Some(Commands::VerifyPack {
+            verbose,
+            stat_only,
+            packs,
+        }) => {
+            let options = commands::verify_pack::VerifyPackOptions { verbose, stat_only };
+            // 有不一致的 pack 时退出码为 1
+            if !commands::verify_pack::invoke(&packs, &options).await? {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Bundle { command }) => {
+            use commands::bundle;
+            let ok = match command {
+                BundleCommand::Create {
+                    _quiet,
+                    version,
+                    file,
+                    args,
+                } => {
+                    bundle::create(&file, &args, version).await?;
+                    true
+                }
+                BundleCommand::Verify { quiet, file } => bundle::verify(&file, quiet)?,
+                BundleCommand::ListHeads { file, refnames } => {
+                    bundle::list_heads(&file, &refnames)?;
+                    true
+                }
+                BundleCommand::Unbundle { file, refnames } => {
+                    bundle::unbundle(&file, &refnames).await?
+                }
+            };
+            // 缺少前提提交时退出码为 1
+            if !ok {
+                std::process::exit(1);
+            }
         }
         // 这行不会执行，因为默认子命令是必须的，除非使用Some(包装)
         _ => println!("No subcommand provided"),
@@ -0,0 +1,528 @@
+//! 三方合并：分别比较 base→ours 和 base→theirs，不重叠的修改直接合入，
+//! 重叠的修改输出冲突标记，移植自 git 的 xdiff（xmerge.c）
+
+pub(crate) mod tree;
+
+use crate::diff::{
+    lines::{Edit, LineDiffOptions, diff_lines, lines_match, split_lines},
+    patch::is_binary,
+};
+
+/// 冲突标记的默认长度
+pub(crate) const DEFAULT_MARKER_SIZE: usize = 7;
+
+/// 冲突的显示方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum ConflictStyle {
+    /// 只显示双方的内容
+    #[default]
+    Merge,
+    /// 同时显示共同祖先的内容
+    Diff3,
+    /// 同 diff3，但把双方相同的开头和结尾移出冲突
+    ZealousDiff3,
+}
+
+/// 冲突的自动解决方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Favor {
+    Ours,
+    Theirs,
+    /// 保留双方的内容
+    Union,
+}
+
+/// 合并的精细程度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Level {
+    /// 所有重叠的修改都是冲突
+    Minimal,
+    /// 双方完全相同的修改不算冲突
+    Eager,
+    /// 进一步比较双方的内容，只把不同的行作为冲突
+    Zealous,
+    /// 同上，并且把间隔不超过三行、或者间隔中没有字母数字的冲突合并
+    ZealousAlnum,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct MergeOptions {
+    pub(crate) level: Level,
+    pub(crate) style: ConflictStyle,
+    pub(crate) favor: Option<Favor>,
+    pub(crate) marker_size: usize,
+    pub(crate) line: LineDiffOptions,
+}
+
+impl Default for MergeOptions {
+    fn default() -> Self {
+        MergeOptions {
+            level: Level::Zealous,
+            style: ConflictStyle::default(),
+            favor: None,
+            marker_size: DEFAULT_MARKER_SIZE,
+            line: LineDiffOptions {
+                indent_heuristic: false,
+                ..LineDiffOptions::default()
+            },
+        }
+    }
+}
+
+/// 冲突标记后面的名称
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Labels<'a> {
+    pub(crate) ours: Option<&'a str>,
+    pub(crate) base: Option<&'a str>,
+    pub(crate) theirs: Option<&'a str>,
+}
+
+/// 合并结果和冲突数
+#[derive(Debug, Clone)]
+pub(crate) struct MergeResult {
+    pub(crate) content: Vec<u8>,
+    pub(crate) conflicts: usize,
+}
+
+/// 合并区域的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Conflict,
+    Ours,
+    Theirs,
+    /// 双方都保留
+    Both,
+    /// 细化后发现双方相同，直接跳过
+    Identical,
+}
+
+/// 一个合并区域：base 中的 `[i0, i0 + chg0)` 在 ours 中是 `[i1, i1 + chg1)`，
+/// 在 theirs 中是 `[i2, i2 + chg2)`
+#[derive(Debug, Clone, Copy)]
+struct Hunk {
+    mode: Mode,
+    i0: isize,
+    chg0: isize,
+    i1: isize,
+    chg1: isize,
+    i2: isize,
+    chg2: isize,
+}
+
+/// 三方合并文件内容，任何一侧是二进制时报错
+pub(crate) fn merge_content(
+    base: &[u8],
+    ours: &[u8],
+    theirs: &[u8],
+    labels: Labels,
+    options: MergeOptions,
+) -> anyhow::Result<MergeResult> {
+    anyhow::ensure!(
+        !is_binary(base) && !is_binary(ours) && !is_binary(theirs),
+        "Cannot merge binary files"
+    );
+    let merger = Merger {
+        base: split_lines(base),
+        ours: split_lines(ours),
+        theirs: split_lines(theirs),
+        labels,
+        options,
+    };
+    let script1 = diff_lines(&merger.base, &merger.ours, options.line);
+    let script2 = diff_lines(&merger.base, &merger.theirs, options.line);
+    // 有一侧没有修改时直接取另一侧
+    if script1.is_empty() {
+        return Ok(MergeResult {
+            content: theirs.to_vec(),
+            conflicts: 0,
+        });
+    }
+    if script2.is_empty() {
+        return Ok(MergeResult {
+            content: ours.to_vec(),
+            conflicts: 0,
+        });
+    }
+    Ok(merger.merge(&script1, &script2))
+}
+
+struct Merger<'a> {
+    base: Vec<&'a [u8]>,
+    ours: Vec<&'a [u8]>,
+    theirs: Vec<&'a [u8]>,
+    labels: Labels<'a>,
+    options: MergeOptions,
+}
+
+impl Merger<'_> {
+    fn merge(&self, script1: &[Edit], script2: &[Edit]) -> MergeResult {
+        let style = self.options.style;
+        // diff3 要显示共同祖先，细化冲突后祖先就对不上了，所以不细化
+        let level = if style == ConflictStyle::Diff3 {
+            self.options.level.min(Level::Eager)
+        } else {
+            self.options.level
+        };
+
+        let mut hunks: Vec<Hunk> = Vec::new();
+        let (mut k1, mut k2) = (0, 0);
+        while k1 < script1.len() && k2 < script2.len() {
+            let (x1, x2) = (bounds(&script1[k1]), bounds(&script2[k2]));
+            // 只有 ours 修改
+            if x1.0 + x1.1 < x2.0 {
+                append(
+                    &mut hunks,
+                    Mode::Ours,
+                    (x1.0, x1.1),
+                    (x1.2, x1.3),
+                    (x2.2 - x2.0 + x1.0, x1.1),
+                );
+                k1 += 1;
+                continue;
+            }
+            // 只有 theirs 修改
+            if x2.0 + x2.1 < x1.0 {
+                append(
+                    &mut hunks,
+                    Mode::Theirs,
+                    (x2.0, x2.1),
+                    (x1.2 - x1.0 + x2.0, x2.1),
+                    (x2.2, x2.3),
+                );
+                k2 += 1;
+                continue;
+            }
+            if level == Level::Minimal
+                || x1.0 != x2.0
+                || x1.1 != x2.1
+                || x1.3 != x2.3
+                || !self.same_lines(x1.2, x2.2, x1.3)
+            {
+                // 重叠的修改，把两边扩展到覆盖相同的 base 区间
+                let off = x1.0 - x2.0;
+                let ffo = off + x1.1 - x2.1;
+                let (mut i0, mut i1, mut i2) = (x1.0, x1.2, x2.2);
+                if off > 0 {
+                    i0 -= off;
+                    i1 -= off;
+                } else {
+                    i2 += off;
+                }
+                let mut chg0 = x1.0 + x1.1 - i0;
+                let mut chg1 = x1.2 + x1.3 - i1;
+                let mut chg2 = x2.2 + x2.3 - i2;
+                if ffo < 0 {
+                    chg0 -= ffo;
+                    chg1 -= ffo;
+                } else {
+                    chg2 += ffo;
+                }
+                append(
+                    &mut hunks,
+                    Mode::Conflict,
+                    (i0, chg0),
+                    (i1, chg1),
+                    (i2, chg2),
+                );
+            }
+            let end1 = x1.0 + x1.1;
+            let end2 = x2.0 + x2.1;
+            if end1 >= end2 {
+                k2 += 1;
+            }
+            if end2 >= end1 {
+                k1 += 1;
+            }
+        }
+        let ours_delta = self.ours.len() as isize - self.base.len() as isize;
+        let theirs_delta = self.theirs.len() as isize - self.base.len() as isize;
+        for x1 in script1[k1..].iter().map(bounds) {
+            append(
+                &mut hunks,
+                Mode::Ours,
+                (x1.0, x1.1),
+                (x1.2, x1.3),
+                (x1.0 + theirs_delta, x1.1),
+            );
+        }
+        for x2 in script2[k2..].iter().map(bounds) {
+            append(
+                &mut hunks,
+                Mode::Theirs,
+                (x2.0, x2.1),
+                (x2.0 + ours_delta, x2.1),
+                (x2.2, x2.3),
+            );
+        }
+
+        if style == ConflictStyle::ZealousDiff3 {
+            self.refine_zdiff3_conflicts(&mut hunks);
+        } else if level >= Level::Zealous {
+            hunks = self.refine_conflicts(hunks);
+            self.simplify_non_conflicts(&mut hunks, level == Level::ZealousAlnum);
+        }
+        self.fill(&mut hunks)
+    }
+
+    fn same_lines(&self, i1: isize, i2: isize, count: isize) -> bool {
+        (0..count)
+            .all(|n| self.line_match(self.ours[(i1 + n) as usize], self.theirs[(i2 + n) as usize]))
+    }
+
+    fn line_match(&self, a: &[u8], b: &[u8]) -> bool {
+        lines_match(a, b, self.options.line.whitespace)
+    }
+
+    /// zdiff3：去掉冲突两侧相同的开头和结尾
+    fn refine_zdiff3_conflicts(&self, hunks: &mut [Hunk]) {
+        for m in hunks.iter_mut().filter(|m| m.mode == Mode::Conflict) {
+            while m.chg1 > 0
+                && m.chg2 > 0
+                && self.line_match(self.ours[m.i1 as usize], self.theirs[m.i2 as usize])
+            {
+                m.chg1 -= 1;
+                m.chg2 -= 1;
+                m.i1 += 1;
+                m.i2 += 1;
+            }
+            while m.chg1 > 0
+                && m.chg2 > 0
+                && self.line_match(
+                    self.ours[(m.i1 + m.chg1 - 1) as usize],
+                    self.theirs[(m.i2 + m.chg2 - 1) as usize],
+                )
+            {
+                m.chg1 -= 1;
+                m.chg2 -= 1;
+            }
+        }
+    }
+
+    /// 比较冲突中双方的内容，只把真正不同的部分作为冲突
+    fn refine_conflicts(&self, hunks: Vec<Hunk>) -> Vec<Hunk> {
+        let mut refined = Vec::with_capacity(hunks.len());
+        for mut m in hunks {
+            // 一侧为空时没有可细化的
+            if m.mode != Mode::Conflict || m.chg1 == 0 || m.chg2 == 0 {
+                refined.push(m);
+                continue;
+            }
+            let ours = &self.ours[m.i1 as usize..(m.i1 + m.chg1) as usize];
+            let theirs = &self.theirs[m.i2 as usize..(m.i2 + m.chg2) as usize];
+            let script = diff_lines(ours, theirs, self.options.line);
+            if script.is_empty() {
+                m.mode = Mode::Identical;
+                refined.push(m);
+                continue;
+            }
+            let (i1, i2) = (m.i1, m.i2);
+            for x in script.iter().map(bounds) {
+                refined.push(Hunk {
+                    mode: Mode::Conflict,
+                    i1: x.0 + i1,
+                    chg1: x.1,
+                    i2: x.2 + i2,
+                    chg2: x.3,
+                    ..m
+                });
+            }
+        }
+        refined
+    }
+
+    /// 两个冲突之间不超过三行（或者这些行中没有字母数字）时，把它们合并成一个冲突
+    fn simplify_non_conflicts(&self, hunks: &mut Vec<Hunk>, simplify_if_no_alnum: bool) {
+        let mut n = 0;
+        while n + 1 < hunks.len() {
+            let (m, next) = (hunks[n], hunks[n + 1]);
+            let begin = m.i1 + m.chg1;
+            let end = next.i1;
+            if m.mode != Mode::Conflict
+                || next.mode != Mode::Conflict
+                || (end - begin > 3
+                    && (!simplify_if_no_alnum || self.lines_contain_alnum(begin, end)))
+            {
+                n += 1;
+            } else {
+                hunks[n].chg1 = next.i1 + next.chg1 - m.i1;
+                hunks[n].chg2 = next.i2 + next.chg2 - m.i2;
+                hunks.remove(n + 1);
+            }
+        }
+    }
+
+    fn lines_contain_alnum(&self, begin: isize, end: isize) -> bool {
+        self.ours[begin as usize..end as usize]
+            .iter()
+            .any(|line| line.iter().any(u8::is_ascii_alphanumeric))
+    }
+
+    /// 生成合并结果，冲突区域加上标记
+    fn fill(&self, hunks: &mut [Hunk]) -> MergeResult {
+        let mut out = Vec::new();
+        let mut conflicts = 0;
+        let mut i = 0;
+        for m in hunks.iter_mut() {
+            if let (Some(favor), Mode::Conflict) = (self.options.favor, m.mode) {
+                m.mode = match favor {
+                    Favor::Ours => Mode::Ours,
+                    Favor::Theirs => Mode::Theirs,
+                    Favor::Union => Mode::Both,
+                };
+            }
+            match m.mode {
+                Mode::Conflict => {
+                    conflicts += 1;
+                    self.fill_conflict(&mut out, i, m);
+                }
+                Mode::Ours | Mode::Theirs | Mode::Both => {
+                    copy_lines(&mut out, &self.ours, i, m.i1 - i, false, false);
+                    if matches!(m.mode, Mode::Ours | Mode::Both) {
+                        let needs_cr = self.is_cr_needed(m);
+                        let add_nl = m.mode == Mode::Both;
+                        copy_lines(&mut out, &self.ours, m.i1, m.chg1, needs_cr, add_nl);
+                    }
+                    if matches!(m.mode, Mode::Theirs | Mode::Both) {
+                        copy_lines(&mut out, &self.theirs, m.i2, m.chg2, false, false);
+                    }
+                }
+                Mode::Identical => continue,
+            }
+            i = m.i1 + m.chg1;
+        }
+        let rest = self.ours.len() as isize - i;
+        copy_lines(&mut out, &self.ours, i, rest, false, false);
+        MergeResult {
+            content: out,
+            conflicts,
+        }
+    }
+
+    fn fill_conflict(&self, out: &mut Vec<u8>, i: isize, m: &Hunk) {
+        let needs_cr = self.is_cr_needed(m);
+        let marker_size = if self.options.marker_size == 0 {
+            DEFAULT_MARKER_SIZE
+        } else {
+            self.options.marker_size
+        };
+        let marker = |out: &mut Vec<u8>, c: u8, label: Option<&str>| {
+            out.extend(std::iter::repeat(c).take(marker_size));
+            if let Some(label) = label {
+                out.push(b' ');
+                out.extend_from_slice(label.as_bytes());
+            }
+            if needs_cr {
+                out.push(b'\r');
+            }
+            out.push(b'\n');
+        };
+
+        copy_lines(out, &self.ours, i, m.i1 - i, false, false);
+        marker(out, b'<', self.labels.ours);
+        copy_lines(out, &self.ours, m.i1, m.chg1, needs_cr, true);
+        if self.options.style != ConflictStyle::Merge {
+            marker(out, b'|', self.labels.base);
+            copy_lines(out, &self.base, m.i0, m.chg0, needs_cr, true);
+        }
+        marker(out, b'=', None);
+        copy_lines(out, &self.theirs, m.i2, m.chg2, needs_cr, true);
+        marker(out, b'>', self.labels.theirs);
+    }
+
+    /// 冲突标记使用的换行符：双方冲突前一行（或第一行）和 base 的第一行都是 CRLF 时用 CRLF
+    fn is_cr_needed(&self, m: &Hunk) -> bool {
+        let mut needs_cr = is_eol_crlf(&self.ours, (m.i1 - 1).max(0));
+        if needs_cr != 0 {
+            needs_cr = is_eol_crlf(&self.theirs, (m.i2 - 1).max(0));
+        }
+        if needs_cr != 0 {
+            needs_cr = is_eol_crlf(&self.base, 0);
+        }
+        needs_cr > 0
+    }
+}
+
+/// 修改在 base、新文件中的位置和长度
+fn bounds(edit: &Edit) -> (isize, isize, isize, isize) {
+    (
+        edit.old as isize,
+        edit.old_len as isize,
+        edit.new as isize,
+        edit.new_len as isize,
+    )
+}
+
+/// 追加一个区域，与上一个区域重叠或相邻时合并，类型不同则成为冲突
+fn append(
+    hunks: &mut Vec<Hunk>,
+    mode: Mode,
+    (i0, chg0): (isize, isize),
+    (i1, chg1): (isize, isize),
+    (i2, chg2): (isize, isize),
+) {
+    if let Some(m) = hunks.last_mut() {
+        if i1 <= m.i1 + m.chg1 || i2 <= m.i2 + m.chg2 {
+            if mode != m.mode {
+                m.mode = Mode::Conflict;
+            }
+            m.chg0 = i0 + chg0 - m.i0;
+            m.chg1 = i1 + chg1 - m.i1;
+            m.chg2 = i2 + chg2 - m.i2;
+            return;
+        }
+    }
+    hunks.push(Hunk {
+        mode,
+        i0,
+        chg0,
+        i1,
+        chg1,
+        i2,
+        chg2,
+    });
+}
+
+/// 复制 `[start, start + count)` 行，add_nl 时给没有换行符的最后一行补上换行
+fn copy_lines(
+    out: &mut Vec<u8>,
+    lines: &[&[u8]],
+    start: isize,
+    count: isize,
+    needs_cr: bool,
+    add_nl: bool,
+) {
+    if count < 1 {
+        return;
+    }
+    let lines = &lines[start as usize..(start + count) as usize];
+    for line in lines {
+        out.extend_from_slice(line);
+    }
+    if add_nl && !lines[lines.len() - 1].ends_with(b"\n") {
+        if needs_cr {
+            out.push(b'\r');
+        }
+        out.push(b'\n');
+    }
+}
+
+/// 第 i 行是否以 CRLF 结尾：1 是，0 否，-1 无法判断。
+/// 没有换行符的最后一行看它的前一行
+fn is_eol_crlf(lines: &[&[u8]], i: isize) -> i32 {
+    let crlf = |line: &[u8]| i32::from(line.len() > 1 && line[line.len() - 2] == b'\r');
+    let n = lines.len() as isize;
+    if i < n - 1 {
+        return crlf(lines[i as usize]);
+    }
+    if n == 0 {
+        return -1;
+    }
+    let line = lines[i as usize];
+    if line.ends_with(b"\n") {
+        return crlf(line);
+    }
+    if i == 0 {
+        return -1;
+    }
+    crlf(lines[i as usize - 1])
+}
@@ -0,0 +1,555 @@
+//! 树的三方合并：分别检测两侧相对共同祖先的重命名，逐个文件合并内容，
+//! 不能自动解决的路径记为冲突，行为参考 git 的 merge-ort
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    future::Future,
+    pin::Pin,
+};
+
+use crate::{
+    diff::rename::RenameOptions,
+    diff::{Side, Status, diff_maps, flatten_tree, patch::is_binary, rename::detect_renames},
+    merge::{DEFAULT_MARKER_SIZE, Labels, MergeOptions, merge_content},
+    objects::{Kind, Mode, Object, commit::Commit, find_unique_abbrev, read_object},
+    revision::merge_bases,
+};
+
+/// 冲突路径在暂存区中的 1/2/3 阶段：共同祖先、我们的和他们的版本
+pub(crate) type Stages = [Option<Side>; 3];
+
+/// 树合并的选项
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TreeMergeOptions<'a> {
+    /// 冲突标记和提示中双方的名称，如 `HEAD` 和分支名
+    pub(crate) ours: &'a str,
+    pub(crate) theirs: &'a str,
+    /// 冲突标记中共同祖先的名称
+    pub(crate) ancestor: &'a str,
+    pub(crate) renames: RenameOptions,
+    /// 合并多个共同祖先时的递归深度，每深一层冲突标记长两个字符
+    depth: usize,
+}
+
+impl<'a> TreeMergeOptions<'a> {
+    pub(crate) fn new(ours: &'a str, theirs: &'a str, ancestor: &'a str) -> Self {
+        TreeMergeOptions {
+            ours,
+            theirs,
+            ancestor,
+            renames: RenameOptions::renames(),
+            depth: 0,
+        }
+    }
+}
+
+/// 树合并的结果
+#[derive(Debug, Default)]
+pub(crate) struct TreeMergeResult {
+    /// 合并后的所有文件，冲突的路径是留在工作区的版本（如带冲突标记的内容）
+    pub(crate) files: BTreeMap<String, Side>,
+    /// 冲突的路径
+    pub(crate) conflicts: BTreeMap<String, Stages>,
+    /// 按路径排列的提示，如 `Auto-merging a.txt`
+    pub(crate) messages: BTreeMap<String, Vec<String>>,
+}
+
+impl TreeMergeResult {
+    pub(crate) fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+
+    fn message(&mut self, path: &str, message: String) {
+        self.messages
+            .entry(path.to_string())
+            .or_default()
+            .push(message);
+    }
+}
+
+/// 合并两个提交：先求出共同祖先（多个时递归合并成一个虚拟祖先），再合并树
+pub(crate) async fn merge_commits(
+    ours: [u8; 20],
+    theirs: [u8; 20],
+    options: TreeMergeOptions<'_>,
+) -> anyhow::Result<TreeMergeResult> {
+    let bases = merge_bases(ours, &[theirs]).await?;
+    let ancestor = match bases.as_slice() {
+        [] => "empty tree".to_string(),
+        [base] => find_unique_abbrev(&hex::encode(base), 7),
+        _ => "merged common ancestors".to_string(),
+    };
+    let base = virtual_base(bases, options.depth + 1).await?;
+    let ours = flatten_tree(&Commit::read(&ours).await?.tree).await?;
+    let theirs = flatten_tree(&Commit::read(&theirs).await?.tree).await?;
+    let options = TreeMergeOptions {
+        ancestor: &ancestor,
+        ..options
+    };
+    merge_trees(&base, &ours, &theirs, options).await
+}
+
+type BaseFuture = Pin<Box<dyn Future<Output = anyhow::Result<BTreeMap<String, Side>>> + Send>>;
+
+/// 把多个共同祖先从旧到新依次合并成一个虚拟祖先，冲突保留冲突标记
+fn virtual_base(mut bases: Vec<[u8; 20]>, depth: usize) -> BaseFuture {
+    Box::pin(async move {
+        bases.reverse();
+        let Some((&first, rest)) = bases.split_first() else {
+            return Ok(BTreeMap::new());
+        };
+        let mut files = flatten_tree(&Commit::read(&first).await?.tree).await?;
+        let mut merged = vec![first];
+        for &next in rest {
+            // 虚拟提交的祖先是已经合并的所有提交的祖先
+            let inner = merge_bases(next, &merged).await?;
+            let base = virtual_base(inner, depth + 1).await?;
+            let theirs = flatten_tree(&Commit::read(&next).await?.tree).await?;
+            let options = TreeMergeOptions {
+                depth,
+                ..TreeMergeOptions::new(
+                    "Temporary merge branch 1",
+                    "Temporary merge branch 2",
+                    "merged common ancestors",
+                )
+            };
+            files = merge_trees(&base, &files, &theirs, options).await?.files;
+            merged.push(next);
+        }
+        Ok(files)
+    })
+}
+
+/// 一个文件在三棵树中的路径和内容，重命名时路径不同
+#[derive(Debug, Clone)]
+struct Item {
+    base: Option<(String, Side)>,
+    ours: Option<(String, Side)>,
+    theirs: Option<(String, Side)>,
+}
+
+/// 文件来自哪一侧，决定目录/文件冲突时改名用的名称
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Origin {
+    Ours,
+    Theirs,
+}
+
+/// 合并后一个路径上的结果
+#[derive(Debug, Clone)]
+struct Entry {
+    /// 留在树和工作区中的版本，只有共同祖先阶段的冲突路径没有
+    file: Option<Side>,
+    stages: Option<Stages>,
+    origin: Origin,
+    /// 修改过的文件被另一侧删除时，共同祖先中的路径。提示要用目录/文件冲突改名后的路径，
+    /// 所以最后才生成
+    modify_delete: Option<String>,
+}
+
+/// 三方合并按路径展开的树
+pub(crate) async fn merge_trees(
+    base: &BTreeMap<String, Side>,
+    ours: &BTreeMap<String, Side>,
+    theirs: &BTreeMap<String, Side>,
+    options: TreeMergeOptions<'_>,
+) -> anyhow::Result<TreeMergeResult> {
+    let ours_renames = side_renames(base, ours, options.renames).await?;
+    let theirs_renames = side_renames(base, theirs, options.renames).await?;
+    let ours_targets: HashSet<&String> = ours_renames.values().collect();
+    let theirs_targets: HashSet<&String> = theirs_renames.values().collect();
+
+    // 按共同祖先中的路径和新增的路径对应三侧的文件
+    let mut items = Vec::new();
+    let side_of =
+        |renames: &HashMap<String, String>, files: &BTreeMap<String, Side>, path: &String| {
+            let path = renames.get(path).unwrap_or(path);
+            files.get(path).map(|side| (path.clone(), *side))
+        };
+    for (path, side) in base {
+        items.push(Item {
+            base: Some((path.clone(), *side)),
+            ours: side_of(&ours_renames, ours, path),
+            theirs: side_of(&theirs_renames, theirs, path),
+        });
+    }
+    let added = |files: &BTreeMap<String, Side>, targets: &HashSet<&String>, path: &String| {
+        !base.contains_key(path) && !targets.contains(path) && files.contains_key(path)
+    };
+    for (path, side) in ours {
+        if added(ours, &ours_targets, path) {
+            items.push(Item {
+                base: None,
+                ours: Some((path.clone(), *side)),
+                theirs: added(theirs, &theirs_targets, path).then(|| (path.clone(), theirs[path])),
+            });
+        }
+    }
+    for (path, side) in theirs {
+        if added(theirs, &theirs_targets, path) && !added(ours, &ours_targets, path) {
+            items.push(Item {
+                base: None,
+                ours: None,
+                theirs: Some((path.clone(), *side)),
+            });
+        }
+    }
+
+    let mut merger = Merger {
+        options,
+        result: TreeMergeResult::default(),
+        entries: Vec::new(),
+    };
+    for item in items {
+        merger.resolve(item).await?;
+    }
+    merger.finish().await
+}
+
+/// 一侧相对共同祖先的重命名：旧路径 → 新路径
+async fn side_renames(
+    base: &BTreeMap<String, Side>,
+    side: &BTreeMap<String, Side>,
+    options: RenameOptions,
+) -> anyhow::Result<HashMap<String, String>> {
+    let changes = detect_renames(diff_maps(base, side), &BTreeMap::new(), options, false).await?;
+    Ok(changes
+        .into_iter()
+        .filter(|change| matches!(change.status, Status::Renamed(_)))
+        .filter_map(|change| Some((change.from?, change.path)))
+        .collect())
+}
+
+struct Merger<'a> {
+    options: TreeMergeOptions<'a>,
+    result: TreeMergeResult,
+    /// 合并后的路径，同一路径可能来自两个文件（如重命名到对方新增的路径）
+    entries: Vec<(String, Entry)>,
+}
+
+impl Merger<'_> {
+    fn push(&mut self, path: &str, file: Option<Side>, stages: Option<Stages>, origin: Origin) {
+        let entry = Entry {
+            file,
+            stages,
+            origin,
+            modify_delete: None,
+        };
+        self.entries.push((path.to_string(), entry));
+    }
+
+    fn name(&self, origin: Origin) -> &str {
+        match origin {
+            Origin::Ours => self.options.ours,
+            Origin::Theirs => self.options.theirs,
+        }
+    }
+
+    async fn resolve(&mut self, item: Item) -> anyhow::Result<()> {
+        match (item.base, item.ours, item.theirs) {
+            (_, None, None) => {}
+            (Some(base), Some(side), None) => self.one_deleted(base, side, Origin::Ours),
+            (Some(base), None, Some(side)) => self.one_deleted(base, side, Origin::Theirs),
+            (Some(base), Some(ours), Some(theirs)) => self.both_kept(base, ours, theirs).await?,
+            (None, Some((path, side)), None) => self.push(&path, Some(side), None, Origin::Ours),
+            (None, None, Some((path, side))) => self.push(&path, Some(side), None, Origin::Theirs),
+            (None, Some((path, ours)), Some((_, theirs))) => {
+                let paths = [path.as_str(); 3];
+                let (merged, clean) = self
+                    .merge_file(&path, None, ours, theirs, paths, "add/add")
+                    .await?;
+                let stages = (!clean).then_some([None, Some(ours), Some(theirs)]);
+                self.push(&path, Some(merged), stages, Origin::Ours);
+            }
+        }
+        Ok(())
+    }
+
+    /// 一侧删除了文件，另一侧保留（可能修改或重命名）
+    fn one_deleted(&mut self, base: (String, Side), (path, side): (String, Side), kept: Origin) {
+        let (base_path, base) = base;
+        let deleted = match kept {
+            Origin::Ours => Origin::Theirs,
+            Origin::Theirs => Origin::Ours,
+        };
+        if path != base_path {
+            let message = format!(
+                "CONFLICT (rename/delete): {base_path} renamed to {path} in {}, \
+                 but deleted in {}.",
+                self.name(kept),
+                self.name(deleted)
+            );
+            self.result.message(&base_path, message);
+        } else if side == base {
+            // 没有修改的文件被另一侧删除
+            return;
+        }
+        let stages = match kept {
+            Origin::Ours => [Some(base), Some(side), None],
+            Origin::Theirs => [Some(base), None, Some(side)],
+        };
+        self.push(&path, Some(side), Some(stages), kept);
+        if side != base {
+            if let Some((_, entry)) = self.entries.last_mut() {
+                entry.modify_delete = Some(base_path);
+            }
+        }
+    }
+
+    /// 双方都保留了文件，路径可能因重命名而不同
+    async fn both_kept(
+        &mut self,
+        (base_path, base): (String, Side),
+        (ours_path, ours): (String, Side),
+        (theirs_path, theirs): (String, Side),
+    ) -> anyhow::Result<()> {
+        let paths = [base_path.as_str(), ours_path.as_str(), theirs_path.as_str()];
+        if ours_path != base_path && theirs_path != base_path && ours_path != theirs_path {
+            // 双方重命名到不同的路径：两个新路径上都放合并后的内容
+            let message = format!(
+                "CONFLICT (rename/rename): {base_path} renamed to {ours_path} in {} \
+                 and to {theirs_path} in {}.",
+                self.options.ours, self.options.theirs
+            );
+            self.result.message(&base_path, message);
+            let (merged, _) = self
+                .merge_file(&base_path, Some(base), ours, theirs, paths, "content")
+                .await?;
+            self.push(
+                &base_path,
+                None,
+                Some([Some(base), None, None]),
+                Origin::Ours,
+            );
+            self.push(
+                &ours_path,
+                Some(merged),
+                Some([None, Some(merged), None]),
+                Origin::Ours,
+            );
+            self.push(
+                &theirs_path,
+                Some(merged),
+                Some([None, None, Some(merged)]),
+                Origin::Theirs,
+            );
+            return Ok(());
+        }
+
+        // 只有一侧重命名时使用新路径
+        let path = if ours_path == base_path {
+            theirs_path.clone()
+        } else {
+            ours_path.clone()
+        };
+        let (merged, clean) = self
+            .merge_file(&path, Some(base), ours, theirs, paths, "content")
+            .await?;
+        let stages = (!clean).then_some([Some(base), Some(ours), Some(theirs)]);
+        self.push(&path, Some(merged), stages, Origin::Ours);
+        Ok(())
+    }
+
+    /// 合并一个文件的三个版本，返回结果和是否没有冲突。
+    /// paths 是文件在三侧的路径，不同时冲突标记的名称后面加上路径
+    async fn merge_file(
+        &mut self,
+        path: &str,
+        base: Option<Side>,
+        ours: Side,
+        theirs: Side,
+        paths: [&str; 3],
+        kind: &str,
+    ) -> anyhow::Result<(Side, bool)> {
+        if ours == theirs || base == Some(theirs) {
+            return Ok((ours, true));
+        }
+        if base == Some(ours) {
+            return Ok((theirs, true));
+        }
+        let base_mode = base.map(|side| side.mode);
+        let mode = if ours.mode == theirs.mode || base_mode == Some(theirs.mode) {
+            ours.mode
+        } else if base_mode == Some(ours.mode) {
+            theirs.mode
+        } else {
+            ours.mode
+        };
+        let base_hash = base.map(|side| side.hash);
+        if ours.hash == theirs.hash || base_hash == Some(theirs.hash) {
+            return Ok((Side { mode, ..ours }, true));
+        }
+        if base_hash == Some(ours.hash) {
+            return Ok((Side { mode, ..theirs }, true));
+        }
+
+        if ours.mode == Mode::Gitlink || theirs.mode == Mode::Gitlink {
+            // 子模块的两个提交不能合并内容，保留我们的版本
+            self.result
+                .message(path, format!("Failed to merge submodule {path}"));
+            let message = format!("CONFLICT (submodule): Merge conflict in {path}");
+            self.result.message(path, message);
+            return Ok((ours, false));
+        }
+        self.result.message(path, format!("Auto-merging {path}"));
+        let (hash, clean) = self
+            .merge_blobs(path, base_hash, ours, theirs, paths)
+            .await?;
+        if !clean {
+            let message = format!("CONFLICT ({kind}): Merge conflict in {path}");
+            self.result.message(path, message);
+        }
+        Ok((Side { mode, hash }, clean))
+    }
+
+    /// 逐行合并双方的内容并写入对象库；符号链接和二进制文件不能合并，保留我们的版本
+    async fn merge_blobs(
+        &mut self,
+        path: &str,
+        base: Option<[u8; 20]>,
+        ours: Side,
+        theirs: Side,
+        paths: [&str; 3],
+    ) -> anyhow::Result<([u8; 20], bool)> {
+        if ours.mode == Mode::SymbolicLink || theirs.mode == Mode::SymbolicLink {
+            return Ok((ours.hash, false));
+        }
+        let base = match base {
+            Some(hash) => read_object(&hex::encode(hash)).await?.1,
+            None => Vec::new(),
+        };
+        let ours_content = read_object(&hex::encode(ours.hash)).await?.1;
+        let theirs_content = read_object(&hex::encode(theirs.hash)).await?.1;
+        if is_binary(&base) || is_binary(&ours_content) || is_binary(&theirs_content) {
+            eprintln!(
+                "warning: Cannot merge binary files: {path} ({} vs. {})",
+                self.options.ours, self.options.theirs
+            );
+            return Ok((ours.hash, false));
+        }
+
+        let names = [
+            self.options.ancestor,
+            self.options.ours,
+            self.options.theirs,
+        ];
+        let labels: Vec<String> = if paths.iter().all(|p| *p == paths[0]) {
+            names.iter().map(|name| name.to_string()).collect()
+        } else {
+            names
+                .iter()
+                .zip(paths)
+                .map(|(name, path)| format!("{name}:{path}"))
+                .collect()
+        };
+        let options = MergeOptions {
+            marker_size: DEFAULT_MARKER_SIZE + 2 * self.options.depth,
+            ..MergeOptions::default()
+        };
+        let merged = merge_content(
+            &base,
+            &ours_content,
+            &theirs_content,
+            Labels {
+                ours: Some(&labels[1]),
+                base: Some(&labels[0]),
+                theirs: Some(&labels[2]),
+            },
+            options,
+        )?;
+        let hash = Object {
+            kind: Kind::Blob,
+            expected_size: merged.content.len() as u64,
+            reader: merged.content.as_slice(),
+        }
+        .write_object()
+        .await?;
+        Ok((hash, merged.conflicts == 0))
+    }
+
+    /// 处理落在同一路径上的两个文件和目录/文件冲突，得到最终结果
+    async fn finish(mut self) -> anyhow::Result<TreeMergeResult> {
+        let mut by_path: BTreeMap<String, Entry> = BTreeMap::new();
+        for (path, entry) in std::mem::take(&mut self.entries) {
+            let Some(existing) = by_path.remove(&path) else {
+                by_path.insert(path, entry);
+                continue;
+            };
+            // 两个文件落在同一路径时按双方新增处理
+            let (first, second) = match existing.origin {
+                Origin::Theirs => (entry, existing),
+                Origin::Ours => (existing, entry),
+            };
+            let entry = match (first.file, second.file) {
+                (Some(ours), Some(theirs)) => {
+                    let paths = [path.as_str(); 3];
+                    let (merged, clean) = self
+                        .merge_file(&path, None, ours, theirs, paths, "add/add")
+                        .await?;
+                    Entry {
+                        file: Some(merged),
+                        stages: (!clean).then_some([None, Some(ours), Some(theirs)]),
+                        origin: Origin::Ours,
+                        modify_delete: None,
+                    }
+                }
+                (None, _) => second,
+                (_, None) => first,
+            };
+            by_path.insert(path, entry);
+        }
+
+        // 文件所在的路径在另一侧是目录时，把文件改名为 `路径~分支名`
+        let paths: Vec<String> = by_path.keys().cloned().collect();
+        for path in paths {
+            let prefix = format!("{path}/");
+            let is_dir = by_path
+                .range(prefix.clone()..)
+                .next()
+                .is_some_and(|(other, _)| other.starts_with(&prefix));
+            if !is_dir || by_path[&path].file.is_none() {
+                continue;
+            }
+            let mut entry = by_path.remove(&path).expect("path from keys");
+            let name = self.name(entry.origin).replace('/', "_");
+            let new_path = format!("{path}~{name}");
+            self.result.message(
+                &path,
+                format!(
+                    "CONFLICT (file/directory): directory in the way of {path} from {name}; \
+                     moving it to {new_path} instead."
+                ),
+            );
+            entry.stages = Some(entry.stages.unwrap_or(match entry.origin {
+                Origin::Ours => [None, entry.file, None],
+                Origin::Theirs => [None, None, entry.file],
+            }));
+            by_path.insert(new_path, entry);
+        }
+
+        for (path, entry) in &by_path {
+            let Some(base_path) = &entry.modify_delete else {
+                continue;
+            };
+            let (kept_in, deleted_in) = match entry.origin {
+                Origin::Ours => (self.options.ours, self.options.theirs),
+                Origin::Theirs => (self.options.theirs, self.options.ours),
+            };
+            let message = format!(
+                "CONFLICT (modify/delete): {path} deleted in {deleted_in} and modified in \
+                 {kept_in}.  Version {kept_in} of {path} left in tree."
+            );
+            self.result.message(base_path, message);
+        }
+
+        for (path, entry) in by_path {
+            if let Some(file) = entry.file {
+                self.result.files.insert(path.clone(), file);
+            }
+            if let Some(stages) = entry.stages {
+                self.result.conflicts.insert(path, stages);
+            }
+        }
+        Ok(self.result)
+    }
+}
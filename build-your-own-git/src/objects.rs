@@ -1,3 +1,7 @@
+pub(crate) mod commit;
+pub(crate) mod commit_graph;
+pub(crate) mod tree;
+
 #[cfg(unix)]
 use std::os::unix::fs::PermissionsExt;
 use std::{
@@ -14,7 +18,9 @@ use sha1::{Digest, Sha1};
 use tempfile::NamedTempFile;
 use tokio::fs;
 
-#[derive(Debug, PartialEq, Eq)]
+use crate::pack;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) enum Kind {
     Blob,
     Tree,
@@ -39,40 +45,51 @@ impl From<Mode> for Kind {
             Mode::File => Kind::Blob,
             Mode::Executable => Kind::Blob,
             Mode::Directory => Kind::Tree,
-            Mode::SymbolicLink => Kind::Tag,
+            Mode::SymbolicLink => Kind::Blob,
+            Mode::Gitlink => Kind::Commit,
         }
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) enum Mode {
     File,
     Executable,
     Directory,
     SymbolicLink,
+    /// 子模块：记录的是子仓库中的提交
+    Gitlink,
 }
 
 impl Mode {
     pub fn from_str(s: &str) -> anyhow::Result<Mode, Error> {
         match s {
-            "040000" => Ok(Mode::Directory),
+            // git 写入树对象时目录模式没有前导 0
+            "040000" | "40000" => Ok(Mode::Directory),
             "100644" => Ok(Mode::File),
             "100755" => Ok(Mode::Executable),
             "120000" => Ok(Mode::SymbolicLink),
+            "160000" => Ok(Mode::Gitlink),
             _ => anyhow::bail!("unknown kind: {s}"),
         }
     }
     pub fn is_dir(&self) -> bool {
         matches!(self, Mode::Directory)
     }
-    pub fn to_bytes(&self) -> &'static [u8] {
+    /// 树对象中的写法，与 git 一样目录没有前导 0
+    pub fn to_bytes(self) -> &'static [u8] {
         match self {
             Mode::File => b"100644",
             Mode::Executable => b"100755",
-            Mode::Directory => b"040000",
+            Mode::Directory => b"40000",
             Mode::SymbolicLink => b"120000",
+            Mode::Gitlink => b"160000",
         }
     }
+    /// 输出中的写法，如 diff-tree 的 `:040000`，补足 6 位
+    pub fn to_padded(self) -> String {
+        format!("{:0>6}", String::from_utf8_lossy(self.to_bytes()))
+    }
     /// 从文件元数据判断 Mode
     pub fn from_meta(metadata: &Metadata) -> Mode {
         let ft = metadata.file_type();
@@ -142,11 +159,36 @@ pub(crate) struct Object<R> {
 }
 
 pub(crate) async fn hash_to_reader(path: &str) -> anyhow::Result<Object<impl BufRead>> {
-    // 使用string构造路径
-    let f = std::fs::File::open(format!(".git/objects/{}/{}", &path[0..2], &path[2..]))
-        .context("open in .git/objects")?;
+    hash_to_reader_in(Path::new(".git/objects"), path).await
+}
+
+/// 同 hash_to_reader，但从 objects 这个对象目录（如另一个仓库的）中读取。
+/// 没有这个松散对象时到 `objects/pack` 的 pack 中查找
+pub(crate) async fn hash_to_reader_in(
+    objects: &Path,
+    path: &str,
+) -> anyhow::Result<Object<std::io::Take<Box<dyn BufRead + Send>>>> {
+    let f = match std::fs::File::open(objects.join(&path[0..2]).join(&path[2..])) {
+        Ok(f) => f,
+        Err(e) => {
+            let packed = match hex::decode(path).ok().and_then(|id| id.try_into().ok()) {
+                Some(id) => pack::store::read(objects, &id).await?,
+                None => None,
+            };
+            let Some((kind, data)) = packed else {
+                return Err(e).with_context(|| format!("open in {}", objects.display()));
+            };
+            let size = data.len() as u64;
+            let reader: Box<dyn BufRead + Send> = Box::new(std::io::Cursor::new(data));
+            return Ok(Object {
+                kind,
+                expected_size: size,
+                reader: reader.take(size),
+            });
+        }
+    };
     let decoder = ZlibDecoder::new(f);
-    let mut buf = std::io::BufReader::new(decoder);
+    let mut buf: Box<dyn BufRead + Send> = Box::new(std::io::BufReader::new(decoder));
 
     let mut ret = Vec::new();
     // 1. 读取文件头
@@ -180,6 +222,92 @@ pub(crate) async fn hash_to_reader(path: &str) -> anyhow::Result<Object<impl Buf
         reader: buf,
     })
 }
+/// 读取完整对象内容到内存，并校验大小
+pub(crate) async fn read_object(hash: &str) -> anyhow::Result<(Kind, Vec<u8>)> {
+    read_object_in(Path::new(".git/objects"), hash).await
+}
+
+/// 同 read_object，但从 objects 这个对象目录中读取
+pub(crate) async fn read_object_in(objects: &Path, hash: &str) -> anyhow::Result<(Kind, Vec<u8>)> {
+    let mut object = hash_to_reader_in(objects, hash)
+        .await
+        .with_context(|| format!("read object {hash}"))?;
+    let mut buf = Vec::with_capacity(object.expected_size as usize);
+    object.reader.read_to_end(&mut buf)?;
+    anyhow::ensure!(
+        buf.len() as u64 == object.expected_size,
+        ".git/objects file was not be expected size (expected {0}, got {1})",
+        object.expected_size,
+        buf.len()
+    );
+    Ok((object.kind, buf))
+}
+
+/// 类型为 kind、内容为 data 的对象的哈希
+pub(crate) fn hash_object(kind: &Kind, data: &[u8]) -> [u8; 20] {
+    let mut hasher = Sha1::new();
+    hasher.update(format!("{kind} {}\0", data.len()).as_bytes());
+    hasher.update(data);
+    hasher.finalize().into()
+}
+
+/// 把类型为 kind、内容为 data 的对象写入 objects 这个对象目录，已经存在时不重写，返回哈希
+pub(crate) fn write_object_in(
+    objects: &Path,
+    kind: &Kind,
+    data: &[u8],
+) -> anyhow::Result<[u8; 20]> {
+    let header = format!("{kind} {}\0", data.len());
+    let id = hash_object(kind, data);
+    let hex = hex::encode(id);
+    let path = objects.join(&hex[..2]).join(&hex[2..]);
+    if path.is_file() {
+        return Ok(id);
+    }
+    let dir = objects.join(&hex[..2]);
+    std::fs::create_dir_all(&dir).with_context(|| format!("create {}", dir.display()))?;
+    // 先写到同一目录下的临时文件再改名，不会留下写了一半的对象
+    let file = NamedTempFile::new_in(&dir)?;
+    let mut encoder = ZlibEncoder::new(file, Compression::default());
+    encoder.write_all(header.as_bytes())?;
+    encoder.write_all(data)?;
+    encoder
+        .finish()?
+        .persist(&path)
+        .with_context(|| format!("write object {hex}"))?;
+    Ok(id)
+}
+
+/// 对象是否存在于对象库中
+pub(crate) fn object_exists(hash: &str) -> bool {
+    match hex::decode(hash).ok().and_then(|id| id.try_into().ok()) {
+        Some(id) => object_exists_in(Path::new(".git/objects"), &id),
+        None => false,
+    }
+}
+
+/// id 是否存在于 objects 这个对象目录中，松散的或在 pack 中的
+pub(crate) fn object_exists_in(objects: &Path, id: &[u8; 20]) -> bool {
+    let hex = hex::encode(id);
+    objects.join(&hex[..2]).join(&hex[2..]).is_file() || pack::store::contains(objects, id)
+}
+
+/// 找到最短的无歧义前缀（至少 min_len 位）
+pub(crate) fn find_unique_abbrev(hex: &str, min_len: usize) -> String {
+    let mut len = min_len.min(hex.len());
+    let loose = std::fs::read_dir(format!(".git/objects/{}", &hex[..2]))
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.ok())
+        .map(|e| format!("{}{}", &hex[..2], e.file_name().to_string_lossy()));
+    let packed = pack::store::ids_with_prefix(Path::new(".git/objects"), &hex[..2]);
+    let others: Vec<String> = loose.chain(packed).filter(|name| name != hex).collect();
+    while len < hex.len() && others.iter().any(|o| o.starts_with(&hex[..len])) {
+        len += 1;
+    }
+    hex[..len].to_string()
+}
+
 impl<R> Object<R>
 where
     R: Read,
@@ -0,0 +1,178 @@
+use std::fmt;
+
+use anyhow::Context;
+
+use crate::objects::{Kind, read_object};
+
+pub(crate) mod gpg;
+
+/// 作者/提交者签名: `Name <email> 1700000000 +0800`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Signature {
+    pub(crate) name: String,
+    pub(crate) email: String,
+    pub(crate) time: i64,
+    /// 时区偏移，单位分钟
+    pub(crate) tz_offset: i32,
+}
+
+impl Signature {
+    pub(crate) fn parse(s: &str) -> anyhow::Result<Signature> {
+        let (name, rest) = s.split_once('<').context("signature missing '<'")?;
+        let (email, rest) = rest.split_once('>').context("signature missing '>'")?;
+        let mut fields = rest.split_whitespace();
+        let time = fields
+            .next()
+            .context("signature missing timestamp")?
+            .parse::<i64>()
+            .context("signature timestamp isn't valid")?;
+        let tz = fields.next().unwrap_or("+0000");
+        let sign = if tz.starts_with('-') { -1 } else { 1 };
+        let digits = tz.trim_start_matches(['+', '-']);
+        let hhmm = digits
+            .parse::<i32>()
+            .context("signature timezone isn't valid")?;
+        Ok(Signature {
+            name: name.trim_end().to_string(),
+            email: email.to_string(),
+            time,
+            tz_offset: sign * (hhmm / 100 * 60 + hhmm % 100),
+        })
+    }
+
+    /// git 默认日期格式: `Thu Oct 18 19:38:23 2026 +0800`
+    pub(crate) fn format_date(&self) -> String {
+        const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+        const MONTHS: [&str; 12] = [
+            "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+        ];
+        let local = self.time + self.tz_offset as i64 * 60;
+        let days = local.div_euclid(86400);
+        let secs = local.rem_euclid(86400);
+        let (year, month, day) = civil_from_days(days);
+        format!(
+            "{} {} {} {:02}:{:02}:{:02} {} {}",
+            DAYS[days.rem_euclid(7) as usize],
+            MONTHS[month as usize - 1],
+            day,
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60,
+            year,
+            self.format_tz()
+        )
+    }
+
+    pub(crate) fn format_tz(&self) -> String {
+        let sign = if self.tz_offset < 0 { '-' } else { '+' };
+        let abs = self.tz_offset.abs();
+        format!("{sign}{:02}{:02}", abs / 60, abs % 60)
+    }
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} <{}> {} {}",
+            self.name,
+            self.email,
+            self.time,
+            self.format_tz()
+        )
+    }
+}
+
+/// 把 unix 纪元以来的天数转换成 (年, 月, 日)
+fn civil_from_days(z: i64) -> (i64, u32, u32) {
+    let z = z + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// 解析后的提交对象
+#[derive(Debug, Clone)]
+pub(crate) struct Commit {
+    pub(crate) tree: [u8; 20],
+    pub(crate) parents: Vec<[u8; 20]>,
+    pub(crate) author: Signature,
+    pub(crate) committer: Signature,
+    pub(crate) message: String,
+}
+
+impl Commit {
+    pub(crate) async fn read(hash: &[u8; 20]) -> anyhow::Result<Commit> {
+        let hex = hex::encode(hash);
+        let (kind, data) = read_object(&hex).await?;
+        anyhow::ensure!(
+            kind == Kind::Commit,
+            "object {hex} is a {kind}, not a commit"
+        );
+        let mut commit = Commit::parse(&data).with_context(|| format!("parse commit {hex}"))?;
+        if is_shallow(&hex) {
+            commit.parents.clear();
+        }
+        Ok(commit)
+    }
+
+    pub(crate) fn parse(data: &[u8]) -> anyhow::Result<Commit> {
+        let text = String::from_utf8_lossy(data);
+        let (headers, message) = text.split_once("\n\n").unwrap_or((&text, ""));
+
+        let mut tree = None;
+        let mut parents = Vec::new();
+        let mut author = None;
+        let mut committer = None;
+        for line in headers.lines() {
+            // 以空格开头的是上一个头部的续行（如 gpgsig）
+            if line.starts_with(' ') {
+                continue;
+            }
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "tree" => tree = Some(parse_hex(value)?),
+                "parent" => parents.push(parse_hex(value)?),
+                "author" => author = Some(Signature::parse(value)?),
+                "committer" => committer = Some(Signature::parse(value)?),
+                _ => {}
+            }
+        }
+        Ok(Commit {
+            tree: tree.context("commit has no tree")?,
+            parents,
+            author: author.context("commit has no author")?,
+            committer: committer.context("commit has no committer")?,
+            message: message.to_string(),
+        })
+    }
+
+    /// 提交说明的第一段，多行用空格连接。与 git 一样只去掉行尾的空白
+    pub(crate) fn subject(&self) -> String {
+        self.message
+            .lines()
+            .skip_while(|line| line.trim().is_empty())
+            .take_while(|line| !line.trim().is_empty())
+            .map(str::trim_end)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// 浅克隆的边界提交记在 `.git/shallow` 中，与 git 一样看作没有父提交
+fn is_shallow(hex: &str) -> bool {
+    std::fs::read_to_string(".git/shallow")
+        .is_ok_and(|shallow| shallow.lines().any(|line| line == hex))
+}
+
+pub(crate) fn parse_hex(s: &str) -> anyhow::Result<[u8; 20]> {
+    let mut out = [0; 20];
+    hex::decode_to_slice(s.trim(), &mut out).with_context(|| format!("invalid object id '{s}'"))?;
+    Ok(out)
+}
@@ -0,0 +1,69 @@
+//! 提交签名：用 gpg 对不含签名的提交内容做分离签名，结果作为 `gpgsig` 头部插入
+
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use anyhow::Context;
+
+use crate::config::Config;
+
+/// 用 `gpg.program`（默认 gpg）对 payload 签名，返回 ASCII 格式的签名。
+/// key 为空时依次使用 `user.signingkey` 和 default_key（提交者身份）
+pub(crate) fn sign(payload: &[u8], key: &str, default_key: &str) -> anyhow::Result<String> {
+    let config = Config::read()?;
+    let program = config
+        .get("gpg.program")
+        .unwrap_or_else(|| "gpg".to_string());
+    let key = match key {
+        "" => config
+            .get("user.signingkey")
+            .unwrap_or_else(|| default_key.to_string()),
+        key => key.to_string(),
+    };
+    let mut child = Command::new(&program)
+        .args(["--status-fd=2", "-bsau", &key])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("cannot run {program}"))?;
+    child
+        .stdin
+        .take()
+        .context("gpg stdin")?
+        .write_all(payload)
+        .context("write data to gpg")?;
+    let output = child.wait_with_output().context("wait for gpg")?;
+    // 与 git 一样以状态输出中的 SIG_CREATED 为准，gpg 的其他输出原样转给用户
+    let status = String::from_utf8_lossy(&output.stderr);
+    let created = status
+        .lines()
+        .any(|line| line.starts_with("[GNUPG:] SIG_CREATED "));
+    if !output.status.success() || !created || output.stdout.is_empty() {
+        let messages: String = status
+            .lines()
+            .filter(|line| !line.starts_with("[GNUPG:] "))
+            .map(|line| format!("{line}\n"))
+            .collect();
+        anyhow::bail!("{messages}gpg failed to sign the data");
+    }
+    // 签名统一使用 LF 换行
+    Ok(String::from_utf8_lossy(&output.stdout).replace("\r\n", "\n"))
+}
+
+/// 把多行的头部插入到提交内容的头部末尾（第一个空行之前），续行以空格开头
+pub(crate) fn insert_header(buf: &mut Vec<u8>, name: &str, value: &str) {
+    let end = buf
+        .windows(2)
+        .position(|pair| pair == b"\n\n")
+        .map_or(buf.len(), |at| at + 1);
+    let mut header = name.to_string();
+    for line in value.lines() {
+        header.push(' ');
+        header.push_str(line);
+        header.push('\n');
+    }
+    buf.splice(end..end, header.into_bytes());
+}
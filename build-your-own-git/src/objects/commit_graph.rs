@@ -0,0 +1,105 @@
+//! 读取 `.git/objects/info/commit-graph` 中的代数（generation number）。
+//! 只支持单个文件，不支持 commit-graph-chain；没有 commit-graph 时所有提交的代数都是无穷大，
+//! 遍历退化为只按提交时间排序
+
+use std::sync::OnceLock;
+
+/// 不在 commit-graph 中的提交的代数
+pub(crate) const GENERATION_INFINITY: u32 = u32::MAX;
+
+const SIGNATURE: &[u8] = b"CGPH";
+const CHUNK_OID_FANOUT: u32 = u32::from_be_bytes(*b"OIDF");
+const CHUNK_OID_LOOKUP: u32 = u32::from_be_bytes(*b"OIDL");
+const CHUNK_DATA: u32 = u32::from_be_bytes(*b"CDAT");
+/// CDAT 每项：树哈希 20 字节 + 两个父提交位置 + 代数和时间
+const DATA_WIDTH: usize = 20 + 16;
+
+struct CommitGraph {
+    data: Vec<u8>,
+    fanout: usize,
+    lookup: usize,
+    commit_data: usize,
+    count: usize,
+}
+
+impl CommitGraph {
+    fn open() -> Option<CommitGraph> {
+        let data = std::fs::read(".git/objects/info/commit-graph").ok()?;
+        // 头部：签名、版本 1、SHA-1、chunk 数、base graph 数
+        if data.len() < 8 || &data[..4] != SIGNATURE || data[4] != 1 || data[5] != 1 {
+            return None;
+        }
+        let chunks = data[6] as usize;
+        let (mut fanout, mut lookup, mut commit_data) = (None, None, None);
+        for i in 0..chunks {
+            let entry = data.get(8 + i * 12..8 + i * 12 + 12)?;
+            let id = u32::from_be_bytes(entry[..4].try_into().ok()?);
+            let offset = u64::from_be_bytes(entry[4..].try_into().ok()?) as usize;
+            match id {
+                CHUNK_OID_FANOUT => fanout = Some(offset),
+                CHUNK_OID_LOOKUP => lookup = Some(offset),
+                CHUNK_DATA => commit_data = Some(offset),
+                _ => {}
+            }
+        }
+        let fanout = fanout?;
+        let count = u32::from_be_bytes(
+            data.get(fanout + 255 * 4..fanout + 256 * 4)?
+                .try_into()
+                .ok()?,
+        ) as usize;
+        let (lookup, commit_data) = (lookup?, commit_data?);
+        if data.len() < lookup + count * 20 || data.len() < commit_data + count * DATA_WIDTH {
+            return None;
+        }
+        Some(CommitGraph {
+            data,
+            fanout,
+            lookup,
+            commit_data,
+            count,
+        })
+    }
+
+    fn fanout(&self, byte: usize) -> usize {
+        let at = self.fanout + byte * 4;
+        u32::from_be_bytes(self.data[at..at + 4].try_into().expect("4 bytes")) as usize
+    }
+
+    /// 在 OIDL 中二分查找提交的位置
+    fn position(&self, id: &[u8; 20]) -> Option<usize> {
+        let start = if id[0] == 0 {
+            0
+        } else {
+            self.fanout(id[0] as usize - 1)
+        };
+        let end = self.fanout(id[0] as usize).min(self.count);
+        let oid = |i: usize| &self.data[self.lookup + i * 20..self.lookup + i * 20 + 20];
+        let (mut lo, mut hi) = (start, end);
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            match oid(mid).cmp(id.as_slice()) {
+                std::cmp::Ordering::Equal => return Some(mid),
+                std::cmp::Ordering::Less => lo = mid + 1,
+                std::cmp::Ordering::Greater => hi = mid,
+            }
+        }
+        None
+    }
+
+    /// 拓扑层级（generation number v1）存在 CDAT 第 28 字节起 4 字节的高 30 位
+    fn generation(&self, id: &[u8; 20]) -> Option<u32> {
+        let at = self.commit_data + self.position(id)? * DATA_WIDTH + 28;
+        Some(u32::from_be_bytes(self.data[at..at + 4].try_into().ok()?) >> 2)
+    }
+}
+
+/// 提交的代数：根提交为 1，其他提交比所有父提交都大；不在 commit-graph 中时为无穷大
+pub(crate) fn generation(id: &[u8; 20]) -> u32 {
+    static GRAPH: OnceLock<Option<CommitGraph>> = OnceLock::new();
+    GRAPH
+        .get_or_init(CommitGraph::open)
+        .as_ref()
+        .and_then(|graph| graph.generation(id))
+        .unwrap_or(GENERATION_INFINITY)
+}
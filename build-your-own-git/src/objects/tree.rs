@@ -0,0 +1,114 @@
+use std::{cmp::Reverse, collections::BTreeMap};
+
+use anyhow::Context;
+
+use crate::objects::{Kind, Mode, Object, read_object};
+
+/// 树对象中的一条记录
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TreeEntry {
+    pub(crate) mode: Mode,
+    pub(crate) name: String,
+    pub(crate) hash: [u8; 20],
+}
+
+/// 解析树对象内容：`<mode> <name>\0<20 字节哈希>` 重复
+pub(crate) fn parse_tree(data: &[u8]) -> anyhow::Result<Vec<TreeEntry>> {
+    let mut entries = Vec::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        let nul = rest
+            .iter()
+            .position(|&b| b == 0)
+            .context("tree entry missing NUL")?;
+        let header = std::str::from_utf8(&rest[..nul]).context("invalid tree entry")?;
+        let (mode, name) = header.split_once(' ').context("invalid tree entry")?;
+        let hash: [u8; 20] = rest
+            .get(nul + 1..nul + 21)
+            .context("truncated tree entry")?
+            .try_into()?;
+        entries.push(TreeEntry {
+            mode: Mode::from_str(mode)?,
+            name: name.to_string(),
+            hash,
+        });
+        rest = &rest[nul + 21..];
+    }
+    Ok(entries)
+}
+
+/// 读取并解析树对象
+pub(crate) async fn read_tree(hash: &[u8; 20]) -> anyhow::Result<Vec<TreeEntry>> {
+    let hex = hex::encode(hash);
+    let (kind, data) = read_object(&hex).await?;
+    anyhow::ensure!(kind == Kind::Tree, "object {hex} is a {kind}, not a tree");
+    parse_tree(&data).with_context(|| format!("parse tree {hex}"))
+}
+
+/// 树中记录的排序：目录名按末尾带 `/` 比较
+fn tree_order(a: &TreeEntry, b: &TreeEntry) -> std::cmp::Ordering {
+    let key = |entry: &TreeEntry| {
+        let mut key = entry.name.as_bytes().to_vec();
+        if entry.mode.is_dir() {
+            key.push(b'/');
+        }
+        key
+    };
+    key(a).cmp(&key(b))
+}
+
+/// 把按路径展开的文件写成树对象（包括所有子树），返回根树的哈希。
+/// 没有文件时写出空树
+pub(crate) async fn write_tree_from_files<'a>(
+    files: impl IntoIterator<Item = (&'a str, Mode, [u8; 20])>,
+) -> anyhow::Result<[u8; 20]> {
+    // 每个目录的直接记录，根目录为空字符串
+    let mut dirs: BTreeMap<String, Vec<TreeEntry>> = BTreeMap::new();
+    dirs.insert(String::new(), Vec::new());
+    for (path, mode, hash) in files {
+        let (dir, name) = path.rsplit_once('/').unwrap_or(("", path));
+        dirs.entry(dir.to_string()).or_default().push(TreeEntry {
+            mode,
+            name: name.to_string(),
+            hash,
+        });
+        let mut dir = dir;
+        while !dir.is_empty() {
+            dir = dir.rsplit_once('/').map_or("", |(parent, _)| parent);
+            dirs.entry(dir.to_string()).or_default();
+        }
+    }
+
+    // 先写深层的目录，子树的哈希再加入父目录
+    let mut order: Vec<String> = dirs.keys().cloned().collect();
+    order.sort_by_key(|dir| Reverse(dir.matches('/').count() + usize::from(!dir.is_empty())));
+    for dir in order {
+        let mut entries = dirs.remove(&dir).unwrap_or_default();
+        entries.sort_by(tree_order);
+        let mut data = Vec::new();
+        for entry in &entries {
+            data.extend_from_slice(entry.mode.to_bytes());
+            data.push(b' ');
+            data.extend_from_slice(entry.name.as_bytes());
+            data.push(0);
+            data.extend_from_slice(&entry.hash);
+        }
+        let hash = Object {
+            kind: Kind::Tree,
+            expected_size: data.len() as u64,
+            reader: data.as_slice(),
+        }
+        .write_object()
+        .await?;
+        if dir.is_empty() {
+            return Ok(hash);
+        }
+        let (parent, name) = dir.rsplit_once('/').unwrap_or(("", &dir));
+        dirs.entry(parent.to_string()).or_default().push(TreeEntry {
+            mode: Mode::Directory,
+            name: name.to_string(),
+            hash,
+        });
+    }
+    unreachable!("root tree is always written last")
+}
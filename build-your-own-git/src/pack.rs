@@ -0,0 +1,347 @@
+//! pack 文件：`PACK`、版本号、对象个数，之后是逐个用 zlib 压缩的对象，最后是前面所有内容的 SHA-1。
+//! 对象可以是完整的，也可以是相对同一个 pack 中前面某个对象的 ofs-delta，
+//! 或相对某个对象哈希的 ref-delta（基对象可能不在 pack 中，如 thin pack）。
+//!
+//! fetch 和 clone 收到的 pack 像 `git unpack-objects` 一样拆成松散对象，receive-pack 收到的
+//! 则带着索引保存在 `objects/pack` 中，读取对象时两处都会查找（见 [`store`]）；
+//! 推送时写出的 pack 只有完整的对象，不做 delta 压缩。给 pack 建索引见 [`index`]
+
+pub(crate) mod index;
+pub(crate) mod store;
+
+use std::{
+    collections::HashMap,
+    io::{BufRead, Read, Write},
+    path::Path,
+};
+
+use anyhow::Context;
+use flate2::{Compression, bufread::ZlibDecoder, write::ZlibEncoder};
+use sha1::{Digest, Sha1};
+
+use crate::objects::{Kind, read_object_in, write_object_in};
+
+/// zlib 的压缩比不超过约 1032:1，用来限制按对方声称的大小预先分配的内存
+const MAX_RATIO: usize = 1032;
+
+/// pack 中一个对象压缩前的内容
+enum Raw {
+    Whole(Kind, Vec<u8>),
+    /// 基对象在 pack 中的偏移
+    OfsDelta(usize, Vec<u8>),
+    RefDelta([u8; 20], Vec<u8>),
+}
+
+/// 把 pack 中的所有对象写入 objects 这个对象目录，返回对象个数
+pub(crate) async fn unpack(pack: &[u8], objects: &Path) -> anyhow::Result<usize> {
+    let count = check(pack)?;
+    let body = &pack[..pack.len() - 20];
+
+    // 已经还原的对象，按在 pack 中的偏移和哈希查找
+    let mut by_offset: HashMap<usize, (Kind, Vec<u8>)> = HashMap::new();
+    let mut by_id: HashMap<[u8; 20], usize> = HashMap::new();
+    // 基对象还没有还原的 delta，最后再处理
+    let mut pending: Vec<(usize, Raw)> = Vec::new();
+    let mut offset = 12;
+    // 按顺序处理，大多数 delta 的基对象都在前面
+    for _ in 0..count {
+        let (raw, next) = read_entry(body, offset)?;
+        if let Some(raw) = resolve(offset, raw, objects, &mut by_offset, &mut by_id).await? {
+            pending.push((offset, raw));
+        }
+        offset = next;
+    }
+    anyhow::ensure!(offset == body.len(), "pack has junk at the end");
+    while !pending.is_empty() {
+        let before = pending.len();
+        for (start, raw) in std::mem::take(&mut pending) {
+            if let Some(raw) = resolve(start, raw, objects, &mut by_offset, &mut by_id).await? {
+                pending.push((start, raw));
+            }
+        }
+        anyhow::ensure!(
+            pending.len() < before,
+            "pack has {} unresolved deltas",
+            pending.len()
+        );
+    }
+    Ok(count)
+}
+
+/// 检查 pack 的头和末尾的校验和，返回对象个数
+fn check(pack: &[u8]) -> anyhow::Result<usize> {
+    anyhow::ensure!(
+        pack.len() >= 32 && &pack[..4] == b"PACK",
+        "protocol error: bad pack header"
+    );
+    let version = u32::from_be_bytes(pack[4..8].try_into()?);
+    anyhow::ensure!(
+        version == 2 || version == 3,
+        "unknown pack file version {version}"
+    );
+    let (body, trailer) = pack.split_at(pack.len() - 20);
+    anyhow::ensure!(
+        Sha1::digest(body).as_slice() == trailer,
+        "pack is corrupted (SHA1 mismatch)"
+    );
+    Ok(u32::from_be_bytes(pack[8..12].try_into()?) as usize)
+}
+
+/// 把对象目录 objects 中的 ids 写成一个 pack
+pub(crate) async fn write(objects: &Path, ids: &[[u8; 20]]) -> anyhow::Result<Vec<u8>> {
+    let mut pack = b"PACK".to_vec();
+    pack.extend_from_slice(&2u32.to_be_bytes());
+    pack.extend_from_slice(&(ids.len() as u32).to_be_bytes());
+    for id in ids {
+        let (kind, data) = read_object_in(objects, &hex::encode(id)).await?;
+        write_entry(&mut pack, &kind, &data)?;
+    }
+    let trailer = Sha1::digest(&pack);
+    pack.extend_from_slice(&trailer);
+    Ok(pack)
+}
+
+/// 把一个完整的对象追加到 pack 中
+fn write_entry(pack: &mut Vec<u8>, kind: &Kind, data: &[u8]) -> anyhow::Result<()> {
+    let kind: u8 = match kind {
+        Kind::Commit => 1,
+        Kind::Tree => 2,
+        Kind::Blob => 3,
+        Kind::Tag => 4,
+    };
+    // 类型和大小的变长头：第一个字节放类型和大小的低 4 位，之后每个字节 7 位
+    let mut size = data.len();
+    let mut byte = (kind << 4) | (size & 0x0f) as u8;
+    size >>= 4;
+    while size > 0 {
+        pack.push(byte | 0x80);
+        byte = (size & 0x7f) as u8;
+        size >>= 7;
+    }
+    pack.push(byte);
+    let mut encoder = ZlibEncoder::new(pack, Compression::default());
+    encoder.write_all(data)?;
+    encoder.finish()?;
+    Ok(())
+}
+
+/// 还原并写入一个对象，基对象还没有时原样返回
+async fn resolve(
+    offset: usize,
+    raw: Raw,
+    objects: &Path,
+    by_offset: &mut HashMap<usize, (Kind, Vec<u8>)>,
+    by_id: &mut HashMap<[u8; 20], usize>,
+) -> anyhow::Result<Option<Raw>> {
+    let (kind, data) = match raw {
+        Raw::Whole(kind, data) => (kind, data),
+        Raw::OfsDelta(base, delta) => match by_offset.get(&base) {
+            Some((kind, base)) => (kind.clone(), apply_delta(base, &delta)?),
+            None => return Ok(Some(Raw::OfsDelta(base, delta))),
+        },
+        Raw::RefDelta(base, delta) => {
+            if let Some((kind, data)) = by_id.get(&base).and_then(|offset| by_offset.get(offset)) {
+                (kind.clone(), apply_delta(data, &delta)?)
+            } else if let Ok((kind, data)) = read_object_in(objects, &hex::encode(base)).await {
+                // thin pack：基对象在本地已有
+                (kind, apply_delta(&data, &delta)?)
+            } else {
+                return Ok(Some(Raw::RefDelta(base, delta)));
+            }
+        }
+    };
+    let id = write_object_in(objects, &kind, &data)?;
+    by_id.insert(id, offset);
+    by_offset.insert(offset, (kind, data));
+    Ok(None)
+}
+
+/// 读取 offset 处的对象：类型和大小的变长头，ofs-delta 的负偏移或 ref-delta 的基对象哈希，
+/// 然后是压缩的内容。返回对象和下一个对象的偏移
+fn read_entry(pack: &[u8], offset: usize) -> anyhow::Result<(Raw, usize)> {
+    let truncated = || anyhow::anyhow!("pack is truncated at offset {offset}");
+    let mut pos = offset;
+    let mut byte = *pack.get(pos).ok_or_else(truncated)?;
+    pos += 1;
+    let kind = (byte >> 4) & 0x7;
+    let mut size = (byte & 0x0f) as usize;
+    let mut shift = 4;
+    while byte & 0x80 != 0 {
+        anyhow::ensure!(shift <= 63, "bad object size at offset {offset}");
+        byte = *pack.get(pos).ok_or_else(truncated)?;
+        pos += 1;
+        size |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+    }
+    let base = match kind {
+        6 => {
+            // 与大小不同，每多一个字节先加 1，避免同一个值有多种写法
+            let mut byte = *pack.get(pos).ok_or_else(truncated)?;
+            pos += 1;
+            let mut distance = (byte & 0x7f) as usize;
+            while byte & 0x80 != 0 {
+                byte = *pack.get(pos).ok_or_else(truncated)?;
+                pos += 1;
+                distance = distance
+                    .checked_add(1)
+                    .and_then(|distance| distance.checked_mul(1 << 7))
+                    .with_context(|| format!("bad delta base offset at {offset}"))?
+                    | (byte & 0x7f) as usize;
+            }
+            let base = offset
+                .checked_sub(distance)
+                .filter(|base| *base >= 12)
+                .with_context(|| format!("bad delta base offset at {offset}"))?;
+            Some(Ok(base))
+        }
+        7 => {
+            let id = pack.get(pos..pos + 20).ok_or_else(truncated)?;
+            pos += 20;
+            Some(Err(<[u8; 20]>::try_from(id)?))
+        }
+        _ => None,
+    };
+    let mut decoder = ZlibDecoder::new(&pack[pos..]);
+    // 大小来自对方，只按剩下的压缩数据能解出的上限预先分配
+    let mut data = Vec::with_capacity(size.min((pack.len() - pos).saturating_mul(MAX_RATIO)));
+    decoder
+        .read_to_end(&mut data)
+        .with_context(|| format!("inflate object at offset {offset}"))?;
+    anyhow::ensure!(
+        data.len() == size,
+        "object at offset {offset} has wrong size"
+    );
+    pos += decoder.total_in() as usize;
+    let raw = match (kind, base) {
+        (1, None) => Raw::Whole(Kind::Commit, data),
+        (2, None) => Raw::Whole(Kind::Tree, data),
+        (3, None) => Raw::Whole(Kind::Blob, data),
+        (4, None) => Raw::Whole(Kind::Tag, data),
+        (_, Some(Ok(base))) => Raw::OfsDelta(base, data),
+        (_, Some(Err(base))) => Raw::RefDelta(base, data),
+        (kind, None) => anyhow::bail!("unknown object type {kind} at offset {offset}"),
+    };
+    Ok((raw, pos))
+}
+
+/// 按 delta 从 base 还原对象：开头是基对象和结果的大小，之后每条指令要么从基对象复制一段，
+/// 要么插入紧跟着的若干字节
+pub(crate) fn apply_delta(base: &[u8], delta: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let corrupt = || anyhow::anyhow!("corrupt delta");
+    let mut pos = 0;
+    let varint = |pos: &mut usize| -> anyhow::Result<usize> {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            anyhow::ensure!(shift <= 63, "corrupt delta");
+            let byte = *delta.get(*pos).ok_or_else(corrupt)?;
+            *pos += 1;
+            value |= ((byte & 0x7f) as usize) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+    };
+    let base_size = varint(&mut pos)?;
+    anyhow::ensure!(base_size == base.len(), "delta base size mismatch");
+    let size = varint(&mut pos)?;
+    // 同样不能相信 delta 声称的大小，先按基对象和 delta 的长度分配，不够时再增长
+    let mut out = Vec::with_capacity(size.min(base.len().saturating_add(delta.len())));
+    while pos < delta.len() {
+        let op = delta[pos];
+        pos += 1;
+        if op & 0x80 != 0 {
+            // 复制：低 4 位标记偏移的哪几个字节存在，接下来 3 位标记大小的
+            let mut fields = [0usize; 7];
+            for (bit, field) in fields.iter_mut().enumerate() {
+                if op & (1 << bit) != 0 {
+                    *field = *delta.get(pos).ok_or_else(corrupt)? as usize;
+                    pos += 1;
+                }
+            }
+            let offset = fields[0] | fields[1] << 8 | fields[2] << 16 | fields[3] << 24;
+            let len = match fields[4] | fields[5] << 8 | fields[6] << 16 {
+                0 => 0x10000,
+                len => len,
+            };
+            let chunk = base.get(offset..offset + len).ok_or_else(corrupt)?;
+            out.extend_from_slice(chunk);
+        } else if op != 0 {
+            let chunk = delta.get(pos..pos + op as usize).ok_or_else(corrupt)?;
+            out.extend_from_slice(chunk);
+            pos += op as usize;
+        } else {
+            return Err(corrupt());
+        }
+    }
+    anyhow::ensure!(out.len() == size, "delta result size mismatch");
+    Ok(out)
+}
+
+/// 从流中读出一个完整的 pack，如接收推送时跟在命令后面的 pack。
+/// 流中没有长度，只能逐个对象解压才知道 pack 在哪里结束
+pub(crate) fn read(reader: &mut dyn BufRead) -> anyhow::Result<Vec<u8>> {
+    let mut tee = Tee {
+        inner: reader,
+        copy: Vec::new(),
+    };
+    let mut header = [0u8; 12];
+    tee.read_exact(&mut header)
+        .context("protocol error: bad pack header")?;
+    anyhow::ensure!(&header[..4] == b"PACK", "protocol error: bad pack header");
+    let count = u32::from_be_bytes(header[8..12].try_into()?);
+    for _ in 0..count {
+        let mut byte = [0u8; 1];
+        tee.read_exact(&mut byte)?;
+        let kind = (byte[0] >> 4) & 0x7;
+        while byte[0] & 0x80 != 0 {
+            tee.read_exact(&mut byte)?;
+        }
+        match kind {
+            6 => loop {
+                tee.read_exact(&mut byte)?;
+                if byte[0] & 0x80 == 0 {
+                    break;
+                }
+            },
+            7 => tee.read_exact(&mut [0u8; 20])?,
+            _ => {}
+        }
+        // bufread 的解压器只消耗压缩流本身的字节
+        std::io::copy(&mut ZlibDecoder::new(&mut tee), &mut std::io::sink())
+            .context("inflate object in pack")?;
+    }
+    tee.read_exact(&mut [0u8; 20])
+        .context("pack is truncated")?;
+    Ok(tee.copy)
+}
+
+/// 把读取时消耗的字节都记下来的 BufRead
+struct Tee<'a> {
+    inner: &'a mut dyn BufRead,
+    copy: Vec<u8>,
+}
+
+impl Read for Tee<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl BufRead for Tee<'_> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if let Ok(buf) = self.inner.fill_buf() {
+            self.copy.extend_from_slice(&buf[..amt]);
+        }
+        self.inner.consume(amt);
+    }
+}
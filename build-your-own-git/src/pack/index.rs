@@ -0,0 +1,255 @@
+//! 给 pack 建索引：逐个对象解压、还原 delta 并算出哈希，写出 `.idx` 和 `.rev`。
+//!
+//! v2 的 `.idx`：魔数 `\377tOc`、版本 2、256 项的扇出表（第 i 项是首字节不大于 i 的对象个数），
+//! 按哈希排序的对象哈希、各对象在 pack 中原样字节的 CRC32、4 字节的偏移（最高位为 1 时
+//! 低 31 位是后面 8 字节偏移表的下标），最后是 pack 的校验和与前面所有内容的 SHA-1。
+//! `.rev` 是 `RIDX`、版本 1、哈希算法 1（SHA-1），之后按 pack 中的顺序列出每个对象在 `.idx`
+//! 中的位置，最后同样是 pack 的校验和与自身的 SHA-1
+
+use std::{collections::HashMap, path::Path};
+
+use anyhow::Context;
+use sha1::{Digest, Sha1};
+
+use super::{Raw, apply_delta, check, read_entry, write_entry};
+use crate::objects::{Kind, hash_object, read_object_in};
+
+/// pack 中的一个对象
+pub(crate) struct Entry {
+    pub(crate) id: [u8; 20],
+    /// 还原后的类型，delta 也是还原出的对象的类型
+    pub(crate) kind: Kind,
+    pub(crate) offset: usize,
+    /// 在 pack 中占的字节数，包括对象头
+    pub(crate) packed_size: usize,
+    /// 对象头中的大小，delta 是 delta 本身的大小
+    pub(crate) size: usize,
+    pub(crate) crc32: u32,
+    /// delta 链的长度和基对象在 entries 中的下标
+    pub(crate) delta: Option<(usize, usize)>,
+}
+
+pub(crate) struct Index {
+    /// 按在 pack 中的顺序
+    pub(crate) entries: Vec<Entry>,
+    /// pack 末尾的校验和，也是 pack 的名字
+    pub(crate) checksum: [u8; 20],
+}
+
+/// 还原中的一个对象
+struct Pending {
+    raw: Option<Raw>,
+    offset: usize,
+    packed_size: usize,
+    size: usize,
+    crc32: u32,
+    resolved: Option<(Kind, Vec<u8>, [u8; 20])>,
+    delta: Option<(usize, usize)>,
+}
+
+/// 给 pack 建索引。thin 给出时，基对象不在 pack 中的 ref-delta 从这个对象目录中读取基对象，
+/// 并像 `--fix-thin` 一样把基对象追加到 pack 末尾，pack 头中的对象个数和校验和随之更新
+pub(crate) async fn index(pack: &mut Vec<u8>, thin: Option<&Path>) -> anyhow::Result<Index> {
+    let count = check(pack)?;
+    let mut trailer = pack.split_off(pack.len() - 20);
+
+    // 对象个数来自 pack 头，每个对象至少占一个字节，不按更大的个数预先分配
+    let mut objects = Vec::with_capacity(count.min(pack.len()));
+    let mut by_offset = HashMap::new();
+    let mut offset = 12;
+    for _ in 0..count {
+        let (raw, next) = read_entry(pack, offset)?;
+        by_offset.insert(offset, objects.len());
+        objects.push(Pending {
+            size: raw_size(&raw),
+            raw: Some(raw),
+            offset,
+            packed_size: next - offset,
+            crc32: crc32(&pack[offset..next]),
+            resolved: None,
+            delta: None,
+        });
+        offset = next;
+    }
+    anyhow::ensure!(offset == pack.len(), "pack has junk at the end");
+
+    // 已经还原的对象按哈希查找，ref-delta 要用
+    let mut by_id: HashMap<[u8; 20], usize> = HashMap::new();
+    let mut unresolved = count;
+    loop {
+        // 反复扫描，直到没有能还原的；大多数 delta 的基对象都在前面，一遍就能还原大半
+        let mut progress = true;
+        while progress {
+            progress = false;
+            for i in 0..objects.len() {
+                let base = match &objects[i].raw {
+                    None => continue,
+                    Some(Raw::Whole(..)) => None,
+                    Some(Raw::OfsDelta(base, _)) => {
+                        let base = by_offset.get(base).with_context(|| {
+                            format!("bad delta base offset at {}", objects[i].offset)
+                        })?;
+                        Some(*base)
+                    }
+                    Some(Raw::RefDelta(base, _)) => match by_id.get(base) {
+                        Some(base) => Some(*base),
+                        None => continue,
+                    },
+                };
+                // 基对象本身还没有还原
+                if base.is_some_and(|base| objects[base].resolved.is_none()) {
+                    continue;
+                }
+                let (kind, data, delta) = match (objects[i].raw.take(), base) {
+                    (Some(Raw::Whole(kind, data)), _) => (kind, data, None),
+                    (Some(Raw::OfsDelta(_, delta) | Raw::RefDelta(_, delta)), Some(base)) => {
+                        let (kind, data, _) = objects[base].resolved.as_ref().expect("resolved");
+                        let depth = objects[base].delta.map_or(0, |(depth, _)| depth);
+                        (
+                            kind.clone(),
+                            apply_delta(data, &delta)?,
+                            Some((depth + 1, base)),
+                        )
+                    }
+                    _ => unreachable!("delta without base"),
+                };
+                let id = hash_object(&kind, &data);
+                by_id.entry(id).or_insert(i);
+                objects[i].resolved = Some((kind, data, id));
+                objects[i].delta = delta;
+                unresolved -= 1;
+                progress = true;
+            }
+        }
+        if unresolved == 0 {
+            break;
+        }
+
+        // thin pack：按在 pack 中的顺序找第一个基对象不在 pack 中的 ref-delta，从本地补上基对象
+        let missing = objects.iter().find_map(|object| match &object.raw {
+            Some(Raw::RefDelta(base, _)) if !by_id.contains_key(base) => Some(*base),
+            _ => None,
+        });
+        let (Some(thin), Some(base)) = (thin, missing) else {
+            anyhow::bail!("pack has {unresolved} unresolved deltas");
+        };
+        let Ok((kind, data)) = read_object_in(thin, &hex::encode(base)).await else {
+            anyhow::bail!("pack has {unresolved} unresolved deltas");
+        };
+        let offset = pack.len();
+        write_entry(pack, &kind, &data)?;
+        by_id.insert(base, objects.len());
+        objects.push(Pending {
+            raw: None,
+            offset,
+            packed_size: pack.len() - offset,
+            size: data.len(),
+            crc32: crc32(&pack[offset..]),
+            resolved: Some((kind, data, base)),
+            delta: None,
+        });
+    }
+
+    if objects.len() > count {
+        let count = objects.len() as u32;
+        pack[8..12].copy_from_slice(&count.to_be_bytes());
+        trailer = Sha1::digest(&pack[..]).to_vec();
+    }
+    pack.extend_from_slice(&trailer);
+    let entries = objects
+        .into_iter()
+        .map(|object| {
+            let (kind, _, id) = object.resolved.expect("all objects resolved");
+            Entry {
+                id,
+                kind,
+                offset: object.offset,
+                packed_size: object.packed_size,
+                size: object.size,
+                crc32: object.crc32,
+                delta: object.delta,
+            }
+        })
+        .collect();
+    Ok(Index {
+        entries,
+        checksum: trailer.try_into().expect("20-byte trailer"),
+    })
+}
+
+/// 对象头中的大小，也就是解压后的长度
+fn raw_size(raw: &Raw) -> usize {
+    match raw {
+        Raw::Whole(_, data) | Raw::OfsDelta(_, data) | Raw::RefDelta(_, data) => data.len(),
+    }
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = flate2::Crc::new();
+    crc.update(data);
+    crc.sum()
+}
+
+/// 按哈希排序的对象下标
+fn sorted(index: &Index) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..index.entries.len()).collect();
+    order.sort_by_key(|&i| index.entries[i].id);
+    order
+}
+
+/// 写出 v2 的 `.idx`
+pub(crate) fn write_idx(index: &Index) -> Vec<u8> {
+    let order = sorted(index);
+    let entries = &index.entries;
+    let mut idx = b"\xfftOc".to_vec();
+    idx.extend_from_slice(&2u32.to_be_bytes());
+    for byte in 0..=255u8 {
+        let count = entries.iter().filter(|entry| entry.id[0] <= byte).count() as u32;
+        idx.extend_from_slice(&count.to_be_bytes());
+    }
+    for &i in &order {
+        idx.extend_from_slice(&entries[i].id);
+    }
+    for &i in &order {
+        idx.extend_from_slice(&entries[i].crc32.to_be_bytes());
+    }
+    let mut large = Vec::new();
+    for &i in &order {
+        let offset = entries[i].offset as u64;
+        match u32::try_from(offset)
+            .ok()
+            .filter(|offset| offset & 0x8000_0000 == 0)
+        {
+            Some(offset) => idx.extend_from_slice(&offset.to_be_bytes()),
+            None => {
+                let slot = (large.len() / 8) as u32 | 0x8000_0000;
+                idx.extend_from_slice(&slot.to_be_bytes());
+                large.extend_from_slice(&offset.to_be_bytes());
+            }
+        }
+    }
+    idx.extend_from_slice(&large);
+    idx.extend_from_slice(&index.checksum);
+    let checksum = Sha1::digest(&idx);
+    idx.extend_from_slice(&checksum);
+    idx
+}
+
+/// 写出 `.rev`
+pub(crate) fn write_rev(index: &Index) -> Vec<u8> {
+    let mut position = vec![0u32; index.entries.len()];
+    for (n, i) in sorted(index).into_iter().enumerate() {
+        position[i] = n as u32;
+    }
+    let mut rev = b"RIDX".to_vec();
+    rev.extend_from_slice(&1u32.to_be_bytes());
+    rev.extend_from_slice(&1u32.to_be_bytes());
+    // entries 已经按在 pack 中的顺序
+    for n in position {
+        rev.extend_from_slice(&n.to_be_bytes());
+    }
+    rev.extend_from_slice(&index.checksum);
+    let checksum = Sha1::digest(&rev);
+    rev.extend_from_slice(&checksum);
+    rev
+}
@@ -0,0 +1,188 @@
+//! 对象库中的 pack：`objects/pack/pack-<校验和>.pack` 和它的 `.idx`。
+//!
+//! 查找对象时在各个 `.idx` 的扇出表确定的范围内二分查找哈希，得到对象在 pack 中的偏移，
+//! 再从 pack 中解压，ofs-delta 和 ref-delta 沿着基对象逐级还原。
+//! 同一个进程中读过的 `.idx` 和 pack 都留在内存里，协商和遍历历史时要连续读很多对象。
+//! pack 的文件名含有校验和，内容不会改变；写出新的 pack 时去掉同名文件的缓存
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use anyhow::Context;
+
+use super::{Raw, apply_delta, index, read_entry};
+use crate::objects::{Kind, read_object_in};
+
+/// 读过的文件，按路径缓存
+fn cache() -> &'static Mutex<HashMap<PathBuf, Arc<Vec<u8>>>> {
+    static CACHE: OnceLock<Mutex<HashMap<PathBuf, Arc<Vec<u8>>>>> = OnceLock::new();
+    CACHE.get_or_init(Default::default)
+}
+
+fn read_cached(path: &Path) -> anyhow::Result<Arc<Vec<u8>>> {
+    if let Some(data) = cache().lock().expect("pack cache").get(path) {
+        return Ok(data.clone());
+    }
+    let data = Arc::new(std::fs::read(path).with_context(|| format!("read {}", path.display()))?);
+    cache()
+        .lock()
+        .expect("pack cache")
+        .insert(path.to_path_buf(), data.clone());
+    Ok(data)
+}
+
+/// 对象目录 objects 中所有 pack 的 `.idx`
+fn indexes(objects: &Path) -> Vec<PathBuf> {
+    let Ok(dir) = std::fs::read_dir(objects.join("pack")) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = dir
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "idx"))
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// v2 的 `.idx` 中对象个数
+fn count(idx: &[u8]) -> anyhow::Result<usize> {
+    anyhow::ensure!(
+        idx.len() >= 8 + 256 * 4 + 40 && idx[..4] == *b"\xfftOc",
+        "unsupported pack index"
+    );
+    Ok(be32(idx, 8 + 255 * 4) as usize)
+}
+
+/// 第 n 个（按哈希排序）对象的哈希
+fn id_at(idx: &[u8], n: usize) -> &[u8] {
+    let start = 8 + 256 * 4 + n * 20;
+    &idx[start..start + 20]
+}
+
+/// id 在 `.idx` 中的位置：扇出表给出首字节相同的对象所在的范围，在其中二分查找
+fn position(idx: &[u8], id: &[u8; 20]) -> anyhow::Result<Option<usize>> {
+    count(idx)?;
+    let first = id[0] as usize;
+    let mut low = match first {
+        0 => 0,
+        _ => be32(idx, 8 + (first - 1) * 4) as usize,
+    };
+    let mut high = be32(idx, 8 + first * 4) as usize;
+    while low < high {
+        let mid = (low + high) / 2;
+        match id_at(idx, mid).cmp(id.as_slice()) {
+            std::cmp::Ordering::Less => low = mid + 1,
+            std::cmp::Ordering::Greater => high = mid,
+            std::cmp::Ordering::Equal => return Ok(Some(mid)),
+        }
+    }
+    Ok(None)
+}
+
+/// 第 n 个对象在 pack 中的偏移，最高位为 1 时是 8 字节偏移表的下标
+fn offset_at(idx: &[u8], n: usize) -> usize {
+    let count = be32(idx, 8 + 255 * 4) as usize;
+    let offsets = 8 + 256 * 4 + count * 24;
+    let offset = be32(idx, offsets + n * 4);
+    if offset & 0x8000_0000 == 0 {
+        return offset as usize;
+    }
+    let at = offsets + count * 4 + (offset & 0x7fff_ffff) as usize * 8;
+    u64::from_be_bytes(idx[at..at + 8].try_into().expect("8 bytes")) as usize
+}
+
+fn be32(data: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes(data[at..at + 4].try_into().expect("4 bytes"))
+}
+
+/// id 是否在对象目录 objects 的某个 pack 中
+pub(crate) fn contains(objects: &Path, id: &[u8; 20]) -> bool {
+    indexes(objects).iter().any(|path| {
+        read_cached(path)
+            .and_then(|idx| position(&idx, id))
+            .is_ok_and(|n| n.is_some())
+    })
+}
+
+/// pack 中以 prefix（十六进制）开头的对象哈希
+pub(crate) fn ids_with_prefix(objects: &Path, prefix: &str) -> Vec<String> {
+    let mut found = Vec::new();
+    for path in indexes(objects) {
+        let Ok(idx) = read_cached(&path) else {
+            continue;
+        };
+        let Ok(count) = count(&idx) else {
+            continue;
+        };
+        found.extend(
+            (0..count)
+                .map(|n| hex::encode(id_at(&idx, n)))
+                .filter(|id| id.starts_with(prefix)),
+        );
+    }
+    found
+}
+
+/// 从对象目录 objects 的 pack 中读取 id，不在任何 pack 中时返回 None
+pub(crate) async fn read(objects: &Path, id: &[u8; 20]) -> anyhow::Result<Option<(Kind, Vec<u8>)>> {
+    for idx_path in indexes(objects) {
+        let idx = read_cached(&idx_path)?;
+        let Some(n) = position(&idx, id)? else {
+            continue;
+        };
+        let pack = read_cached(&idx_path.with_extension("pack"))?;
+        let object = read_at(objects, &pack, offset_at(&idx, n)).await?;
+        return Ok(Some(object));
+    }
+    Ok(None)
+}
+
+/// 还原 pack 中 offset 处的对象。ofs-delta 的基对象在同一个 pack 中，
+/// ref-delta 的基对象可能在对象库的任何地方
+async fn read_at(objects: &Path, pack: &[u8], offset: usize) -> anyhow::Result<(Kind, Vec<u8>)> {
+    // 先沿着 delta 链找到完整的基对象，再从它开始逐级还原
+    let mut deltas = Vec::new();
+    let mut offset = offset;
+    let (kind, mut data) = loop {
+        match read_entry(pack, offset)?.0 {
+            Raw::Whole(kind, data) => break (kind, data),
+            Raw::OfsDelta(base, delta) => {
+                deltas.push(delta);
+                offset = base;
+            }
+            Raw::RefDelta(base, delta) => {
+                deltas.push(delta);
+                break Box::pin(read_object_in(objects, &hex::encode(base))).await?;
+            }
+        }
+    };
+    for delta in deltas.iter().rev() {
+        data = apply_delta(&data, delta)?;
+    }
+    Ok((kind, data))
+}
+
+/// 像 `git index-pack --fix-thin` 一样保存收到的 pack：补上 thin pack 缺少的基对象，
+/// 写出 `objects/pack/pack-<校验和>.pack`、`.idx` 和 `.rev`，返回校验和
+pub(crate) async fn store(objects: &Path, mut pack: Vec<u8>) -> anyhow::Result<String> {
+    let index = index::index(&mut pack, Some(objects)).await?;
+    let name = hex::encode(index.checksum);
+    let dir = objects.join("pack");
+    std::fs::create_dir_all(&dir).with_context(|| format!("create {}", dir.display()))?;
+    let base = dir.join(format!("pack-{name}"));
+    // 最后写 .idx，写好之前这个 pack 对读取的一方不可见
+    for (ext, data) in [
+        ("pack", pack),
+        ("rev", index::write_rev(&index)),
+        ("idx", index::write_idx(&index)),
+    ] {
+        let path = base.with_extension(ext);
+        std::fs::write(&path, data).with_context(|| format!("write {}", path.display()))?;
+        cache().lock().expect("pack cache").remove(&path);
+    }
+    Ok(name)
+}
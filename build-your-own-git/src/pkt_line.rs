@@ -0,0 +1,87 @@
+//! pkt-line：git 传输协议的分帧格式。每帧以 4 位十六进制长度开头（包含这 4 位），
+//! 长度 0000、0001、0002 分别是 flush、delim 和 response-end 这几个特殊帧
+
+use std::io::{BufRead, Write};
+
+use anyhow::Context;
+
+/// 一帧的最大长度，与 git 的 LARGE_PACKET_MAX 相同
+pub(crate) const MAX_LEN: usize = 65520;
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Packet {
+    Data(Vec<u8>),
+    Flush,
+    Delim,
+    ResponseEnd,
+}
+
+impl Packet {
+    /// 文本帧去掉末尾换行后的内容，特殊帧为 None
+    pub(crate) fn text(&self) -> Option<&str> {
+        match self {
+            Packet::Data(data) => std::str::from_utf8(data)
+                .ok()
+                .map(|text| text.strip_suffix('\n').unwrap_or(text)),
+            _ => None,
+        }
+    }
+}
+
+/// 读取一帧，对方关闭连接时返回 None
+pub(crate) fn read<R: BufRead + ?Sized>(reader: &mut R) -> anyhow::Result<Option<Packet>> {
+    let mut header = [0u8; 4];
+    match reader.read_exact(&mut header) {
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        result => result.context("read pkt-line length")?,
+    }
+    let len = std::str::from_utf8(&header)
+        .ok()
+        .and_then(|len| usize::from_str_radix(len, 16).ok())
+        .with_context(|| format!("bad pkt-line length '{}'", String::from_utf8_lossy(&header)))?;
+    Ok(Some(match len {
+        0 => Packet::Flush,
+        1 => Packet::Delim,
+        2 => Packet::ResponseEnd,
+        3 => anyhow::bail!("bad pkt-line length 3"),
+        len if len > MAX_LEN => anyhow::bail!("pkt-line too long: {len}"),
+        len => {
+            let mut data = vec![0; len - 4];
+            reader.read_exact(&mut data).context("read pkt-line")?;
+            Packet::Data(data)
+        }
+    }))
+}
+
+/// 读取一帧，连接已关闭时报错
+pub(crate) fn expect<R: BufRead + ?Sized>(reader: &mut R) -> anyhow::Result<Packet> {
+    read(reader)?.context("the remote end hung up unexpectedly")
+}
+
+/// 写入一个数据帧
+pub(crate) fn write(writer: &mut impl Write, data: &[u8]) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        data.len() + 4 <= MAX_LEN,
+        "pkt-line too long: {}",
+        data.len()
+    );
+    write!(writer, "{:04x}", data.len() + 4)?;
+    writer.write_all(data)?;
+    Ok(())
+}
+
+/// 写入一行文本，自动加上换行
+pub(crate) fn write_line(writer: &mut impl Write, line: &str) -> anyhow::Result<()> {
+    write(writer, format!("{line}\n").as_bytes())
+}
+
+pub(crate) fn flush(writer: &mut impl Write) -> anyhow::Result<()> {
+    writer.write_all(b"0000")?;
+    writer.flush()?;
+    Ok(())
+}
+
+pub(crate) fn delim(writer: &mut impl Write) -> anyhow::Result<()> {
+    writer.write_all(b"0001")?;
+    Ok(())
+}
@@ -0,0 +1,168 @@
+//! 接收端：对象已经放进目标仓库后，在目标仓库中应用推送来的引用更新。
+//! 与 git receive-pack 一样先运行 pre-receive，再逐个引用检查并运行 update，
+//! 写入引用后运行 post-receive 和 post-update
+
+use std::path::Path;
+
+use crate::{commands::commit::committer_ident, config::Config, hook, refs};
+
+/// 推送方请求的一次引用更新，不存在的一边记为全零
+#[derive(Debug, Clone)]
+pub(crate) struct Command {
+    pub(crate) name: String,
+    pub(crate) old: [u8; 20],
+    pub(crate) new: [u8; 20],
+}
+
+impl Command {
+    fn is_delete(&self) -> bool {
+        self.new == [0; 20]
+    }
+
+    /// 钩子标准输入中的一行：`旧值 新值 引用名`
+    fn line(&self) -> String {
+        format!(
+            "{} {} {}\n",
+            hex::encode(self.old),
+            hex::encode(self.new),
+            self.name
+        )
+    }
+}
+
+/// 在 git_dir 中应用 commands，返回每个命令被拒绝的原因，成功的为 None。
+/// atomic 时有一个被拒绝就都不更新
+pub(crate) fn receive(
+    git_dir: &Path,
+    commands: &[Command],
+    atomic: bool,
+) -> anyhow::Result<Vec<Option<String>>> {
+    let config = Config::read_file(git_dir.join("config"))?;
+    let input: String = commands.iter().map(Command::line).collect();
+    if !hook::run_in(git_dir, "pre-receive", &[], Some(input.as_bytes()))? {
+        return Ok(vec![
+            Some("pre-receive hook declined".to_string());
+            commands.len()
+        ]);
+    }
+
+    let mut results: Vec<Option<String>> = vec![None; commands.len()];
+    for (command, result) in commands.iter().zip(results.iter_mut()) {
+        *result = check(git_dir, &config, command)?;
+        if result.is_none() && !run_update_hook(git_dir, command)? {
+            hook::remote_message(&format!("error: hook declined to update {}", command.name));
+            *result = Some("hook declined".to_string());
+        }
+        if atomic && result.is_some() {
+            break;
+        }
+    }
+    if atomic && results.iter().any(Option::is_some) {
+        for result in &mut results {
+            result.get_or_insert_with(|| "atomic push failure".to_string());
+        }
+        return Ok(results);
+    }
+
+    // 与 git 一样，非裸仓库默认记录分支的 reflog
+    let bare = config.get_bool("core.bare")?.unwrap_or(false);
+    let log_updates = config.get_bool("core.logAllRefUpdates")?.unwrap_or(!bare);
+    let ident = committer_ident()?;
+    for (command, result) in commands.iter().zip(&results) {
+        if result.is_some() {
+            continue;
+        }
+        if command.is_delete() {
+            refs::delete_ref_in(git_dir, &command.name)?;
+            continue;
+        }
+        refs::write_ref_in(git_dir, &command.name, &command.new)?;
+        if log_updates && command.name.starts_with("refs/heads/") {
+            refs::append_reflog_in(
+                git_dir,
+                &command.name,
+                &command.old,
+                &command.new,
+                &ident,
+                "push",
+            )?;
+        }
+    }
+
+    let updated: Vec<&Command> = commands
+        .iter()
+        .zip(&results)
+        .filter(|(_, result)| result.is_none())
+        .map(|(command, _)| command)
+        .collect();
+    if !updated.is_empty() {
+        let input: String = updated.iter().map(|command| command.line()).collect();
+        hook::run_in(git_dir, "post-receive", &[], Some(input.as_bytes()))?;
+        let names: Vec<&str> = updated
+            .iter()
+            .map(|command| command.name.as_str())
+            .collect();
+        hook::run_in(git_dir, "post-update", &names, None)?;
+    }
+    Ok(results)
+}
+
+/// 钩子之外的检查：非裸仓库中检出的分支默认不能更新或删除，远程引用的当前值要与推送方看到的一致
+fn check(git_dir: &Path, config: &Config, command: &Command) -> anyhow::Result<Option<String>> {
+    let bare = config.get_bool("core.bare")?.unwrap_or(false);
+    let head = refs::read_ref_in(git_dir, "HEAD")?;
+    let checked_out = head.as_deref().and_then(|head| head.strip_prefix("ref: "));
+    if !bare && checked_out == Some(command.name.as_str()) {
+        if command.is_delete() {
+            let deny = config.get("receive.denyDeleteCurrent");
+            if !matches!(deny.as_deref(), Some("ignore" | "warn" | "false")) {
+                hook::remote_message(&format!(
+                    "error: refusing to delete the current branch: {}",
+                    command.name
+                ));
+                return Ok(Some(
+                    "deletion of the current branch prohibited".to_string(),
+                ));
+            }
+        } else {
+            let deny = config.get("receive.denyCurrentBranch");
+            if !matches!(deny.as_deref(), Some("ignore" | "warn" | "false")) {
+                hook::remote_message(&refuse_current_branch(&command.name, deny.is_none()));
+                return Ok(Some("branch is currently checked out".to_string()));
+            }
+        }
+    }
+    let current = refs::resolve_ref_in(git_dir, &command.name)?.unwrap_or_default();
+    if current != command.old {
+        return Ok(Some("failed to update ref".to_string()));
+    }
+    Ok(None)
+}
+
+fn run_update_hook(git_dir: &Path, command: &Command) -> anyhow::Result<bool> {
+    let (old, new) = (hex::encode(command.old), hex::encode(command.new));
+    hook::run_in(git_dir, "update", &[&command.name, &old, &new], None)
+}
+
+/// 拒绝更新检出的分支时的说明，没有配置 receive.denyCurrentBranch 时附带怎样配置
+fn refuse_current_branch(name: &str, unconfigured: bool) -> String {
+    let mut message = format!("error: refusing to update checked out branch: {name}\n");
+    if unconfigured {
+        message.push_str(
+            "error: By default, updating the current branch in a non-bare repository\n\
+             is denied, because it will make the index and work tree inconsistent\n\
+             with what you pushed, and will require 'git reset --hard' to match\n\
+             the work tree to HEAD.\n\
+             \n\
+             You can set the 'receive.denyCurrentBranch' configuration variable\n\
+             to 'ignore' or 'warn' in the remote repository to allow pushing into\n\
+             its current branch; however, this is not recommended unless you\n\
+             arranged to update its work tree to match what you pushed in some\n\
+             other way.\n\
+             \n\
+             To squelch this message and still keep the default behaviour, set\n\
+             'receive.denyCurrentBranch' configuration variable to 'refuse'.\n",
+        );
+    }
+    message
+}
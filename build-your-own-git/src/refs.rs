@@ -0,0 +1,352 @@
+use std::{collections::BTreeMap, io::Write, path::Path};
+
+use anyhow::Context;
+
+use crate::{hook, objects::commit::parse_hex};
+
+/// 读取引用的原始内容：`ref: refs/heads/main` 或 40 位哈希
+/// 先查松散引用，再查 packed-refs
+pub(crate) fn read_ref(name: &str) -> anyhow::Result<Option<String>> {
+    read_ref_in(Path::new(".git"), name)
+}
+
+/// 同 read_ref，但读取 git_dir 这个仓库（如子模块）中的引用
+pub(crate) fn read_ref_in(git_dir: &Path, name: &str) -> anyhow::Result<Option<String>> {
+    let path = git_dir.join(name);
+    if path.is_file() {
+        let content = std::fs::read_to_string(&path).with_context(|| format!("read {name}"))?;
+        return Ok(Some(content.trim_end().to_string()));
+    }
+    Ok(packed_refs_in(git_dir)?.remove(name).map(hex::encode))
+}
+
+/// 解析引用，跟随符号引用直到得到对象哈希
+pub(crate) fn resolve_ref(name: &str) -> anyhow::Result<Option<[u8; 20]>> {
+    resolve_ref_in(Path::new(".git"), name)
+}
+
+/// 同 resolve_ref，但解析 git_dir 这个仓库中的引用
+pub(crate) fn resolve_ref_in(git_dir: &Path, name: &str) -> anyhow::Result<Option<[u8; 20]>> {
+    let mut name = name.to_string();
+    // 防止符号引用成环
+    for _ in 0..5 {
+        match read_ref_in(git_dir, &name)? {
+            None => return Ok(None),
+            Some(content) => match content.strip_prefix("ref: ") {
+                Some(target) => name = target.to_string(),
+                None => return Ok(Some(parse_hex(&content)?)),
+            },
+        }
+    }
+    anyhow::bail!("symbolic ref loop at {name}")
+}
+
+/// 按 git 的 dwim 规则把简写展开为存在的完整引用名，如 `main` -> `refs/heads/main`
+pub(crate) fn dwim_ref(name: &str) -> anyhow::Result<Option<String>> {
+    for pattern in [
+        "{}",
+        "refs/{}",
+        "refs/tags/{}",
+        "refs/heads/{}",
+        "refs/remotes/{}",
+        "refs/remotes/{}/HEAD",
+    ] {
+        let full = pattern.replace("{}", name);
+        if resolve_ref(&full)?.is_some() {
+            return Ok(Some(full));
+        }
+    }
+    Ok(None)
+}
+
+/// reflog 中的一条记录：`旧值 新值 名字 <邮箱> 时间 时区\t说明`
+#[derive(Debug, Clone)]
+pub(crate) struct ReflogEntry {
+    pub(crate) old: [u8; 20],
+    pub(crate) new: [u8; 20],
+    /// `名字 <邮箱> 时间 时区`
+    pub(crate) ident: String,
+    pub(crate) message: String,
+}
+
+/// 读取引用的 reflog（`.git/logs/<name>`），按记录顺序返回每次更新的 (旧值, 新值)
+pub(crate) fn read_reflog(name: &str) -> anyhow::Result<Vec<([u8; 20], [u8; 20])>> {
+    Ok(read_reflog_entries(name)?
+        .into_iter()
+        .map(|entry| (entry.old, entry.new))
+        .collect())
+}
+
+/// 读取引用的 reflog，按记录顺序（最新的在最后）返回完整的记录
+pub(crate) fn read_reflog_entries(name: &str) -> anyhow::Result<Vec<ReflogEntry>> {
+    let Ok(content) = std::fs::read_to_string(Path::new(".git/logs").join(name)) else {
+        return Ok(Vec::new());
+    };
+    let mut entries = Vec::new();
+    for line in content.lines() {
+        let mut fields = line.splitn(3, ' ');
+        if let (Some(old), Some(new), Some(rest)) = (fields.next(), fields.next(), fields.next()) {
+            let (ident, message) = rest.split_once('\t').unwrap_or((rest, ""));
+            entries.push(ReflogEntry {
+                old: parse_hex(old)?,
+                new: parse_hex(new)?,
+                ident: ident.to_string(),
+                message: message.to_string(),
+            });
+        }
+    }
+    Ok(entries)
+}
+
+/// 在引用的 reflog 末尾追加一条记录，ident 是 `名字 <邮箱> 时间 时区`
+pub(crate) fn append_reflog(
+    name: &str,
+    old: &[u8; 20],
+    new: &[u8; 20],
+    ident: &str,
+    message: &str,
+) -> anyhow::Result<()> {
+    append_reflog_in(Path::new(".git"), name, old, new, ident, message)
+}
+
+/// 同 append_reflog，但写入 git_dir 这个仓库
+pub(crate) fn append_reflog_in(
+    git_dir: &Path,
+    name: &str,
+    old: &[u8; 20],
+    new: &[u8; 20],
+    ident: &str,
+    message: &str,
+) -> anyhow::Result<()> {
+    let path = git_dir.join("logs").join(name);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).with_context(|| format!("create {}", parent.display()))?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("open {}", path.display()))?;
+    file.write_all(format_reflog_line(old, new, ident, message).as_bytes())
+        .with_context(|| format!("write {}", path.display()))
+}
+
+/// 用 entries 重写整个 reflog，没有记录时删除 reflog 文件
+pub(crate) fn write_reflog(name: &str, entries: &[ReflogEntry]) -> anyhow::Result<()> {
+    let path = Path::new(".git/logs").join(name);
+    if entries.is_empty() {
+        return remove_files(&[path]);
+    }
+    let content: String = entries
+        .iter()
+        .map(|entry| format_reflog_line(&entry.old, &entry.new, &entry.ident, &entry.message))
+        .collect();
+    std::fs::write(&path, content).with_context(|| format!("write {}", path.display()))
+}
+
+/// 删除这些文件（如 MERGE_HEAD 这类状态文件），不存在的跳过
+pub(crate) fn remove_files<P: AsRef<Path>>(paths: &[P]) -> anyhow::Result<()> {
+    for path in paths {
+        let path = path.as_ref();
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                return Err(e).with_context(|| format!("remove {}", path.display()));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn format_reflog_line(old: &[u8; 20], new: &[u8; 20], ident: &str, message: &str) -> String {
+    // 说明只能占一行
+    let message = message.lines().next().unwrap_or("");
+    format!(
+        "{} {} {ident}\t{message}\n",
+        hex::encode(old),
+        hex::encode(new)
+    )
+}
+
+/// HEAD 指向的分支（如 `refs/heads/main`），分离头指针时返回 None
+pub(crate) fn head_branch() -> anyhow::Result<Option<String>> {
+    let head = read_ref("HEAD")?.context("read HEAD")?;
+    Ok(head.strip_prefix("ref: ").map(str::to_string))
+}
+
+/// 写入松散引用，需要时创建上级目录
+pub(crate) fn write_ref(name: &str, id: &[u8; 20]) -> anyhow::Result<()> {
+    let old = resolve_ref(name)?.unwrap_or_default();
+    transaction(&[(name, old, *id)], || write_loose_ref(name, id))
+}
+
+fn write_loose_ref(name: &str, id: &[u8; 20]) -> anyhow::Result<()> {
+    write_ref_in(Path::new(".git"), name, id)
+}
+
+/// 直接写入 git_dir 这个仓库（如推送的目标仓库）中的松散引用，不运行钩子
+pub(crate) fn write_ref_in(git_dir: &Path, name: &str, id: &[u8; 20]) -> anyhow::Result<()> {
+    let path = git_dir.join(name);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).with_context(|| format!("create {}", parent.display()))?;
+    }
+    std::fs::write(&path, format!("{}\n", hex::encode(id))).with_context(|| format!("write {name}"))
+}
+
+/// 一次引用更新：先以 prepared 调用 reference-transaction 钩子，钩子拒绝时以 aborted 通知并报错，
+/// 否则写入后以 committed 通知。每项是 (引用名, 旧值, 新值)，不存在的引用记为全零
+fn transaction(
+    updates: &[(&str, [u8; 20], [u8; 20])],
+    write: impl FnOnce() -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let input: String = updates
+        .iter()
+        .map(|(name, old, new)| format!("{} {} {name}\n", hex::encode(old), hex::encode(new)))
+        .collect();
+    let notify = |state: &str| {
+        hook::run_with(
+            "reference-transaction",
+            &[state],
+            &[],
+            Some(input.as_bytes()),
+        )
+    };
+    if !notify("prepared")? {
+        notify("aborted")?;
+        anyhow::bail!("ref updates aborted by hook");
+    }
+    write()?;
+    // committed 阶段钩子的退出码不影响结果
+    notify("committed")?;
+    Ok(())
+}
+
+/// 删除引用（松散引用和 packed-refs 中的记录）和它的 reflog
+pub(crate) fn delete_ref(name: &str) -> anyhow::Result<()> {
+    let Some(old) = resolve_ref(name)? else {
+        return Ok(());
+    };
+    transaction(&[(name, old, [0; 20])], || {
+        let path = Path::new(".git").join(name);
+        if path.is_file() {
+            std::fs::remove_file(&path).with_context(|| format!("remove {name}"))?;
+        }
+        remove_packed_ref(Path::new(".git"), name)
+    })?;
+    write_reflog(name, &[])
+}
+
+/// 直接删除 git_dir 这个仓库中的引用和它的 reflog，不运行钩子
+pub(crate) fn delete_ref_in(git_dir: &Path, name: &str) -> anyhow::Result<()> {
+    for path in [git_dir.join(name), git_dir.join("logs").join(name)] {
+        if path.is_file() {
+            std::fs::remove_file(&path).with_context(|| format!("remove {}", path.display()))?;
+        }
+    }
+    remove_packed_ref(git_dir, name)
+}
+
+/// 从 git_dir 的 packed-refs 中去掉 name 和它后面的 peeled 行
+fn remove_packed_ref(git_dir: &Path, name: &str) -> anyhow::Result<()> {
+    let path = git_dir.join("packed-refs");
+    let Ok(content) = std::fs::read_to_string(&path) else {
+        return Ok(());
+    };
+    let mut kept = String::new();
+    let mut removed = false;
+    let mut skipping = false;
+    for line in content.lines() {
+        if line.starts_with('^') && skipping {
+            continue;
+        }
+        skipping = line
+            .split_once(' ')
+            .is_some_and(|(_, ref_name)| ref_name == name);
+        removed |= skipping;
+        if !skipping {
+            kept.push_str(line);
+            kept.push('\n');
+        }
+    }
+    if removed {
+        std::fs::write(&path, kept).context("write packed-refs")?;
+    }
+    Ok(())
+}
+
+/// 写入符号引用，如让 HEAD 指向 `refs/heads/main`
+pub(crate) fn write_symbolic_ref(name: &str, target: &str) -> anyhow::Result<()> {
+    std::fs::write(Path::new(".git").join(name), format!("ref: {target}\n"))
+        .with_context(|| format!("write {name}"))
+}
+
+/// 移动 HEAD：在分支上时更新分支，分离头指针时直接更新 HEAD
+pub(crate) fn update_head(id: &[u8; 20]) -> anyhow::Result<()> {
+    match head_branch()? {
+        Some(branch) => {
+            // 与 git 一样，钩子同时看到 HEAD 和它指向的分支
+            let old = resolve_ref(&branch)?.unwrap_or_default();
+            transaction(&[("HEAD", old, *id), (&branch, old, *id)], || {
+                write_loose_ref(&branch, id)
+            })
+        }
+        None => write_ref("HEAD", id),
+    }
+}
+
+/// 列出 prefix 下的所有引用（松散 + packed），按名称排序
+pub(crate) fn list_refs(prefix: &str) -> anyhow::Result<Vec<(String, [u8; 20])>> {
+    list_refs_in(Path::new(".git"), prefix)
+}
+
+/// 同 list_refs，但列出 git_dir 这个仓库中的引用
+pub(crate) fn list_refs_in(
+    git_dir: &Path,
+    prefix: &str,
+) -> anyhow::Result<Vec<(String, [u8; 20])>> {
+    let mut refs: BTreeMap<String, [u8; 20]> = packed_refs_in(git_dir)?
+        .into_iter()
+        .filter(|(name, _)| name.starts_with(prefix))
+        .collect();
+    let mut stack = vec![git_dir.join("refs")];
+    while let Some(dir) = stack.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries {
+            let path = entry?.path();
+            if path.is_dir() {
+                stack.push(path);
+                continue;
+            }
+            let name = path
+                .strip_prefix(git_dir)?
+                .to_string_lossy()
+                .replace('\\', "/");
+            if !name.starts_with(prefix) {
+                continue;
+            }
+            if let Some(hash) = resolve_ref_in(git_dir, &name)? {
+                refs.insert(name, hash);
+            }
+        }
+    }
+    Ok(refs.into_iter().collect())
+}
+
+/// 解析 git_dir 下的 packed-refs，忽略注释和 `^` 开头的 peeled 行
+fn packed_refs_in(git_dir: &Path) -> anyhow::Result<BTreeMap<String, [u8; 20]>> {
+    let mut refs = BTreeMap::new();
+    let Ok(content) = std::fs::read_to_string(git_dir.join("packed-refs")) else {
+        return Ok(refs);
+    };
+    for line in content.lines() {
+        if line.starts_with('#') || line.starts_with('^') {
+            continue;
+        }
+        if let Some((hash, name)) = line.split_once(' ') {
+            refs.insert(name.to_string(), parse_hex(hash)?);
+        }
+    }
+    Ok(refs)
+}
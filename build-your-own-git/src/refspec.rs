@@ -0,0 +1,78 @@
+//! refspec：`[+]<src>[:<dst>]` 描述远程引用和本地引用的对应关系，
+//! 两边可以各有一个 `*` 通配，`^<src>` 是负向 refspec，排除匹配的远程引用
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Refspec {
+    /// `+` 开头：不是快进时也强制更新
+    pub(crate) force: bool,
+    /// `^` 开头：排除 src 匹配的引用，没有 dst
+    pub(crate) negative: bool,
+    pub(crate) src: String,
+    pub(crate) dst: Option<String>,
+}
+
+impl Refspec {
+    pub(crate) fn parse(spec: &str) -> anyhow::Result<Refspec> {
+        let invalid = || anyhow::anyhow!("invalid refspec '{spec}'");
+        let (force, rest) = match spec.strip_prefix('+') {
+            Some(rest) => (true, rest),
+            None => (false, spec),
+        };
+        let (negative, rest) = match rest.strip_prefix('^') {
+            Some(rest) => (true, rest),
+            None => (false, rest),
+        };
+        let (src, dst) = match rest.split_once(':') {
+            Some((src, dst)) => (src, Some(dst)),
+            None => (rest, None),
+        };
+        let globs = |side: &str| side.matches('*').count();
+        if (negative && (force || dst.is_some()))
+            || src.is_empty()
+            || globs(src) > 1
+            || dst.is_some_and(|dst| globs(dst) > 1 || (globs(dst) == 1) != (globs(src) == 1))
+        {
+            return Err(invalid());
+        }
+        Ok(Refspec {
+            force,
+            negative,
+            src: src.to_string(),
+            dst: dst.filter(|dst| !dst.is_empty()).map(str::to_string),
+        })
+    }
+
+    pub(crate) fn is_glob(&self) -> bool {
+        self.src.contains('*')
+    }
+
+    /// 远程引用 name 是否匹配 src
+    pub(crate) fn matches(&self, name: &str) -> bool {
+        glob_match(&self.src, name).is_some()
+    }
+
+    /// 远程引用 name 匹配 src 时，按 dst 得到对应的本地引用名；没有 dst 时为 None
+    pub(crate) fn map(&self, name: &str) -> Option<String> {
+        let star = glob_match(&self.src, name)?;
+        Some(self.dst.as_ref()?.replacen('*', star, 1))
+    }
+
+    /// map 的反方向：本地引用 name 匹配 dst 时，得到对应的远程引用名
+    pub(crate) fn reverse(&self, name: &str) -> Option<String> {
+        let star = glob_match(self.dst.as_ref()?, name)?;
+        Some(self.src.replacen('*', star, 1))
+    }
+}
+
+/// name 是否被 specs 中的某个负向 refspec 排除
+pub(crate) fn is_excluded(specs: &[Refspec], name: &str) -> bool {
+    specs.iter().any(|spec| spec.negative && spec.matches(name))
+}
+
+/// pattern 匹配 name 时返回 `*` 对应的部分（没有通配时为空），不匹配时返回 None
+fn glob_match<'a>(pattern: &str, name: &'a str) -> Option<&'a str> {
+    match pattern.split_once('*') {
+        Some((prefix, suffix)) => name.strip_prefix(prefix)?.strip_suffix(suffix),
+        None => (pattern == name).then_some(""),
+    }
+}
@@ -0,0 +1,96 @@
+//! 远程仓库：`.git/config` 中 `[remote "<name>"]` 记录的 URL 和 fetch refspec。
+//! URL 可以是本地路径、`file://`、`http://` URL 或 ssh 地址，见 [`crate::transport`]
+
+use std::{collections::HashSet, path::Path};
+
+use crate::{
+    commands::clone::{copy_object, tag_target},
+    config::Config,
+    objects::{
+        Kind, Mode, commit::Commit, object_exists_in, read_object_in, tree::parse_tree,
+        write_object_in,
+    },
+    refs,
+    refspec::Refspec,
+};
+
+#[derive(Debug, Clone)]
+pub(crate) struct Remote {
+    pub(crate) url: String,
+    pub(crate) fetch: Vec<Refspec>,
+}
+
+impl Remote {
+    /// 读取名为 name 的远程，没有配置 URL 时返回 None
+    pub(crate) fn get(config: &Config, name: &str) -> anyhow::Result<Option<Remote>> {
+        let Some(url) = config.get(&format!("remote.{name}.url")) else {
+            return Ok(None);
+        };
+        let fetch = config
+            .get_all(&format!("remote.{name}.fetch"))
+            .iter()
+            .map(|spec| Refspec::parse(spec))
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Some(Remote { url, fetch }))
+    }
+}
+
+/// 配置中所有远程的名称，按第一次出现的顺序
+pub(crate) fn names(config: &Config) -> Vec<String> {
+    config.subsections("remote")
+}
+
+/// 没有指定远程时使用的：当前分支的 `branch.<name>.remote`，否则是 origin
+pub(crate) fn default_name(config: &Config) -> anyhow::Result<String> {
+    let branch = refs::head_branch()?;
+    let configured = branch
+        .as_deref()
+        .and_then(|branch| branch.strip_prefix("refs/heads/"))
+        .and_then(|branch| config.get(&format!("branch.{branch}.remote")));
+    Ok(configured.unwrap_or_else(|| "origin".to_string()))
+}
+
+/// 把 wants 以及它们引用的、目标仓库中还没有的对象从对象目录 from 复制到 to，返回复制的个数。
+/// 目标仓库中已有的提交和树视为它们引用的对象也都已经有了，遍历到这里为止
+pub(crate) async fn transfer_objects(
+    from: &Path,
+    to: &Path,
+    wants: &[[u8; 20]],
+) -> anyhow::Result<usize> {
+    let exists = |id: &[u8; 20]| object_exists_in(to, id);
+    let mut stack: Vec<[u8; 20]> = wants.to_vec();
+    let mut seen = HashSet::new();
+    let mut copied = 0;
+    while let Some(id) = stack.pop() {
+        if !seen.insert(id) || exists(&id) {
+            continue;
+        }
+        let (kind, data) = read_object_in(from, &hex::encode(id)).await?;
+        match kind {
+            Kind::Commit => {
+                let commit = Commit::parse(&data)?;
+                stack.push(commit.tree);
+                stack.extend(commit.parents);
+            }
+            Kind::Tree => {
+                for entry in parse_tree(&data)? {
+                    // gitlink 指向子模块中的提交，不在这个仓库里
+                    if entry.mode != Mode::Gitlink {
+                        stack.push(entry.hash);
+                    }
+                }
+            }
+            Kind::Tag => stack.push(tag_target(&data)?),
+            Kind::Blob => {}
+        }
+        // 松散对象原样复制，在 pack 中的按内容写成松散对象
+        let hex = hex::encode(id);
+        if from.join(&hex[..2]).join(&hex[2..]).is_file() {
+            copy_object(from, to, &id)?;
+        } else {
+            write_object_in(to, &kind, &data)?;
+        }
+        copied += 1;
+    }
+    Ok(copied)
+}
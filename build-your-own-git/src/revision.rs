@@ -0,0 +1,637 @@
+pub(crate) mod list_objects;
+
+use std::{
+    cmp::Reverse,
+    collections::{BTreeSet, BinaryHeap, HashMap, HashSet},
+    path::Path,
+};
+
+use anyhow::Context;
+
+use crate::{
+    objects::{
+        Kind,
+        commit::{Commit, parse_hex},
+        commit_graph, read_object,
+    },
+    pack, refs,
+};
+
+/// 解析修订版本表达式，如 `HEAD~2`、`main^2`、`v1.0^{}`、`abc1234`
+pub(crate) async fn resolve(rev: &str) -> anyhow::Result<[u8; 20]> {
+    let split = rev.find(['^', '~']).unwrap_or(rev.len());
+    let (base, mut suffix) = rev.split_at(split);
+    let mut id = resolve_base(base)
+        .await?
+        .with_context(|| format!("ambiguous argument '{rev}': unknown revision"))?;
+
+    while !suffix.is_empty() {
+        if let Some(rest) = suffix.strip_prefix("^{") {
+            let (kind, rest) = rest.split_once('}').context("unterminated ^{...}")?;
+            id = match kind {
+                "" => peel(id, None).await?,
+                "commit" => peel(id, Some(Kind::Commit)).await?,
+                "tree" => peel(id, Some(Kind::Tree)).await?,
+                _ => anyhow::bail!("unsupported peel target '{kind}' in '{rev}'"),
+            };
+            suffix = rest;
+            continue;
+        }
+        let op = suffix.as_bytes()[0];
+        let digits = suffix[1..]
+            .find(|c: char| !c.is_ascii_digit())
+            .map_or(suffix.len(), |n| n + 1);
+        let n = match &suffix[1..digits] {
+            "" => 1,
+            n => n.parse::<usize>()?,
+        };
+        suffix = &suffix[digits..];
+        id = peel(id, Some(Kind::Commit)).await?;
+        if op == b'^' {
+            if n > 0 {
+                let commit = Commit::read(&id).await?;
+                id = *commit
+                    .parents
+                    .get(n - 1)
+                    .with_context(|| format!("'{rev}': commit has no parent {n}"))?;
+            }
+        } else {
+            for _ in 0..n {
+                let commit = Commit::read(&id).await?;
+                id = *commit
+                    .parents
+                    .first()
+                    .with_context(|| format!("'{rev}': commit has no parent"))?;
+            }
+        }
+    }
+    Ok(id)
+}
+
+async fn resolve_base(base: &str) -> anyhow::Result<Option<[u8; 20]>> {
+    let base = if base == "@" { "HEAD" } else { base };
+    // `<ref>@{n}`：引用 n 次更新之前的值，取自 reflog
+    if let Some((name, n)) = base
+        .strip_suffix('}')
+        .and_then(|rest| rest.rsplit_once("@{"))
+        .and_then(|(name, n)| Some((name, n.parse::<usize>().ok()?)))
+    {
+        let name = if name.is_empty() { "HEAD" } else { name };
+        let Some(full) = refs::dwim_ref(name)? else {
+            return Ok(None);
+        };
+        let reflog = refs::read_reflog(&full)?;
+        let entry = reflog
+            .iter()
+            .rev()
+            .nth(n)
+            .with_context(|| format!("log for '{name}' only has {} entries", reflog.len()))?;
+        return Ok(Some(entry.1));
+    }
+    if base.len() == 40 && base.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Ok(Some(parse_hex(base)?));
+    }
+    if let Some(name) = refs::dwim_ref(base)? {
+        return refs::resolve_ref(&name);
+    }
+    if base.len() >= 4 && base.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return resolve_prefix(&base.to_ascii_lowercase());
+    }
+    Ok(None)
+}
+
+/// 在松散对象和 pack 中查找唯一匹配的短哈希
+fn resolve_prefix(prefix: &str) -> anyhow::Result<Option<[u8; 20]>> {
+    let mut names = BTreeSet::new();
+    if let Ok(dir) = std::fs::read_dir(format!(".git/objects/{}", &prefix[..2])) {
+        for entry in dir {
+            names.insert(format!(
+                "{}{}",
+                &prefix[..2],
+                entry?.file_name().to_string_lossy()
+            ));
+        }
+    }
+    names.extend(pack::store::ids_with_prefix(
+        Path::new(".git/objects"),
+        prefix,
+    ));
+    let mut found = None;
+    for name in names.iter().filter(|name| name.starts_with(prefix)) {
+        anyhow::ensure!(found.is_none(), "short object ID {prefix} is ambiguous");
+        found = Some(parse_hex(name)?);
+    }
+    Ok(found)
+}
+
+/// 剥离附注标签，直到得到指定类型（None 表示任意非标签对象）
+pub(crate) async fn peel(mut id: [u8; 20], want: Option<Kind>) -> anyhow::Result<[u8; 20]> {
+    loop {
+        let hex = hex::encode(id);
+        let (kind, data) = read_object(&hex).await?;
+        match kind {
+            Kind::Tag => {
+                let text = String::from_utf8_lossy(&data);
+                let target = text
+                    .lines()
+                    .find_map(|line| line.strip_prefix("object "))
+                    .with_context(|| format!("tag {hex} has no object header"))?;
+                id = parse_hex(target)?;
+            }
+            _ if want.as_ref().map_or(true, |want| *want == kind) => return Ok(id),
+            Kind::Commit if want == Some(Kind::Tree) => return Ok(Commit::parse(&data)?.tree),
+            _ => anyhow::bail!("object {hex} is a {kind}, not a {}", want.unwrap()),
+        }
+    }
+}
+
+/// 输出顺序
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Sort {
+    /// 按提交时间，从新到旧
+    Date,
+    /// 拓扑顺序：所有子提交先于父提交输出
+    Topo,
+}
+
+/// 提交遍历器：从一组起点出发，排除另一组起点可达的提交
+pub(crate) struct RevWalk {
+    commits: HashMap<[u8; 20], Commit>,
+    include: Vec<[u8; 20]>,
+    exclude: Vec<[u8; 20]>,
+    uninteresting: HashSet<[u8; 20]>,
+    /// `A...B` 中 A 一侧的起点
+    left_tips: Vec<[u8; 20]>,
+    /// 从左侧起点可达的提交
+    left: HashSet<[u8; 20]>,
+    /// 起点中遇到的附注标签及其名称，`--objects` 时输出
+    tag_objects: Vec<([u8; 20], String)>,
+    pub(crate) first_parent: bool,
+    pub(crate) sort: Sort,
+}
+
+impl RevWalk {
+    pub(crate) fn new() -> Self {
+        RevWalk {
+            commits: HashMap::new(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            uninteresting: HashSet::new(),
+            left_tips: Vec::new(),
+            left: HashSet::new(),
+            tag_objects: Vec::new(),
+            first_parent: false,
+            sort: Sort::Date,
+        }
+    }
+
+    pub(crate) fn push(&mut self, id: [u8; 20]) {
+        self.include.push(id);
+    }
+
+    pub(crate) fn hide(&mut self, id: [u8; 20]) {
+        self.exclude.push(id);
+    }
+
+    /// 解析命令行参数：`A`、`^A`、`A..B`、`A...B`
+    pub(crate) async fn push_arg(&mut self, arg: &str) -> anyhow::Result<()> {
+        if let Some(rev) = arg.strip_prefix('^') {
+            let id = peel(resolve(rev).await?, Some(Kind::Commit)).await?;
+            self.hide(id);
+        } else if let Some((left, right)) = arg.split_once("...") {
+            // 对称差：两侧都包含，排除它们的公共祖先
+            let left = if left.is_empty() { "HEAD" } else { left };
+            let right = if right.is_empty() { "HEAD" } else { right };
+            let left = self.peel_tip(resolve(left).await?).await?;
+            let right = self.peel_tip(resolve(right).await?).await?;
+            for base in merge_bases(left, &[right]).await? {
+                self.hide(base);
+            }
+            self.left_tips.push(left);
+            self.push(left);
+            self.push(right);
+        } else if let Some((from, to)) = arg.split_once("..") {
+            let from = if from.is_empty() { "HEAD" } else { from };
+            let to = if to.is_empty() { "HEAD" } else { to };
+            let from = peel(resolve(from).await?, Some(Kind::Commit)).await?;
+            let to = self.peel_tip(resolve(to).await?).await?;
+            self.hide(from);
+            self.push(to);
+        } else {
+            let id = self.peel_tip(resolve(arg).await?).await?;
+            self.push(id);
+        }
+        Ok(())
+    }
+
+    /// 剥离起点上的附注标签并记录下来，返回最终的提交
+    async fn peel_tip(&mut self, mut id: [u8; 20]) -> anyhow::Result<[u8; 20]> {
+        loop {
+            let hex = hex::encode(id);
+            let (kind, data) = read_object(&hex).await?;
+            match kind {
+                Kind::Commit => return Ok(id),
+                Kind::Tag => {
+                    let text = String::from_utf8_lossy(&data);
+                    let header = |key: &str| {
+                        text.lines()
+                            .take_while(|line| !line.is_empty())
+                            .find_map(|line| line.strip_prefix(key))
+                            .map(str::to_string)
+                    };
+                    let target = header("object ")
+                        .with_context(|| format!("tag {hex} has no object header"))?;
+                    if !self.tag_objects.iter().any(|(tag, _)| *tag == id) {
+                        self.tag_objects
+                            .push((id, header("tag ").unwrap_or_default()));
+                    }
+                    id = parse_hex(&target)?;
+                }
+                kind => anyhow::bail!("object {hex} is a {kind}, not a commit"),
+            }
+        }
+    }
+
+    /// 起点中遇到的附注标签
+    pub(crate) fn tag_objects(&self) -> &[([u8; 20], String)] {
+        &self.tag_objects
+    }
+
+    /// 把 prefix 下所有能剥离成提交的引用作为起点，`--all` 时同时加入 HEAD
+    pub(crate) async fn push_refs(&mut self, prefix: &str) -> anyhow::Result<()> {
+        let mut tips: Vec<[u8; 20]> = refs::list_refs(prefix)?
+            .into_iter()
+            .map(|(_, id)| id)
+            .collect();
+        if prefix == "refs/" {
+            tips.extend(refs::resolve_ref("HEAD")?);
+        }
+        for id in tips {
+            // 指向树或 blob 的标签不参与提交遍历
+            if peel(id, Some(Kind::Commit)).await.is_ok() {
+                let id = self.peel_tip(id).await?;
+                self.push(id);
+            }
+        }
+        Ok(())
+    }
+
+    /// 提交是否属于 `A...B` 的左侧；没有对称差参数时返回 None
+    pub(crate) fn is_left(&self, id: &[u8; 20]) -> Option<bool> {
+        (!self.left_tips.is_empty()).then(|| self.left.contains(id))
+    }
+
+    /// 被排除的边界提交：排除的起点以及输出提交中不感兴趣的父提交
+    pub(crate) fn uninteresting_edges(&self, list: &[[u8; 20]]) -> Vec<[u8; 20]> {
+        let mut edges = self.exclude.clone();
+        for id in list {
+            for parent in &self.commit(id).parents {
+                if self.uninteresting.contains(parent) && !edges.contains(parent) {
+                    edges.push(*parent);
+                }
+            }
+        }
+        edges
+    }
+
+    /// 读取并缓存提交
+    pub(crate) async fn load(&mut self, id: &[u8; 20]) -> anyhow::Result<&Commit> {
+        if !self.commits.contains_key(id) {
+            let commit = Commit::read(id).await?;
+            self.commits.insert(*id, commit);
+        }
+        Ok(&self.commits[id])
+    }
+
+    /// 已经遍历过的提交
+    pub(crate) fn commit(&self, id: &[u8; 20]) -> &Commit {
+        &self.commits[id]
+    }
+
+    /// 参与输出的父提交（排除不感兴趣的，`--first-parent` 时只保留第一个）
+    pub(crate) fn interesting_parents(&self, id: &[u8; 20]) -> Vec<[u8; 20]> {
+        let parents = &self.commit(id).parents;
+        let parents = if self.first_parent {
+            &parents[..parents.len().min(1)]
+        } else {
+            &parents[..]
+        };
+        parents
+            .iter()
+            .filter(|p| !self.uninteresting.contains(*p))
+            .copied()
+            .collect()
+    }
+
+    /// 执行遍历，返回按 `sort` 排好序的提交列表
+    pub(crate) async fn walk(&mut self) -> anyhow::Result<Vec<[u8; 20]>> {
+        // 1. 标记所有从排除起点可达的提交
+        let mut stack = self.exclude.clone();
+        while let Some(id) = stack.pop() {
+            if !self.uninteresting.insert(id) {
+                continue;
+            }
+            stack.extend(self.load(&id).await?.parents.iter().copied());
+        }
+
+        // 2. 按提交时间从新到旧遍历，时间相同时先入队的先出
+        let mut queue = BinaryHeap::new();
+        let mut seen = HashSet::new();
+        let mut seq = 0u64;
+        let mut tips = Vec::new();
+        for id in self.include.clone() {
+            if self.uninteresting.contains(&id) || !seen.insert(id) {
+                continue;
+            }
+            tips.push((self.load(&id).await?.committer.time, id));
+        }
+        // 起点按时间稳定排序
+        tips.sort_by_key(|(time, _)| Reverse(*time));
+        for (time, id) in tips {
+            queue.push((time, Reverse(seq), id));
+            seq += 1;
+        }
+
+        self.left.extend(self.left_tips.iter().copied());
+        let mut list = Vec::new();
+        while let Some((_, _, id)) = queue.pop() {
+            list.push(id);
+            let mut parents = self.commit(&id).parents.clone();
+            if self.first_parent {
+                parents.truncate(1);
+            }
+            let is_left = self.left.contains(&id);
+            for parent in parents {
+                if is_left {
+                    self.left.insert(parent);
+                }
+                if self.uninteresting.contains(&parent) || !seen.insert(parent) {
+                    continue;
+                }
+                let time = self.load(&parent).await?.committer.time;
+                queue.push((time, Reverse(seq), parent));
+                seq += 1;
+            }
+        }
+
+        if self.sort == Sort::Topo {
+            list = self.sort_topo(list);
+        }
+        Ok(list)
+    }
+
+    /// 与 git 的 REV_SORT_IN_GRAPH_ORDER 相同：用栈代替优先队列，
+    /// 这样合并进来的分支会紧跟在合并提交之后连续输出
+    fn sort_topo(&self, list: Vec<[u8; 20]>) -> Vec<[u8; 20]> {
+        let mut indegree: HashMap<[u8; 20], usize> = list.iter().map(|id| (*id, 1)).collect();
+        for id in &list {
+            for parent in &self.commit(id).parents {
+                if let Some(n) = indegree.get_mut(parent) {
+                    *n += 1;
+                }
+            }
+        }
+
+        let mut stack: Vec<[u8; 20]> = list
+            .iter()
+            .filter(|id| indegree[*id] == 1)
+            .copied()
+            .collect();
+        stack.reverse();
+
+        let mut sorted = Vec::with_capacity(list.len());
+        while let Some(id) = stack.pop() {
+            for parent in &self.commit(&id).parents {
+                let Some(n) = indegree.get_mut(parent) else {
+                    continue;
+                };
+                if *n == 0 {
+                    continue;
+                }
+                // 所有子提交都输出后父提交才入栈
+                *n -= 1;
+                if *n == 1 {
+                    stack.push(*parent);
+                }
+            }
+            indegree.insert(id, 0);
+            sorted.push(id);
+        }
+        sorted
+    }
+}
+
+/// 计算 one 与 twos 的最佳公共祖先（paint-down 算法）：
+/// 从两侧同时向下染色，两种颜色都染到的提交即为候选，
+/// 再去掉能被其他候选到达的冗余结果，按提交时间从新到旧返回
+pub(crate) async fn merge_bases(one: [u8; 20], twos: &[[u8; 20]]) -> anyhow::Result<Vec<[u8; 20]>> {
+    if twos.contains(&one) {
+        return Ok(vec![one]);
+    }
+    let (results, flags) = paint_down(one, twos, 0).await?;
+    // 被另一个结果向下染成 STALE 的结果是它的祖先，直接丢弃
+    let results: Vec<[u8; 20]> = results
+        .into_iter()
+        .filter(|id| flags[id] & STALE == 0)
+        .collect();
+    let mut results = remove_redundant(results).await?;
+    let mut times = HashMap::new();
+    for id in &results {
+        times.insert(*id, Commit::read(id).await?.committer.time);
+    }
+    results.sort_by_key(|id| Reverse(times[id]));
+    Ok(results)
+}
+
+/// 多个提交共同的最佳公共祖先（`--octopus`）：依次与已有结果求公共祖先
+pub(crate) async fn octopus_merge_bases(commits: &[[u8; 20]]) -> anyhow::Result<Vec<[u8; 20]>> {
+    // 与 git 一致，从最后一个提交开始
+    let mut commits = commits.iter().rev();
+    let Some(first) = commits.next() else {
+        return Ok(Vec::new());
+    };
+    let mut bases = vec![*first];
+    for next in commits {
+        let mut merged = Vec::new();
+        for base in &bases {
+            merged.extend(merge_bases(*next, &[*base]).await?);
+        }
+        bases = merged;
+    }
+    reduce_heads(bases).await
+}
+
+/// 去掉重复的提交和能从其他提交到达的提交，保持原来的顺序
+pub(crate) async fn reduce_heads(heads: Vec<[u8; 20]>) -> anyhow::Result<Vec<[u8; 20]>> {
+    let mut seen = HashSet::new();
+    let heads = heads.into_iter().filter(|id| seen.insert(*id)).collect();
+    remove_redundant(heads).await
+}
+
+/// commit 是否是 reference 的祖先（包括两者相同）
+pub(crate) async fn is_ancestor(commit: [u8; 20], reference: [u8; 20]) -> anyhow::Result<bool> {
+    // 代数更大的提交不可能是祖先
+    let generation = commit_graph::generation(&commit);
+    if generation > commit_graph::generation(&reference) {
+        return Ok(false);
+    }
+    let (_, flags) = paint_down(commit, &[reference], generation).await?;
+    Ok(flags[&commit] & PARENT2 != 0)
+}
+
+/// `merge-base --fork-point`：找出 commit 从 refname 的哪个历史位置分叉出来。
+/// 候选是 reflog 中记录过的所有值（没有 reflog 时只有引用当前的值），
+/// 只有唯一的公共祖先且它正好是某个候选时才返回
+pub(crate) async fn fork_point(
+    refname: &str,
+    commit: [u8; 20],
+) -> anyhow::Result<Option<[u8; 20]>> {
+    let mut candidates = Vec::new();
+    let add = |id: [u8; 20], candidates: &mut Vec<[u8; 20]>| {
+        if id != [0; 20] && !candidates.contains(&id) {
+            candidates.push(id);
+        }
+    };
+    let reflog = refs::read_reflog(refname)?;
+    if let Some((old, _)) = reflog.first() {
+        add(*old, &mut candidates);
+    }
+    for (_, new) in &reflog {
+        add(*new, &mut candidates);
+    }
+    if candidates.is_empty() {
+        if let Some(tip) = refs::resolve_ref(refname)? {
+            add(tip, &mut candidates);
+        }
+    }
+    // 已经不存在或不是提交的记录跳过
+    let mut commits = Vec::with_capacity(candidates.len());
+    for id in candidates {
+        if matches!(read_object(&hex::encode(id)).await, Ok((Kind::Commit, _))) {
+            commits.push(id);
+        }
+    }
+    if commits.is_empty() {
+        return Ok(None);
+    }
+    match merge_bases(commit, &commits).await?.as_slice() {
+        [base] if commits.contains(base) => Ok(Some(*base)),
+        _ => Ok(None),
+    }
+}
+
+const PARENT1: u8 = 1;
+const PARENT2: u8 = 2;
+const STALE: u8 = 4;
+const RESULT: u8 = 8;
+
+/// 从 one（PARENT1）和 twos（PARENT2）同时向下染色，返回两种颜色都染到的提交和所有提交的标记。
+/// 队列按代数、再按提交时间从大到小出队；代数小于 min_generation 的提交不再处理
+async fn paint_down(
+    one: [u8; 20],
+    twos: &[[u8; 20]],
+    min_generation: u32,
+) -> anyhow::Result<(Vec<[u8; 20]>, HashMap<[u8; 20], u8>)> {
+    let mut flags: HashMap<[u8; 20], u8> = HashMap::new();
+    let mut queue = BinaryHeap::new();
+    let mut seq = 0u64;
+    let mut push = |queue: &mut BinaryHeap<_>, time: i64, id: [u8; 20]| {
+        queue.push((commit_graph::generation(&id), time, Reverse(seq), id));
+        seq += 1;
+    };
+    // 与 git 的 queue_has_nonstale 一样只看不是 STALE 的条目，但不每轮扫描整个队列：
+    // nonstale 是这样的条目数，queued 记录每个提交在其中占几条，提交变成 STALE 时一起减掉
+    let mut nonstale = 0usize;
+    let mut queued: HashMap<[u8; 20], usize> = HashMap::new();
+    flags.insert(one, PARENT1);
+    push(&mut queue, Commit::read(&one).await?.committer.time, one);
+    for two in twos {
+        *flags.entry(*two).or_default() |= PARENT2;
+        push(&mut queue, Commit::read(two).await?.committer.time, *two);
+    }
+    nonstale += queue.len();
+    for (_, _, _, id) in queue.iter() {
+        *queued.entry(*id).or_default() += 1;
+    }
+
+    let mut results = Vec::new();
+    // 队列中只剩 STALE 提交时停止
+    while nonstale > 0 {
+        let (generation, _, _, id) = queue.pop().expect("queue is not empty");
+        if flags[&id] & STALE == 0 {
+            nonstale -= 1;
+            *queued.get_mut(&id).expect("queued commit") -= 1;
+        }
+        if generation < min_generation {
+            break;
+        }
+        let mut commit_flags = flags[&id] & (PARENT1 | PARENT2 | STALE);
+        if commit_flags == PARENT1 | PARENT2 {
+            if flags[&id] & RESULT == 0 {
+                *flags.get_mut(&id).expect("flagged commit") |= RESULT;
+                results.push(id);
+            }
+            // 公共祖先的祖先不可能是最佳结果
+            commit_flags |= STALE;
+        }
+        for parent in Commit::read(&id).await?.parents {
+            let parent_flags = flags.entry(parent).or_default();
+            if *parent_flags & commit_flags == commit_flags {
+                continue;
+            }
+            if *parent_flags & STALE == 0 {
+                if commit_flags & STALE != 0 {
+                    nonstale -= queued.remove(&parent).unwrap_or(0);
+                } else {
+                    nonstale += 1;
+                    *queued.entry(parent).or_default() += 1;
+                }
+            }
+            *parent_flags |= commit_flags;
+            push(
+                &mut queue,
+                Commit::read(&parent).await?.committer.time,
+                parent,
+            );
+        }
+    }
+    Ok((results, flags))
+}
+
+/// 去掉能从其他候选到达的提交
+async fn remove_redundant(candidates: Vec<[u8; 20]>) -> anyhow::Result<Vec<[u8; 20]>> {
+    let mut redundant = HashSet::new();
+    for (i, id) in candidates.iter().enumerate() {
+        if redundant.contains(id) {
+            continue;
+        }
+        let others: HashSet<[u8; 20]> = candidates
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| *j != i)
+            .map(|(_, other)| *other)
+            .collect();
+        // 代数比所有其他候选都小的提交不可能到达它们
+        let min_generation = others
+            .iter()
+            .map(commit_graph::generation)
+            .min()
+            .unwrap_or(0);
+        // 从 id 向下遍历，遇到的其他候选都是冗余的
+        let mut stack = Commit::read(id).await?.parents;
+        let mut seen = HashSet::new();
+        while let Some(next) = stack.pop() {
+            if !seen.insert(next) || commit_graph::generation(&next) < min_generation {
+                continue;
+            }
+            if others.contains(&next) {
+                redundant.insert(next);
+            }
+            stack.extend(Commit::read(&next).await?.parents);
+        }
+    }
+    Ok(candidates
+        .into_iter()
+        .filter(|id| !redundant.contains(id))
+        .collect())
+}
@@ -0,0 +1,204 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Context;
+
+use crate::{
+    objects::{Mode, commit::Commit, hash_to_reader, object_exists, tree::read_tree},
+    revision::RevWalk,
+};
+
+/// `--filter=<spec>`：部分克隆时省略的对象
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Filter {
+    /// 省略所有 blob
+    BlobNone,
+    /// 省略大小不小于 N 字节的 blob
+    BlobLimit(u64),
+    /// 省略深度不小于 N 的树和 blob，根树深度为 0
+    TreeDepth(usize),
+}
+
+impl Filter {
+    pub(crate) fn parse(spec: &str) -> anyhow::Result<Filter> {
+        if spec == "blob:none" {
+            return Ok(Filter::BlobNone);
+        }
+        if let Some(limit) = spec.strip_prefix("blob:limit=") {
+            let (digits, unit) = match limit.char_indices().find(|(_, c)| !c.is_ascii_digit()) {
+                Some((n, _)) => limit.split_at(n),
+                None => (limit, ""),
+            };
+            let factor = match unit.to_ascii_lowercase().as_str() {
+                "" => 1,
+                "k" => 1 << 10,
+                "m" => 1 << 20,
+                "g" => 1 << 30,
+                _ => anyhow::bail!("invalid filter-spec '{spec}'"),
+            };
+            let n = digits
+                .parse::<u64>()
+                .with_context(|| format!("invalid filter-spec '{spec}'"))?;
+            return Ok(Filter::BlobLimit(n * factor));
+        }
+        if let Some(depth) = spec.strip_prefix("tree:") {
+            let depth = depth
+                .parse::<usize>()
+                .with_context(|| format!("invalid filter-spec '{spec}'"))?;
+            return Ok(Filter::TreeDepth(depth));
+        }
+        anyhow::bail!("invalid filter-spec '{spec}'")
+    }
+}
+
+/// `--missing=<action>`：遇到缺失对象时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MissingAction {
+    Error,
+    AllowAny,
+    Print,
+}
+
+impl MissingAction {
+    pub(crate) fn parse(s: &str) -> anyhow::Result<MissingAction> {
+        match s {
+            "error" => Ok(MissingAction::Error),
+            "allow-any" => Ok(MissingAction::AllowAny),
+            "print" => Ok(MissingAction::Print),
+            _ => anyhow::bail!("invalid --missing value '{s}'"),
+        }
+    }
+}
+
+/// 对象枚举结果
+#[derive(Debug, Default)]
+pub(crate) struct ObjectList {
+    /// 标签、树和 blob 以及它们的名称，树和 blob 是在树中的路径，根树路径为空
+    pub(crate) objects: Vec<([u8; 20], String)>,
+    /// 被过滤器省略的对象
+    pub(crate) omitted: Vec<[u8; 20]>,
+    /// 对象库中缺失的对象
+    pub(crate) missing: Vec<[u8; 20]>,
+}
+
+/// 列出 commits 引用到的所有标签、树和 blob，跳过被排除的边界提交可达的对象；
+/// 顺序与 git 相同：先输出起点上的标签，再按提交顺序处理根树，树内深度优先。
+/// record_omitted 为真时会深入被省略的树，记录其中所有被省略的对象
+pub(crate) async fn list_objects(
+    walk: &RevWalk,
+    commits: &[[u8; 20]],
+    filter: Option<Filter>,
+    record_omitted: bool,
+    missing: MissingAction,
+) -> anyhow::Result<ObjectList> {
+    let mut uninteresting = HashSet::new();
+    for edge in walk.uninteresting_edges(commits) {
+        let tree = match Commit::read(&edge).await {
+            Ok(commit) => commit.tree,
+            Err(_) => continue,
+        };
+        mark_seen(tree, &mut uninteresting).await;
+    }
+
+    let mut list = ObjectList::default();
+    for (tag, name) in walk.tag_objects() {
+        list.objects.push((*tag, name.clone()));
+    }
+
+    // 对象 -> 首次遇到时的深度；tree:<depth> 过滤时在更浅处再次遇到要重新处理
+    let mut seen: HashMap<[u8; 20], usize> = HashMap::new();
+    let mut omitted: HashSet<[u8; 20]> = HashSet::new();
+    for id in commits {
+        let root = walk.commit(id).tree;
+        // (哈希, 路径, 是否是树, 深度)
+        let mut stack = vec![(root, String::new(), true, 0usize)];
+        while let Some((hash, path, is_tree, depth)) = stack.pop() {
+            if uninteresting.contains(&hash) {
+                continue;
+            }
+            match seen.get(&hash) {
+                Some(&seen_depth)
+                    if !matches!(filter, Some(Filter::TreeDepth(_))) || seen_depth <= depth =>
+                {
+                    continue;
+                }
+                _ => {}
+            }
+            seen.insert(hash, depth);
+
+            // 不需要读取内容就能确定省略的对象，缺失也无妨
+            let omit = match filter {
+                None | Some(Filter::BlobLimit(_)) => false,
+                Some(Filter::BlobNone) => !is_tree,
+                Some(Filter::TreeDepth(limit)) => depth >= limit,
+            };
+            if (!omit || is_tree) && !object_exists(&hex::encode(hash)) {
+                match missing {
+                    MissingAction::Error => anyhow::bail!(
+                        "missing {} object '{}'",
+                        if is_tree { "tree" } else { "blob" },
+                        hex::encode(hash)
+                    ),
+                    MissingAction::AllowAny => {}
+                    MissingAction::Print => list.missing.push(hash),
+                }
+                continue;
+            }
+            let omit = match filter {
+                Some(Filter::BlobLimit(limit)) => {
+                    !is_tree && hash_to_reader(&hex::encode(hash)).await?.expected_size >= limit
+                }
+                _ => omit,
+            };
+            if omit {
+                omitted.insert(hash);
+                // 被省略的树只有在需要记录时才继续深入
+                if is_tree && !record_omitted {
+                    continue;
+                }
+            } else {
+                omitted.remove(&hash);
+                list.objects.push((hash, path.clone()));
+            }
+
+            if is_tree {
+                let entries = read_tree(&hash).await?;
+                for entry in entries.into_iter().rev() {
+                    // 子模块的提交在子仓库中，不属于这个仓库的对象
+                    if entry.mode == Mode::Gitlink {
+                        continue;
+                    }
+                    let child_path = if path.is_empty() {
+                        entry.name
+                    } else {
+                        format!("{path}/{}", entry.name)
+                    };
+                    stack.push((entry.hash, child_path, entry.mode.is_dir(), depth + 1));
+                }
+            }
+        }
+    }
+    list.omitted = omitted.into_iter().collect();
+    list.omitted.sort();
+    list.missing.sort();
+    Ok(list)
+}
+
+/// 把树及其下所有对象标记为已见过
+async fn mark_seen(tree: [u8; 20], seen: &mut HashSet<[u8; 20]>) {
+    let mut stack = vec![tree];
+    while let Some(tree) = stack.pop() {
+        if !seen.insert(tree) {
+            continue;
+        }
+        let Ok(entries) = read_tree(&tree).await else {
+            continue;
+        };
+        for entry in entries {
+            if entry.mode == Mode::Directory {
+                stack.push(entry.hash);
+            } else {
+                seen.insert(entry.hash);
+            }
+        }
+    }
+}
@@ -0,0 +1,667 @@
+//! cherry-pick 和 revert 共用的 sequencer：用三方树合并逐个应用（或撤销）提交。
+//! 一次处理多个提交时，待办列表、选项和起点保存在 `.git/sequencer` 中，
+//! 冲突解决后用 `--continue`、`--skip` 或 `--abort` 接着处理。rebase 复用同样的合并和提交步骤
+
+pub(crate) mod rebase;
+pub(crate) mod todo;
+
+use std::{collections::BTreeMap, io::Write, path::Path};
+
+use anyhow::Context;
+
+use crate::{
+    commands::{
+        commit::{print_commit_summary, stripspace, write_commit},
+        status::{self, StatusOptions},
+    },
+    diff::{Side, flatten_tree, index_files, rename::RenameOptions},
+    editor::COMMIT_EDITMSG,
+    hook,
+    index::{INDEX_FILE, Index, IndexEntry},
+    merge::tree::{TreeMergeOptions, TreeMergeResult, merge_trees},
+    objects::{
+        commit::{Commit, Signature, parse_hex},
+        find_unique_abbrev,
+        tree::write_tree_from_files,
+    },
+    refs,
+    revision::resolve,
+    worktree,
+};
+
+const SEQUENCER_DIR: &str = ".git/sequencer";
+const TODO: &str = ".git/sequencer/todo";
+const OPTS: &str = ".git/sequencer/opts";
+const HEAD: &str = ".git/sequencer/head";
+const ABORT_SAFETY: &str = ".git/sequencer/abort-safety";
+pub(crate) const MERGE_MSG: &str = ".git/MERGE_MSG";
+
+/// 应用提交还是撤销提交
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Action {
+    Pick,
+    Revert,
+}
+
+impl Action {
+    /// 命令名，用在提示中
+    fn name(self) -> &'static str {
+        match self {
+            Action::Pick => "cherry-pick",
+            Action::Revert => "revert",
+        }
+    }
+
+    /// 待办列表中的指令
+    fn command(self) -> todo::Command {
+        match self {
+            Action::Pick => todo::Command::Pick,
+            Action::Revert => todo::Command::Revert,
+        }
+    }
+
+    /// 冲突时记录正在处理的提交的文件
+    fn head_file(self) -> &'static str {
+        match self {
+            Action::Pick => ".git/CHERRY_PICK_HEAD",
+            Action::Revert => ".git/REVERT_HEAD",
+        }
+    }
+}
+
+/// cherry-pick/revert 的选项，处理多个提交时保存在 `.git/sequencer/opts`
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ReplayOptions {
+    /// `-m`，合并提交以第几个父提交为基准
+    pub(crate) mainline: Option<usize>,
+    /// `-x`，在说明末尾记录原提交
+    pub(crate) record_origin: bool,
+    /// `-n`，只更新暂存区和工作区，不提交
+    pub(crate) no_commit: bool,
+}
+
+impl ReplayOptions {
+    /// 与 git 一样用配置文件的格式保存，只写非默认的选项
+    fn save(&self) -> anyhow::Result<()> {
+        let mut lines = Vec::new();
+        if self.no_commit {
+            lines.push("\tno-commit = true".to_string());
+        }
+        if self.record_origin {
+            lines.push("\trecord-origin = true".to_string());
+        }
+        if let Some(mainline) = self.mainline {
+            lines.push(format!("\tmainline = {mainline}"));
+        }
+        if lines.is_empty() {
+            return Ok(());
+        }
+        std::fs::write(OPTS, format!("[options]\n{}\n", lines.join("\n"))).context("write opts")
+    }
+
+    fn load() -> anyhow::Result<ReplayOptions> {
+        let mut options = ReplayOptions::default();
+        let Ok(content) = std::fs::read_to_string(OPTS) else {
+            return Ok(options);
+        };
+        for line in content.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            match key.trim() {
+                "no-commit" => options.no_commit = value.trim() == "true",
+                "record-origin" => options.record_origin = value.trim() == "true",
+                "mainline" => {
+                    options.mainline =
+                        Some(value.trim().parse().context("invalid mainline in opts")?)
+                }
+                _ => {}
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// 待办列表中的一项：`pick 1a2b3c4 subject`
+#[derive(Debug, Clone)]
+struct TodoItem {
+    action: Action,
+    id: [u8; 20],
+}
+
+/// 正在进行的 cherry-pick 或 revert，以及单个提交冲突时正在处理的提交；
+/// 与 git 一致，有待办列表时不给出具体的提交
+pub(crate) fn in_progress() -> anyhow::Result<Option<(Action, Option<[u8; 20]>)>> {
+    if let Some(action) = last_command() {
+        return Ok(Some((action, None)));
+    }
+    Ok(stopped_at()?.map(|(action, id)| (action, Some(id))))
+}
+
+/// 冲突或结果为空而停下时正在处理的提交
+fn stopped_at() -> anyhow::Result<Option<(Action, [u8; 20])>> {
+    for action in [Action::Pick, Action::Revert] {
+        if let Ok(content) = std::fs::read_to_string(action.head_file()) {
+            return Ok(Some((action, parse_hex(&content)?)));
+        }
+    }
+    Ok(None)
+}
+
+/// 待办列表第一项的指令
+fn last_command() -> Option<Action> {
+    let todo = std::fs::read_to_string(TODO).ok()?;
+    match todo.split_whitespace().next()? {
+        "pick" => Some(Action::Pick),
+        "revert" => Some(Action::Revert),
+        _ => None,
+    }
+}
+
+/// 依次应用 commits。single 表示命令行只给了一个提交，这时不保存 sequencer 状态，
+/// 可以在另一个 cherry-pick 的中途使用。全部成功时返回 true
+pub(crate) async fn pick_revisions(
+    action: Action,
+    commits: Vec<[u8; 20]>,
+    single: bool,
+    options: ReplayOptions,
+) -> anyhow::Result<bool> {
+    if let [commit] = commits.as_slice() {
+        if single {
+            return do_pick(action, commit, &options).await;
+        }
+    }
+    anyhow::ensure!(!commits.is_empty(), "empty commit set passed");
+    if Path::new(SEQUENCER_DIR).exists() {
+        let name = last_command().unwrap_or(action).name();
+        anyhow::bail!(
+            "{name} is already in progress\n\
+             hint: try \"git {name} (--continue | --abort | --quit)\""
+        );
+    }
+
+    std::fs::create_dir_all(SEQUENCER_DIR).context("create sequencer directory")?;
+    let head = refs::resolve_ref("HEAD")?;
+    let head_hex = head.map(hex::encode).unwrap_or_default();
+    std::fs::write(HEAD, format!("{head_hex}\n")).context("write sequencer head")?;
+    options.save()?;
+    let todo: Vec<TodoItem> = commits
+        .into_iter()
+        .map(|id| TodoItem { action, id })
+        .collect();
+    write_todo(&todo).await?;
+    if let Some(head) = head {
+        refs::write_ref("ORIG_HEAD", &head)?;
+    }
+    run(todo, &options).await
+}
+
+/// `--continue`：提交解决冲突后的结果，再接着处理剩下的提交
+pub(crate) async fn continue_sequence(action: Action) -> anyhow::Result<bool> {
+    let sequencing = Path::new(TODO).exists();
+    let stopped = stopped_at()?;
+    anyhow::ensure!(
+        sequencing || stopped.is_some(),
+        "no cherry-pick or revert in progress"
+    );
+    if let Some((running, _)) = stopped {
+        commit_resolved(running).await?;
+    } else {
+        let index = Index::read().await?;
+        ensure_clean_index(&index, action).await?;
+    }
+    if !sequencing {
+        return Ok(true);
+    }
+    let mut todo = read_todo().await?;
+    if !todo.is_empty() {
+        todo.remove(0);
+    }
+    write_abort_safety()?;
+    run(todo, &ReplayOptions::load()?).await
+}
+
+/// `--skip`：放弃正在处理的提交，接着处理剩下的
+pub(crate) async fn skip(action: Action) -> anyhow::Result<bool> {
+    if !Path::new(action.head_file()).exists() {
+        match last_command() {
+            Some(last) if last == action => anyhow::bail!(
+                "have you committed already?\n\
+                 hint: try \"git {} --continue\"",
+                action.name()
+            ),
+            _ => anyhow::bail!("no {} in progress", action.name()),
+        }
+    }
+    let head = refs::resolve_ref("HEAD")?.context("HEAD has no commits yet")?;
+    reset_merge(&head).await?;
+    remove_pick_state()?;
+    if !Path::new(TODO).exists() {
+        return Ok(true);
+    }
+    continue_sequence(action).await
+}
+
+/// `--abort`：放弃整个操作，回到开始前的 HEAD
+pub(crate) async fn abort() -> anyhow::Result<()> {
+    if !Path::new(SEQUENCER_DIR).exists() {
+        // 单个提交冲突时只需要丢弃这次的结果
+        anyhow::ensure!(
+            in_progress()?.is_some(),
+            "no cherry-pick or revert in progress"
+        );
+        let head = refs::resolve_ref("HEAD")?.context("HEAD has no commits yet")?;
+        reset_merge(&head).await?;
+        return remove_pick_state();
+    }
+
+    let orig = std::fs::read_to_string(HEAD).context("cannot read sequencer head")?;
+    let orig = orig.trim();
+    let safety = std::fs::read_to_string(ABORT_SAFETY).unwrap_or_default();
+    let current = refs::resolve_ref("HEAD")?
+        .map(hex::encode)
+        .unwrap_or_default();
+    if orig.is_empty() {
+        eprintln!("error: cannot abort from a branch yet to be born");
+    } else if safety.trim() != current {
+        // HEAD 被移动过时不回退，以免丢失提交
+        eprintln!("warning: You seem to have moved HEAD. Not rewinding, check your HEAD!");
+    } else {
+        let orig = parse_hex(orig)?;
+        reset_merge(&orig).await?;
+        refs::update_head(&orig)?;
+    }
+    remove_pick_state()?;
+    std::fs::remove_dir_all(SEQUENCER_DIR).context("remove sequencer directory")
+}
+
+/// 逐个处理待办列表，冲突时保存剩余的列表（包括当前项）并返回 false
+async fn run(mut todo: Vec<TodoItem>, options: &ReplayOptions) -> anyhow::Result<bool> {
+    while let Some(item) = todo.first().cloned() {
+        let done = do_pick(item.action, &item.id, options).await?;
+        if !done {
+            write_todo(&todo).await?;
+            write_abort_safety()?;
+            return Ok(false);
+        }
+        todo.remove(0);
+        write_abort_safety()?;
+    }
+    std::fs::remove_dir_all(SEQUENCER_DIR).context("remove sequencer directory")?;
+    Ok(true)
+}
+
+/// 应用（或撤销）一个提交，有冲突或结果为空时返回 false
+async fn do_pick(action: Action, id: &[u8; 20], options: &ReplayOptions) -> anyhow::Result<bool> {
+    let index = Index::read().await?;
+    let head = refs::resolve_ref("HEAD")?;
+    let head_files = match head {
+        Some(head) => flatten_tree(&Commit::read(&head).await?.tree).await?,
+        None => BTreeMap::new(),
+    };
+    // 不提交时在暂存区的基础上合并，否则暂存区必须与 HEAD 一致
+    let ours = if options.no_commit {
+        anyhow::ensure!(!index.has_conflicts(), "your index file is unmerged.");
+        index_files(&index)
+    } else {
+        ensure_clean_index(&index, action).await?;
+        head_files.clone()
+    };
+
+    let commit = Commit::read(id).await?;
+    let hex = hex::encode(id);
+    let parent = match (commit.parents.as_slice(), options.mainline) {
+        ([], _) => None,
+        ([parent], None | Some(1)) => Some(*parent),
+        ([_], Some(mainline)) => anyhow::bail!("commit {hex} does not have parent {mainline}"),
+        (_, None) => anyhow::bail!("commit {hex} is a merge but no -m option was given."),
+        (parents, Some(mainline)) => Some(
+            *parents
+                .get(mainline.wrapping_sub(1))
+                .with_context(|| format!("commit {hex} does not have parent {mainline}"))?,
+        ),
+    };
+
+    let abbrev = find_unique_abbrev(&hex, 7);
+    let subject = commit.message.lines().next().unwrap_or_default();
+    let label = format!("{abbrev} ({subject})");
+    let parent_label = format!("parent of {label}");
+    let commit_files = flatten_tree(&commit.tree).await?;
+    let parent_files = match parent {
+        Some(parent) => flatten_tree(&Commit::read(&parent).await?.tree).await?,
+        None => BTreeMap::new(),
+    };
+    let (base, theirs, base_label, theirs_label, message) = match action {
+        Action::Pick => {
+            let mut message = commit.message.trim_start_matches('\n').to_string();
+            if options.record_origin {
+                if !message.ends_with('\n') {
+                    message.push('\n');
+                }
+                if !has_conforming_footer(&message) {
+                    message.push('\n');
+                }
+                message.push_str(&format!("(cherry picked from commit {hex})\n"));
+            }
+            (parent_files, commit_files, parent_label, label, message)
+        }
+        Action::Revert => {
+            let mut message = format!("Revert \"{subject}\"\n\nThis reverts commit {hex}");
+            if let (Some(parent), [_, _, ..]) = (parent, commit.parents.as_slice()) {
+                message.push_str(&format!(
+                    ", reversing\nchanges made to {}",
+                    hex::encode(parent)
+                ));
+            }
+            message.push_str(".\n");
+            (commit_files, parent_files, label, parent_label, message)
+        }
+    };
+
+    let merge_options = TreeMergeOptions::new("HEAD", &theirs_label, &base_label);
+    let result = apply_merge(&index, &base, &ours, &theirs, merge_options, action.name()).await?;
+    print_merge_messages(&result)?;
+
+    if !result.is_clean() {
+        write_conflict_message(&message, &result)?;
+        if !options.no_commit {
+            std::fs::write(action.head_file(), format!("{hex}\n"))
+                .with_context(|| format!("write {}", action.head_file()))?;
+        }
+        let verb = match action {
+            Action::Pick => "apply",
+            Action::Revert => "revert",
+        };
+        eprintln!("error: could not {verb} {abbrev}... {subject}");
+        print_advice(action, options);
+        return Ok(false);
+    }
+    std::fs::write(MERGE_MSG, &message).context("write MERGE_MSG")?;
+    if options.no_commit {
+        return Ok(true);
+    }
+
+    // 结果与 HEAD 相同时不创建空提交，留给用户决定
+    if result.files == head_files {
+        std::fs::write(action.head_file(), format!("{hex}\n"))
+            .with_context(|| format!("write {}", action.head_file()))?;
+        print_status().await?;
+        if action == Action::Pick {
+            eprintln!(
+                "The previous cherry-pick is now empty, possibly due to conflict resolution.\n\
+                 If you wish to commit it anyway, use:\n\n    git commit --allow-empty\n\n\
+                 Otherwise, please use 'git cherry-pick --skip'"
+            );
+        }
+        return Ok(false);
+    }
+
+    let author = match action {
+        Action::Pick => Some(&commit.author),
+        Action::Revert => None,
+    };
+    let commit_id = commit_tree(&result.files, head.as_slice(), &message, author).await?;
+    std::fs::remove_file(MERGE_MSG).context("remove MERGE_MSG")?;
+    print_commit_summary(&commit_id, head.is_none(), true).await?;
+    Ok(true)
+}
+
+/// 把 base 到 theirs 的修改合并到 ours 上并更新工作区和暂存区，冲突的路径在暂存区中
+/// 记录各个阶段。operation 用在本地修改会被覆盖时的提示中
+pub(crate) async fn apply_merge(
+    index: &Index,
+    base: &BTreeMap<String, Side>,
+    ours: &BTreeMap<String, Side>,
+    theirs: &BTreeMap<String, Side>,
+    options: TreeMergeOptions<'_>,
+    operation: &str,
+) -> anyhow::Result<TreeMergeResult> {
+    let result = merge_trees(base, ours, theirs, options).await?;
+    let mut index = worktree::switch(index, ours, &result.files, operation, false).await?;
+    index
+        .entries
+        .retain(|entry| !result.conflicts.contains_key(&entry.path));
+    for (path, stages) in &result.conflicts {
+        for (stage, side) in (1..).zip(stages) {
+            if let Some(side) = side {
+                index
+                    .entries
+                    .push(IndexEntry::new(path, side.mode, side.hash, stage, None));
+            }
+        }
+    }
+    index.write().await?;
+    Ok(result)
+}
+
+/// 把合并信息输出到标准输出
+pub(crate) fn print_merge_messages(result: &TreeMergeResult) -> std::io::Result<()> {
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    for message in result.messages.values().flatten() {
+        writeln!(out, "{message}")?;
+    }
+    Ok(())
+}
+
+/// 冲突时把提交说明和冲突路径的注释写入 MERGE_MSG，供解决后提交使用
+pub(crate) fn write_conflict_message(
+    message: &str,
+    result: &TreeMergeResult,
+) -> anyhow::Result<()> {
+    let mut msg = format!("{message}\n# Conflicts:\n");
+    for path in result.conflicts.keys() {
+        msg.push_str(&format!("#\t{path}\n"));
+    }
+    std::fs::write(MERGE_MSG, msg).context("write MERGE_MSG")
+}
+
+/// 结果为空而停下时输出长格式的状态
+pub(crate) async fn print_status() -> anyhow::Result<()> {
+    status::invoke(StatusOptions {
+        format: status::Format::Long,
+        branch: false,
+        untracked: status::Untracked::Normal,
+        renames: RenameOptions::renames(),
+    })
+    .await
+}
+
+/// 用 files 创建以 parents 为父提交的提交并移动 HEAD。与 git 一样，有 prepare-commit-msg
+/// 钩子时先让它修改 COMMIT_EDITMSG 中的说明，提交后运行 post-commit
+pub(crate) async fn commit_tree(
+    files: &BTreeMap<String, Side>,
+    parents: &[[u8; 20]],
+    message: &str,
+    author: Option<&Signature>,
+) -> anyhow::Result<[u8; 20]> {
+    let hook_env = [("GIT_INDEX_FILE", INDEX_FILE), ("GIT_EDITOR", ":")];
+    let mut message = message.to_string();
+    if hook::find("prepare-commit-msg")?.is_some() {
+        if !message.ends_with('\n') {
+            message.push('\n');
+        }
+        std::fs::write(COMMIT_EDITMSG, &message).context("write COMMIT_EDITMSG")?;
+        let args = [COMMIT_EDITMSG, "message"];
+        anyhow::ensure!(
+            hook::run_with("prepare-commit-msg", &args, &hook_env, None)?,
+            "'prepare-commit-msg' hook failed"
+        );
+        message = std::fs::read_to_string(COMMIT_EDITMSG).context("read COMMIT_EDITMSG")?;
+    }
+    let tree = write_tree_from_files(
+        files
+            .iter()
+            .map(|(path, side)| (path.as_str(), side.mode, side.hash)),
+    )
+    .await?;
+    let parents: Vec<String> = parents.iter().map(hex::encode).collect();
+    let id = write_commit(&hex::encode(tree), message.trim_end(), &parents, author).await?;
+    refs::update_head(&id)?;
+    hook::run_with("post-commit", &[], &hook_env, None)?;
+    Ok(id)
+}
+
+/// 冲突解决后提交：说明取自 MERGE_MSG 并去掉注释，cherry-pick 沿用原提交的作者
+async fn commit_resolved(action: Action) -> anyhow::Result<()> {
+    let index = Index::read().await?;
+    // 与 git commit 一样先列出未解决的路径
+    let mut unmerged: Vec<&str> = index
+        .entries
+        .iter()
+        .filter(|entry| entry.stage > 0)
+        .map(|entry| entry.path.as_str())
+        .collect();
+    unmerged.dedup();
+    for path in &unmerged {
+        println!("U\t{path}");
+    }
+    anyhow::ensure!(
+        unmerged.is_empty(),
+        "Committing is not possible because you have unmerged files.\n\
+         hint: Fix them up in the work tree, and then use 'git add/rm <file>'\n\
+         hint: as appropriate to mark resolution and make a commit."
+    );
+    let picked = parse_hex(&std::fs::read_to_string(action.head_file())?)?;
+    let raw = std::fs::read_to_string(MERGE_MSG).unwrap_or_default();
+    let message = stripspace(&raw, true);
+    anyhow::ensure!(
+        !message.is_empty(),
+        "Aborting commit due to empty commit message."
+    );
+    let author = match action {
+        Action::Pick => Some(Commit::read(&picked).await?.author),
+        Action::Revert => None,
+    };
+    let head = refs::resolve_ref("HEAD")?;
+    let id = commit_tree(
+        &index_files(&index),
+        head.as_slice(),
+        &message,
+        author.as_ref(),
+    )
+    .await?;
+    remove_pick_state()?;
+    // 只有沿用了原作者时才显示作者日期
+    print_commit_summary(&id, head.is_none(), author.is_some()).await
+}
+
+/// 暂存区必须与 HEAD 一致，否则合并结果会混入未提交的修改
+async fn ensure_clean_index(index: &Index, action: Action) -> anyhow::Result<()> {
+    let head_files = match refs::resolve_ref("HEAD")? {
+        Some(head) => flatten_tree(&Commit::read(&head).await?.tree).await?,
+        None => BTreeMap::new(),
+    };
+    anyhow::ensure!(
+        !index.has_conflicts() && index_files(index) == head_files,
+        "your local changes would be overwritten by {}.\n\
+         hint: commit your changes or stash them to proceed.",
+        action.name()
+    );
+    Ok(())
+}
+
+/// 冲突时的提示
+fn print_advice(action: Action, options: &ReplayOptions) {
+    if options.no_commit {
+        eprintln!(
+            "hint: after resolving the conflicts, mark the corrected paths\n\
+             hint: with 'git add <paths>' or 'git rm <paths>'"
+        );
+        return;
+    }
+    let name = action.name();
+    eprintln!(
+        "hint: After resolving the conflicts, mark them with\n\
+         hint: \"git add/rm <pathspec>\", then run\n\
+         hint: \"git {name} --continue\".\n\
+         hint: You can instead skip this commit with \"git {name} --skip\".\n\
+         hint: To abort and get back to the state before \"git {name}\",\n\
+         hint: run \"git {name} --abort\"."
+    );
+}
+
+/// 最后一段是否全是 `Key: value` 形式的 trailer（或已有的 cherry-pick 记录），
+/// 标题段不算
+fn has_conforming_footer(message: &str) -> bool {
+    let paragraphs: Vec<&str> = message
+        .trim_end()
+        .split("\n\n")
+        .filter(|paragraph| !paragraph.trim().is_empty())
+        .collect();
+    let [_, .., last] = paragraphs.as_slice() else {
+        return false;
+    };
+    last.lines().all(|line| {
+        line.starts_with("(cherry picked from commit ")
+            || line.split_once(": ").is_some_and(|(key, _)| {
+                !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+            })
+    })
+}
+
+/// 把暂存区和工作区重置到 target，保留与这次操作无关的本地修改（`reset --merge`）
+pub(crate) async fn reset_merge(target: &[u8; 20]) -> anyhow::Result<()> {
+    let index = Index::read().await?;
+    let target_files = flatten_tree(&Commit::read(target).await?.tree).await?;
+    let mut index =
+        worktree::switch(&index, &index_files(&index), &target_files, "reset", true).await?;
+    index.write().await
+}
+
+fn remove_pick_state() -> anyhow::Result<()> {
+    refs::remove_files(&[
+        Action::Pick.head_file(),
+        Action::Revert.head_file(),
+        MERGE_MSG,
+    ])
+}
+
+/// 记录最后一次成功后的 HEAD，`--abort` 时用来判断 HEAD 是否被移动过
+fn write_abort_safety() -> anyhow::Result<()> {
+    let head = refs::resolve_ref("HEAD")?
+        .map(hex::encode)
+        .unwrap_or_default();
+    std::fs::write(ABORT_SAFETY, format!("{head}\n")).context("write abort-safety")
+}
+
+/// 保存待办列表，与 git 一样每行写成 `pick <缩写> <标题>`
+async fn write_todo(todo: &[TodoItem]) -> anyhow::Result<()> {
+    let mut items = Vec::new();
+    for item in todo {
+        let commit = Commit::read(&item.id).await?;
+        items.push(todo::TodoItem {
+            command: item.action.command(),
+            fixup_message: None,
+            commit: Some(item.id),
+            arg: commit
+                .message
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+        });
+    }
+    todo::write_todo(TODO, &items, true)
+}
+
+async fn read_todo() -> anyhow::Result<Vec<TodoItem>> {
+    let content = std::fs::read_to_string(TODO).context("read todo")?;
+    let mut todo = Vec::new();
+    for line in content.lines() {
+        let mut fields = line.split_whitespace();
+        let (Some(command), Some(rev)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        let action = match command {
+            "pick" | "p" => Action::Pick,
+            "revert" => Action::Revert,
+            _ => anyhow::bail!("invalid line in todo: {line}"),
+        };
+        todo.push(TodoItem {
+            action,
+            id: resolve(rev).await?,
+        });
+    }
+    Ok(todo)
+}
@@ -0,0 +1,934 @@
+//! rebase：把一系列提交逐个重放到新的基础上。待办列表和进度保存在 `.git/rebase-merge` 中，
+//! 遇到冲突、`edit`、`break` 或 `exec` 失败时停下，之后用 `--continue`、`--skip`
+//! 或 `--abort` 接着处理
+
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    path::Path,
+    process::{Command as Process, Stdio},
+};
+
+use anyhow::Context;
+
+use super::{
+    MERGE_MSG, apply_merge, commit_tree, print_merge_messages, print_status, reset_merge,
+    todo::{
+        Command, FixupMessage, TodoItem, count_commands, format_todo, help, parse_todo, write_todo,
+    },
+    write_conflict_message,
+};
+use crate::{
+    commands::commit::{print_commit_summary, stripspace},
+    diff::{Side, flatten_tree, index_files, patch_id::commit_patch, worktree_files},
+    editor, hook,
+    index::Index,
+    merge::tree::TreeMergeOptions,
+    objects::{
+        commit::{Commit, Signature, parse_hex},
+        find_unique_abbrev,
+    },
+    refs,
+    revision::resolve,
+    worktree,
+};
+
+const STATE_DIR: &str = ".git/rebase-merge";
+const TODO: &str = ".git/rebase-merge/git-rebase-todo";
+const TODO_BACKUP: &str = ".git/rebase-merge/git-rebase-todo.backup";
+const DONE: &str = ".git/rebase-merge/done";
+const MSGNUM: &str = ".git/rebase-merge/msgnum";
+const END: &str = ".git/rebase-merge/end";
+const HEAD_NAME: &str = ".git/rebase-merge/head-name";
+const ONTO: &str = ".git/rebase-merge/onto";
+const ORIG_HEAD: &str = ".git/rebase-merge/orig-head";
+const INTERACTIVE: &str = ".git/rebase-merge/interactive";
+/// 非交互式变基时，修改已在新基础上的提交直接丢弃
+const DROP_REDUNDANT: &str = ".git/rebase-merge/drop_redundant_commits";
+const UPDATE_REFS: &str = ".git/rebase-merge/update-refs";
+/// 停下时正在处理的提交和它的说明、作者
+const STOPPED_SHA: &str = ".git/rebase-merge/stopped-sha";
+const MESSAGE: &str = ".git/rebase-merge/message";
+const AUTHOR_SCRIPT: &str = ".git/rebase-merge/author-script";
+const PATCH: &str = ".git/rebase-merge/patch";
+/// `edit` 停下时的 HEAD，`--continue` 时把暂存的修改并入这个提交
+const AMEND: &str = ".git/rebase-merge/amend";
+/// 正在进行的 squash/fixup 链、被修正提交原来的说明和合并后的说明
+const CURRENT_FIXUPS: &str = ".git/rebase-merge/current-fixups";
+const MESSAGE_FIXUP: &str = ".git/rebase-merge/message-fixup";
+const MESSAGE_SQUASH: &str = ".git/rebase-merge/message-squash";
+const REBASE_HEAD: &str = ".git/REBASE_HEAD";
+
+/// 开始一次变基需要的信息
+pub(crate) struct Setup {
+    /// 要变基的分支（如 `refs/heads/topic`），分离头指针时为 None
+    pub(crate) head_name: Option<String>,
+    pub(crate) orig_head: [u8; 20],
+    /// 开始时检出的提交，给出要变基的分支时是还没切换过去的 HEAD
+    pub(crate) checked_out: [u8; 20],
+    pub(crate) onto: [u8; 20],
+    /// 待办列表中提交的范围，显示在编辑说明里
+    pub(crate) upstream: [u8; 20],
+    pub(crate) interactive: bool,
+}
+
+/// 处理一条指令后是继续还是停下，停下时带上命令的结果
+enum Step {
+    Next,
+    Stop(bool),
+}
+
+/// 是否有进行中的变基
+pub(crate) fn in_progress() -> bool {
+    Path::new(STATE_DIR).is_dir()
+}
+
+/// 进行中的变基，供 status 显示
+pub(crate) struct Progress {
+    /// 正在变基的分支名（不含 `refs/heads/`），分离头指针时为 None
+    pub(crate) branch: Option<String>,
+    pub(crate) onto: [u8; 20],
+    /// 已经执行和还没执行的指令，不含注释
+    pub(crate) done: Vec<String>,
+    pub(crate) todo: Vec<String>,
+}
+
+pub(crate) fn progress() -> anyhow::Result<Option<Progress>> {
+    if !in_progress() {
+        return Ok(None);
+    }
+    let commands = |path: &str| -> Vec<String> {
+        std::fs::read_to_string(path)
+            .unwrap_or_default()
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_string)
+            .collect()
+    };
+    Ok(Some(Progress {
+        branch: read_head_name()?.map(|name| {
+            name.strip_prefix("refs/heads/")
+                .unwrap_or(&name)
+                .to_string()
+        }),
+        onto: parse_hex(&read_state(ONTO)?)?,
+        done: commands(DONE),
+        todo: commands(TODO),
+    }))
+}
+
+/// 写入初始状态并开始变基。交互式变基先让用户编辑待办列表，删掉所有指令时放弃
+pub(crate) async fn start(setup: Setup, todo: Vec<TodoItem>) -> anyhow::Result<bool> {
+    std::fs::create_dir_all(STATE_DIR).context("create rebase-merge directory")?;
+    let head_name = setup.head_name.as_deref().unwrap_or("detached HEAD");
+    std::fs::write(HEAD_NAME, format!("{head_name}\n")).context("write head-name")?;
+    std::fs::write(ONTO, format!("{}\n", hex::encode(setup.onto))).context("write onto")?;
+    std::fs::write(ORIG_HEAD, format!("{}\n", hex::encode(setup.orig_head)))
+        .context("write orig-head")?;
+    std::fs::write(INTERACTIVE, "").context("write interactive")?;
+    if !setup.interactive {
+        std::fs::write(DROP_REDUNDANT, "").context("write drop_redundant_commits")?;
+    }
+
+    let range = format!(
+        "{}..{}",
+        find_unique_abbrev(&hex::encode(setup.upstream), 7),
+        find_unique_abbrev(&hex::encode(setup.orig_head), 7)
+    );
+    let onto_abbrev = find_unique_abbrev(&hex::encode(setup.onto), 7);
+    let heading = format!("{range} onto {onto_abbrev}");
+    let help = help(Some((&heading, count_commands(&todo))));
+    std::fs::write(TODO_BACKUP, format_todo(&todo, false) + &help)
+        .context("write git-rebase-todo.backup")?;
+    let todo = if setup.interactive {
+        std::fs::write(TODO, format_todo(&todo, true) + &help).context("write git-rebase-todo")?;
+        let edited = editor::edit_file(Path::new(TODO), true)
+            .and_then(|_| std::fs::read_to_string(TODO).context("read git-rebase-todo"));
+        let parsed = match edited {
+            // 与 git 一样先去掉注释、合并连续的空行
+            Ok(content) => parse_todo(&stripspace(&content, true)).await,
+            Err(e) => Err(e),
+        };
+        match parsed {
+            Ok(todo) if count_commands(&todo) > 0 => todo,
+            Ok(_) => {
+                remove_state()?;
+                anyhow::bail!("nothing to do");
+            }
+            Err(e) => {
+                remove_state()?;
+                return Err(e);
+            }
+        }
+    } else {
+        todo
+    };
+    if let Some(item) = todo.iter().find(|item| item.command.takes_commit()) {
+        if item.command.is_fixup() {
+            remove_state()?;
+            anyhow::bail!("cannot '{}' without a previous commit", item.command.name());
+        }
+    }
+    // 与 git 一样开始时的总数包括空行，继续时再按剩下的指令重新计算
+    let end = todo.len();
+    let (todo, base) = skip_unnecessary_picks(todo, setup.onto).await?;
+    write_todo(TODO, &todo, false)?;
+    std::fs::write(END, format!("{end}\n")).context("write end")?;
+    std::fs::write(MSGNUM, format!("{}\n", end - todo.len())).context("write msgnum")?;
+    let update_refs: Vec<UpdateRef> = todo
+        .iter()
+        .filter(|item| item.command == Command::UpdateRef)
+        .filter_map(|item| {
+            let old = refs::resolve_ref(&item.arg).ok()??;
+            Some((item.arg.clone(), old, [0; 20]))
+        })
+        .collect();
+    if !update_refs.is_empty() {
+        write_update_refs(&update_refs)?;
+    }
+
+    // 在分离头指针上重放，结束时再更新分支
+    refs::write_ref("ORIG_HEAD", &setup.orig_head)?;
+    let index = Index::read().await?;
+    let head_files = commit_files(&setup.checked_out).await?;
+    let base_files = commit_files(&base).await?;
+    let mut index = worktree::switch(&index, &head_files, &base_files, "checkout", false).await?;
+    index.write().await?;
+    refs::write_ref("HEAD", &base)?;
+    hook::run(
+        "post-checkout",
+        &[&hex::encode(setup.checked_out), &hex::encode(base), "1"],
+    )?;
+    run().await
+}
+
+/// 开头父提交就是当前基础的 pick 不需要重放，直接移到 done 并把基础前移到这些提交上。
+/// 返回剩下的列表和新的基础
+async fn skip_unnecessary_picks(
+    mut todo: Vec<TodoItem>,
+    mut base: [u8; 20],
+) -> anyhow::Result<(Vec<TodoItem>, [u8; 20])> {
+    let mut skipped = 0;
+    for item in &todo {
+        if !matches!(item.command, Command::Noop | Command::Comment) {
+            let Some(id) = item.commit.filter(|_| item.command == Command::Pick) else {
+                break;
+            };
+            if Commit::read(&id).await?.parents != [base] {
+                break;
+            }
+            base = id;
+        }
+        skipped += 1;
+    }
+    let mut done = String::new();
+    for item in todo.drain(..skipped) {
+        if item.command != Command::Comment {
+            done.push_str(&format!("{}\n", item.format(false)));
+        }
+    }
+    if !done.is_empty() {
+        std::fs::write(DONE, done).context("write done")?;
+    }
+    Ok((todo, base))
+}
+
+/// `--continue`：提交停下时暂存的修改，然后接着执行待办列表
+pub(crate) async fn continue_rebase() -> anyhow::Result<bool> {
+    anyhow::ensure!(in_progress(), "No rebase in progress?");
+    let index = Index::read().await?;
+    let mut unmerged: Vec<&str> = index
+        .entries
+        .iter()
+        .filter(|entry| entry.stage > 0)
+        .map(|entry| entry.path.as_str())
+        .collect();
+    unmerged.dedup();
+    if !unmerged.is_empty() || worktree_files(&index).await? != index_files(&index) {
+        for path in unmerged {
+            println!("{path}: needs merge");
+        }
+        println!("You must edit all merge conflicts and then\nmark them as resolved using git add");
+        return Ok(false);
+    }
+
+    let head = refs::resolve_ref("HEAD")?.context("HEAD has no commits yet")?;
+    let staged = index_files(&index);
+    let changed = staged != commit_files(&head).await?;
+    let stopped = match std::fs::read_to_string(REBASE_HEAD) {
+        Ok(content) => Some(parse_hex(&content)?),
+        Err(_) => None,
+    };
+    let fixups = read_fixups().await?;
+    let todo = parse_todo(&std::fs::read_to_string(TODO).unwrap_or_default()).await?;
+    if let Ok(amend) = std::fs::read_to_string(AMEND) {
+        // edit 停下后暂存的修改并入这个提交
+        if changed {
+            anyhow::ensure!(
+                parse_hex(&amend)? == head,
+                "\nYou have uncommitted changes in your working tree. Please, commit them\n\
+                 first and then run 'git rebase --continue' again."
+            );
+            let commit = Commit::read(&head).await?;
+            let message = editor::edit_message(&commit.message)?;
+            let id = commit_tree(&staged, &commit.parents, &message, Some(&commit.author)).await?;
+            print_commit_summary(&id, false, true).await?;
+        }
+    } else if stopped.is_some() && fixups.last().and_then(|item| item.commit) == stopped {
+        // squash/fixup 冲突时，解决的结果并入前一个提交
+        amend_fixup(&staged, &todo).await?;
+    } else if let Some(stopped) = stopped.filter(|_| changed) {
+        let raw = std::fs::read_to_string(MERGE_MSG)
+            .or_else(|_| std::fs::read_to_string(MESSAGE))
+            .unwrap_or_default();
+        // 与 git commit 一样打开编辑器确认说明
+        let message = editor::edit_message(&stripspace(&raw, true))?;
+        let author = read_author_script()?.unwrap_or(Commit::read(&stopped).await?.author);
+        let id = commit_tree(&staged, &[head], &message, Some(&author)).await?;
+        print_commit_summary(&id, false, false).await?;
+    } else if stopped.is_none() && changed {
+        anyhow::bail!(
+            "you have staged changes in your working tree\n\
+             If these changes are meant to be squashed into the previous commit, run:\n\n  \
+             git commit --amend \n\n\
+             If they are meant to go into a new commit, run:\n\n  \
+             git commit \n\n\
+             In both cases, once you're done, continue with:\n\n  \
+             git rebase --continue\n"
+        );
+    }
+    remove_stopped_state()?;
+    resume().await
+}
+
+/// `--skip`：丢弃正在处理的提交，接着执行待办列表
+pub(crate) async fn skip() -> anyhow::Result<bool> {
+    anyhow::ensure!(in_progress(), "No rebase in progress?");
+    let head = refs::resolve_ref("HEAD")?.context("HEAD has no commits yet")?;
+    reset_merge(&head).await?;
+    let stopped = match std::fs::read_to_string(REBASE_HEAD) {
+        Ok(content) => Some(parse_hex(&content)?),
+        Err(_) => None,
+    };
+    let mut fixups = read_fixups().await?;
+    if stopped.is_some() && fixups.last().and_then(|item| item.commit) == stopped {
+        // 跳过修正链中的一个提交，链中剩下的照常结束
+        fixups.pop();
+        if fixups.is_empty() {
+            remove_fixup_state()?;
+        } else {
+            write_fixups(&fixups)?;
+            let message = squash_message(&fixups).await?;
+            std::fs::write(MESSAGE_SQUASH, message).context("write message-squash")?;
+            let todo = parse_todo(&std::fs::read_to_string(TODO).unwrap_or_default()).await?;
+            amend_fixup(&commit_files(&head).await?, &todo).await?;
+        }
+    }
+    remove_stopped_state()?;
+    resume().await
+}
+
+/// `--abort`：回到变基前的分支和提交
+pub(crate) async fn abort() -> anyhow::Result<()> {
+    anyhow::ensure!(in_progress(), "No rebase in progress?");
+    let orig_head = parse_hex(&read_state(ORIG_HEAD)?)?;
+    reset_merge(&orig_head).await?;
+    match read_head_name()? {
+        Some(branch) => refs::write_symbolic_ref("HEAD", &branch)?,
+        None => refs::write_ref("HEAD", &orig_head)?,
+    }
+    remove_stopped_state()?;
+    remove_state()
+}
+
+/// `--edit-todo`：编辑剩下的待办列表
+pub(crate) async fn edit_todo() -> anyhow::Result<()> {
+    anyhow::ensure!(in_progress(), "No rebase in progress?");
+    let todo = parse_todo(&std::fs::read_to_string(TODO).unwrap_or_default()).await?;
+    std::fs::write(TODO, format_todo(&todo, true) + &help(None))
+        .context("write git-rebase-todo")?;
+    editor::edit_file(Path::new(TODO), true)?;
+    let edited = std::fs::read_to_string(TODO).context("read git-rebase-todo")?;
+    let todo = parse_todo(&stripspace(&edited, true)).await?;
+    write_todo(TODO, &todo, false)?;
+    write_end(&todo)
+}
+
+/// 总数改为已经执行的加上剩下的指令数
+fn write_end(todo: &[TodoItem]) -> anyhow::Result<()> {
+    let msgnum: usize = read_state(MSGNUM)?.parse().unwrap_or(0);
+    std::fs::write(END, format!("{}\n", msgnum + count_commands(todo))).context("write end")
+}
+
+/// `--continue` 和 `--skip` 处理完停下的提交后接着执行待办列表
+async fn resume() -> anyhow::Result<bool> {
+    let todo = parse_todo(&std::fs::read_to_string(TODO).context("read git-rebase-todo")?).await?;
+    write_end(&todo)?;
+    run().await
+}
+
+/// 逐条执行待办列表。每条指令执行前先从列表移到 done，停下时剩下的列表已经保存好
+async fn run() -> anyhow::Result<bool> {
+    let mut todo =
+        parse_todo(&std::fs::read_to_string(TODO).context("read git-rebase-todo")?).await?;
+    let mut msgnum: usize = read_state(MSGNUM)?.parse().unwrap_or(0);
+    let end: usize = read_state(END)?.parse().unwrap_or(0);
+    while !todo.is_empty() {
+        let item = todo.remove(0);
+        write_todo(TODO, &todo, false)?;
+        if item.command == Command::Comment {
+            continue;
+        }
+        refs::remove_files(&[REBASE_HEAD, STOPPED_SHA, AMEND])?;
+        msgnum += 1;
+        std::fs::write(MSGNUM, format!("{msgnum}\n")).context("write msgnum")?;
+        let mut done = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(DONE)
+            .context("open done")?;
+        writeln!(done, "{}", item.format(false)).context("write done")?;
+        eprint!("Rebasing ({msgnum}/{end})\r");
+
+        let step = match item.command {
+            Command::Pick | Command::Reword | Command::Edit => pick(&item).await?,
+            Command::Squash | Command::Fixup => fixup(&item, &todo).await?,
+            Command::Exec => exec(&item.arg).await?,
+            Command::Break => {
+                let head = refs::resolve_ref("HEAD")?.context("HEAD has no commits yet")?;
+                clear_line();
+                eprintln!("Stopped at {}", label(&head, &Commit::read(&head).await?));
+                Step::Stop(true)
+            }
+            Command::UpdateRef => {
+                let head = refs::resolve_ref("HEAD")?.context("HEAD has no commits yet")?;
+                let mut update_refs = read_update_refs()?;
+                match update_refs.iter_mut().find(|(name, ..)| *name == item.arg) {
+                    Some(entry) => entry.2 = head,
+                    None => update_refs.push((item.arg.clone(), [0; 20], head)),
+                }
+                write_update_refs(&update_refs)?;
+                Step::Next
+            }
+            Command::Drop | Command::Noop | Command::Comment => Step::Next,
+            Command::Revert => anyhow::bail!("revert is not supported by rebase"),
+        };
+        if let Step::Stop(result) = step {
+            return Ok(result);
+        }
+    }
+    finish().await?;
+    Ok(true)
+}
+
+/// 重放一个提交。父提交就是 HEAD 时直接快进，不重新创建提交
+async fn pick(item: &TodoItem) -> anyhow::Result<Step> {
+    let id = item.commit.context("missing commit in todo")?;
+    let commit = Commit::read(&id).await?;
+    let parent = commit_parent(&id, &commit)?;
+    let head = refs::resolve_ref("HEAD")?.context("HEAD has no commits yet")?;
+    if parent == Some(head) {
+        let index = Index::read().await?;
+        let mut index = worktree::switch(
+            &index,
+            &commit_files(&head).await?,
+            &commit_files(&id).await?,
+            "rebase",
+            false,
+        )
+        .await?;
+        index.write().await?;
+        refs::write_ref("HEAD", &id)?;
+    } else {
+        let Some(files) = merge_commit(&id, &commit, parent, head, &commit.message).await? else {
+            return Ok(Step::Stop(false));
+        };
+        if files == commit_files(&head).await? && !is_empty_commit(&commit, parent).await? {
+            // 修改已经包含在新的基础上
+            if Path::new(DROP_REDUNDANT).exists() {
+                eprintln!(
+                    "dropping {} {} -- patch contents already upstream",
+                    hex::encode(id),
+                    first_line(&commit)
+                );
+                return Ok(Step::Next);
+            }
+            write_stopped(&id, &commit).await?;
+            std::fs::write(MERGE_MSG, &commit.message).context("write MERGE_MSG")?;
+            print_status().await?;
+            eprintln!(
+                "The previous cherry-pick is now empty, possibly due to conflict resolution.\n\
+                 If you wish to commit it anyway, use:\n\n    git commit --allow-empty\n\n\
+                 Otherwise, please use 'git rebase --skip'"
+            );
+            eprintln!("Could not apply {}", short_label(&id, &commit));
+            return Ok(Step::Stop(false));
+        }
+        commit_tree(&files, &[head], &commit.message, Some(&commit.author)).await?;
+    }
+
+    match item.command {
+        Command::Reword => {
+            let head = refs::resolve_ref("HEAD")?.context("HEAD has no commits yet")?;
+            let message = editor::edit_message(&commit.message)?;
+            let parents = Commit::read(&head).await?.parents;
+            let files = commit_files(&head).await?;
+            let id = commit_tree(&files, &parents, &message, Some(&commit.author)).await?;
+            print_commit_summary(&id, false, true).await?;
+        }
+        Command::Edit => {
+            let head = refs::resolve_ref("HEAD")?.context("HEAD has no commits yet")?;
+            write_stopped(&id, &commit).await?;
+            std::fs::write(AMEND, format!("{}\n", hex::encode(head))).context("write amend")?;
+            clear_line();
+            eprintln!(
+                "Stopped at {}...  {}\n\
+                 You can amend the commit now, with\n\n  git commit --amend \n\n\
+                 Once you are satisfied with your changes, run\n\n  git rebase --continue",
+                find_unique_abbrev(&hex::encode(id), 7),
+                first_line(&commit)
+            );
+            return Ok(Step::Stop(true));
+        }
+        _ => {}
+    }
+    Ok(Step::Next)
+}
+
+/// squash/fixup：把提交的修改并入 HEAD，并记录到修正链中
+async fn fixup(item: &TodoItem, todo: &[TodoItem]) -> anyhow::Result<Step> {
+    let id = item.commit.context("missing commit in todo")?;
+    let commit = Commit::read(&id).await?;
+    let parent = commit_parent(&id, &commit)?;
+    let head = refs::resolve_ref("HEAD")?.context("HEAD has no commits yet")?;
+
+    let mut fixups = read_fixups().await?;
+    if fixups.is_empty() {
+        std::fs::write(MESSAGE_FIXUP, &Commit::read(&head).await?.message)
+            .context("write message-fixup")?;
+    }
+    fixups.push(item.clone());
+    write_fixups(&fixups)?;
+    let message = squash_message(&fixups).await?;
+    std::fs::write(MESSAGE_SQUASH, &message).context("write message-squash")?;
+
+    // 冲突时 MERGE_MSG 中是合并后的说明
+    let Some(files) = merge_commit(&id, &commit, parent, head, &message).await? else {
+        return Ok(Step::Stop(false));
+    };
+    amend_fixup(&files, todo).await?;
+    Ok(Step::Next)
+}
+
+/// 用 files 修改 HEAD，完成修正链中的一步。后面还有 squash/fixup 时先用合并后的说明提交，
+/// 否则得到最终的说明（链中有 squash 或 `fixup -c` 时打开编辑器）并结束这条链
+async fn amend_fixup(files: &BTreeMap<String, Side>, todo: &[TodoItem]) -> anyhow::Result<()> {
+    let head = refs::resolve_ref("HEAD")?.context("HEAD has no commits yet")?;
+    let head_commit = Commit::read(&head).await?;
+    let message = std::fs::read_to_string(MESSAGE_SQUASH).context("read message-squash")?;
+    let more = todo
+        .iter()
+        .find(|item| item.command != Command::Comment)
+        .is_some_and(|item| item.command.is_fixup());
+    if more {
+        commit_tree(
+            files,
+            &head_commit.parents,
+            &stripspace(&message, true),
+            Some(&head_commit.author),
+        )
+        .await?;
+        return Ok(());
+    }
+    let fixups = read_fixups().await?;
+    let edit = fixups.iter().any(|item| {
+        item.command == Command::Squash || item.fixup_message == Some(FixupMessage::Edit)
+    });
+    let final_message = if edit {
+        // 与 git 一样，交给编辑器的提交会记下正在处理的提交，直到执行下一条指令
+        if let Some(id) = fixups.last().and_then(|item| item.commit) {
+            std::fs::write(REBASE_HEAD, format!("{}\n", hex::encode(id)))
+                .context("write REBASE_HEAD")?;
+        }
+        editor::edit_message(&message)?
+    } else {
+        stripspace(&message, true)
+    };
+    let id = commit_tree(
+        files,
+        &head_commit.parents,
+        &final_message,
+        Some(&head_commit.author),
+    )
+    .await?;
+    remove_fixup_state()?;
+    if edit {
+        print_commit_summary(&id, false, true).await?;
+    }
+    Ok(())
+}
+
+/// 修正链合并后的说明：被修正的提交和每个 squash 的说明依次排列，fixup 的说明注释掉；
+/// `fixup -C`/`-c` 改用这个提交的说明，之前的都注释掉
+async fn squash_message(fixups: &[TodoItem]) -> anyhow::Result<String> {
+    let mut parts = vec![(
+        Command::Pick,
+        std::fs::read_to_string(MESSAGE_FIXUP).context("read message-fixup")?,
+    )];
+    for item in fixups {
+        let id = item.commit.context("missing commit in current-fixups")?;
+        let mut message = Commit::read(&id).await?.message;
+        if item.fixup_message.is_some() {
+            // `amend!` 提交的标题只用来找目标，说明从第二段开始
+            if message.starts_with("amend! ") {
+                message = message
+                    .split_once("\n\n")
+                    .map_or(String::new(), |(_, body)| body.to_string());
+            }
+        }
+        parts.push((item.command, message));
+    }
+    let replaced = fixups
+        .iter()
+        .rposition(|item| item.fixup_message.is_some())
+        .map_or(0, |i| i + 1);
+
+    let mut out = format!("# This is a combination of {} commits.\n", parts.len());
+    for (n, (command, message)) in (1..).zip(&parts) {
+        let skipped = n - 1 < replaced
+            || (*command == Command::Fixup && fixups[n - 2].fixup_message.is_none());
+        let header = match (n, skipped) {
+            (1, false) => "# This is the 1st commit message:".to_string(),
+            (1, true) => "# The 1st commit message will be skipped:".to_string(),
+            (_, false) => format!("# This is the commit message #{n}:"),
+            (_, true) => format!("# The commit message #{n} will be skipped:"),
+        };
+        if n > 1 {
+            out.push('\n');
+        }
+        out.push_str(&header);
+        out.push_str("\n\n");
+        for (i, line) in message.lines().enumerate() {
+            let commented = skipped
+                || (i == 0
+                    && *command == Command::Squash
+                    && ["squash! ", "fixup! ", "amend! "]
+                        .iter()
+                        .any(|prefix| line.starts_with(prefix)));
+            match (commented, line.is_empty()) {
+                (true, true) => out.push('#'),
+                (true, false) => {
+                    out.push_str("# ");
+                    out.push_str(line);
+                }
+                (false, _) => out.push_str(line),
+            }
+            out.push('\n');
+        }
+    }
+    Ok(out)
+}
+
+/// `exec`：用 shell 执行命令，失败或留下未提交的修改时停下
+async fn exec(command: &str) -> anyhow::Result<Step> {
+    clear_line();
+    eprintln!("Executing: {command}");
+    std::io::stdout().flush()?;
+    let status = Process::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::null())
+        .status()
+        .with_context(|| format!("run {command}"))?;
+    if !status.success() {
+        eprintln!(
+            "warning: execution failed: {command}\n\
+             You can fix the problem, and then run\n\n  git rebase --continue\n\n"
+        );
+        return Ok(Step::Stop(false));
+    }
+    let index = Index::read().await?;
+    let head = refs::resolve_ref("HEAD")?.context("HEAD has no commits yet")?;
+    let staged = index_files(&index);
+    if staged != commit_files(&head).await? || worktree_files(&index).await? != staged {
+        eprintln!(
+            "warning: execution succeeded: {command}\n\
+             but left changes to the index and/or the working tree\n\
+             Commit or stash your changes, and then run\n\n  git rebase --continue\n\n"
+        );
+        return Ok(Step::Stop(false));
+    }
+    Ok(Step::Next)
+}
+
+/// 把提交相对父提交的修改合并到 HEAD 上。冲突时记录停下的位置，把 message 和冲突的路径
+/// 写入 MERGE_MSG 并给出提示，返回 None
+async fn merge_commit(
+    id: &[u8; 20],
+    commit: &Commit,
+    parent: Option<[u8; 20]>,
+    head: [u8; 20],
+    message: &str,
+) -> anyhow::Result<Option<BTreeMap<String, Side>>> {
+    let index = Index::read().await?;
+    let label = label(id, commit);
+    let parent_label = format!("parent of {label}");
+    let base = match parent {
+        Some(parent) => commit_files(&parent).await?,
+        None => BTreeMap::new(),
+    };
+    let ours = commit_files(&head).await?;
+    let theirs = commit_files(id).await?;
+    let options = TreeMergeOptions::new("HEAD", &label, &parent_label);
+    let result = apply_merge(&index, &base, &ours, &theirs, options, "rebase").await?;
+    // 与 git 一样只在冲突时输出合并信息
+    if result.is_clean() {
+        return Ok(Some(result.files));
+    }
+    print_merge_messages(&result)?;
+    write_stopped(id, commit).await?;
+    write_conflict_message(message, &result)?;
+    let short = short_label(id, commit);
+    eprintln!(
+        "error: could not apply {short}\n\
+         hint: Resolve all conflicts manually, mark them as resolved with\n\
+         hint: \"git add/rm <conflicted_files>\", then run \"git rebase --continue\".\n\
+         hint: You can instead skip this commit: run \"git rebase --skip\".\n\
+         hint: To abort and get back to the state before \"git rebase\", run \"git rebase --abort\".\n\
+         Could not apply {short}"
+    );
+    Ok(None)
+}
+
+/// 清掉 `Rebasing (n/m)` 进度行，终端不支持控制序列时用空格覆盖
+fn clear_line() {
+    if std::env::var("TERM").map_or(true, |term| term == "dumb") {
+        eprint!("\r{:79}\r", "");
+    } else {
+        eprint!("\r\x1b[K");
+    }
+}
+
+/// 全部完成：把分支移到新的提交上并切换回去，更新 `--update-refs` 记录的引用
+async fn finish() -> anyhow::Result<()> {
+    let head = refs::resolve_ref("HEAD")?.context("HEAD has no commits yet")?;
+    let head_name = read_head_name()?;
+    if let Some(branch) = &head_name {
+        refs::write_ref(branch, &head)?;
+        refs::write_symbolic_ref("HEAD", branch)?;
+    }
+    let mut updated = Vec::new();
+    for (name, _, new) in read_update_refs()? {
+        if new != [0; 20] {
+            refs::write_ref(&name, &new)?;
+            updated.push(name);
+        }
+    }
+    remove_state()?;
+    clear_line();
+    eprintln!(
+        "Successfully rebased and updated {}.",
+        head_name.as_deref().unwrap_or("detached HEAD")
+    );
+    if !updated.is_empty() {
+        eprintln!("Updated the following refs with --update-refs:");
+        for name in updated {
+            eprintln!("\t{name}");
+        }
+    }
+    Ok(())
+}
+
+/// 提交的父提交，合并提交不能重放
+fn commit_parent(id: &[u8; 20], commit: &Commit) -> anyhow::Result<Option<[u8; 20]>> {
+    match commit.parents.as_slice() {
+        [] => Ok(None),
+        [parent] => Ok(Some(*parent)),
+        _ => anyhow::bail!(
+            "commit {} is a merge but no -m option was given.",
+            hex::encode(id)
+        ),
+    }
+}
+
+/// 提交本身没有修改（如 `commit --allow-empty`），这样的提交照样重放
+async fn is_empty_commit(commit: &Commit, parent: Option<[u8; 20]>) -> anyhow::Result<bool> {
+    Ok(match parent {
+        Some(parent) => Commit::read(&parent).await?.tree == commit.tree,
+        None => flatten_tree(&commit.tree).await?.is_empty(),
+    })
+}
+
+async fn commit_files(id: &[u8; 20]) -> anyhow::Result<BTreeMap<String, Side>> {
+    flatten_tree(&Commit::read(id).await?.tree).await
+}
+
+fn first_line(commit: &Commit) -> &str {
+    commit.message.lines().next().unwrap_or_default()
+}
+
+/// 冲突标记中的名称：`1a2b3c4 (subject)`
+fn label(id: &[u8; 20], commit: &Commit) -> String {
+    format!(
+        "{} ({})",
+        find_unique_abbrev(&hex::encode(id), 7),
+        first_line(commit)
+    )
+}
+
+/// 提示中的名称：`1a2b3c4... subject`
+fn short_label(id: &[u8; 20], commit: &Commit) -> String {
+    format!(
+        "{}... {}",
+        find_unique_abbrev(&hex::encode(id), 7),
+        first_line(commit)
+    )
+}
+
+/// 停下时记录正在处理的提交、它的说明、补丁和作者
+async fn write_stopped(id: &[u8; 20], commit: &Commit) -> anyhow::Result<()> {
+    let hex = hex::encode(id);
+    std::fs::write(REBASE_HEAD, format!("{hex}\n")).context("write REBASE_HEAD")?;
+    std::fs::write(STOPPED_SHA, format!("{hex}\n")).context("write stopped-sha")?;
+    std::fs::write(MESSAGE, format!("{}\n", commit.message)).context("write message")?;
+    if let Some(patch) = commit_patch(commit).await? {
+        std::fs::write(PATCH, patch).context("write patch")?;
+    }
+    let author = &commit.author;
+    let quote = |value: &str| format!("'{}'", value.replace('\'', "'\\''"));
+    std::fs::write(
+        AUTHOR_SCRIPT,
+        format!(
+            "GIT_AUTHOR_NAME={}\nGIT_AUTHOR_EMAIL={}\nGIT_AUTHOR_DATE={}\n",
+            quote(&author.name),
+            quote(&author.email),
+            quote(&format!("@{} {}", author.time, author.format_tz()))
+        ),
+    )
+    .context("write author-script")
+}
+
+/// 读取 author-script，值是 shell 的单引号字符串
+fn read_author_script() -> anyhow::Result<Option<Signature>> {
+    let Ok(content) = std::fs::read_to_string(AUTHOR_SCRIPT) else {
+        return Ok(None);
+    };
+    let mut fields = BTreeMap::new();
+    for line in content.lines() {
+        if let Some((key, value)) = line.split_once('=') {
+            let value = value.replace("'\\''", "'");
+            let value = value.trim_matches('\'');
+            fields.insert(key, value.to_string());
+        }
+    }
+    let (Some(name), Some(email), Some(date)) = (
+        fields.get("GIT_AUTHOR_NAME"),
+        fields.get("GIT_AUTHOR_EMAIL"),
+        fields.get("GIT_AUTHOR_DATE"),
+    ) else {
+        anyhow::bail!("invalid author-script");
+    };
+    let date = date.trim_start_matches('@');
+    Signature::parse(&format!("{name} <{email}> {date}")).map(Some)
+}
+
+async fn read_fixups() -> anyhow::Result<Vec<TodoItem>> {
+    match std::fs::read_to_string(CURRENT_FIXUPS) {
+        Ok(content) => parse_todo(&content).await,
+        Err(_) => Ok(Vec::new()),
+    }
+}
+
+/// 修正链中每个提交一行：`fixup <hex>`、`fixup -C <hex>` 或 `squash <hex>`
+fn write_fixups(fixups: &[TodoItem]) -> anyhow::Result<()> {
+    let content: String = fixups
+        .iter()
+        .map(|item| {
+            let item = TodoItem {
+                arg: String::new(),
+                ..item.clone()
+            };
+            format!("{}\n", item.format(false))
+        })
+        .collect();
+    std::fs::write(CURRENT_FIXUPS, content).context("write current-fixups")
+}
+
+/// `--update-refs` 要更新的引用：名称、变基前的位置和变基后的位置（还没到达时为全零），
+/// 在 `update-refs` 文件中每个引用写成这样三行
+type UpdateRef = (String, [u8; 20], [u8; 20]);
+
+fn read_update_refs() -> anyhow::Result<Vec<UpdateRef>> {
+    let content = std::fs::read_to_string(UPDATE_REFS).unwrap_or_default();
+    let lines: Vec<&str> = content.lines().collect();
+    lines
+        .chunks(3)
+        .filter(|chunk| chunk.len() == 3)
+        .map(|chunk| {
+            Ok((
+                chunk[0].to_string(),
+                parse_hex(chunk[1])?,
+                parse_hex(chunk[2])?,
+            ))
+        })
+        .collect()
+}
+
+fn write_update_refs(update_refs: &[UpdateRef]) -> anyhow::Result<()> {
+    let content: String = update_refs
+        .iter()
+        .map(|(name, old, new)| format!("{name}\n{}\n{}\n", hex::encode(old), hex::encode(new)))
+        .collect();
+    std::fs::write(UPDATE_REFS, content).context("write update-refs")
+}
+
+fn read_state(path: &str) -> anyhow::Result<String> {
+    let content =
+        std::fs::read_to_string(path).with_context(|| format!("could not read '{path}'"))?;
+    Ok(content.trim().to_string())
+}
+
+/// `head-name` 中的分支，分离头指针时为 None
+fn read_head_name() -> anyhow::Result<Option<String>> {
+    let name = read_state(HEAD_NAME)?;
+    Ok(name.starts_with("refs/").then_some(name))
+}
+
+fn remove_stopped_state() -> anyhow::Result<()> {
+    refs::remove_files(&[
+        REBASE_HEAD,
+        MERGE_MSG,
+        AMEND,
+        STOPPED_SHA,
+        MESSAGE,
+        AUTHOR_SCRIPT,
+    ])
+}
+
+fn remove_fixup_state() -> anyhow::Result<()> {
+    refs::remove_files(&[CURRENT_FIXUPS, MESSAGE_FIXUP, MESSAGE_SQUASH])
+}
+
+fn remove_state() -> anyhow::Result<()> {
+    std::fs::remove_dir_all(STATE_DIR).context("remove rebase-merge directory")
+}
+
+/// 状态中把指令的提交缩写显示，与 git 一样只要第二个词能解析为对象就缩写
+pub(crate) async fn abbreviate_line(line: &str) -> String {
+    let mut words = line.splitn(3, ' ');
+    let (Some(command), Some(name)) = (words.next(), words.next()) else {
+        return line.to_string();
+    };
+    if matches!(command, "exec" | "x") {
+        return line.to_string();
+    }
+    match resolve(name).await {
+        Ok(id) => format!(
+            "{command} {} {}",
+            find_unique_abbrev(&hex::encode(id), 7),
+            words.next().unwrap_or_default()
+        ),
+        Err(_) => line.to_string(),
+    }
+}
@@ -0,0 +1,383 @@
+//! rebase 的待办列表：每行一条指令，如 `pick 1a2b3c4 subject`、`exec make test`，
+//! 空行和 `#` 开头的行是注释
+
+use std::collections::HashMap;
+
+use anyhow::Context;
+
+use crate::{
+    objects::{Kind, find_unique_abbrev},
+    revision::{peel, resolve},
+};
+
+/// 待办列表中的指令
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Command {
+    Pick,
+    /// 撤销提交，只出现在 cherry-pick 和 revert 的待办列表中，变基不接受
+    Revert,
+    /// 应用提交后修改说明
+    Reword,
+    /// 应用提交后停下，可以修改这个提交
+    Edit,
+    /// 并入前一个提交，合并两者的说明
+    Squash,
+    /// 并入前一个提交，只保留前一个提交的说明
+    Fixup,
+    /// 用 shell 执行命令，失败时停下
+    Exec,
+    /// 停下，之后用 `--continue` 继续
+    Break,
+    Drop,
+    /// 结束时把引用更新到这个位置的新提交
+    UpdateRef,
+    /// 没有要重放的提交时的占位
+    Noop,
+    /// 空行或注释，原样保留
+    Comment,
+}
+
+impl Command {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Command::Pick => "pick",
+            Command::Revert => "revert",
+            Command::Reword => "reword",
+            Command::Edit => "edit",
+            Command::Squash => "squash",
+            Command::Fixup => "fixup",
+            Command::Exec => "exec",
+            Command::Break => "break",
+            Command::Drop => "drop",
+            Command::UpdateRef => "update-ref",
+            Command::Noop => "noop",
+            Command::Comment => "",
+        }
+    }
+
+    /// 完整名称或单字母缩写
+    fn parse(word: &str) -> Option<Command> {
+        let command = match word {
+            "pick" | "p" => Command::Pick,
+            "reword" | "r" => Command::Reword,
+            "edit" | "e" => Command::Edit,
+            "squash" | "s" => Command::Squash,
+            "fixup" | "f" => Command::Fixup,
+            "exec" | "x" => Command::Exec,
+            "break" | "b" => Command::Break,
+            "drop" | "d" => Command::Drop,
+            "update-ref" | "u" => Command::UpdateRef,
+            "noop" => Command::Noop,
+            _ => return None,
+        };
+        Some(command)
+    }
+
+    /// 后面跟着一个提交的指令
+    pub(crate) fn takes_commit(self) -> bool {
+        matches!(
+            self,
+            Command::Pick
+                | Command::Reword
+                | Command::Edit
+                | Command::Squash
+                | Command::Fixup
+                | Command::Drop
+        )
+    }
+
+    /// 并入前一个提交的指令
+    pub(crate) fn is_fixup(self) -> bool {
+        matches!(self, Command::Squash | Command::Fixup)
+    }
+}
+
+/// `fixup -C` 改用这个提交的说明，`fixup -c` 同时打开编辑器
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FixupMessage {
+    Use,
+    Edit,
+}
+
+/// 待办列表中的一行
+#[derive(Debug, Clone)]
+pub(crate) struct TodoItem {
+    pub(crate) command: Command,
+    pub(crate) fixup_message: Option<FixupMessage>,
+    pub(crate) commit: Option<[u8; 20]>,
+    /// 提交后面的说明、exec 的命令、update-ref 的引用名，注释是整行内容
+    pub(crate) arg: String,
+}
+
+impl TodoItem {
+    pub(crate) fn pick(id: [u8; 20], subject: String) -> TodoItem {
+        TodoItem {
+            command: Command::Pick,
+            fixup_message: None,
+            commit: Some(id),
+            arg: subject,
+        }
+    }
+
+    pub(crate) fn new(command: Command, arg: String) -> TodoItem {
+        TodoItem {
+            command,
+            fixup_message: None,
+            commit: None,
+            arg,
+        }
+    }
+
+    /// 写回文件的格式，abbrev 时提交写成缩写（给用户编辑时）
+    pub(crate) fn format(&self, abbrev: bool) -> String {
+        if self.command == Command::Comment {
+            return self.arg.clone();
+        }
+        let mut line = self.command.name().to_string();
+        match self.fixup_message {
+            Some(FixupMessage::Use) => line.push_str(" -C"),
+            Some(FixupMessage::Edit) => line.push_str(" -c"),
+            None => {}
+        }
+        if let Some(id) = &self.commit {
+            let hex = hex::encode(id);
+            line.push(' ');
+            line.push_str(&if abbrev {
+                find_unique_abbrev(&hex, 7)
+            } else {
+                hex
+            });
+        }
+        if !self.arg.is_empty() {
+            line.push(' ');
+            line.push_str(&self.arg);
+        }
+        line
+    }
+}
+
+/// 把待办列表写成文件内容
+pub(crate) fn format_todo(todo: &[TodoItem], abbrev: bool) -> String {
+    todo.iter()
+        .map(|item| format!("{}\n", item.format(abbrev)))
+        .collect()
+}
+
+/// 把待办列表写到 path
+pub(crate) fn write_todo(path: &str, todo: &[TodoItem], abbrev: bool) -> anyhow::Result<()> {
+    std::fs::write(path, format_todo(todo, abbrev)).with_context(|| format!("write {path}"))
+}
+
+/// 解析待办列表，提交可以是任何能解析为提交的名称
+pub(crate) async fn parse_todo(content: &str) -> anyhow::Result<Vec<TodoItem>> {
+    let mut todo = Vec::new();
+    for (n, line) in (1..).zip(content.lines()) {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            todo.push(TodoItem::new(Command::Comment, line.to_string()));
+            continue;
+        }
+        let invalid = || format!("invalid line {n}: {line}");
+        let (word, rest) = trimmed.split_once([' ', '\t']).unwrap_or((trimmed, ""));
+        let command = Command::parse(word).with_context(invalid)?;
+        let mut rest = rest.trim_start();
+        let mut item = TodoItem::new(command, String::new());
+        match command {
+            Command::Break | Command::Noop => {
+                anyhow::ensure!(rest.is_empty(), invalid());
+            }
+            Command::Exec => {
+                anyhow::ensure!(
+                    !rest.is_empty(),
+                    "missing arguments for exec\n{}",
+                    invalid()
+                );
+                item.arg = rest.to_string();
+            }
+            Command::UpdateRef => {
+                anyhow::ensure!(
+                    rest.starts_with("refs/"),
+                    "update-ref requires a fully qualified refname e.g. refs/heads/topic\n{}",
+                    invalid()
+                );
+                item.arg = rest.to_string();
+            }
+            _ => {
+                if command == Command::Fixup {
+                    for (flag, message) in [("-C", FixupMessage::Use), ("-c", FixupMessage::Edit)] {
+                        if let Some(after) = rest.strip_prefix(flag) {
+                            if after.is_empty() || after.starts_with([' ', '\t']) {
+                                item.fixup_message = Some(message);
+                                rest = after.trim_start();
+                            }
+                        }
+                    }
+                }
+                let (rev, subject) = rest.split_once([' ', '\t']).unwrap_or((rest, ""));
+                anyhow::ensure!(
+                    !rev.is_empty(),
+                    "missing arguments for {word}\n{}",
+                    invalid()
+                );
+                let id = match resolve(rev).await {
+                    Ok(id) => peel(id, Some(Kind::Commit)).await.ok(),
+                    Err(_) => None,
+                };
+                item.commit =
+                    Some(id.with_context(|| format!("could not parse '{rev}'\n{}", invalid()))?);
+                item.arg = subject.trim_start().to_string();
+            }
+        }
+        todo.push(item);
+    }
+    Ok(todo)
+}
+
+/// 有实际指令（不是注释）的行数
+pub(crate) fn count_commands(todo: &[TodoItem]) -> usize {
+    todo.iter()
+        .filter(|item| item.command != Command::Comment)
+        .count()
+}
+
+/// 编辑待办列表时附在后面的说明。开始变基时 heading 是范围和指令数，
+/// 如 `1a2b3c4..5d6e7f8 onto 1a2b3c4` 和 3；`--edit-todo` 时没有 heading
+pub(crate) fn help(heading: Option<(&str, usize)>) -> String {
+    let mut help = match heading {
+        Some((range, count)) => {
+            let commands = if count == 1 { "command" } else { "commands" };
+            format!("\n# Rebase {range} ({count} {commands})\n")
+        }
+        None => String::new(),
+    };
+    help.push_str(
+        "#\n\
+         # Commands:\n\
+         # p, pick <commit> = use commit\n\
+         # r, reword <commit> = use commit, but edit the commit message\n\
+         # e, edit <commit> = use commit, but stop for amending\n\
+         # s, squash <commit> = use commit, but meld into previous commit\n\
+         # f, fixup [-C | -c] <commit> = like \"squash\" but keep only the previous\n\
+         #                    commit's log message, unless -C is used, in which case\n\
+         #                    keep only this commit's message; -c is same as -C but\n\
+         #                    opens the editor\n\
+         # x, exec <command> = run command (the rest of the line) using shell\n\
+         # b, break = stop here (continue rebase later with 'git rebase --continue')\n\
+         # d, drop <commit> = remove commit\n\
+         # u, update-ref <ref> = track a placeholder for the <ref> to be updated\n\
+         #                       to this position in the new commits. The <ref> is\n\
+         #                       updated at the end of the rebase\n\
+         #\n\
+         # These lines can be re-ordered; they are executed from top to bottom.\n\
+         #\n\
+         # If you remove a line here THAT COMMIT WILL BE LOST.\n\
+         #\n",
+    );
+    if heading.is_some() {
+        help.push_str("# However, if you remove everything, the rebase will be aborted.\n#\n");
+    } else {
+        help.push_str(
+            "# You are editing the todo file of an ongoing interactive rebase.\n\
+             # To continue rebase after editing, run:\n\
+             #     git rebase --continue\n\
+             #\n",
+        );
+    }
+    help
+}
+
+/// `--autosquash`：说明以 `fixup! `、`squash! ` 或 `amend! ` 开头的提交移到目标提交
+/// （以及已经排在它后面的修正）之后，并改用相应的指令。目标依次按完整标题、
+/// 提交名称和标题前缀在前面的提交中查找
+pub(crate) async fn rearrange_squash(todo: &mut Vec<TodoItem>) -> anyhow::Result<()> {
+    // 每个位置的修正按出现顺序排在它后面
+    let mut fixups: HashMap<usize, Vec<usize>> = HashMap::new();
+    let mut moved = vec![false; todo.len()];
+    let mut subjects: HashMap<String, usize> = HashMap::new();
+    for i in 0..todo.len() {
+        if !todo[i].command.takes_commit() || todo[i].command == Command::Drop {
+            continue;
+        }
+        let subject = todo[i].arg.clone();
+        let mut target = None;
+        if let Some((command, message, rest)) = strip_fixup_prefix(&subject) {
+            target = match subjects.get(rest) {
+                Some(&found) => Some(found),
+                None => find_target(todo, i, rest).await,
+            };
+            if let Some(found) = target {
+                // 目标本身是被移动的修正时，排到它最终的目标后面
+                let root = root_of(&fixups, found);
+                fixups.entry(root).or_default().push(i);
+                moved[i] = true;
+                let item = &mut todo[i];
+                item.command = command;
+                item.fixup_message = message;
+            }
+        }
+        if target.is_none() {
+            subjects.entry(subject).or_insert(i);
+        }
+    }
+    if fixups.is_empty() {
+        return Ok(());
+    }
+    let mut order = Vec::with_capacity(todo.len());
+    for (i, &moved) in moved.iter().enumerate() {
+        if moved {
+            continue;
+        }
+        order.push(i);
+        if let Some(list) = fixups.get(&i) {
+            order.extend(list);
+        }
+    }
+    let mut items: Vec<Option<TodoItem>> = todo.drain(..).map(Some).collect();
+    todo.extend(order.into_iter().filter_map(|i| items[i].take()));
+    Ok(())
+}
+
+/// 去掉标题开头的 `fixup! `、`squash! `、`amend! `（可以重复），返回对应的指令和剩下的部分
+fn strip_fixup_prefix(subject: &str) -> Option<(Command, Option<FixupMessage>, &str)> {
+    let (command, message, mut rest) = if let Some(rest) = subject.strip_prefix("fixup! ") {
+        (Command::Fixup, None, rest)
+    } else if let Some(rest) = subject.strip_prefix("amend! ") {
+        (Command::Fixup, Some(FixupMessage::Use), rest)
+    } else if let Some(rest) = subject.strip_prefix("squash! ") {
+        (Command::Squash, None, rest)
+    } else {
+        return None;
+    };
+    while let Some(next) = ["fixup! ", "squash! ", "amend! "]
+        .iter()
+        .find_map(|prefix| rest.strip_prefix(prefix))
+    {
+        rest = next;
+    }
+    Some((command, message, rest))
+}
+
+/// 在 before 之前找 `fixup! <rest>` 的目标：rest 是其中某个提交的名称，或是某个标题的前缀
+async fn find_target(todo: &[TodoItem], before: usize, rest: &str) -> Option<usize> {
+    if !rest.contains(' ') {
+        if let Ok(id) = resolve(rest).await {
+            if let Some(found) = todo[..before]
+                .iter()
+                .position(|item| item.commit == Some(id))
+            {
+                return Some(found);
+            }
+        }
+    }
+    todo[..before]
+        .iter()
+        .position(|item| item.command.takes_commit() && item.arg.starts_with(rest))
+}
+
+/// 修正链最前面的提交
+fn root_of(fixups: &HashMap<usize, Vec<usize>>, index: usize) -> usize {
+    fixups
+        .iter()
+        .find(|(_, list)| list.contains(&index))
+        .map_or(index, |(&root, _)| root)
+}
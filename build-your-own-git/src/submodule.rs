@@ -0,0 +1,54 @@
+//! 子模块：树中模式为 160000 的 gitlink 记录子仓库中的一个提交，
+//! `.gitmodules` 记录每个子模块的名称、路径和 URL
+
+use std::path::{Path, PathBuf};
+
+use crate::{config::Config, refs};
+
+/// `.gitmodules` 中的一个子模块
+#[derive(Debug, Clone)]
+pub(crate) struct Submodule {
+    /// `[submodule "name"]` 中的名称，也是 `.git/config` 中的子节名
+    pub(crate) name: String,
+    pub(crate) path: String,
+    pub(crate) url: Option<String>,
+}
+
+/// 读取工作区根目录的 `.gitmodules`，没有 path 的子模块跳过
+pub(crate) fn read_gitmodules() -> anyhow::Result<Vec<Submodule>> {
+    let config = Config::read_file(".gitmodules")?;
+    Ok(config
+        .subsections("submodule")
+        .into_iter()
+        .filter_map(|name| {
+            let path = config.get(&format!("submodule.{name}.path"))?;
+            let url = config.get(&format!("submodule.{name}.url"));
+            Some(Submodule {
+                name,
+                path: path.trim_end_matches('/').to_string(),
+                url,
+            })
+        })
+        .collect())
+}
+
+/// 子模块的 git 目录：工作区中的 `.git` 目录，或 `.git` 文件中 `gitdir:` 指向的目录
+pub(crate) fn git_dir(path: impl AsRef<Path>) -> Option<PathBuf> {
+    let path = path.as_ref();
+    let dot_git = path.join(".git");
+    if dot_git.is_dir() {
+        return Some(dot_git);
+    }
+    let content = std::fs::read_to_string(&dot_git).ok()?;
+    let target = content.trim_end().strip_prefix("gitdir: ")?;
+    let target = path.join(target);
+    target.is_dir().then_some(target)
+}
+
+/// 子模块检出的提交，path 不是仓库或还没有提交时返回 None
+pub(crate) fn head(path: impl AsRef<Path>) -> anyhow::Result<Option<[u8; 20]>> {
+    match git_dir(path) {
+        Some(git_dir) => refs::resolve_ref_in(&git_dir, "HEAD"),
+        None => Ok(None),
+    }
+}
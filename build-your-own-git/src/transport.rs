@@ -0,0 +1,295 @@
+//! 传输：按 URL 决定怎样和远程仓库通信。
+//!
+//! 本地路径直接读另一个仓库的目录，是 bundle 文件时读它的头和 pack；`file://` URL 在本机启动 `git-upload-pack`，
+//! `ssh://[user@]host[:port]/path` 和 `[user@]host:path` 经由 ssh 在远端启动它，
+//! `http://` URL 经由智能 HTTP 与服务端的它通信，之后都用协议 v2。
+//! 推送到 `http://` URL 时与服务端的 git-receive-pack 通信，见 [`send_pack`]
+
+pub(crate) mod credential;
+pub(crate) mod http;
+pub(crate) mod protocol;
+pub(crate) mod send_pack;
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::{
+    bundle::Bundle,
+    commands::clone::{peel_in, source_git_dir},
+    config::Config,
+    refs, remote,
+};
+
+/// 远程的一个引用
+#[derive(Debug, Clone)]
+pub(crate) struct RemoteRef {
+    pub(crate) name: String,
+    /// 未诞生的 HEAD（空仓库）为全零
+    pub(crate) id: [u8; 20],
+    /// 符号引用指向的引用，如 HEAD 指向的分支
+    pub(crate) symref: Option<String>,
+    /// 附注标签最终指向的对象
+    pub(crate) peeled: Option<[u8; 20]>,
+}
+
+/// 取回对象时的选项
+#[derive(Debug, Default)]
+pub(crate) struct FetchArgs {
+    /// `--depth`：只取最近的 n 个提交
+    pub(crate) depth: Option<usize>,
+    /// `--filter`：部分克隆的过滤条件，如 `blob:none`
+    pub(crate) filter: Option<String>,
+    /// 顺带取回指向取回的对象的附注标签
+    pub(crate) include_tag: bool,
+    /// 不发送本地已有的提交，用于部分克隆补取缺少的对象：这些对象从已有的提交可以到达
+    pub(crate) no_haves: bool,
+    pub(crate) quiet: bool,
+}
+
+pub(crate) enum Transport {
+    /// 本地仓库的 git 目录
+    Local(PathBuf),
+    /// bundle 文件，取回时解开其中的整个 pack
+    Bundle(Bundle),
+    Remote(protocol::Connection),
+}
+
+impl Transport {
+    /// 连接 url 处的仓库。local 为 false 时本地路径也像 `file://` 一样经由 git-upload-pack
+    pub(crate) fn connect(url: &str, local: bool) -> anyhow::Result<Transport> {
+        match parse_url(url)? {
+            Url::Path(path) if Bundle::is_bundle(Path::new(&path)) => {
+                Ok(Transport::Bundle(Bundle::read(Path::new(&path))?))
+            }
+            Url::Path(path) if local => Ok(Transport::Local(source_git_dir(&path)?)),
+            Url::Path(path) | Url::File(path) => {
+                let mut command = Command::new("git-upload-pack");
+                command.arg(path);
+                let channel = protocol::Channel::spawn(command)?;
+                Ok(Transport::Remote(protocol::Connection::open(channel)?))
+            }
+            Url::Ssh { host, port, path } => {
+                let command = ssh_command(&host, port.as_deref(), &path)?;
+                let channel = protocol::Channel::spawn(command)?;
+                Ok(Transport::Remote(protocol::Connection::open(channel)?))
+            }
+            Url::Http => {
+                let channel = protocol::Channel::http(url, "git-upload-pack")?;
+                Ok(Transport::Remote(protocol::Connection::open(channel)?))
+            }
+        }
+    }
+
+    /// 列出远程的引用，prefixes 非空时只列出以其中之一开头的（HEAD 要明确列出）
+    pub(crate) async fn list_refs(&mut self, prefixes: &[&str]) -> anyhow::Result<Vec<RemoteRef>> {
+        let wanted =
+            |name: &str| prefixes.is_empty() || prefixes.iter().any(|p| name.starts_with(p));
+        let git_dir = match self {
+            Transport::Local(git_dir) => git_dir.clone(),
+            // bundle 中的 HEAD 只有哈希，不知道指向哪个分支。与 git 一样按在文件中相反的顺序列出
+            Transport::Bundle(bundle) => {
+                return Ok(bundle
+                    .refs
+                    .iter()
+                    .rev()
+                    .filter(|(name, _)| wanted(name))
+                    .map(|(name, id)| RemoteRef {
+                        name: name.clone(),
+                        id: *id,
+                        symref: None,
+                        peeled: None,
+                    })
+                    .collect());
+            }
+            Transport::Remote(connection) => return connection.ls_refs(prefixes),
+        };
+        let mut list = Vec::new();
+        if wanted("HEAD") {
+            if let Some(head) = refs::read_ref_in(&git_dir, "HEAD")? {
+                list.push(RemoteRef {
+                    name: "HEAD".to_string(),
+                    id: refs::resolve_ref_in(&git_dir, "HEAD")?.unwrap_or_default(),
+                    symref: head.strip_prefix("ref: ").map(str::to_string),
+                    peeled: None,
+                });
+            }
+        }
+        let objects = git_dir.join("objects");
+        for (name, id) in refs::list_refs_in(&git_dir, "refs/")? {
+            if !wanted(&name) {
+                continue;
+            }
+            let symref = refs::read_ref_in(&git_dir, &name)?
+                .and_then(|content| content.strip_prefix("ref: ").map(str::to_string));
+            let peeled = match name.starts_with("refs/tags/") {
+                true => Some(peel_in(&objects, id).await?).filter(|peeled| *peeled != id),
+                false => None,
+            };
+            list.push(RemoteRef {
+                name,
+                id,
+                symref,
+                peeled,
+            });
+        }
+        Ok(list)
+    }
+
+    /// 把 wants 以及它们引用的对象取回到 git_dir 这个仓库中，浅克隆时同时更新它的 shallow 文件
+    pub(crate) async fn fetch(
+        &mut self,
+        git_dir: &Path,
+        wants: &[[u8; 20]],
+        args: &FetchArgs,
+    ) -> anyhow::Result<()> {
+        match self {
+            Transport::Local(source) => {
+                let objects = git_dir.join("objects");
+                remote::transfer_objects(&source.join("objects"), &objects, wants).await?;
+                Ok(())
+            }
+            Transport::Bundle(bundle) => bundle.unbundle(&git_dir.join("objects")).await,
+            Transport::Remote(connection) => connection.fetch(git_dir, wants, args).await,
+        }
+    }
+}
+
+/// url 是否是本地路径（而不是 `file://` 或其他 URL）
+pub(crate) fn is_local(url: &str) -> bool {
+    matches!(parse_url(url), Ok(Url::Path(_)))
+}
+
+/// url 是否经由 HTTP 访问
+pub(crate) fn is_http(url: &str) -> bool {
+    matches!(parse_url(url), Ok(Url::Http))
+}
+
+enum Url {
+    Path(String),
+    File(String),
+    Ssh {
+        /// `[user@]host`
+        host: String,
+        port: Option<String>,
+        path: String,
+    },
+    /// `http://` 或 `https://`，由 [`http::Client`] 解析
+    Http,
+}
+
+/// 按 git 的规则分辨 URL：有 `scheme://` 的按协议，否则第一个 `/` 之前有 `:` 的是 scp 式的 ssh 地址
+fn parse_url(url: &str) -> anyhow::Result<Url> {
+    if let Some((scheme, rest)) = url.split_once("://") {
+        return match scheme {
+            "file" => Ok(Url::File(rest.to_string())),
+            "http" | "https" => Ok(Url::Http),
+            "ssh" | "git+ssh" | "ssh+git" => {
+                let (authority, path) = rest.find('/').map_or((rest, "/"), |i| rest.split_at(i));
+                // `/~user/path` 是相对于用户主目录的
+                let path = path
+                    .strip_prefix('/')
+                    .filter(|p| p.starts_with('~'))
+                    .unwrap_or(path);
+                // IPv6 地址写在方括号中，如 `[::1]:22`
+                let (host, port) = match authority.rsplit_once(':') {
+                    Some((host, port)) if !port.contains(']') => (host, Some(port.to_string())),
+                    _ => (authority, None),
+                };
+                Ok(Url::Ssh {
+                    host: host.replace(['[', ']'], ""),
+                    port,
+                    path: path.to_string(),
+                })
+            }
+            scheme => anyhow::bail!("unable to find remote helper for '{scheme}'"),
+        };
+    }
+    match url.find(':') {
+        Some(colon) if !url[..colon].contains('/') => Ok(Url::Ssh {
+            host: url[..colon].replace(['[', ']'], ""),
+            port: None,
+            path: url[colon + 1..].to_string(),
+        }),
+        _ => Ok(Url::Path(url.to_string())),
+    }
+}
+
+/// 在 host 上启动 git-upload-pack 的 ssh 命令。与 git 一样依次使用 GIT_SSH_COMMAND、
+/// core.sshCommand、GIT_SSH 和 ssh，按 GIT_SSH_VARIANT、ssh.variant 或程序名决定怎样传端口
+/// 以及能否传递 GIT_PROTOCOL
+fn ssh_command(host: &str, port: Option<&str>, path: &str) -> anyhow::Result<Command> {
+    let config = Config::read().unwrap_or_default();
+    let (mut command, program) = match (
+        std::env::var("GIT_SSH_COMMAND"),
+        std::env::var("GIT_SSH"),
+        config.get("core.sshCommand"),
+    ) {
+        (Ok(ssh), _, _) | (_, _, Some(ssh)) => {
+            // 命令行由 shell 解释，参数通过 "$@" 传入
+            let mut command = Command::new("sh");
+            command.args(["-c", &format!("{ssh} \"$@\""), &ssh]);
+            let program = ssh.split_whitespace().next().unwrap_or("ssh").to_string();
+            (command, program)
+        }
+        (_, Ok(ssh), _) => (Command::new(&ssh), ssh),
+        _ => (Command::new("ssh"), "ssh".to_string()),
+    };
+    let variant = std::env::var("GIT_SSH_VARIANT")
+        .ok()
+        .or_else(|| config.get("ssh.variant"))
+        .unwrap_or_else(|| {
+            let name = Path::new(&program)
+                .file_stem()
+                .map_or(String::new(), |name| name.to_string_lossy().to_lowercase());
+            match name.as_str() {
+                "ssh" | "plink" | "putty" | "tortoiseplink" => name,
+                _ => "simple".to_string(),
+            }
+        });
+    match variant.as_str() {
+        "ssh" | "auto" => {
+            command.args(["-o", "SendEnv=GIT_PROTOCOL"]);
+            if let Some(port) = port {
+                command.args(["-p", port]);
+            }
+        }
+        "plink" | "putty" | "tortoiseplink" => {
+            if variant == "tortoiseplink" {
+                command.arg("-batch");
+            }
+            if let Some(port) = port {
+                command.args(["-P", port]);
+            }
+        }
+        "simple" => {
+            anyhow::ensure!(
+                port.is_none(),
+                "ssh variant 'simple' does not support setting port"
+            );
+        }
+        variant => anyhow::bail!("unknown value for ssh.variant: '{variant}'"),
+    }
+    command.arg(host);
+    command.arg(format!("git-upload-pack {}", sq_quote(path)));
+    Ok(command)
+}
+
+/// 用单引号括起来交给远端的 shell，其中的 `'` 和 `!` 单独转义
+fn sq_quote(text: &str) -> String {
+    let mut quoted = String::from("'");
+    for c in text.chars() {
+        match c {
+            '\'' | '!' => {
+                quoted.push('\'');
+                quoted.push('\\');
+                quoted.push(c);
+                quoted.push('\'');
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('\'');
+    quoted
+}
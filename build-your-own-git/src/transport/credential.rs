@@ -0,0 +1,193 @@
+//! 凭据助手协议：与 git 一样，把 `protocol=`、`host=`、`username=` 这样的 `key=value` 行交给
+//! `credential.helper` 配置的每个助手（`<助手> get`），由它们补上用户名和密码；都没有给出时
+//! 经由 GIT_ASKPASS 或终端询问。认证成功后让助手保存（`store`），失败时删除（`erase`）
+
+use std::{
+    io::{BufRead, BufReader, IsTerminal, Write},
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use anyhow::Context;
+
+use crate::config::Config;
+
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Credential {
+    /// 如 `http`
+    pub(crate) protocol: String,
+    /// `host[:port]`
+    pub(crate) host: String,
+    pub(crate) username: Option<String>,
+    pub(crate) password: Option<String>,
+}
+
+impl Credential {
+    /// 补全用户名和密码：先问助手，再问用户
+    pub(crate) fn fill(&mut self) -> anyhow::Result<()> {
+        for helper in helpers() {
+            if self.username.is_some() && self.password.is_some() {
+                break;
+            }
+            let Ok(output) = self.run_helper(&helper, "get") else {
+                continue;
+            };
+            let mut quit = false;
+            for line in output.lines() {
+                match line.split_once('=') {
+                    Some(("username", value)) => self.username = Some(value.to_string()),
+                    Some(("password", value)) => self.password = Some(value.to_string()),
+                    Some(("quit", value)) => quit = matches!(value, "1" | "true"),
+                    _ => {}
+                }
+            }
+            anyhow::ensure!(!quit, "credential helper '{helper}' told us to quit");
+        }
+        let url = format!("{}://{}", self.protocol, self.host);
+        if self.username.is_none() {
+            let prompt = format!("Username for '{url}': ");
+            self.username = Some(prompt_for("Username", &url, &prompt, true)?);
+        }
+        if self.password.is_none() {
+            let user = self.username.as_deref().unwrap_or_default();
+            let prompt = format!("Password for '{}://{user}@{}': ", self.protocol, self.host);
+            self.password = Some(prompt_for("Password", &url, &prompt, false)?);
+        }
+        Ok(())
+    }
+
+    /// 认证成功：让助手记住这组凭据
+    pub(crate) fn approve(&self) {
+        if self.username.is_none() || self.password.is_none() {
+            return;
+        }
+        for helper in helpers() {
+            let _ = self.run_helper(&helper, "store");
+        }
+    }
+
+    /// 认证失败：让助手忘掉这组凭据，之后重新询问
+    pub(crate) fn reject(&mut self) {
+        for helper in helpers() {
+            let _ = self.run_helper(&helper, "erase");
+        }
+        self.username = None;
+        self.password = None;
+    }
+
+    /// 交给助手的输入
+    fn description(&self) -> String {
+        let mut text = format!("protocol={}\nhost={}\n", self.protocol, self.host);
+        if let Some(username) = &self.username {
+            text.push_str(&format!("username={username}\n"));
+        }
+        if let Some(password) = &self.password {
+            text.push_str(&format!("password={password}\n"));
+        }
+        text
+    }
+
+    /// 运行助手的 action，返回它的标准输出
+    fn run_helper(&self, helper: &str, action: &str) -> anyhow::Result<String> {
+        // `!` 开头的是 shell 命令，绝对路径直接运行，其他的是 `git credential-<名称>`
+        let script = match helper.strip_prefix('!') {
+            Some(script) => format!("{script} {action}"),
+            None if Path::new(helper.split(' ').next().unwrap_or_default()).is_absolute() => {
+                format!("{helper} {action}")
+            }
+            None => format!("git credential-{helper} {action}"),
+        };
+        let mut child = Command::new("sh")
+            .args(["-c", &script])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("cannot run credential helper '{helper}'"))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // 助手可能不读输入就退出
+            let _ = stdin.write_all(self.description().as_bytes());
+        }
+        let output = child.wait_with_output()?;
+        anyhow::ensure!(
+            output.status.success(),
+            "credential helper '{helper}' failed"
+        );
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+/// 配置的助手，空值清除之前配置的
+fn helpers() -> Vec<String> {
+    let config = Config::read().unwrap_or_default();
+    let mut helpers = Vec::new();
+    for helper in config.get_all("credential.helper") {
+        match helper.is_empty() {
+            true => helpers.clear(),
+            false => helpers.push(helper),
+        }
+    }
+    helpers
+}
+
+/// 向用户询问 what（Username 或 Password）：依次使用 GIT_ASKPASS、core.askPass、SSH_ASKPASS，
+/// 都没有时在终端上询问，GIT_TERMINAL_PROMPT 为假时不询问
+fn prompt_for(what: &str, url: &str, prompt: &str, echo: bool) -> anyhow::Result<String> {
+    let config = Config::read().unwrap_or_default();
+    let askpass = std::env::var("GIT_ASKPASS")
+        .ok()
+        .or_else(|| config.get("core.askPass"))
+        .or_else(|| std::env::var("SSH_ASKPASS").ok())
+        .filter(|askpass| !askpass.is_empty());
+    if let Some(askpass) = askpass {
+        let output = Command::new("sh")
+            .args(["-c", &format!("{askpass} \"$@\""), &askpass, prompt])
+            .stdin(Stdio::null())
+            .stderr(Stdio::inherit())
+            .output();
+        match output {
+            Ok(output) if output.status.success() => {
+                let text = String::from_utf8_lossy(&output.stdout);
+                return Ok(text.lines().next().unwrap_or_default().to_string());
+            }
+            _ => eprintln!("error: unable to read askpass response from '{askpass}'"),
+        }
+    }
+
+    let disabled = matches!(
+        std::env::var("GIT_TERMINAL_PROMPT").as_deref(),
+        Ok("0" | "false" | "no" | "off")
+    );
+    anyhow::ensure!(
+        !disabled,
+        "could not read {what} for '{url}': terminal prompts disabled"
+    );
+    let tty = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .with_context(|| format!("could not read {what} for '{url}'"))?;
+    anyhow::ensure!(
+        tty.is_terminal(),
+        "could not read {what} for '{url}': not a terminal"
+    );
+    let mut writer = &tty;
+    write!(writer, "{prompt}")?;
+    writer.flush()?;
+    // 输入密码时关闭回显
+    let stty = |mode: &str| {
+        if let Ok(input) = tty.try_clone() {
+            let _ = Command::new("stty").arg(mode).stdin(input).status();
+        }
+    };
+    if !echo {
+        stty("-echo");
+    }
+    let mut line = String::new();
+    let read = BufReader::new(&tty).read_line(&mut line);
+    if !echo {
+        stty("echo");
+        let _ = writeln!(writer);
+    }
+    read.with_context(|| format!("could not read {what} for '{url}'"))?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
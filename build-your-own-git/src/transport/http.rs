@@ -0,0 +1,355 @@
+//! 智能 HTTP：先 `GET <url>/info/refs?service=<服务>` 取得服务端的通告，之后每次交换都是一个
+//! `POST <url>/<服务>`，请求体和回复体都是 pkt-line。服务端不保存会话，每个请求都要带上全部状态。
+//!
+//! 这里只实现了够用的 HTTP/1.1：每个请求一个连接，回复体可以用 Content-Length 或 chunked 分隔、
+//! 用 gzip 压缩；服务端要求认证（401）时按凭据助手协议取得用户名和密码，用 Basic 认证
+
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpStream,
+};
+
+use anyhow::Context;
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+
+use crate::transport::credential::Credential;
+
+/// 比这更大的 upload-pack 请求体与 git 一样用 gzip 压缩
+const GZIP_THRESHOLD: usize = 1024;
+
+/// 到一个仓库 URL 的 HTTP 客户端
+pub(crate) struct Client {
+    /// 仓库的 URL，去掉了其中的用户名和密码以及末尾的 `/`，用于提示信息
+    url: String,
+    /// `host[:port]`，Host 头的值
+    authority: String,
+    host: String,
+    port: u16,
+    /// 仓库的路径，不带末尾的 `/`
+    path: String,
+    credential: Credential,
+    /// 认证通过后每个请求都带上的 Authorization 头
+    authorization: Option<String>,
+}
+
+struct Response {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+impl Client {
+    /// 解析 `http://[user[:password]@]host[:port]/path`
+    pub(crate) fn new(url: &str) -> anyhow::Result<Client> {
+        let url = url.trim_end_matches('/');
+        let Some(rest) = url.strip_prefix("http://") else {
+            let scheme = url.split("://").next().unwrap_or_default();
+            anyhow::bail!("unable to access '{url}/': {scheme} is not supported");
+        };
+        let (authority, path) = rest.find('/').map_or((rest, ""), |i| rest.split_at(i));
+        let mut credential = Credential {
+            protocol: "http".to_string(),
+            ..Credential::default()
+        };
+        let authority = match authority.rsplit_once('@') {
+            Some((userinfo, authority)) => {
+                let (user, password) = match userinfo.split_once(':') {
+                    Some((user, password)) => (user, Some(password)),
+                    None => (userinfo, None),
+                };
+                credential.username = Some(percent_decode(user));
+                credential.password = password.map(percent_decode);
+                authority
+            }
+            None => authority,
+        };
+        credential.host = authority.to_string();
+        // IPv6 地址写在方括号中，如 `[::1]:8080`
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => (
+                host,
+                port.parse()
+                    .with_context(|| format!("invalid port number in '{url}'"))?,
+            ),
+            _ => (authority, 80),
+        };
+        Ok(Client {
+            url: format!("http://{authority}{path}"),
+            authority: authority.to_string(),
+            host: host
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .to_string(),
+            port,
+            path: path.to_string(),
+            credential,
+            authorization: None,
+        })
+    }
+
+    /// 取得 service（`git-upload-pack` 或 `git-receive-pack`）的通告，去掉协议 v0 开头的
+    /// `# service=<服务>` 和随后的 flush
+    pub(crate) fn discover(&mut self, service: &str) -> anyhow::Result<Vec<u8>> {
+        let mut headers = vec![("Pragma".to_string(), "no-cache".to_string())];
+        if service == "git-upload-pack" {
+            headers.push(("Git-Protocol".to_string(), "version=2".to_string()));
+        }
+        let target = format!("{}/info/refs?service={service}", self.path);
+        let response = self.send("GET", &target, headers, Vec::new())?;
+        // 不是智能 HTTP 的服务端只会返回 info/refs 文件本身
+        let expected = format!("application/x-{service}-advertisement");
+        anyhow::ensure!(
+            response.header("Content-Type") == Some(expected.as_str()),
+            "'{}/' does not support the smart HTTP protocol",
+            self.url
+        );
+        let body = response.body;
+        let header = format!("# service={service}\n");
+        if body.get(4..4 + header.len()) == Some(header.as_bytes()) {
+            let rest = &body[4 + header.len()..];
+            anyhow::ensure!(
+                rest.starts_with(b"0000"),
+                "invalid server response; expected service, got flush packet"
+            );
+            return Ok(rest[4..].to_vec());
+        }
+        Ok(body)
+    }
+
+    /// 把请求 body 发给 service，返回回复体
+    pub(crate) fn rpc(&mut self, service: &str, body: Vec<u8>) -> anyhow::Result<Vec<u8>> {
+        let mut headers = vec![
+            (
+                "Content-Type".to_string(),
+                format!("application/x-{service}-request"),
+            ),
+            (
+                "Accept".to_string(),
+                format!("application/x-{service}-result"),
+            ),
+        ];
+        let mut body = body;
+        if service == "git-upload-pack" {
+            headers.push(("Git-Protocol".to_string(), "version=2".to_string()));
+            if body.len() > GZIP_THRESHOLD {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(&body)?;
+                body = encoder.finish()?;
+                headers.push(("Content-Encoding".to_string(), "gzip".to_string()));
+            }
+        }
+        let target = format!("{}/{service}", self.path);
+        let response = self.send("POST", &target, headers, body)?;
+        let expected = format!("application/x-{service}-result");
+        anyhow::ensure!(
+            response.header("Content-Type") == Some(expected.as_str()),
+            "invalid content-type: '{}'",
+            response.header("Content-Type").unwrap_or_default()
+        );
+        Ok(response.body)
+    }
+
+    /// 发送请求并处理认证：第一次 401 时取得凭据重试，带着凭据仍然 401 时认证失败
+    fn send(
+        &mut self,
+        method: &str,
+        target: &str,
+        headers: Vec<(String, String)>,
+        body: Vec<u8>,
+    ) -> anyhow::Result<Response> {
+        let mut tried = false;
+        loop {
+            let mut all = headers.clone();
+            if let Some(authorization) = &self.authorization {
+                all.push(("Authorization".to_string(), authorization.clone()));
+            }
+            let response = self.request(method, target, &all, &body)?;
+            match response.status {
+                401 if tried => {
+                    self.credential.reject();
+                    self.authorization = None;
+                    anyhow::bail!("Authentication failed for '{}/'", self.url);
+                }
+                401 => {
+                    self.credential.fill()?;
+                    let pair = format!(
+                        "{}:{}",
+                        self.credential.username.as_deref().unwrap_or_default(),
+                        self.credential.password.as_deref().unwrap_or_default()
+                    );
+                    self.authorization = Some(format!("Basic {}", base64(pair.as_bytes())));
+                    tried = true;
+                }
+                200 => {
+                    if tried {
+                        self.credential.approve();
+                    }
+                    return Ok(response);
+                }
+                404 => anyhow::bail!("repository '{}/' not found", self.url),
+                status => anyhow::bail!(
+                    "unable to access '{}/': The requested URL returned error: {status}",
+                    self.url
+                ),
+            }
+        }
+    }
+
+    /// 在一个新连接上发送一个请求，读完整个回复
+    fn request(
+        &self,
+        method: &str,
+        target: &str,
+        headers: &[(String, String)],
+        body: &[u8],
+    ) -> anyhow::Result<Response> {
+        let mut stream =
+            TcpStream::connect((self.host.as_str(), self.port)).with_context(|| {
+                format!(
+                    "unable to access '{}/': Failed to connect to {} port {}",
+                    self.url, self.host, self.port
+                )
+            })?;
+        let mut request = format!(
+            "{method} {target} HTTP/1.1\r\n\
+             Host: {}\r\n\
+             User-Agent: {}\r\n\
+             Accept-Encoding: gzip\r\n\
+             Connection: close\r\n",
+            self.authority,
+            concat!("git/own-git-", env!("CARGO_PKG_VERSION")),
+        );
+        for (name, value) in headers {
+            request.push_str(&format!("{name}: {value}\r\n"));
+        }
+        if method == "POST" {
+            request.push_str(&format!("Content-Length: {}\r\n", body.len()));
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes())?;
+        stream.write_all(body)?;
+        stream.flush()?;
+
+        let mut reader = BufReader::new(stream);
+        let status_line = read_line(&mut reader)?;
+        let status = status_line
+            .split(' ')
+            .nth(1)
+            .and_then(|code| code.parse().ok())
+            .with_context(|| format!("bad HTTP status line '{status_line}'"))?;
+        let mut headers = Vec::new();
+        loop {
+            let line = read_line(&mut reader)?;
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.push((name.trim().to_string(), value.trim().to_string()));
+            }
+        }
+        let mut response = Response {
+            status,
+            headers,
+            body: Vec::new(),
+        };
+        let chunked = response
+            .header("Transfer-Encoding")
+            .is_some_and(|value| value.eq_ignore_ascii_case("chunked"));
+        if chunked {
+            loop {
+                let line = read_line(&mut reader)?;
+                let size = line.split(';').next().unwrap_or_default().trim();
+                let size = usize::from_str_radix(size, 16)
+                    .with_context(|| format!("bad chunk size '{line}'"))?;
+                if size == 0 {
+                    // 忽略 trailer
+                    while !read_line(&mut reader)?.is_empty() {}
+                    break;
+                }
+                let start = response.body.len();
+                response.body.resize(start + size, 0);
+                reader.read_exact(&mut response.body[start..])?;
+                read_line(&mut reader)?;
+            }
+        } else if let Some(length) = response.header("Content-Length") {
+            let length: usize = length.parse().context("bad Content-Length")?;
+            response.body.resize(length, 0);
+            reader.read_exact(&mut response.body)?;
+        } else {
+            reader.read_to_end(&mut response.body)?;
+        }
+        if response
+            .header("Content-Encoding")
+            .is_some_and(|value| value.eq_ignore_ascii_case("gzip"))
+        {
+            let mut body = Vec::new();
+            GzDecoder::new(response.body.as_slice())
+                .read_to_end(&mut body)
+                .context("bad gzip response body")?;
+            response.body = body;
+        }
+        Ok(response)
+    }
+}
+
+/// 读取以 CRLF 结尾的一行，不含行尾
+fn read_line(reader: &mut impl BufRead) -> anyhow::Result<String> {
+    let mut line = String::new();
+    let n = reader.read_line(&mut line)?;
+    anyhow::ensure!(n > 0, "the remote end hung up unexpectedly");
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// URL 中的 `%XX` 转义
+pub(crate) fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = match (bytes[i], bytes.get(i + 1..i + 3)) {
+            (b'%', Some(hex)) => std::str::from_utf8(hex)
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+        match escaped {
+            Some(byte) => {
+                out.push(byte);
+                i += 3;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Basic 认证用的 base64 编码
+fn base64(data: &[u8]) -> String {
+    const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, byte)| n | (*byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => out.push(TABLE[(n >> (18 - 6 * i) & 0x3f) as usize] as char),
+                false => out.push('='),
+            }
+        }
+    }
+    out
+}
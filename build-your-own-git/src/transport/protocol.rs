@@ -0,0 +1,547 @@
+//! 协议 v2 客户端：与 git-upload-pack 交换 pkt-line。
+//!
+//! 服务端先列出支持的能力，之后客户端每次发送一条命令（`command=<名称>`、能力参数、delim、
+//! 命令参数、flush），服务端回复到 flush 为止。这里用到 `ls-refs` 和 `fetch` 两条命令。
+//! 经由标准输入输出时整个会话是一个连接；经由 HTTP 时每条命令是一个请求，见 [`Channel`]
+
+use std::{
+    collections::{BinaryHeap, HashSet},
+    io::{BufRead, BufReader, Cursor, IsTerminal, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+};
+
+use anyhow::Context;
+
+use crate::{
+    commands::clone::tag_target,
+    hook,
+    objects::{
+        Kind,
+        commit::{Commit, parse_hex},
+        object_exists_in, read_object_in,
+    },
+    pack,
+    pkt_line::{self, Packet},
+    refs,
+    transport::{FetchArgs, RemoteRef, http},
+};
+
+/// 每轮协商最多发送的 have 个数
+const HAVES_PER_ROUND: usize = 32;
+/// 与 git 一样，连续这么多 have 都没有共同提交时放弃协商
+const MAX_IN_VAIN: usize = 256;
+
+/// 与服务端交换 pkt-line 的通道
+pub(crate) enum Channel {
+    /// 本机或经由 ssh 启动的服务端进程，一直连着
+    Process {
+        child: Child,
+        stdin: Option<ChildStdin>,
+        stdout: BufReader<ChildStdout>,
+    },
+    /// 智能 HTTP：每次发送是一个 POST，回复整个读进来
+    Http {
+        client: Box<http::Client>,
+        service: &'static str,
+        response: Cursor<Vec<u8>>,
+    },
+}
+
+impl Channel {
+    /// 启动 command，请求协议 v2（服务端不认识时忽略）
+    pub(crate) fn spawn(mut command: Command) -> anyhow::Result<Channel> {
+        let mut child = command
+            .env("GIT_PROTOCOL", "version=2")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .context("unable to fork")?;
+        let stdin = child.stdin.take();
+        let stdout = BufReader::new(child.stdout.take().context("no stdout")?);
+        Ok(Channel::Process {
+            child,
+            stdin,
+            stdout,
+        })
+    }
+
+    /// 连接 url 处的 service，之后可以读到服务端的通告
+    pub(crate) fn http(url: &str, service: &'static str) -> anyhow::Result<Channel> {
+        let mut client = Box::new(http::Client::new(url)?);
+        let advertisement = client.discover(service)?;
+        Ok(Channel::Http {
+            client,
+            service,
+            response: Cursor::new(advertisement),
+        })
+    }
+
+    /// 读取服务端发来的数据
+    pub(crate) fn reader(&mut self) -> &mut dyn BufRead {
+        match self {
+            Channel::Process { stdout, .. } => stdout,
+            Channel::Http { response, .. } => response,
+        }
+    }
+
+    /// 发送 request，经由 HTTP 时同时取回回复
+    pub(crate) fn send(&mut self, request: Vec<u8>) -> anyhow::Result<()> {
+        match self {
+            Channel::Process { stdin, .. } => {
+                let stdin = stdin.as_mut().context("connection is closed")?;
+                stdin
+                    .write_all(&request)
+                    .and_then(|()| stdin.flush())
+                    .context("the remote end hung up unexpectedly")
+            }
+            Channel::Http {
+                client,
+                service,
+                response,
+            } => {
+                *response = Cursor::new(client.rpc(service, request)?);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Drop for Channel {
+    /// 关闭标准输入，服务端读到 EOF 后退出
+    fn drop(&mut self) {
+        if let Channel::Process { child, stdin, .. } = self {
+            drop(stdin.take());
+            let _ = child.wait();
+        }
+    }
+}
+
+/// 读完了服务端能力列表的协议 v2 连接
+pub(crate) struct Connection {
+    channel: Channel,
+    /// 能力列表中的每一行，如 `agent=git/2.39.5`、`fetch=shallow wait-for-done`
+    capabilities: Vec<String>,
+}
+
+/// fetch 命令一次回复的结果
+enum Response {
+    /// 服务端还没有准备好发送 pack，继续协商；带着这一轮确认的共同提交
+    Continue(Vec<[u8; 20]>),
+    /// 已经收到并解开 pack
+    Done,
+}
+
+impl Connection {
+    /// 在 channel 上请求协议 v2 并读取能力列表
+    pub(crate) fn open(channel: Channel) -> anyhow::Result<Connection> {
+        let mut connection = Connection {
+            channel,
+            capabilities: Vec::new(),
+        };
+        let first = match pkt_line::read(connection.channel.reader()) {
+            Ok(Some(packet)) => packet,
+            // 对方还没有说话就退出了，它自己已经在标准错误上说明了原因
+            _ => anyhow::bail!(
+                "Could not read from remote repository.\n\n\
+                 Please make sure you have the correct access rights\n\
+                 and the repository exists."
+            ),
+        };
+        match first.text() {
+            Some("version 2") => {}
+            Some(line) if line.starts_with("ERR ") => {
+                anyhow::bail!("remote error: {}", &line[4..])
+            }
+            _ => anyhow::bail!("server does not support protocol version 2"),
+        }
+        while let Some(line) = connection.read_line()? {
+            connection.capabilities.push(line);
+        }
+        Ok(connection)
+    }
+
+    /// 能力 name 的值，没有值的能力为空字符串，不支持时为 None
+    fn capability(&self, name: &str) -> Option<&str> {
+        self.capabilities
+            .iter()
+            .find_map(|line| match line.split_once('=') {
+                Some((key, value)) if key == name => Some(value),
+                None if line == name => Some(""),
+                _ => None,
+            })
+    }
+
+    /// 命令 command 是否支持 feature，如 fetch 的 `shallow`、`filter`
+    fn supports(&self, command: &str, feature: &str) -> bool {
+        self.capability(command)
+            .is_some_and(|features| features.split(' ').any(|f| f == feature))
+    }
+
+    /// 读取一行文本，到 flush 或 delim 时返回 None。ERR 行转为错误
+    fn read_line(&mut self) -> anyhow::Result<Option<String>> {
+        match pkt_line::expect(self.channel.reader())? {
+            Packet::Flush | Packet::Delim => Ok(None),
+            packet => {
+                let line = packet
+                    .text()
+                    .context("protocol error: expected text line")?;
+                if let Some(message) = line.strip_prefix("ERR ") {
+                    anyhow::bail!("remote error: {message}");
+                }
+                Ok(Some(line.to_string()))
+            }
+        }
+    }
+
+    /// 发送一条命令，args 是 delim 之后的命令参数
+    fn send(&mut self, command: &str, args: &[String]) -> anyhow::Result<()> {
+        let mut request = Vec::new();
+        pkt_line::write_line(&mut request, &format!("command={command}"))?;
+        let agent = concat!("agent=own-git/", env!("CARGO_PKG_VERSION"));
+        pkt_line::write_line(&mut request, agent)?;
+        if self.capability("object-format").is_some() {
+            pkt_line::write_line(&mut request, "object-format=sha1")?;
+        }
+        pkt_line::delim(&mut request)?;
+        for arg in args {
+            pkt_line::write_line(&mut request, arg)?;
+        }
+        pkt_line::flush(&mut request)?;
+        self.channel.send(request)
+    }
+
+    /// ls-refs：列出以 prefixes 之一开头的引用（为空时列出全部），带上符号引用和剥离后的标签
+    pub(crate) fn ls_refs(&mut self, prefixes: &[&str]) -> anyhow::Result<Vec<RemoteRef>> {
+        let mut args = vec!["peel".to_string(), "symrefs".to_string()];
+        if self.supports("ls-refs", "unborn") {
+            args.push("unborn".to_string());
+        }
+        args.extend(prefixes.iter().map(|prefix| format!("ref-prefix {prefix}")));
+        self.send("ls-refs", &args)?;
+        let mut list = Vec::new();
+        while let Some(line) = self.read_line()? {
+            let mut fields = line.split(' ');
+            let (Some(id), Some(name)) = (fields.next(), fields.next()) else {
+                anyhow::bail!("invalid ls-refs response: {line}");
+            };
+            let mut entry = RemoteRef {
+                name: name.to_string(),
+                id: match id {
+                    "unborn" => [0; 20],
+                    id => parse_hex(id)?,
+                },
+                symref: None,
+                peeled: None,
+            };
+            for attribute in fields {
+                if let Some(target) = attribute.strip_prefix("symref-target:") {
+                    entry.symref = Some(target.to_string());
+                } else if let Some(peeled) = attribute.strip_prefix("peeled:") {
+                    entry.peeled = Some(parse_hex(peeled)?);
+                }
+            }
+            list.push(entry);
+        }
+        Ok(list)
+    }
+
+    /// fetch：用 git_dir 中已有的提交与服务端协商，取回 wants 需要的对象并写入 git_dir
+    pub(crate) async fn fetch(
+        &mut self,
+        git_dir: &Path,
+        wants: &[[u8; 20]],
+        args: &FetchArgs,
+    ) -> anyhow::Result<()> {
+        let objects = git_dir.join("objects");
+        let shallow_path = git_dir.join("shallow");
+        let shallow: Vec<String> = std::fs::read_to_string(&shallow_path)
+            .unwrap_or_default()
+            .lines()
+            .map(str::to_string)
+            .collect();
+        // 不加深时已经有的对象不用再取
+        let mut wanted = HashSet::new();
+        let wants: Vec<[u8; 20]> = wants
+            .iter()
+            .filter(|id| wanted.insert(**id))
+            .filter(|id| args.depth.is_some() || !object_exists_in(&objects, id))
+            .copied()
+            .collect();
+        if wants.is_empty() {
+            return Ok(());
+        }
+
+        let mut common_args: Vec<String> = wants
+            .iter()
+            .map(|id| format!("want {}", hex::encode(id)))
+            .collect();
+        common_args.push("thin-pack".to_string());
+        common_args.push("ofs-delta".to_string());
+        if args.quiet || !std::io::stderr().is_terminal() {
+            common_args.push("no-progress".to_string());
+        }
+        if args.include_tag {
+            common_args.push("include-tag".to_string());
+        }
+        if !shallow.is_empty() || args.depth.is_some() {
+            anyhow::ensure!(
+                self.supports("fetch", "shallow"),
+                "Server does not support shallow clients"
+            );
+        }
+        common_args.extend(shallow.iter().map(|id| format!("shallow {id}")));
+        if let Some(depth) = args.depth {
+            common_args.push(format!("deepen {depth}"));
+        }
+        if let Some(filter) = &args.filter {
+            match self.supports("fetch", "filter") {
+                true => common_args.push(format!("filter {filter}")),
+                false => eprintln!("warning: filtering not recognized by server, ignoring"),
+            }
+        }
+
+        let mut negotiator = Negotiator::new(&objects, &shallow);
+        if !args.no_haves {
+            negotiator.push_refs(git_dir).await?;
+        }
+        let mut common: Vec<[u8; 20]> = Vec::new();
+        let mut in_vain = 0;
+        let mut updates = Vec::new();
+        loop {
+            let mut request = common_args.clone();
+            request.extend(common.iter().map(|id| format!("have {}", hex::encode(id))));
+            let mut sent = 0;
+            while sent < HAVES_PER_ROUND {
+                let Some(id) = negotiator.next().await? else {
+                    break;
+                };
+                request.push(format!("have {}", hex::encode(id)));
+                sent += 1;
+            }
+            // 没有更多可以说的，或者已经说了太多都没有共同提交，就让服务端直接发送
+            let done = sent == 0 || in_vain >= MAX_IN_VAIN;
+            if done {
+                request.push("done".to_string());
+            }
+            self.send("fetch", &request)?;
+            match self.read_response(&objects, &mut updates).await? {
+                Response::Done => break,
+                Response::Continue(_) if done => {
+                    anyhow::bail!("protocol error: expected packfile after done")
+                }
+                Response::Continue(acks) => {
+                    in_vain = match acks.is_empty() {
+                        true => in_vain + sent,
+                        false => 0,
+                    };
+                    negotiator.mark_common(&acks).await?;
+                    common.extend(acks);
+                }
+            }
+        }
+        update_shallow(&shallow_path, shallow, &updates)
+    }
+
+    /// 读取 fetch 的回复：确认（acknowledgments）、浅克隆边界（shallow-info）等各节，最后是 pack
+    async fn read_response(
+        &mut self,
+        objects: &Path,
+        shallow_updates: &mut Vec<String>,
+    ) -> anyhow::Result<Response> {
+        let mut acks = Vec::new();
+        loop {
+            let header = self
+                .read_line()?
+                .context("protocol error: expected section")?;
+            match header.as_str() {
+                "acknowledgments" => {
+                    let mut ready = false;
+                    loop {
+                        match pkt_line::expect(self.channel.reader())? {
+                            // flush 表示这一轮协商结束，服务端还没有准备好
+                            Packet::Flush if !ready => return Ok(Response::Continue(acks)),
+                            Packet::Delim if ready => break,
+                            packet => match packet.text() {
+                                Some("NAK") => {}
+                                Some("ready") => ready = true,
+                                Some(line) if line.starts_with("ACK ") => {
+                                    acks.push(parse_hex(&line[4..])?)
+                                }
+                                _ => anyhow::bail!("protocol error: bad acknowledgment"),
+                            },
+                        }
+                    }
+                }
+                "shallow-info" => {
+                    while let Some(line) = self.read_line()? {
+                        shallow_updates.push(line);
+                    }
+                }
+                // 我们没有请求具体的引用，也不接受 packfile URI，跳过
+                "wanted-refs" | "packfile-uris" => while self.read_line()?.is_some() {},
+                "packfile" => {
+                    let pack = read_sideband(self.channel.reader())?;
+                    pack::unpack(&pack, objects).await?;
+                    return Ok(Response::Done);
+                }
+                section => anyhow::bail!("protocol error: unknown section '{section}'"),
+            }
+        }
+    }
+}
+
+/// 读取 side-band-64k 复用的数据直到 flush：1 号通道是数据，2 号是进度，3 号是错误
+pub(crate) fn read_sideband(reader: &mut dyn BufRead) -> anyhow::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    loop {
+        let packet = match pkt_line::expect(reader)? {
+            Packet::Flush => return Ok(data),
+            Packet::Data(packet) if !packet.is_empty() => packet,
+            _ => anyhow::bail!("protocol error: bad band"),
+        };
+        match packet[0] {
+            1 => data.extend_from_slice(&packet[1..]),
+            2 => progress(&packet[1..]),
+            3 => anyhow::bail!(
+                "remote error: {}",
+                String::from_utf8_lossy(&packet[1..]).trim_end()
+            ),
+            band => anyhow::bail!("protocol error: bad band #{band}"),
+        }
+    }
+}
+
+/// 输出服务端的进度，每行加上 `remote: `。进度行以 `\r` 结尾，原样保留
+fn progress(data: &[u8]) {
+    let text = String::from_utf8_lossy(data);
+    let mut stderr = std::io::stderr().lock();
+    for part in text.split_inclusive(['\r', '\n']) {
+        let line = part.trim_end_matches(['\r', '\n']);
+        let end = &part[line.len()..];
+        let _ = match line.is_empty() {
+            true => write!(stderr, "remote: {end}"),
+            false => write!(stderr, "remote: {line}{}{end}", hook::clear_to_eol()),
+        };
+    }
+}
+
+/// 按服务端的 shallow-info 更新 shallow 文件：`shallow` 加入新的边界提交，`unshallow` 去掉已经补全的
+fn update_shallow(path: &Path, mut shallow: Vec<String>, updates: &[String]) -> anyhow::Result<()> {
+    if updates.is_empty() {
+        return Ok(());
+    }
+    for update in updates {
+        match update.split_once(' ') {
+            Some(("shallow", id)) => shallow.push(id.to_string()),
+            Some(("unshallow", id)) => shallow.retain(|other| other != id),
+            _ => anyhow::bail!("protocol error: bad shallow-info line '{update}'"),
+        }
+    }
+    shallow.sort();
+    shallow.dedup();
+    if shallow.is_empty() {
+        if path.exists() {
+            std::fs::remove_file(path).context("remove shallow")?;
+        }
+        return Ok(());
+    }
+    let lines: String = shallow.iter().map(|id| format!("{id}\n")).collect();
+    std::fs::write(path, lines).context("write shallow")
+}
+
+/// 协商时按提交时间从新到旧列出本地的提交，作为 have 发给服务端
+struct Negotiator {
+    objects: PathBuf,
+    queue: BinaryHeap<(i64, [u8; 20])>,
+    seen: HashSet<[u8; 20]>,
+    /// 服务端有的提交，它们的祖先也不用再说
+    common: HashSet<[u8; 20]>,
+    shallow: HashSet<[u8; 20]>,
+}
+
+impl Negotiator {
+    fn new(objects: &Path, shallow: &[String]) -> Negotiator {
+        Negotiator {
+            objects: objects.to_path_buf(),
+            queue: BinaryHeap::new(),
+            seen: HashSet::new(),
+            common: HashSet::new(),
+            shallow: shallow.iter().filter_map(|id| parse_hex(id).ok()).collect(),
+        }
+    }
+
+    /// 从 git_dir 中所有引用以及 HEAD 指向的提交开始
+    async fn push_refs(&mut self, git_dir: &Path) -> anyhow::Result<()> {
+        let mut tips: Vec<[u8; 20]> = refs::list_refs_in(git_dir, "refs/")?
+            .into_iter()
+            .map(|(_, id)| id)
+            .collect();
+        tips.extend(refs::resolve_ref_in(git_dir, "HEAD")?);
+        for tip in tips {
+            self.push(tip).await;
+        }
+        Ok(())
+    }
+
+    /// 把 id 剥离到提交后放入队列。不是提交或读不到的（如部分克隆缺少的）跳过
+    async fn push(&mut self, mut id: [u8; 20]) {
+        loop {
+            if !self.seen.insert(id) {
+                return;
+            }
+            let Ok((kind, data)) = read_object_in(&self.objects, &hex::encode(id)).await else {
+                return;
+            };
+            match kind {
+                Kind::Tag => match tag_target(&data) {
+                    Ok(target) => id = target,
+                    Err(_) => return,
+                },
+                Kind::Commit => {
+                    if let Ok(commit) = Commit::parse(&data) {
+                        self.queue.push((commit.committer.time, id));
+                    }
+                    return;
+                }
+                _ => return,
+            }
+        }
+    }
+
+    async fn next(&mut self) -> anyhow::Result<Option<[u8; 20]>> {
+        while let Some((_, id)) = self.queue.pop() {
+            let parents = self.parents(&id).await?;
+            // 共同提交的祖先也是共同的，跳过并继续往下标记
+            let common = self.common.contains(&id);
+            for parent in parents {
+                if common {
+                    self.common.insert(parent);
+                }
+                self.push(parent).await;
+            }
+            if !common {
+                return Ok(Some(id));
+            }
+        }
+        Ok(None)
+    }
+
+    /// 服务端确认有 ids，它们的父提交不用再说
+    async fn mark_common(&mut self, ids: &[[u8; 20]]) -> anyhow::Result<()> {
+        for id in ids {
+            let parents = self.parents(id).await?;
+            self.common.extend(parents);
+        }
+        Ok(())
+    }
+
+    /// 浅克隆的边界提交看作没有父提交
+    async fn parents(&self, id: &[u8; 20]) -> anyhow::Result<Vec<[u8; 20]>> {
+        if self.shallow.contains(id) {
+            return Ok(Vec::new());
+        }
+        let (_, data) = read_object_in(&self.objects, &hex::encode(id)).await?;
+        Ok(Commit::parse(&data)?.parents)
+    }
+}
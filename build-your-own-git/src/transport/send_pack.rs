@@ -0,0 +1,186 @@
+//! 推送客户端：与 git-receive-pack 用协议 v0 通信（receive-pack 没有 v2）。
+//!
+//! 服务端先列出所有引用，第一行在 NUL 之后带着能力列表；客户端发送 `旧值 新值 引用名` 命令、
+//! flush 和 pack，服务端用 report-status 报告解包和每个引用的结果，
+//! 钩子的输出经由 side-band 的 2 号通道送回，显示为 `remote: ` 开头的行
+
+use std::{
+    io::{BufRead, Cursor},
+    path::Path,
+};
+
+use anyhow::Context;
+
+use crate::{
+    objects::{Kind, commit::parse_hex},
+    pack,
+    pkt_line::{self, Packet},
+    receive::Command,
+    revision::{
+        self, RevWalk,
+        list_objects::{MissingAction, list_objects},
+    },
+    transport::protocol::{Channel, read_sideband},
+};
+
+/// 读完了引用通告的 git-receive-pack 连接
+pub(crate) struct Connection {
+    channel: Channel,
+    /// 服务端的引用
+    pub(crate) refs: Vec<(String, [u8; 20])>,
+    capabilities: Vec<String>,
+}
+
+impl Connection {
+    /// 连接 url 处的 git-receive-pack，读取它通告的引用和能力
+    pub(crate) fn open(url: &str) -> anyhow::Result<Connection> {
+        let mut connection = Connection {
+            channel: Channel::http(url, "git-receive-pack")?,
+            refs: Vec::new(),
+            capabilities: Vec::new(),
+        };
+        loop {
+            let line = match pkt_line::expect(connection.channel.reader())? {
+                Packet::Flush => break,
+                packet => packet
+                    .text()
+                    .context("protocol error: expected ref advertisement")?
+                    .to_string(),
+            };
+            if let Some(message) = line.strip_prefix("ERR ") {
+                anyhow::bail!("remote error: {message}");
+            }
+            let line = match line.split_once('\0') {
+                Some((line, capabilities)) => {
+                    connection.capabilities = capabilities.split(' ').map(str::to_string).collect();
+                    line.to_string()
+                }
+                None => line,
+            };
+            let Some((id, name)) = line.split_once(' ') else {
+                anyhow::bail!("protocol error: unexpected '{line}'");
+            };
+            // 空仓库用 `capabilities^{}` 这个假引用带出能力列表，`.have` 是备用对象库中的提交
+            if name != "capabilities^{}" && name != ".have" {
+                connection.refs.push((name.to_string(), parse_hex(id)?));
+            }
+        }
+        Ok(connection)
+    }
+
+    fn supports(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|c| c == capability)
+    }
+
+    /// 发送 commands 和它们需要的对象，返回每个命令被拒绝的原因，成功的为 None
+    pub(crate) async fn push(
+        &mut self,
+        commands: &[Command],
+        atomic: bool,
+    ) -> anyhow::Result<Vec<Option<String>>> {
+        anyhow::ensure!(
+            !atomic || self.supports("atomic"),
+            "the receiving end does not support --atomic push"
+        );
+        anyhow::ensure!(
+            commands.iter().all(|command| command.new != [0; 20]) || self.supports("delete-refs"),
+            "the receiving end does not support deleting refs"
+        );
+        let sideband = self.supports("side-band-64k");
+        let mut capabilities = String::from(" report-status");
+        if sideband {
+            capabilities.push_str(" side-band-64k");
+        }
+        if atomic {
+            capabilities.push_str(" atomic");
+        }
+        capabilities.push_str(concat!(" agent=own-git/", env!("CARGO_PKG_VERSION")));
+        if self
+            .capabilities
+            .iter()
+            .any(|c| c.starts_with("object-format="))
+        {
+            capabilities.push_str(" object-format=sha1");
+        }
+
+        let mut request = Vec::new();
+        for (i, command) in commands.iter().enumerate() {
+            let mut line = format!(
+                "{} {} {}",
+                hex::encode(command.old),
+                hex::encode(command.new),
+                command.name
+            );
+            if i == 0 {
+                line.push('\0');
+                line.push_str(&capabilities);
+            }
+            pkt_line::write_line(&mut request, &line)?;
+        }
+        pkt_line::flush(&mut request)?;
+        // 只删除引用时不发送 pack
+        if commands.iter().any(|command| command.new != [0; 20]) {
+            request.extend(self.pack_objects(commands).await?);
+        }
+        self.channel.send(request)?;
+
+        match sideband {
+            true => {
+                let report = read_sideband(self.channel.reader())?;
+                read_report(&mut Cursor::new(report), commands)
+            }
+            false => read_report(self.channel.reader(), commands),
+        }
+    }
+
+    /// commands 的新值可以到达、服务端的引用不能到达的对象写成的 pack
+    async fn pack_objects(&self, commands: &[Command]) -> anyhow::Result<Vec<u8>> {
+        let mut walk = RevWalk::new();
+        for command in commands.iter().filter(|command| command.new != [0; 20]) {
+            walk.push_arg(&hex::encode(command.new)).await?;
+        }
+        // 服务端的引用本地也有的，它们的历史不用发送
+        for (_, id) in &self.refs {
+            if let Ok(commit) = revision::peel(*id, Some(Kind::Commit)).await {
+                walk.hide(commit);
+            }
+        }
+        let commits = walk.walk().await?;
+        let list = list_objects(&walk, &commits, None, false, MissingAction::Error).await?;
+        let mut ids = commits;
+        ids.extend(list.objects.iter().map(|(id, _)| *id));
+        pack::write(Path::new(".git/objects"), &ids).await
+    }
+}
+
+/// 读取 report-status：`unpack ok` 之后每个引用一行 `ok <引用>` 或 `ng <引用> <原因>`
+fn read_report(
+    reader: &mut dyn BufRead,
+    commands: &[Command],
+) -> anyhow::Result<Vec<Option<String>>> {
+    let mut results = vec![Some("remote failed to report status".to_string()); commands.len()];
+    let unpack = pkt_line::expect(reader)?;
+    match unpack.text().and_then(|line| line.strip_prefix("unpack ")) {
+        Some("ok") => {}
+        Some(reason) => eprintln!("error: remote unpack failed: {reason}"),
+        None => anyhow::bail!("protocol error: expected 'unpack', received something else"),
+    }
+    loop {
+        let packet = pkt_line::expect(reader)?;
+        let Some(line) = packet.text() else {
+            break;
+        };
+        let (name, result) = if let Some(name) = line.strip_prefix("ok ") {
+            (name, None)
+        } else if let Some(rest) = line.strip_prefix("ng ") {
+            let (name, reason) = rest.split_once(' ').unwrap_or((rest, "failed"));
+            (name, Some(reason.to_string()))
+        } else {
+            anyhow::bail!("protocol error: invalid ref status from remote: {line}");
+        };
+        if let Some(i) = commands.iter().position(|command| command.name == name) {
+            results[i] = result;
+        }
+    }
+    Ok(results)
+}
@@ -0,0 +1,308 @@
+//! 工作区的检出：把对象写成文件，并在两组文件之间切换工作区和暂存区
+
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::Metadata,
+    path::Path,
+};
+
+use anyhow::Context;
+
+use crate::{
+    config::Config,
+    diff::{Side, index_files, read_worktree_file},
+    index::{Index, IndexEntry},
+    objects::{Kind, Mode, Object, read_object},
+    submodule,
+};
+
+/// 把对象写到工作区，返回写入后的文件状态。与 git 一样，子模块只建出空目录，
+/// 内容由 `submodule update` 检出
+pub(crate) async fn checkout_file(path: &str, side: Side) -> anyhow::Result<Metadata> {
+    if side.mode == Mode::Gitlink {
+        if let Ok(metadata) = std::fs::symlink_metadata(path) {
+            if metadata.is_dir() {
+                return Ok(metadata);
+            }
+            std::fs::remove_file(path).with_context(|| format!("unlink {path}"))?;
+        }
+        std::fs::create_dir_all(path).with_context(|| format!("create {path}"))?;
+        return std::fs::symlink_metadata(path).with_context(|| format!("stat {path}"));
+    }
+    let hex = hex::encode(side.hash);
+    let (kind, content) = read_object(&hex).await?;
+    anyhow::ensure!(kind == Kind::Blob, "object {hex} is a {kind}, not a blob");
+    write_file(path, side.mode, &content)
+}
+
+/// 写入工作区文件：挡路的空目录会先被删除，上级路径上的文件要由调用方先处理
+pub(crate) fn write_file(path: &str, mode: Mode, content: &[u8]) -> anyhow::Result<Metadata> {
+    let target = Path::new(path);
+    match std::fs::symlink_metadata(target) {
+        Ok(metadata) if metadata.is_dir() => std::fs::remove_dir(target)
+            .with_context(|| format!("directory in the way of {path}"))?,
+        Ok(_) => std::fs::remove_file(target).with_context(|| format!("unlink {path}"))?,
+        Err(_) => {}
+    }
+    if let Some((parent, _)) = path.rsplit_once('/') {
+        std::fs::create_dir_all(parent).with_context(|| format!("create {parent}"))?;
+    }
+
+    // core.symlinks=false 时符号链接写成内容为目标路径的普通文件
+    if mode == Mode::SymbolicLink && has_symlinks()? {
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+            std::os::unix::fs::symlink(std::ffi::OsStr::from_bytes(content), target)
+                .with_context(|| format!("symlink {path}"))?;
+        }
+    } else {
+        std::fs::write(target, content).with_context(|| format!("write {path}"))?;
+        #[cfg(unix)]
+        {
+            let mut permissions = std::fs::metadata(target)?.permissions();
+            let bits = permissions.mode();
+            permissions.set_mode(if mode == Mode::Executable {
+                bits | (bits & 0o444) >> 2
+            } else {
+                bits & !0o111
+            });
+            std::fs::set_permissions(target, permissions)
+                .with_context(|| format!("chmod {path}"))?;
+        }
+    }
+    std::fs::symlink_metadata(target).with_context(|| format!("stat {path}"))
+}
+
+/// 删除工作区文件，并删除因此变空的上级目录。子模块的目录只在为空时删除
+pub(crate) fn remove_file(path: &str) -> anyhow::Result<()> {
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => {
+            if std::fs::remove_dir(path).is_err() {
+                eprintln!("warning: unable to rmdir '{path}': Directory not empty");
+                return Ok(());
+            }
+        }
+        Ok(_) => std::fs::remove_file(path).with_context(|| format!("unlink {path}"))?,
+        Err(_) => {}
+    }
+    let mut dir = path;
+    while let Some((parent, _)) = dir.rsplit_once('/') {
+        if std::fs::remove_dir(parent).is_err() {
+            break;
+        }
+        dir = parent;
+    }
+    Ok(())
+}
+
+/// 文件系统是否支持符号链接，即 `core.symlinks`，默认为 true
+pub(crate) fn has_symlinks() -> anyhow::Result<bool> {
+    Ok(Config::read()?.get_bool("core.symlinks")?.unwrap_or(true))
+}
+
+/// 工作区文件的模式。与 git 一样，`core.symlinks=false` 时暂存区中的符号链接检出成普通文件，
+/// 这样的普通文件仍然看作符号链接。staged 是路径在暂存区中的模式
+pub(crate) fn worktree_mode(metadata: &Metadata, staged: Option<Mode>) -> anyhow::Result<Mode> {
+    let mode = Mode::from_meta(metadata);
+    if staged == Some(Mode::SymbolicLink) && metadata.is_file() && !has_symlinks()? {
+        return Ok(Mode::SymbolicLink);
+    }
+    Ok(mode)
+}
+
+/// 工作区文件当前的模式和哈希，不存在时返回 None。检出的子模块取它的 HEAD，
+/// staged 是路径在暂存区中的模式
+pub(crate) async fn hash_worktree_file(
+    path: &str,
+    staged: Option<Mode>,
+) -> anyhow::Result<Option<Side>> {
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return Ok(None);
+    };
+    if metadata.is_dir() {
+        return Ok(submodule::head(path)?.map(|hash| Side {
+            mode: Mode::Gitlink,
+            hash,
+        }));
+    }
+    let data = read_worktree_file(path)?;
+    let hash = Object {
+        kind: Kind::Blob,
+        expected_size: data.len() as u64,
+        reader: data.as_slice(),
+    }
+    .compute_hash(std::io::sink())
+    .await?;
+    Ok(Some(Side {
+        mode: worktree_mode(&metadata, staged)?,
+        hash,
+    }))
+}
+
+/// 把工作区和暂存区从 old 切换到 new，返回新的暂存区（尚未写入）。
+/// 只改动两边不同的路径，其余路径保留原来的暂存区记录和本地修改。
+/// 不是 force 时，要改动的路径上有本地修改或未跟踪的文件就拒绝切换，
+/// action 是错误提示中的操作名称，如 `merge`；force 时同时丢弃所有冲突记录
+pub(crate) async fn switch(
+    index: &Index,
+    old: &BTreeMap<String, Side>,
+    new: &BTreeMap<String, Side>,
+    action: &str,
+    force: bool,
+) -> anyhow::Result<Index> {
+    let staged = index_files(index);
+    let mut touched: BTreeSet<&String> = old
+        .keys()
+        .chain(new.keys())
+        .filter(|path| old.get(*path) != new.get(*path))
+        .collect();
+    // 挡在新路径上的东西：上级路径上不属于 old 的文件和新文件处的目录，
+    // 目录中有未跟踪的文件时切换会丢失它们
+    let mut blocking = BTreeSet::new();
+    let mut dirs = Vec::new();
+    let mut lost_dirs = Vec::new();
+    for &path in &touched {
+        let Some(side) = new.get(path) else {
+            continue;
+        };
+        for (end, _) in path.match_indices('/') {
+            let parent = &path[..end];
+            if !old.contains_key(parent)
+                && std::fs::symlink_metadata(parent).is_ok_and(|metadata| !metadata.is_dir())
+            {
+                blocking.insert(parent);
+            }
+        }
+        if side.mode != Mode::Gitlink
+            && !matches!(staged.get(path), Some(side) if side.mode == Mode::Gitlink)
+            && std::fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_dir())
+        {
+            dirs.push(path.as_str());
+            if has_untracked(Path::new(path), old)? {
+                lost_dirs.push(path.as_str());
+            }
+        }
+    }
+    if force {
+        touched.extend(
+            index
+                .entries
+                .iter()
+                .filter(|entry| entry.stage > 0)
+                .map(|entry| &entry.path),
+        );
+        // 与 `git reset --hard` 一样直接删掉挡路的未跟踪文件
+        for &path in &blocking {
+            std::fs::remove_file(path).with_context(|| format!("unlink {path}"))?;
+        }
+    } else {
+        anyhow::ensure!(
+            !index.has_conflicts(),
+            "you need to resolve your current index first"
+        );
+        let mut local_changes = Vec::new();
+        let mut untracked = Vec::new();
+        for &path in &touched {
+            let (before, after, in_index) = (old.get(path), new.get(path), staged.get(path));
+            if in_index != before && in_index != after {
+                local_changes.push(path.as_str());
+                continue;
+            }
+            let current = hash_worktree_file(path, in_index.map(|side| side.mode)).await?;
+            match (in_index, current) {
+                (Some(entry), Some(file)) if *entry != file => local_changes.push(path),
+                (None, Some(_)) if after.is_some() && !lost_dirs.contains(&path.as_str()) => {
+                    untracked.push(path.as_str())
+                }
+                _ => {}
+            }
+        }
+        for &path in &blocking {
+            if staged.contains_key(path) {
+                local_changes.push(path);
+            } else {
+                untracked.push(path);
+            }
+        }
+        local_changes.sort_unstable();
+        untracked.sort_unstable();
+        if !local_changes.is_empty() {
+            anyhow::bail!(
+                "Your local changes to the following files would be overwritten by {action}:\n{}\
+                 Please commit your changes or stash them before you {action}.\nAborting",
+                list_paths(&local_changes)
+            );
+        }
+        if !untracked.is_empty() || !lost_dirs.is_empty() {
+            let mut message = String::new();
+            if !lost_dirs.is_empty() {
+                message.push_str(&format!(
+                    "Updating the following directories would lose untracked files in them:\n{}\n",
+                    list_paths(&lost_dirs)
+                ));
+            }
+            if !untracked.is_empty() {
+                message.push_str(&format!(
+                    "The following untracked working tree files would be overwritten by {action}:\n{}\
+                     Please move or remove them before you {action}.\n",
+                    list_paths(&untracked)
+                ));
+            }
+            anyhow::bail!("{message}Aborting");
+        }
+    }
+
+    // 先删除再写入，这样文件和目录可以互相替换。挡路的目录中剩下的只有空目录，
+    // 或者是 force 时可以丢弃的未跟踪文件
+    for &path in &touched {
+        if !new.contains_key(path) {
+            remove_file(path)?;
+        }
+    }
+    for path in dirs {
+        if std::fs::symlink_metadata(path).is_ok() {
+            std::fs::remove_dir_all(path).with_context(|| format!("remove {path}"))?;
+        }
+    }
+    let mut entries: Vec<IndexEntry> = index
+        .entries
+        .iter()
+        .filter(|entry| !touched.contains(&entry.path))
+        .cloned()
+        .collect();
+    for &path in &touched {
+        if let Some(&side) = new.get(path) {
+            let metadata = checkout_file(path, side).await?;
+            entries.push(IndexEntry::new(
+                path,
+                side.mode,
+                side.hash,
+                0,
+                Some(&metadata),
+            ));
+        }
+    }
+    Ok(Index { entries })
+}
+
+/// 目录 dir 中是否有 old 之外的文件，即切换时会丢失的未跟踪文件
+fn has_untracked(dir: &Path, old: &BTreeMap<String, Side>) -> anyhow::Result<bool> {
+    for entry in std::fs::read_dir(dir).with_context(|| format!("read {}", dir.display()))? {
+        let path = entry?.path();
+        if std::fs::symlink_metadata(&path).is_ok_and(|metadata| metadata.is_dir()) {
+            if has_untracked(&path, old)? {
+                return Ok(true);
+            }
+        } else if !old.contains_key(path.to_string_lossy().as_ref()) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn list_paths(paths: &[&str]) -> String {
+    paths.iter().map(|path| format!("\t{path}\n")).collect()
+}